@@ -0,0 +1,28 @@
+// Minimal Node.js host for modules built with `monkey wasm`.
+// Usage: node examples/wasm_host.js program.wasm
+const bytes = require("fs").readFileSync(process.argv[2]);
+
+let memory;
+let line = "";
+const text = (ptr) => {
+  const length = new DataView(memory.buffer).getUint32(ptr, true);
+  return Buffer.from(memory.buffer, ptr + 8, length).toString("utf8");
+};
+
+const imports = {
+  monkey: {
+    print_int: (value) => { line += value.toString(); },
+    print_str: (ptr) => { line += text(ptr); },
+    newline: () => { console.log(line); line = ""; },
+  },
+};
+
+WebAssembly.instantiate(bytes, imports)
+  .then(({ instance }) => {
+    memory = instance.exports.memory;
+    instance.exports.main();
+  })
+  .catch((err) => {
+    console.error(String(err));
+    process.exit(1);
+  });
@@ -4,6 +4,7 @@ set -euo pipefail
 ROOT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")/.." && pwd)"
 BIN_PATH="${1:-${MONKEY_RUST_BIN:-}}"
 ROUNDS="${BENCH_ROUNDS:-3}"
+WARMUP="${BENCH_WARMUP:-1}"
 BENCH_FILTER="${BENCH_FILTER:-}"
 BENCH_FILES=(
   "$ROOT_DIR/bench/b1.monkey"
@@ -13,24 +14,22 @@ BENCH_FILES=(
   "$ROOT_DIR/bench/b5.monkey"
 )
 
-run_once() {
+run_bench() {
   local file="$1"
+  local flags=(--iterations "$ROUNDS" --warmup "$WARMUP")
   if [[ -n "$BIN_PATH" ]]; then
-    "$BIN_PATH" bench "$file"
+    "$BIN_PATH" bench "$file" "${flags[@]}"
   else
-    cargo run --quiet --release -- bench "$file"
+    cargo run --quiet --release -- bench "$file" "${flags[@]}"
   fi
 }
 
-echo "Running Monkey benchmarks (${ROUNDS} rounds each, release profile)"
+echo "Running Monkey benchmarks (${ROUNDS} rounds after ${WARMUP} warmup each, release profile)"
 for file in "${BENCH_FILES[@]}"; do
   if [[ -n "$BENCH_FILTER" && "$(basename "$file")" != *"$BENCH_FILTER"* ]]; then
     continue
   fi
   echo "--- $(basename "$file") ---"
-  for (( round=1; round<=ROUNDS; round++ )); do
-    echo "round $round"
-    run_once "$file"
-  done
+  run_bench "$file"
   echo
 done
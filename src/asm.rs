@@ -0,0 +1,342 @@
+//! Textual bytecode assembly, the inverse of `Chunk::disassemble`.
+//!
+//! The syntax is the disassembler's output, so a disassembly can be edited
+//! and assembled again:
+//!
+//! ```text
+//! ; comments start with a semicolon
+//! 0000 Constant 0 @1:1 (1:1-1:4)
+//! 0003 Closure 1 0
+//! 0007 ReturnValue
+//!
+//! Constants:
+//! 0000 STRING "a\n"
+//! 0001 FUNCTION add arity=2 locals=2
+//!     GetLocal 0
+//!     GetLocal 1
+//!     Add @2:5
+//!     ReturnValue
+//! ```
+//!
+//! Leading offsets and constant indices are optional; when present they
+//! must match where the line actually lands. `@line:col` records a
+//! position and an optional `(start-end)` after it the expression's
+//! extent. Constants are `INTEGER`, `BOOLEAN`, `STRING`, `NULL` and
+//! `FUNCTION`; a function's instructions follow it, indented deeper than
+//! the `FUNCTION` line.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use crate::builtins::builtin_names;
+use crate::bytecode::{lookup_definition, make, Chunk, Opcode, OperandKind};
+use crate::object::{CompiledFunctionObject, Object, Shared};
+use crate::position::{Position, Span};
+
+/// A line that could not be assembled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembleError {
+    /// 1-based line in the assembly text.
+    pub line: usize,
+    pub message: String,
+}
+
+impl AssembleError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for AssembleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+/// Instructions and positions of one function body, or of the top level.
+#[derive(Default)]
+struct Code {
+    instructions: Vec<u8>,
+    positions: Vec<(usize, Span)>,
+    /// Offset of every instruction, for checking jump targets. A jump may
+    /// also land just past the last one.
+    offsets: Vec<usize>,
+    /// `(line, kind, value)` of operands that can only be checked once the
+    /// whole text has been read.
+    operands: Vec<(usize, OperandKind, usize)>,
+}
+
+impl Code {
+    /// Check operands against the finished constant pool and, for function
+    /// bodies, the number of locals.
+    fn check_operands(&self, constants: usize, locals: Option<usize>) -> Result<(), AssembleError> {
+        for &(line, kind, value) in &self.operands {
+            let message = match kind {
+                OperandKind::ConstIndex if value >= constants => {
+                    format!("constant {value} out of range ({constants} constants)")
+                }
+                OperandKind::BuiltinIndex if value >= builtin_names().len() => {
+                    format!("builtin {value} out of range")
+                }
+                OperandKind::LocalSlot if locals.is_some_and(|locals| value >= locals) => {
+                    format!(
+                        "local {value} out of range (locals={})",
+                        locals.unwrap_or(0)
+                    )
+                }
+                OperandKind::JumpTarget
+                    if value != self.instructions.len()
+                        && self.offsets.binary_search(&value).is_err() =>
+                {
+                    format!("jump target {value:04} is not an instruction")
+                }
+                _ => continue,
+            };
+            return Err(AssembleError::new(line, message));
+        }
+        Ok(())
+    }
+}
+
+struct PendingFunction {
+    name: Option<String>,
+    num_params: usize,
+    num_locals: usize,
+    code: Code,
+}
+
+enum Constant {
+    Value(Object),
+    Function(PendingFunction),
+}
+
+/// Assemble `text` into a chunk.
+pub fn assemble(text: &str) -> Result<Chunk, AssembleError> {
+    let mut main = Code::default();
+    let mut constants: Vec<Constant> = Vec::new();
+    let mut in_constants = false;
+    // Indentation of the last constant line; deeper lines are its body.
+    let mut constant_indent = 0;
+
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let content = raw.trim();
+        if content.is_empty() || content.starts_with(';') {
+            continue;
+        }
+        if content == "Constants:" {
+            in_constants = true;
+            continue;
+        }
+        let indent = raw.len() - raw.trim_start().len();
+        if !in_constants {
+            assemble_instruction(&mut main, content, line)?;
+        } else if indent > constant_indent && !constants.is_empty() {
+            match constants.last_mut() {
+                Some(Constant::Function(function)) => {
+                    assemble_instruction(&mut function.code, content, line)?
+                }
+                _ => {
+                    return Err(AssembleError::new(
+                        line,
+                        "indented instruction outside a FUNCTION constant",
+                    ))
+                }
+            }
+        } else {
+            constant_indent = indent;
+            constants.push(parse_constant(content, constants.len(), line)?);
+        }
+    }
+
+    main.check_operands(constants.len(), None)?;
+    for constant in &constants {
+        if let Constant::Function(function) = constant {
+            function
+                .code
+                .check_operands(constants.len(), Some(function.num_locals))?;
+        }
+    }
+
+    let mut chunk = Chunk::new();
+    chunk.instructions = main.instructions;
+    chunk.positions = main.positions;
+    for constant in constants {
+        let object = match constant {
+            Constant::Value(object) => object,
+            Constant::Function(function) => {
+                Object::CompiledFunction(Shared::new(CompiledFunctionObject {
+                    name: function.name,
+                    num_params: function.num_params,
+                    num_locals: function.num_locals,
+                    instructions: function.code.instructions.into(),
+                    positions: function.code.positions.into(),
+                }))
+            }
+        };
+        chunk.add_constant(object.rc());
+    }
+    Ok(chunk)
+}
+
+/// Strip an optional leading offset or index from `content`, checking it
+/// against `expected`.
+fn strip_index(content: &str, expected: usize, line: usize) -> Result<&str, AssembleError> {
+    let Some((first, rest)) = content.split_once(char::is_whitespace) else {
+        return Ok(content);
+    };
+    match first.parse::<usize>() {
+        Ok(index) if index == expected => Ok(rest.trim_start()),
+        Ok(index) => Err(AssembleError::new(
+            line,
+            format!("{index:04} does not match the actual offset or index {expected:04}"),
+        )),
+        Err(_) => Ok(content),
+    }
+}
+
+fn assemble_instruction(code: &mut Code, content: &str, line: usize) -> Result<(), AssembleError> {
+    let offset = code.instructions.len();
+    let content = strip_index(content, offset, line)?;
+    let (instruction, span) = match content.split_once('@') {
+        Some((instruction, span)) => (instruction, Some(parse_span(span.trim(), line)?)),
+        None => (content, None),
+    };
+
+    let mut words = instruction.split_whitespace();
+    let name = words
+        .next()
+        .ok_or_else(|| AssembleError::new(line, "missing opcode"))?;
+    let op = Opcode::all()
+        .iter()
+        .copied()
+        .find(|op| lookup_definition(*op).name == name)
+        .ok_or_else(|| AssembleError::new(line, format!("unknown opcode {name}")))?;
+    let kinds = lookup_definition(op).operand_kinds;
+    // Extra operands parse as plain numbers so `make` reports the count.
+    let operands = words
+        .enumerate()
+        .map(|(index, word)| {
+            let value = match kinds.get(index) {
+                Some(kind) => kind.parse(word),
+                None => word.parse().ok(),
+            };
+            value.ok_or_else(|| AssembleError::new(line, format!("invalid operand {word}")))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let bytes = make(op, &operands).map_err(|err| AssembleError::new(line, err.to_string()))?;
+
+    code.offsets.push(offset);
+    code.operands.extend(
+        kinds
+            .iter()
+            .zip(&operands)
+            .map(|(kind, value)| (line, *kind, *value)),
+    );
+    code.instructions.extend_from_slice(&bytes);
+    if let Some(span) = span {
+        code.positions.push((offset, span));
+    }
+    Ok(())
+}
+
+/// `line:col`, optionally followed by `(line:col-line:col)`.
+fn parse_span(text: &str, line: usize) -> Result<Span, AssembleError> {
+    let (pos, extent) = match text.split_once('(') {
+        Some((pos, extent)) => (pos.trim(), Some(extent)),
+        None => (text, None),
+    };
+    let pos = parse_position(pos, line)?;
+    let Some(extent) = extent else {
+        return Ok(Span::at(pos));
+    };
+    let (start, end) = extent
+        .strip_suffix(')')
+        .and_then(|extent| extent.split_once('-'))
+        .ok_or_else(|| AssembleError::new(line, format!("invalid extent ({extent}")))?;
+    Ok(Span::new(
+        pos,
+        parse_position(start, line)?,
+        parse_position(end, line)?,
+    ))
+}
+
+fn parse_position(text: &str, line: usize) -> Result<Position, AssembleError> {
+    text.split_once(':')
+        .and_then(|(l, c)| Some(Position::new(l.parse().ok()?, c.parse().ok()?)))
+        .ok_or_else(|| AssembleError::new(line, format!("invalid position {text}")))
+}
+
+fn parse_constant(content: &str, index: usize, line: usize) -> Result<Constant, AssembleError> {
+    let content = strip_index(content, index, line)?;
+    let (kind, value) = content.split_once(' ').unwrap_or((content, ""));
+    let invalid = || AssembleError::new(line, format!("invalid {kind} constant {value}"));
+    let object = match kind {
+        "INTEGER" => Object::Integer(value.parse().map_err(|_| invalid())?),
+        "BOOLEAN" => Object::Boolean(value.parse().map_err(|_| invalid())?),
+        "STRING" => Object::String(unescape(value).ok_or_else(invalid)?.into()),
+        "NULL" if value == "null" => Object::Null,
+        "NULL" => return Err(invalid()),
+        "FUNCTION" => return parse_function(value).ok_or_else(invalid),
+        other => {
+            return Err(AssembleError::new(
+                line,
+                format!("unsupported constant type {other}"),
+            ))
+        }
+    };
+    Ok(Constant::Value(object))
+}
+
+/// `name arity=N locals=M`, where `<anonymous>` means no name.
+fn parse_function(text: &str) -> Option<Constant> {
+    let mut words = text.split_whitespace();
+    let name = words.next()?;
+    let num_params = words.next()?.strip_prefix("arity=")?.parse().ok()?;
+    let num_locals = words.next()?.strip_prefix("locals=")?.parse().ok()?;
+    if words.next().is_some() {
+        return None;
+    }
+    Some(Constant::Function(PendingFunction {
+        name: (name != "<anonymous>").then(|| name.to_string()),
+        num_params,
+        num_locals,
+        code: Code::default(),
+    }))
+}
+
+/// Read a string literal in the escaped form `{:?}` prints.
+fn unescape(text: &str) -> Option<String> {
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+        out.push(match chars.next()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            'u' => {
+                let digits = chars
+                    .by_ref()
+                    .skip_while(|ch| *ch == '{')
+                    .take_while(|ch| *ch != '}')
+                    .collect::<String>();
+                char::from_u32(u32::from_str_radix(&digits, 16).ok()?)?
+            }
+            _ => return None,
+        });
+    }
+    Some(out)
+}
@@ -1,15 +1,44 @@
-use crate::position::Position;
+use crate::position::{Position, Span};
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
 /// Program root node.
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Program {
     pub statements: Vec<Statement>,
+    /// Full source extent of each parsed expression, keyed by the
+    /// expression's `pos()`. Filled in by the parser; programs built by hand
+    /// can leave it empty and the compiler records bare positions instead.
+    pub spans: HashMap<Position, Span>,
+    /// Full source extent of each parsed statement, including its `;`,
+    /// keyed by the statement's `pos()`.
+    pub statement_spans: HashMap<Position, Span>,
 }
 
 impl Program {
     pub fn new(statements: Vec<Statement>) -> Self {
-        Self { statements }
+        Self {
+            statements,
+            spans: HashMap::new(),
+            statement_spans: HashMap::new(),
+        }
+    }
+
+    /// The source range `expression` covers, or an empty span at its
+    /// position when it was not parsed from source.
+    pub fn expression_span(&self, expression: &Expression) -> Span {
+        let pos = expression.pos();
+        self.spans.get(&pos).copied().unwrap_or(Span::at(pos))
+    }
+
+    /// The source range `statement` covers, or an empty span at its
+    /// position when it was not parsed from source.
+    pub fn statement_span(&self, statement: &Statement) -> Span {
+        let pos = statement.pos();
+        self.statement_spans
+            .get(&pos)
+            .copied()
+            .unwrap_or(Span::at(pos))
     }
 }
 
@@ -0,0 +1,210 @@
+//! JSON form of a parsed program for external tools, printed by
+//! `monkey --ast-json`.
+//!
+//! Every node is an object whose `"node"` member names its kind (the
+//! `Statement`/`Expression` variant, with `"ExpressionStatement"` and
+//! `"Block"` for the other two), followed by its reported `"pos"`. Parsed
+//! statements and expressions also carry their `"span"`. Child fields are
+//! named after the AST's.
+
+use crate::ast::{BlockStatement, Expression, Identifier, Program, Statement};
+use crate::json::Json;
+use crate::position::Span;
+
+/// `program` as indented JSON.
+pub fn program_to_json(program: &Program) -> String {
+    Json::Object(vec![
+        ("node", Json::string("Program")),
+        ("statements", statements(program, &program.statements)),
+    ])
+    .render()
+}
+
+fn statements(program: &Program, statements: &[Statement]) -> Json {
+    Json::Array(
+        statements
+            .iter()
+            .map(|statement| statement_json(program, statement))
+            .collect(),
+    )
+}
+
+fn statement_json(program: &Program, statement: &Statement) -> Json {
+    let (kind, fields) = match statement {
+        Statement::Let { name, value, .. } => (
+            "Let",
+            vec![
+                ("name", identifier(name)),
+                ("value", expression_json(program, value)),
+            ],
+        ),
+        Statement::Return { value, .. } => {
+            ("Return", vec![("value", expression_json(program, value))])
+        }
+        Statement::While {
+            condition, body, ..
+        } => (
+            "While",
+            vec![
+                ("condition", expression_json(program, condition)),
+                ("body", block(program, body)),
+            ],
+        ),
+        Statement::Break { .. } => ("Break", vec![]),
+        Statement::Continue { .. } => ("Continue", vec![]),
+        Statement::Expression { expression, .. } => (
+            "ExpressionStatement",
+            vec![("expression", expression_json(program, expression))],
+        ),
+    };
+    node(kind, program.statement_span(statement), fields)
+}
+
+fn expression_json(program: &Program, expression: &Expression) -> Json {
+    let (kind, fields) = match expression {
+        Expression::Identifier { value, .. } => {
+            ("Identifier", vec![("value", Json::string(value))])
+        }
+        Expression::IntegerLiteral { value, raw, .. } => (
+            "IntegerLiteral",
+            vec![("value", Json::Int(*value)), ("raw", Json::string(raw))],
+        ),
+        Expression::BooleanLiteral { value, .. } => {
+            ("BooleanLiteral", vec![("value", Json::Bool(*value))])
+        }
+        Expression::StringLiteral { value, .. } => {
+            ("StringLiteral", vec![("value", Json::string(value))])
+        }
+        Expression::Prefix {
+            operator, right, ..
+        } => (
+            "Prefix",
+            vec![
+                ("operator", Json::string(operator)),
+                ("right", expression_json(program, right)),
+            ],
+        ),
+        Expression::Infix {
+            left,
+            operator,
+            right,
+            ..
+        } => (
+            "Infix",
+            vec![
+                ("left", expression_json(program, left)),
+                ("operator", Json::string(operator)),
+                ("right", expression_json(program, right)),
+            ],
+        ),
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+            ..
+        } => (
+            "If",
+            vec![
+                ("condition", expression_json(program, condition)),
+                ("consequence", block(program, consequence)),
+                (
+                    "alternative",
+                    alternative
+                        .as_ref()
+                        .map_or(Json::Null, |alternative| block(program, alternative)),
+                ),
+            ],
+        ),
+        Expression::FunctionLiteral {
+            parameters, body, ..
+        } => (
+            "FunctionLiteral",
+            vec![
+                (
+                    "parameters",
+                    Json::Array(parameters.iter().map(identifier).collect()),
+                ),
+                ("body", block(program, body)),
+            ],
+        ),
+        Expression::Call {
+            function,
+            arguments,
+            ..
+        } => (
+            "Call",
+            vec![
+                ("function", expression_json(program, function)),
+                ("arguments", expressions(program, arguments)),
+            ],
+        ),
+        Expression::ArrayLiteral { elements, .. } => (
+            "ArrayLiteral",
+            vec![("elements", expressions(program, elements))],
+        ),
+        Expression::HashLiteral { pairs, .. } => (
+            "HashLiteral",
+            vec![(
+                "pairs",
+                Json::Array(
+                    pairs
+                        .iter()
+                        .map(|(key, value)| {
+                            Json::Object(vec![
+                                ("key", expression_json(program, key)),
+                                ("value", expression_json(program, value)),
+                            ])
+                        })
+                        .collect(),
+                ),
+            )],
+        ),
+        Expression::Index { left, index, .. } => (
+            "Index",
+            vec![
+                ("left", expression_json(program, left)),
+                ("index", expression_json(program, index)),
+            ],
+        ),
+    };
+    node(kind, program.expression_span(expression), fields)
+}
+
+/// A statement or expression: its kind, position and, when known, extent,
+/// then its children.
+fn node(kind: &str, span: Span, fields: Vec<(&'static str, Json)>) -> Json {
+    let mut members = vec![
+        ("node", Json::string(kind)),
+        ("pos", Json::position(span.pos)),
+    ];
+    if !span.is_empty() {
+        members.push(("span", Json::span(span)));
+    }
+    members.extend(fields);
+    Json::Object(members)
+}
+
+fn expressions(program: &Program, expressions: &[Expression]) -> Json {
+    Json::Array(
+        expressions
+            .iter()
+            .map(|expression| expression_json(program, expression))
+            .collect(),
+    )
+}
+
+fn block(program: &Program, block: &BlockStatement) -> Json {
+    Json::Object(vec![
+        ("node", Json::string("Block")),
+        ("pos", Json::position(block.pos)),
+        ("statements", statements(program, &block.statements)),
+    ])
+}
+
+fn identifier(identifier: &Identifier) -> Json {
+    Json::Object(vec![
+        ("node", Json::string("Identifier")),
+        ("pos", Json::position(identifier.pos)),
+        ("value", Json::string(&identifier.value)),
+    ])
+}
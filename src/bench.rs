@@ -0,0 +1,96 @@
+//! Timing statistics for `monkey bench`, which runs a program several
+//! times after a few unmeasured warmup runs.
+
+use std::time::Duration;
+
+pub const DEFAULT_ITERATIONS: usize = 10;
+pub const DEFAULT_WARMUP: usize = 3;
+
+/// Wall-clock times of the measured runs, kept sorted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchStats {
+    samples: Vec<Duration>,
+}
+
+impl BenchStats {
+    /// Statistics over `samples`, or `None` when there are none.
+    pub fn new(mut samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort();
+        Some(Self { samples })
+    }
+
+    pub fn iterations(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn min(&self) -> Duration {
+        self.samples[0]
+    }
+
+    pub fn max(&self) -> Duration {
+        self.samples[self.samples.len() - 1]
+    }
+
+    /// The middle sample, or the mean of the two middle ones.
+    pub fn median(&self) -> Duration {
+        let mid = self.samples.len() / 2;
+        if self.samples.len().is_multiple_of(2) {
+            (self.samples[mid - 1] + self.samples[mid]) / 2
+        } else {
+            self.samples[mid]
+        }
+    }
+
+    pub fn mean(&self) -> Duration {
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+
+    /// Sample standard deviation; zero for a single sample.
+    pub fn stddev(&self) -> Duration {
+        let n = self.samples.len();
+        if n < 2 {
+            return Duration::ZERO;
+        }
+        let mean = self.mean().as_secs_f64();
+        let variance = self
+            .samples
+            .iter()
+            .map(|sample| (sample.as_secs_f64() - mean).powi(2))
+            .sum::<f64>()
+            / (n - 1) as f64;
+        Duration::from_secs_f64(variance.sqrt())
+    }
+
+    /// Report for `monkey bench`. With the instruction count of one run,
+    /// a throughput line based on the median follows.
+    pub fn render(&self, warmup: usize, instructions: Option<u64>) -> String {
+        let mut out = format!(
+            "Iterations: {} ({warmup} warmup)\nMin: {}\nMedian: {}\nMean: {} ± {}\nMax: {}",
+            self.iterations(),
+            ms(self.min()),
+            ms(self.median()),
+            ms(self.mean()),
+            ms(self.stddev()),
+            ms(self.max()),
+        );
+        if let Some(instructions) = instructions {
+            let seconds = self.median().as_secs_f64();
+            let rate = if seconds > 0.0 {
+                format!("{:.0}", instructions as f64 / seconds)
+            } else {
+                "-".to_string()
+            };
+            out.push_str(&format!(
+                "\nInstructions: {instructions} per run, {rate} per second"
+            ));
+        }
+        out
+    }
+}
+
+fn ms(duration: Duration) -> String {
+    format!("{:.3} ms", duration.as_secs_f64() * 1000.0)
+}
@@ -1,15 +1,116 @@
-use crate::object::{Object, ObjectRef};
+use crate::object::{Object, ObjectRef, StringInterner};
 use crate::runtime_error::RuntimeErrorType;
 
 /// Stable builtin names expected by compatibility contract.
+///
+/// Extension builtins are appended after the protocol set so existing
+/// `GetBuiltin` indices never shift.
 pub fn builtin_names() -> &'static [&'static str] {
-    &["len", "first", "last", "rest", "push", "puts"]
+    &[
+        "len", "first", "last", "rest", "push", "puts", "eval", "args", "substr", "char_at",
+        "chars", "eq", "assert",
+    ]
 }
 
 pub fn builtin_name_at(index: usize) -> Option<&'static str> {
     builtin_names().get(index).copied()
 }
 
+/// Signature and one-line description of a builtin, as shown by the
+/// REPL's `:doc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuiltinDoc {
+    pub name: &'static str,
+    pub signature: &'static str,
+    pub summary: &'static str,
+}
+
+const BUILTIN_DOCS: &[BuiltinDoc] = &[
+    doc(
+        "len",
+        "len(value) -> INTEGER",
+        "Number of characters in a STRING or elements in an ARRAY.",
+    ),
+    doc(
+        "first",
+        "first(arr) -> any",
+        "First element of an ARRAY, or null when it is empty.",
+    ),
+    doc(
+        "last",
+        "last(arr) -> any",
+        "Last element of an ARRAY, or null when it is empty.",
+    ),
+    doc(
+        "rest",
+        "rest(arr) -> ARRAY",
+        "New ARRAY without the first element, or null when it is empty.",
+    ),
+    doc(
+        "push",
+        "push(arr, value) -> ARRAY",
+        "New ARRAY with value appended; arr itself is unchanged.",
+    ),
+    doc(
+        "puts",
+        "puts(values...) -> NULL",
+        "Print the values concatenated on one line.",
+    ),
+    doc(
+        "eval",
+        "eval(source) -> any",
+        "Run a STRING of Monkey code in a fresh VM and return its result.",
+    ),
+    doc(
+        "args",
+        "args() -> ARRAY",
+        "Arguments given to the script after its path, as STRINGs.",
+    ),
+    doc(
+        "substr",
+        "substr(s, start, len) -> STRING",
+        "Up to len characters of s from index start, or null for a negative start or len.",
+    ),
+    doc(
+        "char_at",
+        "char_at(s, index) -> STRING",
+        "Character of s at index, or null when index is out of range.",
+    ),
+    doc(
+        "chars",
+        "chars(s) -> ARRAY",
+        "Characters of s as one-character STRINGs.",
+    ),
+    doc(
+        "eq",
+        "eq(a, b) -> BOOLEAN",
+        "Whether a and b are equal, comparing arrays and hashes by content.",
+    ),
+    doc(
+        "assert",
+        "assert(condition, message?) -> NULL",
+        "Raise ASSERTION_FAILED, with message if given, unless condition is truthy.",
+    ),
+];
+
+const fn doc(name: &'static str, signature: &'static str, summary: &'static str) -> BuiltinDoc {
+    BuiltinDoc {
+        name,
+        signature,
+        summary,
+    }
+}
+
+/// Documentation for every builtin, in `builtin_names` order.
+pub fn builtin_docs() -> &'static [BuiltinDoc] {
+    BUILTIN_DOCS
+}
+
+/// Documentation for the builtin `name`, if there is one.
+pub fn builtin_doc(name: &str) -> Option<&'static BuiltinDoc> {
+    builtin_docs().iter().find(|doc| doc.name == name)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BuiltinError {
     pub error_type: RuntimeErrorType,
@@ -17,14 +118,14 @@ pub struct BuiltinError {
 }
 
 impl BuiltinError {
-    fn wrong_arg_count(name: &str, expected: usize, got: usize) -> Self {
+    pub(crate) fn wrong_arg_count(name: &str, expected: usize, got: usize) -> Self {
         Self {
             error_type: RuntimeErrorType::WrongArgumentCount,
             message: format!("{name} expected {expected} argument(s), got {got}"),
         }
     }
 
-    fn invalid_arg_type(name: &str, expected: &str, got: &str) -> Self {
+    pub(crate) fn invalid_arg_type(name: &str, expected: &str, got: &str) -> Self {
         Self {
             error_type: RuntimeErrorType::InvalidArgumentType,
             message: format!("{name} expected {expected}, got {got}"),
@@ -32,10 +133,18 @@ impl BuiltinError {
     }
 }
 
+/// Host state a builtin may read or write while executing.
+#[derive(Debug)]
+pub struct BuiltinContext<'a> {
+    pub output: &'a mut Vec<String>,
+    pub script_args: &'a [String],
+    pub strings: &'a mut StringInterner,
+}
+
 pub fn execute_builtin(
     name: &str,
     args: &[ObjectRef],
-    output: &mut Vec<String>,
+    ctx: &mut BuiltinContext<'_>,
 ) -> Result<ObjectRef, BuiltinError> {
     match name {
         "len" => {
@@ -124,12 +233,111 @@ pub fn execute_builtin(
                 .map(|arg| arg.inspect())
                 .collect::<Vec<_>>()
                 .join("");
-            output.push(line);
+            ctx.output.push(line);
             Ok(Object::Null.rc())
         }
+        "args" => {
+            if !args.is_empty() {
+                return Err(BuiltinError::wrong_arg_count("args", 0, args.len()));
+            }
+            let values = ctx
+                .script_args
+                .iter()
+                .map(|arg| Object::String(ctx.strings.intern(arg)).rc())
+                .collect();
+            Ok(Object::Array(values).rc())
+        }
+        "substr" => {
+            if args.len() != 3 {
+                return Err(BuiltinError::wrong_arg_count("substr", 3, args.len()));
+            }
+            let s = expect_string("substr", &args[0])?;
+            let start = expect_integer("substr", &args[1])?;
+            let len = expect_integer("substr", &args[2])?;
+            if start < 0 || len < 0 {
+                return Ok(Object::Null.rc());
+            }
+            let out: String = s.chars().skip(start as usize).take(len as usize).collect();
+            Ok(Object::String(ctx.strings.intern(&out)).rc())
+        }
+        "char_at" => {
+            if args.len() != 2 {
+                return Err(BuiltinError::wrong_arg_count("char_at", 2, args.len()));
+            }
+            let s = expect_string("char_at", &args[0])?;
+            let index = expect_integer("char_at", &args[1])?;
+            if index < 0 {
+                return Ok(Object::Null.rc());
+            }
+            Ok(s.chars()
+                .nth(index as usize)
+                .map(|ch| Object::String(ctx.strings.intern(ch.encode_utf8(&mut [0; 4]))).rc())
+                .unwrap_or_else(|| Object::Null.rc()))
+        }
+        "chars" => {
+            if args.len() != 1 {
+                return Err(BuiltinError::wrong_arg_count("chars", 1, args.len()));
+            }
+            let s = expect_string("chars", &args[0])?;
+            let values = s
+                .chars()
+                .map(|ch| Object::String(ctx.strings.intern(ch.encode_utf8(&mut [0; 4]))).rc())
+                .collect();
+            Ok(Object::Array(values).rc())
+        }
+        "eq" => {
+            if args.len() != 2 {
+                return Err(BuiltinError::wrong_arg_count("eq", 2, args.len()));
+            }
+            Ok(Object::Boolean(args[0].deep_eq(&args[1])).rc())
+        }
+        "assert" => {
+            if !(1..=2).contains(&args.len()) {
+                return Err(BuiltinError::wrong_arg_count("assert", 1, args.len()));
+            }
+            if args[0].is_truthy() {
+                return Ok(Object::Null.rc());
+            }
+            let message = match args.get(1).map(|message| message.as_ref()) {
+                Some(Object::String(message)) => format!("assertion failed: {message}"),
+                Some(other) => format!("assertion failed: {}", other.inspect()),
+                None => "assertion failed".to_string(),
+            };
+            Err(BuiltinError {
+                error_type: RuntimeErrorType::AssertionFailed,
+                message,
+            })
+        }
+        // `eval` needs a compiler and a child VM, so the VM dispatches it itself.
+        "eval" => Err(BuiltinError {
+            error_type: RuntimeErrorType::UnsupportedOperation,
+            message: "eval requires a VM context".to_string(),
+        }),
         _ => Err(BuiltinError {
             error_type: RuntimeErrorType::UnsupportedOperation,
             message: format!("unknown builtin: {name}"),
         }),
     }
 }
+
+fn expect_string<'a>(name: &str, arg: &'a ObjectRef) -> Result<&'a str, BuiltinError> {
+    match arg.as_ref() {
+        Object::String(v) => Ok(v),
+        other => Err(BuiltinError::invalid_arg_type(
+            name,
+            "STRING",
+            other.type_name(),
+        )),
+    }
+}
+
+fn expect_integer(name: &str, arg: &ObjectRef) -> Result<i64, BuiltinError> {
+    match arg.as_ref() {
+        Object::Integer(v) => Ok(*v),
+        other => Err(BuiltinError::invalid_arg_type(
+            name,
+            "INTEGER",
+            other.type_name(),
+        )),
+    }
+}
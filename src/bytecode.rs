@@ -1,7 +1,9 @@
+use std::collections::BTreeMap;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
-use crate::object::ObjectRef;
-use crate::position::Position;
+pub use crate::asm::{assemble, AssembleError};
+use crate::object::{Object, ObjectRef};
+use crate::position::{Position, Span};
 
 pub type Instructions = Vec<u8>;
 
@@ -44,9 +46,15 @@ pub enum Opcode {
     InvalidBreak = 33,
     InvalidContinue = 34,
     Nop = 35,
+    AddConstants = 36,
+    AddLocals = 37,
+    JumpIfFalsePop = 38,
+    ConstInt = 39,
+    Const0 = 40,
+    Const1 = 41,
 }
 
-const ALL_OPCODES: [Opcode; 36] = [
+const ALL_OPCODES: [Opcode; 42] = [
     Opcode::Constant,
     Opcode::True,
     Opcode::False,
@@ -83,6 +91,12 @@ const ALL_OPCODES: [Opcode; 36] = [
     Opcode::InvalidBreak,
     Opcode::InvalidContinue,
     Opcode::Nop,
+    Opcode::AddConstants,
+    Opcode::AddLocals,
+    Opcode::JumpIfFalsePop,
+    Opcode::ConstInt,
+    Opcode::Const0,
+    Opcode::Const1,
 ];
 
 impl Opcode {
@@ -132,160 +146,279 @@ impl Opcode {
             33 => Some(Opcode::InvalidBreak),
             34 => Some(Opcode::InvalidContinue),
             35 => Some(Opcode::Nop),
+            36 => Some(Opcode::AddConstants),
+            37 => Some(Opcode::AddLocals),
+            38 => Some(Opcode::JumpIfFalsePop),
+            39 => Some(Opcode::ConstInt),
+            40 => Some(Opcode::Const0),
+            41 => Some(Opcode::Const1),
             _ => None,
         }
     }
 }
 
+/// What an operand refers to, so tools can render and check it without
+/// knowing each opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    /// Index into the chunk's constant pool.
+    ConstIndex,
+    GlobalSlot,
+    /// Slot in the current function's locals, parameters first.
+    LocalSlot,
+    /// Index into the current closure's captured variables.
+    FreeSlot,
+    /// Index into `builtins::builtin_names`.
+    BuiltinIndex,
+    /// Absolute byte offset in the same function.
+    JumpTarget,
+    /// Number of arguments passed to a call.
+    Arity,
+    /// Number of array elements, hash pairs or captured variables.
+    Count,
+    /// An `i16` stored as its two's-complement `u16`.
+    SmallInt,
+}
+
+impl OperandKind {
+    /// The operand as the disassembler prints it.
+    pub fn render(self, value: usize) -> String {
+        match self {
+            OperandKind::SmallInt => (value as u16 as i16).to_string(),
+            _ => value.to_string(),
+        }
+    }
+
+    /// Read an operand as `render` prints it.
+    pub fn parse(self, text: &str) -> Option<usize> {
+        match self {
+            OperandKind::SmallInt => text.parse::<i16>().ok().map(|value| value as u16 as usize),
+            _ => text.parse().ok(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Definition {
     pub name: &'static str,
     pub operand_widths: &'static [usize],
+    /// One per operand, parallel to `operand_widths`.
+    pub operand_kinds: &'static [OperandKind],
 }
 
 const DEF_CONSTANT: Definition = Definition {
     name: "Constant",
     operand_widths: &[2],
+    operand_kinds: &[OperandKind::ConstIndex],
 };
 const DEF_TRUE: Definition = Definition {
     name: "True",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_FALSE: Definition = Definition {
     name: "False",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_NULL: Definition = Definition {
     name: "Null",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_POP: Definition = Definition {
     name: "Pop",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_ADD: Definition = Definition {
     name: "Add",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_SUB: Definition = Definition {
     name: "Sub",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_MUL: Definition = Definition {
     name: "Mul",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_DIV: Definition = Definition {
     name: "Div",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_NEG: Definition = Definition {
     name: "Neg",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_BANG: Definition = Definition {
     name: "Bang",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_EQ: Definition = Definition {
     name: "Eq",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_NE: Definition = Definition {
     name: "Ne",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_LT: Definition = Definition {
     name: "Lt",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_GT: Definition = Definition {
     name: "Gt",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_LE: Definition = Definition {
     name: "Le",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_GE: Definition = Definition {
     name: "Ge",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_JUMP: Definition = Definition {
     name: "Jump",
     operand_widths: &[2],
+    operand_kinds: &[OperandKind::JumpTarget],
 };
 const DEF_JUMP_IF_FALSE: Definition = Definition {
     name: "JumpIfFalse",
     operand_widths: &[2],
+    operand_kinds: &[OperandKind::JumpTarget],
 };
 const DEF_GET_GLOBAL: Definition = Definition {
     name: "GetGlobal",
     operand_widths: &[2],
+    operand_kinds: &[OperandKind::GlobalSlot],
 };
 const DEF_SET_GLOBAL: Definition = Definition {
     name: "SetGlobal",
     operand_widths: &[2],
+    operand_kinds: &[OperandKind::GlobalSlot],
 };
 const DEF_GET_LOCAL: Definition = Definition {
     name: "GetLocal",
     operand_widths: &[1],
+    operand_kinds: &[OperandKind::LocalSlot],
 };
 const DEF_SET_LOCAL: Definition = Definition {
     name: "SetLocal",
     operand_widths: &[1],
+    operand_kinds: &[OperandKind::LocalSlot],
 };
 const DEF_GET_BUILTIN: Definition = Definition {
     name: "GetBuiltin",
     operand_widths: &[1],
+    operand_kinds: &[OperandKind::BuiltinIndex],
 };
 const DEF_GET_FREE: Definition = Definition {
     name: "GetFree",
     operand_widths: &[1],
+    operand_kinds: &[OperandKind::FreeSlot],
 };
 const DEF_CLOSURE: Definition = Definition {
     name: "Closure",
     operand_widths: &[2, 1],
+    operand_kinds: &[OperandKind::ConstIndex, OperandKind::Count],
 };
 const DEF_CURRENT_CLOSURE: Definition = Definition {
     name: "CurrentClosure",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_CALL: Definition = Definition {
     name: "Call",
     operand_widths: &[1],
+    operand_kinds: &[OperandKind::Arity],
 };
 const DEF_RETURN_VALUE: Definition = Definition {
     name: "ReturnValue",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_RETURN: Definition = Definition {
     name: "Return",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_ARRAY: Definition = Definition {
     name: "Array",
     operand_widths: &[2],
+    operand_kinds: &[OperandKind::Count],
 };
 const DEF_HASH: Definition = Definition {
     name: "Hash",
     operand_widths: &[2],
+    operand_kinds: &[OperandKind::Count],
 };
 const DEF_INDEX: Definition = Definition {
     name: "Index",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_INVALID_BREAK: Definition = Definition {
     name: "InvalidBreak",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_INVALID_CONTINUE: Definition = Definition {
     name: "InvalidContinue",
     operand_widths: &[],
+    operand_kinds: &[],
 };
 const DEF_NOP: Definition = Definition {
     name: "Nop",
     operand_widths: &[],
+    operand_kinds: &[],
+};
+// Superinstructions produced by `peephole::fuse_superinstructions`. Each is
+// padded with `Nop` bytes to the length of the sequence it replaces.
+const DEF_ADD_CONSTANTS: Definition = Definition {
+    name: "AddConstants",
+    operand_widths: &[2, 2],
+    operand_kinds: &[OperandKind::ConstIndex, OperandKind::ConstIndex],
+};
+const DEF_ADD_LOCALS: Definition = Definition {
+    name: "AddLocals",
+    operand_widths: &[1, 1],
+    operand_kinds: &[OperandKind::LocalSlot, OperandKind::LocalSlot],
+};
+const DEF_JUMP_IF_FALSE_POP: Definition = Definition {
+    name: "JumpIfFalsePop",
+    operand_widths: &[2],
+    operand_kinds: &[OperandKind::JumpTarget],
+};
+// Small integer literals, pushed without a constant pool entry. The
+// `ConstInt` operand is an `i16` stored as its two's-complement `u16`.
+const DEF_CONST_INT: Definition = Definition {
+    name: "ConstInt",
+    operand_widths: &[2],
+    operand_kinds: &[OperandKind::SmallInt],
+};
+const DEF_CONST_0: Definition = Definition {
+    name: "Const0",
+    operand_widths: &[],
+    operand_kinds: &[],
+};
+const DEF_CONST_1: Definition = Definition {
+    name: "Const1",
+    operand_widths: &[],
+    operand_kinds: &[],
 };
 
 pub fn lookup_definition(op: Opcode) -> &'static Definition {
@@ -326,6 +459,12 @@ pub fn lookup_definition(op: Opcode) -> &'static Definition {
         Opcode::InvalidBreak => &DEF_INVALID_BREAK,
         Opcode::InvalidContinue => &DEF_INVALID_CONTINUE,
         Opcode::Nop => &DEF_NOP,
+        Opcode::AddConstants => &DEF_ADD_CONSTANTS,
+        Opcode::AddLocals => &DEF_ADD_LOCALS,
+        Opcode::JumpIfFalsePop => &DEF_JUMP_IF_FALSE_POP,
+        Opcode::ConstInt => &DEF_CONST_INT,
+        Opcode::Const0 => &DEF_CONST_0,
+        Opcode::Const1 => &DEF_CONST_1,
     }
 }
 
@@ -393,6 +532,33 @@ impl Display for BytecodeError {
     }
 }
 
+/// A fingerprint of the instruction set: every opcode's byte, name and
+/// operand widths, hashed with 64-bit FNV-1a. It changes whenever an opcode
+/// is added, renumbered or re-encoded, so serialized bytecode can tell it
+/// was produced for a different VM.
+pub fn opcode_set_hash() -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    let mut hash = OFFSET_BASIS;
+    let mut feed = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(PRIME);
+        }
+    };
+    for &op in Opcode::all() {
+        let def = lookup_definition(op);
+        feed(&[op.to_byte()]);
+        feed(def.name.as_bytes());
+        for &width in def.operand_widths {
+            feed(&[width as u8]);
+        }
+        // Separates one opcode from the next.
+        feed(&[0xff]);
+    }
+    hash
+}
+
 pub fn make(op: Opcode, operands: &[usize]) -> Result<Vec<u8>, BytecodeError> {
     let def = lookup_definition(op);
     if operands.len() != def.operand_widths.len() {
@@ -476,11 +642,70 @@ pub fn read_operands(def: &Definition, bytes: &[u8]) -> Result<(Vec<usize>, usiz
     Ok((operands, offset))
 }
 
+/// Decode every instruction of `code` as `(offset, opcode, operands)`.
+/// Returns `None` when an opcode is unknown or an instruction is cut off.
+pub fn decode_instructions(code: &[u8]) -> Option<Vec<(usize, Opcode, Vec<usize>)>> {
+    let mut out = Vec::new();
+    let mut offset = 0;
+    while offset < code.len() {
+        let op = Opcode::from_byte(code[offset])?;
+        let (operands, consumed) =
+            read_operands(lookup_definition(op), &code[offset + 1..]).ok()?;
+        out.push((offset, op, operands));
+        offset += 1 + consumed;
+    }
+    Some(out)
+}
+
+/// Size and shape of a chunk, from `Chunk::stats`. Counts cover the
+/// top-level code and every compiled function constant.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkStats {
+    pub instructions: usize,
+    pub bytes: usize,
+    pub functions: usize,
+    /// Constant count per type name, e.g. `"STRING"`.
+    pub constants: BTreeMap<&'static str, usize>,
+    /// Largest distance in bytes between a jump and its target.
+    pub max_jump_distance: usize,
+    /// How deeply function literals nest; 0 without functions, 1 when
+    /// functions are only created by top-level code.
+    pub max_function_depth: usize,
+}
+
+impl Display for ChunkStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        writeln!(
+            f,
+            "instructions: {} ({} bytes)",
+            self.instructions, self.bytes
+        )?;
+        writeln!(
+            f,
+            "functions: {} (max nesting depth {})",
+            self.functions, self.max_function_depth
+        )?;
+        let total = self.constants.values().sum::<usize>();
+        let by_type = self
+            .constants
+            .iter()
+            .map(|(kind, count)| format!("{kind} {count}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if by_type.is_empty() {
+            writeln!(f, "constants: {total}")?;
+        } else {
+            writeln!(f, "constants: {total} ({by_type})")?;
+        }
+        write!(f, "max jump distance: {} bytes", self.max_jump_distance)
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Chunk {
     pub instructions: Instructions,
     pub constants: Vec<ObjectRef>,
-    pub positions: Vec<(usize, Position)>,
+    pub positions: Vec<(usize, Span)>,
 }
 
 impl Chunk {
@@ -504,70 +729,163 @@ impl Chunk {
         idx
     }
 
-    pub fn record_pos(&mut self, offset: usize, pos: Position) {
-        self.positions.push((offset, pos));
+    pub fn record_pos(&mut self, offset: usize, span: impl Into<Span>) {
+        self.positions.push((offset, span.into()));
         self.positions.sort_by_key(|(off, _)| *off);
     }
 
     pub fn position_for_offset(&self, offset: usize) -> Option<Position> {
-        self.positions
-            .iter()
-            .take_while(|(off, _)| *off <= offset)
-            .last()
-            .map(|(_, pos)| *pos)
+        position_at(&self.positions, offset)
     }
 
-    pub fn disassemble(&self) -> String {
-        // TODO(step-10): compiler will emit chunk instructions and position metadata.
-        // TODO(step-17): VM will consume offsets for runtime error source mapping.
-        let mut lines = Vec::new();
-        let mut offset = 0;
-
-        while offset < self.instructions.len() {
-            let byte = self.instructions[offset];
-            let Some(op) = Opcode::from_byte(byte) else {
-                lines.push(format!("{:04} <unknown opcode {}>", offset, byte));
-                break;
-            };
+    pub fn span_for_offset(&self, offset: usize) -> Option<Span> {
+        span_at(&self.positions, offset)
+    }
 
-            let def = lookup_definition(op);
-            let operands_start = offset + 1;
-            let operand_bytes = &self.instructions[operands_start..];
-            let decoded = read_operands(def, operand_bytes);
-
-            match decoded {
-                Ok((operands, consumed)) => {
-                    let operands_rendered = if operands.is_empty() {
-                        String::new()
-                    } else {
-                        format!(
-                            " {}",
-                            operands
-                                .iter()
-                                .map(ToString::to_string)
-                                .collect::<Vec<_>>()
-                                .join(" ")
-                        )
-                    };
-                    let pos_suffix = self
-                        .position_for_offset(offset)
-                        .map(|p| format!(" @{}", p))
-                        .unwrap_or_default();
-                    lines.push(format!(
-                        "{:04} {}{}{}",
-                        offset, def.name, operands_rendered, pos_suffix
-                    ));
-                    offset += 1 + consumed;
+    pub fn stats(&self) -> ChunkStats {
+        let mut stats = ChunkStats::default();
+        let mut bodies = vec![&self.instructions[..]];
+        for constant in &self.constants {
+            *stats.constants.entry(constant.type_name()).or_default() += 1;
+            if let Object::CompiledFunction(function) = constant.as_ref() {
+                stats.functions += 1;
+                bodies.push(&function.instructions);
+            }
+        }
+        for code in bodies {
+            stats.bytes += code.len();
+            for (offset, op, operands) in decode_instructions(code).unwrap_or_default() {
+                stats.instructions += 1;
+                if matches!(
+                    op,
+                    Opcode::Jump | Opcode::JumpIfFalse | Opcode::JumpIfFalsePop
+                ) {
+                    stats.max_jump_distance =
+                        stats.max_jump_distance.max(operands[0].abs_diff(offset));
                 }
-                Err(_) => {
-                    lines.push(format!("{:04} {} <truncated>", offset, def.name));
-                    break;
+            }
+        }
+        stats.max_function_depth = self.function_depth(&self.instructions, 0);
+        stats
+    }
+
+    /// Deepest chain of `Closure` instructions starting in `code`, which
+    /// sits at `depth`. Stops at the pool size so malformed, self-creating
+    /// functions cannot recurse forever.
+    fn function_depth(&self, code: &[u8], depth: usize) -> usize {
+        if depth >= self.constants.len() {
+            return depth;
+        }
+        decode_instructions(code)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(_, op, _)| *op == Opcode::Closure)
+            .filter_map(
+                |(_, _, operands)| match self.constants.get(operands[0])?.as_ref() {
+                    Object::CompiledFunction(function) => {
+                        Some(self.function_depth(&function.instructions, depth + 1))
+                    }
+                    _ => None,
+                },
+            )
+            .max()
+            .unwrap_or(depth)
+    }
+
+    /// The top-level instructions, then a `Constants:` table with one line
+    /// per constant (index, type, value). Compiled functions are followed
+    /// by their own instructions and positions, indented by four spaces.
+    pub fn disassemble(&self) -> String {
+        let mut out = disassemble_code(&self.instructions, &self.positions);
+        if self.constants.is_empty() {
+            return out;
+        }
+        out.push_str("\n\nConstants:");
+        for (index, constant) in self.constants.iter().enumerate() {
+            let value = match constant.as_ref() {
+                Object::String(value) => format!("{value:?}"),
+                Object::CompiledFunction(function) => format!(
+                    "{} arity={} locals={}",
+                    function.name.as_deref().unwrap_or("<anonymous>"),
+                    function.num_params,
+                    function.num_locals
+                ),
+                other => other.inspect(),
+            };
+            out.push_str(&format!("\n{index:04} {} {value}", constant.type_name()));
+            if let Object::CompiledFunction(function) = constant.as_ref() {
+                for line in disassemble_code(&function.instructions, &function.positions).lines() {
+                    out.push_str(&format!("\n    {line}"));
                 }
             }
         }
+        out
+    }
+}
+
+/// One line per instruction of `code`: offset, opcode name, operands and
+/// the source span from `positions`, if any. Used for the main chunk
+/// and for the bodies of compiled functions alike.
+pub fn disassemble_code(code: &[u8], positions: &[(usize, Span)]) -> String {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    while offset < code.len() {
+        let byte = code[offset];
+        let Some(op) = Opcode::from_byte(byte) else {
+            lines.push(format!("{:04} <unknown opcode {}>", offset, byte));
+            break;
+        };
 
-        lines.join("\n")
+        let def = lookup_definition(op);
+        let operands_start = offset + 1;
+        let operand_bytes = &code[operands_start..];
+        let decoded = read_operands(def, operand_bytes);
+
+        match decoded {
+            Ok((operands, consumed)) => {
+                let operands_rendered = if operands.is_empty() {
+                    String::new()
+                } else {
+                    format!(
+                        " {}",
+                        operands
+                            .iter()
+                            .zip(def.operand_kinds)
+                            .map(|(value, kind)| kind.render(*value))
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    )
+                };
+                let pos_suffix = span_at(positions, offset)
+                    .map(|p| format!(" @{}", p))
+                    .unwrap_or_default();
+                lines.push(format!(
+                    "{:04} {}{}{}",
+                    offset, def.name, operands_rendered, pos_suffix
+                ));
+                offset += 1 + consumed;
+            }
+            Err(_) => {
+                lines.push(format!("{:04} {} <truncated>", offset, def.name));
+                break;
+            }
+        }
     }
+
+    lines.join("\n")
+}
+
+/// Reported position of the last entry at or before `offset` in an
+/// offset-sorted position table.
+pub fn position_at(positions: &[(usize, Span)], offset: usize) -> Option<Position> {
+    span_at(positions, offset).map(|span| span.pos)
+}
+
+/// Like `position_at`, returning the whole span.
+pub fn span_at(positions: &[(usize, Span)], offset: usize) -> Option<Span> {
+    let end = positions.partition_point(|(off, _)| *off <= offset);
+    end.checked_sub(1).map(|idx| positions[idx].1)
 }
 
 pub type Bytecode = Chunk;
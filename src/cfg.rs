@@ -0,0 +1,192 @@
+//! Control-flow graphs of compiled bytecode.
+//!
+//! `build` splits one function's instructions into basic blocks: straight
+//! runs of code entered only at the top and left only at the bottom. Blocks
+//! start at offset 0, at every jump target and after every jump or return.
+//! `to_dot` renders a whole chunk as Graphviz, one cluster per function, to
+//! show how `if`, `while`, `&&` and `||` were lowered.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::bytecode::{decode_instructions, lookup_definition, Chunk, Opcode};
+use crate::object::Object;
+
+/// How control gets from one block to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// The block ends without a jump and execution continues below it.
+    Fallthrough,
+    /// An unconditional `Jump`.
+    Jump,
+    /// A conditional jump whose condition held, so it did not jump.
+    True,
+    /// A conditional jump that was taken.
+    False,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    /// Index of the successor in the block list.
+    pub target: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// Offset of the first instruction.
+    pub start: usize,
+    /// Offset just past the last instruction.
+    pub end: usize,
+    /// `(offset, opcode, operands)` of each instruction.
+    pub instructions: Vec<(usize, Opcode, Vec<usize>)>,
+    /// Successors; empty for blocks that return or raise an error.
+    pub edges: Vec<Edge>,
+}
+
+/// Split `code` into basic blocks, in offset order. Returns `None` when
+/// the code does not decode or a jump lands between instructions.
+pub fn build(code: &[u8]) -> Option<Vec<BasicBlock>> {
+    let decoded = decode_instructions(code)?;
+
+    let boundaries = decoded
+        .iter()
+        .map(|(offset, _, _)| *offset)
+        .chain([code.len()])
+        .collect::<Vec<_>>();
+    let mut leaders = BTreeSet::from([0]);
+    for (index, (_, op, operands)) in decoded.iter().enumerate() {
+        if let Some(target) = jump_target(*op, operands) {
+            if boundaries.binary_search(&target).is_err() {
+                return None;
+            }
+            leaders.insert(target);
+        }
+        if ends_block(*op) {
+            leaders.insert(boundaries[index + 1]);
+        }
+    }
+    leaders.retain(|&leader| leader < code.len());
+    let starts = leaders.into_iter().collect::<Vec<_>>();
+    let block_at = |offset: usize| starts.iter().position(|&start| start == offset);
+
+    let mut blocks = Vec::with_capacity(starts.len());
+    for (index, &start) in starts.iter().enumerate() {
+        let end = starts.get(index + 1).copied().unwrap_or(code.len());
+        let instructions = decoded
+            .iter()
+            .filter(|(offset, _, _)| (start..end).contains(offset))
+            .cloned()
+            .collect::<Vec<_>>();
+        let (_, last, operands) = instructions.last()?;
+        let fallthrough = block_at(end).map(|target| Edge {
+            target,
+            kind: EdgeKind::Fallthrough,
+        });
+        let jump = |kind| {
+            jump_target(*last, operands)
+                .and_then(block_at)
+                .map(|target| Edge { target, kind })
+        };
+        let edges = match last {
+            Opcode::Jump => jump(EdgeKind::Jump).into_iter().collect(),
+            Opcode::JumpIfFalse | Opcode::JumpIfFalsePop => fallthrough
+                .map(|edge| Edge {
+                    kind: EdgeKind::True,
+                    ..edge
+                })
+                .into_iter()
+                .chain(jump(EdgeKind::False))
+                .collect(),
+            op if ends_block(*op) => Vec::new(),
+            _ => fallthrough.into_iter().collect(),
+        };
+        blocks.push(BasicBlock {
+            start,
+            end,
+            instructions,
+            edges,
+        });
+    }
+    Some(blocks)
+}
+
+fn jump_target(op: Opcode, operands: &[usize]) -> Option<usize> {
+    match op {
+        Opcode::Jump | Opcode::JumpIfFalse | Opcode::JumpIfFalsePop => operands.first().copied(),
+        _ => None,
+    }
+}
+
+fn ends_block(op: Opcode) -> bool {
+    matches!(
+        op,
+        Opcode::Jump
+            | Opcode::JumpIfFalse
+            | Opcode::JumpIfFalsePop
+            | Opcode::ReturnValue
+            | Opcode::Return
+            | Opcode::InvalidBreak
+            | Opcode::InvalidContinue
+    )
+}
+
+/// Render the top-level code and every compiled function in `chunk` as a
+/// Graphviz digraph. Functions whose code does not decode are drawn as a
+/// single node saying so.
+pub fn to_dot(chunk: &Chunk) -> String {
+    let mut functions = vec![("<main>".to_string(), chunk.instructions.to_vec())];
+    for (index, constant) in chunk.constants.iter().enumerate() {
+        if let Object::CompiledFunction(function) = constant.as_ref() {
+            let name = function.name.as_deref().unwrap_or("<anonymous>");
+            functions.push((
+                format!("{name} (constant {index})"),
+                function.instructions.to_vec(),
+            ));
+        }
+    }
+
+    let mut out = String::from("digraph cfg {\n  node [shape=box, fontname=\"monospace\"];\n");
+    for (fn_index, (name, code)) in functions.iter().enumerate() {
+        let _ = writeln!(out, "  subgraph cluster_{fn_index} {{");
+        let _ = writeln!(out, "    label=\"{}\";", escape(name));
+        let node = |block: usize| format!("f{fn_index}_b{block}");
+        match build(code) {
+            Some(blocks) => {
+                for (index, block) in blocks.iter().enumerate() {
+                    let mut label = String::new();
+                    for (offset, op, operands) in &block.instructions {
+                        let def = lookup_definition(*op);
+                        let _ = write!(label, "{offset:04} {}", def.name);
+                        for (operand, kind) in operands.iter().zip(def.operand_kinds) {
+                            let _ = write!(label, " {}", kind.render(*operand));
+                        }
+                        label.push_str("\\l");
+                    }
+                    let _ = writeln!(out, "    {} [label=\"{label}\"];", node(index));
+                }
+                for (index, block) in blocks.iter().enumerate() {
+                    for edge in &block.edges {
+                        let attrs = match edge.kind {
+                            EdgeKind::Fallthrough | EdgeKind::Jump => String::new(),
+                            EdgeKind::True => " [label=\"true\"]".to_string(),
+                            EdgeKind::False => " [label=\"false\"]".to_string(),
+                        };
+                        let _ =
+                            writeln!(out, "    {} -> {}{attrs};", node(index), node(edge.target));
+                    }
+                }
+            }
+            None => {
+                let _ = writeln!(out, "    {} [label=\"invalid bytecode\"];", node(0));
+            }
+        }
+        out.push_str("  }\n");
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -1,23 +1,415 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use crate::diagnostics::ErrorFormat;
+use crate::pretty::{AstFormat, TokenFormat};
+
+pub const USAGE: &str = "Usage: monkey [repl [--no-color] | run <path> [--timeout <ms>] [--trace] [--profile] [--no-fuse] [--register] [--warnings] [--stats] [--coverage] [--exit-with-result] [--error-format text|json] [--watch] [-- <args>...] | bench <path> [--iterations <n>] [--warmup <n>] [--timeout <ms>] [--trace] [--profile] [--no-fuse] [--register] [--warnings] [--stats] [--coverage] [--exit-with-result] [--error-format text|json] [-- <args>...] | debug <path> [-- <args>...] | profile <path> [-- <args>...] | compile <path> [-o <out.mkc>] [--stats] | emit-c <path> [-o <out.c>] | wasm <path> [-o <out.wasm>] | disasm <path> | cfg <path> | fmt [--check] <path>... | check [--deny-warnings] [--error-format text|json] <path>... | test [<path>...] | explain <code> | --tokens <path> [--format text|json] | --ast <path> [--format text|sexpr|json|pretty] [--indent <n>] [--width <n>] | --ast-json <path>]";
+
+/// Arguments `parse_args` does not accept. It displays as `USAGE`, which
+/// the CLI prints to stderr before exiting with code 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsageError;
+
+impl Display for UsageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(USAGE)
+    }
+}
+
+impl Error for UsageError {}
+
+/// Path argument that stands for standard input, e.g. `monkey run -`.
+pub const STDIN_PATH: &str = "-";
+
+/// Options accepted by `run` and `bench` before the `--` separator.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunFlags {
+    /// Wall-clock limit from `--timeout <ms>`.
+    pub timeout_ms: Option<u64>,
+    /// Print a per-instruction execution trace to stderr (`--trace`).
+    pub trace: bool,
+    /// Print opcode and function counters to stderr (`--profile`).
+    pub profile: bool,
+    /// Run the compiler's bytecode without superinstruction fusion
+    /// (`--no-fuse`).
+    pub no_fuse: bool,
+    /// Use the experimental register VM (`--register`, requires the
+    /// `register-vm` feature). Cannot be combined with `--timeout`,
+    /// `--trace`, `--profile` or `--coverage`.
+    pub register: bool,
+    /// Print compiler warnings to stderr before running (`--warnings`).
+    /// Off by default so stderr stays what the Java implementation prints.
+    pub warnings: bool,
+    /// Print `Chunk::stats` to stderr before running (`--stats`).
+    pub stats: bool,
+    /// Print which source lines never ran to stderr afterwards
+    /// (`--coverage`).
+    pub coverage: bool,
+    /// Exit with the program's result when it is an integer
+    /// (`--exit-with-result`).
+    pub exit_with_result: bool,
+    /// How errors and warnings are printed (`--error-format <text|json>`).
+    pub error_format: ErrorFormat,
+    /// Run again whenever the file changes (`--watch`, `run` only).
+    pub watch: bool,
+    /// Measured runs (`--iterations <n>`, `bench` only, at least 1).
+    pub iterations: Option<usize>,
+    /// Unmeasured runs before them (`--warmup <n>`, `bench` only).
+    pub warmup: Option<usize>,
+}
+
 /// Parsed CLI command.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Command {
-    Repl,
-    Run { path: String },
-    Bench { path: String },
-    Tokens { path: String },
-    Ast { path: String },
+    Repl {
+        /// Print plainly even on a terminal (`--no-color`).
+        no_color: bool,
+    },
+    Run {
+        path: String,
+        args: Vec<String>,
+        flags: RunFlags,
+    },
+    Bench {
+        path: String,
+        args: Vec<String>,
+        flags: RunFlags,
+    },
+    Debug {
+        path: String,
+        args: Vec<String>,
+    },
+    /// Run with profiling and print instructions per call stack in folded
+    /// format.
+    Profile {
+        path: String,
+        args: Vec<String>,
+    },
+    /// Compile to a `.mkc` file; `output` defaults to `path` with an
+    /// `.mkc` extension.
+    Compile {
+        path: String,
+        output: Option<String>,
+        /// Print `Chunk::stats` to stderr (`--stats`).
+        stats: bool,
+    },
+    /// Translate to a C program; `output` defaults to `path` with a `.c`
+    /// extension.
+    EmitC {
+        path: String,
+        output: Option<String>,
+    },
+    /// Compile to a WebAssembly module; `output` defaults to `path` with
+    /// a `.wasm` extension.
+    Wasm {
+        path: String,
+        output: Option<String>,
+    },
+    /// Print the disassembly of a source or `.mkc` file, followed by
+    /// each compiled function it contains.
+    Disasm {
+        path: String,
+    },
+    /// Print the control-flow graph of a source or `.mkc` file as
+    /// Graphviz dot.
+    Cfg {
+        path: String,
+    },
+    /// Print the token stream (`--format text|json`).
+    Tokens {
+        path: String,
+        format: TokenFormat,
+    },
+    /// Print the parsed program (`--format text|sexpr|json|pretty`, the
+    /// last taking `--indent <n>` and `--width <n>`).
+    Ast {
+        path: String,
+        format: AstFormat,
+    },
+    /// Print the parsed program as JSON for external tools.
+    AstJson {
+        path: String,
+    },
+    /// Reformat source files in place, or with `check` only report the
+    /// ones that are not formatted.
+    Fmt {
+        paths: Vec<String>,
+        check: bool,
+    },
+    /// Parse and compile source files without running them, reporting
+    /// errors and warnings; with `deny_warnings`, warnings fail too.
+    Check {
+        paths: Vec<String>,
+        deny_warnings: bool,
+        error_format: ErrorFormat,
+    },
+    /// Run every `*_test.monkey` file under `paths` (default `.`).
+    Test {
+        paths: Vec<String>,
+    },
+    /// Print the extended description of a diagnostic code.
+    Explain {
+        code: String,
+    },
     Help,
 }
 
-pub fn parse_args(args: &[String]) -> Result<Command, ()> {
+pub fn parse_args(args: &[String]) -> Result<Command, UsageError> {
     match args {
-        [] => Ok(Command::Repl),
-        [one] if one == "repl" => Ok(Command::Repl),
+        [] => Ok(Command::Repl { no_color: false }),
+        [one] if one == "repl" => Ok(Command::Repl { no_color: false }),
+        [one] if one == "--no-color" => Ok(Command::Repl { no_color: true }),
+        [cmd, flag] if cmd == "repl" && flag == "--no-color" => {
+            Ok(Command::Repl { no_color: true })
+        }
         [one] if one == "--help" || one == "-h" => Ok(Command::Help),
-        [cmd, path] if cmd == "run" => Ok(Command::Run { path: path.clone() }),
-        [cmd, path] if cmd == "bench" => Ok(Command::Bench { path: path.clone() }),
-        [cmd, path] if cmd == "--tokens" => Ok(Command::Tokens { path: path.clone() }),
-        [cmd, path] if cmd == "--ast" => Ok(Command::Ast { path: path.clone() }),
-        _ => Err(()),
+        [cmd, rest @ ..] if cmd == "run" => match run_args(rest)? {
+            (_, _, flags) if flags.iterations.is_some() || flags.warmup.is_some() => {
+                Err(UsageError)
+            }
+            (path, args, flags) => Ok(Command::Run { path, args, flags }),
+        },
+        [cmd, rest @ ..] if cmd == "bench" => match run_args(rest)? {
+            (_, _, flags) if flags.watch => Err(UsageError),
+            (path, args, flags) => Ok(Command::Bench { path, args, flags }),
+        },
+        // The debugger reads its commands from stdin, so the program
+        // cannot come from there too.
+        [cmd, rest @ ..] if cmd == "debug" => match run_args(rest)? {
+            (path, args, flags) if flags == RunFlags::default() && path != STDIN_PATH => {
+                Ok(Command::Debug { path, args })
+            }
+            _ => Err(UsageError),
+        },
+        [cmd, rest @ ..] if cmd == "profile" => match run_args(rest)? {
+            (path, args, flags) if flags == RunFlags::default() => {
+                Ok(Command::Profile { path, args })
+            }
+            _ => Err(UsageError),
+        },
+        [cmd, rest @ ..] if cmd == "compile" => compile_args(rest),
+        [cmd, path] if cmd == "emit-c" => Ok(Command::EmitC {
+            path: path.clone(),
+            output: None,
+        }),
+        [cmd, path, flag, output] if cmd == "emit-c" && flag == "-o" => Ok(Command::EmitC {
+            path: path.clone(),
+            output: Some(output.clone()),
+        }),
+        [cmd, path] if cmd == "wasm" => Ok(Command::Wasm {
+            path: path.clone(),
+            output: None,
+        }),
+        [cmd, path, flag, output] if cmd == "wasm" && flag == "-o" => Ok(Command::Wasm {
+            path: path.clone(),
+            output: Some(output.clone()),
+        }),
+        [cmd, path] if cmd == "disasm" => Ok(Command::Disasm { path: path.clone() }),
+        [cmd, path] if cmd == "cfg" => Ok(Command::Cfg { path: path.clone() }),
+        [cmd, rest @ ..] if cmd == "fmt" => fmt_args(rest),
+        [cmd, rest @ ..] if cmd == "check" => check_args(rest),
+        [cmd, rest @ ..] if cmd == "test" => {
+            if rest.iter().any(|arg| arg.starts_with('-')) {
+                return Err(UsageError);
+            }
+            Ok(Command::Test {
+                paths: rest.to_vec(),
+            })
+        }
+        [cmd, rest @ ..] if cmd == "--tokens" => {
+            let (path, format) = path_and_format(rest)?;
+            let format = format.map_or(Some(TokenFormat::Text), TokenFormat::from_name);
+            Ok(Command::Tokens {
+                path,
+                format: format.ok_or(UsageError)?,
+            })
+        }
+        [cmd, rest @ ..] if cmd == "--ast" => ast_args(rest),
+        [cmd, path] if cmd == "--ast-json" => Ok(Command::AstJson { path: path.clone() }),
+        [cmd, code] if cmd == "explain" => Ok(Command::Explain { code: code.clone() }),
+        _ => Err(UsageError),
+    }
+}
+
+/// Parse `compile <path> [-o <out.mkc>] [--stats]`, in any order.
+fn compile_args(rest: &[String]) -> Result<Command, UsageError> {
+    let mut path = None;
+    let mut output = None;
+    let mut stats = false;
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-o" if output.is_none() => output = Some(iter.next().ok_or(UsageError)?.clone()),
+            "--stats" => stats = true,
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg.clone()),
+            _ => return Err(UsageError),
+        }
+    }
+    Ok(Command::Compile {
+        path: path.ok_or(UsageError)?,
+        output,
+        stats,
+    })
+}
+
+/// Parse `fmt [--check] <path>...`, in any order.
+fn fmt_args(rest: &[String]) -> Result<Command, UsageError> {
+    let mut paths = Vec::new();
+    let mut check = false;
+    for arg in rest {
+        match arg.as_str() {
+            "--check" => check = true,
+            _ if !arg.starts_with('-') => paths.push(arg.clone()),
+            _ => return Err(UsageError),
+        }
+    }
+    if paths.is_empty() {
+        return Err(UsageError);
+    }
+    Ok(Command::Fmt { paths, check })
+}
+
+/// Parse `check [--deny-warnings] [--error-format <name>] <path>...`, in
+/// any order.
+fn check_args(rest: &[String]) -> Result<Command, UsageError> {
+    let mut paths = Vec::new();
+    let mut deny_warnings = false;
+    let mut format = ErrorFormat::default();
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--deny-warnings" => deny_warnings = true,
+            "--error-format" => format = error_format(iter.next())?,
+            _ if !arg.starts_with('-') => paths.push(arg.clone()),
+            _ => return Err(UsageError),
+        }
+    }
+    if paths.is_empty() {
+        return Err(UsageError);
+    }
+    Ok(Command::Check {
+        paths,
+        deny_warnings,
+        error_format: format,
+    })
+}
+
+/// The value of `--error-format`.
+fn error_format(name: Option<&String>) -> Result<ErrorFormat, UsageError> {
+    name.and_then(|name| ErrorFormat::from_name(name))
+        .ok_or(UsageError)
+}
+
+/// Parse `--ast <path> [--format <name>] [--indent <n>] [--width <n>]`, in
+/// any order. `--indent` and `--width` need `--format pretty`.
+fn ast_args(rest: &[String]) -> Result<Command, UsageError> {
+    let mut layout = Vec::new();
+    let mut others = Vec::new();
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--indent" | "--width" => {
+                let value = iter
+                    .next()
+                    .ok_or(UsageError)?
+                    .parse()
+                    .map_err(|_| UsageError)?;
+                layout.push((arg.as_str(), value));
+            }
+            _ => others.push(arg.clone()),
+        }
+    }
+    let (path, format) = path_and_format(&others)?;
+    let mut format = format
+        .map_or(Some(AstFormat::Text), AstFormat::from_name)
+        .ok_or(UsageError)?;
+    for (flag, value) in layout {
+        let AstFormat::Pretty(options) = &mut format else {
+            return Err(UsageError);
+        };
+        *options = match flag {
+            "--indent" => options.with_indent_width(value),
+            _ => options.with_max_width(value),
+        };
+    }
+    Ok(Command::Ast { path, format })
+}
+
+/// Parse `<path> [--format <name>]`, in any order, for `--tokens` and
+/// `--ast`.
+fn path_and_format(rest: &[String]) -> Result<(String, Option<&str>), UsageError> {
+    let mut path = None;
+    let mut format = None;
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--format" if format.is_none() => {
+                format = Some(iter.next().ok_or(UsageError)?.as_str())
+            }
+            _ if path.is_none() && (arg == STDIN_PATH || !arg.starts_with('-')) => {
+                path = Some(arg.clone())
+            }
+            _ => return Err(UsageError),
+        }
+    }
+    Ok((path.ok_or(UsageError)?, format))
+}
+
+/// Parse `<path>` plus flags, followed by program arguments after `--`.
+fn run_args(rest: &[String]) -> Result<(String, Vec<String>, RunFlags), UsageError> {
+    let mut path = None;
+    let mut flags = RunFlags::default();
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--" => break,
+            "--trace" => flags.trace = true,
+            "--profile" => flags.profile = true,
+            "--no-fuse" => flags.no_fuse = true,
+            "--register" => flags.register = true,
+            "--warnings" => flags.warnings = true,
+            "--stats" => flags.stats = true,
+            "--coverage" => flags.coverage = true,
+            "--exit-with-result" => flags.exit_with_result = true,
+            "--error-format" => flags.error_format = error_format(iter.next())?,
+            "--watch" => flags.watch = true,
+            "--iterations" => {
+                let n = iter
+                    .next()
+                    .ok_or(UsageError)?
+                    .parse()
+                    .map_err(|_| UsageError)?;
+                if n == 0 {
+                    return Err(UsageError);
+                }
+                flags.iterations = Some(n);
+            }
+            "--warmup" => {
+                flags.warmup = Some(
+                    iter.next()
+                        .ok_or(UsageError)?
+                        .parse()
+                        .map_err(|_| UsageError)?,
+                )
+            }
+            "--timeout" => {
+                let ms = iter.next().ok_or(UsageError)?;
+                flags.timeout_ms = Some(ms.parse().map_err(|_| UsageError)?);
+            }
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg.clone()),
+            _ => return Err(UsageError),
+        }
+    }
+    let path = path.ok_or(UsageError)?;
+    // A program read from stdin has no file to watch.
+    if flags.watch && path == STDIN_PATH {
+        return Err(UsageError);
+    }
+    if flags.register
+        && (flags.timeout_ms.is_some() || flags.trace || flags.profile || flags.coverage)
+    {
+        return Err(UsageError);
     }
+    Ok((path, iter.cloned().collect(), flags))
 }
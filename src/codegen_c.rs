@@ -0,0 +1,429 @@
+//! C backend (`monkey emit-c`).
+//!
+//! Turns a chunk into a single C file: the mini-runtime in
+//! `codegen_c_runtime.c` followed by one C function per compiled function
+//! and one for the top-level code. Each instruction becomes a call into the
+//! runtime operating on a shared value stack, and jumps become `goto`s, so
+//! the program behaves like the VM: same output, same result line and the
+//! same first line for runtime errors (without the stack trace).
+//!
+//! Every code body is verified first: instructions must decode, jumps must
+//! land on instructions, operands must refer to existing constants, locals
+//! and builtins, and the stack depth must be consistent and never
+//! underflow. The maximum depth found this way sizes each call's stack
+//! check.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter, Result as FmtResult, Write};
+
+use crate::builtins::builtin_names;
+use crate::bytecode::{lookup_definition, position_at, read_operands, Chunk, Opcode, OperandKind};
+use crate::object::Object;
+use crate::position::Span;
+
+const RUNTIME: &str = include_str!("codegen_c_runtime.c");
+
+/// A code body that failed verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodegenError {
+    /// `<main>` for top-level code, otherwise the function's name.
+    pub function: String,
+    pub offset: usize,
+    pub message: String,
+}
+
+impl Display for CodegenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "{} at {:04}: {}",
+            self.function, self.offset, self.message
+        )
+    }
+}
+
+/// Generate a self-contained C program equivalent to `chunk`.
+pub fn emit_c(chunk: &Chunk) -> Result<String, CodegenError> {
+    let mut functions = Vec::new();
+    for (idx, constant) in chunk.constants.iter().enumerate() {
+        if let Object::CompiledFunction(function) = constant.as_ref() {
+            let body = Body {
+                name: function
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| "<anonymous>".to_string()),
+                code: &function.instructions,
+                positions: &function.positions,
+                locals: Some(function.num_locals),
+            };
+            let verified = verify(&body, chunk)?;
+            functions.push((idx, function, body, verified));
+        }
+    }
+    let main = Body {
+        name: "<main>".to_string(),
+        code: &chunk.instructions,
+        positions: &chunk.positions,
+        locals: None,
+    };
+    let main_verified = verify(&main, chunk)?;
+
+    let mut out = String::from("/* Generated by `monkey emit-c`. */\n");
+    out.push_str(RUNTIME);
+    out.push('\n');
+    if !chunk.constants.is_empty() {
+        let _ = writeln!(out, "static Value K[{}];\n", chunk.constants.len());
+    }
+    for (idx, function, _, verified) in &functions {
+        let name = match &function.name {
+            Some(name) => c_string(name),
+            None => "NULL".to_string(),
+        };
+        let _ = writeln!(out, "static void fn_{idx}(Value *bp, Closure *self);");
+        let _ = writeln!(
+            out,
+            "static const Function F_{idx} = {{{name}, {}, {}, {}, fn_{idx}}};",
+            function.num_params, function.num_locals, verified.max_stack
+        );
+    }
+
+    out.push_str("\nstatic void init_constants(void) {\n");
+    for (idx, constant) in chunk.constants.iter().enumerate() {
+        let value = match constant.as_ref() {
+            Object::Integer(v) => format!("mk_int(INT64_C({v}))"),
+            Object::Boolean(v) => format!("mk_bool({})", u8::from(*v)),
+            Object::String(v) => format!("mk_str({}, {})", c_string(v), v.len()),
+            Object::CompiledFunction(_) => format!("mk_func(&F_{idx})"),
+            _ => "MK_NULL".to_string(),
+        };
+        let _ = writeln!(out, "    K[{idx}] = {value};");
+    }
+    out.push_str("}\n");
+
+    for (idx, _, body, verified) in &functions {
+        let _ = writeln!(out, "\nstatic void fn_{idx}(Value *bp, Closure *self) {{");
+        out.push_str("    (void)bp;\n    (void)self;\n");
+        emit_body(&mut out, body, verified);
+        out.push_str("}\n");
+    }
+    out.push_str("\nstatic Value monkey_main(void) {\n");
+    emit_body(&mut out, &main, &main_verified);
+    out.push_str("}\n");
+    Ok(out)
+}
+
+struct Body<'a> {
+    name: String,
+    code: &'a [u8],
+    positions: &'a [(usize, Span)],
+    /// `None` for top-level code, which has no frame of its own.
+    locals: Option<usize>,
+}
+
+struct Instruction {
+    offset: usize,
+    op: Opcode,
+    operands: Vec<usize>,
+}
+
+struct Verified {
+    instructions: Vec<Instruction>,
+    /// Offsets some jump lands on.
+    targets: HashSet<usize>,
+    max_stack: usize,
+}
+
+fn verify(body: &Body, chunk: &Chunk) -> Result<Verified, CodegenError> {
+    let error = |offset: usize, message: String| CodegenError {
+        function: body.name.clone(),
+        offset,
+        message,
+    };
+
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    while offset < body.code.len() {
+        let byte = body.code[offset];
+        let op = Opcode::from_byte(byte)
+            .ok_or_else(|| error(offset, format!("unknown opcode {byte}")))?;
+        let (operands, width) = read_operands(lookup_definition(op), &body.code[offset + 1..])
+            .map_err(|_| error(offset, "truncated instruction".to_string()))?;
+        instructions.push(Instruction {
+            offset,
+            op,
+            operands,
+        });
+        offset += 1 + width;
+    }
+    let index_of: HashMap<usize, usize> = instructions
+        .iter()
+        .enumerate()
+        .map(|(idx, insn)| (insn.offset, idx))
+        .collect();
+
+    let mut targets = HashSet::new();
+    for insn in &instructions {
+        let operand = |at: usize| insn.operands[at];
+        let in_range = |value: usize, limit: usize, what: &str| {
+            if value < limit {
+                Ok(())
+            } else {
+                Err(error(insn.offset, format!("{what} {value} out of range")))
+            }
+        };
+        let kinds = lookup_definition(insn.op).operand_kinds;
+        for (&value, kind) in insn.operands.iter().zip(kinds) {
+            match kind {
+                OperandKind::JumpTarget => {
+                    if !index_of.contains_key(&value) {
+                        return Err(error(
+                            insn.offset,
+                            format!("jump target {value} is not an instruction"),
+                        ));
+                    }
+                    targets.insert(value);
+                }
+                OperandKind::ConstIndex => in_range(value, chunk.constants.len(), "constant")?,
+                OperandKind::LocalSlot => in_range(value, body.locals.unwrap_or(0), "local")?,
+                OperandKind::BuiltinIndex => in_range(value, builtin_names().len(), "builtin")?,
+                _ => {}
+            }
+        }
+        match insn.op {
+            Opcode::Closure => {
+                let is_function = matches!(
+                    chunk.constants.get(operand(0)).map(|c| c.as_ref()),
+                    Some(Object::CompiledFunction(_))
+                );
+                if !is_function {
+                    return Err(error(
+                        insn.offset,
+                        format!("constant {} is not a compiled function", operand(0)),
+                    ));
+                }
+            }
+            Opcode::GetFree | Opcode::CurrentClosure if body.locals.is_none() => {
+                return Err(error(
+                    insn.offset,
+                    "top-level code has no enclosing closure".to_string(),
+                ))
+            }
+            _ => {}
+        }
+    }
+
+    // Propagate stack depths along every path from the entry.
+    let mut depths: Vec<Option<usize>> = vec![None; instructions.len()];
+    let mut work = Vec::new();
+    let mut max_stack = 0;
+    if !instructions.is_empty() {
+        depths[0] = Some(0);
+        work.push(0);
+    }
+    while let Some(idx) = work.pop() {
+        let insn = &instructions[idx];
+        let depth = depths[idx].expect("queued instructions have a depth");
+        let (pops, pushes) = stack_effect(insn);
+        if depth < pops {
+            return Err(error(insn.offset, "stack underflow".to_string()));
+        }
+        let after = depth - pops + pushes;
+        max_stack = max_stack.max(depth).max(after);
+
+        let mut successors = Vec::new();
+        match insn.op {
+            Opcode::ReturnValue
+            | Opcode::Return
+            | Opcode::InvalidBreak
+            | Opcode::InvalidContinue => {}
+            Opcode::Jump => successors.push((index_of[&insn.operands[0]], after)),
+            Opcode::JumpIfFalse => {
+                successors.push((index_of[&insn.operands[0]], after));
+                successors.push((idx + 1, after));
+            }
+            Opcode::JumpIfFalsePop => {
+                successors.push((index_of[&insn.operands[0]], after));
+                successors.push((idx + 1, after - 1));
+            }
+            _ => successors.push((idx + 1, after)),
+        }
+        for (next, next_depth) in successors {
+            if next >= instructions.len() {
+                return Err(error(
+                    insn.offset,
+                    "execution runs past the end".to_string(),
+                ));
+            }
+            match depths[next] {
+                None => {
+                    depths[next] = Some(next_depth);
+                    work.push(next);
+                }
+                Some(known) if known != next_depth => {
+                    return Err(error(
+                        instructions[next].offset,
+                        format!("inconsistent stack depth ({known} and {next_depth})"),
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    if instructions.is_empty() {
+        return Err(error(0, "execution runs past the end".to_string()));
+    }
+
+    Ok(Verified {
+        instructions,
+        targets,
+        max_stack,
+    })
+}
+
+/// Values an instruction pops and pushes (on the fall-through path for
+/// `JumpIfFalsePop`, whose pop is handled by the caller).
+fn stack_effect(insn: &Instruction) -> (usize, usize) {
+    match insn.op {
+        Opcode::Constant
+        | Opcode::True
+        | Opcode::False
+        | Opcode::Null
+        | Opcode::GetGlobal
+        | Opcode::GetLocal
+        | Opcode::GetBuiltin
+        | Opcode::GetFree
+        | Opcode::CurrentClosure
+        | Opcode::ConstInt
+        | Opcode::Const0
+        | Opcode::Const1
+        | Opcode::AddConstants
+        | Opcode::AddLocals => (0, 1),
+        Opcode::Pop | Opcode::SetGlobal | Opcode::SetLocal | Opcode::ReturnValue => (1, 0),
+        Opcode::Add
+        | Opcode::Sub
+        | Opcode::Mul
+        | Opcode::Div
+        | Opcode::Eq
+        | Opcode::Ne
+        | Opcode::Lt
+        | Opcode::Gt
+        | Opcode::Le
+        | Opcode::Ge
+        | Opcode::Index => (2, 1),
+        Opcode::Neg | Opcode::Bang | Opcode::JumpIfFalse | Opcode::JumpIfFalsePop => (1, 1),
+        Opcode::Closure => (insn.operands[1], 1),
+        Opcode::Call => (insn.operands[0] + 1, 1),
+        Opcode::Array => (insn.operands[0], 1),
+        Opcode::Hash => (insn.operands[0] * 2, 1),
+        Opcode::Jump
+        | Opcode::Return
+        | Opcode::InvalidBreak
+        | Opcode::InvalidContinue
+        | Opcode::Nop => (0, 0),
+    }
+}
+
+fn emit_body(out: &mut String, body: &Body, verified: &Verified) {
+    let top_level = body.locals.is_none();
+    for insn in &verified.instructions {
+        if verified.targets.contains(&insn.offset) {
+            let _ = writeln!(out, "L_{}:;", insn.offset);
+        }
+        let pos = position_at(body.positions, insn.offset).unwrap_or_default();
+        let at = format!("{}, {}", pos.line, pos.col);
+        let operand = |idx: usize| insn.operands[idx];
+        let statement = match insn.op {
+            Opcode::Constant => format!("PUSH(K[{}]);", operand(0)),
+            Opcode::True => "PUSH(mk_bool(1));".to_string(),
+            Opcode::False => "PUSH(mk_bool(0));".to_string(),
+            Opcode::Null => "PUSH(MK_NULL);".to_string(),
+            Opcode::Const0 => "PUSH(mk_int(0));".to_string(),
+            Opcode::Const1 => "PUSH(mk_int(1));".to_string(),
+            Opcode::ConstInt => format!("PUSH(mk_int({}));", operand(0) as u16 as i16),
+            Opcode::Pop => "sp--;".to_string(),
+            Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div => {
+                let op = [Opcode::Add, Opcode::Sub, Opcode::Mul, Opcode::Div]
+                    .iter()
+                    .position(|op| *op == insn.op)
+                    .unwrap_or(0);
+                format!("op_arith({op}, {at});")
+            }
+            Opcode::Eq | Opcode::Ne | Opcode::Lt | Opcode::Gt | Opcode::Le | Opcode::Ge => {
+                let op = [
+                    Opcode::Eq,
+                    Opcode::Ne,
+                    Opcode::Lt,
+                    Opcode::Gt,
+                    Opcode::Le,
+                    Opcode::Ge,
+                ]
+                .iter()
+                .position(|op| *op == insn.op)
+                .unwrap_or(0);
+                format!("op_compare({op}, {at});")
+            }
+            Opcode::Neg => format!("op_neg({at});"),
+            Opcode::Bang => "op_bang();".to_string(),
+            Opcode::Jump => format!("goto L_{};", operand(0)),
+            Opcode::JumpIfFalse => format!("if (!truthy(sp[-1])) goto L_{};", operand(0)),
+            Opcode::JumpIfFalsePop => {
+                format!("if (!truthy(sp[-1])) goto L_{}; sp--;", operand(0))
+            }
+            Opcode::GetGlobal => format!("PUSH(mk_globals[{}]);", operand(0)),
+            Opcode::SetGlobal => format!("mk_globals[{}] = *--sp;", operand(0)),
+            Opcode::GetLocal => format!("PUSH(bp[{}]);", operand(0)),
+            Opcode::SetLocal => format!("bp[{}] = *--sp;", operand(0)),
+            Opcode::GetBuiltin => format!("PUSH(mk_builtin({}));", operand(0)),
+            Opcode::GetFree => format!("PUSH(self->free[{}]);", operand(0)),
+            Opcode::Closure => format!("op_closure(&F_{}, {});", operand(0), operand(1)),
+            Opcode::CurrentClosure => {
+                "PUSH(((Value){.tag = T_CLOSURE, .as.c = self}));".to_string()
+            }
+            Opcode::Call => format!("op_call({}, {at});", operand(0)),
+            Opcode::ReturnValue if top_level => "return sp[-1];".to_string(),
+            Opcode::ReturnValue => "bp[-1] = sp[-1]; sp = bp; return;".to_string(),
+            Opcode::Return if top_level => "return MK_NULL;".to_string(),
+            Opcode::Return => "bp[-1] = MK_NULL; sp = bp; return;".to_string(),
+            Opcode::Array => format!("op_array({});", operand(0)),
+            Opcode::Hash => format!("op_hash({}, {at});", operand(0)),
+            Opcode::Index => format!("op_index({at});"),
+            Opcode::InvalidBreak => format!("op_invalid(\"break\", {at});"),
+            Opcode::InvalidContinue => format!("op_invalid(\"continue\", {at});"),
+            Opcode::Nop => continue,
+            Opcode::AddConstants => format!(
+                "PUSH(K[{}]); PUSH(K[{}]); op_arith(0, {at});",
+                operand(0),
+                operand(1)
+            ),
+            Opcode::AddLocals => format!(
+                "PUSH(bp[{}]); PUSH(bp[{}]); op_arith(0, {at});",
+                operand(0),
+                operand(1)
+            ),
+        };
+        let _ = writeln!(
+            out,
+            "    {statement} /* {:04} {} */",
+            insn.offset,
+            lookup_definition(insn.op).name
+        );
+    }
+}
+
+/// A C string literal with every byte outside printable ASCII escaped.
+fn c_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for byte in value.bytes() {
+        match byte {
+            b'"' | b'\\' | b'?' => {
+                let _ = write!(out, "\\{:03o}", byte);
+            }
+            0x20..=0x7e => out.push(byte as char),
+            _ => {
+                let _ = write!(out, "\\{:03o}", byte);
+            }
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,577 @@
+/* Mini-runtime for C emitted by `monkey emit-c`. Memory is never freed. */
+#include <inttypes.h>
+#include <stdarg.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#if defined(__GNUC__)
+/* Programs only use some of the helpers below. */
+#pragma GCC diagnostic ignored "-Wunused-function"
+#endif
+
+#define MK_STACK_SIZE (1 << 20)
+#define MK_MAX_DEPTH 10000
+#define MK_GLOBALS 65536
+
+typedef enum { T_INT, T_BOOL, T_NULL, T_STR, T_ARRAY, T_HASH, T_FUNC, T_CLOSURE, T_BUILTIN } Tag;
+
+struct Str;
+struct Array;
+struct Hash;
+struct Function;
+struct Closure;
+
+typedef struct {
+    Tag tag;
+    union {
+        int64_t i;
+        int b;
+        struct Str *s;
+        struct Array *a;
+        struct Hash *h;
+        const struct Function *f;
+        struct Closure *c;
+        int builtin;
+    } as;
+} Value;
+
+typedef struct Str {
+    size_t len;
+    char data[];
+} Str;
+
+typedef struct Array {
+    size_t len;
+    Value items[];
+} Array;
+
+typedef struct Hash {
+    size_t len, cap;
+    Value *keys, *vals;
+    /* Open-addressing table of pair slots (SIZE_MAX = empty), 2 * cap long. */
+    size_t *index;
+} Hash;
+
+typedef struct Function {
+    const char *name;
+    int num_params, num_locals, max_stack;
+    void (*code)(Value *bp, struct Closure *self);
+} Function;
+
+typedef struct Closure {
+    const Function *fn;
+    size_t nfree;
+    Value free[];
+} Closure;
+
+static Value mk_stack[MK_STACK_SIZE];
+static Value *sp = mk_stack;
+static Value mk_globals[MK_GLOBALS];
+static int mk_depth = 1;
+static int mk_argc;
+static char **mk_argv;
+
+static const char *const BUILTIN_NAMES[] = {
+    "len", "first", "last", "rest", "push", "puts", "eval", "args", "substr", "char_at", "chars", "eq", "assert",
+};
+
+#define PUSH(v) (*sp++ = (v))
+#define MK_NULL ((Value){.tag = T_NULL})
+
+static void *mk_alloc(size_t size) {
+    void *p = malloc(size ? size : 1);
+    if (!p) {
+        fprintf(stderr, "out of memory\n");
+        exit(1);
+    }
+    return p;
+}
+
+static void mk_error(const char *type, int line, int col, const char *fmt, ...) {
+    va_list ap;
+    fflush(stdout);
+    fprintf(stderr, "Runtime error:\nError[%s] at %d:%d: ", type, line, col);
+    va_start(ap, fmt);
+    vfprintf(stderr, fmt, ap);
+    va_end(ap);
+    fputc('\n', stderr);
+    exit(1);
+}
+
+static Value mk_int(int64_t i) { return (Value){.tag = T_INT, .as.i = i}; }
+static Value mk_bool(int b) { return (Value){.tag = T_BOOL, .as.b = b != 0}; }
+
+static Value mk_str(const char *data, size_t len) {
+    Str *s = mk_alloc(sizeof(Str) + len + 1);
+    s->len = len;
+    memcpy(s->data, data, len);
+    s->data[len] = 0;
+    return (Value){.tag = T_STR, .as.s = s};
+}
+
+static Value mk_func(const Function *f) { return (Value){.tag = T_FUNC, .as.f = f}; }
+static Value mk_builtin(int index) { return (Value){.tag = T_BUILTIN, .as.builtin = index}; }
+
+static Array *mk_array_alloc(size_t len) {
+    Array *a = mk_alloc(sizeof(Array) + len * sizeof(Value));
+    a->len = len;
+    return a;
+}
+
+static Value mk_array_value(Array *a) { return (Value){.tag = T_ARRAY, .as.a = a}; }
+
+static int truthy(Value v) { return !(v.tag == T_NULL || (v.tag == T_BOOL && !v.as.b)); }
+
+static const char *type_name(Value v) {
+    switch (v.tag) {
+    case T_INT: return "INTEGER";
+    case T_BOOL: return "BOOLEAN";
+    case T_NULL: return "NULL";
+    case T_STR: return "STRING";
+    case T_ARRAY: return "ARRAY";
+    case T_HASH: return "HASH";
+    case T_FUNC: return "FUNCTION";
+    case T_CLOSURE: return "CLOSURE";
+    case T_BUILTIN: return "BUILTIN";
+    }
+    return "UNKNOWN";
+}
+
+/* ---- strings ---- */
+
+static size_t utf8_width(unsigned char lead) {
+    if (lead < 0x80) return 1;
+    if ((lead >> 5) == 0x6) return 2;
+    if ((lead >> 4) == 0xe) return 3;
+    return 4;
+}
+
+static size_t utf8_count(const Str *s) {
+    size_t n = 0;
+    for (size_t i = 0; i < s->len; i += utf8_width((unsigned char)s->data[i])) n++;
+    return n;
+}
+
+/* Byte offset of character `index` (or s->len when past the end). */
+static size_t utf8_offset(const Str *s, size_t index) {
+    size_t i = 0;
+    while (i < s->len && index > 0) {
+        i += utf8_width((unsigned char)s->data[i]);
+        index--;
+    }
+    return i < s->len ? i : s->len;
+}
+
+static int str_eq(const Str *a, const Str *b) {
+    return a->len == b->len && memcmp(a->data, b->data, a->len) == 0;
+}
+
+typedef struct {
+    char *data;
+    size_t len, cap;
+} Buf;
+
+static void buf_put(Buf *b, const char *data, size_t len) {
+    if (b->len + len + 1 > b->cap) {
+        b->cap = (b->len + len + 1) * 2;
+        b->data = realloc(b->data, b->cap);
+        if (!b->data) {
+            fprintf(stderr, "out of memory\n");
+            exit(1);
+        }
+    }
+    memcpy(b->data + b->len, data, len);
+    b->len += len;
+    b->data[b->len] = 0;
+}
+
+static void buf_puts(Buf *b, const char *s) { buf_put(b, s, strlen(s)); }
+
+static void inspect(Buf *b, Value v) {
+    char tmp[32];
+    switch (v.tag) {
+    case T_INT:
+        snprintf(tmp, sizeof tmp, "%" PRId64, v.as.i);
+        buf_puts(b, tmp);
+        break;
+    case T_BOOL: buf_puts(b, v.as.b ? "true" : "false"); break;
+    case T_NULL: buf_puts(b, "null"); break;
+    case T_STR: buf_put(b, v.as.s->data, v.as.s->len); break;
+    case T_ARRAY:
+        buf_puts(b, "[");
+        for (size_t i = 0; i < v.as.a->len; i++) {
+            if (i) buf_puts(b, ", ");
+            inspect(b, v.as.a->items[i]);
+        }
+        buf_puts(b, "]");
+        break;
+    case T_HASH:
+        buf_puts(b, "{");
+        for (size_t i = 0; i < v.as.h->len; i++) {
+            if (i) buf_puts(b, ", ");
+            inspect(b, v.as.h->keys[i]);
+            buf_puts(b, ": ");
+            inspect(b, v.as.h->vals[i]);
+        }
+        buf_puts(b, "}");
+        break;
+    case T_FUNC:
+        if (v.as.f->name) {
+            buf_puts(b, "<compiled fn:");
+            buf_puts(b, v.as.f->name);
+            buf_puts(b, ">");
+        } else {
+            buf_puts(b, "<compiled fn>");
+        }
+        break;
+    case T_CLOSURE: buf_puts(b, "<closure>"); break;
+    case T_BUILTIN:
+        buf_puts(b, "<builtin: ");
+        buf_puts(b, BUILTIN_NAMES[v.as.builtin]);
+        buf_puts(b, ">");
+        break;
+    }
+}
+
+/* ---- hashes ---- */
+
+static int hashable(Value v) { return v.tag == T_INT || v.tag == T_BOOL || v.tag == T_STR; }
+
+static uint64_t hash_of(Value v) {
+    uint64_t h = 1469598103934665603ull ^ (uint64_t)v.tag;
+    if (v.tag == T_STR) {
+        for (size_t i = 0; i < v.as.s->len; i++) h = (h ^ (unsigned char)v.as.s->data[i]) * 1099511628211ull;
+    } else {
+        uint64_t x = v.tag == T_INT ? (uint64_t)v.as.i : (uint64_t)v.as.b;
+        for (int i = 0; i < 8; i++) h = (h ^ ((x >> (i * 8)) & 0xff)) * 1099511628211ull;
+    }
+    return h;
+}
+
+static int key_eq(Value a, Value b) {
+    if (a.tag != b.tag) return 0;
+    switch (a.tag) {
+    case T_INT: return a.as.i == b.as.i;
+    case T_BOOL: return a.as.b == b.as.b;
+    case T_STR: return str_eq(a.as.s, b.as.s);
+    default: return 0;
+    }
+}
+
+/* Slot of `key` in the index table: either its pair's entry or an empty one. */
+static size_t *hash_find(Hash *h, Value key) {
+    size_t mask = h->cap * 2 - 1;
+    size_t i = (size_t)hash_of(key) & mask;
+    while (h->index[i] != SIZE_MAX && !key_eq(h->keys[h->index[i]], key)) i = (i + 1) & mask;
+    return &h->index[i];
+}
+
+static Hash *hash_new(size_t cap) {
+    size_t c = 4;
+    while (c < cap) c *= 2;
+    Hash *h = mk_alloc(sizeof(Hash));
+    h->len = 0;
+    h->cap = c;
+    h->keys = mk_alloc(c * sizeof(Value));
+    h->vals = mk_alloc(c * sizeof(Value));
+    h->index = mk_alloc(c * 2 * sizeof(size_t));
+    for (size_t i = 0; i < c * 2; i++) h->index[i] = SIZE_MAX;
+    return h;
+}
+
+static void hash_insert(Hash *h, Value key, Value val) {
+    size_t *slot = hash_find(h, key);
+    if (*slot != SIZE_MAX) {
+        h->vals[*slot] = val;
+        return;
+    }
+    *slot = h->len;
+    h->keys[h->len] = key;
+    h->vals[h->len] = val;
+    h->len++;
+}
+
+static Value *hash_get(Hash *h, Value key) {
+    size_t *slot = hash_find(h, key);
+    return *slot == SIZE_MAX ? NULL : &h->vals[*slot];
+}
+
+static int deep_eq(Value a, Value b) {
+    if (a.tag != b.tag) return 0;
+    switch (a.tag) {
+    case T_INT: return a.as.i == b.as.i;
+    case T_BOOL: return a.as.b == b.as.b;
+    case T_NULL: return 1;
+    case T_STR: return str_eq(a.as.s, b.as.s);
+    case T_ARRAY:
+        if (a.as.a->len != b.as.a->len) return 0;
+        for (size_t i = 0; i < a.as.a->len; i++)
+            if (!deep_eq(a.as.a->items[i], b.as.a->items[i])) return 0;
+        return 1;
+    case T_HASH:
+        if (a.as.h->len != b.as.h->len) return 0;
+        for (size_t i = 0; i < a.as.h->len; i++) {
+            Value *other = hash_get(b.as.h, a.as.h->keys[i]);
+            if (!other || !deep_eq(a.as.h->vals[i], *other)) return 0;
+        }
+        return 1;
+    case T_FUNC: return a.as.f == b.as.f;
+    case T_CLOSURE: return a.as.c == b.as.c;
+    case T_BUILTIN: return a.as.builtin == b.as.builtin;
+    }
+    return 0;
+}
+
+/* ---- operators ---- */
+
+static const char *const ARITH_NAMES[] = {"Add", "Sub", "Mul", "Div"};
+static const char *const COMPARE_NAMES[] = {"Eq", "Ne", "Lt", "Gt", "Le", "Ge"};
+
+/* `op` indexes ARITH_NAMES. Integer arithmetic wraps like the VM's release build. */
+static void op_arith(int op, int line, int col) {
+    Value r = *--sp, l = *--sp;
+    if (l.tag == T_INT && r.tag == T_INT) {
+        uint64_t a = (uint64_t)l.as.i, b = (uint64_t)r.as.i;
+        switch (op) {
+        case 0: PUSH(mk_int((int64_t)(a + b))); return;
+        case 1: PUSH(mk_int((int64_t)(a - b))); return;
+        case 2: PUSH(mk_int((int64_t)(a * b))); return;
+        default:
+            if (r.as.i == 0) mk_error("DIVISION_BY_ZERO", line, col, "division by zero");
+            PUSH(mk_int(r.as.i == -1 ? (int64_t)(0 - a) : l.as.i / r.as.i));
+            return;
+        }
+    }
+    if (l.tag == T_STR && r.tag == T_STR) {
+        if (op != 0) mk_error("UNSUPPORTED_OPERATION", line, col, "unsupported string operation: %s", ARITH_NAMES[op]);
+        Str *s = mk_alloc(sizeof(Str) + l.as.s->len + r.as.s->len + 1);
+        s->len = l.as.s->len + r.as.s->len;
+        memcpy(s->data, l.as.s->data, l.as.s->len);
+        memcpy(s->data + l.as.s->len, r.as.s->data, r.as.s->len);
+        s->data[s->len] = 0;
+        PUSH(((Value){.tag = T_STR, .as.s = s}));
+        return;
+    }
+    mk_error("TYPE_MISMATCH", line, col, "unsupported operand types for %s: %s and %s", ARITH_NAMES[op], type_name(l),
+             type_name(r));
+}
+
+/* `op` indexes COMPARE_NAMES. */
+static void op_compare(int op, int line, int col) {
+    Value r = *--sp, l = *--sp;
+    int result;
+    if (l.tag == T_INT && r.tag == T_INT) {
+        int64_t a = l.as.i, b = r.as.i;
+        int results[] = {a == b, a != b, a < b, a > b, a <= b, a >= b};
+        result = results[op];
+    } else if (l.tag == T_BOOL && r.tag == T_BOOL && op < 2) {
+        result = (l.as.b == r.as.b) == (op == 0);
+    } else if (l.tag == T_NULL && r.tag == T_NULL && op < 2) {
+        result = op == 0;
+    } else if (l.tag == T_STR && r.tag == T_STR) {
+        if (op >= 2) mk_error("UNSUPPORTED_OPERATION", line, col, "unsupported string operation: %s", COMPARE_NAMES[op]);
+        result = str_eq(l.as.s, r.as.s) == (op == 0);
+    } else {
+        mk_error("TYPE_MISMATCH", line, col, "unsupported operand types for %s: %s and %s", COMPARE_NAMES[op],
+                 type_name(l), type_name(r));
+        return;
+    }
+    PUSH(mk_bool(result));
+}
+
+static void op_neg(int line, int col) {
+    Value v = sp[-1];
+    if (v.tag == T_INT) {
+        sp[-1] = mk_int((int64_t)(0 - (uint64_t)v.as.i));
+    } else if (v.tag != T_NULL) {
+        mk_error("TYPE_MISMATCH", line, col, "unsupported operand type for -: %s", type_name(v));
+    }
+}
+
+static void op_bang(void) { sp[-1] = mk_bool(!truthy(sp[-1])); }
+
+static void op_array(size_t n) {
+    Array *a = mk_array_alloc(n);
+    sp -= n;
+    memcpy(a->items, sp, n * sizeof(Value));
+    PUSH(mk_array_value(a));
+}
+
+static void op_hash(size_t pairs, int line, int col) {
+    Hash *h = hash_new(pairs);
+    sp -= pairs * 2;
+    for (size_t i = 0; i < pairs; i++) {
+        Value key = sp[i * 2];
+        if (!hashable(key)) mk_error("UNHASHABLE", line, col, "unusable as hash key: %s", type_name(key));
+        hash_insert(h, key, sp[i * 2 + 1]);
+    }
+    PUSH(((Value){.tag = T_HASH, .as.h = h}));
+}
+
+static void op_index(int line, int col) {
+    Value index = *--sp, left = *--sp;
+    if (left.tag == T_ARRAY) {
+        if (index.tag != T_INT) mk_error("INVALID_INDEX", line, col, "array index must be INTEGER, got %s", type_name(index));
+        int ok = index.as.i >= 0 && (uint64_t)index.as.i < left.as.a->len;
+        PUSH(ok ? left.as.a->items[index.as.i] : MK_NULL);
+    } else if (left.tag == T_HASH) {
+        if (!hashable(index)) mk_error("UNHASHABLE", line, col, "unusable as hash key: %s", type_name(index));
+        Value *found = hash_get(left.as.h, index);
+        PUSH(found ? *found : MK_NULL);
+    } else {
+        mk_error("INVALID_INDEX", line, col, "index operator not supported: %s", type_name(left));
+    }
+}
+
+static void op_closure(const Function *fn, size_t nfree) {
+    Closure *c = mk_alloc(sizeof(Closure) + nfree * sizeof(Value));
+    c->fn = fn;
+    c->nfree = nfree;
+    sp -= nfree;
+    memcpy(c->free, sp, nfree * sizeof(Value));
+    PUSH(((Value){.tag = T_CLOSURE, .as.c = c}));
+}
+
+/* ---- builtins ---- */
+
+static void wrong_args(const char *name, int expected, int got, int line, int col) {
+    mk_error("WRONG_ARGUMENT_COUNT", line, col, "%s expected %d argument(s), got %d", name, expected, got);
+}
+
+static void bad_arg(const char *name, const char *expected, Value got, int line, int col) {
+    mk_error("INVALID_ARGUMENT_TYPE", line, col, "%s expected %s, got %s", name, expected, type_name(got));
+}
+
+static Value call_builtin(int index, Value *args, int argc, int line, int col) {
+    const char *name = BUILTIN_NAMES[index];
+    static const int arity[] = {1, 1, 1, 1, 2, -1, 1, 0, 3, 2, 1, 2, -1};
+    if (arity[index] >= 0 && argc != arity[index]) wrong_args(name, arity[index], argc, line, col);
+    if (index == 12 && (argc < 1 || argc > 2)) wrong_args(name, 1, argc, line, col);
+    switch (index) {
+    case 0: /* len */
+        if (args[0].tag == T_STR) return mk_int((int64_t)utf8_count(args[0].as.s));
+        if (args[0].tag == T_ARRAY) return mk_int((int64_t)args[0].as.a->len);
+        bad_arg(name, "STRING or ARRAY", args[0], line, col);
+        break;
+    case 1: /* first */
+    case 2: /* last */
+    case 3: /* rest */
+    case 4: { /* push */
+        if (args[0].tag != T_ARRAY) bad_arg(name, "ARRAY", args[0], line, col);
+        Array *a = args[0].as.a;
+        if (index == 1) return a->len ? a->items[0] : MK_NULL;
+        if (index == 2) return a->len ? a->items[a->len - 1] : MK_NULL;
+        if (index == 3) {
+            if (!a->len) return MK_NULL;
+            Array *out = mk_array_alloc(a->len - 1);
+            memcpy(out->items, a->items + 1, (a->len - 1) * sizeof(Value));
+            return mk_array_value(out);
+        }
+        Array *out = mk_array_alloc(a->len + 1);
+        memcpy(out->items, a->items, a->len * sizeof(Value));
+        out->items[a->len] = args[1];
+        return mk_array_value(out);
+    }
+    case 5: { /* puts */
+        Buf b = {0};
+        buf_puts(&b, "");
+        for (int i = 0; i < argc; i++) inspect(&b, args[i]);
+        puts(b.data);
+        free(b.data);
+        return MK_NULL;
+    }
+    case 6: /* eval */
+        mk_error("UNSUPPORTED_OPERATION", line, col, "eval is not supported in generated C");
+        break;
+    case 7: { /* args */
+        Array *out = mk_array_alloc((size_t)mk_argc);
+        for (int i = 0; i < mk_argc; i++) out->items[i] = mk_str(mk_argv[i], strlen(mk_argv[i]));
+        return mk_array_value(out);
+    }
+    case 8: /* substr */
+    case 9: /* char_at */
+    case 10: { /* chars */
+        if (args[0].tag != T_STR) bad_arg(name, "STRING", args[0], line, col);
+        for (int i = 1; i < argc; i++)
+            if (args[i].tag != T_INT) bad_arg(name, "INTEGER", args[i], line, col);
+        Str *s = args[0].as.s;
+        if (index == 10) {
+            Array *out = mk_array_alloc(utf8_count(s));
+            size_t at = 0;
+            for (size_t i = 0; i < out->len; i++) {
+                size_t w = utf8_width((unsigned char)s->data[at]);
+                out->items[i] = mk_str(s->data + at, w);
+                at += w;
+            }
+            return mk_array_value(out);
+        }
+        int64_t start = args[1].as.i;
+        int64_t len = index == 8 ? args[2].as.i : 1;
+        if (start < 0 || len < 0) return MK_NULL;
+        if (index == 9 && (uint64_t)start >= utf8_count(s)) return MK_NULL;
+        size_t from = utf8_offset(s, (size_t)start);
+        size_t to = from;
+        while (to < s->len && len-- > 0) to += utf8_width((unsigned char)s->data[to]);
+        return mk_str(s->data + from, (to < s->len ? to : s->len) - from);
+    }
+    case 11: /* eq */
+        return mk_bool(deep_eq(args[0], args[1]));
+    case 12: { /* assert */
+        if (truthy(args[0])) return MK_NULL;
+        if (argc == 1) mk_error("ASSERTION_FAILED", line, col, "assertion failed");
+        Buf b = {0};
+        buf_puts(&b, "");
+        inspect(&b, args[1]);
+        mk_error("ASSERTION_FAILED", line, col, "assertion failed: %s", b.data);
+        break;
+    }
+    }
+    return MK_NULL;
+}
+
+/* ---- calls ---- */
+
+static void op_call(int argc, int line, int col) {
+    Value callee = sp[-1 - argc];
+    if (callee.tag == T_BUILTIN) {
+        Value result = call_builtin(callee.as.builtin, sp - argc, argc, line, col);
+        sp -= argc + 1;
+        PUSH(result);
+        return;
+    }
+    if (callee.tag != T_CLOSURE) mk_error("NOT_CALLABLE", line, col, "object is not callable: %s", type_name(callee));
+    Closure *c = callee.as.c;
+    const Function *fn = c->fn;
+    if (argc != fn->num_params)
+        wrong_args(fn->name ? fn->name : "<anonymous>", fn->num_params, argc, line, col);
+    Value *bp = sp - argc;
+    if (mk_depth >= MK_MAX_DEPTH || bp + fn->num_locals + fn->max_stack >= mk_stack + MK_STACK_SIZE)
+        mk_error("STACK_OVERFLOW", line, col, "maximum call depth of %d exceeded", MK_MAX_DEPTH);
+    for (Value *slot = sp; slot < bp + fn->num_locals; slot++) *slot = MK_NULL;
+    sp = bp + fn->num_locals;
+    mk_depth++;
+    fn->code(bp, c);
+    mk_depth--;
+}
+
+static void op_invalid(const char *what, int line, int col) {
+    mk_error("INVALID_CONTROL_FLOW", line, col, "%s used outside of loop", what);
+}
+
+static Value monkey_main(void);
+static void init_constants(void);
+
+int main(int argc, char **argv) {
+    mk_argc = argc - 1;
+    mk_argv = argv + 1;
+    for (int i = 0; i < MK_GLOBALS; i++) mk_globals[i] = MK_NULL;
+    init_constants();
+    Value result = monkey_main();
+    Buf b = {0};
+    buf_puts(&b, "");
+    inspect(&b, result);
+    puts(b.data);
+    return 0;
+}
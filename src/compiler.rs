@@ -1,29 +1,103 @@
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter, Result as FmtResult};
-use std::rc::Rc;
 
 use crate::ast::{BlockStatement, Expression, Identifier, Program, Statement};
 use crate::bytecode::{make, BytecodeError, Chunk, Opcode};
-use crate::object::{CompiledFunctionObject, Object};
-use crate::position::Position;
-use crate::symbol_table::{define_builtins, Symbol, SymbolScope, SymbolTable, SymbolTableRef};
+use crate::object::{CompiledFunctionObject, HashKey, Object, Shared};
+use crate::peephole::optimize_scope;
+use crate::position::{Position, Span};
+use crate::source::snippet;
+use crate::symbol_table::{
+    define_builtins, Symbol, SymbolScope, SymbolTable, SymbolTableRef, BUILTIN_NAMES,
+};
+use crate::token::similar_keyword;
+
+/// Compile error categories, each with a stable code for `monkey explain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompileErrorKind {
+    UnresolvedIdentifier,
+    UnsupportedOperator,
+    UnsupportedByBackend,
+    BackendTypeMismatch,
+    /// An invariant of the compiler itself broke; never caused by the
+    /// program alone.
+    Internal,
+    OutOfScope,
+    Redefinition,
+}
+
+impl CompileErrorKind {
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            CompileErrorKind::UnresolvedIdentifier => "C001",
+            CompileErrorKind::UnsupportedOperator => "C002",
+            CompileErrorKind::UnsupportedByBackend => "C003",
+            CompileErrorKind::BackendTypeMismatch => "C004",
+            CompileErrorKind::Internal => "C005",
+            CompileErrorKind::OutOfScope => "C006",
+            CompileErrorKind::Redefinition => "C007",
+        }
+    }
+}
 
 /// Deterministic compile-time error for unsupported or invalid compiler input.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CompileError {
+    pub kind: CompileErrorKind,
     pub message: String,
     pub pos: Option<Position>,
 }
 
 impl CompileError {
+    /// An `Internal` error; use `with_kind` for anything the program
+    /// itself caused.
     pub fn new(message: impl Into<String>, pos: Option<Position>) -> Self {
         Self {
+            kind: CompileErrorKind::Internal,
             message: message.into(),
             pos,
         }
     }
 
-    fn unresolved_identifier(name: &str, pos: Position) -> Self {
-        Self::new(format!("unresolved identifier: {name}"), Some(pos))
+    pub fn with_kind(mut self, kind: CompileErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn error_code(&self) -> &'static str {
+        self.kind.error_code()
+    }
+
+    /// The error tagged with its code and followed by the offending line of
+    /// `source`, underlined, when the error has a position.
+    pub fn render_with_source(&self, source: &str) -> String {
+        let line = format!("{self} [{}]", self.error_code());
+        match self.pos.and_then(|pos| snippet(source, pos)) {
+            Some(snippet) => format!("{line}\n{snippet}"),
+            None => line,
+        }
+    }
+
+    /// `suggestions` are appended as a "did you mean" hint.
+    fn unresolved_identifier(name: &str, pos: Position, suggestions: &[String]) -> Self {
+        let mut message = format!("unresolved identifier: {name}");
+        if let Some((last, rest)) = suggestions.split_last() {
+            let mut names = rest.join(", ");
+            if !names.is_empty() {
+                names.push_str(" or ");
+            }
+            names.push_str(last);
+            message.push_str(&format!(" (did you mean {names}?)"));
+        }
+        Self::new(message, Some(pos)).with_kind(CompileErrorKind::UnresolvedIdentifier)
+    }
+
+    fn out_of_scope(name: &str, pos: Position) -> Self {
+        Self::new(
+            format!("{name} is out of scope: it is only defined inside an earlier block"),
+            Some(pos),
+        )
+        .with_kind(CompileErrorKind::OutOfScope)
     }
 }
 
@@ -36,6 +110,85 @@ impl Display for CompileError {
     }
 }
 
+/// Warning categories, each with a stable code for `monkey explain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompileWarningKind {
+    UnusedVariable,
+    UnusedParameter,
+    UnreachableCode,
+    Redefinition,
+    ShadowedBuiltin,
+}
+
+impl CompileWarningKind {
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            CompileWarningKind::UnusedVariable => "W001",
+            CompileWarningKind::UnusedParameter => "W002",
+            CompileWarningKind::UnreachableCode => "W003",
+            CompileWarningKind::Redefinition => "W004",
+            CompileWarningKind::ShadowedBuiltin => "W005",
+        }
+    }
+}
+
+/// Non-fatal diagnostic reported while compiling; see `Compiler::warnings`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileWarning {
+    pub kind: CompileWarningKind,
+    pub message: String,
+    pub pos: Position,
+}
+
+impl CompileWarning {
+    pub fn new(kind: CompileWarningKind, message: impl Into<String>, pos: Position) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            pos,
+        }
+    }
+
+    pub fn error_code(&self) -> &'static str {
+        self.kind.error_code()
+    }
+}
+
+impl Display for CompileWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}: {} [{}]", self.pos, self.message, self.error_code())
+    }
+}
+
+/// How a `let` of a name already defined in the same block is reported.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RedefinitionPolicy {
+    #[default]
+    Warn,
+    Error,
+}
+
+/// Settings that change the bytecode the compiler emits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompilerOptions {
+    /// Run `peephole::optimize_scope` over each scope once it is finished,
+    /// removing redundant instruction sequences.
+    pub optimize: bool,
+    pub redefinition: RedefinitionPolicy,
+}
+
+impl CompilerOptions {
+    pub fn with_optimize(mut self, optimize: bool) -> Self {
+        self.optimize = optimize;
+        self
+    }
+
+    pub fn with_redefinition(mut self, redefinition: RedefinitionPolicy) -> Self {
+        self.redefinition = redefinition;
+        self
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct EmittedInstruction {
     opcode: Opcode,
@@ -50,47 +203,95 @@ struct LoopContext {
     loop_pos: Position,
 }
 
+/// A `let` name or parameter, remembered so it can be reported if nothing
+/// reads it.
+#[derive(Debug, Clone)]
+struct Binding {
+    name: String,
+    pos: Position,
+    parameter: bool,
+}
+
 #[derive(Debug, Clone, Default)]
 struct CompilationScope {
     instructions: Vec<u8>,
-    positions: Vec<(usize, Position)>,
+    positions: Vec<(usize, Span)>,
     last_instruction: Option<EmittedInstruction>,
     previous_instruction: Option<EmittedInstruction>,
     loop_stack: Vec<LoopContext>,
+    bindings: Vec<Binding>,
 }
 
 /// Compiler for Monkey bytecode.
 #[derive(Debug)]
 pub struct Compiler {
+    options: CompilerOptions,
     chunk: Chunk,
     symbol_table: SymbolTableRef,
     last_instruction: Option<EmittedInstruction>,
     previous_instruction: Option<EmittedInstruction>,
     loop_stack: Vec<LoopContext>,
+    bindings: Vec<Binding>,
     scopes: Vec<CompilationScope>,
     scope_index: usize,
+    /// Constant pool index of every interned integer, string and boolean.
+    /// There is one pool for the whole program, so function scopes at any
+    /// depth share entries with each other and with the top level.
+    constant_indices: HashMap<HashKey, usize>,
+    warnings: Vec<CompileWarning>,
+    /// Errors of statements that failed to compile; see `compile_program`.
+    errors: Vec<CompileError>,
+    /// Symbol table from before the last successful `extend`.
+    before_extend: Option<SymbolTable>,
+    /// Expression extents of the program being compiled, from
+    /// `Program::spans`.
+    spans: HashMap<Position, Span>,
 }
 
 impl Compiler {
     pub fn new() -> Self {
+        Self::with_options(CompilerOptions::default())
+    }
+
+    pub fn with_options(options: CompilerOptions) -> Self {
         let mut root = SymbolTable::new();
         define_builtins(&mut root);
 
         Self {
+            options,
             chunk: Chunk::new(),
             symbol_table: root.into_ref(),
             last_instruction: None,
             previous_instruction: None,
             loop_stack: Vec::new(),
+            bindings: Vec::new(),
             scopes: Vec::new(),
             scope_index: 0,
+            constant_indices: HashMap::new(),
+            warnings: Vec::new(),
+            errors: Vec::new(),
+            before_extend: None,
+            spans: HashMap::new(),
         }
     }
 
-    pub fn compile_program(&mut self, program: &Program) -> Result<(), CompileError> {
-        for stmt in &program.statements {
-            self.compile_statement(stmt)?;
+    /// Compile a whole program. A statement that fails to compile is
+    /// recorded and compilation carries on with the next one, so all of a
+    /// file's errors are reported together, in source order.
+    pub fn compile_program(&mut self, program: &Program) -> Result<(), Vec<CompileError>> {
+        self.spans = program.spans.clone();
+        if let Err(err) = self.compile_program_statements(program) {
+            self.errors.push(err);
         }
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn compile_program_statements(&mut self, program: &Program) -> Result<(), CompileError> {
+        self.compile_statements(&program.statements)?;
 
         let terminal_pos = program
             .statements
@@ -98,12 +299,7 @@ impl Compiler {
             .map(Statement::pos)
             .unwrap_or_default();
 
-        let ends_with_expression = matches!(
-            program.statements.last(),
-            Some(Statement::Expression { .. })
-        );
-
-        if ends_with_expression && self.last_instruction_is(Opcode::Pop) {
+        if ends_with_expression(&program.statements) && self.last_instruction_is(Opcode::Pop) {
             self.replace_last_pop_with_return_value(terminal_pos)?;
         } else if !self.last_instruction_is(Opcode::ReturnValue)
             && !self.last_instruction_is(Opcode::Return)
@@ -111,7 +307,10 @@ impl Compiler {
             self.emit(Opcode::Return, &[], terminal_pos)?;
         }
 
-        Ok(())
+        self.report_unused_bindings();
+        self.warnings
+            .sort_by_key(|warning| (warning.pos.line, warning.pos.col));
+        self.optimize_current_scope()
     }
 
     pub fn compile_statement(&mut self, stmt: &Statement) -> Result<(), CompileError> {
@@ -131,7 +330,8 @@ impl Compiler {
                     _ => self.compile_expression(value)?,
                 }
 
-                let symbol = self.symbol_table.borrow_mut().define(name.value.clone());
+                let symbol = self.define_binding(name)?;
+                self.record_binding(&name.value, name.pos, false);
                 match symbol.scope {
                     SymbolScope::Global => {
                         self.emit(Opcode::SetGlobal, &[symbol.index], *pos)?;
@@ -152,7 +352,13 @@ impl Compiler {
             }
             Statement::Expression { expression, pos } => {
                 self.compile_expression(expression)?;
-                self.emit(Opcode::Pop, &[], *pos)?;
+                // The statement starts where its expression does, so look
+                // the span up by the expression rather than by `pos`.
+                let span = self
+                    .spans
+                    .get(&expression.pos())
+                    .map_or(Span::at(*pos), |span| Span::new(*pos, span.start, span.end));
+                self.emit_spanned(Opcode::Pop, &[], span)?;
             }
             Statement::Return { value, pos } => {
                 self.compile_expression(value)?;
@@ -174,7 +380,7 @@ impl Compiler {
                 let false_jump = self.emit_jump(Opcode::JumpIfFalse, *pos)?;
                 self.emit_pop(*pos)?;
 
-                self.compile_block(body)?;
+                self.compile_scoped_block(body)?;
                 self.emit(Opcode::Jump, &[loop_start], *pos)?;
 
                 let cond_false_label = self.current_offset();
@@ -220,21 +426,127 @@ impl Compiler {
 
     pub(crate) fn compile_block(&mut self, block: &BlockStatement) -> Result<(), CompileError> {
         // TODO(step-14): function-body compilation reuses statement-context block compilation.
-        for stmt in &block.statements {
-            self.compile_statement(stmt)?;
+        self.compile_statements(&block.statements)
+    }
+
+    /// Compile an `if` branch or loop body. At the top level, names it
+    /// defines for the first time are local to it; a `let` of a name that
+    /// is already visible rebinds that name, which is how loops update
+    /// their counters. Inside a function a block's `let`s define locals of
+    /// the whole function, as they always have.
+    fn compile_scoped_block(&mut self, block: &BlockStatement) -> Result<(), CompileError> {
+        if self.scope_index > 0 {
+            return self.compile_block(block);
+        }
+        self.symbol_table.borrow_mut().enter_block();
+        let result = self.compile_block(block);
+        self.symbol_table.borrow_mut().leave_block();
+        result
+    }
+
+    /// Compile statements in order. Whatever follows a `return`, `break` or
+    /// `continue` can never run: it is reported and its code dropped.
+    fn compile_statements(&mut self, statements: &[Statement]) -> Result<(), CompileError> {
+        for (idx, stmt) in statements.iter().enumerate() {
+            self.compile_recovering(stmt);
+            if matches!(
+                stmt,
+                Statement::Return { .. } | Statement::Break { .. } | Statement::Continue { .. }
+            ) {
+                let rest = &statements[idx + 1..];
+                if let Some(next) = rest.first() {
+                    self.compile_unreachable(rest, next.pos())?;
+                }
+                break;
+            }
         }
         Ok(())
     }
 
+    /// Compile one statement of a statement list. On failure the error is
+    /// recorded, the scope and loop nesting the statement entered are
+    /// unwound, and a failed `let` still defines its name, so the following
+    /// statements compile (and report errors) independently.
+    fn compile_recovering(&mut self, stmt: &Statement) {
+        let scope_index = self.scope_index;
+        let loop_depth = self.current_loop_stack().len();
+        let Err(err) = self.compile_statement(stmt) else {
+            return;
+        };
+        self.errors.push(err);
+        while self.scope_index > scope_index {
+            if self.leave_scope().is_err() {
+                break;
+            }
+        }
+        self.current_loop_stack_mut().truncate(loop_depth);
+        if let Statement::Let { name, .. } = stmt {
+            self.symbol_table.borrow_mut().define(name.value.clone());
+        }
+    }
+
+    /// Compile dead statements so their errors are still reported, then
+    /// drop their code and warn once at `pos`.
+    fn compile_unreachable(
+        &mut self,
+        statements: &[Statement],
+        pos: Position,
+    ) -> Result<(), CompileError> {
+        let offset = self.current_offset();
+        let warnings = self.warnings.len();
+        for stmt in statements {
+            self.compile_recovering(stmt);
+        }
+        self.warnings.truncate(warnings);
+        self.warnings.push(CompileWarning::new(
+            CompileWarningKind::UnreachableCode,
+            "unreachable code",
+            pos,
+        ));
+        self.truncate_instructions(offset)
+    }
+
+    /// `if (true)` / `if (false)`: only the branch that can run is emitted.
+    fn compile_constant_if(
+        &mut self,
+        condition: bool,
+        consequence: &BlockStatement,
+        alternative: Option<&BlockStatement>,
+        pos: Position,
+    ) -> Result<(), CompileError> {
+        let (live, dead) = if condition {
+            (Some(consequence), alternative)
+        } else {
+            (alternative, Some(consequence))
+        };
+        match live {
+            Some(block) => self.compile_block_expression_value(block, pos)?,
+            None => {
+                self.emit(Opcode::Null, &[], pos)?;
+            }
+        }
+        match dead {
+            Some(block) if !block.statements.is_empty() => {
+                self.symbol_table.borrow_mut().enter_block();
+                let result = self.compile_unreachable(&block.statements, block.pos);
+                self.symbol_table.borrow_mut().leave_block();
+                result
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn compile_block_expression_value(
         &mut self,
         block: &BlockStatement,
         owner_pos: Position,
     ) -> Result<(), CompileError> {
         // TODO(step-14): function-body expression mode can share this branch-value shaping.
-        self.compile_block(block)?;
+        self.compile_scoped_block(block)?;
 
-        if self.last_instruction_is(Opcode::Pop) {
+        // Only an expression statement's `Pop` discards the block's value;
+        // a trailing `while` ends in the `Pop` of its condition.
+        if ends_with_expression(&block.statements) && self.last_instruction_is(Opcode::Pop) {
             self.remove_last_pop()?;
         } else if !self.last_instruction_is(Opcode::ReturnValue)
             && !self.last_instruction_is(Opcode::Return)
@@ -248,8 +560,19 @@ impl Compiler {
     pub fn compile_expression(&mut self, expr: &Expression) -> Result<(), CompileError> {
         match expr {
             Expression::IntegerLiteral { value, pos, .. } => {
-                let idx = self.add_constant(Object::Integer(*value), *pos);
-                self.emit(Opcode::Constant, &[idx], *pos)?;
+                // Small literals are encoded inline instead of taking a
+                // constant pool slot.
+                match *value {
+                    0 => self.emit(Opcode::Const0, &[], *pos)?,
+                    1 => self.emit(Opcode::Const1, &[], *pos)?,
+                    small if i16::try_from(small).is_ok() => {
+                        self.emit(Opcode::ConstInt, &[small as i16 as u16 as usize], *pos)?
+                    }
+                    value => {
+                        let idx = self.add_constant(Object::Integer(value), *pos);
+                        self.emit(Opcode::Constant, &[idx], *pos)?
+                    }
+                };
             }
             Expression::BooleanLiteral { value, pos } => {
                 if *value {
@@ -259,14 +582,26 @@ impl Compiler {
                 }
             }
             Expression::StringLiteral { value, pos } => {
-                let idx = self.add_constant(Object::String(value.clone()), *pos);
+                let idx = self.add_constant(Object::String(value.as_str().into()), *pos);
                 self.emit(Opcode::Constant, &[idx], *pos)?;
             }
             Expression::Identifier { value, pos } => {
                 let symbol = self.symbol_table.borrow_mut().resolve(value);
                 let Some(symbol) = symbol else {
                     // TODO(step-17): unresolved identifiers should align with runtime UNKNOWN_IDENTIFIER flow.
-                    return Err(CompileError::unresolved_identifier(value, *pos));
+                    if self.symbol_table.borrow().ended_in_block(value) {
+                        return Err(CompileError::out_of_scope(value, *pos));
+                    }
+                    let mut suggestions = self.symbol_table.borrow().similar_names(value);
+                    if suggestions.is_empty() {
+                        // `retrun x;` parses as two expression statements.
+                        suggestions.extend(similar_keyword(value).map(str::to_string));
+                    }
+                    return Err(CompileError::unresolved_identifier(
+                        value,
+                        *pos,
+                        &suggestions,
+                    ));
                 };
                 self.emit_for_symbol_load(&symbol, *pos)?;
             }
@@ -287,7 +622,8 @@ impl Compiler {
                         return Err(CompileError::new(
                             format!("unsupported prefix operator in step 15: {operator}"),
                             Some(*pos),
-                        ));
+                        )
+                        .with_kind(CompileErrorKind::UnsupportedOperator));
                     }
                 }
             }
@@ -354,7 +690,8 @@ impl Compiler {
                         return Err(CompileError::new(
                             format!("unsupported infix operator in step 15: {operator}"),
                             Some(*pos),
-                        ));
+                        )
+                        .with_kind(CompileErrorKind::UnsupportedOperator));
                     }
                 };
                 self.emit(opcode, &[], *pos)?;
@@ -365,6 +702,14 @@ impl Compiler {
                 alternative,
                 pos,
             } => {
+                if let Expression::BooleanLiteral { value, .. } = condition.as_ref() {
+                    return self.compile_constant_if(
+                        *value,
+                        consequence,
+                        alternative.as_ref(),
+                        *pos,
+                    );
+                }
                 self.compile_expression(condition)?;
                 let false_jump = self.emit_jump(Opcode::JumpIfFalse, *pos)?;
                 self.emit_pop(*pos)?;
@@ -445,7 +790,8 @@ impl Compiler {
         }
 
         for param in parameters {
-            self.symbol_table.borrow_mut().define(param.value.clone());
+            self.define_binding(param)?;
+            self.record_binding(&param.value, param.pos, true);
         }
 
         self.compile_block(body)?;
@@ -457,6 +803,8 @@ impl Compiler {
         {
             self.emit(Opcode::Return, &[], pos)?;
         }
+        self.optimize_current_scope()?;
+        self.report_unused_bindings();
 
         let free_symbols = self.symbol_table.borrow().free_symbols.clone();
         let num_locals = self.symbol_table.borrow().num_definitions;
@@ -468,12 +816,12 @@ impl Compiler {
             self.emit_for_symbol_load(free, pos)?;
         }
 
-        let function = Object::CompiledFunction(Rc::new(CompiledFunctionObject {
+        let function = Object::CompiledFunction(Shared::new(CompiledFunctionObject {
             name: inferred_name,
             num_params,
             num_locals,
-            instructions: scope.instructions,
-            positions: scope.positions,
+            instructions: scope.instructions.into(),
+            positions: scope.positions.into(),
         }));
 
         let const_idx = self.add_constant(function, pos);
@@ -510,7 +858,85 @@ impl Compiler {
         Ok(scope)
     }
 
-    pub fn compile(&mut self, program: &Program) -> Result<(), CompileError> {
+    /// Compile `program` as a continuation of everything compiled so far:
+    /// it sees the existing globals and shares the constant pool. Returns a
+    /// delta chunk holding only the new top-level code and the constants
+    /// this call added, which `Vm::append` runs on the VM that ran the
+    /// earlier deltas. On error, nothing `program` defined is kept.
+    pub fn extend(&mut self, program: &Program) -> Result<Chunk, Vec<CompileError>> {
+        let symbols = self.symbol_table.borrow().clone();
+        let first_constant = self.chunk.constants.len();
+        self.chunk.instructions.clear();
+        self.chunk.positions.clear();
+        self.last_instruction = None;
+        self.previous_instruction = None;
+        self.warnings.clear();
+
+        let result = self.compile_program(program);
+        let instructions = std::mem::take(&mut self.chunk.instructions);
+        let positions = std::mem::take(&mut self.chunk.positions);
+        if let Err(errors) = result {
+            self.roll_back(symbols, first_constant);
+            return Err(errors);
+        }
+        self.before_extend = Some(symbols);
+        Ok(Chunk {
+            instructions,
+            constants: self.chunk.constants[first_constant..].to_vec(),
+            positions,
+        })
+    }
+
+    /// Compile `program` the way `extend` would, then forget it, e.g. to
+    /// show its bytecode without running it. The chunk holds the whole
+    /// constant pool, so its constant operands resolve.
+    pub fn preview(&mut self, program: &Program) -> Result<Chunk, Vec<CompileError>> {
+        let symbols = self.symbol_table.borrow().clone();
+        let first_constant = self.chunk.constants.len();
+        let before_extend = self.before_extend.take();
+        let result = self.extend(program);
+        let constants = self.chunk.constants.clone();
+        self.roll_back(symbols, first_constant);
+        self.before_extend = before_extend;
+        result.map(|delta| Chunk { constants, ..delta })
+    }
+
+    /// Drop the names and constants defined since the pool had
+    /// `first_constant` entries and the symbol table was `symbols`.
+    fn roll_back(&mut self, symbols: SymbolTable, first_constant: usize) {
+        *self.symbol_table.borrow_mut() = symbols;
+        self.chunk.constants.truncate(first_constant);
+        self.constant_indices
+            .retain(|_, index| *index < first_constant);
+    }
+
+    /// Forget the names the last successful `extend` defined, e.g. because
+    /// running its delta failed. Its constants stay in the pool, since the
+    /// VM already holds them.
+    pub fn forget_last_extend(&mut self) {
+        if let Some(symbols) = self.before_extend.take() {
+            *self.symbol_table.borrow_mut() = symbols;
+        }
+    }
+
+    /// Define the top-level binding `name` for later `extend`s, e.g. for a
+    /// value the host stores in its slot, and return the slot. Only valid
+    /// between compilations, when the global scope is the current one.
+    pub fn define_global(&mut self, name: &str) -> usize {
+        self.symbol_table.borrow_mut().define(name).index
+    }
+
+    /// Global slot of the top-level binding `name`, if it is in scope.
+    pub fn global_index(&self, name: &str) -> Option<usize> {
+        self.symbol_table
+            .borrow()
+            .store
+            .get(name)
+            .filter(|symbol| symbol.scope == SymbolScope::Global)
+            .map(|symbol| symbol.index)
+    }
+
+    pub fn compile(&mut self, program: &Program) -> Result<(), Vec<CompileError>> {
         self.compile_program(program)
     }
 
@@ -522,21 +948,71 @@ impl Compiler {
         self.chunk
     }
 
+    /// Warnings collected so far, in the order they were found; after
+    /// `extend`, only the ones for the program it compiled.
+    pub fn warnings(&self) -> &[CompileWarning] {
+        &self.warnings
+    }
+
+    /// Names of the top-level bindings, indexed by global slot.
+    pub fn global_names(&self) -> Vec<String> {
+        let table = self.symbol_table.borrow();
+        let mut globals = table
+            .store
+            .values()
+            .chain(&table.ended)
+            .filter(|symbol| symbol.scope == SymbolScope::Global)
+            .collect::<Vec<_>>();
+        globals.sort_by_key(|symbol| symbol.index);
+        globals
+            .into_iter()
+            .map(|symbol| symbol.name.clone())
+            .collect()
+    }
+
+    /// Emit `op` for the expression at `pos`, recording that expression's
+    /// span when the parser provided one.
     fn emit(
         &mut self,
         op: Opcode,
         operands: &[usize],
         pos: Position,
     ) -> Result<usize, CompileError> {
-        let bytes = make(op, operands).map_err(|err| self.bytecode_error(op, pos, err))?;
+        let span = self.spans.get(&pos).copied().unwrap_or(Span::at(pos));
+        self.emit_spanned(op, operands, span)
+    }
+
+    fn emit_spanned(
+        &mut self,
+        op: Opcode,
+        operands: &[usize],
+        span: Span,
+    ) -> Result<usize, CompileError> {
+        let bytes = make(op, operands).map_err(|err| self.bytecode_error(op, span.pos, err))?;
         let offset = self.current_offset();
         self.current_instructions_mut().extend_from_slice(&bytes);
-        self.current_positions_mut().push((offset, pos));
+        self.current_positions_mut().push((offset, span));
         self.current_positions_mut().sort_by_key(|(off, _)| *off);
         self.set_last_instruction(op, offset);
         Ok(offset)
     }
 
+    fn optimize_current_scope(&mut self) -> Result<(), CompileError> {
+        if !self.options.optimize {
+            return Ok(());
+        }
+        let (instructions, positions) = if self.scope_index == 0 {
+            (&mut self.chunk.instructions, &mut self.chunk.positions)
+        } else {
+            let scope = &mut self.scopes[self.scope_index - 1];
+            (&mut scope.instructions, &mut scope.positions)
+        };
+        if optimize_scope(instructions, positions) {
+            self.record_last_instruction_from_tail()?;
+        }
+        Ok(())
+    }
+
     fn current_offset(&self) -> usize {
         self.current_instructions().len()
     }
@@ -621,6 +1097,100 @@ impl Compiler {
         Ok(())
     }
 
+    /// Define a `let` name or parameter, reporting a redefinition in the
+    /// same block (per `CompilerOptions::redefinition`) and a name that
+    /// shadows a builtin.
+    fn define_binding(&mut self, name: &Identifier) -> Result<Symbol, CompileError> {
+        let (symbol, previous) = self
+            .symbol_table
+            .borrow_mut()
+            .define_at(name.value.clone(), name.pos);
+        if BUILTIN_NAMES.contains(&name.value.as_str()) {
+            self.warnings.push(CompileWarning::new(
+                CompileWarningKind::ShadowedBuiltin,
+                format!("shadowed builtin: {}", name.value),
+                name.pos,
+            ));
+        }
+        if let Some(previous) = previous {
+            let message = format!(
+                "redefinition of {} (first defined at {previous})",
+                name.value
+            );
+            match self.options.redefinition {
+                RedefinitionPolicy::Warn => self.warnings.push(CompileWarning::new(
+                    CompileWarningKind::Redefinition,
+                    message,
+                    name.pos,
+                )),
+                RedefinitionPolicy::Error => {
+                    return Err(CompileError::new(message, Some(name.pos))
+                        .with_kind(CompileErrorKind::Redefinition));
+                }
+            }
+        }
+        Ok(symbol)
+    }
+
+    /// Remember the first definition of `name` in the current scope; a
+    /// re-`let` reuses the same slot, so it is the same binding.
+    fn record_binding(&mut self, name: &str, pos: Position, parameter: bool) {
+        let bindings = if self.scope_index == 0 {
+            &mut self.bindings
+        } else {
+            &mut self.scopes[self.scope_index - 1].bindings
+        };
+        if bindings.iter().all(|binding| binding.name != name) {
+            bindings.push(Binding {
+                name: name.to_string(),
+                pos,
+                parameter,
+            });
+        }
+    }
+
+    /// Warn about the current scope's bindings that were never read. Names
+    /// starting with `_` are exempt, as are globals once the program calls
+    /// `eval`, whose source may read them.
+    fn report_unused_bindings(&mut self) {
+        let bindings = if self.scope_index == 0 {
+            std::mem::take(&mut self.bindings)
+        } else {
+            std::mem::take(&mut self.scopes[self.scope_index - 1].bindings)
+        };
+        let table = self.symbol_table.borrow();
+        if self.scope_index == 0 && table.used.contains("eval") {
+            return;
+        }
+        for binding in bindings {
+            if binding.name.starts_with('_') || table.used.contains(&binding.name) {
+                continue;
+            }
+            let (kind, what) = if binding.parameter {
+                (CompileWarningKind::UnusedParameter, "parameter")
+            } else {
+                (CompileWarningKind::UnusedVariable, "variable")
+            };
+            self.warnings.push(CompileWarning::new(
+                kind,
+                format!("unused {what}: {}", binding.name),
+                binding.pos,
+            ));
+        }
+    }
+
+    /// Drop everything emitted from `offset` on, including breaks still
+    /// waiting to be patched.
+    fn truncate_instructions(&mut self, offset: usize) -> Result<(), CompileError> {
+        self.current_instructions_mut().truncate(offset);
+        self.current_positions_mut()
+            .retain(|(off, _)| *off < offset);
+        for loop_ctx in self.current_loop_stack_mut() {
+            loop_ctx.break_jumps.retain(|jump| *jump < offset);
+        }
+        self.record_last_instruction_from_tail()
+    }
+
     fn remove_last_instruction(&mut self) -> Result<(), CompileError> {
         let Some(last) = self.current_last_instruction() else {
             return Err(CompileError::new(
@@ -750,7 +1320,15 @@ impl Compiler {
     }
 
     fn add_constant(&mut self, obj: Object, _pos: Position) -> usize {
-        self.chunk.add_constant(obj.rc())
+        let Some(key) = obj.hash_key() else {
+            return self.chunk.add_constant(obj.rc());
+        };
+        if let Some(&idx) = self.constant_indices.get(&key) {
+            return idx;
+        }
+        let idx = self.chunk.add_constant(obj.rc());
+        self.constant_indices.insert(key, idx);
+        idx
     }
 
     fn emit_for_symbol_load(&mut self, symbol: &Symbol, pos: Position) -> Result<(), CompileError> {
@@ -800,7 +1378,7 @@ impl Compiler {
         }
     }
 
-    fn current_positions_mut(&mut self) -> &mut Vec<(usize, Position)> {
+    fn current_positions_mut(&mut self) -> &mut Vec<(usize, Span)> {
         if self.scope_index == 0 {
             &mut self.chunk.positions
         } else {
@@ -831,6 +1409,12 @@ impl Default for Compiler {
     }
 }
 
+/// Whether the value of the last statement is what `statements` evaluates
+/// to, so its trailing `Pop` is the one to drop.
+fn ends_with_expression(statements: &[Statement]) -> bool {
+    matches!(statements.last(), Some(Statement::Expression { .. }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::Compiler;
@@ -902,13 +1486,14 @@ mod tests {
         assert_eq!(
             ops,
             vec![
-                Opcode::Constant,
+                Opcode::Const1,
                 Opcode::Pop,
-                Opcode::Constant,
+                Opcode::ConstInt,
                 Opcode::ReturnValue,
-                Opcode::Constant,
-                Opcode::SetGlobal,
             ]
         );
+        // The dead `let` is dropped but still defines its binding.
+        assert_eq!(compiler.warnings().len(), 1);
+        assert!(compiler.symbol_table.borrow_mut().resolve("x").is_some());
     }
 }
@@ -0,0 +1,115 @@
+//! Source-line coverage collected by a `Vm` run with
+//! `VmOptions::coverage`.
+//!
+//! A line counts as code when any instruction's position in the chunk's
+//! position tables is on it, and as executed once such an instruction
+//! ran. Superinstructions also cover the lines of the instructions they
+//! replaced.
+
+use std::collections::BTreeSet;
+
+use crate::bytecode::{lookup_definition, span_at, Chunk, Opcode};
+use crate::object::{CompiledFunctionObject, Object};
+use crate::position::Span;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    lines: BTreeSet<usize>,
+    executed: BTreeSet<usize>,
+}
+
+impl Coverage {
+    /// Coverage of `chunk` with nothing executed yet.
+    pub fn for_chunk(chunk: &Chunk) -> Self {
+        let mut coverage = Self::default();
+        coverage.add_chunk(chunk);
+        coverage
+    }
+
+    /// Count the lines of `chunk`'s top-level code and compiled functions
+    /// as code, e.g. for a delta appended to a running VM.
+    pub fn add_chunk(&mut self, chunk: &Chunk) {
+        self.add_positions(&chunk.positions);
+        for constant in &chunk.constants {
+            if let Object::CompiledFunction(function) = constant.as_ref() {
+                self.add_positions(&function.positions);
+            }
+        }
+    }
+
+    fn add_positions(&mut self, positions: &[(usize, Span)]) {
+        self.lines.extend(
+            positions
+                .iter()
+                .map(|(_, span)| span.pos.line)
+                .filter(|&line| line > 0),
+        );
+    }
+
+    /// Lines that hold code, in order.
+    pub fn lines(&self) -> &BTreeSet<usize> {
+        &self.lines
+    }
+
+    /// Lines at least one instruction of which ran.
+    pub fn executed_lines(&self) -> &BTreeSet<usize> {
+        &self.executed
+    }
+
+    /// Lines with code that never ran.
+    pub fn unexecuted_lines(&self) -> Vec<usize> {
+        self.lines.difference(&self.executed).copied().collect()
+    }
+
+    /// Mark the instruction at `ip` in `function` as executed.
+    pub(crate) fn record(&mut self, function: &CompiledFunctionObject, ip: usize) {
+        let code = &function.instructions;
+        let positions = &function.positions;
+        if let Some(span) = span_at(positions, ip) {
+            self.executed.insert(span.pos.line);
+        }
+        let Some(op) = code.get(ip).copied().and_then(Opcode::from_byte) else {
+            return;
+        };
+        // A superinstruction is padded with `Nop`s over the code it
+        // replaced, whose positions are still in the table.
+        let mut end = ip + 1 + lookup_definition(op).operand_widths.iter().sum::<usize>();
+        while code.get(end) == Some(&Opcode::Nop.to_byte()) {
+            end += 1;
+        }
+        let first = positions.partition_point(|(offset, _)| *offset <= ip);
+        self.executed.extend(
+            positions[first..]
+                .iter()
+                .take_while(|(offset, _)| *offset < end)
+                .map(|(_, span)| span.pos.line),
+        );
+    }
+
+    /// Report for `monkey run --coverage`: a summary line, then each
+    /// unexecuted line with its text when `source` is given.
+    pub fn render(&self, source: Option<&str>) -> String {
+        let total = self.lines.len();
+        let executed = self.lines.intersection(&self.executed).count();
+        let percent = if total == 0 {
+            100.0
+        } else {
+            executed as f64 * 100.0 / total as f64
+        };
+        let mut out = format!("{executed} of {total} lines executed ({percent:.1}%)");
+        let missed = self.unexecuted_lines();
+        if missed.is_empty() {
+            return out;
+        }
+        out.push_str("\nUnexecuted lines:");
+        let width = missed.last().map_or(1, |line| line.to_string().len());
+        for line in missed {
+            let text = source
+                .and_then(|source| source.lines().nth(line - 1))
+                .map(|text| format!(" | {}", text.trim_end()))
+                .unwrap_or_default();
+            out.push_str(&format!("\n  {line:>width$}{text}"));
+        }
+        out
+    }
+}
@@ -0,0 +1,404 @@
+//! A lossless concrete syntax tree: the exact text of every token, the
+//! whitespace and comments between them, and a tree of nodes over those
+//! tokens that mirrors the AST, for formatters and source-preserving
+//! refactorings.
+//!
+//! Whitespace and comments are trivia attached to the token that follows
+//! them; the final `Eof` token carries whatever trails the last real token.
+//! Concatenating every token's trivia and text gives back the source
+//! exactly, which is what `Cst`'s `Display` does.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::ops::Range;
+
+use crate::ast::{BlockStatement, Expression, Identifier, Program, Statement};
+use crate::lexer::{Lexer, LexerOptions};
+use crate::parse_error::ParseError;
+use crate::parser::Parser;
+use crate::position::{Position, Span};
+use crate::token::TokenKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    /// A `#` comment, without the line break that ends it.
+    Comment,
+}
+
+/// Source text between tokens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub pos: Position,
+}
+
+/// A token with its exact source text and the trivia before it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CstToken {
+    pub kind: TokenKind,
+    /// The token as written, e.g. a string with its quotes.
+    pub text: String,
+    pub pos: Position,
+    pub end: Position,
+    pub leading: Vec<Trivia>,
+}
+
+impl CstToken {
+    /// The comments among the token's leading trivia.
+    pub fn comments(&self) -> impl Iterator<Item = &Trivia> {
+        self.leading
+            .iter()
+            .filter(|trivia| trivia.kind == TriviaKind::Comment)
+    }
+}
+
+/// What a `CstNode` stands for: the `Program`, a `Block`, a binding
+/// (`let` name or parameter) or the `Statement`/`Expression` variant of the
+/// same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Program,
+    Block,
+    Binding,
+    Let,
+    Return,
+    While,
+    Break,
+    Continue,
+    ExpressionStatement,
+    Identifier,
+    IntegerLiteral,
+    BooleanLiteral,
+    StringLiteral,
+    Prefix,
+    Infix,
+    If,
+    FunctionLiteral,
+    Call,
+    ArrayLiteral,
+    HashLiteral,
+    Index,
+}
+
+/// A syntax node covering `tokens`, indices into `Cst::tokens`. Its
+/// children are in source order; the tokens between them, such as
+/// keywords, operators and delimiters, belong to the node itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CstNode {
+    pub kind: NodeKind,
+    pub tokens: Range<usize>,
+    pub children: Vec<CstNode>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cst {
+    tokens: Vec<CstToken>,
+    root: CstNode,
+    program: Program,
+}
+
+impl Cst {
+    /// Parse `source` into both a CST and the AST it mirrors.
+    pub fn parse(source: &str) -> Result<Self, Vec<ParseError>> {
+        let mut parser = Parser::new(Lexer::new(source));
+        let program = parser.parse_program();
+        if !parser.errors().is_empty() {
+            return Err(parser.errors().to_vec());
+        }
+        let tokens = lossless_tokens(source);
+        let root = Builder { tokens: &tokens }.program(&program);
+        Ok(Self {
+            tokens,
+            root,
+            program,
+        })
+    }
+
+    /// Every token up to and including `Eof`.
+    pub fn tokens(&self) -> &[CstToken] {
+        &self.tokens
+    }
+
+    pub fn root(&self) -> &CstNode {
+        &self.root
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// The exact source of `node`, with the trivia inside it but not the
+    /// trivia before its first token.
+    pub fn text(&self, node: &CstNode) -> String {
+        let mut out = String::new();
+        for (idx, token) in self.tokens[node.tokens.clone()].iter().enumerate() {
+            if idx > 0 {
+                push_trivia(&mut out, token);
+            }
+            out.push_str(&token.text);
+        }
+        out
+    }
+}
+
+impl Display for Cst {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut out = String::new();
+        for token in &self.tokens {
+            push_trivia(&mut out, token);
+            out.push_str(&token.text);
+        }
+        f.write_str(&out)
+    }
+}
+
+fn push_trivia(out: &mut String, token: &CstToken) {
+    for trivia in &token.leading {
+        out.push_str(&trivia.text);
+    }
+}
+
+/// Tokenize `source` keeping its exact text and trivia. Unlike `Cst::parse`
+/// this works for any input, including input that does not parse.
+pub fn lossless_tokens(source: &str) -> Vec<CstToken> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut line_starts = vec![0];
+    line_starts.extend(
+        chars
+            .iter()
+            .enumerate()
+            .filter(|(_, ch)| **ch == '\n')
+            .map(|(idx, _)| idx + 1),
+    );
+    // Positions are 1-based and count every character but `\n` as a
+    // column, so they map straight onto character indices.
+    let index = |pos: Position| (line_starts[pos.line - 1] + pos.col - 1).min(chars.len());
+    let text = |range: Range<usize>| chars[range].iter().collect::<String>();
+
+    let mut tokens = Vec::new();
+    let mut leading = Vec::new();
+    let mut cursor = 0;
+    let mut cursor_pos = Position::new(1, 1);
+    for token in Lexer::new(source).tokenize_all(LexerOptions::default().with_comments(true)) {
+        let start = index(token.pos);
+        let end = index(token.end);
+        if start > cursor {
+            leading.push(Trivia {
+                kind: TriviaKind::Whitespace,
+                text: text(cursor..start),
+                pos: cursor_pos,
+            });
+        }
+        cursor = end;
+        cursor_pos = token.end;
+        if token.kind == TokenKind::Comment {
+            leading.push(Trivia {
+                kind: TriviaKind::Comment,
+                text: text(start..end),
+                pos: token.pos,
+            });
+            continue;
+        }
+        tokens.push(CstToken {
+            kind: token.kind,
+            text: text(start..end),
+            pos: token.pos,
+            end: token.end,
+            leading: std::mem::take(&mut leading),
+        });
+    }
+    tokens
+}
+
+/// Lays `CstNode`s over the tokens using the AST's positions and spans.
+struct Builder<'a> {
+    tokens: &'a [CstToken],
+}
+
+impl Builder<'_> {
+    fn program(&self, program: &Program) -> CstNode {
+        CstNode {
+            kind: NodeKind::Program,
+            tokens: 0..self.tokens.len(),
+            children: program
+                .statements
+                .iter()
+                .map(|statement| self.statement(program, statement))
+                .collect(),
+        }
+    }
+
+    fn statement(&self, program: &Program, statement: &Statement) -> CstNode {
+        let (kind, children) = match statement {
+            Statement::Let { name, value, .. } => (
+                NodeKind::Let,
+                vec![self.binding(name), self.expression(program, value)],
+            ),
+            Statement::Return { value, .. } => {
+                (NodeKind::Return, vec![self.expression(program, value)])
+            }
+            Statement::While {
+                condition, body, ..
+            } => (
+                NodeKind::While,
+                vec![
+                    self.expression(program, condition),
+                    self.block(program, body),
+                ],
+            ),
+            Statement::Break { .. } => (NodeKind::Break, vec![]),
+            Statement::Continue { .. } => (NodeKind::Continue, vec![]),
+            Statement::Expression { expression, .. } => (
+                NodeKind::ExpressionStatement,
+                vec![self.expression(program, expression)],
+            ),
+        };
+        CstNode {
+            kind,
+            tokens: self.range(program.statement_span(statement)),
+            children,
+        }
+    }
+
+    fn expression(&self, program: &Program, expression: &Expression) -> CstNode {
+        let expressions = |expressions: &[Expression]| {
+            expressions
+                .iter()
+                .map(|expression| self.expression(program, expression))
+                .collect::<Vec<_>>()
+        };
+        let (kind, children) = match expression {
+            Expression::Identifier { .. } => (NodeKind::Identifier, vec![]),
+            Expression::IntegerLiteral { .. } => (NodeKind::IntegerLiteral, vec![]),
+            Expression::BooleanLiteral { .. } => (NodeKind::BooleanLiteral, vec![]),
+            Expression::StringLiteral { .. } => (NodeKind::StringLiteral, vec![]),
+            Expression::Prefix { right, .. } => {
+                (NodeKind::Prefix, vec![self.expression(program, right)])
+            }
+            Expression::Infix { left, right, .. } => (
+                NodeKind::Infix,
+                vec![
+                    self.expression(program, left),
+                    self.expression(program, right),
+                ],
+            ),
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+                ..
+            } => {
+                let mut children = vec![
+                    self.expression(program, condition),
+                    self.block(program, consequence),
+                ];
+                children.extend(
+                    alternative
+                        .iter()
+                        .map(|alternative| self.block(program, alternative)),
+                );
+                (NodeKind::If, children)
+            }
+            Expression::FunctionLiteral {
+                parameters, body, ..
+            } => {
+                let mut children = parameters
+                    .iter()
+                    .map(|parameter| self.binding(parameter))
+                    .collect::<Vec<_>>();
+                children.push(self.block(program, body));
+                (NodeKind::FunctionLiteral, children)
+            }
+            Expression::Call {
+                function,
+                arguments,
+                ..
+            } => {
+                let mut children = vec![self.expression(program, function)];
+                children.extend(expressions(arguments));
+                (NodeKind::Call, children)
+            }
+            Expression::ArrayLiteral { elements, .. } => {
+                (NodeKind::ArrayLiteral, expressions(elements))
+            }
+            Expression::HashLiteral { pairs, .. } => (
+                NodeKind::HashLiteral,
+                pairs
+                    .iter()
+                    .flat_map(|(key, value)| {
+                        [
+                            self.expression(program, key),
+                            self.expression(program, value),
+                        ]
+                    })
+                    .collect(),
+            ),
+            Expression::Index { left, index, .. } => (
+                NodeKind::Index,
+                vec![
+                    self.expression(program, left),
+                    self.expression(program, index),
+                ],
+            ),
+        };
+        CstNode {
+            kind,
+            tokens: self.range(program.expression_span(expression)),
+            children,
+        }
+    }
+
+    /// From the block's `{` to the `}` that closes it.
+    fn block(&self, program: &Program, block: &BlockStatement) -> CstNode {
+        let start = self.first_at_or_after(block.pos);
+        let mut depth = 0usize;
+        let mut end = self.tokens.len() - 1;
+        for (idx, token) in self.tokens.iter().enumerate().skip(start) {
+            match token.kind {
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace => depth = depth.saturating_sub(1),
+                _ => continue,
+            }
+            if depth == 0 {
+                end = idx + 1;
+                break;
+            }
+        }
+        CstNode {
+            kind: NodeKind::Block,
+            tokens: start..end,
+            children: block
+                .statements
+                .iter()
+                .map(|statement| self.statement(program, statement))
+                .collect(),
+        }
+    }
+
+    fn binding(&self, name: &Identifier) -> CstNode {
+        let start = self.first_at_or_after(name.pos);
+        CstNode {
+            kind: NodeKind::Binding,
+            tokens: start..start + 1,
+            children: vec![],
+        }
+    }
+
+    /// The tokens inside `span`; at least the one at its position when its
+    /// extent is unknown.
+    fn range(&self, span: Span) -> Range<usize> {
+        let start = self.first_at_or_after(span.start);
+        let end = if span.is_empty() {
+            start + 1
+        } else {
+            self.first_at_or_after(span.end)
+        };
+        start..end.max(start + 1).min(self.tokens.len())
+    }
+
+    fn first_at_or_after(&self, pos: Position) -> usize {
+        self.tokens
+            .partition_point(|token| (token.pos.line, token.pos.col) < (pos.line, pos.col))
+    }
+}
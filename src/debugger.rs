@@ -0,0 +1,360 @@
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+
+use crate::bytecode::Chunk;
+use crate::compiler::Compiler;
+use crate::lexer::Lexer;
+use crate::object::{Object, ObjectRef};
+use crate::parser::Parser;
+use crate::position::Position;
+use crate::runner::RunnerError;
+use crate::runtime_error::{RuntimeError, StackFrameInfo};
+use crate::vm::{StepOutcome, Vm, VmOptions};
+
+const HELP: &str = "Commands:
+  break <line>   set a breakpoint (alias: b)
+  delete <line>  remove a breakpoint
+  continue       run to the next breakpoint (alias: c)
+  step           step to the next line, entering calls (alias: s)
+  next           step to the next line, over calls (alias: n)
+  finish         run until the current function returns (alias: out)
+  locals         show local slots of the current function
+  globals        show global bindings
+  stack          show the value stack
+  backtrace      show active frames (alias: bt)
+  where          show the current line
+  quit           stop debugging (alias: q)";
+
+/// Why a debugger command handed control back to the user.
+#[derive(Debug, Clone)]
+pub enum StopReason {
+    /// Execution reached a breakpoint on this line.
+    Breakpoint(usize),
+    /// A step command reached its target line.
+    Step,
+    /// The program finished with this value.
+    Finished(ObjectRef),
+    /// The program stopped with a runtime error.
+    Error(RuntimeError),
+}
+
+/// Where the VM is paused: call depth plus source line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PausePoint {
+    depth: usize,
+    line: usize,
+}
+
+/// Source-level debugger driving a `Vm` through its stepping API.
+///
+/// Breakpoints are set by source line; lines without code snap forward to
+/// the next line that has an instruction in the chunk position tables.
+#[derive(Debug)]
+pub struct Debugger {
+    vm: Vm,
+    breakpoints: BTreeSet<usize>,
+    code_lines: BTreeSet<usize>,
+    global_names: Vec<String>,
+    source_lines: Vec<String>,
+    finished: Option<StopReason>,
+}
+
+impl Debugger {
+    /// Create a debugger paused before the first instruction of `chunk`.
+    /// `global_names` labels global slots, as returned by
+    /// `Compiler::global_names`.
+    pub fn new(chunk: Chunk, global_names: Vec<String>) -> Self {
+        let mut code_lines = chunk
+            .positions
+            .iter()
+            .map(|(_, span)| span.pos.line)
+            .collect::<BTreeSet<_>>();
+        for constant in &chunk.constants {
+            if let Object::CompiledFunction(function) = constant.as_ref() {
+                code_lines.extend(function.positions.iter().map(|(_, span)| span.pos.line));
+            }
+        }
+
+        // Fusion rewrites instruction boundaries, so debug the bytecode as
+        // the compiler emitted it; native code would skip whole calls.
+        let options = VmOptions::default()
+            .with_superinstructions(false)
+            .with_jit(false);
+        Self {
+            vm: Vm::with_options(chunk, options),
+            breakpoints: BTreeSet::new(),
+            code_lines,
+            global_names,
+            source_lines: Vec::new(),
+            finished: None,
+        }
+    }
+
+    /// Parse and compile `source`, keeping its text for line listings.
+    pub fn from_source(source: &str, args: &[String]) -> Result<Self, RunnerError> {
+        let mut parser = Parser::new(Lexer::new(source));
+        let program = parser.parse_program();
+        if !parser.errors().is_empty() {
+            return Err(RunnerError::Parse(parser.errors().to_vec()));
+        }
+
+        let mut compiler = Compiler::new();
+        compiler
+            .compile_program(&program)
+            .map_err(RunnerError::Compile)?;
+        let global_names = compiler.global_names();
+
+        let mut debugger = Self::new(compiler.into_bytecode(), global_names);
+        debugger.vm.set_script_args(args.to_vec());
+        debugger.source_lines = source.lines().map(str::to_string).collect();
+        Ok(debugger)
+    }
+
+    pub fn vm(&self) -> &Vm {
+        &self.vm
+    }
+
+    /// Set a breakpoint on `line`, or on the next line with code. Returns the
+    /// line the breakpoint was placed on.
+    pub fn set_breakpoint(&mut self, line: usize) -> Option<usize> {
+        let resolved = *self.code_lines.range(line..).next()?;
+        self.breakpoints.insert(resolved);
+        Some(resolved)
+    }
+
+    pub fn clear_breakpoint(&mut self, line: usize) -> bool {
+        self.breakpoints.remove(&line)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Source line of the next instruction, or `None` once finished.
+    pub fn line(&self) -> Option<usize> {
+        self.pause_point().map(|point| point.line)
+    }
+
+    pub fn position(&self) -> Option<Position> {
+        self.vm.position()
+    }
+
+    /// Run until a breakpoint is reached or the program ends.
+    pub fn resume(&mut self) -> StopReason {
+        self.run_until(|_, _| false)
+    }
+
+    /// Run to the next source line, stopping inside any function called on
+    /// the way.
+    pub fn step_into(&mut self) -> StopReason {
+        self.run_until(|start, now| now != start)
+    }
+
+    /// Run to the next source line in the current function or a caller.
+    pub fn step_over(&mut self) -> StopReason {
+        self.run_until(|start, now| {
+            now.depth < start.depth || (now.depth == start.depth && now.line != start.line)
+        })
+    }
+
+    /// Run until the current function returns to its caller.
+    pub fn step_out(&mut self) -> StopReason {
+        self.run_until(|start, now| now.depth < start.depth)
+    }
+
+    /// Local slots of the current function, labelled by slot index.
+    pub fn locals(&self) -> Vec<(String, ObjectRef)> {
+        self.vm
+            .locals()
+            .iter()
+            .enumerate()
+            .map(|(slot, value)| (format!("[{slot}]"), value.clone()))
+            .collect()
+    }
+
+    /// Global bindings that have been assigned so far.
+    pub fn globals(&self) -> Vec<(String, ObjectRef)> {
+        self.global_names
+            .iter()
+            .cloned()
+            .zip(self.vm.globals().iter().cloned())
+            .collect()
+    }
+
+    pub fn stack(&self) -> &[ObjectRef] {
+        self.vm.stack()
+    }
+
+    pub fn backtrace(&self) -> Vec<StackFrameInfo> {
+        self.vm.backtrace()
+    }
+
+    /// Execute one command line and return the text to show the user;
+    /// `None` means the session should end.
+    pub fn handle_command(&mut self, line: &str) -> Option<String> {
+        let mut parts = line.split_whitespace();
+        let command = parts.next().unwrap_or("");
+        let argument = parts.next();
+        let text = match command {
+            "" => String::new(),
+            "break" | "b" | "delete" => match argument.and_then(|arg| arg.parse().ok()) {
+                Some(line) if command == "delete" => {
+                    if self.clear_breakpoint(line) {
+                        format!("Breakpoint at line {line} removed")
+                    } else {
+                        format!("No breakpoint at line {line}")
+                    }
+                }
+                Some(line) => match self.set_breakpoint(line) {
+                    Some(resolved) => format!("Breakpoint set at line {resolved}"),
+                    None => format!("No code at or after line {line}"),
+                },
+                None => format!("Usage: {command} <line>"),
+            },
+            "continue" | "c" => self.report(|debugger| debugger.resume()),
+            "step" | "s" => self.report(|debugger| debugger.step_into()),
+            "next" | "n" => self.report(|debugger| debugger.step_over()),
+            "finish" | "out" => self.report(|debugger| debugger.step_out()),
+            "locals" => match self.locals() {
+                locals if locals.is_empty() => "(no locals)".to_string(),
+                locals => format_bindings(&locals),
+            },
+            "globals" => match self.globals() {
+                globals if globals.is_empty() => "(no globals)".to_string(),
+                globals => format_bindings(&globals),
+            },
+            "stack" => match self.stack() {
+                [] => "(empty stack)".to_string(),
+                stack => stack
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, value)| format!("  {idx}: {}", value.inspect()))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            },
+            "backtrace" | "bt" => self
+                .backtrace()
+                .iter()
+                .map(|frame| format!("  {}", frame.format_frame()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            "where" => self.describe_location(),
+            "help" => HELP.to_string(),
+            "quit" | "q" => return None,
+            other => format!("Unknown command: {other} (try 'help')"),
+        };
+        Some(text)
+    }
+
+    /// Interactive session on stdin/stdout used by `monkey debug`.
+    pub fn run_stdio(&mut self) -> i32 {
+        let stdin = io::stdin();
+        self.run_session(stdin.lock(), io::stdout())
+    }
+
+    /// Read commands from `input` until `quit` or end of input.
+    pub fn run_session(&mut self, mut input: impl BufRead, mut output: impl Write) -> i32 {
+        let mut line = String::new();
+        if writeln!(output, "{}", self.describe_location()).is_err() {
+            return 1;
+        }
+        loop {
+            if write!(output, "(debug) ").is_err() || output.flush().is_err() {
+                return 1;
+            }
+            line.clear();
+            match input.read_line(&mut line) {
+                Ok(0) => return 0,
+                Ok(_) => {}
+                Err(_) => return 1,
+            }
+            let Some(text) = self.handle_command(line.trim()) else {
+                return 0;
+            };
+            if !text.is_empty() && writeln!(output, "{text}").is_err() {
+                return 1;
+            }
+        }
+    }
+
+    fn pause_point(&self) -> Option<PausePoint> {
+        self.vm.position().map(|pos| PausePoint {
+            depth: self.vm.frame_depth(),
+            line: pos.line,
+        })
+    }
+
+    /// Step instructions until `stop(start, now)` holds, a breakpoint line
+    /// is entered, or the program ends.
+    fn run_until(&mut self, mut stop: impl FnMut(PausePoint, PausePoint) -> bool) -> StopReason {
+        if let Some(finished) = &self.finished {
+            return finished.clone();
+        }
+        let Some(start) = self.pause_point() else {
+            return StopReason::Finished(Object::Null.rc());
+        };
+        let mut previous = start;
+        loop {
+            let reason = match self.vm.step() {
+                StepOutcome::Continue => {
+                    let Some(now) = self.pause_point() else {
+                        continue;
+                    };
+                    // Returning to a caller resumes its line; only entering
+                    // a line (or a call) counts as a breakpoint hit.
+                    let entered = now != previous && now.depth >= previous.depth;
+                    if entered && self.breakpoints.contains(&now.line) {
+                        return StopReason::Breakpoint(now.line);
+                    }
+                    if stop(start, now) {
+                        return StopReason::Step;
+                    }
+                    previous = now;
+                    continue;
+                }
+                StepOutcome::Done(value) => StopReason::Finished(value),
+                StepOutcome::Error(err) => StopReason::Error(err),
+            };
+            self.finished = Some(reason.clone());
+            return reason;
+        }
+    }
+
+    /// Run `command` and describe where it stopped, after any program
+    /// output it produced.
+    fn report(&mut self, command: impl FnOnce(&mut Self) -> StopReason) -> String {
+        let reason = command(self);
+        let mut lines = self.vm.take_output();
+        match reason {
+            StopReason::Breakpoint(line) => {
+                lines.push(format!("Breakpoint hit at line {line}"));
+                lines.push(self.describe_location());
+            }
+            StopReason::Step => lines.push(self.describe_location()),
+            StopReason::Finished(value) => {
+                lines.push(format!("Program finished: {}", value.inspect()));
+            }
+            StopReason::Error(err) => lines.push(err.format_multiline()),
+        }
+        lines.join("\n")
+    }
+
+    fn describe_location(&self) -> String {
+        let (Some(line), Some(name)) = (self.line(), self.vm.current_function_name()) else {
+            return "Program finished".to_string();
+        };
+        let mut text = format!("Stopped in {name} at line {line}");
+        if let Some(source) = self.source_lines.get(line.wrapping_sub(1)) {
+            text.push_str(&format!("\n{line:>4} | {source}"));
+        }
+        text
+    }
+}
+
+fn format_bindings(bindings: &[(String, ObjectRef)]) -> String {
+    bindings
+        .iter()
+        .map(|(name, value)| format!("  {name} = {}", value.inspect()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,136 @@
+//! Errors and warnings as JSON lines for editors and CI, printed to stderr
+//! by `--error-format json`.
+//!
+//! Each diagnostic is one object on its own line:
+//! `{"file": "a.monkey", "kind": "parse", "code": "P001", "message": "...",
+//! "line": 1, "col": 5}`. `kind` is `parse`, `compile`, `runtime` or
+//! `warning`. Runtime errors add their protocol `type` (e.g.
+//! `DIVISION_BY_ZERO`) and a `stack` of `{"function", "line", "col",
+//! "args"}` frames, innermost first. A compile error without a position
+//! has `null` for `line` and `col`.
+
+use crate::compiler::{CompileError, CompileWarning};
+use crate::json::Json;
+use crate::parse_error::ParseError;
+use crate::position::Position;
+use crate::runner::RunnerError;
+use crate::runtime_error::RuntimeError;
+
+/// How `run`, `bench` and `check` print diagnostics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// The human-readable form, which matches the Java implementation.
+    #[default]
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+impl ErrorFormat {
+    /// The format named `text` or `json`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(ErrorFormat::Text),
+            "json" => Some(ErrorFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// One line per error in `err`, which was reported for `file`.
+pub fn runner_error_json(file: &str, err: &RunnerError) -> Vec<String> {
+    let diagnostics = match err {
+        RunnerError::Parse(errors) => errors.iter().map(|e| parse_error_json(file, e)).collect(),
+        RunnerError::Compile(errors) => {
+            errors.iter().map(|e| compile_error_json(file, e)).collect()
+        }
+        RunnerError::Runtime(err) => vec![runtime_error_json(file, err)],
+    };
+    diagnostics.iter().map(Json::render_line).collect()
+}
+
+/// One line per warning.
+pub fn warnings_json(file: &str, warnings: &[CompileWarning]) -> Vec<String> {
+    warnings
+        .iter()
+        .map(|warning| warning_json(file, warning).render_line())
+        .collect()
+}
+
+pub fn parse_error_json(file: &str, err: &ParseError) -> Json {
+    Json::Object(diagnostic(
+        file,
+        "parse",
+        err.error_code(),
+        &err.message,
+        Some(err.pos),
+    ))
+}
+
+pub fn compile_error_json(file: &str, err: &CompileError) -> Json {
+    Json::Object(diagnostic(
+        file,
+        "compile",
+        err.error_code(),
+        &err.message,
+        err.pos,
+    ))
+}
+
+pub fn warning_json(file: &str, warning: &CompileWarning) -> Json {
+    Json::Object(diagnostic(
+        file,
+        "warning",
+        warning.error_code(),
+        &warning.message,
+        Some(warning.pos),
+    ))
+}
+
+pub fn runtime_error_json(file: &str, err: &RuntimeError) -> Json {
+    let mut members = diagnostic(
+        file,
+        "runtime",
+        err.error_type.error_code(),
+        &err.message,
+        Some(err.pos),
+    );
+    members.insert(2, ("type", Json::string(err.error_type.code())));
+    let stack = err
+        .stack
+        .iter()
+        .map(|frame| {
+            Json::Object(vec![
+                ("function", Json::string(&frame.function_name)),
+                ("line", Json::Int(frame.pos.line as i64)),
+                ("col", Json::Int(frame.pos.col as i64)),
+                (
+                    "args",
+                    frame.arg_count.map_or(Json::Null, |n| Json::Int(n as i64)),
+                ),
+            ])
+        })
+        .collect();
+    members.push(("stack", Json::Array(stack)));
+    Json::Object(members)
+}
+
+fn diagnostic(
+    file: &str,
+    kind: &str,
+    code: &str,
+    message: &str,
+    pos: Option<Position>,
+) -> Vec<(&'static str, Json)> {
+    let (line, col) = pos.map_or((Json::Null, Json::Null), |pos| {
+        (Json::Int(pos.line as i64), Json::Int(pos.col as i64))
+    });
+    vec![
+        ("file", Json::string(file)),
+        ("kind", Json::string(kind)),
+        ("code", Json::string(code)),
+        ("message", Json::string(message)),
+        ("line", line),
+        ("col", col),
+    ]
+}
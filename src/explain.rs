@@ -0,0 +1,305 @@
+//! Extended descriptions of diagnostic codes, printed by `monkey explain`.
+//!
+//! Codes are stable: a code is never reused for a different kind of
+//! diagnostic, so they can be searched for and referenced in issues.
+//! Parse errors use `P`, compile errors `C`, runtime errors `R` and
+//! compiler warnings `W`.
+
+/// One diagnostic code and its extended description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Explanation {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    /// A program that reports this code, for codes a program can cause.
+    pub example: Option<&'static str>,
+}
+
+impl Explanation {
+    /// The text `monkey explain` prints.
+    pub fn render(&self) -> String {
+        let mut out = format!("{}: {}\n\n{}\n", self.code, self.title, self.description);
+        if let Some(example) = self.example {
+            out.push_str("\nExample:\n\n");
+            for line in example.lines() {
+                out.push_str(&format!("    {line}\n"));
+            }
+        }
+        out
+    }
+}
+
+/// Look up `code`, ignoring case.
+pub fn explain(code: &str) -> Option<&'static Explanation> {
+    EXPLANATIONS
+        .iter()
+        .find(|explanation| explanation.code.eq_ignore_ascii_case(code))
+}
+
+pub const EXPLANATIONS: &[Explanation] = &[
+    Explanation {
+        code: "P001",
+        title: "unexpected token",
+        description: "The parser needed a specific token next, such as `=` after the name in a \
+`let` or `{` after an `if` condition, and found something else. The error points at the token \
+it found.",
+        example: Some("let x 5;"),
+    },
+    Explanation {
+        code: "P002",
+        title: "token cannot start an expression",
+        description: "An expression was expected but the token there cannot begin one, for \
+example a `;` right after `=` or a stray closing bracket. Usually a value is missing or an \
+operator has no right-hand side.",
+        example: Some("let x = ;"),
+    },
+    Explanation {
+        code: "P003",
+        title: "invalid integer literal",
+        description: "Integer literals must fit in a signed 64-bit integer, so the largest is \
+9223372036854775807.",
+        example: Some("let big = 99999999999999999999;"),
+    },
+    Explanation {
+        code: "P004",
+        title: "expected parameter name",
+        description: "Function parameters are plain identifiers separated by commas. Literals, \
+patterns and default values are not allowed in a parameter list.",
+        example: Some("let f = fn(1) { 1 };"),
+    },
+    Explanation {
+        code: "P005",
+        title: "float literal",
+        description: "Monkey numbers are 64-bit integers. A literal with a fractional part or \
+an exponent, such as `1.5` or `2e3`, is read as one number so the error points at all of it, \
+but it cannot be used as a value.",
+        example: Some("let half = 0.5;"),
+    },
+    Explanation {
+        code: "P006",
+        title: "malformed number literal",
+        description: "A number has more than one decimal point, as in `1.2.3`. The whole \
+literal is reported rather than being split into separate numbers.",
+        example: Some("let version = 1.2.3;"),
+    },
+    Explanation {
+        code: "P007",
+        title: "expression too deeply nested",
+        description: "Expressions are nested inside each other more deeply than the parser \
+allows, 128 levels by default, such as hundreds of parentheses around a value or arrays \
+inside arrays. Parsing stops there instead of overflowing the stack. Bind inner parts to \
+names with `let` to flatten the expression.",
+        example: None,
+    },
+    Explanation {
+        code: "C001",
+        title: "unresolved identifier",
+        description: "The name is not bound by a `let`, a parameter or a builtin where it is \
+used. Check the spelling, and that the `let` comes before the use: a global cannot be read \
+before the statement that defines it. When a visible name is spelled similarly, the message \
+suggests it.",
+        example: Some("puts(y);"),
+    },
+    Explanation {
+        code: "C002",
+        title: "unsupported operator",
+        description: "The compiler has no instruction for the operator. The parser only \
+produces operators the compiler knows, so this is reported for hand-built syntax trees.",
+        example: None,
+    },
+    Explanation {
+        code: "C003",
+        title: "not supported by the backend",
+        description: "The construct works on the VM but not with the backend that was asked \
+for. `monkey wasm` only handles integers, booleans, strings, top-level functions and loops; \
+arrays, hashes, closures and most builtins need `monkey run`.",
+        example: Some("let a = [1, 2];"),
+    },
+    Explanation {
+        code: "C004",
+        title: "backend type mismatch",
+        description: "The backend works out every value's type when compiling, and an \
+operation is applied to a type it does not support. The VM would report this as a runtime \
+error instead.",
+        example: Some("-\"a\";"),
+    },
+    Explanation {
+        code: "C005",
+        title: "internal compiler error",
+        description: "An invariant of the compiler itself broke. This is a bug in the \
+compiler rather than in the program; please report it with the program that triggers it.",
+        example: None,
+    },
+    Explanation {
+        code: "C006",
+        title: "binding used outside its block",
+        description: "A `let` inside an `if` branch or a `while` body that introduces a new \
+name is local to that block; the name cannot be used once the block ends. A `let` of a name \
+that is already visible rebinds it instead, and the new value outlives the block. Define the \
+name before the block to use it afterwards.",
+        example: Some("let c = true;\nif (c) { let x = 1; }\nputs(x);"),
+    },
+    Explanation {
+        code: "C007",
+        title: "redefinition",
+        description: "A name was defined twice in the same block, or a function has two \
+parameters with the same name. This is only an error when the compiler is configured with \
+`RedefinitionPolicy::Error`; by default it is warning W004. Rebinding a name from an inner \
+block, as loops do with their counters, is not a redefinition.",
+        example: None,
+    },
+    Explanation {
+        code: "R001",
+        title: "TYPE_MISMATCH",
+        description: "An operator was applied to operands of types it does not combine, such \
+as adding an integer to a string or negating a string. Monkey never converts between types \
+implicitly.",
+        example: Some("1 + \"a\";"),
+    },
+    Explanation {
+        code: "R002",
+        title: "UNKNOWN_IDENTIFIER",
+        description: "A global was read before any value was stored in its slot. The compiler \
+rejects such programs up front (C001), so this only happens with bytecode that was built or \
+edited by hand.",
+        example: None,
+    },
+    Explanation {
+        code: "R003",
+        title: "NOT_CALLABLE",
+        description: "Only functions, closures and builtins can be called. The error names \
+the type of the value that was called instead.",
+        example: Some("let x = 1; x();"),
+    },
+    Explanation {
+        code: "R004",
+        title: "WRONG_ARGUMENT_COUNT",
+        description: "Functions must be called with exactly as many arguments as they have \
+parameters; there are no optional or variadic parameters, except in some builtins.",
+        example: Some("let f = fn(a) { a }; f(1, 2);"),
+    },
+    Explanation {
+        code: "R005",
+        title: "INVALID_ARGUMENT_TYPE",
+        description: "A builtin was passed an argument of a type it does not accept, for \
+example `len` of an integer.",
+        example: Some("len(1);"),
+    },
+    Explanation {
+        code: "R006",
+        title: "INVALID_CONTROL_FLOW",
+        description: "`break` and `continue` only work inside a `while` loop. Outside one \
+they compile but fail when reached.",
+        example: Some("break;"),
+    },
+    Explanation {
+        code: "R007",
+        title: "INVALID_INDEX",
+        description: "Arrays are indexed by integers and strings cannot be indexed. Indexing \
+past the end of an array is not an error; it gives `null`.",
+        example: Some("[1][\"a\"];"),
+    },
+    Explanation {
+        code: "R008",
+        title: "UNHASHABLE",
+        description: "Hash keys must be integers, booleans or strings. Arrays, hashes and \
+functions cannot be used as keys.",
+        example: Some("{[1]: 2};"),
+    },
+    Explanation {
+        code: "R009",
+        title: "DIVISION_BY_ZERO",
+        description: "Integer division by zero.",
+        example: Some("1 / 0;"),
+    },
+    Explanation {
+        code: "R010",
+        title: "UNSUPPORTED_OPERATION",
+        description: "The operand types are valid together but the operator is not defined \
+for them, such as subtracting strings.",
+        example: Some("\"a\" - \"b\";"),
+    },
+    Explanation {
+        code: "R011",
+        title: "EVAL_ERROR",
+        description: "The string passed to `eval` failed to parse or compile. The message \
+includes the errors `eval` reported, with positions inside the string.",
+        example: Some("eval(\"let\");"),
+    },
+    Explanation {
+        code: "R012",
+        title: "STACK_OVERFLOW",
+        description: "Calls nested deeper than the VM's frame limit, usually through \
+recursion without a base case. Only the innermost and outermost frames are kept in the stack \
+trace.",
+        example: Some("let f = fn() { f() }; f();"),
+    },
+    Explanation {
+        code: "R013",
+        title: "BUDGET_EXCEEDED",
+        description: "The program ran more instructions than the budget set with \
+`VmOptions::max_instructions` allows. The CLI does not set a budget.",
+        example: None,
+    },
+    Explanation {
+        code: "R014",
+        title: "TIMEOUT",
+        description: "The program was still running when the `--timeout` given to \
+`monkey run` ran out.",
+        example: None,
+    },
+    Explanation {
+        code: "R015",
+        title: "OUT_OF_MEMORY",
+        description: "The program kept more strings, arrays and hashes reachable than \
+`VmOptions::max_memory_bytes` allows. \
+The CLI does not set a limit.",
+        example: None,
+    },
+    Explanation {
+        code: "R016",
+        title: "ASSERTION_FAILED",
+        description: "`assert` was called with a condition that is `false` or `null`. The \
+message is `assertion failed`, followed by the second argument when one was given. \
+`monkey test` reports these as test failures.",
+        example: Some("assert(1 > 2, \"one is not greater than two\");"),
+    },
+    Explanation {
+        code: "W001",
+        title: "unused variable",
+        description: "A `let` binding is never read. Remove it, or start its name with `_` \
+to keep it and silence the warning.",
+        example: Some("let unused = 1;"),
+    },
+    Explanation {
+        code: "W002",
+        title: "unused parameter",
+        description: "A function parameter is never read. Start its name with `_` when the \
+function must accept it anyway.",
+        example: Some("let f = fn(x) { 1 }; f(2);"),
+    },
+    Explanation {
+        code: "W003",
+        title: "unreachable code",
+        description: "Statements after a `return`, `break` or `continue` in the same block, or \
+the branch of an `if` whose condition is a literal that never selects it, can never run. \
+They are still checked but no code is emitted for them.",
+        example: Some("let f = fn() { return 1; puts(2); }; f();"),
+    },
+    Explanation {
+        code: "W004",
+        title: "redefinition",
+        description: "A `let` defines a name that is already defined in the same block, or a \
+function has two parameters with the same name. The second definition replaces the first, \
+which is usually a mistake; pick a new name, or rebind it from an inner block when the \
+update is intended. `RedefinitionPolicy::Error` turns this into error C007.",
+        example: Some("let x = 1;\nlet x = 2;\nputs(x);"),
+    },
+    Explanation {
+        code: "W005",
+        title: "shadowed builtin",
+        description: "A `let` or parameter uses the name of a builtin function such as `len` \
+or `puts`, hiding the builtin wherever the binding is visible.",
+        example: Some("let len = 3;\nputs(len);"),
+    },
+];
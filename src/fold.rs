@@ -0,0 +1,179 @@
+//! Rebuilding the AST, replacing nodes along the way, for desugaring
+//! passes and AST-level optimizations.
+//!
+//! Implement `Folder` and override the methods for the nodes to rewrite.
+//! Every default calls the free function of the same name, which rebuilds
+//! the node from its folded children; an override that returns a node
+//! without calling it leaves that node's children as they were. Positions
+//! are whatever the folder gives the new nodes, and `Program`'s span tables
+//! are carried over unchanged.
+
+use crate::ast::{BlockStatement, Expression, Identifier, Program, Statement};
+
+pub trait Folder {
+    fn fold_program(&mut self, program: Program) -> Program {
+        fold_program(self, program)
+    }
+
+    /// Fold a program's or block's statements. Override to insert or drop
+    /// statements.
+    fn fold_statements(&mut self, statements: Vec<Statement>) -> Vec<Statement> {
+        fold_statements(self, statements)
+    }
+
+    fn fold_statement(&mut self, statement: Statement) -> Statement {
+        fold_statement(self, statement)
+    }
+
+    fn fold_block(&mut self, block: BlockStatement) -> BlockStatement {
+        fold_block(self, block)
+    }
+
+    fn fold_expression(&mut self, expression: Expression) -> Expression {
+        fold_expression(self, expression)
+    }
+
+    /// A name being bound: a `let` name or a function parameter.
+    fn fold_binding(&mut self, name: Identifier) -> Identifier {
+        name
+    }
+}
+
+pub fn fold_program<F: Folder + ?Sized>(folder: &mut F, program: Program) -> Program {
+    Program {
+        statements: folder.fold_statements(program.statements),
+        ..program
+    }
+}
+
+pub fn fold_statements<F: Folder + ?Sized>(
+    folder: &mut F,
+    statements: Vec<Statement>,
+) -> Vec<Statement> {
+    statements
+        .into_iter()
+        .map(|statement| folder.fold_statement(statement))
+        .collect()
+}
+
+pub fn fold_statement<F: Folder + ?Sized>(folder: &mut F, statement: Statement) -> Statement {
+    match statement {
+        Statement::Let { name, value, pos } => Statement::Let {
+            name: folder.fold_binding(name),
+            value: folder.fold_expression(value),
+            pos,
+        },
+        Statement::Return { value, pos } => Statement::Return {
+            value: folder.fold_expression(value),
+            pos,
+        },
+        Statement::While {
+            condition,
+            body,
+            pos,
+        } => Statement::While {
+            condition: folder.fold_expression(condition),
+            body: folder.fold_block(body),
+            pos,
+        },
+        Statement::Break { .. } | Statement::Continue { .. } => statement,
+        Statement::Expression { expression, pos } => Statement::Expression {
+            expression: folder.fold_expression(expression),
+            pos,
+        },
+    }
+}
+
+pub fn fold_block<F: Folder + ?Sized>(folder: &mut F, block: BlockStatement) -> BlockStatement {
+    BlockStatement {
+        statements: folder.fold_statements(block.statements),
+        pos: block.pos,
+    }
+}
+
+pub fn fold_expression<F: Folder + ?Sized>(folder: &mut F, expression: Expression) -> Expression {
+    match expression {
+        Expression::Identifier { .. }
+        | Expression::IntegerLiteral { .. }
+        | Expression::BooleanLiteral { .. }
+        | Expression::StringLiteral { .. } => expression,
+        Expression::Prefix {
+            operator,
+            right,
+            pos,
+        } => Expression::Prefix {
+            operator,
+            right: Box::new(folder.fold_expression(*right)),
+            pos,
+        },
+        Expression::Infix {
+            left,
+            operator,
+            right,
+            pos,
+        } => Expression::Infix {
+            left: Box::new(folder.fold_expression(*left)),
+            operator,
+            right: Box::new(folder.fold_expression(*right)),
+            pos,
+        },
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+            pos,
+        } => Expression::If {
+            condition: Box::new(folder.fold_expression(*condition)),
+            consequence: folder.fold_block(consequence),
+            alternative: alternative.map(|alternative| folder.fold_block(alternative)),
+            pos,
+        },
+        Expression::FunctionLiteral {
+            parameters,
+            body,
+            pos,
+        } => Expression::FunctionLiteral {
+            parameters: parameters
+                .into_iter()
+                .map(|parameter| folder.fold_binding(parameter))
+                .collect(),
+            body: folder.fold_block(body),
+            pos,
+        },
+        Expression::Call {
+            function,
+            arguments,
+            pos,
+        } => Expression::Call {
+            function: Box::new(folder.fold_expression(*function)),
+            arguments: fold_expressions(folder, arguments),
+            pos,
+        },
+        Expression::ArrayLiteral { elements, pos } => Expression::ArrayLiteral {
+            elements: fold_expressions(folder, elements),
+            pos,
+        },
+        Expression::HashLiteral { pairs, pos } => Expression::HashLiteral {
+            pairs: pairs
+                .into_iter()
+                .map(|(key, value)| (folder.fold_expression(key), folder.fold_expression(value)))
+                .collect(),
+            pos,
+        },
+        Expression::Index { left, index, pos } => Expression::Index {
+            left: Box::new(folder.fold_expression(*left)),
+            index: Box::new(folder.fold_expression(*index)),
+            pos,
+        },
+    }
+}
+
+fn fold_expressions<F: Folder + ?Sized>(
+    folder: &mut F,
+    expressions: Vec<Expression>,
+) -> Vec<Expression> {
+    expressions
+        .into_iter()
+        .map(|expression| folder.fold_expression(expression))
+        .collect()
+}
@@ -0,0 +1,537 @@
+//! Source formatter behind `monkey fmt`.
+//!
+//! The program is reprinted from its AST with canonical spacing and
+//! indentation and only the parentheses precedence requires, while the CST
+//! it was parsed with supplies what the AST drops: comments, blank lines
+//! between statements (at most one is kept), whether an expression
+//! statement ended in `;`, and whether a block with a single statement was
+//! written on one line. Argument, element and pair lists that would run
+//! past the maximum width are broken one item per line.
+//!
+//! A statement with a comment inside an expression, where the comment has
+//! no line of its own to go on, is kept as written apart from its
+//! indentation.
+//!
+//! The same printer lays out `monkey --ast --format pretty`, without a CST
+//! and with every operator expression parenthesized the way the AST's
+//! `Display` does.
+
+use crate::ast::{BlockStatement, Expression, Program, Statement};
+use crate::cst::{Cst, CstNode, NodeKind, TriviaKind};
+use crate::parse_error::ParseError;
+use crate::token::TokenKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Spaces per nesting level.
+    pub indent_width: usize,
+    /// Column past which lists are broken over several lines.
+    pub max_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent_width: 2,
+            max_width: 100,
+        }
+    }
+}
+
+impl FormatOptions {
+    pub fn with_indent_width(mut self, indent_width: usize) -> Self {
+        self.indent_width = indent_width;
+        self
+    }
+
+    pub fn with_max_width(mut self, max_width: usize) -> Self {
+        self.max_width = max_width;
+        self
+    }
+}
+
+/// `source` formatted, ending in a newline unless it has no code or
+/// comments at all.
+pub fn format_source(source: &str, options: FormatOptions) -> Result<String, Vec<ParseError>> {
+    let cst = Cst::parse(source)?;
+    let printer = Printer {
+        options,
+        cst: Some(&cst),
+        parenthesize: false,
+    };
+    let lines = printer.statement_list(
+        &cst.program().statements,
+        Some(&cst.root().children),
+        0,
+        Some(cst.tokens().len() - 1),
+    );
+    if lines.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(lines.join("\n") + "\n")
+}
+
+/// `program` in the notation of its `Display`, but with blocks broken over
+/// indented lines and long lists wrapped. Unlike `format_source` there is
+/// no trailing newline.
+pub(crate) fn layout_program(program: &Program, options: FormatOptions) -> String {
+    let printer = Printer {
+        options,
+        cst: None,
+        parenthesize: true,
+    };
+    printer
+        .statement_list(&program.statements, None, 0, None)
+        .join("\n")
+}
+
+/// The CST node mirroring the AST node being printed, when there is one.
+type Node<'a> = Option<&'a CstNode>;
+
+fn child(node: Node<'_>, idx: usize) -> Node<'_> {
+    node.and_then(|node| node.children.get(idx))
+}
+
+/// Binding strength of an expression's outermost operator, as the parser
+/// sees it: operands that bind more loosely than their parent need
+/// parentheses.
+fn precedence(expression: &Expression) -> u8 {
+    match expression {
+        Expression::Infix { operator, .. } => infix_precedence(operator),
+        Expression::Prefix { .. } => 7,
+        Expression::Call { .. } => 8,
+        Expression::Index { .. } => 9,
+        _ => 10,
+    }
+}
+
+fn infix_precedence(operator: &str) -> u8 {
+    match operator {
+        "||" => 1,
+        "&&" => 2,
+        "==" | "!=" => 3,
+        "<" | ">" | "<=" | ">=" => 4,
+        "+" | "-" => 5,
+        _ => 6,
+    }
+}
+
+/// The column just past `text` when it starts at `col`. Lines after the
+/// first are already indented absolutely.
+fn end_col(col: usize, text: &str) -> usize {
+    match text.rsplit_once('\n') {
+        Some((_, last)) => last.chars().count(),
+        None => col + text.chars().count(),
+    }
+}
+
+struct Printer<'a> {
+    options: FormatOptions,
+    cst: Option<&'a Cst>,
+    /// Wrap prefix, infix and index expressions in parentheses, as the
+    /// AST's `Display` does, instead of adding only those precedence needs.
+    parenthesize: bool,
+}
+
+impl Printer<'_> {
+    fn indent(&self, level: usize) -> String {
+        " ".repeat(level * self.options.indent_width)
+    }
+
+    /// The lines of a program's or block's statements at `level`, with the
+    /// comments before each of them and before `closing`, the index of the
+    /// `}` or `Eof` token that ends the list.
+    fn statement_list(
+        &self,
+        statements: &[Statement],
+        nodes: Option<&[CstNode]>,
+        level: usize,
+        closing: Option<usize>,
+    ) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (idx, statement) in statements.iter().enumerate() {
+            let node = nodes.and_then(|nodes| nodes.get(idx));
+            if let Some(node) = node {
+                self.comments_before(node.tokens.start, level, &mut lines, false);
+            }
+            let text = self.statement(statement, node, level);
+            lines.push(format!("{}{text}", self.indent(level)));
+        }
+        if let Some(closing) = closing {
+            self.comments_before(closing, level, &mut lines, true);
+        }
+        lines
+    }
+
+    /// Emit the comments in the trivia before token `idx`. One on the same
+    /// line as the previous token trails the last line; the others get
+    /// lines of their own. A blank line in the trivia is kept, except at
+    /// the start and end of the list.
+    fn comments_before(&self, idx: usize, level: usize, lines: &mut Vec<String>, closing: bool) {
+        let Some(cst) = self.cst else {
+            return;
+        };
+        let tokens = cst.tokens();
+        let previous_line = idx.checked_sub(1).map(|prev| tokens[prev].end.line);
+        let mut blank = false;
+        for trivia in &tokens[idx].leading {
+            match trivia.kind {
+                TriviaKind::Whitespace => blank |= trivia.text.matches('\n').count() > 1,
+                TriviaKind::Comment => {
+                    let text = trivia.text.trim_end();
+                    match lines.last_mut() {
+                        Some(last) if Some(trivia.pos.line) == previous_line => {
+                            last.push(' ');
+                            last.push_str(text);
+                        }
+                        _ => {
+                            if blank && !lines.is_empty() {
+                                lines.push(String::new());
+                            }
+                            lines.push(format!("{}{text}", self.indent(level)));
+                        }
+                    }
+                    blank = false;
+                }
+            }
+        }
+        if blank && !closing && !lines.is_empty() {
+            lines.push(String::new());
+        }
+    }
+
+    fn has_comments_before(&self, idx: usize) -> bool {
+        self.cst
+            .is_some_and(|cst| cst.tokens()[idx].comments().next().is_some())
+    }
+
+    /// The statement at `level`, without indentation on its first line.
+    fn statement(&self, statement: &Statement, node: Node<'_>, level: usize) -> String {
+        if let Some(node) = node.filter(|node| self.has_inner_comments(node)) {
+            return self.verbatim(node, level);
+        }
+        let col = level * self.options.indent_width;
+        match statement {
+            Statement::Let { name, value, .. } => {
+                let head = format!("let {} = ", name.value);
+                let value = self.expression(value, child(node, 1), level, col + head.len());
+                format!("{head}{value};")
+            }
+            Statement::Return { value, .. } => {
+                let value = self.expression(value, child(node, 0), level, col + 7);
+                format!("return {value};")
+            }
+            Statement::While {
+                condition, body, ..
+            } => {
+                let condition = self.expression(condition, child(node, 0), level, col + 7);
+                let head = format!("while ({condition}) ");
+                let body = self.block(body, child(node, 1), level, end_col(col, &head));
+                format!("{head}{body}")
+            }
+            Statement::Break { .. } => "break;".to_string(),
+            Statement::Continue { .. } => "continue;".to_string(),
+            Statement::Expression { expression, .. } => {
+                let mut text = self.expression(expression, child(node, 0), level, col);
+                let semicolon = match (self.cst, node) {
+                    (Some(cst), Some(node)) => {
+                        cst.tokens()[node.tokens.end - 1].kind == TokenKind::Semicolon
+                    }
+                    _ => true,
+                };
+                if semicolon {
+                    text.push(';');
+                }
+                text
+            }
+        }
+    }
+
+    /// Whether a comment sits between two tokens of `node` outside the
+    /// blocks nested in it, where `statement_list` would not place it.
+    fn has_inner_comments(&self, node: &CstNode) -> bool {
+        let mut blocks = Vec::new();
+        outer_blocks(node, &mut blocks);
+        (node.tokens.start + 1..node.tokens.end).any(|idx| {
+            self.has_comments_before(idx)
+                && !blocks
+                    .iter()
+                    .any(|block| block.tokens.start < idx && idx < block.tokens.end)
+        })
+    }
+
+    /// `node` as written, re-indented to `level`.
+    fn verbatim(&self, node: &CstNode, level: usize) -> String {
+        let Some(cst) = self.cst else {
+            return String::new();
+        };
+        let original = cst.tokens()[node.tokens.start].pos.col - 1;
+        let indent = self.indent(level);
+        let text = cst.text(node);
+        let mut lines = text.lines().map(str::trim_end);
+        let mut out = lines.next().unwrap_or_default().to_string();
+        for line in lines {
+            let strip = line
+                .char_indices()
+                .take(original)
+                .take_while(|(_, ch)| ch.is_whitespace())
+                .last()
+                .map_or(0, |(idx, ch)| idx + ch.len_utf8());
+            out.push('\n');
+            if !line.is_empty() {
+                out.push_str(&indent);
+                out.push_str(&line[strip..]);
+            }
+        }
+        out
+    }
+
+    /// `block` with its braces, the `{` at column `col`.
+    fn block(&self, block: &BlockStatement, node: Node<'_>, level: usize, col: usize) -> String {
+        let closing = node.map(|node| node.tokens.end - 1);
+        let commented = node.is_some_and(|node| {
+            (node.tokens.start + 1..node.tokens.end).any(|idx| self.has_comments_before(idx))
+        });
+        if block.statements.is_empty() && !closing.is_some_and(|idx| self.has_comments_before(idx))
+        {
+            return "{}".to_string();
+        }
+        let one_line = match (self.cst, node) {
+            (Some(cst), Some(node)) => {
+                let tokens = cst.tokens();
+                tokens[node.tokens.start].pos.line == tokens[node.tokens.end - 1].pos.line
+            }
+            _ => false,
+        };
+        if one_line && !commented && block.statements.len() == 1 {
+            let statement = self.statement(&block.statements[0], child(node, 0), level);
+            if !statement.contains('\n')
+                && col + statement.chars().count() + 4 <= self.options.max_width
+            {
+                return format!("{{ {statement} }}");
+            }
+        }
+        let lines = self.statement_list(
+            &block.statements,
+            node.map(|node| node.children.as_slice()),
+            level + 1,
+            closing,
+        );
+        format!("{{\n{}\n{}}}", lines.join("\n"), self.indent(level))
+    }
+
+    /// `expression` starting at column `col`, nested `level` deep.
+    fn expression(
+        &self,
+        expression: &Expression,
+        node: Node<'_>,
+        level: usize,
+        col: usize,
+    ) -> String {
+        match expression {
+            Expression::Identifier { value, .. } => value.clone(),
+            Expression::IntegerLiteral { raw, .. } => raw.clone(),
+            Expression::BooleanLiteral { value, .. } => value.to_string(),
+            Expression::StringLiteral { value, .. } => format!("\"{value}\""),
+            Expression::Prefix {
+                operator, right, ..
+            } if self.parenthesize => {
+                let right = self.expression(right, child(node, 0), level, col + operator.len() + 1);
+                format!("({operator}{right})")
+            }
+            Expression::Prefix {
+                operator, right, ..
+            } => {
+                let right = self.operand(right, child(node, 0), 7, level, col + operator.len());
+                format!("{operator}{right}")
+            }
+            Expression::Infix {
+                left,
+                operator,
+                right,
+                ..
+            } if self.parenthesize => {
+                let left = self.expression(left, child(node, 0), level, col + 1);
+                let col = end_col(col + 1, &left) + operator.len() + 2;
+                let right = self.expression(right, child(node, 1), level, col);
+                format!("({left} {operator} {right})")
+            }
+            Expression::Infix {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let strength = infix_precedence(operator);
+                let left = self.operand(left, child(node, 0), strength, level, col);
+                let col = end_col(col, &left) + operator.len() + 2;
+                // Operators are left-associative, so an equally strong
+                // right operand was parenthesized.
+                let right = self.operand(right, child(node, 1), strength + 1, level, col);
+                format!("{left} {operator} {right}")
+            }
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+                ..
+            } => {
+                let condition = self.expression(condition, child(node, 0), level, col + 4);
+                let head = format!("if ({condition}) ");
+                let mut out = head.clone();
+                out.push_str(&self.block(consequence, child(node, 1), level, end_col(col, &head)));
+                if let Some(alternative) = alternative {
+                    out.push_str(" else ");
+                    let col = end_col(col, &out);
+                    out.push_str(&self.block(alternative, child(node, 2), level, col));
+                }
+                out
+            }
+            Expression::FunctionLiteral {
+                parameters, body, ..
+            } => {
+                let body_node = child(node, parameters.len());
+                let parameters = parameters
+                    .iter()
+                    .map(|parameter| parameter.value.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let head = format!("fn({parameters}) ");
+                let body = self.block(body, body_node, level, end_col(col, &head));
+                format!("{head}{body}")
+            }
+            Expression::Call {
+                function,
+                arguments,
+                ..
+            } => {
+                let function = self.operand(function, child(node, 0), 8, level, col);
+                let arguments = self.list(
+                    "(",
+                    ")",
+                    arguments.len(),
+                    |printer, idx, level, col| {
+                        printer.expression(&arguments[idx], child(node, idx + 1), level, col)
+                    },
+                    level,
+                    end_col(col, &function),
+                );
+                format!("{function}{arguments}")
+            }
+            Expression::ArrayLiteral { elements, .. } => self.list(
+                "[",
+                "]",
+                elements.len(),
+                |printer, idx, level, col| {
+                    printer.expression(&elements[idx], child(node, idx), level, col)
+                },
+                level,
+                col,
+            ),
+            Expression::HashLiteral { pairs, .. } => self.list(
+                "{",
+                "}",
+                pairs.len(),
+                |printer, idx, level, col| {
+                    let (key, value) = &pairs[idx];
+                    let key = printer.expression(key, child(node, 2 * idx), level, col);
+                    let col = end_col(col, &key) + 2;
+                    let value = printer.expression(value, child(node, 2 * idx + 1), level, col);
+                    format!("{key}: {value}")
+                },
+                level,
+                col,
+            ),
+            Expression::Index { left, index, .. } if self.parenthesize => {
+                let left = self.expression(left, child(node, 0), level, col + 1);
+                let col = end_col(col + 1, &left) + 1;
+                let index = self.expression(index, child(node, 1), level, col);
+                format!("({left}[{index}])")
+            }
+            Expression::Index { left, index, .. } => {
+                let left = self.operand(left, child(node, 0), 9, level, col);
+                let index = self.expression(index, child(node, 1), level, end_col(col, &left) + 1);
+                format!("{left}[{index}]")
+            }
+        }
+    }
+
+    /// `expression` as the operand of an operator of `strength`,
+    /// parenthesized when it binds more loosely.
+    fn operand(
+        &self,
+        expression: &Expression,
+        node: Node<'_>,
+        strength: u8,
+        level: usize,
+        col: usize,
+    ) -> String {
+        if self.parenthesize || precedence(expression) >= strength {
+            return self.expression(expression, node, level, col);
+        }
+        format!("({})", self.expression(expression, node, level, col + 1))
+    }
+
+    /// `count` items between `open` and `close`, on one line when they
+    /// fit, otherwise one per line. Whether they fit is judged before
+    /// breaking any list nested in them.
+    fn list(
+        &self,
+        open: &str,
+        close: &str,
+        count: usize,
+        item: impl Fn(&Printer<'_>, usize, usize, usize) -> String,
+        level: usize,
+        col: usize,
+    ) -> String {
+        let flat = |printer: &Printer<'_>| {
+            let mut flat = String::from(open);
+            for idx in 0..count {
+                if idx > 0 {
+                    flat.push_str(", ");
+                }
+                let col = end_col(col, &flat);
+                flat.push_str(&item(printer, idx, level, col));
+            }
+            flat.push_str(close);
+            flat
+        };
+        let unbounded = Printer {
+            options: self.options.with_max_width(usize::MAX),
+            ..*self
+        };
+        let fits = |text: &str| {
+            col + text.lines().next().unwrap_or_default().chars().count() <= self.options.max_width
+        };
+        let candidate = flat(&unbounded);
+        if !candidate.contains('\n') && fits(&candidate) {
+            return candidate;
+        }
+        // Lists whose items span lines anyway, such as a callback with a
+        // block body, stay flat while their first line fits.
+        let candidate = flat(self);
+        if candidate.contains('\n') && fits(&candidate) {
+            return candidate;
+        }
+        let indent = self.indent(level + 1);
+        let items = (0..count)
+            .map(|idx| format!("{indent}{}", item(self, idx, level + 1, indent.len())))
+            .collect::<Vec<_>>();
+        format!(
+            "{open}\n{}\n{}{close}",
+            items.join(",\n"),
+            self.indent(level)
+        )
+    }
+}
+
+/// The blocks nested in `node` that are not inside another of them.
+fn outer_blocks<'a>(node: &'a CstNode, blocks: &mut Vec<&'a CstNode>) {
+    for child in &node.children {
+        if child.kind == NodeKind::Block {
+            blocks.push(child);
+        } else {
+            outer_blocks(child, blocks);
+        }
+    }
+}
@@ -0,0 +1,733 @@
+//! Random, well-formed Monkey programs for fuzzing and differential
+//! testing.
+//!
+//! `generate` is deterministic in its seed, and every program it returns
+//! parses, compiles and runs to completion without a runtime error, so an
+//! error or a disagreement between two ways of running one is a bug.
+//! Generation is type-directed: names are only read once bound, division
+//! is by non-zero literals, indexing stays in bounds, and every integer
+//! carries a bound on its magnitude that keeps arithmetic from overflowing.
+//! Loops count up to a small literal and functions only call functions
+//! defined before them, so programs terminate.
+
+/// Largest magnitude any integer reaches.
+const INT_LIMIT: u64 = 1 << 40;
+/// Largest magnitude of a call argument, which parameters assume.
+const ARG_LIMIT: u64 = 1_000;
+/// Longest string.
+const STR_LIMIT: u64 = 48;
+/// Most calls, counting those the callees make, that one function body or
+/// the top level may make.
+const CALL_BUDGET: u64 = 2_000;
+/// Most iterations of one loop.
+const MAX_ITERATIONS: u64 = 5;
+
+const WORDS: [&str; 8] = ["", "a", "ok", "x y", "42", "Hello", "monkey", "banana"];
+const KEYS: [&str; 4] = ["a", "b", "key", "z"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenOptions {
+    /// Top-level statements before the closing `puts`.
+    pub statements: usize,
+    /// Deepest expression nesting; blocks nest at most this deep too.
+    pub max_depth: usize,
+    /// Function definitions, calls and closures.
+    pub functions: bool,
+    /// `while` loops, `break` and `continue`.
+    pub loops: bool,
+    /// Arrays, hashes and their builtins.
+    pub collections: bool,
+    /// String literals, concatenation and comparison.
+    pub strings: bool,
+}
+
+impl Default for GenOptions {
+    fn default() -> Self {
+        Self {
+            statements: 12,
+            max_depth: 4,
+            functions: true,
+            loops: true,
+            collections: true,
+            strings: true,
+        }
+    }
+}
+
+impl GenOptions {
+    pub fn with_statements(mut self, statements: usize) -> Self {
+        self.statements = statements;
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_functions(mut self, functions: bool) -> Self {
+        self.functions = functions;
+        self
+    }
+
+    pub fn with_loops(mut self, loops: bool) -> Self {
+        self.loops = loops;
+        self
+    }
+
+    pub fn with_collections(mut self, collections: bool) -> Self {
+        self.collections = collections;
+        self
+    }
+
+    pub fn with_strings(mut self, strings: bool) -> Self {
+        self.strings = strings;
+        self
+    }
+}
+
+/// The program for `seed`, one statement per line.
+pub fn generate(seed: u64, options: GenOptions) -> String {
+    let mut generator = Generator {
+        rng: Rng(seed),
+        options,
+        names: 0,
+        scope: Vec::new(),
+        loops: Vec::new(),
+        accumulators: Vec::new(),
+        calls: 0,
+        blocks: 0,
+        lines: Vec::new(),
+    };
+    generator.program();
+    let mut program = generator.lines.join("\n");
+    program.push('\n');
+    program
+}
+
+/// SplitMix64, so a seed means the same program everywhere.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n`; `n` must be non-zero.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len() as u64) as usize]
+    }
+}
+
+/// What the generator knows about a value.
+#[derive(Debug, Clone)]
+enum Shape {
+    /// An integer of at most this magnitude.
+    Int(u64),
+    Bool,
+    /// A string of at most this length.
+    Str(u64),
+    /// Exactly `len` integers of at most magnitude `bound`.
+    Array {
+        len: usize,
+        bound: u64,
+    },
+    /// Integers of at most magnitude `bound` under each of `keys`.
+    Hash {
+        keys: Vec<&'static str>,
+        bound: u64,
+    },
+    /// A function of `arity` integers returning `ret` that makes at most
+    /// `calls` calls.
+    Func {
+        arity: usize,
+        ret: Box<Shape>,
+        calls: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Int,
+    Bool,
+    Str,
+    Array,
+    Hash,
+}
+
+struct Binding {
+    name: String,
+    shape: Shape,
+    /// Cleared while the binding is an accumulator of an enclosing loop,
+    /// since its value then grows between iterations.
+    readable: bool,
+}
+
+/// An integer bound just before a loop that only its own
+/// `let acc = acc + ...;` statements read inside the loop.
+struct Accumulator {
+    name: String,
+    bound: u64,
+    /// Index in `Generator::loops` of the loop it belongs to.
+    level: usize,
+}
+
+struct Generator {
+    rng: Rng,
+    options: GenOptions,
+    names: usize,
+    scope: Vec<Binding>,
+    /// Iteration counts of the loops around the current statement in the
+    /// current function body, innermost last.
+    loops: Vec<u64>,
+    accumulators: Vec<Accumulator>,
+    /// Calls made so far by the current function body or the top level.
+    calls: u64,
+    blocks: usize,
+    lines: Vec<String>,
+}
+
+impl Generator {
+    fn program(&mut self) {
+        for _ in 0..self.options.statements {
+            self.statement(0);
+        }
+        let values: Vec<String> = self
+            .scope
+            .iter()
+            .filter(|binding| !matches!(binding.shape, Shape::Func { .. }))
+            .map(|binding| binding.name.clone())
+            .collect();
+        if !values.is_empty() {
+            let start = values.len().saturating_sub(4);
+            self.line(0, format!("puts({});", values[start..].join(", ")));
+        }
+    }
+
+    fn line(&mut self, indent: usize, text: String) {
+        self.lines.push(format!("{}{text}", "  ".repeat(indent)));
+    }
+
+    fn fresh(&mut self, prefix: &str) -> String {
+        self.names += 1;
+        format!("{prefix}{}", self.names)
+    }
+
+    fn bind(&mut self, name: String, shape: Shape) {
+        self.scope.push(Binding {
+            name,
+            shape,
+            readable: true,
+        });
+    }
+
+    fn kinds(&self) -> Vec<Kind> {
+        let mut kinds = vec![Kind::Int, Kind::Int, Kind::Bool];
+        if self.options.strings {
+            kinds.push(Kind::Str);
+        }
+        if self.options.collections {
+            kinds.extend([Kind::Array, Kind::Hash]);
+        }
+        kinds
+    }
+
+    /// How many times the current statement runs per run of the enclosing
+    /// body, counting loops from `level` inwards.
+    fn multiplier(&self, level: usize) -> u64 {
+        self.loops[level..].iter().product()
+    }
+
+    fn statement(&mut self, indent: usize) {
+        let nest = self.blocks < self.options.max_depth.min(3);
+        loop {
+            match self.rng.below(100) {
+                0..=34 => return self.let_statement(indent),
+                35..=54 => return self.puts_statement(indent),
+                55..=64 if nest => return self.if_statement(indent),
+                65..=74 if nest && self.options.loops && self.loops.len() < 2 => {
+                    return self.while_statement(indent)
+                }
+                75..=84 if nest && self.options.functions => return self.function(indent),
+                85..=94 if !self.accumulators.is_empty() => return self.accumulate(indent),
+                95..=99 if !self.loops.is_empty() => return self.jump(indent),
+                _ => {}
+            }
+        }
+    }
+
+    fn let_statement(&mut self, indent: usize) {
+        let kind = self.rng.pick(&self.kinds());
+        let (code, shape) = self.value(kind, self.options.max_depth);
+        let name = self.fresh("v");
+        self.line(indent, format!("let {name} = {code};"));
+        self.bind(name, shape);
+    }
+
+    fn puts_statement(&mut self, indent: usize) {
+        let count = 1 + self.rng.below(2);
+        let values: Vec<String> = (0..count)
+            .map(|_| {
+                let kind = self.rng.pick(&self.kinds());
+                self.value(kind, self.options.max_depth).0
+            })
+            .collect();
+        self.line(indent, format!("puts({});", values.join(", ")));
+    }
+
+    fn if_statement(&mut self, indent: usize) {
+        let condition = self.boolean(self.options.max_depth);
+        self.line(indent, format!("if ({condition}) {{"));
+        self.block(indent + 1);
+        if self.rng.chance(50) {
+            self.line(indent, "} else {".to_string());
+            self.block(indent + 1);
+        }
+        // The `;` keeps a following `(` or `[` from applying to the `if`.
+        self.line(indent, "};".to_string());
+    }
+
+    /// `let i = 0; while (i < n) { let i = i + 1; ... }`, with fresh
+    /// accumulators bound before it.
+    fn while_statement(&mut self, indent: usize) {
+        let iterations = self.rng.below(MAX_ITERATIONS + 1);
+        let counter = self.fresh("i");
+        self.line(indent, format!("let {counter} = 0;"));
+        let level = self.loops.len();
+        for _ in 0..self.rng.below(3) {
+            let name = self.fresh("acc");
+            self.line(indent, format!("let {name} = 0;"));
+            self.scope.push(Binding {
+                name: name.clone(),
+                shape: Shape::Int(0),
+                readable: false,
+            });
+            self.accumulators.push(Accumulator {
+                name,
+                bound: 0,
+                level,
+            });
+        }
+        self.bind(counter.clone(), Shape::Int(iterations));
+
+        self.line(indent, format!("while ({counter} < {iterations}) {{"));
+        self.line(indent + 1, format!("let {counter} = {counter} + 1;"));
+        self.loops.push(iterations.max(1));
+        self.block(indent + 1);
+        self.loops.pop();
+        self.line(indent, "}".to_string());
+
+        while self
+            .accumulators
+            .last()
+            .is_some_and(|acc| acc.level == level)
+        {
+            let acc = self.accumulators.pop().expect("checked above");
+            if let Some(binding) = self.scope.iter_mut().rev().find(|b| b.name == acc.name) {
+                binding.shape = Shape::Int(acc.bound);
+                binding.readable = true;
+            }
+        }
+    }
+
+    fn accumulate(&mut self, indent: usize) {
+        let index = self.rng.below(self.accumulators.len() as u64) as usize;
+        let multiplier = self.multiplier(self.accumulators[index].level);
+        let room = (INT_LIMIT - self.accumulators[index].bound) / multiplier;
+        let (code, bound) = self.int(self.options.max_depth, room.min(ARG_LIMIT));
+        let acc = &mut self.accumulators[index];
+        acc.bound += bound * multiplier;
+        let name = acc.name.clone();
+        self.line(indent, format!("let {name} = {name} + {code};"));
+    }
+
+    fn jump(&mut self, indent: usize) {
+        let condition = self.boolean(self.options.max_depth);
+        let keyword = if self.rng.chance(50) {
+            "break"
+        } else {
+            "continue"
+        };
+        self.line(indent, format!("if ({condition}) {{ {keyword}; }}"));
+    }
+
+    /// `let f = fn(...) { ... };`, whose body sees the enclosing scope but
+    /// none of its loops.
+    fn function(&mut self, indent: usize) {
+        let arity = self.rng.below(4) as usize;
+        let params: Vec<String> = (0..arity).map(|_| self.fresh("p")).collect();
+        let name = self.fresh("f");
+        self.line(indent, format!("let {name} = fn({}) {{", params.join(", ")));
+
+        let scope_len = self.scope.len();
+        let loops = std::mem::take(&mut self.loops);
+        let accumulators = std::mem::take(&mut self.accumulators);
+        let calls = std::mem::replace(&mut self.calls, 0);
+        self.blocks += 1;
+        for param in &params {
+            self.bind(param.clone(), Shape::Int(ARG_LIMIT));
+        }
+        for _ in 0..self.rng.below(4) {
+            self.statement(indent + 1);
+        }
+        let mut kinds = vec![Kind::Int, Kind::Bool];
+        if self.options.strings {
+            kinds.push(Kind::Str);
+        }
+        let kind = self.rng.pick(&kinds);
+        let (code, ret) = self.value(kind, self.options.max_depth);
+        if self.rng.chance(50) {
+            self.line(indent + 1, format!("return {code};"));
+        } else {
+            self.line(indent + 1, code);
+        }
+        self.blocks -= 1;
+        self.scope.truncate(scope_len);
+        self.loops = loops;
+        self.accumulators = accumulators;
+        let body_calls = std::mem::replace(&mut self.calls, calls);
+
+        self.line(indent, "};".to_string());
+        let shape = Shape::Func {
+            arity,
+            ret: Box::new(ret),
+            calls: body_calls,
+        };
+        self.bind(name, shape);
+    }
+
+    fn block(&mut self, indent: usize) {
+        let scope_len = self.scope.len();
+        self.blocks += 1;
+        for _ in 0..1 + self.rng.below(3) {
+            self.statement(indent);
+        }
+        self.blocks -= 1;
+        self.scope.truncate(scope_len);
+    }
+
+    fn value(&mut self, kind: Kind, depth: usize) -> (String, Shape) {
+        match kind {
+            Kind::Int => {
+                let (code, bound) = self.int(depth, INT_LIMIT);
+                (code, Shape::Int(bound))
+            }
+            Kind::Bool => (self.boolean(depth), Shape::Bool),
+            Kind::Str => {
+                let (code, len) = self.string(depth, STR_LIMIT);
+                (code, Shape::Str(len))
+            }
+            Kind::Array => {
+                let (code, len, bound) = self.array(depth, INT_LIMIT);
+                (code, Shape::Array { len, bound })
+            }
+            Kind::Hash => {
+                let (code, keys, bound) = self.hash(depth, INT_LIMIT);
+                (code, Shape::Hash { keys, bound })
+            }
+        }
+    }
+
+    /// A readable binding whose shape `fits`.
+    fn variable(&mut self, fits: impl Fn(&Shape) -> bool) -> Option<(String, Shape)> {
+        let candidates: Vec<&Binding> = self
+            .scope
+            .iter()
+            .filter(|binding| binding.readable && fits(&binding.shape))
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        let binding = candidates[self.rng.below(candidates.len() as u64) as usize];
+        Some((binding.name.clone(), binding.shape.clone()))
+    }
+
+    /// A call of a function returning a value that `fits`, if one is in
+    /// scope and the call budget allows it.
+    fn call(&mut self, depth: usize, fits: impl Fn(&Shape) -> bool) -> Option<(String, Shape)> {
+        let multiplier = self.multiplier(0);
+        let budget = CALL_BUDGET - self.calls;
+        let (name, shape) = self.variable(|shape| {
+            matches!(shape, Shape::Func { ret, calls, .. }
+                if fits(ret) && multiplier * (calls + 1) <= budget)
+        })?;
+        let Shape::Func { arity, ret, calls } = shape else {
+            unreachable!("only functions fit");
+        };
+        self.calls += multiplier * (calls + 1);
+        let args: Vec<String> = (0..arity).map(|_| self.int(depth, ARG_LIMIT).0).collect();
+        Some((format!("{name}({})", args.join(", ")), *ret))
+    }
+
+    /// An integer expression and a bound on its magnitude of at most
+    /// `limit`.
+    fn int(&mut self, depth: usize, limit: u64) -> (String, u64) {
+        if depth > 0 && self.rng.chance(60) {
+            if let Some(found) = self.int_operation(depth - 1, limit) {
+                return found;
+            }
+        }
+        if self.rng.chance(50) {
+            if let Some((name, Shape::Int(bound))) =
+                self.variable(|shape| matches!(shape, Shape::Int(bound) if *bound <= limit))
+            {
+                return (name, bound);
+            }
+        }
+        let value = self.rng.below(limit.min(100) + 1);
+        (value.to_string(), value)
+    }
+
+    fn int_operation(&mut self, depth: usize, limit: u64) -> Option<(String, u64)> {
+        match self.rng.below(10) {
+            0 | 1 => {
+                let op = self.rng.pick(&["+", "-"]);
+                let (left, a) = self.int(depth, limit / 2);
+                let (right, b) = self.int(depth, limit / 2);
+                Some((format!("({left} {op} {right})"), a + b))
+            }
+            2 => {
+                let root = isqrt(limit);
+                let (left, a) = self.int(depth, root);
+                let (right, b) = self.int(depth, root);
+                Some((format!("({left} * {right})"), a * b))
+            }
+            3 => {
+                let divisor = 1 + self.rng.below(9);
+                let (left, a) = self.int(depth, limit);
+                Some((format!("({left} / {divisor})"), a))
+            }
+            4 => {
+                let (operand, a) = self.int(depth, limit);
+                Some((format!("(-{operand})"), a))
+            }
+            5 => {
+                let condition = self.boolean(depth);
+                let (then, a) = self.int(depth, limit);
+                let (otherwise, b) = self.int(depth, limit);
+                Some((
+                    format!("(if ({condition}) {{ {then} }} else {{ {otherwise} }})"),
+                    a.max(b),
+                ))
+            }
+            6 if self.options.collections => {
+                let (array, len, _) = self.array(depth, INT_LIMIT);
+                (len as u64 <= limit).then(|| (format!("len({array})"), len as u64))
+            }
+            6 if self.options.strings => {
+                let (string, len) = self.string(depth, limit.min(STR_LIMIT));
+                Some((format!("len({string})"), len))
+            }
+            7 if self.options.collections => {
+                let (array, len, bound) = self.array(depth, limit);
+                let code = match self.rng.below(4) {
+                    0 => format!("{array}[0]"),
+                    1 => format!("{array}[{}]", len - 1),
+                    2 => format!("first({array})"),
+                    _ => format!("last({array})"),
+                };
+                Some((code, bound))
+            }
+            8 if self.options.collections => {
+                let (hash, keys, bound) = self.hash(depth, limit);
+                let key = self.rng.pick(&keys);
+                Some((format!("{hash}[\"{key}\"]"), bound))
+            }
+            9 if self.options.functions => {
+                let (code, shape) = self.call(
+                    depth,
+                    |shape| matches!(shape, Shape::Int(bound) if *bound <= limit),
+                )?;
+                let Shape::Int(bound) = shape else {
+                    unreachable!("only integer results fit");
+                };
+                Some((code, bound))
+            }
+            _ => None,
+        }
+    }
+
+    fn boolean(&mut self, depth: usize) -> String {
+        if depth > 0 && self.rng.chance(60) {
+            if let Some(found) = self.bool_operation(depth - 1) {
+                return found;
+            }
+        }
+        if self.rng.chance(50) {
+            if let Some((name, _)) = self.variable(|shape| matches!(shape, Shape::Bool)) {
+                return name;
+            }
+        }
+        self.rng.pick(&["true", "false"]).to_string()
+    }
+
+    fn bool_operation(&mut self, depth: usize) -> Option<String> {
+        match self.rng.below(7) {
+            0 | 1 => {
+                let op = self.rng.pick(&["<", ">", "<=", ">=", "==", "!="]);
+                let (left, _) = self.int(depth, INT_LIMIT);
+                let (right, _) = self.int(depth, INT_LIMIT);
+                Some(format!("({left} {op} {right})"))
+            }
+            2 => Some(format!("(!{})", self.boolean(depth))),
+            3 => {
+                let op = self.rng.pick(&["&&", "||", "==", "!="]);
+                let left = self.boolean(depth);
+                let right = self.boolean(depth);
+                Some(format!("({left} {op} {right})"))
+            }
+            4 if self.options.strings => {
+                let op = self.rng.pick(&["==", "!="]);
+                let (left, _) = self.string(depth, STR_LIMIT);
+                let (right, _) = self.string(depth, STR_LIMIT);
+                Some(format!("({left} {op} {right})"))
+            }
+            5 => {
+                let condition = self.boolean(depth);
+                let then = self.boolean(depth);
+                let otherwise = self.boolean(depth);
+                Some(format!(
+                    "(if ({condition}) {{ {then} }} else {{ {otherwise} }})"
+                ))
+            }
+            6 if self.options.functions => self
+                .call(depth, |shape| matches!(shape, Shape::Bool))
+                .map(|(code, _)| code),
+            _ => None,
+        }
+    }
+
+    /// A string expression and a bound on its length of at most `limit`.
+    fn string(&mut self, depth: usize, limit: u64) -> (String, u64) {
+        if depth > 0 && self.rng.chance(50) {
+            if let Some(found) = self.string_operation(depth - 1, limit) {
+                return found;
+            }
+        }
+        if self.rng.chance(50) {
+            if let Some((name, Shape::Str(len))) =
+                self.variable(|shape| matches!(shape, Shape::Str(len) if *len <= limit))
+            {
+                return (name, len);
+            }
+        }
+        let word = self.rng.pick(&WORDS);
+        let word = if word.len() as u64 <= limit { word } else { "" };
+        (format!("\"{word}\""), word.len() as u64)
+    }
+
+    fn string_operation(&mut self, depth: usize, limit: u64) -> Option<(String, u64)> {
+        match self.rng.below(3) {
+            0 => {
+                let (left, a) = self.string(depth, limit / 2);
+                let (right, b) = self.string(depth, limit / 2);
+                Some((format!("({left} + {right})"), a + b))
+            }
+            1 => {
+                let condition = self.boolean(depth);
+                let (then, a) = self.string(depth, limit);
+                let (otherwise, b) = self.string(depth, limit);
+                Some((
+                    format!("(if ({condition}) {{ {then} }} else {{ {otherwise} }})"),
+                    a.max(b),
+                ))
+            }
+            _ if self.options.functions => {
+                let (code, shape) = self.call(
+                    depth,
+                    |shape| matches!(shape, Shape::Str(len) if *len <= limit),
+                )?;
+                let Shape::Str(len) = shape else {
+                    unreachable!("only string results fit");
+                };
+                Some((code, len))
+            }
+            _ => None,
+        }
+    }
+
+    /// A non-empty array of integers of at most magnitude `limit`, with
+    /// its exact length.
+    fn array(&mut self, depth: usize, limit: u64) -> (String, usize, u64) {
+        if depth > 0 && self.rng.chance(40) {
+            let (array, len, bound) = self.array(depth - 1, limit);
+            if self.rng.chance(50) || len < 2 {
+                let (element, value) = self.int(depth - 1, limit);
+                return (
+                    format!("push({array}, {element})"),
+                    len + 1,
+                    bound.max(value),
+                );
+            }
+            return (format!("rest({array})"), len - 1, bound);
+        }
+        if self.rng.chance(50) {
+            if let Some((name, Shape::Array { len, bound })) = self
+                .variable(|shape| matches!(shape, Shape::Array { bound, .. } if *bound <= limit))
+            {
+                return (name, len, bound);
+            }
+        }
+        let len = 1 + self.rng.below(4) as usize;
+        let mut bound = 0;
+        let elements: Vec<String> = (0..len)
+            .map(|_| {
+                let (code, value) = self.int(depth.saturating_sub(1), limit);
+                bound = bound.max(value);
+                code
+            })
+            .collect();
+        (format!("[{}]", elements.join(", ")), len, bound)
+    }
+
+    /// A hash of integers of at most magnitude `limit`, with its keys.
+    fn hash(&mut self, depth: usize, limit: u64) -> (String, Vec<&'static str>, u64) {
+        if self.rng.chance(50) {
+            if let Some((name, Shape::Hash { keys, bound })) =
+                self.variable(|shape| matches!(shape, Shape::Hash { bound, .. } if *bound <= limit))
+            {
+                return (name, keys, bound);
+            }
+        }
+        let mut keys = Vec::new();
+        for _ in 0..1 + self.rng.below(3) {
+            let key = self.rng.pick(&KEYS);
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        let mut bound = 0;
+        let pairs: Vec<String> = keys
+            .iter()
+            .map(|key| {
+                let (code, value) = self.int(depth.saturating_sub(1), limit);
+                bound = bound.max(value);
+                format!("\"{key}\": {code}")
+            })
+            .collect();
+        (format!("{{{}}}", pairs.join(", ")), keys, bound)
+    }
+}
+
+fn isqrt(n: u64) -> u64 {
+    let mut root = (n as f64).sqrt() as u64;
+    while root * root > n {
+        root -= 1;
+    }
+    while (root + 1) * (root + 1) <= n {
+        root += 1;
+    }
+    root
+}
@@ -0,0 +1,131 @@
+//! ANSI colors for Monkey source and values shown on a terminal.
+//!
+//! Source is classified by the lexer, so highlighting agrees with how the
+//! code is tokenized. Callers decide whether color is wanted; piped output
+//! must stay plain to match the Java implementation.
+
+use crate::lexer::{Lexer, LexerOptions};
+use crate::object::Object;
+use crate::position::Position;
+use crate::pretty::{layout_value, ValueLayout};
+use crate::token::TokenKind;
+
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Keyword,
+    /// `true`, `false` and `null`.
+    Constant,
+    Number,
+    String,
+    Comment,
+    Error,
+    Banner,
+}
+
+impl Style {
+    fn code(self) -> &'static str {
+        match self {
+            Style::Keyword => "\x1b[35m",
+            Style::Constant => "\x1b[36m",
+            Style::Number => "\x1b[33m",
+            Style::String => "\x1b[32m",
+            Style::Comment => "\x1b[2m",
+            Style::Error => "\x1b[31m",
+            Style::Banner => "\x1b[1;33m",
+        }
+    }
+
+    /// How tokens of `kind` are shown; `None` leaves them plain.
+    pub fn of_token(kind: TokenKind) -> Option<Style> {
+        match kind {
+            TokenKind::Function
+            | TokenKind::Let
+            | TokenKind::If
+            | TokenKind::Else
+            | TokenKind::Return
+            | TokenKind::While
+            | TokenKind::Break
+            | TokenKind::Continue => Some(Style::Keyword),
+            TokenKind::True | TokenKind::False => Some(Style::Constant),
+            TokenKind::Int | TokenKind::Float => Some(Style::Number),
+            TokenKind::String => Some(Style::String),
+            TokenKind::Comment => Some(Style::Comment),
+            TokenKind::Illegal => Some(Style::Error),
+            _ => None,
+        }
+    }
+}
+
+/// `text` wrapped in the escape codes for `style`.
+pub fn paint(text: &str, style: Style) -> String {
+    format!("{}{text}{RESET}", style.code())
+}
+
+/// `source` with each token colored by its kind; the text between tokens
+/// is kept as it is.
+pub fn highlight(source: &str) -> String {
+    let chars: Vec<char> = source.chars().collect();
+    let mut line_starts = vec![0];
+    line_starts.extend(
+        chars
+            .iter()
+            .enumerate()
+            .filter(|(_, ch)| **ch == '\n')
+            .map(|(i, _)| i + 1),
+    );
+    let index = |pos: Position| {
+        line_starts
+            .get(pos.line.saturating_sub(1))
+            .map_or(chars.len(), |start| start + pos.col.saturating_sub(1))
+            .min(chars.len())
+    };
+
+    let mut out = String::new();
+    let mut at = 0;
+    let tokens = Lexer::new(source).tokenize_all(LexerOptions::default().with_comments(true));
+    for token in tokens {
+        let Some(style) = Style::of_token(token.kind) else {
+            continue;
+        };
+        let start = index(token.pos).max(at);
+        let end = index(token.end).max(start);
+        out.extend(&chars[at..start]);
+        out.push_str(&paint(&chars[start..end].iter().collect::<String>(), style));
+        at = end;
+    }
+    out.extend(&chars[at..]);
+    out
+}
+
+/// `value.inspect()` with scalars colored by type, inside collections too.
+pub fn paint_value(value: &Object) -> String {
+    match value {
+        Object::Integer(_) => paint(&value.inspect(), Style::Number),
+        Object::String(_) => paint(&value.inspect(), Style::String),
+        Object::Boolean(_) | Object::Null => paint(&value.inspect(), Style::Constant),
+        Object::Array(values) => {
+            let rendered = values
+                .iter()
+                .map(|v| paint_value(v))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("[{rendered}]")
+        }
+        Object::Hash(hash) => {
+            let rendered = hash
+                .iter()
+                .map(|(k, v)| format!("{}: {}", paint_value(k), paint_value(v)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{{{rendered}}}")
+        }
+        _ => value.inspect(),
+    }
+}
+
+/// `pretty::format_value_pretty` with scalars colored as by `paint_value`.
+pub fn paint_value_pretty(value: &Object, layout: ValueLayout) -> String {
+    layout_value(value, layout, &paint_value)
+}
@@ -0,0 +1,690 @@
+//! Native-code tier for hot functions (`jit` feature).
+//!
+//! The VM counts calls per compiled function; once a function reaches
+//! `JIT_THRESHOLD` calls it is offered to `Jit`, which compiles it with
+//! Cranelift if its body only works on integers and booleans: locals,
+//! integer/boolean constants, arithmetic, comparisons, `!`, `-`, jumps and
+//! calls to itself through `CurrentClosure`. Parameters are assumed to be
+//! integers, so native code only runs when every argument is an integer.
+//!
+//! Such functions have no side effects, so whenever native code hits
+//! something the interpreter would report — division by zero, arithmetic
+//! overflow, too deep recursion — it gives up and the VM interprets the
+//! whole call instead, producing the usual error and stack trace.
+
+use std::collections::HashMap;
+
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{
+    types, AbiParam, Block, InstBuilder, MemFlags, Signature, UserFuncName, Value,
+};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Module};
+
+use crate::bytecode::{lookup_definition, read_operands, Opcode};
+use crate::object::{CompiledFunctionObject, Object, ObjectRef, Shared};
+
+/// Calls a function must receive before it is compiled.
+pub(crate) const JIT_THRESHOLD: u64 = 100;
+
+/// Native entry point: argument array, call depth of the new frame, and a
+/// status flag set to non-zero when the interpreter has to take over.
+type EntryFn = unsafe extern "C" fn(*const i64, i64, *mut i64) -> i64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Ty {
+    Int,
+    Bool,
+    /// The function's own closure, only usable as a call target.
+    SelfFn,
+}
+
+/// Abstract machine state on entry to an instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct State {
+    stack: Vec<Ty>,
+    /// `None` for locals that are unset or typed differently on some path.
+    locals: Vec<Option<Ty>>,
+}
+
+struct Compiled {
+    entry: EntryFn,
+    returns: Ty,
+}
+
+enum Entry {
+    Counting(u64),
+    Rejected,
+    Compiled(Compiled),
+}
+
+/// Per-function call counters and compiled code, keyed by function
+/// identity. Each entry keeps its function alive so the key stays unique.
+pub(crate) struct Jit {
+    module: Option<JITModule>,
+    functions: HashMap<usize, (Shared<CompiledFunctionObject>, Entry)>,
+    max_call_depth: usize,
+}
+
+impl std::fmt::Debug for Jit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Jit")
+            .field("functions", &self.functions.len())
+            .finish()
+    }
+}
+
+/// Native code cannot be shared between VMs; a cloned VM starts cold.
+impl Clone for Jit {
+    fn clone(&self) -> Self {
+        Self::new(self.max_call_depth)
+    }
+}
+
+impl Jit {
+    pub(crate) fn new(max_call_depth: usize) -> Self {
+        Self {
+            module: None,
+            functions: HashMap::new(),
+            max_call_depth,
+        }
+    }
+
+    /// Number of functions compiled to native code so far.
+    pub(crate) fn compiled_count(&self) -> usize {
+        self.functions
+            .values()
+            .filter(|(_, entry)| matches!(entry, Entry::Compiled(_)))
+            .count()
+    }
+
+    /// Count a call of `function` and run it natively when possible.
+    /// `depth` is the number of frames once the callee's frame is pushed.
+    /// `None` means the call must be interpreted.
+    pub(crate) fn call(
+        &mut self,
+        function: &Shared<CompiledFunctionObject>,
+        constants: &[ObjectRef],
+        args: &[ObjectRef],
+        depth: usize,
+    ) -> Option<Object> {
+        let key = Shared::as_ptr(function) as *const u8 as usize;
+        let (_, entry) = self
+            .functions
+            .entry(key)
+            .or_insert_with(|| (Shared::clone(function), Entry::Counting(0)));
+        let hot = match entry {
+            Entry::Counting(calls) => {
+                *calls += 1;
+                if *calls < JIT_THRESHOLD {
+                    return None;
+                }
+                true
+            }
+            _ => false,
+        };
+        if hot {
+            let compiled = self.compile(function, constants);
+            let (_, entry) = self.functions.get_mut(&key)?;
+            *entry = compiled.map_or(Entry::Rejected, Entry::Compiled);
+        }
+        let (_, Entry::Compiled(compiled)) = self.functions.get(&key)? else {
+            return None;
+        };
+
+        let args = args
+            .iter()
+            .map(|arg| match arg.as_ref() {
+                Object::Integer(value) => Some(*value),
+                _ => None,
+            })
+            .collect::<Option<Vec<i64>>>()?;
+        let mut status = 0i64;
+        // SAFETY: `entry` was produced by `compile` for a function taking
+        // exactly `args.len()` integers (the VM checked the arity), it reads
+        // only that many values from the array, and the module that owns the
+        // code lives as long as `self`.
+        let value = unsafe { (compiled.entry)(args.as_ptr(), depth as i64, &mut status) };
+        if status != 0 {
+            return None;
+        }
+        Some(match compiled.returns {
+            Ty::Bool => Object::Boolean(value != 0),
+            _ => Object::Integer(value),
+        })
+    }
+
+    fn compile(
+        &mut self,
+        function: &CompiledFunctionObject,
+        constants: &[ObjectRef],
+    ) -> Option<Compiled> {
+        let code = decode(&function.instructions)?;
+        let (returns, states) = [Ty::Int, Ty::Bool]
+            .into_iter()
+            .find_map(|returns| Some((returns, analyze(function, &code, constants, returns)?)))?;
+
+        if self.module.is_none() {
+            self.module = Some(new_module()?);
+        }
+        let module = self.module.as_mut()?;
+        let body_id = build_body(
+            module,
+            function,
+            &code,
+            &states,
+            constants,
+            self.max_call_depth,
+        )?;
+        let entry_id = build_entry(module, function.num_params, body_id)?;
+        module.finalize_definitions().ok()?;
+        let pointer = module.get_finalized_function(entry_id);
+        // SAFETY: `build_entry` defined this function with the `EntryFn`
+        // signature using the host calling convention.
+        let entry = unsafe { std::mem::transmute::<*const u8, EntryFn>(pointer) };
+        Some(Compiled { entry, returns })
+    }
+}
+
+type Instruction = (usize, Opcode, Vec<usize>);
+
+fn decode(code: &[u8]) -> Option<Vec<Instruction>> {
+    let mut out = Vec::new();
+    let mut offset = 0;
+    while offset < code.len() {
+        let op = Opcode::from_byte(code[offset])?;
+        let (operands, consumed) =
+            read_operands(lookup_definition(op), &code[offset + 1..]).ok()?;
+        out.push((offset, op, operands));
+        offset += 1 + consumed;
+    }
+    Some(out)
+}
+
+fn constant_type(constants: &[ObjectRef], idx: usize) -> Option<Ty> {
+    match constants.get(idx)?.as_ref() {
+        Object::Integer(_) => Some(Ty::Int),
+        Object::Boolean(_) => Some(Ty::Bool),
+        _ => None,
+    }
+}
+
+/// Check that every reachable instruction is supported with consistent
+/// types, assuming integer parameters and a `returns` result. Returns the
+/// entry state of each reachable instruction, keyed by offset.
+fn analyze(
+    function: &CompiledFunctionObject,
+    code: &[Instruction],
+    constants: &[ObjectRef],
+    returns: Ty,
+) -> Option<HashMap<usize, State>> {
+    let index_of = code
+        .iter()
+        .enumerate()
+        .map(|(idx, (offset, _, _))| (*offset, idx))
+        .collect::<HashMap<_, _>>();
+    let mut locals = vec![None; function.num_locals];
+    for local in locals.iter_mut().take(function.num_params) {
+        *local = Some(Ty::Int);
+    }
+    let mut states = HashMap::new();
+    let mut work = vec![(
+        0,
+        State {
+            stack: Vec::new(),
+            locals,
+        },
+    )];
+
+    while let Some((offset, incoming)) = work.pop() {
+        let state = match states.get(&offset) {
+            None => incoming,
+            Some(existing) => {
+                let merged = merge(existing, &incoming)?;
+                if &merged == existing {
+                    continue;
+                }
+                merged
+            }
+        };
+        states.insert(offset, state.clone());
+
+        let (_, op, operands) = code.get(*index_of.get(&offset)?)?;
+        let next = code
+            .get(index_of[&offset] + 1)
+            .map(|(offset, _, _)| *offset);
+        let mut s = state;
+        let operand = operands.first().copied().unwrap_or(0);
+        match op {
+            Opcode::Constant => s.stack.push(constant_type(constants, operand)?),
+            Opcode::ConstInt | Opcode::Const0 | Opcode::Const1 => s.stack.push(Ty::Int),
+            Opcode::True | Opcode::False => s.stack.push(Ty::Bool),
+            Opcode::Pop => {
+                s.stack.pop()?;
+            }
+            Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div => {
+                let (b, a) = (s.stack.pop()?, s.stack.pop()?);
+                if (a, b) != (Ty::Int, Ty::Int) {
+                    return None;
+                }
+                s.stack.push(Ty::Int);
+            }
+            Opcode::AddConstants => {
+                constant_type(constants, operands[0]).filter(|ty| *ty == Ty::Int)?;
+                constant_type(constants, operands[1]).filter(|ty| *ty == Ty::Int)?;
+                s.stack.push(Ty::Int);
+            }
+            Opcode::AddLocals => {
+                for local in operands {
+                    if *s.locals.get(*local)? != Some(Ty::Int) {
+                        return None;
+                    }
+                }
+                s.stack.push(Ty::Int);
+            }
+            Opcode::Eq | Opcode::Ne => {
+                let (b, a) = (s.stack.pop()?, s.stack.pop()?);
+                if a != b || a == Ty::SelfFn {
+                    return None;
+                }
+                s.stack.push(Ty::Bool);
+            }
+            Opcode::Lt | Opcode::Gt | Opcode::Le | Opcode::Ge => {
+                let (b, a) = (s.stack.pop()?, s.stack.pop()?);
+                if (a, b) != (Ty::Int, Ty::Int) {
+                    return None;
+                }
+                s.stack.push(Ty::Bool);
+            }
+            Opcode::Bang => {
+                if s.stack.pop()? == Ty::SelfFn {
+                    return None;
+                }
+                s.stack.push(Ty::Bool);
+            }
+            Opcode::Neg => {
+                if s.stack.pop()? != Ty::Int {
+                    return None;
+                }
+                s.stack.push(Ty::Int);
+            }
+            Opcode::Jump => {
+                work.push((operand, s));
+                continue;
+            }
+            Opcode::JumpIfFalse | Opcode::JumpIfFalsePop => {
+                if *s.stack.last()? == Ty::SelfFn {
+                    return None;
+                }
+                // Both keep the condition when jumping; the fused form pops
+                // it on fall-through.
+                work.push((operand, s.clone()));
+                if *op == Opcode::JumpIfFalsePop {
+                    s.stack.pop();
+                }
+            }
+            Opcode::GetLocal => {
+                let ty = (*s.locals.get(operand)?)?;
+                s.stack.push(ty);
+            }
+            Opcode::SetLocal => {
+                let ty = s.stack.pop()?;
+                if ty == Ty::SelfFn {
+                    return None;
+                }
+                *s.locals.get_mut(operand)? = Some(ty);
+            }
+            Opcode::CurrentClosure => s.stack.push(Ty::SelfFn),
+            Opcode::Call => {
+                if operand != function.num_params {
+                    return None;
+                }
+                for _ in 0..operand {
+                    if s.stack.pop()? != Ty::Int {
+                        return None;
+                    }
+                }
+                if s.stack.pop()? != Ty::SelfFn {
+                    return None;
+                }
+                s.stack.push(returns);
+            }
+            Opcode::ReturnValue => {
+                if s.stack.pop()? != returns {
+                    return None;
+                }
+                continue;
+            }
+            Opcode::Nop => {}
+            _ => return None,
+        }
+        work.push((next?, s));
+    }
+    Some(states)
+}
+
+/// Join two states reaching the same instruction.
+fn merge(a: &State, b: &State) -> Option<State> {
+    if a.stack != b.stack {
+        return None;
+    }
+    Some(State {
+        stack: a.stack.clone(),
+        locals: a
+            .locals
+            .iter()
+            .zip(&b.locals)
+            .map(|(x, y)| if x == y { *x } else { None })
+            .collect(),
+    })
+}
+
+fn new_module() -> Option<JITModule> {
+    let mut flags = settings::builder();
+    flags.set("use_colocated_libcalls", "false").ok()?;
+    flags.set("is_pic", "false").ok()?;
+    flags.set("opt_level", "speed").ok()?;
+    let isa = cranelift_native::builder()
+        .ok()?
+        .finish(settings::Flags::new(flags))
+        .ok()?;
+    Some(JITModule::new(JITBuilder::with_isa(
+        isa,
+        default_libcall_names(),
+    )))
+}
+
+/// `(args..., depth, status) -> value`, used for self-calls.
+fn body_signature(module: &JITModule, num_params: usize) -> Signature {
+    let mut sig = module.make_signature();
+    for _ in 0..num_params + 1 {
+        sig.params.push(AbiParam::new(types::I64));
+    }
+    sig.params
+        .push(AbiParam::new(module.target_config().pointer_type()));
+    sig.returns.push(AbiParam::new(types::I64));
+    sig
+}
+
+fn build_body(
+    module: &mut JITModule,
+    function: &CompiledFunctionObject,
+    code: &[Instruction],
+    states: &HashMap<usize, State>,
+    constants: &[ObjectRef],
+    max_call_depth: usize,
+) -> Option<FuncId> {
+    let num_params = function.num_params;
+    let num_locals = function.num_locals;
+    let sig = body_signature(module, num_params);
+    let id = module.declare_anonymous_function(&sig).ok()?;
+    let mut ctx = module.make_context();
+    ctx.func.signature = sig;
+    ctx.func.name = UserFuncName::user(0, id.as_u32());
+    let mut builder_ctx = FunctionBuilderContext::new();
+    let mut b = FunctionBuilder::new(&mut ctx.func, &mut builder_ctx);
+    let self_ref = module.declare_func_in_func(id, b.func);
+
+    let max_stack = states.values().map(|s| s.stack.len()).max().unwrap_or(0) + 1;
+    let var = |slot: usize| Variable::from_u32(slot as u32);
+    for slot in 0..num_locals + max_stack {
+        b.declare_var(var(slot), types::I64);
+    }
+    let stack_var = |depth: usize| var(num_locals + depth);
+
+    let entry = b.create_block();
+    b.append_block_params_for_function_params(entry);
+    b.switch_to_block(entry);
+    let params = b.block_params(entry).to_vec();
+    let depth = params[num_params];
+    let status = params[num_params + 1];
+    let zero = b.ins().iconst(types::I64, 0);
+    for slot in 0..num_locals {
+        let value = params.get(slot).copied().filter(|_| slot < num_params);
+        b.def_var(var(slot), value.unwrap_or(zero));
+    }
+
+    // Leave the call to the interpreter: flag it and return.
+    let bail = b.create_block();
+    let mut blocks = HashMap::new();
+    for (offset, op, operands) in code {
+        if matches!(
+            op,
+            Opcode::Jump | Opcode::JumpIfFalse | Opcode::JumpIfFalsePop
+        ) && states.contains_key(&operands[0])
+        {
+            blocks
+                .entry(operands[0])
+                .or_insert_with(|| b.create_block());
+        }
+        let next = offset + 1 + lookup_definition(*op).operand_widths.iter().sum::<usize>();
+        if matches!(op, Opcode::JumpIfFalse | Opcode::JumpIfFalsePop) && states.contains_key(&next)
+        {
+            blocks.entry(next).or_insert_with(|| b.create_block());
+        }
+    }
+
+    let mut open = true;
+    for (offset, op, operands) in code {
+        if let Some(&block) = blocks.get(offset) {
+            if open {
+                b.ins().jump(block, &[]);
+            }
+            b.switch_to_block(block);
+            open = true;
+        }
+        let Some(state) = states.get(offset).filter(|_| open) else {
+            open = false;
+            continue;
+        };
+        let sp = state.stack.len();
+        let operand = operands.first().copied().unwrap_or(0);
+        let int_constant = |idx: usize| match constants[idx].as_ref() {
+            Object::Integer(value) => *value,
+            Object::Boolean(value) => i64::from(*value),
+            _ => 0,
+        };
+        match op {
+            Opcode::Constant => {
+                let value = b.ins().iconst(types::I64, int_constant(operand));
+                b.def_var(stack_var(sp), value);
+            }
+            Opcode::ConstInt | Opcode::Const0 | Opcode::Const1 | Opcode::True | Opcode::False => {
+                let value = match op {
+                    Opcode::ConstInt => i64::from(operand as u16 as i16),
+                    Opcode::Const1 | Opcode::True => 1,
+                    _ => 0,
+                };
+                let value = b.ins().iconst(types::I64, value);
+                b.def_var(stack_var(sp), value);
+            }
+            Opcode::Pop | Opcode::Nop => {}
+            Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div => {
+                let x = b.use_var(stack_var(sp - 2));
+                let y = b.use_var(stack_var(sp - 1));
+                let value = arithmetic(&mut b, *op, x, y, bail);
+                b.def_var(stack_var(sp - 2), value);
+            }
+            Opcode::AddConstants => {
+                let x = b.ins().iconst(types::I64, int_constant(operands[0]));
+                let y = b.ins().iconst(types::I64, int_constant(operands[1]));
+                let value = arithmetic(&mut b, Opcode::Add, x, y, bail);
+                b.def_var(stack_var(sp), value);
+            }
+            Opcode::AddLocals => {
+                let x = b.use_var(var(operands[0]));
+                let y = b.use_var(var(operands[1]));
+                let value = arithmetic(&mut b, Opcode::Add, x, y, bail);
+                b.def_var(stack_var(sp), value);
+            }
+            Opcode::Eq | Opcode::Ne | Opcode::Lt | Opcode::Gt | Opcode::Le | Opcode::Ge => {
+                let cc = match op {
+                    Opcode::Eq => IntCC::Equal,
+                    Opcode::Ne => IntCC::NotEqual,
+                    Opcode::Lt => IntCC::SignedLessThan,
+                    Opcode::Gt => IntCC::SignedGreaterThan,
+                    Opcode::Le => IntCC::SignedLessThanOrEqual,
+                    _ => IntCC::SignedGreaterThanOrEqual,
+                };
+                let x = b.use_var(stack_var(sp - 2));
+                let y = b.use_var(stack_var(sp - 1));
+                let flag = b.ins().icmp(cc, x, y);
+                let value = b.ins().uextend(types::I64, flag);
+                b.def_var(stack_var(sp - 2), value);
+            }
+            Opcode::Bang => {
+                let value = if state.stack[sp - 1] == Ty::Bool {
+                    let x = b.use_var(stack_var(sp - 1));
+                    b.ins().bxor_imm(x, 1)
+                } else {
+                    // Integers are always truthy.
+                    b.ins().iconst(types::I64, 0)
+                };
+                b.def_var(stack_var(sp - 1), value);
+            }
+            Opcode::Neg => {
+                let x = b.use_var(stack_var(sp - 1));
+                let overflow = b.ins().icmp_imm(IntCC::Equal, x, i64::MIN);
+                let ok = b.create_block();
+                b.ins().brif(overflow, bail, &[], ok, &[]);
+                b.switch_to_block(ok);
+                let value = b.ins().ineg(x);
+                b.def_var(stack_var(sp - 1), value);
+            }
+            Opcode::Jump => {
+                b.ins().jump(blocks[&operand], &[]);
+                open = false;
+            }
+            Opcode::JumpIfFalse | Opcode::JumpIfFalsePop => {
+                let next = offset + 1 + lookup_definition(*op).operand_widths.iter().sum::<usize>();
+                if state.stack[sp - 1] == Ty::Bool {
+                    let cond = b.use_var(stack_var(sp - 1));
+                    b.ins()
+                        .brif(cond, blocks[&next], &[], blocks[&operand], &[]);
+                } else {
+                    b.ins().jump(blocks[&next], &[]);
+                }
+                open = false;
+            }
+            Opcode::GetLocal => {
+                let value = b.use_var(var(operand));
+                b.def_var(stack_var(sp), value);
+            }
+            Opcode::SetLocal => {
+                let value = b.use_var(stack_var(sp - 1));
+                b.def_var(var(operand), value);
+            }
+            Opcode::CurrentClosure => b.def_var(stack_var(sp), zero),
+            Opcode::Call => {
+                let too_deep = b.ins().icmp_imm(
+                    IntCC::SignedGreaterThanOrEqual,
+                    depth,
+                    max_call_depth as i64,
+                );
+                let ok = b.create_block();
+                b.ins().brif(too_deep, bail, &[], ok, &[]);
+                b.switch_to_block(ok);
+
+                let mut args = (sp - operand..sp)
+                    .map(|slot| b.use_var(stack_var(slot)))
+                    .collect::<Vec<_>>();
+                let next_depth = b.ins().iadd_imm(depth, 1);
+                args.push(next_depth);
+                args.push(status);
+                let call = b.ins().call(self_ref, &args);
+                let value = b.inst_results(call)[0];
+                let failed = b.ins().load(types::I64, MemFlags::trusted(), status, 0);
+                let ok = b.create_block();
+                let fail = b.create_block();
+                b.ins().brif(failed, fail, &[], ok, &[]);
+                b.switch_to_block(fail);
+                b.ins().return_(&[zero]);
+                b.switch_to_block(ok);
+                b.def_var(stack_var(sp - operand - 1), value);
+            }
+            Opcode::ReturnValue => {
+                let value = b.use_var(stack_var(sp - 1));
+                b.ins().return_(&[value]);
+                open = false;
+            }
+            _ => return None,
+        }
+    }
+    if open {
+        b.ins().return_(&[zero]);
+    }
+
+    b.switch_to_block(bail);
+    let one = b.ins().iconst(types::I64, 1);
+    b.ins().store(MemFlags::trusted(), one, status, 0);
+    b.ins().return_(&[zero]);
+
+    b.seal_all_blocks();
+    b.finalize();
+    module.define_function(id, &mut ctx).ok()?;
+    Some(id)
+}
+
+/// Integer arithmetic that bails out wherever the interpreter would fail
+/// or panic: division by zero and overflow.
+fn arithmetic(b: &mut FunctionBuilder, op: Opcode, x: Value, y: Value, bail: Block) -> Value {
+    let (value, overflow) = match op {
+        Opcode::Add => b.ins().sadd_overflow(x, y),
+        Opcode::Sub => b.ins().ssub_overflow(x, y),
+        Opcode::Mul => b.ins().smul_overflow(x, y),
+        _ => {
+            let by_zero = b.ins().icmp_imm(IntCC::Equal, y, 0);
+            let min = b.ins().icmp_imm(IntCC::Equal, x, i64::MIN);
+            let minus_one = b.ins().icmp_imm(IntCC::Equal, y, -1);
+            let overflow = b.ins().band(min, minus_one);
+            let invalid = b.ins().bor(by_zero, overflow);
+            let ok = b.create_block();
+            b.ins().brif(invalid, bail, &[], ok, &[]);
+            b.switch_to_block(ok);
+            return b.ins().sdiv(x, y);
+        }
+    };
+    let ok = b.create_block();
+    b.ins().brif(overflow, bail, &[], ok, &[]);
+    b.switch_to_block(ok);
+    value
+}
+
+/// Adapter from `EntryFn` to the body's register-argument signature.
+fn build_entry(module: &mut JITModule, num_params: usize, body: FuncId) -> Option<FuncId> {
+    let pointer = module.target_config().pointer_type();
+    let mut sig = module.make_signature();
+    sig.params.push(AbiParam::new(pointer));
+    sig.params.push(AbiParam::new(types::I64));
+    sig.params.push(AbiParam::new(pointer));
+    sig.returns.push(AbiParam::new(types::I64));
+    let id = module.declare_anonymous_function(&sig).ok()?;
+
+    let mut ctx = module.make_context();
+    ctx.func.signature = sig;
+    ctx.func.name = UserFuncName::user(0, id.as_u32());
+    let mut builder_ctx = FunctionBuilderContext::new();
+    let mut b = FunctionBuilder::new(&mut ctx.func, &mut builder_ctx);
+    let body_ref = module.declare_func_in_func(body, b.func);
+    let block = b.create_block();
+    b.append_block_params_for_function_params(block);
+    b.switch_to_block(block);
+    let params = b.block_params(block).to_vec();
+    let mut args = (0..num_params)
+        .map(|idx| {
+            b.ins()
+                .load(types::I64, MemFlags::trusted(), params[0], (idx * 8) as i32)
+        })
+        .collect::<Vec<_>>();
+    args.push(params[1]);
+    args.push(params[2]);
+    let call = b.ins().call(body_ref, &args);
+    let value = b.inst_results(call)[0];
+    b.ins().return_(&[value]);
+    b.seal_all_blocks();
+    b.finalize();
+    module.define_function(id, &mut ctx).ok()?;
+    Some(id)
+}
@@ -1,16 +1,45 @@
+use std::fmt;
+use std::io::{self, BufRead};
+
 use crate::position::Position;
 use crate::token::{lookup_ident, Token, TokenKind};
 
+/// Characters already consumed are dropped from a streaming lexer's buffer
+/// once at least this many have piled up.
+const DISCARD_THRESHOLD: usize = 4096;
+
+/// How `Lexer::tokenize_all` treats trivia.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LexerOptions {
+    /// Emit `#` comments as `Comment` tokens instead of skipping them, for
+    /// tools such as formatters that must keep them.
+    pub comments: bool,
+}
+
+impl LexerOptions {
+    pub fn with_comments(mut self, comments: bool) -> Self {
+        self.comments = comments;
+        self
+    }
+}
+
 /// Lexer for Monkey source input.
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Lexer {
+    /// The whole source for `new`; empty for `from_reader`.
     source: String,
+    /// The characters read so far, less those a streaming lexer has
+    /// discarded; positions below index into it.
     input: Vec<char>,
+    /// The rest of the input of `from_reader`, until it runs out or fails.
+    stream: Option<CharReader>,
+    read_error: Option<io::Error>,
     position: usize,
     read_position: usize,
     ch: Option<char>,
     line: usize,
     col: usize,
+    keep_comments: bool,
 }
 
 impl Lexer {
@@ -19,25 +48,72 @@ impl Lexer {
         let mut lexer = Self {
             input: source.chars().collect(),
             source,
+            stream: None,
+            read_error: None,
+            position: 0,
+            read_position: 0,
+            ch: None,
+            line: 1,
+            col: 0,
+            keep_comments: false,
+        };
+        lexer.read_char();
+        lexer
+    }
+
+    /// Lex source decoded as UTF-8 from `reader` while tokens are asked
+    /// for, keeping only the text of the token being read and a little
+    /// lookahead in memory. A read error or invalid UTF-8 ends the input as
+    /// if it had run out; `read_error` reports it.
+    pub fn from_reader(reader: impl BufRead + 'static) -> Self {
+        let mut lexer = Self {
+            source: String::new(),
+            input: Vec::new(),
+            stream: Some(CharReader {
+                reader: Box::new(reader),
+                partial: Vec::new(),
+            }),
+            read_error: None,
             position: 0,
             read_position: 0,
             ch: None,
             line: 1,
             col: 0,
+            keep_comments: false,
         };
         lexer.read_char();
         lexer
     }
 
+    /// The source of a lexer made with `new`, or `""` for `from_reader`.
     pub fn input(&self) -> &str {
         &self.source
     }
 
+    /// The error that cut a `from_reader` lexer's input short, if any.
+    pub fn read_error(&self) -> Option<&io::Error> {
+        self.read_error.as_ref()
+    }
+
     pub fn next_token(&mut self) -> Token {
+        if self.stream.is_some() && self.position >= DISCARD_THRESHOLD {
+            self.input.drain(..self.position);
+            self.read_position -= self.position;
+            self.position = 0;
+        }
         self.skip_whitespace_and_comments();
 
         let pos = self.current_pos();
+        let token = self.read_token(pos);
+        token.with_end(self.current_pos())
+    }
+
+    fn read_token(&mut self, pos: Position) -> Token {
         match self.ch {
+            Some('#') => {
+                let literal = self.read_comment();
+                Token::new(TokenKind::Comment, literal, pos)
+            }
             Some('=') if self.peek_char() == Some('=') => {
                 self.read_char();
                 self.read_char();
@@ -100,8 +176,8 @@ impl Lexer {
                 Token::new(kind, literal, pos)
             }
             Some(ch) if ch.is_ascii_digit() => {
-                let literal = self.read_number();
-                Token::new(TokenKind::Int, literal, pos)
+                let (kind, literal) = self.read_number();
+                Token::new(kind, literal, pos)
             }
             Some(ch) => {
                 self.read_char();
@@ -111,7 +187,9 @@ impl Lexer {
         }
     }
 
-    pub fn tokenize_all(mut self) -> Vec<Token> {
+    /// Every remaining token up to and including `Eof`.
+    pub fn tokenize_all(mut self, options: LexerOptions) -> Vec<Token> {
+        self.keep_comments = options.comments;
         // TODO(step-4): parser should consume tokens incrementally from `next_token`.
         let mut tokens = Vec::new();
         loop {
@@ -125,7 +203,28 @@ impl Lexer {
         tokens
     }
 
+    /// Buffer input up to index `idx` if the stream has that much.
+    fn fill(&mut self, idx: usize) {
+        while idx >= self.input.len() {
+            let Some(stream) = self.stream.as_mut() else {
+                return;
+            };
+            match stream.read_into(&mut self.input) {
+                Ok(true) => {}
+                Ok(false) => self.stream = None,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    self.read_error = Some(err);
+                    self.stream = None;
+                }
+            }
+        }
+    }
+
     fn read_char(&mut self) {
+        // `peek_char` and `exponent_follows` look up to two characters
+        // past the one about to become current.
+        self.fill(self.read_position + 2);
         let prev = self.ch;
         if let Some(next) = self.input.get(self.read_position).copied() {
             self.position = self.read_position;
@@ -171,7 +270,9 @@ impl Lexer {
         self.input.get(self.read_position).copied()
     }
 
-    fn current_pos(&self) -> Position {
+    /// Position of the next unread character, which after `next_token` is
+    /// just past the token it returned.
+    pub(crate) fn current_pos(&self) -> Position {
         Position::new(self.line, self.col)
     }
 
@@ -190,8 +291,8 @@ impl Lexer {
                 }
             }
 
-            if self.ch == Some('#') {
-                self.skip_line_comment();
+            if self.ch == Some('#') && !self.keep_comments {
+                self.read_comment();
                 continue;
             }
 
@@ -199,13 +300,17 @@ impl Lexer {
         }
     }
 
-    fn skip_line_comment(&mut self) {
+    /// Read a `#` comment up to, but not including, the end of the line.
+    fn read_comment(&mut self) -> String {
+        let start = self.position;
         while let Some(ch) = self.ch {
             if ch == '\n' {
                 break;
             }
             self.read_char();
         }
+        let text: String = self.input[start..self.position].iter().collect();
+        text.trim_end_matches('\r').to_string()
     }
 
     fn read_identifier(&mut self) -> String {
@@ -220,16 +325,52 @@ impl Lexer {
         self.input[start..self.position].iter().collect()
     }
 
-    fn read_number(&mut self) -> String {
+    /// Read an integer, or a float with a fraction (`1.5`), an exponent
+    /// (`2e3`) or both. A number followed by a further `.digit`, as in
+    /// `1.2.3`, is read whole as one `Illegal` token.
+    fn read_number(&mut self) -> (TokenKind, String) {
         let start = self.position;
-        while let Some(ch) = self.ch {
-            if ch.is_ascii_digit() {
+        let mut kind = TokenKind::Int;
+        self.skip_digits();
+        if self.ch == Some('.') && self.peek_char().is_some_and(|ch| ch.is_ascii_digit()) {
+            kind = TokenKind::Float;
+            self.read_char();
+            self.skip_digits();
+        }
+        if matches!(self.ch, Some('e' | 'E')) && self.exponent_follows() {
+            kind = TokenKind::Float;
+            self.read_char();
+            if matches!(self.ch, Some('+' | '-')) {
                 self.read_char();
-            } else {
-                break;
             }
+            self.skip_digits();
         }
-        self.input[start..self.position].iter().collect()
+        if self.ch == Some('.') && self.peek_char().is_some_and(|ch| ch.is_ascii_digit()) {
+            kind = TokenKind::Illegal;
+            while self
+                .ch
+                .is_some_and(|ch| ch == '.' || ch.is_ascii_alphanumeric())
+            {
+                self.read_char();
+            }
+        }
+        (kind, self.input[start..self.position].iter().collect())
+    }
+
+    fn skip_digits(&mut self) {
+        while self.ch.is_some_and(|ch| ch.is_ascii_digit()) {
+            self.read_char();
+        }
+    }
+
+    /// Whether the `e` at the current character starts an exponent: it is
+    /// followed by digits, optionally after a sign.
+    fn exponent_follows(&self) -> bool {
+        let mut next = self.read_position;
+        if matches!(self.input.get(next), Some('+' | '-')) {
+            next += 1;
+        }
+        self.input.get(next).is_some_and(|ch| ch.is_ascii_digit())
     }
 
     fn read_string(&mut self) -> (String, bool) {
@@ -250,6 +391,64 @@ impl Lexer {
     }
 }
 
+/// UTF-8 decoding of a `from_reader` lexer's input, a chunk at a time.
+struct CharReader {
+    reader: Box<dyn BufRead>,
+    /// Bytes read but not yet decoded, such as a character split between
+    /// two reads.
+    partial: Vec<u8>,
+}
+
+impl CharReader {
+    /// Decode the next chunk of input onto `out`; `Ok(false)` once there is
+    /// none left.
+    fn read_into(&mut self, out: &mut Vec<char>) -> io::Result<bool> {
+        loop {
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                if self.partial.is_empty() {
+                    return Ok(false);
+                }
+                return Err(invalid_utf8());
+            }
+            let len = buf.len();
+            self.partial.extend_from_slice(buf);
+            self.reader.consume(len);
+            let (valid, invalid) = match std::str::from_utf8(&self.partial) {
+                Ok(text) => (text.len(), false),
+                // With no `error_len` only the last character is cut short
+                // and the rest of it is still to be read.
+                Err(err) => (err.valid_up_to(), err.error_len().is_some()),
+            };
+            if valid > 0 {
+                let text =
+                    std::str::from_utf8(&self.partial[..valid]).map_err(|_| invalid_utf8())?;
+                out.extend(text.chars());
+                self.partial.drain(..valid);
+                return Ok(true);
+            }
+            if invalid {
+                return Err(invalid_utf8());
+            }
+        }
+    }
+}
+
+impl fmt::Debug for CharReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CharReader")
+            .field("partial", &self.partial)
+            .finish_non_exhaustive()
+    }
+}
+
+fn invalid_utf8() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "stream did not contain valid UTF-8",
+    )
+}
+
 fn is_ident_start(ch: char) -> bool {
     ch == '_' || ch.is_ascii_alphabetic()
 }
@@ -1,23 +1,51 @@
 //! Monkey compiler + VM library skeleton.
 
+pub mod asm;
 pub mod ast;
+pub mod ast_json;
+pub mod bench;
 pub mod builtins;
 pub mod bytecode;
+pub mod cfg;
 pub mod cli;
+pub mod codegen_c;
 pub mod compiler;
+pub mod coverage;
+pub mod cst;
+pub mod debugger;
+pub mod diagnostics;
+pub mod explain;
+pub mod fold;
+pub mod format;
+pub mod genprog;
+pub mod highlight;
+#[cfg(feature = "jit")]
+mod jit;
+pub mod json;
 pub mod lexer;
+#[cfg(feature = "line-editor")]
+pub mod line_editor;
+pub mod mkc;
 pub mod object;
 pub mod parse_error;
 pub mod parser;
+pub mod peephole;
 pub mod position;
 pub mod pretty;
+pub mod profile;
+#[cfg(feature = "register-vm")]
+pub mod regvm;
 pub mod repl;
 pub mod runner;
 pub mod runtime_error;
 pub mod source;
 pub mod symbol_table;
+pub mod testing;
 pub mod token;
+pub mod visit;
 pub mod vm;
+pub mod wasm;
+pub mod watch;
 
 pub use position::Position;
 pub use token::{Token, TokenKind};
@@ -0,0 +1,47 @@
+//! Terminal line editing for the REPL: arrow keys, in-line editing and
+//! Ctrl-R history search, provided by `rustyline`.
+
+use std::io;
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use crate::repl::LineReader;
+
+pub struct LineEditor {
+    editor: DefaultEditor,
+}
+
+impl LineEditor {
+    /// An editor that can recall `history`, oldest entry first.
+    pub fn new(history: &[String]) -> io::Result<Self> {
+        let mut editor = DefaultEditor::new().map_err(into_io)?;
+        for line in history {
+            editor.add_history_entry(line.as_str()).map_err(into_io)?;
+        }
+        Ok(Self { editor })
+    }
+}
+
+impl LineReader for LineEditor {
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        match self.editor.readline(prompt) {
+            Ok(line) => Ok(Some(line)),
+            // Ctrl-C drops the line being edited, like an empty line.
+            Err(ReadlineError::Interrupted) => Ok(Some(String::new())),
+            Err(ReadlineError::Eof) => Ok(None),
+            Err(err) => Err(into_io(err)),
+        }
+    }
+
+    fn add_history(&mut self, line: &str) {
+        let _ = self.editor.add_history_entry(line);
+    }
+}
+
+fn into_io(err: ReadlineError) -> io::Error {
+    match err {
+        ReadlineError::Io(err) => err,
+        err => io::Error::other(err),
+    }
+}
@@ -1,94 +1,668 @@
 use std::env;
 use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use monkey_rust_compiler::cli::{parse_args, Command};
-use monkey_rust_compiler::repl::ReplSession;
-use monkey_rust_compiler::runner::{dump_ast, format_tokens, run_source, RunnerError};
+use monkey_rust_compiler::bench::{BenchStats, DEFAULT_ITERATIONS, DEFAULT_WARMUP};
+use monkey_rust_compiler::bytecode::Chunk;
+use monkey_rust_compiler::cfg;
+use monkey_rust_compiler::cli::{parse_args, Command, RunFlags, STDIN_PATH, USAGE};
+use monkey_rust_compiler::codegen_c::emit_c;
+use monkey_rust_compiler::compiler::{CompileError, CompileWarning, CompilerOptions};
+use monkey_rust_compiler::debugger::Debugger;
+use monkey_rust_compiler::diagnostics::{runner_error_json, warnings_json, ErrorFormat};
+use monkey_rust_compiler::explain::explain;
+use monkey_rust_compiler::format::{format_source, FormatOptions};
+use monkey_rust_compiler::mkc::is_mkc;
+use monkey_rust_compiler::object::Object;
+use monkey_rust_compiler::parse_error::ParseError;
+use monkey_rust_compiler::pretty::{AstFormat, TokenFormat};
+use monkey_rust_compiler::repl::{default_history_path, default_rc_path, ReplSession};
+use monkey_rust_compiler::runner::{
+    compile_source, compile_source_for, compile_source_with_warnings, compile_wasm, dump_ast_as,
+    format_tokens_as, run_chunk_with, run_source, run_source_with, RunOptions, RunOutcome,
+    RunnerError,
+};
+use monkey_rust_compiler::runtime_error::RuntimeError;
+use monkey_rust_compiler::source::SourceMap;
+use monkey_rust_compiler::testing::{discover_tests, failure_summary};
+use monkey_rust_compiler::vm::{OutputSink, TraceSink, VmOptions};
+use monkey_rust_compiler::watch::{FileWatch, DEFAULT_POLL_INTERVAL};
 
-const USAGE: &str = "Usage: monkey [run <path> | bench <path> | --tokens <path> | --ast <path>]";
-
-fn print_usage(stderr: bool) {
-    if stderr {
-        eprintln!("{USAGE}");
+/// Contents of `path`, or of stdin for `cli::STDIN_PATH`.
+fn read_file(path: &str) -> Result<String, ExitCode> {
+    let text = if path == STDIN_PATH {
+        io::read_to_string(io::stdin())
     } else {
-        println!("{USAGE}");
-    }
+        fs::read_to_string(path)
+    };
+    text.map_err(|err| {
+        eprintln!("Failed to read {path}: {err}");
+        ExitCode::from(1)
+    })
 }
 
-fn read_file(path: &str) -> Result<String, ExitCode> {
-    fs::read_to_string(path).map_err(|err| {
+fn read_bytes(path: &str) -> Result<Vec<u8>, ExitCode> {
+    let bytes = if path == STDIN_PATH {
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes).map(|_| bytes)
+    } else {
+        fs::read(path)
+    };
+    bytes.map_err(|err| {
         eprintln!("Failed to read {path}: {err}");
         ExitCode::from(1)
     })
 }
 
-fn print_parse_errors(path: &str, errors: &[monkey_rust_compiler::parse_error::ParseError]) {
+/// Source to quote under diagnostics. Snippets are only shown when stderr
+/// is a terminal, so piped output keeps the Java implementation's format.
+fn snippet_source(source: Option<&str>) -> Option<&str> {
+    source.filter(|_| io::stderr().is_terminal())
+}
+
+fn print_parse_errors(path: &str, errors: &[ParseError], source: Option<&str>) {
     eprintln!("Parse errors in {path}:");
     for err in errors {
-        eprintln!("- {err}");
+        match snippet_source(source) {
+            Some(source) => eprintln!("- {}", err.render_with_source(source)),
+            None => eprintln!("- {err}"),
+        }
     }
 }
 
-fn run_file(path: &str, bench: bool) -> ExitCode {
-    let source = match read_file(path) {
-        Ok(s) => s,
+fn print_compile_errors(path: &str, errors: &[CompileError], source: Option<&str>) {
+    eprintln!("Compile error in {path}:");
+    for err in errors {
+        match snippet_source(source) {
+            Some(source) => eprintln!("{}", err.render_with_source(source)),
+            None => eprintln!("{err}"),
+        }
+    }
+}
+
+fn print_runtime_error(path: &str, err: &RuntimeError, source: Option<&str>) {
+    eprintln!("Runtime error in {path}:");
+    match snippet_source(source) {
+        Some(source) => eprintln!("{}", err.format_multiline_with_source(source)),
+        None => eprintln!("{}", err.format_multiline()),
+    }
+}
+
+fn print_warnings(path: &str, warnings: &[CompileWarning], format: ErrorFormat) {
+    if format == ErrorFormat::Json {
+        for line in warnings_json(path, warnings) {
+            eprintln!("{line}");
+        }
+        return;
+    }
+    if warnings.is_empty() {
+        return;
+    }
+    eprintln!("Warnings in {path}:");
+    for warning in warnings {
+        eprintln!("- {warning}");
+    }
+}
+
+fn print_stats(path: &str, chunk: &Chunk) {
+    eprintln!("Bytecode stats for {path}:");
+    for line in chunk.stats().to_string().lines() {
+        eprintln!("  {line}");
+    }
+}
+
+fn run_file(path: &str, args: &[String], flags: &RunFlags, bench: bool) -> ExitCode {
+    let bytes = match read_bytes(path) {
+        Ok(bytes) => bytes,
         Err(code) => return code,
     };
 
-    let started = Instant::now();
-    match run_source(&source) {
+    let mut options = RunOptions::default()
+        .with_args(args.to_vec())
+        .with_vm(
+            VmOptions::default()
+                .with_superinstructions(!flags.no_fuse)
+                .with_coverage(flags.coverage),
+        )
+        .with_profile(flags.profile);
+    options.timeout = flags.timeout_ms.map(Duration::from_millis);
+    if flags.trace {
+        options = options.with_trace(TraceSink::new(|line| eprintln!("{line}")));
+    }
+    // Print as the program runs; bench keeps only the last run's output.
+    if !bench {
+        options = options.with_output(OutputSink::from_writer(io::stdout()));
+    }
+    let run_once = |chunk: Chunk| {
+        if flags.register {
+            run_register(chunk, &options)
+        } else {
+            run_chunk_with(chunk, &options)
+        }
+    };
+    let mut bench_stats = None;
+    let mut run = |chunk: Chunk| {
+        if flags.stats {
+            print_stats(path, &chunk);
+        }
+        if !bench {
+            return run_once(chunk);
+        }
+        let warmup = flags.warmup.unwrap_or(DEFAULT_WARMUP);
+        let iterations = flags.iterations.unwrap_or(DEFAULT_ITERATIONS);
+        for _ in 0..warmup {
+            run_once(chunk.clone())?;
+        }
+        let mut samples = Vec::with_capacity(iterations);
+        let mut last = None;
+        for _ in 0..iterations {
+            let started = Instant::now();
+            last = Some(run_once(chunk.clone())?);
+            samples.push(started.elapsed());
+        }
+        bench_stats = BenchStats::new(samples).map(|stats| (warmup, stats));
+        Ok(last.expect("bench runs at least once"))
+    };
+    let mut source = None;
+    let result = if is_mkc(&bytes) {
+        match Chunk::deserialize(&bytes) {
+            Ok(chunk) => run(chunk),
+            Err(err) => {
+                eprintln!("Invalid bytecode file {path}: {err}");
+                return ExitCode::from(1);
+            }
+        }
+    } else {
+        let Ok(text) = String::from_utf8(bytes) else {
+            eprintln!("Failed to read {path}: stream did not contain valid UTF-8");
+            return ExitCode::from(1);
+        };
+        let source = source.insert(text);
+        compile_source_for(source, &options).and_then(|(chunk, warnings)| {
+            if flags.warnings {
+                print_warnings(path, &warnings, flags.error_format);
+            }
+            run(chunk)
+        })
+    };
+    match result {
         Ok(outcome) => {
             for line in outcome.output {
                 println!("{line}");
             }
             println!("{}", outcome.result.inspect());
-            if bench {
-                let ms = started.elapsed().as_secs_f64() * 1000.0;
+            if let Some((warmup, stats)) = bench_stats {
+                let ms = stats.median().as_secs_f64() * 1000.0;
                 eprintln!("Execution time: {ms:.2} ms");
+                eprintln!(
+                    "{}",
+                    stats.render(warmup, outcome.stats.map(|stats| stats.instructions))
+                );
+            }
+            if let Some(stats) = outcome.stats.filter(|_| flags.stats) {
+                eprintln!("Execution stats for {path}:");
+                for line in stats.to_string().lines() {
+                    eprintln!("  {line}");
+                }
+            }
+            if let Some(profile) = outcome.profile {
+                eprintln!("{}", profile.render());
+            }
+            if let Some(coverage) = outcome.coverage {
+                eprintln!("Coverage for {path}:");
+                for line in coverage.render(source.as_deref()).lines() {
+                    eprintln!("  {line}");
+                }
+            }
+            match outcome.result.as_ref() {
+                Object::Integer(code) if flags.exit_with_result => result_exit_code(*code),
+                _ => ExitCode::SUCCESS,
+            }
+        }
+        Err(err) if flags.error_format == ErrorFormat::Json => {
+            for line in runner_error_json(path, &err) {
+                eprintln!("{line}");
+            }
+            ExitCode::from(1)
+        }
+        Err(RunnerError::Parse(errors)) => {
+            print_parse_errors(path, &errors, source.as_deref());
+            ExitCode::from(1)
+        }
+        Err(RunnerError::Compile(errors)) => {
+            print_compile_errors(path, &errors, source.as_deref());
+            ExitCode::from(1)
+        }
+        Err(RunnerError::Runtime(err)) => {
+            print_runtime_error(path, &err, source.as_deref());
+            ExitCode::from(1)
+        }
+    }
+}
+
+/// Exit status for an integer result under `--exit-with-result`; like a
+/// shell, only the low 8 bits are kept, so `-1` exits with 255.
+fn result_exit_code(code: i64) -> ExitCode {
+    ExitCode::from(code.rem_euclid(256) as u8)
+}
+
+/// `run_file` again after each change to `path`, until interrupted. On a
+/// terminal the screen is cleared before every run.
+fn watch_file(path: &str, args: &[String], flags: &RunFlags) -> ExitCode {
+    let mut watch = FileWatch::new(path);
+    let clear = io::stdout().is_terminal();
+    loop {
+        if clear {
+            print!("\x1b[2J\x1b[H");
+        }
+        let started = Instant::now();
+        let code = run_file(path, args, flags, false);
+        let ms = started.elapsed().as_secs_f64() * 1000.0;
+        let status = if code == ExitCode::SUCCESS {
+            "Finished"
+        } else {
+            "Failed"
+        };
+        eprintln!("[watch] {status} in {ms:.2} ms; waiting for changes to {path}");
+        watch.wait(DEFAULT_POLL_INTERVAL);
+    }
+}
+
+#[cfg(feature = "register-vm")]
+fn run_register(chunk: Chunk, options: &RunOptions) -> Result<RunOutcome, RunnerError> {
+    monkey_rust_compiler::runner::run_chunk_register(chunk, options)
+}
+
+#[cfg(not(feature = "register-vm"))]
+fn run_register(chunk: Chunk, options: &RunOptions) -> Result<RunOutcome, RunnerError> {
+    eprintln!("--register needs a build with `--features register-vm`; using the stack VM");
+    run_chunk_with(chunk, options)
+}
+
+fn debug_file(path: &str, args: &[String]) -> ExitCode {
+    let source = match read_file(path) {
+        Ok(s) => s,
+        Err(code) => return code,
+    };
+
+    match Debugger::from_source(&source, args) {
+        Ok(mut debugger) => ExitCode::from(debugger.run_stdio() as u8),
+        Err(RunnerError::Parse(errors)) => {
+            print_parse_errors(path, &errors, Some(&source));
+            ExitCode::from(1)
+        }
+        Err(RunnerError::Compile(errors)) => {
+            print_compile_errors(path, &errors, Some(&source));
+            ExitCode::from(1)
+        }
+        Err(RunnerError::Runtime(err)) => {
+            print_runtime_error(path, &err, Some(&source));
+            ExitCode::from(1)
+        }
+    }
+}
+
+/// Folded stacks go to stdout so they can be piped into a flamegraph
+/// tool; the program's own output goes to stderr instead.
+fn profile_file(path: &str, args: &[String]) -> ExitCode {
+    let source = match read_file(path) {
+        Ok(s) => s,
+        Err(code) => return code,
+    };
+
+    let options = RunOptions::default()
+        .with_args(args.to_vec())
+        .with_profile(true)
+        .with_output(OutputSink::from_writer(io::stderr()));
+    match run_source_with(&source, &options) {
+        Ok(outcome) => {
+            if let Some(profile) = outcome.profile {
+                println!("{}", profile.render_folded());
             }
             ExitCode::SUCCESS
         }
         Err(RunnerError::Parse(errors)) => {
-            print_parse_errors(path, &errors);
+            print_parse_errors(path, &errors, Some(&source));
             ExitCode::from(1)
         }
-        Err(RunnerError::Compile(err)) => {
-            eprintln!("Compile error in {path}:");
-            eprintln!("{err}");
+        Err(RunnerError::Compile(errors)) => {
+            print_compile_errors(path, &errors, Some(&source));
             ExitCode::from(1)
         }
         Err(RunnerError::Runtime(err)) => {
-            eprintln!("Runtime error in {path}:");
-            eprintln!("{}", err.format_multiline());
+            print_runtime_error(path, &err, Some(&source));
+            ExitCode::from(1)
+        }
+    }
+}
+
+fn compile_file(path: &str, output: Option<&str>, stats: bool) -> ExitCode {
+    let source = match read_file(path) {
+        Ok(s) => s,
+        Err(code) => return code,
+    };
+    let output = output
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(path).with_extension("mkc"));
+
+    let chunk = match compile_source_with_warnings(&source, CompilerOptions::default()) {
+        Ok((chunk, warnings)) => {
+            print_warnings(path, &warnings, ErrorFormat::Text);
+            if stats {
+                print_stats(path, &chunk);
+            }
+            chunk
+        }
+        Err(RunnerError::Parse(errors)) => {
+            print_parse_errors(path, &errors, Some(&source));
+            return ExitCode::from(1);
+        }
+        Err(RunnerError::Compile(errors)) => {
+            print_compile_errors(path, &errors, Some(&source));
+            return ExitCode::from(1);
+        }
+        Err(RunnerError::Runtime(_)) => unreachable!("compiling does not run the program"),
+    };
+    let bytes = match chunk.serialize() {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            eprintln!("Failed to serialize {path}: {err}");
+            return ExitCode::from(1);
+        }
+    };
+    match fs::write(&output, bytes) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Failed to write {}: {err}", output.display());
             ExitCode::from(1)
         }
     }
 }
 
-fn tokens_file(path: &str) -> ExitCode {
+/// Load `path` as a `.mkc` file, or compile it when it is source code.
+fn load_chunk(path: &str) -> Result<Chunk, ExitCode> {
+    let bytes = read_bytes(path)?;
+    if is_mkc(&bytes) {
+        return Chunk::deserialize(&bytes).map_err(|err| {
+            eprintln!("Invalid bytecode file {path}: {err}");
+            ExitCode::from(1)
+        });
+    }
+    let Ok(source) = String::from_utf8(bytes) else {
+        eprintln!("Failed to read {path}: stream did not contain valid UTF-8");
+        return Err(ExitCode::from(1));
+    };
+    match compile_source(&source) {
+        Ok(chunk) => Ok(chunk),
+        Err(RunnerError::Parse(errors)) => {
+            print_parse_errors(path, &errors, Some(&source));
+            Err(ExitCode::from(1))
+        }
+        Err(RunnerError::Compile(errors)) => {
+            print_compile_errors(path, &errors, Some(&source));
+            Err(ExitCode::from(1))
+        }
+        Err(RunnerError::Runtime(_)) => unreachable!("compiling does not run the program"),
+    }
+}
+
+fn emit_c_file(path: &str, output: Option<&str>) -> ExitCode {
+    let chunk = match load_chunk(path) {
+        Ok(chunk) => chunk,
+        Err(code) => return code,
+    };
+    let output = output
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(path).with_extension("c"));
+    let source = match emit_c(&chunk) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("Invalid bytecode in {path}: {err}");
+            return ExitCode::from(1);
+        }
+    };
+    match fs::write(&output, source) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Failed to write {}: {err}", output.display());
+            ExitCode::from(1)
+        }
+    }
+}
+
+fn cfg_file(path: &str) -> ExitCode {
+    match load_chunk(path) {
+        Ok(chunk) => {
+            print!("{}", cfg::to_dot(&chunk));
+            ExitCode::SUCCESS
+        }
+        Err(code) => code,
+    }
+}
+
+/// Format every file, reporting (with `check`) or rewriting those that
+/// change. Fails if any file does not parse, cannot be written or, with
+/// `check`, is not formatted.
+fn fmt_files(paths: &[String], check: bool) -> ExitCode {
+    let mut ok = true;
+    for path in paths {
+        let source = match read_file(path) {
+            Ok(s) => s,
+            Err(_) => {
+                ok = false;
+                continue;
+            }
+        };
+        let formatted = match format_source(&source, FormatOptions::default()) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                print_parse_errors(path, &errors, Some(&source));
+                ok = false;
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        if check {
+            eprintln!("{path} is not formatted");
+            ok = false;
+        } else if let Err(err) = fs::write(path, formatted) {
+            eprintln!("Failed to write {path}: {err}");
+            ok = false;
+        }
+    }
+    if ok {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
+
+fn check_files(paths: &[String], deny_warnings: bool, format: ErrorFormat) -> ExitCode {
+    let mut ok = true;
+    for path in paths {
+        let source = match read_file(path) {
+            Ok(s) => s,
+            Err(_) => {
+                ok = false;
+                continue;
+            }
+        };
+        match compile_source_with_warnings(&source, CompilerOptions::default()) {
+            Ok((_, warnings)) => {
+                print_warnings(path, &warnings, format);
+                if deny_warnings && !warnings.is_empty() {
+                    ok = false;
+                }
+            }
+            Err(err) if format == ErrorFormat::Json => {
+                for line in runner_error_json(path, &err) {
+                    eprintln!("{line}");
+                }
+                ok = false;
+            }
+            Err(RunnerError::Parse(errors)) => {
+                print_parse_errors(path, &errors, Some(&source));
+                ok = false;
+            }
+            Err(RunnerError::Compile(errors)) => {
+                print_compile_errors(path, &errors, Some(&source));
+                ok = false;
+            }
+            Err(RunnerError::Runtime(_)) => unreachable!("checking does not run the program"),
+        }
+    }
+    if ok {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
+
+fn disasm_file(path: &str) -> ExitCode {
+    match load_chunk(path) {
+        Ok(chunk) => {
+            println!("{}", chunk.disassemble());
+            ExitCode::SUCCESS
+        }
+        Err(code) => code,
+    }
+}
+
+fn wasm_file(path: &str, output: Option<&str>) -> ExitCode {
+    let source = match read_file(path) {
+        Ok(s) => s,
+        Err(code) => return code,
+    };
+    let output = output
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(path).with_extension("wasm"));
+
+    let bytes = match compile_wasm(&source) {
+        Ok(bytes) => bytes,
+        Err(RunnerError::Parse(errors)) => {
+            print_parse_errors(path, &errors, Some(&source));
+            return ExitCode::from(1);
+        }
+        Err(RunnerError::Compile(errors)) => {
+            print_compile_errors(path, &errors, Some(&source));
+            return ExitCode::from(1);
+        }
+        Err(RunnerError::Runtime(_)) => unreachable!("compiling does not run the program"),
+    };
+    match fs::write(&output, bytes) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Failed to write {}: {err}", output.display());
+            ExitCode::from(1)
+        }
+    }
+}
+
+fn tokens_file(path: &str, format: TokenFormat) -> ExitCode {
     let source = match read_file(path) {
         Ok(s) => s,
         Err(code) => return code,
     };
-    println!("{}", format_tokens(&source));
+    println!("{}", format_tokens_as(&source, format));
     ExitCode::SUCCESS
 }
 
-fn ast_file(path: &str) -> ExitCode {
+fn ast_file(path: &str, format: AstFormat) -> ExitCode {
     let source = match read_file(path) {
         Ok(s) => s,
         Err(code) => return code,
     };
 
-    match dump_ast(&source) {
+    match dump_ast_as(&source, format) {
         Ok(ast) => {
             println!("{ast}");
             ExitCode::SUCCESS
         }
         Err(errors) => {
-            print_parse_errors(path, &errors);
+            print_parse_errors(path, &errors, Some(&source));
+            ExitCode::from(1)
+        }
+    }
+}
+
+/// Run each test file, printing `PASS`/`FAIL` lines to stdout with the
+/// errors on stderr, then the failures and counts.
+fn test_files(paths: &[String]) -> ExitCode {
+    let roots: Vec<PathBuf> = if paths.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        paths.iter().map(PathBuf::from).collect()
+    };
+    let discovery = discover_tests(&roots);
+    let mut failures = Vec::new();
+    for (path, err) in &discovery.errors {
+        let path = path.display();
+        println!("FAIL {path}");
+        eprintln!("Failed to find tests in {path}: {err}");
+        failures.push(format!("{path}: {err}"));
+    }
+    let files = discovery.files;
+    let mut passed = 0;
+    let mut sources = SourceMap::new();
+    for file in &files {
+        let path = file.display().to_string();
+        let source = match fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(err) => {
+                println!("FAIL {path}");
+                eprintln!("Failed to read {path}: {err}");
+                failures.push(format!("{path}: {err}"));
+                continue;
+            }
+        };
+        let file_id = sources.add(path.clone(), source.clone());
+        let started = Instant::now();
+        match run_source(&source) {
+            Ok(_) => {
+                let ms = started.elapsed().as_secs_f64() * 1000.0;
+                println!("PASS {path} ({ms:.2} ms)");
+                passed += 1;
+            }
+            Err(err) => {
+                println!("FAIL {path}");
+                match &err {
+                    RunnerError::Parse(errors) => print_parse_errors(&path, errors, Some(&source)),
+                    RunnerError::Compile(errors) => {
+                        print_compile_errors(&path, errors, Some(&source))
+                    }
+                    RunnerError::Runtime(err) => print_runtime_error(&path, err, Some(&source)),
+                }
+                failures.push(failure_summary(&sources, file_id, &err));
+            }
+        }
+    }
+    if !failures.is_empty() {
+        println!("\nFailures:");
+        for failure in &failures {
+            println!("  {failure}");
+        }
+    }
+    println!("\n{passed} passed, {} failed", failures.len());
+    if failures.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
+
+fn explain_code(code: &str) -> ExitCode {
+    match explain(code) {
+        Some(explanation) => {
+            print!("{}", explanation.render());
+            ExitCode::SUCCESS
+        }
+        None => {
+            eprintln!("Unknown error code: {code}");
             ExitCode::from(1)
         }
     }
@@ -98,21 +672,76 @@ fn main() -> ExitCode {
     let args: Vec<String> = env::args().skip(1).collect();
     let command = match parse_args(&args) {
         Ok(cmd) => cmd,
-        Err(()) => {
-            print_usage(true);
+        Err(err) => {
+            eprintln!("{err}");
             return ExitCode::from(2);
         }
     };
 
     match command {
         Command::Help => {
-            print_usage(false);
+            println!("{USAGE}");
             ExitCode::SUCCESS
         }
-        Command::Repl => ExitCode::from(ReplSession::new().run_stdio() as u8),
-        Command::Run { path } => run_file(&path, false),
-        Command::Bench { path } => run_file(&path, true),
-        Command::Tokens { path } => tokens_file(&path),
-        Command::Ast { path } => ast_file(&path),
+        Command::Repl { no_color } => {
+            let color = !no_color
+                && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                && io::stdout().is_terminal();
+            let mut session = ReplSession::new().with_color(color);
+            if let Some(path) = default_history_path() {
+                session = session.with_history_file(path);
+            }
+            if let Some(path) = default_rc_path() {
+                match session.run_rc_file(&path) {
+                    Ok(failures) => {
+                        for failure in failures {
+                            eprintln!("{failure}");
+                        }
+                    }
+                    // Only a startup file named by `MONKEY_RC` has to exist.
+                    Err(err)
+                        if err.kind() != io::ErrorKind::NotFound
+                            || env::var_os("MONKEY_RC").is_some() =>
+                    {
+                        eprintln!("Failed to read {}: {err}", path.display());
+                    }
+                    Err(_) => {}
+                }
+            }
+            #[cfg(feature = "line-editor")]
+            if io::stdin().is_terminal() {
+                if let Ok(mut editor) =
+                    monkey_rust_compiler::line_editor::LineEditor::new(session.history())
+                {
+                    return ExitCode::from(session.run(&mut editor) as u8);
+                }
+            }
+            ExitCode::from(session.run_stdio() as u8)
+        }
+        Command::Run { path, args, flags } if flags.watch => watch_file(&path, &args, &flags),
+        Command::Run { path, args, flags } => run_file(&path, &args, &flags, false),
+        Command::Bench { path, args, flags } => run_file(&path, &args, &flags, true),
+        Command::Debug { path, args } => debug_file(&path, &args),
+        Command::Profile { path, args } => profile_file(&path, &args),
+        Command::Compile {
+            path,
+            output,
+            stats,
+        } => compile_file(&path, output.as_deref(), stats),
+        Command::EmitC { path, output } => emit_c_file(&path, output.as_deref()),
+        Command::Wasm { path, output } => wasm_file(&path, output.as_deref()),
+        Command::Disasm { path } => disasm_file(&path),
+        Command::Cfg { path } => cfg_file(&path),
+        Command::Tokens { path, format } => tokens_file(&path, format),
+        Command::Ast { path, format } => ast_file(&path, format),
+        Command::AstJson { path } => ast_file(&path, AstFormat::Json),
+        Command::Fmt { paths, check } => fmt_files(&paths, check),
+        Command::Check {
+            paths,
+            deny_warnings,
+            error_format,
+        } => check_files(&paths, deny_warnings, error_format),
+        Command::Test { paths } => test_files(&paths),
+        Command::Explain { code } => explain_code(&code),
     }
 }
@@ -0,0 +1,338 @@
+//! `.mkc` compiled-bytecode files.
+//!
+//! Layout (all integers little-endian):
+//!
+//! ```text
+//! magic      b"MKC\0"
+//! version    u32 format version, `FORMAT_VERSION`
+//! opcodes    u64 `bytecode::opcode_set_hash` of the compiler that wrote it
+//! code       u32 length, then instruction bytes
+//! positions  u32 count, then a u32 offset and three (u32 line, u32 col)
+//!            pairs each: the reported position, span start and span end
+//! constants  u32 count, then one tagged constant each
+//! ```
+//!
+//! Constant tags: 0 integer (i64), 1 boolean (u8), 2 string (u32 length +
+//! UTF-8), 3 null, 4 compiled function (optional name, u32 params, u32
+//! locals, then its own code and positions).
+//!
+//! The loader rejects files with another version or opcode-set hash before
+//! reading any code, so bytecode from a different build is reported rather
+//! than misinterpreted.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use crate::bytecode::{opcode_set_hash, Chunk};
+use crate::object::{CompiledFunctionObject, Object, Shared};
+use crate::position::{Position, Span};
+
+pub const MAGIC: &[u8; 4] = b"MKC\0";
+
+/// Bumped whenever the layout below the header changes.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Length of the magic number, version and opcode-set hash.
+pub const HEADER_LEN: usize = MAGIC.len() + 4 + 8;
+
+const TAG_INTEGER: u8 = 0;
+const TAG_BOOLEAN: u8 = 1;
+const TAG_STRING: u8 = 2;
+const TAG_NULL: u8 = 3;
+const TAG_FUNCTION: u8 = 4;
+
+/// Local slots a function can address: `GetLocal`/`SetLocal` take a
+/// one-byte operand.
+const MAX_LOCALS: usize = 1 << 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MkcError {
+    /// The constant pool holds a runtime-only value such as a closure.
+    UnsupportedConstant(String),
+    BadMagic,
+    UnsupportedVersion {
+        found: u32,
+    },
+    /// Written by a compiler whose opcodes differ from this build's.
+    OpcodeSetMismatch {
+        found: u64,
+        expected: u64,
+    },
+    UnexpectedEof {
+        offset: usize,
+    },
+    UnknownConstantTag {
+        tag: u8,
+        offset: usize,
+    },
+    InvalidUtf8 {
+        offset: usize,
+    },
+    TrailingBytes {
+        offset: usize,
+    },
+    /// A function's parameter or local count is more than its code can
+    /// address, which would make calls reserve stack slots for nothing.
+    InvalidLocalCount {
+        num_params: usize,
+        num_locals: usize,
+        offset: usize,
+    },
+}
+
+impl Display for MkcError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            MkcError::UnsupportedConstant(kind) => {
+                write!(f, "cannot serialize {kind} constant")
+            }
+            MkcError::BadMagic => write!(f, "not a compiled Monkey (.mkc) file"),
+            MkcError::UnsupportedVersion { found } => write!(
+                f,
+                "unsupported .mkc format version {found} (this build reads version {FORMAT_VERSION}); recompile the source"
+            ),
+            MkcError::OpcodeSetMismatch { found, expected } => write!(
+                f,
+                "compiled for a different instruction set (opcode hash {found:016x}, this build uses {expected:016x}); recompile the source"
+            ),
+            MkcError::UnexpectedEof { offset } => {
+                write!(f, "unexpected end of file at byte {offset}")
+            }
+            MkcError::UnknownConstantTag { tag, offset } => {
+                write!(f, "unknown constant tag {tag} at byte {offset}")
+            }
+            MkcError::InvalidUtf8 { offset } => {
+                write!(f, "invalid UTF-8 string at byte {offset}")
+            }
+            MkcError::TrailingBytes { offset } => {
+                write!(f, "unexpected trailing data at byte {offset}")
+            }
+            MkcError::InvalidLocalCount {
+                num_params,
+                num_locals,
+                offset,
+            } => write!(
+                f,
+                "function at byte {offset} has {num_params} parameters and {num_locals} locals (at most {MAX_LOCALS} locals, parameters included)"
+            ),
+        }
+    }
+}
+
+/// Whether `bytes` start with the `.mkc` magic number.
+pub fn is_mkc(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// The magic number, version and opcode-set hash this build writes.
+pub fn header() -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.extend_from_slice(&opcode_set_hash().to_le_bytes());
+    out
+}
+
+impl Chunk {
+    /// Encode the chunk, its constant pool and position tables as `.mkc`.
+    pub fn serialize(&self) -> Result<Vec<u8>, MkcError> {
+        let mut out = header();
+        write_code(&mut out, &self.instructions, &self.positions);
+        write_u32(&mut out, self.constants.len());
+        for constant in &self.constants {
+            write_constant(&mut out, constant)?;
+        }
+        Ok(out)
+    }
+
+    /// Decode a chunk produced by `serialize`.
+    pub fn deserialize(bytes: &[u8]) -> Result<Chunk, MkcError> {
+        if !is_mkc(bytes) {
+            return Err(MkcError::BadMagic);
+        }
+        let mut reader = Reader {
+            bytes,
+            offset: MAGIC.len(),
+        };
+        let found = reader.u32()? as u32;
+        if found != FORMAT_VERSION {
+            return Err(MkcError::UnsupportedVersion { found });
+        }
+        let found = reader.u64()?;
+        let expected = opcode_set_hash();
+        if found != expected {
+            return Err(MkcError::OpcodeSetMismatch { found, expected });
+        }
+        let (instructions, positions) = reader.code()?;
+        let count = reader.u32()?;
+        let mut constants = Vec::with_capacity(count.min(bytes.len()));
+        for _ in 0..count {
+            constants.push(reader.constant()?.rc());
+        }
+        if reader.offset != bytes.len() {
+            return Err(MkcError::TrailingBytes {
+                offset: reader.offset,
+            });
+        }
+        Ok(Chunk {
+            instructions,
+            constants,
+            positions,
+        })
+    }
+}
+
+fn write_u32(out: &mut Vec<u8>, value: usize) {
+    out.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+fn write_str(out: &mut Vec<u8>, value: &str) {
+    write_u32(out, value.len());
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn write_code(out: &mut Vec<u8>, instructions: &[u8], positions: &[(usize, Span)]) {
+    write_u32(out, instructions.len());
+    out.extend_from_slice(instructions);
+    write_u32(out, positions.len());
+    for (offset, span) in positions {
+        write_u32(out, *offset);
+        for pos in [span.pos, span.start, span.end] {
+            write_u32(out, pos.line);
+            write_u32(out, pos.col);
+        }
+    }
+}
+
+fn write_constant(out: &mut Vec<u8>, constant: &Object) -> Result<(), MkcError> {
+    match constant {
+        Object::Integer(value) => {
+            out.push(TAG_INTEGER);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        Object::Boolean(value) => {
+            out.push(TAG_BOOLEAN);
+            out.push(u8::from(*value));
+        }
+        Object::String(value) => {
+            out.push(TAG_STRING);
+            write_str(out, value);
+        }
+        Object::Null => out.push(TAG_NULL),
+        Object::CompiledFunction(function) => {
+            out.push(TAG_FUNCTION);
+            match &function.name {
+                Some(name) => {
+                    out.push(1);
+                    write_str(out, name);
+                }
+                None => out.push(0),
+            }
+            write_u32(out, function.num_params);
+            write_u32(out, function.num_locals);
+            write_code(out, &function.instructions, &function.positions);
+        }
+        other => return Err(MkcError::UnsupportedConstant(other.type_name().to_string())),
+    }
+    Ok(())
+}
+
+/// Instruction bytes plus their position table.
+type Code = (Vec<u8>, Vec<(usize, Span)>);
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], MkcError> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(MkcError::UnexpectedEof {
+                offset: self.bytes.len(),
+            })?;
+        let slice = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, MkcError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<usize, MkcError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    fn u64(&mut self) -> Result<u64, MkcError> {
+        let mut value = [0; 8];
+        value.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(value))
+    }
+
+    fn string(&mut self) -> Result<String, MkcError> {
+        let len = self.u32()?;
+        let offset = self.offset;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| MkcError::InvalidUtf8 { offset })
+    }
+
+    fn code(&mut self) -> Result<Code, MkcError> {
+        let len = self.u32()?;
+        let instructions = self.take(len)?.to_vec();
+        let count = self.u32()?;
+        let mut positions = Vec::with_capacity(count.min(self.bytes.len()));
+        for _ in 0..count {
+            let offset = self.u32()?;
+            let pos = self.position()?;
+            let start = self.position()?;
+            let end = self.position()?;
+            positions.push((offset, Span::new(pos, start, end)));
+        }
+        Ok((instructions, positions))
+    }
+
+    fn position(&mut self) -> Result<Position, MkcError> {
+        let line = self.u32()?;
+        let col = self.u32()?;
+        Ok(Position::new(line, col))
+    }
+
+    fn constant(&mut self) -> Result<Object, MkcError> {
+        let offset = self.offset;
+        match self.u8()? {
+            TAG_INTEGER => Ok(Object::Integer(self.u64()? as i64)),
+            TAG_BOOLEAN => Ok(Object::Boolean(self.u8()? != 0)),
+            TAG_STRING => Ok(Object::String(self.string()?.into())),
+            TAG_NULL => Ok(Object::Null),
+            TAG_FUNCTION => {
+                let name = match self.u8()? {
+                    0 => None,
+                    _ => Some(self.string()?),
+                };
+                let num_params = self.u32()?;
+                let num_locals = self.u32()?;
+                if num_params > num_locals || num_locals > MAX_LOCALS {
+                    return Err(MkcError::InvalidLocalCount {
+                        num_params,
+                        num_locals,
+                        offset,
+                    });
+                }
+                let (instructions, positions) = self.code()?;
+                Ok(Object::CompiledFunction(Shared::new(
+                    CompiledFunctionObject {
+                        name,
+                        num_params,
+                        num_locals,
+                        instructions: instructions.into(),
+                        positions: positions.into(),
+                    },
+                )))
+            }
+            tag => Err(MkcError::UnknownConstantTag { tag, offset }),
+        }
+    }
+}
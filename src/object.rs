@@ -1,16 +1,245 @@
-use crate::position::Position;
+use crate::position::Span;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter, Result as FmtResult};
-use std::rc::Rc;
+
+/// Reference-counted handle used throughout object graphs: `Rc` by
+/// default, `Arc` with the `sync` feature so a `Vm` and its values can move
+/// to another thread.
+#[cfg(not(feature = "sync"))]
+pub use std::rc::Rc as Shared;
+#[cfg(feature = "sync")]
+pub use std::sync::Arc as Shared;
 
 /// Shared reference type for runtime objects.
-pub type ObjectRef = Rc<Object>;
+///
+/// Plain reference counting is enough for now: objects are immutable once
+/// built and closures capture values rather than slots (recursion goes
+/// through `CurrentClosure`, not a captured self-reference), so no object
+/// graph can contain a cycle. Code outside this module should clone and
+/// compare handles through `ObjectRef` rather than `Shared` so a collected heap
+/// can replace it once mutation makes cycles possible.
+pub type ObjectRef = Shared<Object>;
 
 /// Hashable Monkey runtime key types.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum HashKey {
     Integer(i64),
     Boolean(bool),
-    String(String),
+    String(Shared<str>),
+}
+
+/// Insertion-ordered hash map backing `Object::Hash`.
+///
+/// Lookups go through a `HashKey` index while iteration follows the order in
+/// which keys were first inserted. Re-inserting an existing key replaces its
+/// value in place (last write wins).
+#[derive(Debug, Clone, Default)]
+pub struct HashObject {
+    index: HashMap<HashKey, usize>,
+    pairs: Vec<(ObjectRef, ObjectRef)>,
+}
+
+impl HashObject {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a hash from pairs in source order, returning the first
+    /// unhashable key on failure.
+    pub fn from_pairs(
+        pairs: impl IntoIterator<Item = (ObjectRef, ObjectRef)>,
+    ) -> Result<Self, ObjectRef> {
+        let mut hash = Self::new();
+        for (key, value) in pairs {
+            hash.insert(key, value)?;
+        }
+        Ok(hash)
+    }
+
+    /// Insert or replace a pair, returning the key back if it is unhashable.
+    pub fn insert(&mut self, key: ObjectRef, value: ObjectRef) -> Result<(), ObjectRef> {
+        let Some(hash_key) = key.hash_key() else {
+            return Err(key);
+        };
+        match self.index.get(&hash_key) {
+            Some(&slot) => self.pairs[slot].1 = value,
+            None => {
+                self.index.insert(hash_key, self.pairs.len());
+                self.pairs.push((key, value));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: &HashKey) -> Option<&ObjectRef> {
+        self.index.get(key).map(|&slot| &self.pairs[slot].1)
+    }
+
+    /// Insertion-order slot of `key`, for callers caching lookups.
+    pub(crate) fn slot_of(&self, key: &HashKey) -> Option<usize> {
+        self.index.get(key).copied()
+    }
+
+    pub(crate) fn pair_at(&self, slot: usize) -> Option<&(ObjectRef, ObjectRef)> {
+        self.pairs.get(slot)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&ObjectRef, &ObjectRef)> {
+        self.pairs.iter().map(|(k, v)| (k, v))
+    }
+}
+
+impl PartialEq for HashObject {
+    fn eq(&self, other: &Self) -> bool {
+        self.pairs == other.pairs
+    }
+}
+
+impl Eq for HashObject {}
+
+/// Deduplicating store for runtime strings.
+///
+/// Interning the same text twice returns clones of one `Shared<str>`, so equal
+/// constants and short concatenation results share a single allocation.
+/// Texts longer than `MAX_INTERNED_LEN` are rarely repeated and costly to
+/// hash, so they get an allocation of their own. Entries nothing else holds
+/// any more are dropped whenever the set has doubled since the last sweep.
+#[derive(Debug, Clone)]
+pub struct StringInterner {
+    strings: HashSet<Shared<str>>,
+    /// Size at which the next sweep runs.
+    sweep_at: usize,
+}
+
+/// Longest text, in bytes, that `StringInterner` deduplicates.
+pub const MAX_INTERNED_LEN: usize = 64;
+
+/// Entries a `StringInterner` may hold before its first sweep.
+const MIN_SWEEP_AT: usize = 1024;
+
+impl Default for StringInterner {
+    fn default() -> Self {
+        Self {
+            strings: HashSet::new(),
+            sweep_at: MIN_SWEEP_AT,
+        }
+    }
+}
+
+impl StringInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, text: &str) -> Shared<str> {
+        if text.len() > MAX_INTERNED_LEN {
+            return Shared::from(text);
+        }
+        if let Some(existing) = self.strings.get(text) {
+            return Shared::clone(existing);
+        }
+        self.make_room();
+        let shared: Shared<str> = Shared::from(text);
+        self.strings.insert(Shared::clone(&shared));
+        shared
+    }
+
+    /// Like `intern`, but adopts an existing allocation when the text is new.
+    pub fn intern_shared(&mut self, text: Shared<str>) -> Shared<str> {
+        if text.len() > MAX_INTERNED_LEN {
+            return text;
+        }
+        if let Some(existing) = self.strings.get(&text) {
+            return Shared::clone(existing);
+        }
+        self.make_room();
+        self.strings.insert(Shared::clone(&text));
+        text
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Drop the entries only the interner holds once the set has reached
+    /// `sweep_at`, keeping it proportional to the strings still in use.
+    fn make_room(&mut self) {
+        if self.strings.len() < self.sweep_at {
+            return;
+        }
+        self.strings.retain(|text| Shared::strong_count(text) > 1);
+        self.sweep_at = (self.strings.len() * 2).max(MIN_SWEEP_AT);
+    }
+}
+
+/// Total of `Object::heap_size` over the values reachable from the ones
+/// added, counting each shared value once; an approximation of the heap a
+/// VM still uses.
+#[derive(Debug, Default)]
+pub(crate) struct HeapMeasure {
+    /// Addresses of the objects and string buffers already counted.
+    seen: HashSet<usize>,
+    total: usize,
+}
+
+impl HeapMeasure {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count `value` and everything it references that is not counted yet.
+    pub(crate) fn add(&mut self, value: &ObjectRef) {
+        let mut pending = Vec::new();
+        self.push_unseen(value, &mut pending);
+        while let Some(value) = pending.pop() {
+            match value.as_ref() {
+                Object::String(text) if self.seen.insert(text.as_ptr() as usize) => {
+                    self.total += text.len();
+                }
+                Object::Array(values) => {
+                    self.total += value.heap_size();
+                    for item in values {
+                        self.push_unseen(item, &mut pending);
+                    }
+                }
+                Object::Hash(hash) => {
+                    self.total += value.heap_size();
+                    for (key, item) in hash.iter() {
+                        self.push_unseen(key, &mut pending);
+                        self.push_unseen(item, &mut pending);
+                    }
+                }
+                Object::Closure(closure) => {
+                    for item in &closure.free {
+                        self.push_unseen(item, &mut pending);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub(crate) fn total(&self) -> usize {
+        self.total
+    }
+
+    fn push_unseen(&mut self, value: &ObjectRef, pending: &mut Vec<ObjectRef>) {
+        if self.seen.insert(Shared::as_ptr(value) as usize) {
+            pending.push(Shared::clone(value));
+        }
+    }
 }
 
 /// Placeholder compiled function metadata.
@@ -19,14 +248,14 @@ pub struct CompiledFunctionObject {
     pub name: Option<String>,
     pub num_params: usize,
     pub num_locals: usize,
-    pub instructions: Vec<u8>,
-    pub positions: Vec<(usize, Position)>,
+    pub instructions: Shared<[u8]>,
+    pub positions: Shared<[(usize, Span)]>,
 }
 
 /// Placeholder closure object metadata.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ClosureObject {
-    pub function: Rc<CompiledFunctionObject>,
+    pub function: Shared<CompiledFunctionObject>,
     pub free: Vec<ObjectRef>,
 }
 
@@ -41,12 +270,12 @@ pub struct BuiltinObject {
 pub enum Object {
     Integer(i64),
     Boolean(bool),
-    String(String),
+    String(Shared<str>),
     Null,
     Array(Vec<ObjectRef>),
-    Hash(Vec<(ObjectRef, ObjectRef)>),
-    CompiledFunction(Rc<CompiledFunctionObject>),
-    Closure(Rc<ClosureObject>),
+    Hash(HashObject),
+    CompiledFunction(Shared<CompiledFunctionObject>),
+    Closure(Shared<ClosureObject>),
     Builtin(BuiltinObject),
 }
 
@@ -71,7 +300,7 @@ impl Eq for Object {}
 
 impl Object {
     pub fn rc(self) -> ObjectRef {
-        Rc::new(self)
+        Shared::new(self)
     }
 
     pub fn type_name(&self) -> &'static str {
@@ -88,6 +317,19 @@ impl Object {
         }
     }
 
+    /// Approximate heap bytes owned directly by this value.
+    ///
+    /// Nested values are not included; they are accounted for when created.
+    pub fn heap_size(&self) -> usize {
+        let slot = std::mem::size_of::<ObjectRef>();
+        match self {
+            Object::String(v) => v.len(),
+            Object::Array(values) => values.len() * slot,
+            Object::Hash(hash) => hash.len() * (2 * slot + std::mem::size_of::<HashKey>()),
+            _ => 0,
+        }
+    }
+
     pub fn is_truthy(&self) -> bool {
         !matches!(self, Object::Boolean(false) | Object::Null)
     }
@@ -96,17 +338,47 @@ impl Object {
         match self {
             Object::Integer(v) => Some(HashKey::Integer(*v)),
             Object::Boolean(v) => Some(HashKey::Boolean(*v)),
-            Object::String(v) => Some(HashKey::String(v.clone())),
+            Object::String(v) => Some(HashKey::String(Shared::clone(v))),
             _ => None,
         }
     }
 
+    /// Structural equality used by the `eq` builtin.
+    ///
+    /// Scalars compare by value and `null` equals only `null`. Arrays compare
+    /// element-wise and hashes compare their effective key/value sets
+    /// regardless of insertion order. Functions and closures compare by
+    /// identity, builtins by name. Values of different types are never equal.
+    pub fn deep_eq(&self, other: &Object) -> bool {
+        match (self, other) {
+            (Object::Integer(a), Object::Integer(b)) => a == b,
+            (Object::Boolean(a), Object::Boolean(b)) => a == b,
+            (Object::String(a), Object::String(b)) => a == b,
+            (Object::Null, Object::Null) => true,
+            (Object::Array(a), Object::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| x.deep_eq(y))
+            }
+            (Object::Hash(a), Object::Hash(b)) => {
+                a.len() == b.len()
+                    && a.iter().all(|(key, value)| {
+                        key.hash_key()
+                            .and_then(|k| b.get(&k))
+                            .is_some_and(|v| value.deep_eq(v))
+                    })
+            }
+            (Object::CompiledFunction(a), Object::CompiledFunction(b)) => Shared::ptr_eq(a, b),
+            (Object::Closure(a), Object::Closure(b)) => Shared::ptr_eq(a, b),
+            (Object::Builtin(a), Object::Builtin(b)) => a == b,
+            _ => false,
+        }
+    }
+
     pub fn inspect(&self) -> String {
         // TODO(step-7): runtime error wiring (e.g., UNHASHABLE/type checks) will use this model.
         match self {
             Object::Integer(v) => v.to_string(),
             Object::Boolean(v) => v.to_string(),
-            Object::String(v) => v.clone(),
+            Object::String(v) => v.to_string(),
             Object::Null => "null".to_string(),
             Object::Array(values) => {
                 let rendered = values
@@ -116,8 +388,8 @@ impl Object {
                     .join(", ");
                 format!("[{rendered}]")
             }
-            Object::Hash(pairs) => {
-                let rendered = pairs
+            Object::Hash(hash) => {
+                let rendered = hash
                     .iter()
                     .map(|(k, v)| format!("{}: {}", k.inspect(), v.inspect()))
                     .collect::<Vec<_>>()
@@ -1,20 +1,135 @@
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
 use crate::position::Position;
+use crate::source::snippet;
+use crate::token::{TokenKind, BINARY_OPERATORS};
+
+/// Parse error categories, each with a stable code for `monkey explain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParseErrorKind {
+    UnexpectedToken,
+    NoPrefixParse,
+    InvalidIntegerLiteral,
+    ExpectedParameterName,
+    FloatLiteral,
+    MalformedNumber,
+    TooDeeplyNested,
+}
+
+impl ParseErrorKind {
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            ParseErrorKind::UnexpectedToken => "P001",
+            ParseErrorKind::NoPrefixParse => "P002",
+            ParseErrorKind::InvalidIntegerLiteral => "P003",
+            ParseErrorKind::ExpectedParameterName => "P004",
+            ParseErrorKind::FloatLiteral => "P005",
+            ParseErrorKind::MalformedNumber => "P006",
+            ParseErrorKind::TooDeeplyNested => "P007",
+        }
+    }
+}
+
+/// Secondary information about a `ParseError`, such as where the parser
+/// resumed after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseNote {
+    pub pos: Position,
+    pub message: String,
+}
+
+impl Display for ParseNote {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}: note: {}", self.pos, self.message)
+    }
+}
 
 /// Parser error with source position.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ParseError {
+    pub kind: ParseErrorKind,
     pub message: String,
     pub pos: Position,
+    /// Every token the parser would have accepted where the error is, when
+    /// known.
+    pub expected: Vec<TokenKind>,
+    /// Shown by `render_with_source` only, like `expected`, so the
+    /// one-line `Display` stays what the Java implementation prints.
+    pub notes: Vec<ParseNote>,
 }
 
 impl ParseError {
-    pub fn new(pos: Position, message: impl Into<String>) -> Self {
+    pub fn new(kind: ParseErrorKind, pos: Position, message: impl Into<String>) -> Self {
         Self {
+            kind,
             message: message.into(),
             pos,
+            expected: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_expected(mut self, expected: impl IntoIterator<Item = TokenKind>) -> Self {
+        self.expected = expected.into_iter().collect();
+        self
+    }
+
+    pub fn with_note(mut self, pos: Position, message: impl Into<String>) -> Self {
+        self.notes.push(ParseNote {
+            pos,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn error_code(&self) -> &'static str {
+        self.kind.error_code()
+    }
+
+    /// The error tagged with its code and followed by the offending line of
+    /// `source`, underlined, the tokens that were expected when there was a
+    /// choice, then each note with its own line.
+    pub fn render_with_source(&self, source: &str) -> String {
+        let mut out = format!("{self} [{}]", self.error_code());
+        if let Some(snippet) = snippet(source, self.pos) {
+            out.push_str(&format!("\n{snippet}"));
+        }
+        if self.expected.len() > 1 {
+            out.push_str(&format!(
+                "\nhelp: expected {}",
+                describe_expected(&self.expected)
+            ));
+        }
+        for note in &self.notes {
+            out.push_str(&format!("\n{note}"));
+            if let Some(snippet) = snippet(source, note.pos) {
+                out.push_str(&format!("\n{snippet}"));
+            }
+        }
+        out
+    }
+}
+
+/// `kinds` as a list for a sentence, e.g. "one of `)`, an operator, `(`
+/// or `[`", with the binary operators, when all are there, collapsed.
+fn describe_expected(kinds: &[TokenKind]) -> String {
+    let operators = BINARY_OPERATORS.iter().all(|kind| kinds.contains(kind));
+    let mut items = Vec::new();
+    for kind in kinds {
+        let item = if operators && BINARY_OPERATORS.contains(kind) {
+            "an operator"
+        } else {
+            kind.description()
+        };
+        if !items.contains(&item) {
+            items.push(item);
+        }
+    }
+    match items.split_last() {
+        Some((last, rest)) if !rest.is_empty() => {
+            format!("one of {} or {last}", rest.join(", "))
         }
+        _ => items.join(""),
     }
 }
 
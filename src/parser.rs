@@ -1,7 +1,10 @@
+use std::collections::HashMap;
+
 use crate::ast::{BlockStatement, Expression, Identifier, Program, Statement};
 use crate::lexer::Lexer;
-use crate::parse_error::ParseError;
-use crate::token::{Token, TokenKind};
+use crate::parse_error::{ParseError, ParseErrorKind};
+use crate::position::{Position, Span};
+use crate::token::{lookup_ident, similar_keyword, Token, TokenKind, BINARY_OPERATORS};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum Precedence {
@@ -31,13 +34,45 @@ fn token_precedence(kind: &TokenKind) -> Precedence {
     }
 }
 
+/// Tokens that can start an expression.
+const EXPRESSION_START: [TokenKind; 12] = [
+    TokenKind::Ident,
+    TokenKind::Int,
+    TokenKind::String,
+    TokenKind::True,
+    TokenKind::False,
+    TokenKind::Bang,
+    TokenKind::Minus,
+    TokenKind::LParen,
+    TokenKind::LBracket,
+    TokenKind::LBrace,
+    TokenKind::If,
+    TokenKind::Function,
+];
+
+/// How deeply `Parser` lets expressions nest by default, e.g. 128
+/// parentheses around a value, or 128 `if`s one inside the other. Nested
+/// `if`s take the most stack per level; a debug build parses 128 of them
+/// in a 2 MiB thread.
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
 /// Pratt parser for Monkey source.
 #[derive(Debug)]
 pub struct Parser {
     lexer: Lexer,
     cur_token: Token,
     peek_token: Token,
+    spans: HashMap<Position, Span>,
+    statement_spans: HashMap<Position, Span>,
     errors: Vec<ParseError>,
+    /// Expressions being parsed, one inside the other.
+    depth: usize,
+    max_depth: usize,
+    /// An expression was nested more than `max_depth` deep.
+    too_deep: bool,
+    /// The last statement that started with a likely misspelt keyword,
+    /// and the keyword.
+    keyword_typo: Option<(Position, &'static str)>,
 }
 
 impl Parser {
@@ -48,10 +83,29 @@ impl Parser {
             lexer,
             cur_token,
             peek_token,
+            spans: HashMap::new(),
+            statement_spans: HashMap::new(),
             errors: Vec::new(),
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            too_deep: false,
+            keyword_typo: None,
         }
     }
 
+    /// Report an expression nested more than `max_depth` deep as a parse
+    /// error, rather than recursing until the stack overflows.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// The lexer tokens are read from, e.g. to check `Lexer::read_error`
+    /// after parsing a stream.
+    pub fn lexer(&self) -> &Lexer {
+        &self.lexer
+    }
+
     pub fn parse_program(&mut self) -> Program {
         // TODO(step-6): evaluator/compiler will consume the parsed AST.
         let mut statements = Vec::new();
@@ -62,6 +116,8 @@ impl Parser {
                 continue;
             }
 
+            let start = self.cur_token.pos;
+            let errors = self.errors.len();
             match self.parse_statement() {
                 Some(stmt) => {
                     statements.push(stmt);
@@ -71,13 +127,16 @@ impl Parser {
                     if self.cur_token_is(TokenKind::RBrace) {
                         self.next_token();
                     } else {
-                        self.synchronize_statement();
+                        self.recover(start, errors);
                     }
                 }
             }
         }
 
-        Program::new(statements)
+        let mut program = Program::new(statements);
+        program.spans = std::mem::take(&mut self.spans);
+        program.statement_spans = std::mem::take(&mut self.statement_spans);
+        program
     }
 
     pub fn errors(&self) -> &[ParseError] {
@@ -85,8 +144,15 @@ impl Parser {
     }
 
     fn next_token(&mut self) {
-        self.cur_token = self.peek_token.clone();
-        self.peek_token = self.lexer.next_token();
+        self.cur_token = std::mem::replace(&mut self.peek_token, self.lexer.next_token());
+    }
+
+    /// Record that `expression`, which began at `start`, ends with the
+    /// current token.
+    fn record_span(&mut self, expression: &Expression, start: Position) {
+        let pos = expression.pos();
+        self.spans
+            .insert(pos, Span::new(pos, start, self.cur_token.end));
     }
 
     fn cur_token_is(&self, kind: TokenKind) -> bool {
@@ -106,56 +172,187 @@ impl Parser {
     }
 
     fn expect_peek(&mut self, expected: TokenKind) -> bool {
+        self.expect_peek_or(expected, &[])
+    }
+
+    /// Like `expect_peek`, where `alternatives` were also acceptable, e.g.
+    /// because the parser would have taken them before getting here.
+    fn expect_peek_or(&mut self, expected: TokenKind, alternatives: &[TokenKind]) -> bool {
         if self.peek_token.kind == expected {
             self.next_token();
             true
         } else {
-            self.peek_error(expected, self.peek_token.kind.clone(), self.peek_token.pos);
+            self.peek_error(expected, alternatives);
             false
         }
     }
 
-    fn peek_error(
+    /// `expect_peek_or` after an expression parsed at `Precedence::Lowest`,
+    /// which a binary operator, `(` for a call or `[` for an index would
+    /// have continued.
+    fn expect_peek_after_expression(
         &mut self,
         expected: TokenKind,
-        actual: TokenKind,
-        pos: crate::position::Position,
-    ) {
-        self.errors.push(ParseError::new(
-            pos,
-            format!("expected next token to be {expected}, got {actual}"),
-        ));
-    }
-
-    fn no_prefix_parse_fn_error(&mut self, token_kind: TokenKind, pos: crate::position::Position) {
-        self.errors.push(ParseError::new(
-            pos,
-            format!("no prefix parse function for {token_kind}"),
-        ));
-    }
-
-    fn synchronize_statement(&mut self) {
-        while !matches!(
-            self.cur_token.kind,
-            TokenKind::Semicolon | TokenKind::RBrace | TokenKind::Eof
-        ) {
+        alternatives: &[TokenKind],
+    ) -> bool {
+        if self.peek_token.kind == expected {
+            return self.expect_peek(expected);
+        }
+        let mut alternatives = alternatives.to_vec();
+        alternatives.extend(BINARY_OPERATORS);
+        alternatives.extend([TokenKind::LParen, TokenKind::LBracket]);
+        self.expect_peek_or(expected, &alternatives)
+    }
+
+    fn peek_error(&mut self, expected: TokenKind, alternatives: &[TokenKind]) {
+        let message = format!(
+            "expected next token to be {expected}, got {}",
+            self.peek_token.kind
+        );
+        let expected = std::iter::once(expected).chain(alternatives.iter().cloned());
+        self.errors.push(
+            ParseError::new(
+                ParseErrorKind::UnexpectedToken,
+                self.peek_token.pos,
+                message,
+            )
+            .with_expected(expected),
+        );
+    }
+
+    fn no_prefix_parse_fn_error(&mut self, token_kind: TokenKind, pos: Position) {
+        self.errors.push(
+            ParseError::new(
+                ParseErrorKind::NoPrefixParse,
+                pos,
+                format!("no prefix parse function for {token_kind}"),
+            )
+            .with_expected(EXPRESSION_START),
+        );
+    }
+
+    /// Skip the rest of the statement starting at `start` that failed to
+    /// parse, noting where parsing resumes on the last of the errors it
+    /// reported after the first `errors`.
+    fn recover(&mut self, start: Position, errors: usize) {
+        let skipped = self.synchronize_statement(start);
+        if self.errors.len() <= errors {
+            return;
+        }
+        let Some(mut error) = self.errors.pop() else {
+            return;
+        };
+        if let Some((pos, keyword)) = self.keyword_typo.filter(|(pos, _)| pos.line == start.line) {
+            error = error.with_note(pos, format!("did you mean `{keyword}`?"));
+        }
+        if let Some((pos, message)) = skipped {
+            error = error.with_note(pos, message);
+        }
+        self.errors.push(error);
+    }
+
+    /// Skip past the `;` ending the statement starting at `start`, or up to
+    /// the `}` closing the enclosing block or a keyword starting the next
+    /// statement, so a missing `;` costs at most one statement. Braces
+    /// opened on the way are skipped with their contents. Returns where it
+    /// stopped, described for a note, unless nothing was skipped.
+    fn synchronize_statement(&mut self, start: Position) -> Option<(Position, String)> {
+        let mut depth = 0usize;
+        let mut skipped = false;
+        let stop = loop {
+            let pos = self.cur_token.pos;
+            match self.cur_token.kind {
+                TokenKind::Eof => break (pos, "skipped to the end of the input".to_string()),
+                TokenKind::Semicolon if depth == 0 => {
+                    self.next_token();
+                    return Some((pos, "skipped to the end of the statement".to_string()));
+                }
+                TokenKind::RBrace if depth == 0 => {
+                    break (pos, "skipped to the `}` closing the block".to_string())
+                }
+                TokenKind::Let
+                | TokenKind::Return
+                | TokenKind::While
+                | TokenKind::Break
+                | TokenKind::Continue
+                    if depth == 0 && pos != start =>
+                {
+                    let message = format!(
+                        "skipped to `{}`, which starts the next statement",
+                        self.cur_token.literal
+                    );
+                    break (pos, message);
+                }
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace => depth -= 1,
+                _ => {}
+            }
             self.next_token();
-        }
+            skipped = true;
+        };
+        skipped.then_some(stop)
+    }
 
-        if self.cur_token_is(TokenKind::Semicolon) {
-            self.next_token();
+    /// The keyword the identifier starting a statement is likely a typo
+    /// of, judging by its spelling and the token after it, e.g. `retrun x`
+    /// or `iff (x)`.
+    fn keyword_typo(&self) -> Option<&'static str> {
+        if !self.cur_token_is(TokenKind::Ident) {
+            return None;
         }
+        let keyword = similar_keyword(&self.cur_token.literal)?;
+        let next = &self.peek_token.kind;
+        let plausible = match lookup_ident(keyword) {
+            TokenKind::Let => *next == TokenKind::Ident,
+            TokenKind::Return => matches!(
+                next,
+                TokenKind::Ident
+                    | TokenKind::Int
+                    | TokenKind::String
+                    | TokenKind::True
+                    | TokenKind::False
+                    | TokenKind::Bang
+                    | TokenKind::LBrace
+                    | TokenKind::If
+                    | TokenKind::Function
+            ),
+            TokenKind::If | TokenKind::While | TokenKind::Function => *next == TokenKind::LParen,
+            TokenKind::Else => matches!(next, TokenKind::LBrace | TokenKind::If),
+            TokenKind::Break | TokenKind::Continue => {
+                matches!(next, TokenKind::Semicolon | TokenKind::RBrace)
+            }
+            _ => false,
+        };
+        plausible.then_some(keyword)
     }
 
     fn parse_statement(&mut self) -> Option<Statement> {
-        match self.cur_token.kind {
+        let start = self.cur_token.pos;
+        // Errors in statements starting on the same line as a likely typo
+        // of a keyword suggest the keyword.
+        match self.keyword_typo() {
+            Some(keyword) => self.keyword_typo = Some((start, keyword)),
+            None if self
+                .keyword_typo
+                .is_some_and(|(pos, _)| pos.line != start.line) =>
+            {
+                self.keyword_typo = None
+            }
+            None => {}
+        }
+        let statement = match self.cur_token.kind {
             TokenKind::Let => self.parse_let_statement(),
             TokenKind::Return => self.parse_return_statement(),
             TokenKind::While => self.parse_while_statement(),
             TokenKind::Break => Some(self.parse_break_statement()),
             TokenKind::Continue => Some(self.parse_continue_statement()),
             _ => self.parse_expression_statement(),
-        }
+        }?;
+        // Statements end with their `;` when they have one.
+        let pos = statement.pos();
+        self.statement_spans
+            .insert(pos, Span::new(pos, start, self.cur_token.end));
+        Some(statement)
     }
 
     fn parse_let_statement(&mut self) -> Option<Statement> {
@@ -200,14 +397,14 @@ impl Parser {
         self.next_token();
         let condition = self.parse_expression(Precedence::Lowest)?;
 
-        if !self.expect_peek(TokenKind::RParen) {
+        if !self.expect_peek_after_expression(TokenKind::RParen, &[]) {
             return None;
         }
         if !self.expect_peek(TokenKind::LBrace) {
             return None;
         }
 
-        let body = self.parse_block_statement(self.cur_token.pos);
+        let body = self.parse_block_statement(self.cur_token.pos)?;
         Some(Statement::While {
             condition,
             body,
@@ -242,10 +439,9 @@ impl Parser {
         Some(Statement::Expression { expression, pos })
     }
 
-    fn parse_block_statement(
-        &mut self,
-        open_brace_pos: crate::position::Position,
-    ) -> BlockStatement {
+    /// `None` only when an expression in the block was nested too deeply,
+    /// which ends parsing.
+    fn parse_block_statement(&mut self, open_brace_pos: Position) -> Option<BlockStatement> {
         let mut statements = Vec::new();
         self.next_token();
 
@@ -255,25 +451,87 @@ impl Parser {
                 continue;
             }
 
+            let start = self.cur_token.pos;
+            let errors = self.errors.len();
             match self.parse_statement() {
                 Some(stmt) => {
                     statements.push(stmt);
                     self.next_token();
                 }
-                None => self.synchronize_statement(),
+                None => self.recover(start, errors),
             }
         }
 
-        BlockStatement::new(statements, open_brace_pos)
+        if self.too_deep {
+            return None;
+        }
+        Some(BlockStatement::new(statements, open_brace_pos))
     }
 
     fn parse_expression(&mut self, precedence: Precedence) -> Option<Expression> {
+        if self.depth >= self.max_depth {
+            self.too_deeply_nested();
+            return None;
+        }
+        self.depth += 1;
+        let expression = self.parse_nested_expression(precedence);
+        self.depth -= 1;
+        expression
+    }
+
+    /// Report the expression at the current token as nested too deeply
+    /// and skip the rest of the input: closing brackets past this point
+    /// would only produce more errors.
+    fn too_deeply_nested(&mut self) {
+        let pos = self.cur_token.pos;
+        while !self.cur_token_is(TokenKind::Eof) {
+            self.next_token();
+        }
+        let error = ParseError::new(
+            ParseErrorKind::TooDeeplyNested,
+            pos,
+            format!(
+                "expression too deeply nested: more than {} levels",
+                self.max_depth
+            ),
+        );
+        self.errors
+            .push(error.with_note(self.cur_token.pos, "skipped to the end of the input"));
+        self.too_deep = true;
+    }
+
+    fn parse_nested_expression(&mut self, precedence: Precedence) -> Option<Expression> {
+        let start = self.cur_token.pos;
         let mut left = match self.cur_token.kind {
             TokenKind::Ident => Some(Expression::Identifier {
                 value: self.cur_token.literal.clone(),
                 pos: self.cur_token.pos,
             }),
             TokenKind::Int => self.parse_integer_literal(),
+            TokenKind::Float => {
+                self.errors.push(ParseError::new(
+                    ParseErrorKind::FloatLiteral,
+                    self.cur_token.pos,
+                    format!(
+                        "float literals are not supported: {}",
+                        self.cur_token.literal
+                    ),
+                ));
+                None
+            }
+            TokenKind::Illegal
+                if self
+                    .cur_token
+                    .literal
+                    .starts_with(|ch: char| ch.is_ascii_digit()) =>
+            {
+                self.errors.push(ParseError::new(
+                    ParseErrorKind::MalformedNumber,
+                    self.cur_token.pos,
+                    format!("malformed number literal {}", self.cur_token.literal),
+                ));
+                None
+            }
             TokenKind::True | TokenKind::False => Some(Expression::BooleanLiteral {
                 value: self.cur_token_is(TokenKind::True),
                 pos: self.cur_token.pos,
@@ -293,6 +551,7 @@ impl Parser {
                 None
             }
         }?;
+        self.record_span(&left, start);
 
         while !self.peek_token_is(TokenKind::Semicolon) && precedence < self.peek_precedence() {
             match self.peek_token.kind {
@@ -321,6 +580,7 @@ impl Parser {
                 }
                 _ => return Some(left),
             }
+            self.record_span(&left, start);
         }
 
         Some(left)
@@ -336,6 +596,7 @@ impl Parser {
             }),
             Err(_) => {
                 self.errors.push(ParseError::new(
+                    ParseErrorKind::InvalidIntegerLiteral,
                     self.cur_token.pos,
                     format!("invalid integer literal {raw}"),
                 ));
@@ -359,7 +620,7 @@ impl Parser {
     fn parse_grouped_expression(&mut self) -> Option<Expression> {
         self.next_token();
         let exp = self.parse_expression(Precedence::Lowest)?;
-        if !self.expect_peek(TokenKind::RParen) {
+        if !self.expect_peek_after_expression(TokenKind::RParen, &[]) {
             return None;
         }
         Some(exp)
@@ -374,19 +635,19 @@ impl Parser {
         self.next_token();
         let condition = self.parse_expression(Precedence::Lowest)?;
 
-        if !self.expect_peek(TokenKind::RParen) {
+        if !self.expect_peek_after_expression(TokenKind::RParen, &[]) {
             return None;
         }
         if !self.expect_peek(TokenKind::LBrace) {
             return None;
         }
 
-        let consequence = self.parse_block_statement(self.cur_token.pos);
+        let consequence = self.parse_block_statement(self.cur_token.pos)?;
         let alternative = if self.peek_token_is(TokenKind::Else) {
             self.next_token(); // else
             if self.peek_token_is(TokenKind::LBrace) {
                 self.next_token();
-                Some(self.parse_block_statement(self.cur_token.pos))
+                Some(self.parse_block_statement(self.cur_token.pos)?)
             } else if self.peek_token_is(TokenKind::If) {
                 self.next_token();
                 let nested_if = self.parse_if_expression()?;
@@ -399,14 +660,7 @@ impl Parser {
                     nested_pos,
                 ))
             } else {
-                self.errors.push(ParseError::new(
-                    self.peek_token.pos,
-                    format!(
-                        "expected next token to be {}, got {}",
-                        TokenKind::LBrace,
-                        self.peek_token.kind
-                    ),
-                ));
+                self.peek_error(TokenKind::LBrace, &[TokenKind::If]);
                 return None;
             }
         } else {
@@ -431,7 +685,7 @@ impl Parser {
         if !self.expect_peek(TokenKind::LBrace) {
             return None;
         }
-        let body = self.parse_block_statement(self.cur_token.pos);
+        let body = self.parse_block_statement(self.cur_token.pos)?;
         Some(Expression::FunctionLiteral {
             parameters,
             body,
@@ -449,10 +703,14 @@ impl Parser {
 
         self.next_token();
         if !self.cur_token_is(TokenKind::Ident) {
-            self.errors.push(ParseError::new(
-                self.cur_token.pos,
-                "expected identifier in parameter list",
-            ));
+            self.errors.push(
+                ParseError::new(
+                    ParseErrorKind::ExpectedParameterName,
+                    self.cur_token.pos,
+                    "expected identifier in parameter list",
+                )
+                .with_expected([TokenKind::Ident, TokenKind::RParen]),
+            );
             return None;
         }
         params.push(Identifier::new(
@@ -462,12 +720,20 @@ impl Parser {
 
         while self.peek_token_is(TokenKind::Comma) {
             self.next_token();
+            // A trailing comma before `)` is allowed.
+            if self.peek_token_is(TokenKind::RParen) {
+                break;
+            }
             self.next_token();
             if !self.cur_token_is(TokenKind::Ident) {
-                self.errors.push(ParseError::new(
-                    self.cur_token.pos,
-                    "expected identifier in parameter list",
-                ));
+                self.errors.push(
+                    ParseError::new(
+                        ParseErrorKind::ExpectedParameterName,
+                        self.cur_token.pos,
+                        "expected identifier in parameter list",
+                    )
+                    .with_expected([TokenKind::Ident, TokenKind::RParen]),
+                );
                 return None;
             }
             params.push(Identifier::new(
@@ -476,7 +742,7 @@ impl Parser {
             ));
         }
 
-        if !self.expect_peek(TokenKind::RParen) {
+        if !self.expect_peek_or(TokenKind::RParen, &[TokenKind::Comma]) {
             return None;
         }
         Some(params)
@@ -501,7 +767,7 @@ impl Parser {
             self.next_token();
             let key = self.parse_expression(Precedence::Lowest)?;
 
-            if !self.expect_peek(TokenKind::Colon) {
+            if !self.expect_peek_after_expression(TokenKind::Colon, &[]) {
                 return None;
             }
 
@@ -513,9 +779,12 @@ impl Parser {
                 break;
             }
             self.next_token();
+            if self.peek_token_is(TokenKind::RBrace) {
+                break;
+            }
         }
 
-        if !self.expect_peek(TokenKind::RBrace) {
+        if !self.expect_peek_after_expression(TokenKind::RBrace, &[TokenKind::Comma]) {
             return None;
         }
 
@@ -535,11 +804,14 @@ impl Parser {
 
         while self.peek_token_is(TokenKind::Comma) {
             self.next_token();
+            if self.peek_token.kind == end {
+                break;
+            }
             self.next_token();
             list.push(self.parse_expression(Precedence::Lowest)?);
         }
 
-        if !self.expect_peek(end) {
+        if !self.expect_peek_after_expression(end, &[TokenKind::Comma]) {
             return None;
         }
         Some(list)
@@ -573,7 +845,7 @@ impl Parser {
         let pos = self.cur_token.pos;
         self.next_token();
         let index = self.parse_expression(Precedence::Lowest)?;
-        if !self.expect_peek(TokenKind::RBracket) {
+        if !self.expect_peek_after_expression(TokenKind::RBracket, &[]) {
             return None;
         }
         Some(Expression::Index {
@@ -0,0 +1,303 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::bytecode::{lookup_definition, make, read_operands, Chunk, Opcode};
+use crate::object::{CompiledFunctionObject, Object, Shared};
+use crate::position::Span;
+
+/// Fuse common instruction sequences into superinstructions.
+///
+/// Rewrites the top-level code and every compiled function in the constant
+/// pool:
+///
+/// - `Constant a; Constant b; Add` becomes `AddConstants a b`
+/// - `GetLocal a; GetLocal b; Add` becomes `AddLocals a b`
+/// - `JumpIfFalse t; Pop` becomes `JumpIfFalsePop t`
+///
+/// Fused instructions are padded with `Nop` bytes to the length of the
+/// sequence they replace, so jump targets and position tables stay valid.
+/// Sequences that contain a jump target after their first instruction are
+/// left alone.
+pub fn fuse_superinstructions(chunk: &mut Chunk) {
+    fuse_code(&mut chunk.instructions);
+    for constant in &mut chunk.constants {
+        if let Object::CompiledFunction(function) = constant.as_ref() {
+            let mut instructions = function.instructions.to_vec();
+            if fuse_code(&mut instructions) {
+                *constant = Object::CompiledFunction(Shared::new(CompiledFunctionObject {
+                    instructions: instructions.into(),
+                    ..function.as_ref().clone()
+                }))
+                .rc();
+            }
+        }
+    }
+}
+
+/// Fuse one instruction stream in place; returns whether anything changed.
+fn fuse_code(code: &mut [u8]) -> bool {
+    let Some(decoded) = decode(code) else {
+        return false;
+    };
+    let targets = jump_targets(&decoded);
+    let is_target = |offset: usize| targets.contains(&offset);
+
+    let mut changed = false;
+    let mut i = 0;
+    while i < decoded.len() {
+        let window = &decoded[i..];
+        match window {
+            [(at, Opcode::Constant, a), (second, Opcode::Constant, b), (add, Opcode::Add, _), ..]
+                if !is_target(*second) && !is_target(*add) =>
+            {
+                let [a_hi, a_lo] = (a[0] as u16).to_be_bytes();
+                let [b_hi, b_lo] = (b[0] as u16).to_be_bytes();
+                write(
+                    code,
+                    *at,
+                    &[Opcode::AddConstants.to_byte(), a_hi, a_lo, b_hi, b_lo],
+                    7,
+                );
+                changed = true;
+                i += 3;
+            }
+            [(at, Opcode::GetLocal, a), (second, Opcode::GetLocal, b), (add, Opcode::Add, _), ..]
+                if !is_target(*second) && !is_target(*add) =>
+            {
+                write(
+                    code,
+                    *at,
+                    &[Opcode::AddLocals.to_byte(), a[0] as u8, b[0] as u8],
+                    5,
+                );
+                changed = true;
+                i += 3;
+            }
+            [(at, Opcode::JumpIfFalse, t), (pop, Opcode::Pop, _), ..] if !is_target(*pop) => {
+                let [t_hi, t_lo] = (t[0] as u16).to_be_bytes();
+                write(
+                    code,
+                    *at,
+                    &[Opcode::JumpIfFalsePop.to_byte(), t_hi, t_lo],
+                    4,
+                );
+                changed = true;
+                i += 2;
+            }
+            _ => i += 1,
+        }
+    }
+    changed
+}
+
+/// Remove redundant sequences from one finished compilation scope.
+///
+/// Used by the compiler when `CompilerOptions::optimize` is set. Jumps
+/// whose target is an unconditional `Jump` are first pointed at the end of
+/// the chain. Then, unlike `fuse_superinstructions`, the code shrinks, so
+/// jump operands and the position table are rewritten to the new offsets:
+///
+/// - `Bang Bang` right after an instruction that already pushes a boolean
+/// - a literal push (`Null`, `True`, `Constant`, ...) followed by `Pop`
+/// - `Jump t` or `JumpIfFalse t` where `t` is the next instruction
+///
+/// Repeats until nothing changes; returns whether the code changed.
+pub fn optimize_scope(code: &mut Vec<u8>, positions: &mut Vec<(usize, Span)>) -> bool {
+    let mut changed = false;
+    while let Some(decoded) = decode(code) {
+        if thread_jumps(code, &decoded) {
+            changed = true;
+            continue;
+        }
+        let removed = redundant_instructions(&decoded, code.len());
+        if !removed.contains(&true) {
+            break;
+        }
+        rebuild(code, positions, &decoded, &removed);
+        changed = true;
+    }
+    changed
+}
+
+/// Retarget every jump that lands on an unconditional `Jump` to where the
+/// chain finally leads. Operands keep their width, so this patches `code`
+/// in place; returns whether any operand changed.
+fn thread_jumps(code: &mut [u8], decoded: &[(usize, Opcode, Vec<usize>)]) -> bool {
+    let jumps_at = decoded
+        .iter()
+        .filter(|(_, op, _)| *op == Opcode::Jump)
+        .map(|(at, _, operands)| (*at, operands[0]))
+        .collect::<HashMap<_, _>>();
+
+    let mut changed = false;
+    for (at, op, operands) in decoded {
+        if !is_jump(*op) {
+            continue;
+        }
+        let mut target = operands[0];
+        // A chain longer than the jump count is a cycle (`L: Jump L`); leave
+        // such jumps where they are.
+        let mut hops = 0;
+        while let Some(&next) = jumps_at.get(&target) {
+            if hops > jumps_at.len() {
+                target = operands[0];
+                break;
+            }
+            target = next;
+            hops += 1;
+        }
+        if target != operands[0] {
+            code[at + 1..at + 3].copy_from_slice(&(target as u16).to_be_bytes());
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Flags the instructions one round of `optimize_scope` drops.
+fn redundant_instructions(decoded: &[(usize, Opcode, Vec<usize>)], len: usize) -> Vec<bool> {
+    let targets = jump_targets(decoded);
+    let is_target = |offset: usize| targets.contains(&offset);
+    let next_offset = |i: usize| decoded.get(i + 1).map_or(len, |(at, _, _)| *at);
+
+    let mut removed = vec![false; decoded.len()];
+    let mut i = 0;
+    while i < decoded.len() {
+        match &decoded[i..] {
+            [(_, Opcode::Jump | Opcode::JumpIfFalse, t), ..] if t[0] == next_offset(i) => {
+                removed[i] = true;
+                i += 1;
+            }
+            [(first, Opcode::Bang, _), (second, Opcode::Bang, _), ..]
+                if i > 0
+                    && !removed[i - 1]
+                    && pushes_boolean(decoded[i - 1].1)
+                    && !is_target(*first)
+                    && !is_target(*second) =>
+            {
+                removed[i] = true;
+                removed[i + 1] = true;
+                i += 2;
+            }
+            [(_, push, _), (pop, Opcode::Pop, _), ..]
+                if pushes_literal(*push) && !is_target(*pop) =>
+            {
+                removed[i] = true;
+                removed[i + 1] = true;
+                i += 2;
+            }
+            _ => i += 1,
+        }
+    }
+    removed
+}
+
+/// Re-encode `decoded` without the removed instructions. A jump to a
+/// removed instruction lands on the next one kept.
+fn rebuild(
+    code: &mut Vec<u8>,
+    positions: &mut Vec<(usize, Span)>,
+    decoded: &[(usize, Opcode, Vec<usize>)],
+    removed: &[bool],
+) {
+    let mut new_offsets = HashMap::new();
+    let mut kept = 0;
+    for ((at, op, _), removed) in decoded.iter().zip(removed) {
+        new_offsets.insert(*at, kept);
+        if !removed {
+            kept += instruction_len(*op);
+        }
+    }
+    new_offsets.insert(code.len(), kept);
+
+    let mut out = Vec::with_capacity(kept);
+    for ((_, op, operands), removed) in decoded.iter().zip(removed) {
+        if *removed {
+            continue;
+        }
+        let operands = if is_jump(*op) {
+            vec![new_offsets[&operands[0]]]
+        } else {
+            operands.clone()
+        };
+        out.extend(make(*op, &operands).expect("re-encoding a decoded instruction"));
+    }
+    *code = out;
+
+    let kept_starts = decoded
+        .iter()
+        .zip(removed)
+        .filter(|(_, removed)| !**removed)
+        .map(|((at, _, _), _)| *at)
+        .collect::<HashSet<_>>();
+    positions.retain(|(offset, _)| kept_starts.contains(offset));
+    for (offset, _) in positions.iter_mut() {
+        *offset = new_offsets[offset];
+    }
+}
+
+fn jump_targets(decoded: &[(usize, Opcode, Vec<usize>)]) -> HashSet<usize> {
+    decoded
+        .iter()
+        .filter(|(_, op, _)| is_jump(*op))
+        .map(|(_, _, operands)| operands[0])
+        .collect()
+}
+
+fn is_jump(op: Opcode) -> bool {
+    matches!(
+        op,
+        Opcode::Jump | Opcode::JumpIfFalse | Opcode::JumpIfFalsePop
+    )
+}
+
+fn pushes_boolean(op: Opcode) -> bool {
+    matches!(
+        op,
+        Opcode::True
+            | Opcode::False
+            | Opcode::Bang
+            | Opcode::Eq
+            | Opcode::Ne
+            | Opcode::Lt
+            | Opcode::Gt
+            | Opcode::Le
+            | Opcode::Ge
+    )
+}
+
+/// Pushes with no side effects, so dropping one together with the `Pop`
+/// that discards it is unobservable.
+fn pushes_literal(op: Opcode) -> bool {
+    matches!(
+        op,
+        Opcode::Null
+            | Opcode::True
+            | Opcode::False
+            | Opcode::Const0
+            | Opcode::Const1
+            | Opcode::ConstInt
+            | Opcode::Constant
+    )
+}
+
+fn instruction_len(op: Opcode) -> usize {
+    1 + lookup_definition(op).operand_widths.iter().sum::<usize>()
+}
+
+fn decode(code: &[u8]) -> Option<Vec<(usize, Opcode, Vec<usize>)>> {
+    let mut out = Vec::new();
+    let mut offset = 0;
+    while offset < code.len() {
+        let op = Opcode::from_byte(code[offset])?;
+        let (operands, consumed) =
+            read_operands(lookup_definition(op), &code[offset + 1..]).ok()?;
+        out.push((offset, op, operands));
+        offset += 1 + consumed;
+    }
+    Some(out)
+}
+
+fn write(code: &mut [u8], at: usize, fused: &[u8], len: usize) {
+    code[at..at + fused.len()].copy_from_slice(fused);
+    code[at + fused.len()..at + len].fill(Opcode::Nop.to_byte());
+}
@@ -26,3 +26,52 @@ impl Display for Position {
         write!(f, "{}:{}", self.line, self.col)
     }
 }
+
+/// Where a compiled instruction came from. `pos` is the position
+/// diagnostics report, e.g. the operator of an infix expression, and
+/// `start..end` is the whole expression around it, `end` exclusive.
+/// Instructions whose extent is unknown have `start == end == pos`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub pos: Position,
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(pos: Position, start: Position, end: Position) -> Self {
+        Self { pos, start, end }
+    }
+
+    /// A span with no known extent beyond `pos`.
+    pub fn at(pos: Position) -> Self {
+        Self::new(pos, pos, pos)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl From<Position> for Span {
+    fn from(pos: Position) -> Self {
+        Self::at(pos)
+    }
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Self::at(Position::default())
+    }
+}
+
+impl Display for Span {
+    /// `pos`, followed by the extent when one is known: `1:3 (1:1-1:6)`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        if self.is_empty() {
+            write!(f, "{}", self.pos)
+        } else {
+            write!(f, "{} ({}-{})", self.pos, self.start, self.end)
+        }
+    }
+}
@@ -1,6 +1,81 @@
-use crate::ast::Program;
+use crate::ast::{BlockStatement, Expression, Program, Statement};
+use crate::ast_json::program_to_json;
+use crate::format::{layout_program, FormatOptions};
+use crate::json::Json;
+use crate::object::Object;
 use crate::token::Token;
 
+/// How `monkey --ast` renders a program (`--format <name>`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AstFormat {
+    /// The AST's `Display`, with fully parenthesized infix expressions.
+    #[default]
+    Text,
+    /// One s-expression per statement, see `format_ast_sexpr`.
+    Sexpr,
+    /// `ast_json::program_to_json`.
+    Json,
+    /// `format_ast_pretty` with these options.
+    Pretty(FormatOptions),
+}
+
+impl AstFormat {
+    /// The format named `text`, `sexpr`, `json` or `pretty`, the last with
+    /// default options.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(AstFormat::Text),
+            "sexpr" => Some(AstFormat::Sexpr),
+            "json" => Some(AstFormat::Json),
+            "pretty" => Some(AstFormat::Pretty(FormatOptions::default())),
+            _ => None,
+        }
+    }
+}
+
+/// How `monkey --tokens` renders the token stream (`--format <name>`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TokenFormat {
+    /// `Kind('literal') @ line:col`, one token per line.
+    #[default]
+    Text,
+    /// See `format_tokens_json`.
+    Json,
+}
+
+impl TokenFormat {
+    /// The format named `text` or `json`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(TokenFormat::Text),
+            "json" => Some(TokenFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// A JSON array with one object per token, on its own line:
+/// `{"kind": "Ident", "literal": "x", "line": 1, "col": 5, "end_line": 1,
+/// "end_col": 6}`. `end_line`/`end_col` are just past the token.
+pub fn format_tokens_json(tokens: &[Token]) -> String {
+    Json::Array(
+        tokens
+            .iter()
+            .map(|token| {
+                Json::Object(vec![
+                    ("kind", Json::string(token.kind.to_string())),
+                    ("literal", Json::string(&token.literal)),
+                    ("line", Json::Int(token.pos.line as i64)),
+                    ("col", Json::Int(token.pos.col as i64)),
+                    ("end_line", Json::Int(token.end.line as i64)),
+                    ("end_col", Json::Int(token.end.col as i64)),
+                ])
+            })
+            .collect(),
+    )
+    .render()
+}
+
 /// Placeholder token rendering for future --tokens mode.
 pub fn format_tokens_placeholder(tokens: &[Token]) -> String {
     format!("TOKENS: {} token(s)", tokens.len())
@@ -15,3 +90,314 @@ pub fn format_ast_placeholder(program: &Program) -> String {
 pub fn format_ast(program: &Program) -> String {
     program.to_string()
 }
+
+/// Render `program` in `format`.
+pub fn format_ast_as(program: &Program, format: AstFormat) -> String {
+    match format {
+        AstFormat::Text => format_ast(program),
+        AstFormat::Sexpr => format_ast_sexpr(program),
+        AstFormat::Json => program_to_json(program),
+        AstFormat::Pretty(options) => format_ast_pretty(program, options),
+    }
+}
+
+/// Multi-line rendering of `program` in `format_ast`'s notation: a block
+/// with statements puts each on its own line, indented
+/// `options.indent_width` spaces per level, and argument, element and pair
+/// lists running past `options.max_width` are broken one item per line.
+/// A program whose statements have no blocks or long lists renders exactly
+/// as `format_ast` does.
+pub fn format_ast_pretty(program: &Program, options: FormatOptions) -> String {
+    layout_program(program, options)
+}
+
+/// Layout of REPL results for `format_value_pretty`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValueLayout {
+    /// Spaces per nesting level.
+    pub indent_width: usize,
+    /// Column past which arrays and hashes are broken one item per line.
+    pub max_width: usize,
+    /// Items shown per array or hash; the rest are summarized as
+    /// `… (N more)`. `None` shows them all.
+    pub max_items: Option<usize>,
+}
+
+impl Default for ValueLayout {
+    fn default() -> Self {
+        Self {
+            indent_width: 2,
+            max_width: 80,
+            max_items: Some(100),
+        }
+    }
+}
+
+impl ValueLayout {
+    pub fn with_indent_width(mut self, indent_width: usize) -> Self {
+        self.indent_width = indent_width;
+        self
+    }
+
+    pub fn with_max_width(mut self, max_width: usize) -> Self {
+        self.max_width = max_width;
+        self
+    }
+
+    pub fn with_max_items(mut self, max_items: Option<usize>) -> Self {
+        self.max_items = max_items;
+        self
+    }
+}
+
+/// `value.inspect()`, except that arrays and hashes running past
+/// `layout.max_width` are broken one item per line, and long ones are cut
+/// to `layout.max_items` items. Values that fit render as `inspect` does.
+pub fn format_value_pretty(value: &Object, layout: ValueLayout) -> String {
+    layout_value(value, layout, &Object::inspect)
+}
+
+/// `format_value_pretty` with each scalar rendered by `leaf`, which may
+/// add escape codes; widths are measured on `inspect`.
+pub(crate) fn layout_value(
+    value: &Object,
+    layout: ValueLayout,
+    leaf: &dyn Fn(&Object) -> String,
+) -> String {
+    ValuePrinter { layout, leaf }.render(value, 0, 0)
+}
+
+struct ValuePrinter<'a> {
+    layout: ValueLayout,
+    leaf: &'a dyn Fn(&Object) -> String,
+}
+
+/// An item of an array or hash as laid out: a key and value, or the
+/// summary of the items left out.
+enum ValueItem<'v> {
+    Element(&'v Object),
+    Pair(&'v Object, &'v Object),
+    More(usize),
+}
+
+impl ValuePrinter<'_> {
+    /// `value` starting at column `indent + prefix`, where `indent` is the
+    /// indentation of its line.
+    fn render(&self, value: &Object, indent: usize, prefix: usize) -> String {
+        let Some((open, close, items)) = self.items(value) else {
+            return (self.leaf)(value);
+        };
+        if items.is_empty() {
+            return format!("{open}{close}");
+        }
+        let multiline = indent + prefix + self.flat_width(value) > self.layout.max_width;
+        if !multiline {
+            let rendered = items
+                .iter()
+                .map(|item| self.render_item(item, indent, 0))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return format!("{open}{rendered}{close}");
+        }
+        let inner = indent + self.layout.indent_width;
+        let pad = " ".repeat(inner);
+        let rendered = items
+            .iter()
+            .map(|item| format!("{pad}{}", self.render_item(item, inner, 0)))
+            .collect::<Vec<_>>()
+            .join(",\n");
+        format!("{open}\n{rendered}\n{}{close}", " ".repeat(indent))
+    }
+
+    fn render_item(&self, item: &ValueItem<'_>, indent: usize, prefix: usize) -> String {
+        match item {
+            ValueItem::Element(value) => self.render(value, indent, prefix),
+            ValueItem::Pair(key, value) => {
+                let value_prefix = prefix + self.flat_width(key) + 2;
+                format!(
+                    "{}: {}",
+                    self.render(key, indent, prefix),
+                    self.render(value, indent, value_prefix)
+                )
+            }
+            ValueItem::More(count) => format!("… ({count} more)"),
+        }
+    }
+
+    /// Delimiters and items of an array or hash, after `max_items`.
+    fn items<'v>(
+        &self,
+        value: &'v Object,
+    ) -> Option<(&'static str, &'static str, Vec<ValueItem<'v>>)> {
+        let (open, close, total, mut items): (_, _, _, Vec<ValueItem<'v>>) = match value {
+            Object::Array(values) => (
+                "[",
+                "]",
+                values.len(),
+                values
+                    .iter()
+                    .map(|v| ValueItem::Element(v.as_ref()))
+                    .collect(),
+            ),
+            Object::Hash(hash) => (
+                "{",
+                "}",
+                hash.len(),
+                hash.iter()
+                    .map(|(k, v)| ValueItem::Pair(k.as_ref(), v.as_ref()))
+                    .collect(),
+            ),
+            _ => return None,
+        };
+        if let Some(max) = self.layout.max_items {
+            if total > max {
+                items.truncate(max);
+                items.push(ValueItem::More(total - max));
+            }
+        }
+        Some((open, close, items))
+    }
+
+    /// Width of `value` rendered on one line.
+    fn flat_width(&self, value: &Object) -> usize {
+        let Some((_, _, items)) = self.items(value) else {
+            return value.inspect().chars().count();
+        };
+        let separators = 2 * items.len().saturating_sub(1);
+        2 + separators
+            + items
+                .iter()
+                .map(|item| match item {
+                    ValueItem::Element(value) => self.flat_width(value),
+                    ValueItem::Pair(key, value) => {
+                        self.flat_width(key) + 2 + self.flat_width(value)
+                    }
+                    ValueItem::More(count) => format!("… ({count} more)").chars().count(),
+                })
+                .sum::<usize>()
+    }
+}
+
+/// Compact s-expression rendering, one top-level statement per line, e.g.
+/// `let x = a + b * 2;` becomes `(let x (+ a (* b 2)))`.
+///
+/// Operators head their own lists; other nodes are headed by a keyword
+/// (`let`, `return`, `while`, `break`, `continue`, `if`, `fn`, `call`,
+/// `array`, `hash`, `index`, `block`). Expression statements are just
+/// their expression, and a missing `else` is left out.
+pub fn format_ast_sexpr(program: &Program) -> String {
+    program
+        .statements
+        .iter()
+        .map(statement_sexpr)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn statement_sexpr(statement: &Statement) -> String {
+    match statement {
+        Statement::Let { name, value, .. } => {
+            format!("(let {} {})", name.value, expression_sexpr(value))
+        }
+        Statement::Return { value, .. } => format!("(return {})", expression_sexpr(value)),
+        Statement::While {
+            condition, body, ..
+        } => format!(
+            "(while {} {})",
+            expression_sexpr(condition),
+            block_sexpr(body)
+        ),
+        Statement::Break { .. } => "(break)".to_string(),
+        Statement::Continue { .. } => "(continue)".to_string(),
+        Statement::Expression { expression, .. } => expression_sexpr(expression),
+    }
+}
+
+fn expression_sexpr(expression: &Expression) -> String {
+    match expression {
+        Expression::Identifier { value, .. } => value.clone(),
+        Expression::IntegerLiteral { raw, .. } => raw.clone(),
+        Expression::BooleanLiteral { value, .. } => value.to_string(),
+        Expression::StringLiteral { value, .. } => format!("\"{value}\""),
+        Expression::Prefix {
+            operator, right, ..
+        } => format!("({operator} {})", expression_sexpr(right)),
+        Expression::Infix {
+            left,
+            operator,
+            right,
+            ..
+        } => format!(
+            "({operator} {} {})",
+            expression_sexpr(left),
+            expression_sexpr(right)
+        ),
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+            ..
+        } => {
+            let mut out = format!(
+                "(if {} {}",
+                expression_sexpr(condition),
+                block_sexpr(consequence)
+            );
+            if let Some(alternative) = alternative {
+                out.push(' ');
+                out.push_str(&block_sexpr(alternative));
+            }
+            out.push(')');
+            out
+        }
+        Expression::FunctionLiteral {
+            parameters, body, ..
+        } => {
+            let parameters = parameters
+                .iter()
+                .map(|parameter| parameter.value.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!("(fn ({parameters}) {})", block_sexpr(body))
+        }
+        Expression::Call {
+            function,
+            arguments,
+            ..
+        } => list(
+            "call",
+            std::iter::once(function.as_ref())
+                .chain(arguments)
+                .map(expression_sexpr),
+        ),
+        Expression::ArrayLiteral { elements, .. } => {
+            list("array", elements.iter().map(expression_sexpr))
+        }
+        Expression::HashLiteral { pairs, .. } => list(
+            "hash",
+            pairs.iter().map(|(key, value)| {
+                format!("({} {})", expression_sexpr(key), expression_sexpr(value))
+            }),
+        ),
+        Expression::Index { left, index, .. } => format!(
+            "(index {} {})",
+            expression_sexpr(left),
+            expression_sexpr(index)
+        ),
+    }
+}
+
+fn block_sexpr(block: &BlockStatement) -> String {
+    list("block", block.statements.iter().map(statement_sexpr))
+}
+
+/// `(head item...)`, or `(head)` with no items.
+fn list(head: &str, items: impl Iterator<Item = String>) -> String {
+    let mut out = format!("({head}");
+    for item in items {
+        out.push(' ');
+        out.push_str(&item);
+    }
+    out.push(')');
+    out
+}
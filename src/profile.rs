@@ -0,0 +1,188 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use crate::bytecode::{lookup_definition, Opcode};
+
+/// Call statistics for one Monkey function, keyed by name in `Profile`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionProfile {
+    pub calls: u64,
+    /// Inclusive wall-clock time; recursive calls are only timed at their
+    /// outermost activation so time is not counted twice.
+    pub total_time: Duration,
+}
+
+/// Name of the top-level code in folded stacks, as in `cfg` and `emit-c`.
+pub const MAIN_FRAME: &str = "<main>";
+
+/// Counters collected by a profiling `Vm`.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    opcode_counts: BTreeMap<u8, u64>,
+    functions: BTreeMap<String, FunctionProfile>,
+    active: HashMap<String, usize>,
+    stacks: StackTree,
+}
+
+/// Instructions executed under each distinct call stack. Node 0 is the
+/// top-level code; `current` follows calls and returns.
+#[derive(Debug, Clone)]
+struct StackTree {
+    nodes: Vec<StackNode>,
+    current: usize,
+}
+
+#[derive(Debug, Clone)]
+struct StackNode {
+    name: String,
+    parent: usize,
+    children: HashMap<String, usize>,
+    instructions: u64,
+}
+
+impl StackNode {
+    fn new(name: &str, parent: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            parent,
+            children: HashMap::new(),
+            instructions: 0,
+        }
+    }
+}
+
+impl Default for StackTree {
+    fn default() -> Self {
+        Self {
+            nodes: vec![StackNode::new(MAIN_FRAME, 0)],
+            current: 0,
+        }
+    }
+}
+
+impl StackTree {
+    fn enter(&mut self, name: &str) {
+        let next = self.nodes.len();
+        let child = *self.nodes[self.current]
+            .children
+            .entry(name.to_string())
+            .or_insert(next);
+        if child == next {
+            self.nodes.push(StackNode::new(name, self.current));
+        }
+        self.current = child;
+    }
+
+    fn exit(&mut self) {
+        self.current = self.nodes[self.current].parent;
+    }
+
+    /// `a;b;c` for node `index`, outermost frame first.
+    fn path(&self, mut index: usize) -> String {
+        let mut names = vec![self.nodes[index].name.as_str()];
+        while index != 0 {
+            index = self.nodes[index].parent;
+            names.push(self.nodes[index].name.as_str());
+        }
+        names.reverse();
+        names.join(";")
+    }
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Executed instruction counts per opcode name, most frequent first.
+    pub fn opcode_counts(&self) -> Vec<(&'static str, u64)> {
+        let mut counts = self
+            .opcode_counts
+            .iter()
+            .filter_map(|(&byte, &count)| {
+                Opcode::from_byte(byte).map(|op| (lookup_definition(op).name, count))
+            })
+            .collect::<Vec<_>>();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts
+    }
+
+    pub fn functions(&self) -> &BTreeMap<String, FunctionProfile> {
+        &self.functions
+    }
+
+    /// Instructions executed per call stack, as `<main>;outer;inner`
+    /// paths sorted by path. Stacks that only called further functions
+    /// without running instructions of their own are left out.
+    pub fn folded_stacks(&self) -> Vec<(String, u64)> {
+        let mut stacks = self
+            .stacks
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.instructions > 0)
+            .map(|(index, node)| (self.stacks.path(index), node.instructions))
+            .collect::<Vec<_>>();
+        stacks.sort();
+        stacks
+    }
+
+    /// `folded_stacks` in the folded format read by flamegraph tools such
+    /// as inferno: one `stack count` line each.
+    pub fn render_folded(&self) -> String {
+        self.folded_stacks()
+            .iter()
+            .map(|(stack, count)| format!("{stack} {count}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub(crate) fn record_opcode(&mut self, op: Opcode) {
+        *self.opcode_counts.entry(op.to_byte()).or_default() += 1;
+        let current = self.stacks.current;
+        self.stacks.nodes[current].instructions += 1;
+    }
+
+    /// Record a call; returns true when this is the outermost active call
+    /// of `name` and should therefore be timed.
+    pub(crate) fn enter_function(&mut self, name: &str) -> bool {
+        self.functions.entry(name.to_string()).or_default().calls += 1;
+        self.stacks.enter(name);
+        let active = self.active.entry(name.to_string()).or_default();
+        *active += 1;
+        *active == 1
+    }
+
+    pub(crate) fn exit_function(&mut self, name: &str, elapsed: Option<Duration>) {
+        self.stacks.exit();
+        if let Some(active) = self.active.get_mut(name) {
+            *active = active.saturating_sub(1);
+        }
+        if let (Some(elapsed), Some(function)) = (elapsed, self.functions.get_mut(name)) {
+            function.total_time += elapsed;
+        }
+    }
+
+    /// Human-readable report used by `monkey bench --profile`.
+    pub fn render(&self) -> String {
+        let mut lines = vec!["Opcodes:".to_string()];
+        for (name, count) in self.opcode_counts() {
+            lines.push(format!("  {name:<16} {count}"));
+        }
+
+        lines.push("Functions:".to_string());
+        if self.functions.is_empty() {
+            lines.push("  (none)".to_string());
+        }
+        let mut functions = self.functions.iter().collect::<Vec<_>>();
+        functions.sort_by(|a, b| b.1.total_time.cmp(&a.1.total_time).then(a.0.cmp(b.0)));
+        for (name, function) in functions {
+            lines.push(format!(
+                "  {name:<16} calls={} time={:.3} ms",
+                function.calls,
+                function.total_time.as_secs_f64() * 1000.0
+            ));
+        }
+        lines.join("\n")
+    }
+}
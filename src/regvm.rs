@@ -0,0 +1,838 @@
+//! Experimental register-machine backend (`register-vm` feature).
+//!
+//! `RegisterVm` translates the compiler's stack bytecode into register
+//! instructions and interprets those. Registers `0..num_locals` of a frame
+//! are the function's locals; the stack slot at depth `d` lives in register
+//! `num_locals + d`. The translator tracks the operand stack abstractly, so
+//! constants, locals and globals are read in place instead of being pushed
+//! first, and only materializes real registers at jumps, jump targets and
+//! calls.
+//!
+//! The backend only covers the happy path. Anything it does not handle —
+//! runtime errors, `eval`, unknown opcodes — makes `run` return `None`, and
+//! the caller reruns the program on `Vm` so results, output and error
+//! reports stay identical to the stack VM.
+
+use std::collections::HashMap;
+
+use crate::builtins::{builtin_name_at, execute_builtin, BuiltinContext};
+use crate::bytecode::{lookup_definition, read_operands, Chunk, Opcode};
+use crate::object::{
+    BuiltinObject, ClosureObject, CompiledFunctionObject, HashObject, Object, ObjectRef, Shared,
+    StringInterner,
+};
+use crate::vm::VmOptions;
+
+/// Where an instruction reads a value from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    Reg(u16),
+    Const(u32),
+    Global(u16),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Insn {
+    Move {
+        dst: u16,
+        src: Operand,
+    },
+    StoreGlobal {
+        idx: u16,
+        src: Operand,
+    },
+    LoadFree {
+        dst: u16,
+        idx: u16,
+    },
+    LoadClosure {
+        dst: u16,
+    },
+    Binary {
+        op: Opcode,
+        dst: u16,
+        a: Operand,
+        b: Operand,
+    },
+    Neg {
+        dst: u16,
+        src: Operand,
+    },
+    Not {
+        dst: u16,
+        src: Operand,
+    },
+    Jump {
+        target: u32,
+    },
+    JumpIfFalse {
+        cond: Operand,
+        target: u32,
+    },
+    Array {
+        dst: u16,
+        start: u16,
+        count: u16,
+    },
+    Hash {
+        dst: u16,
+        start: u16,
+        pairs: u16,
+    },
+    Index {
+        dst: u16,
+        left: Operand,
+        index: Operand,
+    },
+    Closure {
+        dst: u16,
+        function: u32,
+        start: u16,
+        count: u16,
+    },
+    /// Call the value in `base` with the `argc` registers after it; the
+    /// result replaces the callee.
+    Call {
+        base: u16,
+        argc: u16,
+    },
+    Return {
+        src: Operand,
+    },
+    ReturnNull,
+    /// Hand the program back to the stack VM.
+    Fallback,
+}
+
+/// Why a chunk could not be translated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranslateError(pub String);
+
+#[derive(Debug)]
+struct RegFunction {
+    code: Shared<[Insn]>,
+    num_params: usize,
+    num_locals: usize,
+    num_regs: usize,
+}
+
+/// Constant pool: the chunk's constants followed by values the translator
+/// materializes (booleans, null, inline integers, builtins).
+#[derive(Debug)]
+struct ConstPool {
+    values: Vec<ObjectRef>,
+    ints: HashMap<i64, u32>,
+    builtins: HashMap<usize, u32>,
+    true_idx: u32,
+    false_idx: u32,
+    null_idx: u32,
+}
+
+impl ConstPool {
+    fn new(constants: &[ObjectRef]) -> Self {
+        let mut values = constants.to_vec();
+        let base = values.len() as u32;
+        values.push(Object::Boolean(true).rc());
+        values.push(Object::Boolean(false).rc());
+        values.push(Object::Null.rc());
+        Self {
+            values,
+            ints: HashMap::new(),
+            builtins: HashMap::new(),
+            true_idx: base,
+            false_idx: base + 1,
+            null_idx: base + 2,
+        }
+    }
+
+    fn int(&mut self, value: i64) -> u32 {
+        let values = &mut self.values;
+        *self.ints.entry(value).or_insert_with(|| {
+            values.push(Object::Integer(value).rc());
+            (values.len() - 1) as u32
+        })
+    }
+
+    fn builtin(&mut self, idx: usize) -> Result<u32, TranslateError> {
+        let name = builtin_name_at(idx)
+            .ok_or_else(|| TranslateError(format!("unknown builtin index: {idx}")))?;
+        let values = &mut self.values;
+        Ok(*self.builtins.entry(idx).or_insert_with(|| {
+            values.push(
+                Object::Builtin(BuiltinObject {
+                    name: name.to_string(),
+                })
+                .rc(),
+            );
+            (values.len() - 1) as u32
+        }))
+    }
+}
+
+struct Translator<'a> {
+    pool: &'a mut ConstPool,
+    num_locals: usize,
+    stack: Vec<Operand>,
+    code: Vec<Insn>,
+    max_depth: usize,
+    /// Stack depth on entry to each jump target, once known.
+    depths: HashMap<usize, Option<usize>>,
+    /// Instruction index of each jump target that was reached.
+    labels: HashMap<usize, u32>,
+}
+
+impl Translator<'_> {
+    fn temp(&self, depth: usize) -> Result<u16, TranslateError> {
+        u16::try_from(self.num_locals + depth)
+            .map_err(|_| TranslateError("too many registers".to_string()))
+    }
+
+    fn pop(&mut self) -> Result<Operand, TranslateError> {
+        self.stack
+            .pop()
+            .ok_or_else(|| TranslateError("stack underflow".to_string()))
+    }
+
+    /// Push the register that receives the next result.
+    fn result(&mut self) -> Result<u16, TranslateError> {
+        let dst = self.temp(self.stack.len())?;
+        self.stack.push(Operand::Reg(dst));
+        self.max_depth = self.max_depth.max(self.stack.len());
+        Ok(dst)
+    }
+
+    /// Move stack entries from `from` upwards into their own registers.
+    fn materialize(&mut self, from: usize) -> Result<(), TranslateError> {
+        for depth in from..self.stack.len() {
+            let dst = self.temp(depth)?;
+            if self.stack[depth] != Operand::Reg(dst) {
+                self.max_depth = self.max_depth.max(depth + 1);
+                self.code.push(Insn::Move {
+                    dst,
+                    src: self.stack[depth],
+                });
+                self.stack[depth] = Operand::Reg(dst);
+            }
+        }
+        Ok(())
+    }
+
+    /// Materialize pending reads of `operand` before it is overwritten.
+    fn flush(&mut self, operand: Operand) -> Result<(), TranslateError> {
+        for depth in 0..self.stack.len() {
+            if self.stack[depth] == operand {
+                let dst = self.temp(depth)?;
+                self.max_depth = self.max_depth.max(depth + 1);
+                self.code.push(Insn::Move { dst, src: operand });
+                self.stack[depth] = Operand::Reg(dst);
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle a possible jump target at `offset`; returns whether the code
+    /// from here on is reachable.
+    fn enter_target(&mut self, offset: usize, live: bool) -> Result<bool, TranslateError> {
+        let Some(&recorded) = self.depths.get(&offset) else {
+            return Ok(live);
+        };
+        if live {
+            self.materialize(0)?;
+            self.record_depth(offset)?;
+        } else {
+            // Dead code until a jump is known to land here.
+            let Some(depth) = recorded else {
+                return Ok(false);
+            };
+            self.stack = (0..depth)
+                .map(|d| self.temp(d).map(Operand::Reg))
+                .collect::<Result<_, _>>()?;
+        }
+        self.labels.insert(offset, self.code.len() as u32);
+        Ok(true)
+    }
+
+    /// Every path into `offset` must arrive with the same stack depth.
+    fn record_depth(&mut self, offset: usize) -> Result<(), TranslateError> {
+        let depth = self.stack.len();
+        match self.depths.insert(offset, Some(depth)) {
+            Some(Some(recorded)) if recorded != depth => {
+                Err(TranslateError(format!("stack depth mismatch at {offset}")))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Materialize the top `count` entries and pop them, returning the
+    /// first register of the run.
+    fn take_run(&mut self, count: usize) -> Result<u16, TranslateError> {
+        let start = self
+            .stack
+            .len()
+            .checked_sub(count)
+            .ok_or_else(|| TranslateError("stack underflow".to_string()))?;
+        self.materialize(start)?;
+        self.stack.truncate(start);
+        self.temp(start)
+    }
+}
+
+fn narrow<T: TryFrom<usize>>(value: usize) -> Result<T, TranslateError> {
+    T::try_from(value).map_err(|_| TranslateError(format!("operand out of range: {value}")))
+}
+
+fn decode(code: &[u8]) -> Result<Vec<(usize, Opcode, Vec<usize>)>, TranslateError> {
+    let mut out = Vec::new();
+    let mut offset = 0;
+    while offset < code.len() {
+        let op = Opcode::from_byte(code[offset])
+            .ok_or_else(|| TranslateError(format!("unknown opcode {}", code[offset])))?;
+        let (operands, consumed) = read_operands(lookup_definition(op), &code[offset + 1..])
+            .map_err(|_| TranslateError(format!("truncated instruction at {offset}")))?;
+        out.push((offset, op, operands));
+        offset += 1 + consumed;
+    }
+    Ok(out)
+}
+
+fn translate(
+    code: &[u8],
+    num_params: usize,
+    num_locals: usize,
+    pool: &mut ConstPool,
+) -> Result<RegFunction, TranslateError> {
+    let decoded = decode(code)?;
+    let depths = decoded
+        .iter()
+        .filter(|(_, op, _)| matches!(op, Opcode::Jump | Opcode::JumpIfFalse))
+        .map(|(_, _, operands)| (operands[0], None))
+        .collect();
+    let mut t = Translator {
+        pool,
+        num_locals,
+        stack: Vec::new(),
+        code: Vec::new(),
+        max_depth: 0,
+        depths,
+        labels: HashMap::new(),
+    };
+    let mut fixups = Vec::new();
+    let mut live = true;
+
+    for (offset, op, operands) in &decoded {
+        live = t.enter_target(*offset, live)?;
+        if !live {
+            continue;
+        }
+        let operand = operands.first().copied().unwrap_or(0);
+        match op {
+            Opcode::Constant => t.stack.push(Operand::Const(narrow(operand)?)),
+            Opcode::ConstInt => {
+                let value = operand as u16 as i16;
+                let idx = t.pool.int(i64::from(value));
+                t.stack.push(Operand::Const(idx));
+            }
+            Opcode::Const0 | Opcode::Const1 => {
+                let idx = t.pool.int(i64::from(*op == Opcode::Const1));
+                t.stack.push(Operand::Const(idx));
+            }
+            Opcode::True => t.stack.push(Operand::Const(t.pool.true_idx)),
+            Opcode::False => t.stack.push(Operand::Const(t.pool.false_idx)),
+            Opcode::Null => t.stack.push(Operand::Const(t.pool.null_idx)),
+            Opcode::Pop => {
+                t.pop()?;
+            }
+            Opcode::Add
+            | Opcode::Sub
+            | Opcode::Mul
+            | Opcode::Div
+            | Opcode::Eq
+            | Opcode::Ne
+            | Opcode::Lt
+            | Opcode::Gt
+            | Opcode::Le
+            | Opcode::Ge => {
+                let b = t.pop()?;
+                let a = t.pop()?;
+                let dst = t.result()?;
+                t.code.push(Insn::Binary { op: *op, dst, a, b });
+            }
+            Opcode::Neg | Opcode::Bang => {
+                let src = t.pop()?;
+                let dst = t.result()?;
+                t.code.push(if *op == Opcode::Neg {
+                    Insn::Neg { dst, src }
+                } else {
+                    Insn::Not { dst, src }
+                });
+            }
+            Opcode::Jump | Opcode::JumpIfFalse => {
+                t.materialize(0)?;
+                t.record_depth(operand)?;
+                fixups.push((t.code.len(), operand));
+                if *op == Opcode::Jump {
+                    t.code.push(Insn::Jump { target: 0 });
+                    live = false;
+                } else {
+                    // The condition stays on the stack; both successors pop it.
+                    let cond = *t
+                        .stack
+                        .last()
+                        .ok_or_else(|| TranslateError("stack underflow".to_string()))?;
+                    t.code.push(Insn::JumpIfFalse { cond, target: 0 });
+                }
+            }
+            Opcode::GetGlobal => t.stack.push(Operand::Global(narrow(operand)?)),
+            Opcode::SetGlobal => {
+                let src = t.pop()?;
+                let idx = narrow(operand)?;
+                t.flush(Operand::Global(idx))?;
+                t.code.push(Insn::StoreGlobal { idx, src });
+            }
+            Opcode::GetLocal => t.stack.push(Operand::Reg(narrow(operand)?)),
+            Opcode::SetLocal => {
+                let src = t.pop()?;
+                let dst = narrow(operand)?;
+                t.flush(Operand::Reg(dst))?;
+                t.code.push(Insn::Move { dst, src });
+            }
+            Opcode::GetBuiltin => {
+                let idx = t.pool.builtin(operand)?;
+                t.stack.push(Operand::Const(idx));
+            }
+            Opcode::GetFree => {
+                let dst = t.result()?;
+                t.code.push(Insn::LoadFree {
+                    dst,
+                    idx: narrow(operand)?,
+                });
+            }
+            Opcode::CurrentClosure => {
+                let dst = t.result()?;
+                t.code.push(Insn::LoadClosure { dst });
+            }
+            Opcode::Closure => {
+                if !matches!(
+                    t.pool.values.get(operand).map(AsRef::as_ref),
+                    Some(Object::CompiledFunction(_))
+                ) {
+                    return Err(TranslateError(format!(
+                        "closure constant {operand} is not a function"
+                    )));
+                }
+                let count = operands[1];
+                let start = t.take_run(count)?;
+                let dst = t.result()?;
+                t.code.push(Insn::Closure {
+                    dst,
+                    function: narrow(operand)?,
+                    start,
+                    count: narrow(count)?,
+                });
+            }
+            Opcode::Call => {
+                let base = t.take_run(operand + 1)?;
+                t.result()?;
+                t.code.push(Insn::Call {
+                    base,
+                    argc: narrow(operand)?,
+                });
+            }
+            Opcode::ReturnValue => {
+                let src = t.pop()?;
+                t.code.push(Insn::Return { src });
+                live = false;
+            }
+            Opcode::Return => {
+                t.code.push(Insn::ReturnNull);
+                live = false;
+            }
+            Opcode::Array => {
+                let start = t.take_run(operand)?;
+                let dst = t.result()?;
+                t.code.push(Insn::Array {
+                    dst,
+                    start,
+                    count: narrow(operand)?,
+                });
+            }
+            Opcode::Hash => {
+                let start = t.take_run(operand * 2)?;
+                let dst = t.result()?;
+                t.code.push(Insn::Hash {
+                    dst,
+                    start,
+                    pairs: narrow(operand)?,
+                });
+            }
+            Opcode::Index => {
+                let index = t.pop()?;
+                let left = t.pop()?;
+                let dst = t.result()?;
+                t.code.push(Insn::Index { dst, left, index });
+            }
+            Opcode::InvalidBreak | Opcode::InvalidContinue => {
+                t.code.push(Insn::Fallback);
+                live = false;
+            }
+            Opcode::Nop => {}
+            Opcode::AddConstants | Opcode::AddLocals | Opcode::JumpIfFalsePop => {
+                return Err(TranslateError(format!(
+                    "superinstruction {} is not supported",
+                    lookup_definition(*op).name
+                )));
+            }
+        }
+    }
+    // Jumps may land one past the last instruction.
+    if t.enter_target(code.len(), live)? {
+        t.code.push(Insn::Fallback);
+    }
+
+    for (at, offset) in fixups {
+        let resolved = *t
+            .labels
+            .get(&offset)
+            .ok_or_else(|| TranslateError(format!("jump into unreachable code at {offset}")))?;
+        match &mut t.code[at] {
+            Insn::Jump { target } | Insn::JumpIfFalse { target, .. } => *target = resolved,
+            _ => unreachable!("fixups only point at jumps"),
+        }
+    }
+
+    Ok(RegFunction {
+        code: t.code.into(),
+        num_params,
+        num_locals,
+        num_regs: num_locals + t.max_depth,
+    })
+}
+
+/// Caller state saved across a call.
+#[derive(Debug)]
+struct RegFrame {
+    code: Shared<[Insn]>,
+    closure: Shared<ClosureObject>,
+    pc: usize,
+    base: usize,
+}
+
+/// Register-machine interpreter for a translated chunk.
+#[derive(Debug)]
+pub struct RegisterVm {
+    main: RegFunction,
+    main_closure: Shared<ClosureObject>,
+    functions: Vec<(Shared<CompiledFunctionObject>, RegFunction)>,
+    consts: Vec<ObjectRef>,
+    regs: Vec<ObjectRef>,
+    globals: Vec<ObjectRef>,
+    frames: Vec<RegFrame>,
+    strings: StringInterner,
+    output: Vec<String>,
+    script_args: Vec<String>,
+    max_call_depth: usize,
+}
+
+impl RegisterVm {
+    /// Translate the top-level code and every function in the constant pool.
+    pub fn new(chunk: &Chunk) -> Result<Self, TranslateError> {
+        let mut pool = ConstPool::new(&chunk.constants);
+        let main = translate(&chunk.instructions, 0, 0, &mut pool)?;
+        let mut functions = Vec::new();
+        for constant in &chunk.constants {
+            if let Object::CompiledFunction(function) = constant.as_ref() {
+                let translated = translate(
+                    &function.instructions,
+                    function.num_params,
+                    function.num_locals,
+                    &mut pool,
+                )?;
+                functions.push((Shared::clone(function), translated));
+            }
+        }
+
+        let mut strings = StringInterner::new();
+        for constant in &chunk.constants {
+            if let Object::String(text) = constant.as_ref() {
+                strings.intern_shared(Shared::clone(text));
+            }
+        }
+        let main_closure = Shared::new(ClosureObject {
+            function: Shared::new(CompiledFunctionObject {
+                name: Some("<repl>".to_string()),
+                num_params: 0,
+                num_locals: 0,
+                instructions: Vec::new().into(),
+                positions: Vec::new().into(),
+            }),
+            free: Vec::new(),
+        });
+
+        Ok(Self {
+            regs: vec![Object::Null.rc(); main.num_regs],
+            main,
+            main_closure,
+            functions,
+            consts: pool.values,
+            globals: Vec::new(),
+            frames: Vec::new(),
+            strings,
+            output: Vec::new(),
+            script_args: Vec::new(),
+            max_call_depth: VmOptions::default().max_call_depth,
+        })
+    }
+
+    pub fn set_script_args(&mut self, args: Vec<String>) {
+        self.script_args = args;
+    }
+
+    /// Call frames allowed before `run` gives up so the stack VM can
+    /// report the overflow; see `VmOptions::max_call_depth`.
+    pub fn set_max_call_depth(&mut self, max_call_depth: usize) {
+        self.max_call_depth = max_call_depth;
+    }
+
+    pub fn take_output(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.output)
+    }
+
+    /// Run to completion. `None` means the program needs the stack VM: it
+    /// raised an error or reached something this backend does not handle.
+    pub fn run(&mut self) -> Option<ObjectRef> {
+        let true_obj = Object::Boolean(true).rc();
+        let false_obj = Object::Boolean(false).rc();
+        let null = Object::Null.rc();
+
+        let mut code = Shared::clone(&self.main.code);
+        let mut closure = Shared::clone(&self.main_closure);
+        let mut pc = 0;
+        let mut base = 0;
+
+        // Reads borrow only the field they touch, so results can be written
+        // back while operands are still in use.
+        macro_rules! read {
+            ($operand:expr) => {
+                match $operand {
+                    Operand::Reg(reg) => &self.regs[base + reg as usize],
+                    Operand::Const(idx) => &self.consts[idx as usize],
+                    Operand::Global(idx) => self.globals.get(idx as usize)?,
+                }
+            };
+        }
+
+        loop {
+            let insn = *code.get(pc)?;
+            pc += 1;
+            match insn {
+                Insn::Move { dst, src } => {
+                    let value = read!(src).clone();
+                    self.regs[base + dst as usize] = value;
+                }
+                Insn::StoreGlobal { idx, src } => {
+                    let value = read!(src).clone();
+                    let idx = idx as usize;
+                    if self.globals.len() <= idx {
+                        self.globals.resize(idx + 1, null.clone());
+                    }
+                    self.globals[idx] = value;
+                }
+                Insn::LoadFree { dst, idx } => {
+                    self.regs[base + dst as usize] = closure.free.get(idx as usize)?.clone();
+                }
+                Insn::LoadClosure { dst } => {
+                    self.regs[base + dst as usize] = Object::Closure(Shared::clone(&closure)).rc();
+                }
+                Insn::Binary { op, dst, a, b } => {
+                    let a = read!(a);
+                    let b = read!(b);
+                    let value = match (a.as_ref(), b.as_ref()) {
+                        (Object::Integer(a), Object::Integer(b)) => match op {
+                            Opcode::Add => Object::Integer(a + b).rc(),
+                            Opcode::Sub => Object::Integer(a - b).rc(),
+                            Opcode::Mul => Object::Integer(a * b).rc(),
+                            Opcode::Div if *b == 0 => return None,
+                            Opcode::Div => Object::Integer(a / b).rc(),
+                            _ => bool_ref(compare(op, a, b), &true_obj, &false_obj),
+                        },
+                        (Object::String(a), Object::String(b)) => match op {
+                            Opcode::Add => {
+                                Object::String(self.strings.intern(&format!("{a}{b}"))).rc()
+                            }
+                            Opcode::Eq => bool_ref(a == b, &true_obj, &false_obj),
+                            Opcode::Ne => bool_ref(a != b, &true_obj, &false_obj),
+                            _ => return None,
+                        },
+                        (Object::Boolean(a), Object::Boolean(b)) => {
+                            bool_ref(equality(op, a == b)?, &true_obj, &false_obj)
+                        }
+                        (Object::Null, Object::Null) => {
+                            bool_ref(equality(op, true)?, &true_obj, &false_obj)
+                        }
+                        _ => return None,
+                    };
+                    self.regs[base + dst as usize] = value;
+                }
+                Insn::Neg { dst, src } => {
+                    let value = match read!(src).as_ref() {
+                        Object::Integer(v) => Object::Integer(-v).rc(),
+                        Object::Null => null.clone(),
+                        _ => return None,
+                    };
+                    self.regs[base + dst as usize] = value;
+                }
+                Insn::Not { dst, src } => {
+                    let truthy = read!(src).is_truthy();
+                    self.regs[base + dst as usize] = bool_ref(!truthy, &true_obj, &false_obj);
+                }
+                Insn::Jump { target } => pc = target as usize,
+                Insn::JumpIfFalse { cond, target } => {
+                    if !read!(cond).is_truthy() {
+                        pc = target as usize;
+                    }
+                }
+                Insn::Array { dst, start, count } => {
+                    let start = base + start as usize;
+                    let items = self.regs[start..start + count as usize].to_vec();
+                    self.regs[base + dst as usize] = Object::Array(items).rc();
+                }
+                Insn::Hash { dst, start, pairs } => {
+                    let start = base + start as usize;
+                    let values = &self.regs[start..start + 2 * pairs as usize];
+                    let pairs = values
+                        .chunks_exact(2)
+                        .map(|kv| (kv[0].clone(), kv[1].clone()));
+                    let hash = HashObject::from_pairs(pairs).ok()?;
+                    self.regs[base + dst as usize] = Object::Hash(hash).rc();
+                }
+                Insn::Index { dst, left, index } => {
+                    let left = read!(left);
+                    let index = read!(index);
+                    let value = match (left.as_ref(), index.as_ref()) {
+                        (Object::Array(values), Object::Integer(i)) => usize::try_from(*i)
+                            .ok()
+                            .and_then(|i| values.get(i))
+                            .unwrap_or(&null)
+                            .clone(),
+                        (Object::Hash(hash), key) => {
+                            hash.get(&key.hash_key()?).unwrap_or(&null).clone()
+                        }
+                        _ => return None,
+                    };
+                    self.regs[base + dst as usize] = value;
+                }
+                Insn::Closure {
+                    dst,
+                    function,
+                    start,
+                    count,
+                } => {
+                    let Object::CompiledFunction(function) =
+                        self.consts[function as usize].as_ref()
+                    else {
+                        return None;
+                    };
+                    let start = base + start as usize;
+                    let free = self.regs[start..start + count as usize].to_vec();
+                    self.regs[base + dst as usize] = Object::Closure(Shared::new(ClosureObject {
+                        function: Shared::clone(function),
+                        free,
+                    }))
+                    .rc();
+                }
+                Insn::Call { base: callee, argc } => {
+                    let callee = base + callee as usize;
+                    let argc = argc as usize;
+                    match self.regs[callee].as_ref() {
+                        Object::Closure(target) => {
+                            if target.function.num_params != argc
+                                || self.frames.len() + 1 >= self.max_call_depth
+                            {
+                                return None;
+                            }
+                            let (_, function) = self
+                                .functions
+                                .iter()
+                                .find(|(f, _)| Shared::ptr_eq(f, &target.function))?;
+                            let target = Shared::clone(target);
+                            let new_base = callee + 1;
+                            let required = new_base + function.num_regs;
+                            if self.regs.len() < required {
+                                self.regs.resize(required, null.clone());
+                            }
+                            for slot in function.num_params..function.num_locals {
+                                self.regs[new_base + slot] = null.clone();
+                            }
+                            let callee_code = Shared::clone(&function.code);
+                            self.frames.push(RegFrame {
+                                code: std::mem::replace(&mut code, callee_code),
+                                closure: std::mem::replace(&mut closure, target),
+                                pc,
+                                base,
+                            });
+                            pc = 0;
+                            base = new_base;
+                        }
+                        Object::Builtin(builtin) if builtin.name != "eval" => {
+                            let args = &self.regs[callee + 1..callee + 1 + argc];
+                            let mut ctx = BuiltinContext {
+                                output: &mut self.output,
+                                script_args: &self.script_args,
+                                strings: &mut self.strings,
+                            };
+                            let result = execute_builtin(&builtin.name, args, &mut ctx).ok()?;
+                            self.regs[callee] = result;
+                        }
+                        _ => return None,
+                    }
+                }
+                Insn::Return { src } => {
+                    let value = read!(src).clone();
+                    let Some(caller) = self.frames.pop() else {
+                        return Some(value);
+                    };
+                    self.regs[base - 1] = value;
+                    (code, closure, pc, base) =
+                        (caller.code, caller.closure, caller.pc, caller.base);
+                }
+                Insn::ReturnNull => {
+                    let Some(caller) = self.frames.pop() else {
+                        return Some(null);
+                    };
+                    self.regs[base - 1] = null.clone();
+                    (code, closure, pc, base) =
+                        (caller.code, caller.closure, caller.pc, caller.base);
+                }
+                Insn::Fallback => return None,
+            }
+        }
+    }
+}
+
+fn compare(op: Opcode, a: &i64, b: &i64) -> bool {
+    match op {
+        Opcode::Eq => a == b,
+        Opcode::Ne => a != b,
+        Opcode::Lt => a < b,
+        Opcode::Gt => a > b,
+        Opcode::Le => a <= b,
+        _ => a >= b,
+    }
+}
+
+/// `==` / `!=` for operands that only support equality.
+fn equality(op: Opcode, equal: bool) -> Option<bool> {
+    match op {
+        Opcode::Eq => Some(equal),
+        Opcode::Ne => Some(!equal),
+        _ => None,
+    }
+}
+
+fn bool_ref(value: bool, true_obj: &ObjectRef, false_obj: &ObjectRef) -> ObjectRef {
+    if value {
+        true_obj.clone()
+    } else {
+        false_obj.clone()
+    }
+}
@@ -1,14 +1,25 @@
 use std::collections::BTreeSet;
+use std::env;
+use std::fs::{self, OpenOptions};
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
-use crate::ast::Statement;
+use crate::ast::{Expression, Statement};
+use crate::builtins::{builtin_doc, builtin_names};
 use crate::compiler::CompileError;
-use crate::lexer::Lexer;
-use crate::object::ObjectRef;
+use crate::format::FormatOptions;
+use crate::highlight::{highlight, paint, paint_value, paint_value_pretty, Style};
+use crate::lexer::{Lexer, LexerOptions};
+use crate::object::{Object, ObjectRef};
 use crate::parse_error::ParseError;
 use crate::parser::Parser;
-use crate::runner::{dump_ast, format_tokens, run_source, RunnerError};
+use crate::position::Position;
+use crate::pretty::{format_value_pretty, AstFormat, ValueLayout};
+use crate::runner::{dump_ast, dump_ast_as, format_tokens, Runner, RunnerError};
 use crate::runtime_error::RuntimeError;
+use crate::source::load_source;
+use crate::token::TokenKind;
 
 const MONKEY_FACE: &str = "            __,____\n   .--.  .-\"     \"-.  .--.\n  / .. \\/  .-. .-.  \\/ .. \\\n | |  '|  /   Y   \\  |'  | |\n | \\   \\  \\ 0 | 0 /  /   / |\n  \\ '- ,\\.-\"`` ``\"-./, -' /\n   `'-' /_   ^ ^   _\\ '-'`\n       |  \\._   _./  |\n       \\   \\ `~` /   /\n        '._ '-=-' _.'\n           '-----'";
 
@@ -20,19 +31,116 @@ pub enum ReplEvalResult {
         output: Vec<String>,
     },
     ParseErrors(Vec<ParseError>),
-    CompileError(CompileError),
+    CompileErrors(Vec<CompileError>),
     RuntimeError(RuntimeError),
     MetaOutput(String),
     ExitRequested,
 }
 
-/// Stateful REPL session that preserves definitions across inputs.
+impl ReplEvalResult {
+    /// The result as the REPL golden fixtures show it, e.g. `RESULT: 3`,
+    /// or `PUTS:` lines followed by the result.
+    pub fn render(&self) -> String {
+        match self {
+            ReplEvalResult::Empty => "(empty)".to_string(),
+            ReplEvalResult::Value { result, output } => {
+                if output.is_empty() {
+                    format!("RESULT: {}", result.inspect())
+                } else {
+                    format!("PUTS:\n{}\nRESULT: {}", output.join("\n"), result.inspect())
+                }
+            }
+            ReplEvalResult::ParseErrors(errors) => {
+                format!("PARSE_ERROR:\n{}", format_parse_errors(errors))
+            }
+            ReplEvalResult::CompileErrors(errors) => {
+                let rendered = errors
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                format!("COMPILE_ERROR:\n{rendered}")
+            }
+            ReplEvalResult::RuntimeError(err) => {
+                format!("RUNTIME_ERROR:\n{}", err.format_multiline())
+            }
+            ReplEvalResult::MetaOutput(text) => format!("META:\n{text}"),
+            ReplEvalResult::ExitRequested => "EXIT".to_string(),
+        }
+    }
+}
+
+/// A line given to `ReplSession::eval_line` and its rendered result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscriptEntry {
+    /// The line, trimmed.
+    pub input: String,
+    /// `ReplEvalResult::render` of what the line evaluated to.
+    pub output: String,
+}
+
+/// `entries` in the format of the REPL golden fixtures: an `INPUT:` line
+/// and an `OUTPUT:` block per entry, separated by blank lines.
+pub fn render_transcript(entries: &[TranscriptEntry]) -> String {
+    entries
+        .iter()
+        .map(|entry| format!("INPUT: {}\nOUTPUT:\n{}", entry.input, entry.output))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Source of the lines `ReplSession::run` evaluates.
+pub trait LineReader {
+    /// Show `prompt` and read the next line, without its line ending;
+    /// `None` at the end of input.
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>>;
+
+    /// Offer `line` for recall by later reads. Readers without a history
+    /// ignore it.
+    fn add_history(&mut self, _line: &str) {}
+}
+
+/// Lines from stdin with the prompt printed to stdout, as the Java REPL
+/// reads them.
+#[derive(Debug, Default)]
+pub struct StdinReader;
+
+impl LineReader for StdinReader {
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        print!("{prompt}");
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(input))
+    }
+}
+
+/// Stateful REPL session that preserves definitions across inputs. Inputs
+/// are evaluated by a `Runner`, so earlier inputs are never compiled or
+/// executed again.
 #[derive(Debug, Default)]
 pub struct ReplSession {
     history: Vec<String>,
+    /// This session's successful inputs in full, including files run by
+    /// `:load`, for `:save`.
+    inputs: Vec<String>,
     bindings: BTreeSet<String>,
     pending_lines: Vec<String>,
-    history_output_len: usize,
+    /// Set by `:paste`: lines are collected as they are until `:end`.
+    pasting: bool,
+    runner: Runner,
+    /// Layout for `:ast`; `None` prints the one-line `format_ast`.
+    ast_layout: Option<FormatOptions>,
+    /// File that successful inputs are appended to.
+    history_file: Option<PathBuf>,
+    /// Whether `run_stdio` colors results, errors and `:ast` output.
+    color: bool,
+    /// Layout of the results `run_stdio` prints; `None` prints `inspect()`.
+    value_layout: Option<ValueLayout>,
+    /// Every non-blank line evaluated, kept across `:reset`.
+    transcript: Vec<TranscriptEntry>,
 }
 
 impl ReplSession {
@@ -40,102 +148,325 @@ impl ReplSession {
         Self::default()
     }
 
+    /// Print `:ast` output with `format_ast_pretty` instead of on one line.
+    pub fn with_ast_layout(mut self, options: FormatOptions) -> Self {
+        self.ast_layout = Some(options);
+        self
+    }
+
+    /// Color what `run_stdio` prints. Meant for terminals only; the
+    /// `ReplEvalResult`s themselves are never colored.
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Print results with `format_value_pretty`, as `:pretty on` does.
+    pub fn with_value_layout(mut self, layout: ValueLayout) -> Self {
+        self.value_layout = Some(layout);
+        self
+    }
+
+    /// Start from the inputs saved in `path`, one per line, and append
+    /// each later successful input to it. A missing file starts an empty
+    /// history; failures to read or write the file are ignored, since
+    /// history is a convenience.
+    pub fn with_history_file(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        if let Ok(saved) = fs::read_to_string(&path) {
+            for line in saved.lines() {
+                self.push_history(line);
+            }
+        }
+        self.history_file = Some(path);
+        self
+    }
+
+    /// Bind `name` to `value` for later inputs, as if by a `let`. The
+    /// value comes from the host, so `:save` cannot write it out.
+    pub fn define(&mut self, name: &str, value: ObjectRef) {
+        self.runner.define(name, value);
+        self.bindings.insert(name.to_string());
+    }
+
+    /// `define` for each of `bindings`, in order.
+    pub fn define_all<S: AsRef<str>>(
+        &mut self,
+        bindings: impl IntoIterator<Item = (S, ObjectRef)>,
+    ) {
+        for (name, value) in bindings {
+            self.define(name.as_ref(), value);
+        }
+    }
+
+    /// `define`, as a builder.
+    pub fn with_binding(mut self, name: &str, value: ObjectRef) -> Self {
+        self.define(name, value);
+        self
+    }
+
+    /// Run the startup file at `path` line by line, as if typed, without
+    /// adding its lines to the history. Meta commands such as `:pretty on`
+    /// work, and their output is dropped. Returns a message for each input
+    /// that failed; the session keeps what the others defined.
+    pub fn run_rc_file(&mut self, path: &Path) -> io::Result<Vec<String>> {
+        let source = fs::read_to_string(path)?;
+        let history_file = self.history_file.take();
+        let history_len = self.history.len();
+        let transcript_len = self.transcript.len();
+        let mut failures = Vec::new();
+        let mut start = 1;
+        for (index, line) in source.lines().enumerate() {
+            let rendered = match self.eval_line(line) {
+                ReplEvalResult::Empty => {
+                    if self.pending_lines.is_empty() && !self.pasting {
+                        start = index + 2;
+                    }
+                    continue;
+                }
+                ReplEvalResult::ExitRequested => break,
+                ReplEvalResult::Value { .. } | ReplEvalResult::MetaOutput(_) => None,
+                ReplEvalResult::ParseErrors(errors) => Some(format_parse_errors(&errors)),
+                ReplEvalResult::CompileErrors(errors) => Some(format_compile_errors(&errors)),
+                ReplEvalResult::RuntimeError(err) => Some(err.format_multiline()),
+            };
+            if let Some(rendered) = rendered {
+                failures.push(format!(
+                    "Error in {} at line {start}:\n{rendered}",
+                    path.display()
+                ));
+            }
+            start = index + 2;
+        }
+        if !self.pending_lines.is_empty() || self.pasting {
+            failures.push(format!(
+                "Error in {} at line {start}: unfinished input at end of file",
+                path.display()
+            ));
+            self.pending_lines.clear();
+            self.pasting = false;
+        }
+        self.history.truncate(history_len);
+        self.transcript.truncate(transcript_len);
+        self.history_file = history_file;
+        Ok(failures)
+    }
+
+    /// Successful inputs in order, one entry per line, without
+    /// consecutive repeats.
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// The history line `line` asks to run again, if it is a recall: `!!`
+    /// for the last entry or `!N` for entry N as numbered by `:history`.
+    /// Recalls are only recognized at the start of an input; `Err` holds
+    /// the message for an entry that does not exist.
+    pub fn recall(&self, line: &str) -> Option<Result<String, String>> {
+        let line = line.trim();
+        if self.pasting || !self.pending_lines.is_empty() {
+            return None;
+        }
+        let entry = if line == "!!" {
+            self.history.last()
+        } else {
+            let number = line.strip_prefix('!')?;
+            if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            number
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| self.history.get(n.checked_sub(1)?))
+        };
+        Some(
+            entry
+                .cloned()
+                .ok_or_else(|| format!("No history entry {line}")),
+        )
+    }
+
+    /// The lines evaluated so far with their rendered results, except
+    /// blank lines and those of the startup file.
+    pub fn transcript(&self) -> &[TranscriptEntry] {
+        &self.transcript
+    }
+
     pub fn eval_line(&mut self, line: &str) -> ReplEvalResult {
+        let result = self.eval_line_unrecorded(line);
+        let input = line.trim();
+        if !input.is_empty() {
+            self.transcript.push(TranscriptEntry {
+                input: input.to_string(),
+                output: result.render(),
+            });
+        }
+        result
+    }
+
+    fn eval_line_unrecorded(&mut self, line: &str) -> ReplEvalResult {
         let raw = line.trim_end_matches(['\n', '\r']);
         let trimmed = raw.trim();
 
+        if self.pasting && trimmed != ":reset" {
+            if trimmed != ":end" {
+                self.pending_lines.push(raw.to_string());
+                return ReplEvalResult::Empty;
+            }
+            self.pasting = false;
+            if self.pending_lines.iter().all(|line| line.trim().is_empty()) {
+                self.pending_lines.clear();
+                return ReplEvalResult::Empty;
+            }
+            return self.eval_pending();
+        }
+
         if trimmed.is_empty() {
             return ReplEvalResult::Empty;
         }
 
-        if self.pending_lines.is_empty() && trimmed.starts_with(':') {
+        match self.recall(trimmed) {
+            Some(Ok(entry)) => self.eval_input_line(&entry),
+            Some(Err(message)) => ReplEvalResult::MetaOutput(message),
+            None => self.eval_input_line(raw),
+        }
+    }
+
+    /// `eval_line` for a line that is not a recall, so that a recalled
+    /// entry looking like one runs as code.
+    fn eval_input_line(&mut self, raw: &str) -> ReplEvalResult {
+        let trimmed = raw.trim();
+        // `:reset` also abandons an unfinished input, which would otherwise
+        // take the line as its continuation.
+        if trimmed == ":reset" || (self.pending_lines.is_empty() && trimmed.starts_with(':')) {
             return self.eval_meta(trimmed);
         }
 
         self.pending_lines.push(raw.to_string());
-        let pending_source = self.pending_lines.join("\n");
-        if !Self::is_complete_source(&pending_source) {
+        if Self::needs_more_input(&self.pending_lines.join("\n")) {
             return ReplEvalResult::Empty;
         }
+        self.eval_pending()
+    }
 
-        let mut all = self.history.clone();
-        all.extend(self.pending_lines.iter().cloned());
+    /// Run the pending lines as one input and clear them.
+    fn eval_pending(&mut self) -> ReplEvalResult {
+        let pending_source = self.pending_lines.join("\n");
+        let mut source = pending_source.clone();
         if let Some(name) = self.single_let_binding_name(&pending_source) {
-            all.push(format!("{name};"));
+            source.push_str(&format!("\n{name};"));
         }
-        let source = all.join("\n");
 
-        let result = match run_source(&source) {
-            Ok(outcome) => {
-                let total_output_len = outcome.output.len();
-                let new_output = if self.history_output_len <= total_output_len {
-                    outcome.output[self.history_output_len..].to_vec()
-                } else {
-                    outcome.output.clone()
-                };
-                self.history_output_len = total_output_len;
-                self.history.extend(self.pending_lines.iter().cloned());
-                self.remember_bindings_from_source(&pending_source);
-                ReplEvalResult::Value {
-                    result: outcome.result,
-                    output: new_output,
-                }
+        let result = self.eval_source(&source);
+        if matches!(result, ReplEvalResult::Value { .. }) {
+            let lines = std::mem::take(&mut self.pending_lines);
+            let added = lines
+                .iter()
+                .filter(|line| !line.trim().is_empty() && self.push_history(line))
+                .count();
+            if let Some(path) = &self.history_file {
+                // Best effort: the session goes on without its history file.
+                let _ = append_history(path, &self.history[self.history.len() - added..]);
             }
-            Err(RunnerError::Parse(errors)) => ReplEvalResult::ParseErrors(errors),
-            Err(RunnerError::Compile(err)) => ReplEvalResult::CompileError(err),
-            Err(RunnerError::Runtime(err)) => ReplEvalResult::RuntimeError(err),
-        };
+            self.remember_bindings_from_source(&pending_source);
+            self.inputs.push(pending_source);
+        }
 
         self.pending_lines.clear();
         result
     }
 
+    /// Compile `source` on top of the earlier inputs and run it. An input
+    /// that fails to compile or run leaves no definitions behind.
+    fn eval_source(&mut self, source: &str) -> ReplEvalResult {
+        match self.runner.eval(source) {
+            Ok(outcome) => ReplEvalResult::Value {
+                result: outcome.result,
+                output: outcome.output,
+            },
+            Err(err) => runner_error_result(err),
+        }
+    }
+
     pub fn run_stdio(&mut self) -> i32 {
-        let stdin = io::stdin();
-        let mut input = String::new();
+        self.run(&mut StdinReader)
+    }
 
+    /// Read lines from `reader` and print what each evaluates to, until
+    /// the input ends or `:quit`. Returns the process exit code.
+    pub fn run(&mut self, reader: &mut dyn LineReader) -> i32 {
         loop {
-            print!(">> ");
-            if io::stdout().flush().is_err() {
-                return 1;
-            }
-
-            input.clear();
-            let read = match stdin.read_line(&mut input) {
-                Ok(n) => n,
+            let input = match reader.read_line(">> ") {
+                Ok(Some(input)) => input,
+                Ok(None) => return 0,
                 Err(_) => return 1,
             };
-            if read == 0 {
-                return 0;
+            let line = input.trim_end_matches(['\n', '\r']);
+            match self.recall(line) {
+                Some(Ok(entry)) => {
+                    println!("{entry}");
+                    reader.add_history(&entry);
+                }
+                _ if !line.trim().is_empty() => reader.add_history(line),
+                _ => {}
             }
-
-            match self.eval_line(input.trim_end_matches(['\n', '\r'])) {
+            match self.eval_line(line) {
                 ReplEvalResult::Empty => {}
                 ReplEvalResult::Value { result, output } => {
                     for line in output {
                         println!("{line}");
                     }
-                    println!("{}", result.inspect());
+                    println!("{}", self.render_result(&result));
                 }
                 ReplEvalResult::ParseErrors(errors) => {
-                    println!("{}", format_parse_errors(&errors));
+                    let text = format_parse_errors(&errors);
+                    if self.color {
+                        let (face, rest) = text.split_at(MONKEY_FACE.len());
+                        println!(
+                            "{}{}",
+                            paint(face, Style::Banner),
+                            paint(rest, Style::Error)
+                        );
+                    } else {
+                        println!("{text}");
+                    }
                 }
-                ReplEvalResult::CompileError(err) => {
-                    println!("Compile error:");
-                    println!("{err}");
+                ReplEvalResult::CompileErrors(errors) => {
+                    println!("{}", self.paint_error(&format_compile_errors(&errors)));
                 }
                 ReplEvalResult::RuntimeError(err) => {
-                    println!("{}", err.format_multiline());
+                    println!("{}", self.paint_error(&err.format_multiline()));
                 }
                 ReplEvalResult::MetaOutput(text) => {
-                    println!("{text}");
+                    if self.color && line.split_whitespace().next() == Some(":ast") {
+                        println!("{}", highlight(&text));
+                    } else {
+                        println!("{text}");
+                    }
                 }
                 ReplEvalResult::ExitRequested => return 0,
             }
         }
     }
 
-    fn eval_meta(&self, line: &str) -> ReplEvalResult {
+    fn render_result(&self, result: &Object) -> String {
+        match (self.value_layout, self.color) {
+            (Some(layout), true) => paint_value_pretty(result, layout),
+            (Some(layout), false) => format_value_pretty(result, layout),
+            (None, true) => paint_value(result),
+            (None, false) => result.inspect(),
+        }
+    }
+
+    fn paint_error(&self, text: &str) -> String {
+        if self.color {
+            paint(text, Style::Error)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn eval_meta(&mut self, line: &str) -> ReplEvalResult {
         let raw = &line[1..];
         let mut parts = raw.splitn(2, char::is_whitespace);
         let cmd = parts.next().unwrap_or_default();
@@ -171,8 +502,17 @@ impl ReplSession {
                 if src.is_empty() {
                     ReplEvalResult::MetaOutput("AST:\n  (no input)".to_string())
                 } else {
-                    match dump_ast(src) {
-                        Ok(ast) => ReplEvalResult::MetaOutput(format!("AST:\n  {ast}")),
+                    let ast = match self.ast_layout {
+                        Some(options) => dump_ast_as(src, AstFormat::Pretty(options)).map(|ast| {
+                            ast.lines()
+                                .map(|l| format!("  {l}"))
+                                .collect::<Vec<_>>()
+                                .join("\n")
+                        }),
+                        None => dump_ast(src).map(|ast| format!("  {ast}")),
+                    };
+                    match ast {
+                        Ok(ast) => ReplEvalResult::MetaOutput(format!("AST:\n{ast}")),
                         Err(errors) => {
                             let body = errors
                                 .iter()
@@ -184,12 +524,258 @@ impl ReplSession {
                     }
                 }
             }
+            "disasm" => {
+                let src = if arg.is_empty() {
+                    self.history.last().map(String::as_str).unwrap_or("")
+                } else {
+                    arg
+                };
+                if src.is_empty() {
+                    ReplEvalResult::MetaOutput("DISASM:\n  (no input)".to_string())
+                } else {
+                    let src = src.to_string();
+                    self.disasm(&src)
+                }
+            }
+            "doc" => ReplEvalResult::MetaOutput(if arg.is_empty() {
+                format!("Builtins: {}", builtin_names().join(", "))
+            } else {
+                match builtin_doc(arg) {
+                    Some(doc) => format!("{}\n  {}", doc.signature, doc.summary),
+                    None => format!("No documentation for {arg}"),
+                }
+            }),
             "env" => ReplEvalResult::MetaOutput(self.render_env()),
+            "history" => ReplEvalResult::MetaOutput(self.render_history(arg)),
+            "export" => self.export(arg),
+            "load" => self.load(arg),
+            "reset" => {
+                self.reset();
+                ReplEvalResult::MetaOutput("Session reset".to_string())
+            }
+            "paste" => {
+                self.pasting = true;
+                ReplEvalResult::MetaOutput(
+                    "Paste mode: lines are run together after a line with :end".to_string(),
+                )
+            }
+            "pretty" => self.pretty(arg),
+            "save" => self.save(arg),
+            "time" => self.time(arg),
+            "type" => self.type_of(arg),
             "quit" | "exit" => ReplEvalResult::ExitRequested,
             _ => ReplEvalResult::MetaOutput(format!("Unknown command: :{cmd}")),
         }
     }
 
+    /// `:reset`: forget every input and definition, keeping the session's
+    /// settings and its history file.
+    fn reset(&mut self) {
+        *self = Self {
+            ast_layout: self.ast_layout,
+            history_file: self.history_file.take(),
+            color: self.color,
+            value_layout: self.value_layout,
+            transcript: std::mem::take(&mut self.transcript),
+            ..Self::default()
+        };
+    }
+
+    /// `:load <path>`: run the file in the session as one input, keeping
+    /// its bindings when it succeeds.
+    fn load(&mut self, path: &str) -> ReplEvalResult {
+        if path.is_empty() {
+            return ReplEvalResult::MetaOutput("Usage: :load <path>".to_string());
+        }
+        let source = match load_source(Path::new(path)) {
+            Ok(source) => source,
+            Err(err) => return ReplEvalResult::MetaOutput(format!("Failed to read {path}: {err}")),
+        };
+        let result = self.eval_source(&source);
+        if matches!(result, ReplEvalResult::Value { .. }) {
+            self.remember_bindings_from_source(&source);
+            self.inputs.push(source.trim_end().to_string());
+        }
+        result
+    }
+
+    /// `:pretty [on|off|width <n>|items <n|all>]`: change how results are
+    /// laid out, then show the current setting.
+    fn pretty(&mut self, arg: &str) -> ReplEvalResult {
+        let mut parts = arg.split_whitespace();
+        let layout = self.value_layout.unwrap_or_default();
+        let number = |value: Option<&str>| value.and_then(|v| v.parse::<usize>().ok());
+        let updated = match (parts.next(), parts.next(), parts.next()) {
+            (None, _, _) => Some(self.value_layout),
+            (Some("on"), None, _) => Some(Some(layout)),
+            (Some("off"), None, _) => Some(None),
+            (Some("width"), width, None) => {
+                number(width).map(|width| Some(layout.with_max_width(width)))
+            }
+            (Some("items"), Some("all"), None) => Some(Some(layout.with_max_items(None))),
+            (Some("items"), items, None) => {
+                number(items).map(|items| Some(layout.with_max_items(Some(items))))
+            }
+            _ => None,
+        };
+        let Some(updated) = updated else {
+            return ReplEvalResult::MetaOutput(
+                "Usage: :pretty [on|off|width <n>|items <n|all>]".to_string(),
+            );
+        };
+        self.value_layout = updated;
+        ReplEvalResult::MetaOutput(match updated {
+            None => "Pretty results: off".to_string(),
+            Some(layout) => format!(
+                "Pretty results: width {}, items {}",
+                layout.max_width,
+                layout
+                    .max_items
+                    .map_or("all".to_string(), |items| items.to_string())
+            ),
+        })
+    }
+
+    /// `:export <path>`: write the transcript so far in the format of the
+    /// REPL golden fixtures.
+    fn export(&self, path: &str) -> ReplEvalResult {
+        if path.is_empty() {
+            return ReplEvalResult::MetaOutput("Usage: :export <path>".to_string());
+        }
+        let rendered = format!("{}\n", render_transcript(&self.transcript));
+        match fs::write(path, rendered) {
+            Ok(()) => ReplEvalResult::MetaOutput(format!(
+                "Exported {} entr{} to {path}",
+                self.transcript.len(),
+                if self.transcript.len() == 1 {
+                    "y"
+                } else {
+                    "ies"
+                }
+            )),
+            Err(err) => ReplEvalResult::MetaOutput(format!("Failed to write {path}: {err}")),
+        }
+    }
+
+    /// `:save <path>`: write this session's successful inputs to `path` as
+    /// a script that recreates its state.
+    fn save(&self, path: &str) -> ReplEvalResult {
+        if path.is_empty() {
+            return ReplEvalResult::MetaOutput("Usage: :save <path>".to_string());
+        }
+        let script: String = self
+            .inputs
+            .iter()
+            .map(|input| format!("{}\n", terminate_statement(input)))
+            .collect();
+        match fs::write(path, script) {
+            Ok(()) => ReplEvalResult::MetaOutput(format!(
+                "Saved {} input{} to {path}",
+                self.inputs.len(),
+                if self.inputs.len() == 1 { "" } else { "s" }
+            )),
+            Err(err) => ReplEvalResult::MetaOutput(format!("Failed to write {path}: {err}")),
+        }
+    }
+
+    /// `:disasm`: the bytecode `source` compiles to on top of the earlier
+    /// inputs, without running it or keeping its definitions.
+    fn disasm(&mut self, source: &str) -> ReplEvalResult {
+        match self.runner.preview(source) {
+            Ok(chunk) => {
+                let body = chunk
+                    .disassemble()
+                    .lines()
+                    .map(|l| {
+                        if l.is_empty() {
+                            String::new()
+                        } else {
+                            format!("  {l}")
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                ReplEvalResult::MetaOutput(format!("DISASM:\n{body}"))
+            }
+            Err(err) => runner_error_result(err),
+        }
+    }
+
+    /// `:time <input>`: run the input in the session like any other, then
+    /// report how long it took and how many instructions it executed.
+    fn time(&mut self, input: &str) -> ReplEvalResult {
+        if input.is_empty() {
+            return ReplEvalResult::MetaOutput("Usage: :time <input>".to_string());
+        }
+        let executed_before = self.runner.vm().instructions_executed();
+        let mut source = input.to_string();
+        if let Some(name) = self.single_let_binding_name(input) {
+            source.push_str(&format!("\n{name};"));
+        }
+        let started = Instant::now();
+        let result = self.eval_source(&source);
+        let ms = started.elapsed().as_secs_f64() * 1000.0;
+        let ReplEvalResult::Value { result, output } = result else {
+            return result;
+        };
+        self.remember_bindings_from_source(input);
+        self.inputs.push(input.to_string());
+
+        let executed = self.runner.vm().instructions_executed() - executed_before;
+        let mut lines = output;
+        lines.push(result.inspect());
+        lines.push(format!("Time: {ms:.3} ms, {executed} instructions"));
+        ReplEvalResult::MetaOutput(lines.join("\n"))
+    }
+
+    /// `:type <expression>`: the object type of the expression's value.
+    /// Literals and builtins are typed without running anything; other
+    /// expressions are evaluated in the session.
+    fn type_of(&mut self, source: &str) -> ReplEvalResult {
+        let mut parser = Parser::new(Lexer::new(source));
+        let program = parser.parse_program();
+        if !parser.errors().is_empty() {
+            return ReplEvalResult::ParseErrors(parser.errors().to_vec());
+        }
+        let [Statement::Expression { expression, .. }] = program.statements.as_slice() else {
+            return ReplEvalResult::MetaOutput("Usage: :type <expression>".to_string());
+        };
+        let static_type = match expression {
+            Expression::IntegerLiteral { .. } => Some("INTEGER"),
+            Expression::BooleanLiteral { .. } => Some("BOOLEAN"),
+            Expression::StringLiteral { .. } => Some("STRING"),
+            Expression::FunctionLiteral { .. } => Some("CLOSURE"),
+            Expression::Identifier { value, .. }
+                if self.runner.compiler().global_index(value).is_none()
+                    && builtin_names().contains(&value.as_str()) =>
+            {
+                Some("BUILTIN")
+            }
+            _ => None,
+        };
+        if let Some(name) = static_type {
+            return ReplEvalResult::MetaOutput(format!("TYPE: {name}"));
+        }
+        match self.eval_source(source) {
+            ReplEvalResult::Value { result, output } => {
+                let mut lines = output;
+                lines.push(format!("TYPE: {}", result.type_name()));
+                ReplEvalResult::MetaOutput(lines.join("\n"))
+            }
+            other => other,
+        }
+    }
+
+    /// Record `line` unless it repeats the previous entry; returns whether
+    /// it was recorded.
+    fn push_history(&mut self, line: &str) -> bool {
+        if self.history.last().is_some_and(|last| last == line) {
+            return false;
+        }
+        self.history.push(line.to_string());
+        true
+    }
+
     fn remember_bindings_from_source(&mut self, source: &str) {
         let mut parser = Parser::new(Lexer::new(source));
         let program = parser.parse_program();
@@ -217,6 +803,29 @@ impl ReplSession {
         }
     }
 
+    /// `:history [count]`: the last `count` entries, or all of them,
+    /// numbered for `!N`.
+    fn render_history(&self, count: &str) -> String {
+        let count = if count.is_empty() {
+            Some(self.history.len())
+        } else {
+            count.parse::<usize>().ok()
+        };
+        let Some(count) = count else {
+            return "Usage: :history [count]".to_string();
+        };
+        if self.history.is_empty() {
+            return "HISTORY:\n  (empty)".to_string();
+        }
+        let first = self.history.len().saturating_sub(count);
+        let width = self.history.len().to_string().len();
+        let mut lines = vec!["HISTORY:".to_string()];
+        for (index, entry) in self.history.iter().enumerate().skip(first) {
+            lines.push(format!("  {:>width$}  {entry}", index + 1));
+        }
+        lines.join("\n")
+    }
+
     fn render_env(&self) -> String {
         if self.bindings.is_empty() {
             return "ENV:\n  (empty)".to_string();
@@ -231,68 +840,119 @@ impl ReplSession {
     }
 
     fn resolve_binding_value(&self, name: &str) -> String {
-        let mut all = self.history.clone();
-        all.push(format!("{name};"));
-        match run_source(&all.join("\n")) {
-            Ok(outcome) => outcome.result.inspect(),
-            Err(RunnerError::Parse(errs)) => format!("<parse error: {}>", errs.len()),
-            Err(RunnerError::Compile(err)) => format!("<compile error: {err}>"),
-            Err(RunnerError::Runtime(err)) => {
-                format!("<runtime error: {}>", err.error_type.code())
-            }
-        }
+        self.runner
+            .global(name)
+            .map(|value| value.inspect())
+            .unwrap_or_else(|| "null".to_string())
     }
 
-    fn is_complete_source(source: &str) -> bool {
-        let mut paren = 0i32;
-        let mut brace = 0i32;
-        let mut bracket = 0i32;
-        let mut in_string = false;
+    /// Whether `source` is the start of an input rather than a finished
+    /// one: it ends inside a string or an unclosed block, or it fails to
+    /// parse only because the input ran out. Any other parse error means
+    /// the input is complete and wrong.
+    fn needs_more_input(source: &str) -> bool {
+        let tokens = Lexer::new(source).tokenize_all(LexerOptions::default());
+        let Some(eof) = tokens.last().map(|token| token.pos) else {
+            return false;
+        };
+        let starts_string = |pos: Position| {
+            source
+                .lines()
+                .nth(pos.line.saturating_sub(1))
+                .and_then(|line| line.chars().nth(pos.col.saturating_sub(1)))
+                == Some('"')
+        };
+        let last = tokens.len().checked_sub(2).map(|index| &tokens[index]);
+        if last.is_some_and(|token| token.kind == TokenKind::Illegal && starts_string(token.pos)) {
+            return true;
+        }
 
-        for line in source.lines() {
-            let mut chars = line.chars().peekable();
-            while let Some(ch) = chars.next() {
-                if in_string {
-                    if ch == '"' {
-                        in_string = false;
-                    }
-                    continue;
-                }
+        let mut parser = Parser::new(Lexer::new(source));
+        parser.parse_program();
+        if !parser.errors().is_empty() {
+            return parser.errors().iter().all(|err| err.pos == eof);
+        }
+        // Like the Java parser, ours accepts a block cut off by the end of
+        // the input, so an unclosed `{` has to be spotted here.
+        let depth: i32 = tokens
+            .iter()
+            .map(|token| match token.kind {
+                TokenKind::LBrace => 1,
+                TokenKind::RBrace => -1,
+                _ => 0,
+            })
+            .sum();
+        depth > 0
+    }
+}
 
-                if ch == '#' {
-                    break;
-                }
+/// Where the `monkey` REPL keeps its history: `$MONKEY_HISTORY` if set,
+/// else `.monkey_history` in the home directory. An empty
+/// `MONKEY_HISTORY` turns the history file off.
+pub fn default_history_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("MONKEY_HISTORY") {
+        return (!path.is_empty()).then(|| PathBuf::from(path));
+    }
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+    Some(Path::new(&home).join(".monkey_history"))
+}
 
-                match ch {
-                    '"' => in_string = true,
-                    '(' => paren += 1,
-                    ')' => {
-                        paren -= 1;
-                        if paren < 0 {
-                            return true;
-                        }
-                    }
-                    '{' => brace += 1,
-                    '}' => {
-                        brace -= 1;
-                        if brace < 0 {
-                            return true;
-                        }
-                    }
-                    '[' => bracket += 1,
-                    ']' => {
-                        bracket -= 1;
-                        if bracket < 0 {
-                            return true;
-                        }
-                    }
-                    _ => {}
-                }
+/// The REPL's startup file: `$MONKEY_RC` if set, else `.monkeyrc` in the
+/// home directory. An empty `MONKEY_RC` turns it off.
+pub fn default_rc_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("MONKEY_RC") {
+        return (!path.is_empty()).then(|| PathBuf::from(path));
+    }
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+    Some(Path::new(&home).join(".monkeyrc"))
+}
+
+/// `input` ending in a `;`, so that a following input starting with `(`
+/// or `[` is not read as a call or index of its last expression.
+fn terminate_statement(input: &str) -> String {
+    let tokens = Lexer::new(input).tokenize_all(LexerOptions::default());
+    let last = tokens
+        .iter()
+        .rev()
+        .find(|token| token.kind != TokenKind::Eof);
+    match last {
+        Some(token) if token.kind != TokenKind::Semicolon => {
+            // A `;` after a trailing comment would be commented out.
+            if input.lines().last().is_some_and(|line| line.contains('#')) {
+                format!("{input}\n;")
+            } else {
+                format!("{input};")
             }
         }
+        _ => input.to_string(),
+    }
+}
+
+fn append_history(path: &Path, lines: &[String]) -> io::Result<()> {
+    if lines.is_empty() {
+        return Ok(());
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    for line in lines {
+        writeln!(file, "{line}")?;
+    }
+    Ok(())
+}
+
+fn runner_error_result(err: RunnerError) -> ReplEvalResult {
+    match err {
+        RunnerError::Parse(errors) => ReplEvalResult::ParseErrors(errors),
+        RunnerError::Compile(errors) => ReplEvalResult::CompileErrors(errors),
+        RunnerError::Runtime(err) => ReplEvalResult::RuntimeError(err),
+    }
+}
 
-        !in_string && paren == 0 && brace == 0 && bracket == 0
+fn format_compile_errors(errors: &[CompileError]) -> String {
+    let mut text = "Compile error:".to_string();
+    for err in errors {
+        text.push_str(&format!("\n{err}"));
     }
+    text
 }
 
 pub fn format_parse_errors(errors: &[ParseError]) -> String {
@@ -1,54 +1,465 @@
-use crate::compiler::{CompileError, Compiler};
-use crate::lexer::Lexer;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::time::{Duration, Instant};
+
+use crate::ast::Program;
+use crate::bytecode::Chunk;
+use crate::compiler::{CompileError, CompileWarning, Compiler, CompilerOptions};
+use crate::coverage::Coverage;
+use crate::lexer::{Lexer, LexerOptions};
 use crate::object::ObjectRef;
 use crate::parse_error::ParseError;
 use crate::parser::Parser;
+use crate::pretty::{format_ast_as, format_tokens_json, AstFormat, TokenFormat};
+use crate::profile::Profile;
 use crate::runtime_error::RuntimeError;
 use crate::token::Token;
-use crate::vm::Vm;
+use crate::vm::{OutputSink, TraceSink, Vm, VmOptions};
 
 #[derive(Debug, Clone)]
 pub struct RunOutcome {
     pub result: ObjectRef,
     pub output: Vec<String>,
+    /// Collected counters when the run was profiled.
+    pub profile: Option<Profile>,
+    /// Executed lines when the run had `VmOptions::coverage` set.
+    pub coverage: Option<Coverage>,
+    /// Compiler warnings for runs that started from source.
+    pub warnings: Vec<CompileWarning>,
+    /// What the run cost; `None` on the register VM, which does not
+    /// count it.
+    pub stats: Option<RunStats>,
+}
+
+/// Execution statistics of a stack VM run, printed by `run --stats`.
+/// Work done inside `eval` counts toward the run that called it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunStats {
+    pub instructions: u64,
+    /// Most values the VM stack held at once, locals included.
+    pub peak_stack_depth: usize,
+    /// Most call frames active at once, the top-level frame included.
+    pub peak_frame_depth: usize,
+    /// Strings, arrays and hashes created, and their approximate size.
+    pub allocations: u64,
+    pub allocated_bytes: usize,
+    /// Time spent executing, without parsing and compiling.
+    pub elapsed: Duration,
+}
+
+impl Display for RunStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        writeln!(f, "instructions: {}", self.instructions)?;
+        writeln!(f, "peak stack depth: {}", self.peak_stack_depth)?;
+        writeln!(f, "peak frame depth: {}", self.peak_frame_depth)?;
+        writeln!(
+            f,
+            "allocations: {} ({} bytes)",
+            self.allocations, self.allocated_bytes
+        )?;
+        write!(f, "elapsed: {:.3} ms", self.elapsed.as_secs_f64() * 1000.0)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum RunnerError {
     Parse(Vec<ParseError>),
-    Compile(CompileError),
+    Compile(Vec<CompileError>),
     Runtime(RuntimeError),
 }
 
+/// Configuration for `run_source_with` and `run_chunk_with`; the `run`
+/// command's flags map onto it.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Values returned by the `args()` builtin.
+    pub args: Vec<String>,
+    pub compiler: CompilerOptions,
+    /// Limits and execution settings, including the instruction budget
+    /// (fuel) and memory limit.
+    pub vm: VmOptions,
+    /// Wall-clock limit, after which the run fails with `TIMEOUT`.
+    pub timeout: Option<Duration>,
+    pub trace: Option<TraceSink>,
+    pub profile: bool,
+    /// Receives each printed line as it is printed; `RunOutcome::output`
+    /// is then empty.
+    pub output: Option<OutputSink>,
+    /// Globals bound before the program runs, in the first global slots.
+    pub globals: Vec<(String, ObjectRef)>,
+}
+
+impl RunOptions {
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    pub fn with_compiler(mut self, compiler: CompilerOptions) -> Self {
+        self.compiler = compiler;
+        self
+    }
+
+    /// Run the peephole optimizer over the compiled bytecode.
+    pub fn with_optimize(mut self, optimize: bool) -> Self {
+        self.compiler.optimize = optimize;
+        self
+    }
+
+    pub fn with_vm(mut self, vm: VmOptions) -> Self {
+        self.vm = vm;
+        self
+    }
+
+    /// Fail with `BUDGET_EXCEEDED` after `instructions` instructions.
+    pub fn with_fuel(mut self, instructions: u64) -> Self {
+        self.vm.max_instructions = Some(instructions);
+        self
+    }
+
+    pub fn with_max_memory_bytes(mut self, max_memory_bytes: usize) -> Self {
+        self.vm.max_memory_bytes = Some(max_memory_bytes);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_trace(mut self, trace: TraceSink) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
+    pub fn with_profile(mut self, profile: bool) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    pub fn with_output(mut self, output: OutputSink) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    /// Bind `name` to `value` for the program, like a `let` before it.
+    pub fn with_global(mut self, name: impl Into<String>, value: ObjectRef) -> Self {
+        self.globals.push((name.into(), value));
+        self
+    }
+}
+
 pub fn run_source(source: &str) -> Result<RunOutcome, RunnerError> {
+    run_source_with(source, &RunOptions::default())
+}
+
+/// Compile and run a program as configured by `options`. The outcome
+/// carries the compiler's warnings.
+pub fn run_source_with(source: &str, options: &RunOptions) -> Result<RunOutcome, RunnerError> {
+    let (chunk, warnings) = compile_source_for(source, options)?;
+    let outcome = run_chunk_with(chunk, options)?;
+    Ok(RunOutcome {
+        warnings,
+        ..outcome
+    })
+}
+
+/// Parse and compile a program with `options.compiler`, defining
+/// `options.globals` first so `run_chunk_with` can fill their slots.
+pub fn compile_source_for(
+    source: &str,
+    options: &RunOptions,
+) -> Result<(Chunk, Vec<CompileWarning>), RunnerError> {
+    let program = parse(source)?;
+    let mut compiler = Compiler::with_options(options.compiler);
+    for (name, _) in &options.globals {
+        compiler.define_global(name);
+    }
+    compiler
+        .compile_program(&program)
+        .map_err(RunnerError::Compile)?;
+    let warnings = compiler.warnings().to_vec();
+    Ok((compiler.into_bytecode(), warnings))
+}
+
+/// Parse and compile a program without running it.
+pub fn compile_source(source: &str) -> Result<Chunk, RunnerError> {
+    compile_source_with_options(source, CompilerOptions::default())
+}
+
+/// Parse and compile a program with the given compiler settings.
+pub fn compile_source_with_options(
+    source: &str,
+    options: CompilerOptions,
+) -> Result<Chunk, RunnerError> {
+    compile_source_with_warnings(source, options).map(|(chunk, _)| chunk)
+}
+
+/// Like `compile_source_with_options`, also returning the compiler's
+/// warnings (unused bindings, unreachable code) in source order.
+pub fn compile_source_with_warnings(
+    source: &str,
+    options: CompilerOptions,
+) -> Result<(Chunk, Vec<CompileWarning>), RunnerError> {
+    compile_source_for(source, &RunOptions::default().with_compiler(options))
+}
+
+fn parse(source: &str) -> Result<Program, RunnerError> {
     let mut parser = Parser::new(Lexer::new(source));
     let program = parser.parse_program();
     if !parser.errors().is_empty() {
         return Err(RunnerError::Parse(parser.errors().to_vec()));
     }
+    Ok(program)
+}
 
-    let mut compiler = Compiler::new();
-    compiler
-        .compile_program(&program)
-        .map_err(RunnerError::Compile)?;
+/// Compile source to a standalone WebAssembly module (see `crate::wasm`).
+pub fn compile_wasm(source: &str) -> Result<Vec<u8>, RunnerError> {
+    let program = parse(source)?;
+    crate::wasm::compile_program(&program).map_err(|err| RunnerError::Compile(vec![err]))
+}
 
-    let mut vm = Vm::new(compiler.into_bytecode());
-    let result = vm.run().map_err(RunnerError::Runtime)?;
+/// Run already-compiled bytecode, e.g. a chunk loaded from a `.mkc` file,
+/// as configured by `options`. Its globals go in the first global slots,
+/// where `compile_source_for` put them; `options.compiler` is not used.
+pub fn run_chunk_with(chunk: Chunk, options: &RunOptions) -> Result<RunOutcome, RunnerError> {
+    let mut vm = Vm::with_options(chunk, options.vm.clone());
+    vm.set_script_args(options.args.clone());
+    vm.set_trace(options.trace.clone());
+    vm.set_output_sink(options.output.clone());
+    vm.set_profiling(options.profile);
+    for (index, value) in global_slots(&options.globals) {
+        vm.set_global(index, value.clone());
+    }
+    let started = Instant::now();
+    let result = match options.timeout {
+        Some(timeout) => vm.run_with_deadline(timeout),
+        None => vm.run(),
+    }
+    .map_err(RunnerError::Runtime)?;
+    let elapsed = started.elapsed();
     let output = vm.take_output();
-    Ok(RunOutcome { result, output })
+    Ok(RunOutcome {
+        result,
+        output,
+        profile: vm.profile().cloned(),
+        coverage: vm.coverage().cloned(),
+        warnings: Vec::new(),
+        stats: Some(RunStats {
+            instructions: vm.instructions_executed(),
+            peak_stack_depth: vm.peak_stack_depth(),
+            peak_frame_depth: vm.peak_frame_depth(),
+            allocations: vm.allocations(),
+            allocated_bytes: vm.allocated_bytes(),
+            elapsed,
+        }),
+    })
+}
+
+/// The slot `Compiler::define_global` gives each of `globals` when they are
+/// defined in order: a repeated name keeps its first slot and takes the
+/// later value.
+fn global_slots(globals: &[(String, ObjectRef)]) -> Vec<(usize, &ObjectRef)> {
+    let mut slots: Vec<(&str, &ObjectRef)> = Vec::new();
+    for (name, value) in globals {
+        match slots.iter_mut().find(|(seen, _)| seen == name) {
+            Some(slot) => slot.1 = value,
+            None => slots.push((name, value)),
+        }
+    }
+    slots
+        .into_iter()
+        .enumerate()
+        .map(|(index, (_, value))| (index, value))
+        .collect()
+}
+
+/// A session that keeps its compiled bindings, globals and VM between
+/// `eval` calls, for the REPL and embedders. Each call compiles only the
+/// new source with `Compiler::extend` and runs it on the same VM, so
+/// earlier sources are never compiled or executed again.
+#[derive(Debug)]
+pub struct Runner {
+    compiler: Compiler,
+    vm: Vm,
+    options: RunOptions,
+}
+
+impl Default for Runner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Runner {
+    pub fn new() -> Self {
+        Self::with_options(RunOptions::default())
+    }
+
+    /// A session configured by `options`; its globals are bound as by
+    /// `define`, and its timeout applies to each `eval`.
+    pub fn with_options(options: RunOptions) -> Self {
+        let mut vm = Vm::with_options(Chunk::default(), options.vm.clone());
+        vm.set_script_args(options.args.clone());
+        vm.set_trace(options.trace.clone());
+        vm.set_output_sink(options.output.clone());
+        vm.set_profiling(options.profile);
+        let mut runner = Self {
+            compiler: Compiler::with_options(options.compiler),
+            vm,
+            options,
+        };
+        for (name, value) in runner.options.globals.clone() {
+            runner.define(&name, value);
+        }
+        runner
+    }
+
+    /// Compile `source` on top of the earlier ones and run it. A source
+    /// that fails to compile or run leaves no definitions behind. The
+    /// outcome's stats and warnings are this call's alone.
+    pub fn eval(&mut self, source: &str) -> Result<RunOutcome, RunnerError> {
+        let program = parse(source)?;
+        let delta = self
+            .compiler
+            .extend(&program)
+            .map_err(RunnerError::Compile)?;
+        self.vm.append(delta);
+        let snapshot = self.vm.snapshot();
+        self.vm.reset_peak_depths();
+        let instructions_before = self.vm.instructions_executed();
+        let allocations_before = self.vm.allocations();
+        let bytes_before = self.vm.allocated_bytes();
+        let started = Instant::now();
+        let result = match self.options.timeout {
+            Some(timeout) => self.vm.run_with_deadline(timeout),
+            None => self.vm.run(),
+        };
+        let result = match result {
+            Ok(result) => result,
+            Err(err) => {
+                self.vm.restore(snapshot);
+                self.compiler.forget_last_extend();
+                return Err(RunnerError::Runtime(err));
+            }
+        };
+        let stats = RunStats {
+            instructions: self.vm.instructions_executed() - instructions_before,
+            peak_stack_depth: self.vm.peak_stack_depth(),
+            peak_frame_depth: self.vm.peak_frame_depth(),
+            allocations: self.vm.allocations() - allocations_before,
+            allocated_bytes: self.vm.allocated_bytes() - bytes_before,
+            elapsed: started.elapsed(),
+        };
+        Ok(RunOutcome {
+            result,
+            output: self.vm.take_output(),
+            profile: self.vm.profile().cloned(),
+            coverage: self.vm.coverage().cloned(),
+            warnings: self.compiler.warnings().to_vec(),
+            stats: Some(stats),
+        })
+    }
+
+    /// The bytecode `source` compiles to on top of the earlier sources,
+    /// without running it or keeping its definitions.
+    pub fn preview(&mut self, source: &str) -> Result<Chunk, RunnerError> {
+        let program = parse(source)?;
+        self.compiler
+            .preview(&program)
+            .map_err(RunnerError::Compile)
+    }
+
+    /// Bind `name` to `value` for later sources, as if by a `let`.
+    pub fn define(&mut self, name: &str, value: ObjectRef) {
+        let index = self.compiler.define_global(name);
+        self.vm.set_global(index, value);
+    }
+
+    /// Current value of the top-level binding `name`, if it is in scope.
+    pub fn global(&self, name: &str) -> Option<ObjectRef> {
+        let index = self.compiler.global_index(name)?;
+        self.vm.globals().get(index).cloned()
+    }
+
+    /// Forget every source and binding, keeping the options this session
+    /// was created with, including their globals.
+    pub fn reset(&mut self) {
+        *self = Self::with_options(self.options.clone());
+    }
+
+    pub fn compiler(&self) -> &Compiler {
+        &self.compiler
+    }
+
+    pub fn vm(&self) -> &Vm {
+        &self.vm
+    }
+}
+
+/// Run on the experimental register VM as configured by `options`.
+/// Programs it cannot translate or finish (including every program that
+/// raises a runtime error) are rerun on the stack VM, so the outcome
+/// matches `run_chunk_with`. So are runs asking for what the register VM
+/// does not do: a timeout, fuel, a memory limit, tracing, profiling,
+/// coverage or predefined globals. Its output reaches `options.output`
+/// once the run has finished.
+#[cfg(feature = "register-vm")]
+pub fn run_chunk_register(chunk: Chunk, options: &RunOptions) -> Result<RunOutcome, RunnerError> {
+    if register_vm_supports(options) {
+        if let Ok(mut vm) = crate::regvm::RegisterVm::new(&chunk) {
+            vm.set_script_args(options.args.clone());
+            vm.set_max_call_depth(options.vm.max_call_depth);
+            if let Some(result) = vm.run() {
+                let mut output = vm.take_output();
+                if let Some(sink) = &options.output {
+                    for line in output.drain(..) {
+                        sink.write(&line);
+                    }
+                }
+                return Ok(RunOutcome {
+                    result,
+                    output,
+                    profile: None,
+                    coverage: None,
+                    warnings: Vec::new(),
+                    stats: None,
+                });
+            }
+        }
+    }
+    run_chunk_with(chunk, options)
+}
+
+#[cfg(feature = "register-vm")]
+fn register_vm_supports(options: &RunOptions) -> bool {
+    options.timeout.is_none()
+        && options.trace.is_none()
+        && !options.profile
+        && options.globals.is_empty()
+        && options.vm.max_instructions.is_none()
+        && options.vm.max_memory_bytes.is_none()
+        && !options.vm.coverage
 }
 
 pub fn tokenize(source: &str) -> Vec<Token> {
-    Lexer::new(source).tokenize_all()
+    Lexer::new(source).tokenize_all(LexerOptions::default())
 }
 
 pub fn format_tokens(source: &str) -> String {
+    format_tokens_as(source, TokenFormat::Text)
+}
+
+/// The token stream of `source` rendered in `format`.
+pub fn format_tokens_as(source: &str, format: TokenFormat) -> String {
     let tokens = tokenize(source);
-    tokens
-        .iter()
-        .map(|t| format!("{}('{}') @ {}", t.kind, t.literal, t.pos))
-        .collect::<Vec<_>>()
-        .join("\n")
+    match format {
+        TokenFormat::Text => tokens
+            .iter()
+            .map(|t| format!("{}('{}') @ {}", t.kind, t.literal, t.pos))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        TokenFormat::Json => format_tokens_json(&tokens),
+    }
 }
 
 pub fn dump_ast(source: &str) -> Result<String, Vec<ParseError>> {
@@ -59,3 +470,18 @@ pub fn dump_ast(source: &str) -> Result<String, Vec<ParseError>> {
     }
     Ok(program.to_string())
 }
+
+/// Like `dump_ast`, rendering the program with `ast_json::program_to_json`.
+pub fn dump_ast_json(source: &str) -> Result<String, Vec<ParseError>> {
+    dump_ast_as(source, AstFormat::Json)
+}
+
+/// Like `dump_ast`, rendering the program in `format`.
+pub fn dump_ast_as(source: &str, format: AstFormat) -> Result<String, Vec<ParseError>> {
+    let mut parser = Parser::new(Lexer::new(source));
+    let program = parser.parse_program();
+    if !parser.errors().is_empty() {
+        return Err(parser.errors().to_vec());
+    }
+    Ok(format_ast_as(&program, format))
+}
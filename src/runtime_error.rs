@@ -1,6 +1,7 @@
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
-use crate::position::Position;
+use crate::position::{Position, Span};
+use crate::source::snippet_for_span;
 
 /// Protocol-compatible runtime error categories.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -15,6 +16,12 @@ pub enum RuntimeErrorType {
     Unhashable,
     DivisionByZero,
     UnsupportedOperation,
+    EvalError,
+    StackOverflow,
+    BudgetExceeded,
+    Timeout,
+    OutOfMemory,
+    AssertionFailed,
 }
 
 impl RuntimeErrorType {
@@ -30,6 +37,34 @@ impl RuntimeErrorType {
             RuntimeErrorType::Unhashable => "UNHASHABLE",
             RuntimeErrorType::DivisionByZero => "DIVISION_BY_ZERO",
             RuntimeErrorType::UnsupportedOperation => "UNSUPPORTED_OPERATION",
+            RuntimeErrorType::EvalError => "EVAL_ERROR",
+            RuntimeErrorType::StackOverflow => "STACK_OVERFLOW",
+            RuntimeErrorType::BudgetExceeded => "BUDGET_EXCEEDED",
+            RuntimeErrorType::Timeout => "TIMEOUT",
+            RuntimeErrorType::OutOfMemory => "OUT_OF_MEMORY",
+            RuntimeErrorType::AssertionFailed => "ASSERTION_FAILED",
+        }
+    }
+
+    /// Stable code for `monkey explain`; `code` is the protocol name.
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            RuntimeErrorType::TypeMismatch => "R001",
+            RuntimeErrorType::UnknownIdentifier => "R002",
+            RuntimeErrorType::NotCallable => "R003",
+            RuntimeErrorType::WrongArgumentCount => "R004",
+            RuntimeErrorType::InvalidArgumentType => "R005",
+            RuntimeErrorType::InvalidControlFlow => "R006",
+            RuntimeErrorType::InvalidIndex => "R007",
+            RuntimeErrorType::Unhashable => "R008",
+            RuntimeErrorType::DivisionByZero => "R009",
+            RuntimeErrorType::UnsupportedOperation => "R010",
+            RuntimeErrorType::EvalError => "R011",
+            RuntimeErrorType::StackOverflow => "R012",
+            RuntimeErrorType::BudgetExceeded => "R013",
+            RuntimeErrorType::Timeout => "R014",
+            RuntimeErrorType::OutOfMemory => "R015",
+            RuntimeErrorType::AssertionFailed => "R016",
         }
     }
 }
@@ -76,6 +111,9 @@ pub struct RuntimeError {
     pub error_type: RuntimeErrorType,
     pub message: String,
     pub pos: Position,
+    /// Start and end of the failing expression, when the bytecode
+    /// recorded them. Only used to underline source; messages report `pos`.
+    pub extent: Option<(Position, Position)>,
     pub stack: Vec<StackFrameInfo>,
 }
 
@@ -85,6 +123,7 @@ impl RuntimeError {
             error_type,
             message: message.into(),
             pos,
+            extent: None,
             stack: Vec::new(),
         }
     }
@@ -93,6 +132,12 @@ impl RuntimeError {
         Self::new(error_type, message, pos)
     }
 
+    /// Take the extent from `span`; its reported position is not used.
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.extent = (!span.is_empty()).then_some((span.start, span.end));
+        self
+    }
+
     pub fn with_stack(mut self, stack: Vec<StackFrameInfo>) -> Self {
         self.stack = stack;
         self
@@ -128,6 +173,29 @@ impl RuntimeError {
 
         format!("{}\nStack trace:\n{}", self.format_single_line(), frames)
     }
+
+    /// `format_multiline` with the error code appended to the first line,
+    /// followed by the offending line of `source` with the failing
+    /// expression underlined.
+    /// `format_multiline` itself stays in the exact shape the Java
+    /// implementation prints.
+    pub fn format_multiline_with_source(&self, source: &str) -> String {
+        let mut first = format!(
+            "{} [{}]",
+            self.format_single_line(),
+            self.error_type.error_code()
+        );
+        let span = self.extent.map_or(Span::at(self.pos), |(start, end)| {
+            Span::new(self.pos, start, end)
+        });
+        if let Some(snippet) = snippet_for_span(source, span) {
+            first = format!("{first}\n{snippet}");
+        }
+        match self.format_multiline().split_once('\n') {
+            Some((_, rest)) => format!("{first}\n{rest}"),
+            None => first,
+        }
+    }
 }
 
 impl Display for RuntimeError {
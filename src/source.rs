@@ -1,8 +1,169 @@
 use std::io;
 use std::path::Path;
 
+use crate::position::{Position, Span};
+
 /// Load source file contents from disk.
 pub fn load_source(path: &Path) -> io::Result<String> {
     // TODO(step-3): add path-specific error context for CLI reporting.
     std::fs::read_to_string(path)
 }
+
+/// A file added to a `SourceMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(usize);
+
+/// A span in a particular file of a `SourceMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Location {
+    pub file: FileId,
+    pub span: Span,
+}
+
+impl Location {
+    pub fn new(file: FileId, span: impl Into<Span>) -> Self {
+        Self {
+            file,
+            span: span.into(),
+        }
+    }
+}
+
+/// The files diagnostics can point into, so that a position from one of
+/// several inputs names the file it came from.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+#[derive(Debug, Clone)]
+struct SourceFile {
+    name: String,
+    source: Option<String>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file named `name` (usually its path) with its text.
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) -> FileId {
+        self.push(name.into(), Some(source.into()))
+    }
+
+    /// Add a file whose text is unavailable, e.g. precompiled bytecode.
+    /// Its locations are still named but get no snippet.
+    pub fn add_without_source(&mut self, name: impl Into<String>) -> FileId {
+        self.push(name.into(), None)
+    }
+
+    fn push(&mut self, name: String, source: Option<String>) -> FileId {
+        self.files.push(SourceFile { name, source });
+        FileId(self.files.len() - 1)
+    }
+
+    /// The files in the order they were added.
+    pub fn files(&self) -> impl Iterator<Item = FileId> {
+        (0..self.files.len()).map(FileId)
+    }
+
+    /// Panics when `file` came from another map.
+    pub fn name(&self, file: FileId) -> &str {
+        &self.files[file.0].name
+    }
+
+    /// Panics when `file` came from another map.
+    pub fn source(&self, file: FileId) -> Option<&str> {
+        self.files[file.0].source.as_deref()
+    }
+
+    /// `name:line:col` for the reported position of `location`.
+    pub fn describe(&self, location: Location) -> String {
+        format!("{}:{}", self.name(location.file), location.span.pos)
+    }
+
+    /// The source line of `location` underlined as `snippet_for_span`
+    /// does, or `None` when the file's text is unavailable.
+    pub fn snippet(&self, location: Location) -> Option<String> {
+        snippet_for_span(self.source(location.file)?, location.span)
+    }
+}
+
+/// Render the source line containing `pos` with a `^` underline beneath the
+/// token that starts there, e.g. for a runtime error at 2:7:
+///
+/// ```text
+///   2 | puts(1 / 0);
+///     |        ^
+/// ```
+///
+/// Returns `None` when `pos` is outside `source`.
+pub fn snippet(source: &str, pos: Position) -> Option<String> {
+    let line = source.lines().nth(pos.line.checked_sub(1)?)?;
+    let width = token_width(line, pos.col)?;
+    Some(underline(line, pos, width))
+}
+
+/// Like `snippet`, underlining exactly `width` characters from `pos`.
+pub fn snippet_span(source: &str, pos: Position, width: usize) -> Option<String> {
+    let line = source.lines().nth(pos.line.checked_sub(1)?)?;
+    token_width(line, pos.col)?;
+    Some(underline(line, pos, width.max(1)))
+}
+
+/// Underline the whole of `span` when it lies on one line, otherwise the
+/// token at its reported position as `snippet` does:
+///
+/// ```text
+///   2 | puts(1 / 0);
+///     |      ^^^^^
+/// ```
+pub fn snippet_for_span(source: &str, span: Span) -> Option<String> {
+    if span.is_empty() || span.start.line != span.end.line {
+        return snippet(source, span.pos);
+    }
+    snippet_span(
+        source,
+        span.start,
+        span.end.col.saturating_sub(span.start.col),
+    )
+}
+
+fn underline(line: &str, pos: Position, width: usize) -> String {
+    let number = pos.line.to_string();
+    let gutter = " ".repeat(number.len());
+    // Keep tabs so the carets line up under the same characters.
+    let indent = line
+        .chars()
+        .take(pos.col - 1)
+        .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+    format!(
+        "  {number} | {line}\n  {gutter} | {indent}{}",
+        "^".repeat(width)
+    )
+}
+
+/// Characters in the token starting at 1-based `col`: a whole identifier,
+/// number or string literal, otherwise one character. `None` when `col` is
+/// past the end of the line (an end-of-line error still gets one caret).
+fn token_width(line: &str, col: usize) -> Option<usize> {
+    let chars = line.chars().collect::<Vec<_>>();
+    if col == 0 || col > chars.len() + 1 {
+        return None;
+    }
+    let rest = &chars[col - 1..];
+    let width = match rest.first() {
+        Some(ch) if ch.is_alphanumeric() || *ch == '_' => rest
+            .iter()
+            .take_while(|ch| ch.is_alphanumeric() || **ch == '_')
+            .count(),
+        Some('"') => rest[1..]
+            .iter()
+            .position(|ch| *ch == '"')
+            .map_or(rest.len(), |end| end + 2),
+        _ => 1,
+    };
+    Some(width)
+}
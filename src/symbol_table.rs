@@ -1,10 +1,15 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::rc::Rc;
 
+use crate::position::Position;
+
 /// Stable builtin symbol ordering used by compiler symbol registration.
-pub const BUILTIN_NAMES: &[&str] = &["len", "first", "last", "rest", "push", "puts"];
+pub const BUILTIN_NAMES: &[&str] = &[
+    "len", "first", "last", "rest", "push", "puts", "eval", "args", "substr", "char_at", "chars",
+    "eq", "assert",
+];
 
 /// Symbol scope classification for compiler name resolution.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -47,15 +52,41 @@ impl Symbol {
     }
 }
 
+/// Where a binding recorded with `define_at` was introduced, and how many
+/// blocks deep inside its table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Definition {
+    pos: Position,
+    depth: usize,
+}
+
+/// A name first defined inside an open block, with the symbol and
+/// definition it shadows until the block ends.
+#[derive(Debug, Clone)]
+struct BlockBinding {
+    name: String,
+    shadowed: Option<Symbol>,
+    shadowed_definition: Option<Definition>,
+}
+
 pub type SymbolTableRef = Rc<RefCell<SymbolTable>>;
 
 /// Lexical symbol table used by compiler frontend.
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct SymbolTable {
     pub store: HashMap<String, Symbol>,
     pub outer: Option<SymbolTableRef>,
     pub num_definitions: usize,
     pub free_symbols: Vec<Symbol>,
+    /// Names `resolve` found in this table's own store, i.e. bindings that
+    /// are read somewhere.
+    pub used: HashSet<String>,
+    /// Names first defined inside each open block, innermost last.
+    blocks: Vec<Vec<BlockBinding>>,
+    /// Definition sites of the visible names defined with `define_at`.
+    definitions: HashMap<String, Definition>,
+    /// Symbols whose block has ended. Their slots stay allocated.
+    pub ended: Vec<Symbol>,
 }
 
 impl SymbolTable {
@@ -89,11 +120,85 @@ impl SymbolTable {
         }
 
         let symbol = Symbol::new(name.clone(), scope, self.num_definitions);
-        self.store.insert(name, symbol.clone());
+        let shadowed = self.store.insert(name.clone(), symbol.clone());
+        let shadowed_definition = self.definitions.remove(&name);
+        if let Some(block) = self.blocks.last_mut() {
+            block.push(BlockBinding {
+                name,
+                shadowed,
+                shadowed_definition,
+            });
+        }
         self.num_definitions += 1;
         symbol
     }
 
+    /// `define`, recording `pos` as where the name was introduced. Also
+    /// returns the earlier definition site when `name` was already defined
+    /// in the same block of this table, i.e. this is a redefinition rather
+    /// than a rebinding of an outer name.
+    pub fn define_at(
+        &mut self,
+        name: impl Into<String>,
+        pos: Position,
+    ) -> (Symbol, Option<Position>) {
+        let name = name.into();
+        let depth = self.blocks.len();
+        let previous = self.definitions.get(&name).copied();
+        let symbol = self.define(name.clone());
+        match previous {
+            Some(previous) if self.definitions.contains_key(&name) => {
+                let redefined = previous.depth == depth;
+                (symbol, redefined.then_some(previous.pos))
+            }
+            _ => {
+                self.definitions.insert(name, Definition { pos, depth });
+                (symbol, None)
+            }
+        }
+    }
+
+    /// Where the visible binding `name` of this table was defined, if it
+    /// was defined with `define_at`.
+    pub fn definition(&self, name: &str) -> Option<Position> {
+        self.definitions.get(name).map(|definition| definition.pos)
+    }
+
+    /// Open a block: names defined until the matching `leave_block` go out
+    /// of scope there. Defining a name that is already visible in this
+    /// table's scope rebinds it instead, and that outlives the block.
+    pub fn enter_block(&mut self) {
+        self.blocks.push(Vec::new());
+    }
+
+    pub fn leave_block(&mut self) {
+        let Some(defined) = self.blocks.pop() else {
+            return;
+        };
+        for binding in defined.into_iter().rev() {
+            if let Some(symbol) = self.store.remove(&binding.name) {
+                self.ended.push(symbol);
+            }
+            self.definitions.remove(&binding.name);
+            if let Some(definition) = binding.shadowed_definition {
+                self.definitions.insert(binding.name.clone(), definition);
+            }
+            if let Some(shadowed) = binding.shadowed {
+                self.store.insert(binding.name, shadowed);
+            }
+        }
+    }
+
+    /// Whether `name` is unresolvable only because the block defining it,
+    /// here or in an enclosing scope, has ended.
+    pub fn ended_in_block(&self, name: &str) -> bool {
+        self.ended.iter().any(|symbol| symbol.name == name)
+            || self
+                .outer
+                .as_ref()
+                .is_some_and(|outer| outer.borrow().ended_in_block(name))
+    }
+
     pub fn define_builtin(&mut self, index: usize, name: impl Into<String>) -> Symbol {
         let name = name.into();
         let symbol = Symbol::new(name.clone(), SymbolScope::Builtin, index);
@@ -110,7 +215,9 @@ impl SymbolTable {
 
     pub fn resolve(&mut self, name: &str) -> Option<Symbol> {
         if let Some(symbol) = self.store.get(name) {
-            return Some(symbol.clone());
+            let symbol = symbol.clone();
+            self.used.insert(symbol.name.clone());
+            return Some(symbol);
         }
 
         let outer = self.outer.clone()?;
@@ -124,6 +231,38 @@ impl SymbolTable {
         }
     }
 
+    /// Visible names closest in spelling to `name`, nearest first, for
+    /// "did you mean" hints. At most three are returned, and only names
+    /// within a third of `name`'s length in edits (at least one), and fewer
+    /// edits than `name` has characters, so one-letter names match nothing.
+    pub fn similar_names(&self, name: &str) -> Vec<String> {
+        let mut names = HashSet::new();
+        self.collect_visible_names(&mut names);
+        let len = name.chars().count();
+        let limit = (len / 3).max(1).min(len.saturating_sub(1));
+        let mut candidates = names
+            .into_iter()
+            .filter(|candidate| candidate != name)
+            .filter_map(|candidate| {
+                let distance = edit_distance(name, &candidate);
+                (distance <= limit).then_some((distance, candidate))
+            })
+            .collect::<Vec<_>>();
+        candidates.sort();
+        candidates
+            .into_iter()
+            .take(3)
+            .map(|(_, candidate)| candidate)
+            .collect()
+    }
+
+    fn collect_visible_names(&self, names: &mut HashSet<String>) {
+        names.extend(self.store.keys().cloned());
+        if let Some(outer) = &self.outer {
+            outer.borrow().collect_visible_names(names);
+        }
+    }
+
     fn define_free(&mut self, original: Symbol) -> Symbol {
         if let Some(existing) = self.store.get(&original.name) {
             if existing.scope == SymbolScope::Free {
@@ -142,6 +281,22 @@ impl SymbolTable {
     }
 }
 
+/// Levenshtein distance between `a` and `b`, counted in chars.
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
 pub fn define_builtins(table: &mut SymbolTable) {
     // TODO(step-10): compiler will consume builtin symbol indices for GetBuiltin emission.
     for (index, &name) in BUILTIN_NAMES.iter().enumerate() {
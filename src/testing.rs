@@ -0,0 +1,98 @@
+//! Test discovery for `monkey test`.
+//!
+//! A test is a source file whose name ends in `_test.monkey`. It passes
+//! when it runs to completion and fails on any parse, compile or runtime
+//! error, usually one raised by `assert`.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::position::Position;
+use crate::runner::RunnerError;
+use crate::source::{FileId, Location, SourceMap};
+
+pub const TEST_FILE_SUFFIX: &str = "_test.monkey";
+
+pub fn is_test_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with(TEST_FILE_SUFFIX))
+}
+
+/// What `discover_tests` found: the test files, sorted and without
+/// duplicates, and each path that could not be searched with the reason.
+#[derive(Debug, Default)]
+pub struct Discovery {
+    pub files: Vec<PathBuf>,
+    pub errors: Vec<(PathBuf, io::Error)>,
+}
+
+/// Test files under `paths`. Directories are searched recursively,
+/// skipping hidden ones and `target`; a file named directly is a test
+/// whatever its name. A missing or unreadable path is recorded in
+/// `Discovery::errors` and the search goes on with the rest.
+pub fn discover_tests(paths: &[PathBuf]) -> Discovery {
+    let mut found = Discovery::default();
+    for path in paths {
+        if path.is_dir() {
+            collect(path, &mut found);
+        } else {
+            // Report a missing file now rather than as a failing test.
+            match fs::metadata(path) {
+                Ok(_) => found.files.push(path.clone()),
+                Err(err) => found.errors.push((path.clone(), err)),
+            }
+        }
+    }
+    found.files.sort();
+    found.files.dedup();
+    found
+}
+
+fn collect(dir: &Path, found: &mut Discovery) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            found.errors.push((dir.to_path_buf(), err));
+            return;
+        }
+    };
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(err) => {
+                found.errors.push((dir.to_path_buf(), err));
+                continue;
+            }
+        };
+        let name = path.file_name().and_then(|name| name.to_str());
+        if name.is_some_and(|name| name.starts_with('.') || name == "target") {
+            continue;
+        }
+        if path.is_dir() {
+            collect(&path, found);
+        } else if is_test_file(&path) {
+            found.files.push(path);
+        }
+    }
+}
+
+/// `name:line:col: message` for the first error in `err`, raised by the
+/// test in `file`, for the summary `monkey test` prints after all files
+/// ran.
+pub fn failure_summary(files: &SourceMap, file: FileId, err: &RunnerError) -> String {
+    let (pos, message): (Option<Position>, &str) = match err {
+        RunnerError::Parse(errors) => errors
+            .first()
+            .map_or((None, "parse error"), |e| (Some(e.pos), &e.message)),
+        RunnerError::Compile(errors) => errors
+            .first()
+            .map_or((None, "compile error"), |e| (e.pos, &e.message)),
+        RunnerError::Runtime(err) => (Some(err.pos), &err.message),
+    };
+    match pos {
+        Some(pos) => format!("{}: {message}", files.describe(Location::new(file, pos))),
+        None => format!("{}: {message}", files.name(file)),
+    }
+}
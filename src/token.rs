@@ -1,4 +1,5 @@
-use crate::position::Position;
+use crate::position::{Position, Span};
+use crate::symbol_table::edit_distance;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
 /// Token kinds recognized by the Monkey language.
@@ -9,7 +10,13 @@ pub enum TokenKind {
 
     Ident,
     Int,
+    /// `1.5` or `2.0e3`. The language has no float values yet, so the
+    /// parser reports these as errors.
+    Float,
     String,
+    /// A `#` comment up to the end of its line. Only produced when
+    /// `LexerOptions::comments` is set.
+    Comment,
 
     Assign,
     Plus,
@@ -50,40 +57,142 @@ pub enum TokenKind {
     Continue,
 }
 
+impl TokenKind {
+    /// How diagnostics refer to the kind: its text in backticks when every
+    /// token of the kind is spelled the same, otherwise what it is.
+    pub fn description(&self) -> &'static str {
+        match self {
+            TokenKind::Illegal => "an illegal character",
+            TokenKind::Eof => "the end of the input",
+            TokenKind::Ident => "an identifier",
+            TokenKind::Int => "an integer",
+            TokenKind::Float => "a float",
+            TokenKind::String => "a string",
+            TokenKind::Comment => "a comment",
+            TokenKind::Assign => "`=`",
+            TokenKind::Plus => "`+`",
+            TokenKind::Minus => "`-`",
+            TokenKind::Bang => "`!`",
+            TokenKind::Asterisk => "`*`",
+            TokenKind::Slash => "`/`",
+            TokenKind::Lt => "`<`",
+            TokenKind::Gt => "`>`",
+            TokenKind::Eq => "`==`",
+            TokenKind::NotEq => "`!=`",
+            TokenKind::Le => "`<=`",
+            TokenKind::Ge => "`>=`",
+            TokenKind::And => "`&&`",
+            TokenKind::Or => "`||`",
+            TokenKind::Comma => "`,`",
+            TokenKind::Semicolon => "`;`",
+            TokenKind::Colon => "`:`",
+            TokenKind::LParen => "`(`",
+            TokenKind::RParen => "`)`",
+            TokenKind::LBrace => "`{`",
+            TokenKind::RBrace => "`}`",
+            TokenKind::LBracket => "`[`",
+            TokenKind::RBracket => "`]`",
+            TokenKind::Function => "`fn`",
+            TokenKind::Let => "`let`",
+            TokenKind::True => "`true`",
+            TokenKind::False => "`false`",
+            TokenKind::If => "`if`",
+            TokenKind::Else => "`else`",
+            TokenKind::Return => "`return`",
+            TokenKind::While => "`while`",
+            TokenKind::Break => "`break`",
+            TokenKind::Continue => "`continue`",
+        }
+    }
+}
+
+/// The binary operators, in precedence order.
+pub const BINARY_OPERATORS: [TokenKind; 12] = [
+    TokenKind::Or,
+    TokenKind::And,
+    TokenKind::Eq,
+    TokenKind::NotEq,
+    TokenKind::Lt,
+    TokenKind::Gt,
+    TokenKind::Le,
+    TokenKind::Ge,
+    TokenKind::Plus,
+    TokenKind::Minus,
+    TokenKind::Asterisk,
+    TokenKind::Slash,
+];
+
 /// Token with literal text and source position.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Token {
     pub kind: TokenKind,
     pub literal: String,
     pub pos: Position,
+    /// Position just past the token's last character.
+    pub end: Position,
 }
 
 impl Token {
+    /// A token whose end is `pos` advanced by the literal's length, which
+    /// is exact for tokens on one line whose literal is their source text.
+    /// The lexer sets the real end of strings with `with_end`.
     pub fn new(kind: TokenKind, literal: impl Into<String>, pos: Position) -> Self {
+        let literal = literal.into();
+        let end = Position::new(pos.line, pos.col + literal.chars().count());
         Self {
             kind,
-            literal: literal.into(),
+            literal,
             pos,
+            end,
         }
     }
+
+    pub fn with_end(mut self, end: Position) -> Self {
+        self.end = end;
+        self
+    }
+
+    /// The source range the token covers.
+    pub fn span(&self) -> Span {
+        Span::new(self.pos, self.pos, self.end)
+    }
 }
 
+/// Keywords and the tokens they lex as.
+pub const KEYWORDS: [(&str, TokenKind); 10] = [
+    ("fn", TokenKind::Function),
+    ("let", TokenKind::Let),
+    ("true", TokenKind::True),
+    ("false", TokenKind::False),
+    ("if", TokenKind::If),
+    ("else", TokenKind::Else),
+    ("return", TokenKind::Return),
+    ("while", TokenKind::While),
+    ("break", TokenKind::Break),
+    ("continue", TokenKind::Continue),
+];
+
 /// Resolve identifier text to keyword tokens when applicable.
 pub fn lookup_ident(ident: &str) -> TokenKind {
-    // TODO(step-3): lexer should call this for identifier token classification.
-    match ident {
-        "fn" => TokenKind::Function,
-        "let" => TokenKind::Let,
-        "true" => TokenKind::True,
-        "false" => TokenKind::False,
-        "if" => TokenKind::If,
-        "else" => TokenKind::Else,
-        "return" => TokenKind::Return,
-        "while" => TokenKind::While,
-        "break" => TokenKind::Break,
-        "continue" => TokenKind::Continue,
-        _ => TokenKind::Ident,
+    KEYWORDS
+        .iter()
+        .find(|(keyword, _)| *keyword == ident)
+        .map_or(TokenKind::Ident, |(_, kind)| kind.clone())
+}
+
+/// The keyword `ident` is most likely a misspelling of, for "did you mean"
+/// hints: the nearest within a third of the keyword's length in edits (at
+/// least one). Identifiers of one character are never taken for keywords.
+pub fn similar_keyword(ident: &str) -> Option<&'static str> {
+    if ident.chars().count() < 2 {
+        return None;
     }
+    KEYWORDS
+        .iter()
+        .map(|(keyword, _)| (edit_distance(ident, keyword), *keyword))
+        .filter(|(distance, keyword)| *distance > 0 && *distance <= (keyword.len() / 3).max(1))
+        .min()
+        .map(|(_, keyword)| keyword)
 }
 
 impl Display for TokenKind {
@@ -93,7 +202,9 @@ impl Display for TokenKind {
             TokenKind::Eof => "Eof",
             TokenKind::Ident => "Ident",
             TokenKind::Int => "Int",
+            TokenKind::Float => "Float",
             TokenKind::String => "String",
+            TokenKind::Comment => "Comment",
             TokenKind::Assign => "Assign",
             TokenKind::Plus => "Plus",
             TokenKind::Minus => "Minus",
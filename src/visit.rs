@@ -0,0 +1,126 @@
+//! Read-only traversal of the AST for analyses such as lints and metrics.
+//!
+//! Implement `Visitor` and override the methods for the nodes of interest.
+//! Every default calls the matching `walk_*` function, which visits the
+//! node's children in source order; an override that still wants the
+//! children visited calls `walk_*` itself, and one that doesn't prunes the
+//! traversal there.
+
+use crate::ast::{BlockStatement, Expression, Identifier, Program, Statement};
+
+pub trait Visitor<'ast> {
+    fn visit_program(&mut self, program: &'ast Program) {
+        walk_program(self, program);
+    }
+
+    fn visit_statement(&mut self, statement: &'ast Statement) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_block(&mut self, block: &'ast BlockStatement) {
+        walk_block(self, block);
+    }
+
+    fn visit_expression(&mut self, expression: &'ast Expression) {
+        walk_expression(self, expression);
+    }
+
+    /// A name being bound: a `let` name or a function parameter. Names
+    /// being read are `Expression::Identifier`s.
+    fn visit_binding(&mut self, _name: &'ast Identifier) {}
+}
+
+pub fn walk_program<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, program: &'ast Program) {
+    for statement in &program.statements {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_statement<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    statement: &'ast Statement,
+) {
+    match statement {
+        Statement::Let { name, value, .. } => {
+            visitor.visit_binding(name);
+            visitor.visit_expression(value);
+        }
+        Statement::Return { value, .. } => visitor.visit_expression(value),
+        Statement::While {
+            condition, body, ..
+        } => {
+            visitor.visit_expression(condition);
+            visitor.visit_block(body);
+        }
+        Statement::Break { .. } | Statement::Continue { .. } => {}
+        Statement::Expression { expression, .. } => visitor.visit_expression(expression),
+    }
+}
+
+pub fn walk_block<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, block: &'ast BlockStatement) {
+    for statement in &block.statements {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_expression<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    expression: &'ast Expression,
+) {
+    match expression {
+        Expression::Identifier { .. }
+        | Expression::IntegerLiteral { .. }
+        | Expression::BooleanLiteral { .. }
+        | Expression::StringLiteral { .. } => {}
+        Expression::Prefix { right, .. } => visitor.visit_expression(right),
+        Expression::Infix { left, right, .. } => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        }
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+            ..
+        } => {
+            visitor.visit_expression(condition);
+            visitor.visit_block(consequence);
+            if let Some(alternative) = alternative {
+                visitor.visit_block(alternative);
+            }
+        }
+        Expression::FunctionLiteral {
+            parameters, body, ..
+        } => {
+            for parameter in parameters {
+                visitor.visit_binding(parameter);
+            }
+            visitor.visit_block(body);
+        }
+        Expression::Call {
+            function,
+            arguments,
+            ..
+        } => {
+            visitor.visit_expression(function);
+            for argument in arguments {
+                visitor.visit_expression(argument);
+            }
+        }
+        Expression::ArrayLiteral { elements, .. } => {
+            for element in elements {
+                visitor.visit_expression(element);
+            }
+        }
+        Expression::HashLiteral { pairs, .. } => {
+            for (key, value) in pairs {
+                visitor.visit_expression(key);
+                visitor.visit_expression(value);
+            }
+        }
+        Expression::Index { left, index, .. } => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(index);
+        }
+    }
+}
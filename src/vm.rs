@@ -1,23 +1,36 @@
-use std::rc::Rc;
+use std::cell::RefCell;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::io::Write;
+use std::time::{Duration, Instant};
 
-use crate::builtins::{builtin_name_at, execute_builtin};
-use crate::bytecode::{lookup_definition, Chunk, Opcode};
-use crate::object::{ClosureObject, CompiledFunctionObject, Object, ObjectRef};
-use crate::position::Position;
+use crate::builtins::{builtin_name_at, execute_builtin, BuiltinContext, BuiltinError};
+use crate::bytecode::{lookup_definition, read_operands, span_at, Chunk, Opcode};
+use crate::compiler::Compiler;
+use crate::coverage::Coverage;
+use crate::lexer::Lexer;
+use crate::object::{
+    ClosureObject, CompiledFunctionObject, HashObject, HeapMeasure, Object, ObjectRef, Shared,
+    StringInterner,
+};
+use crate::parser::Parser;
+use crate::peephole::fuse_superinstructions;
+use crate::position::{Position, Span};
+use crate::profile::Profile;
 use crate::runtime_error::{RuntimeError, RuntimeErrorType, StackFrameInfo};
 
 #[derive(Debug, Clone)]
 struct Frame {
-    closure: Rc<ClosureObject>,
+    closure: Shared<ClosureObject>,
     ip: usize,
     base_pointer: usize,
     call_site_pos: Position,
     arg_count: usize,
+    entered_at: Option<Instant>,
 }
 
 impl Frame {
     fn new(
-        closure: Rc<ClosureObject>,
+        closure: Shared<ClosureObject>,
         base_pointer: usize,
         call_site_pos: Position,
         arg_count: usize,
@@ -28,10 +41,296 @@ impl Frame {
             base_pointer,
             call_site_pos,
             arg_count,
+            entered_at: None,
         }
     }
 }
 
+/// Frames kept from the innermost end of a `StackOverflow` trace; the root
+/// frame is always appended after them.
+const OVERFLOW_TRACE_FRAMES: usize = 16;
+
+/// Nested `eval` calls allowed at once. Each one runs a child VM on the
+/// native stack, so this stays far below `max_call_depth`.
+const MAX_EVAL_DEPTH: usize = 32;
+
+/// How many instructions run between wall-clock deadline checks.
+const DEADLINE_CHECK_INTERVAL: u64 = 1024;
+
+/// Integers below this are shared through `Vm::small_int` rather than
+/// allocated on every `ConstInt`/`Const0`/`Const1`.
+const SMALL_INT_CACHE_SIZE: usize = 256;
+
+/// Entries in the hash `Index` inline cache, addressed by instruction
+/// offset; a power of two.
+const INDEX_CACHE_SIZE: usize = 256;
+
+/// Execution limits applied to a `Vm` and inherited by `eval` children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmOptions {
+    /// Maximum number of active call frames, including the root frame.
+    pub max_call_depth: usize,
+    /// Maximum number of instructions executed before `BudgetExceeded` is
+    /// raised; `None` means unlimited.
+    pub max_instructions: Option<u64>,
+    /// Maximum approximate bytes of strings, arrays and hashes the program
+    /// may keep reachable before `OutOfMemory` is raised; `None` means
+    /// unlimited.
+    pub max_memory_bytes: Option<usize>,
+    /// Value stack slots reserved up front so hot call loops do not grow it.
+    pub stack_capacity: usize,
+    /// Call frames reserved up front; popped frames leave their slot for
+    /// reuse by the next call.
+    pub frame_capacity: usize,
+    /// Fuse common instruction sequences into superinstructions before
+    /// running. Disable to execute exactly what the compiler emitted, e.g.
+    /// for conformance comparison.
+    pub superinstructions: bool,
+    /// Compile hot integer/boolean functions to native code. Only has an
+    /// effect in builds with the `jit` feature, and only for runs without
+    /// instruction limits, deadlines, tracing, profiling, coverage or
+    /// stepping.
+    pub jit: bool,
+    /// Record which source lines ran; read them back with `Vm::coverage`.
+    /// Not inherited by `eval`, whose lines belong to another source.
+    pub coverage: bool,
+}
+
+impl Default for VmOptions {
+    fn default() -> Self {
+        Self {
+            max_call_depth: 10_000,
+            max_instructions: None,
+            max_memory_bytes: None,
+            stack_capacity: 2048,
+            frame_capacity: 256,
+            superinstructions: true,
+            jit: true,
+            coverage: false,
+        }
+    }
+}
+
+impl VmOptions {
+    pub fn with_max_call_depth(mut self, max_call_depth: usize) -> Self {
+        self.max_call_depth = max_call_depth;
+        self
+    }
+
+    pub fn with_max_instructions(mut self, max_instructions: u64) -> Self {
+        self.max_instructions = Some(max_instructions);
+        self
+    }
+
+    pub fn with_max_memory_bytes(mut self, max_memory_bytes: usize) -> Self {
+        self.max_memory_bytes = Some(max_memory_bytes);
+        self
+    }
+
+    pub fn with_stack_capacity(mut self, stack_capacity: usize) -> Self {
+        self.stack_capacity = stack_capacity;
+        self
+    }
+
+    pub fn with_frame_capacity(mut self, frame_capacity: usize) -> Self {
+        self.frame_capacity = frame_capacity;
+        self
+    }
+
+    pub fn with_superinstructions(mut self, superinstructions: bool) -> Self {
+        self.superinstructions = superinstructions;
+        self
+    }
+
+    pub fn with_jit(mut self, jit: bool) -> Self {
+        self.jit = jit;
+        self
+    }
+
+    pub fn with_coverage(mut self, coverage: bool) -> Self {
+        self.coverage = coverage;
+        self
+    }
+}
+
+/// Callback receiving one line per executed instruction while tracing
+/// (`Vm::set_trace`).
+///
+/// Trace lines look like `<repl> 0003 Constant 1 stack=1 @1:5`: function name,
+/// offset, opcode and operands, stack depth before the instruction, and
+/// source position.
+///
+/// With the `sync` feature the callback must be `Send`, like the rest of
+/// the `Vm`.
+#[derive(Clone)]
+pub struct TraceSink(LineCallback);
+
+impl TraceSink {
+    #[cfg(not(feature = "sync"))]
+    pub fn new(sink: impl FnMut(&str) + 'static) -> Self {
+        Self(LineCallback::new(sink))
+    }
+
+    #[cfg(feature = "sync")]
+    pub fn new(sink: impl FnMut(&str) + Send + 'static) -> Self {
+        Self(LineCallback::new(sink))
+    }
+}
+
+impl Debug for TraceSink {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("TraceSink(..)")
+    }
+}
+
+/// Destination for the lines a program prints (`Vm::set_output_sink`),
+/// given each line without its newline as soon as it is printed.
+///
+/// With the `sync` feature the callback or writer must be `Send`, like the
+/// rest of the `Vm`.
+#[derive(Clone)]
+pub struct OutputSink(LineCallback);
+
+impl OutputSink {
+    #[cfg(not(feature = "sync"))]
+    pub fn new(sink: impl FnMut(&str) + 'static) -> Self {
+        Self(LineCallback::new(sink))
+    }
+
+    #[cfg(feature = "sync")]
+    pub fn new(sink: impl FnMut(&str) + Send + 'static) -> Self {
+        Self(LineCallback::new(sink))
+    }
+
+    /// Send one printed line to the sink.
+    pub fn write(&self, line: &str) {
+        self.0.emit(line);
+    }
+
+    /// Write each line to `writer` followed by a newline, flushing so it
+    /// shows up at once. Write errors are ignored.
+    #[cfg(not(feature = "sync"))]
+    pub fn from_writer(mut writer: impl Write + 'static) -> Self {
+        Self::new(move |line| write_line(&mut writer, line))
+    }
+
+    /// Write each line to `writer` followed by a newline, flushing so it
+    /// shows up at once. Write errors are ignored.
+    #[cfg(feature = "sync")]
+    pub fn from_writer(mut writer: impl Write + Send + 'static) -> Self {
+        Self::new(move |line| write_line(&mut writer, line))
+    }
+}
+
+impl Debug for OutputSink {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("OutputSink(..)")
+    }
+}
+
+fn write_line(writer: &mut impl Write, line: &str) {
+    let _ = writeln!(writer, "{line}").and_then(|()| writer.flush());
+}
+
+/// Shared, mutable line callback behind `TraceSink` and `OutputSink`.
+#[derive(Clone)]
+struct LineCallback(Shared<LineCell>);
+
+#[cfg(not(feature = "sync"))]
+type LineCell = RefCell<dyn FnMut(&str)>;
+#[cfg(feature = "sync")]
+type LineCell = std::sync::Mutex<dyn FnMut(&str) + Send>;
+
+impl LineCallback {
+    #[cfg(not(feature = "sync"))]
+    fn new(sink: impl FnMut(&str) + 'static) -> Self {
+        Self(Shared::new(RefCell::new(sink)))
+    }
+
+    #[cfg(feature = "sync")]
+    fn new(sink: impl FnMut(&str) + Send + 'static) -> Self {
+        Self(Shared::new(std::sync::Mutex::new(sink)))
+    }
+
+    #[cfg(not(feature = "sync"))]
+    fn emit(&self, line: &str) {
+        (self.0.borrow_mut())(line);
+    }
+
+    #[cfg(feature = "sync")]
+    fn emit(&self, line: &str) {
+        let mut sink = self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        (sink)(line);
+    }
+}
+
+/// Result of a bounded `Vm::step` / `Vm::run_for` call.
+#[derive(Debug, Clone)]
+pub enum StepOutcome {
+    /// Paused with instructions left to run.
+    Continue,
+    /// The program finished with this value.
+    Done(ObjectRef),
+    /// Execution stopped with a runtime error.
+    Error(RuntimeError),
+}
+
+/// Execution state captured by `Vm::snapshot`.
+///
+/// Frames hold their closures, so a snapshot can be restored after the VM
+/// has moved on; it must be restored into a VM running the same chunk, since
+/// constant indices are not captured.
+#[derive(Debug, Clone)]
+pub struct VmSnapshot {
+    stack: Vec<ObjectRef>,
+    globals: Vec<ObjectRef>,
+    frames: Vec<Frame>,
+    last_popped: Option<ObjectRef>,
+    output: Vec<String>,
+    instructions_executed: u64,
+    allocated_bytes: usize,
+    allocations: u64,
+    heap_estimate: usize,
+}
+
+impl VmSnapshot {
+    pub fn globals(&self) -> &[ObjectRef] {
+        &self.globals
+    }
+
+    pub fn stack(&self) -> &[ObjectRef] {
+        &self.stack
+    }
+
+    pub fn output(&self) -> &[String] {
+        &self.output
+    }
+
+    pub fn frame_depth(&self) -> usize {
+        self.frames.len()
+    }
+}
+
+/// Root frame running the top-level code of `chunk`. The main function
+/// takes ownership of the code; only the constant pool stays behind.
+fn main_frame(chunk: &mut Chunk) -> Frame {
+    let main_function = Shared::new(CompiledFunctionObject {
+        name: Some("<repl>".to_string()),
+        num_params: 0,
+        num_locals: 0,
+        instructions: std::mem::take(&mut chunk.instructions).into(),
+        positions: std::mem::take(&mut chunk.positions).into(),
+    });
+    let main_closure = Shared::new(ClosureObject {
+        function: main_function,
+        free: Vec::new(),
+    });
+    Frame::new(main_closure, 0, Position::default(), 0)
+}
+
 /// Stack-based VM for executing compiled Monkey bytecode.
 #[derive(Debug, Clone)]
 pub struct Vm {
@@ -41,48 +340,351 @@ pub struct Vm {
     frames: Vec<Frame>,
     last_popped: Option<ObjectRef>,
     output: Vec<String>,
+    script_args: Vec<String>,
+    strings: StringInterner,
+    options: VmOptions,
+    instructions_executed: u64,
+    deadline: Option<(Instant, Duration)>,
+    allocated_bytes: usize,
+    /// Values charged by `charge_allocation`.
+    allocations: u64,
+    /// Bytes reachable at the last measurement plus those charged since:
+    /// an upper bound on the live heap, checked against `max_memory_bytes`.
+    heap_estimate: usize,
+    /// Heap reachable from the VMs whose `eval` this one runs inside,
+    /// measured when it started.
+    outer_heap_bytes: usize,
+    /// Deepest stack and frame stack since creation or
+    /// `reset_peak_depths`, counting `eval` children on top of this VM.
+    peak_stack_depth: usize,
+    peak_frame_depth: usize,
+    /// Frames of the VMs whose `eval` calls this one runs inside; they
+    /// count toward `max_call_depth`.
+    outer_frames: usize,
+    /// Number of `eval` calls this VM runs inside.
+    eval_depth: usize,
+    trace: Option<TraceSink>,
+    /// Receives printed lines instead of `output` when set.
+    output_sink: Option<OutputSink>,
+    profile: Option<Profile>,
+    coverage: Option<Coverage>,
+    /// Hash slot last found by the `Index` instruction at each offset.
+    index_cache: Vec<usize>,
+    /// Lazily created objects for small immediate integers.
+    small_ints: Vec<Option<ObjectRef>>,
+    /// Last `(function, ip)` resolved by `current_position`; calls look up
+    /// the same call site repeatedly inside loops.
+    position_cache: RefCell<Option<(Shared<CompiledFunctionObject>, usize, Span)>>,
+    /// Shared singletons so hot paths push `null`/booleans without allocating.
+    null: ObjectRef,
+    true_obj: ObjectRef,
+    false_obj: ObjectRef,
+    #[cfg(feature = "jit")]
+    jit: crate::jit::Jit,
+    /// Whether calls in the current `execute` may run native code.
+    #[cfg(feature = "jit")]
+    jit_active: bool,
 }
 
 impl Vm {
     pub fn new(chunk: Chunk) -> Self {
-        let main_function = Rc::new(CompiledFunctionObject {
-            name: Some("<repl>".to_string()),
-            num_params: 0,
-            num_locals: 0,
-            instructions: chunk.instructions.clone(),
-            positions: chunk.positions.clone(),
-        });
-        let main_closure = Rc::new(ClosureObject {
-            function: main_function,
-            free: Vec::new(),
-        });
+        Self::with_options(chunk, VmOptions::default())
+    }
+
+    pub fn with_options(mut chunk: Chunk, options: VmOptions) -> Self {
+        if options.superinstructions {
+            fuse_superinstructions(&mut chunk);
+        }
+        let coverage = options.coverage.then(|| Coverage::for_chunk(&chunk));
+        let mut frames =
+            Vec::with_capacity(options.frame_capacity.min(options.max_call_depth).max(1));
+        frames.push(main_frame(&mut chunk));
+        let mut strings = StringInterner::new();
+        for constant in &chunk.constants {
+            if let Object::String(text) = constant.as_ref() {
+                strings.intern_shared(Shared::clone(text));
+            }
+        }
 
         Self {
             chunk,
-            stack: Vec::new(),
+            stack: Vec::with_capacity(options.stack_capacity),
             globals: Vec::new(),
-            frames: vec![Frame::new(main_closure, 0, Position::default(), 0)],
+            frames,
             last_popped: None,
             output: Vec::new(),
+            script_args: Vec::new(),
+            strings,
+            #[cfg(feature = "jit")]
+            jit: crate::jit::Jit::new(options.max_call_depth),
+            #[cfg(feature = "jit")]
+            jit_active: false,
+            options,
+            instructions_executed: 0,
+            deadline: None,
+            allocated_bytes: 0,
+            allocations: 0,
+            heap_estimate: 0,
+            outer_heap_bytes: 0,
+            peak_stack_depth: 0,
+            peak_frame_depth: 1,
+            outer_frames: 0,
+            eval_depth: 0,
+            trace: None,
+            output_sink: None,
+            profile: None,
+            coverage,
+            index_cache: vec![usize::MAX; INDEX_CACHE_SIZE],
+            small_ints: vec![None; SMALL_INT_CACHE_SIZE],
+            position_cache: RefCell::new(None),
+            null: Object::Null.rc(),
+            true_obj: Object::Boolean(true).rc(),
+            false_obj: Object::Boolean(false).rc(),
+        }
+    }
+
+    pub fn options(&self) -> &VmOptions {
+        &self.options
+    }
+
+    /// Make `delta` (from `Compiler::extend`) the next top-level code: its
+    /// constants are appended to the pool, and globals and output carry
+    /// over. Call `run` afterwards to execute it.
+    pub fn append(&mut self, mut delta: Chunk) {
+        if self.options.superinstructions {
+            fuse_superinstructions(&mut delta);
+        }
+        for constant in &delta.constants {
+            if let Object::String(text) = constant.as_ref() {
+                self.strings.intern_shared(Shared::clone(text));
+            }
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.add_chunk(&delta);
         }
+        let frame = main_frame(&mut delta);
+        self.chunk.constants.append(&mut delta.constants);
+        self.frames.clear();
+        self.frames.push(frame);
+        self.stack.clear();
+        self.last_popped = None;
+        self.index_cache.fill(usize::MAX);
+        *self.position_cache.borrow_mut() = None;
+    }
+
+    /// Functions compiled to native code so far.
+    #[cfg(feature = "jit")]
+    pub fn jit_compiled_count(&self) -> usize {
+        self.jit.compiled_count()
+    }
+
+    /// Instructions executed so far, including those run by `eval` children.
+    /// Calls that ran as native code (`jit` feature) are not counted.
+    pub fn instructions_executed(&self) -> u64 {
+        self.instructions_executed
+    }
+
+    /// Approximate bytes allocated for strings, arrays and hashes so far.
+    /// This is a running total; `max_memory_bytes` limits the live heap.
+    pub fn allocated_bytes(&self) -> usize {
+        self.allocated_bytes
+    }
+
+    /// Strings, arrays and hashes created so far, as counted towards
+    /// `allocated_bytes`.
+    pub fn allocations(&self) -> u64 {
+        self.allocations
+    }
+
+    /// Most values the stack has held at once, locals included.
+    pub fn peak_stack_depth(&self) -> usize {
+        self.peak_stack_depth
+    }
+
+    /// Most call frames active at once, the top-level frame included.
+    /// Calls that ran as native code (`jit` feature) are not counted.
+    pub fn peak_frame_depth(&self) -> usize {
+        self.peak_frame_depth
+    }
+
+    /// Start measuring `peak_stack_depth` and `peak_frame_depth` again from
+    /// the current depths, e.g. before running an appended chunk.
+    pub fn reset_peak_depths(&mut self) {
+        self.peak_stack_depth = self.stack.len();
+        self.peak_frame_depth = self.frames.len();
+    }
+
+    /// Install or remove the per-instruction trace callback.
+    pub fn set_trace(&mut self, sink: Option<TraceSink>) {
+        self.trace = sink;
+    }
+
+    /// Send each line the program prints to `sink` as soon as it is
+    /// printed, instead of collecting it for `output`/`take_output`.
+    pub fn set_output_sink(&mut self, sink: Option<OutputSink>) {
+        self.output_sink = sink;
+    }
+
+    /// Start collecting a fresh `Profile`, or stop profiling.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = enabled.then(Profile::new);
+    }
+
+    /// Counters collected since profiling was enabled.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Lines executed so far, when `VmOptions::coverage` is set.
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Set the program arguments returned by the `args()` builtin.
+    pub fn set_script_args(&mut self, args: Vec<String>) {
+        self.script_args = args;
+    }
+
+    /// Run like `run`, aborting with a `Timeout` error once `timeout` has
+    /// elapsed. The clock is sampled every few instructions, so a script may
+    /// overrun the deadline slightly before it is stopped.
+    pub fn run_with_deadline(&mut self, timeout: Duration) -> Result<ObjectRef, RuntimeError> {
+        self.deadline = Instant::now()
+            .checked_add(timeout)
+            .map(|deadline| (deadline, timeout));
+        let result = self.run();
+        self.deadline = None;
+        result
     }
 
     pub fn run(&mut self) -> Result<ObjectRef, RuntimeError> {
-        while !self.frames.is_empty() {
-            let (ip, instr_len) = {
-                let frame = self.current_frame().ok_or_else(|| {
-                    RuntimeError::new(
-                        RuntimeErrorType::UnsupportedOperation,
-                        "frame stack underflow",
-                        Position::default(),
-                    )
-                })?;
-                (frame.ip, frame.closure.function.instructions.len())
-            };
+        self.execute(None)
+            .map(|done| done.expect("unbounded execution always finishes"))
+    }
+
+    /// Execute a single instruction.
+    pub fn step(&mut self) -> StepOutcome {
+        self.run_for(1)
+    }
+
+    /// Execute at most `n_instructions` instructions. `Continue` means the
+    /// program is paused and can be resumed with another `step`/`run_for`
+    /// or finished with `run`.
+    pub fn run_for(&mut self, n_instructions: u64) -> StepOutcome {
+        match self.execute(Some(n_instructions)) {
+            Ok(Some(value)) => StepOutcome::Done(value),
+            Ok(None) => StepOutcome::Continue,
+            Err(err) => StepOutcome::Error(err),
+        }
+    }
+
+    /// Instruction pointer of the active frame, or `None` once finished.
+    pub fn ip(&self) -> Option<usize> {
+        self.current_frame().map(|frame| frame.ip)
+    }
+
+    /// Number of active call frames, including the root frame.
+    pub fn frame_depth(&self) -> usize {
+        self.frames.len()
+    }
 
-            if ip >= instr_len {
+    /// Name of the function executing in the active frame.
+    pub fn current_function_name(&self) -> Option<&str> {
+        self.current_frame().map(frame_name)
+    }
+
+    /// Source position of the next instruction to execute.
+    pub fn position(&self) -> Option<Position> {
+        self.ip().map(|ip| self.current_position(ip))
+    }
+
+    /// Value stack, bottom first.
+    pub fn stack(&self) -> &[ObjectRef] {
+        &self.stack
+    }
+
+    /// Local slots of the active function frame; empty at the top level.
+    pub fn locals(&self) -> &[ObjectRef] {
+        match self.current_frame() {
+            Some(frame) if self.frames.len() > 1 => {
+                let end =
+                    (frame.base_pointer + frame.closure.function.num_locals).min(self.stack.len());
+                &self.stack[frame.base_pointer.min(end)..end]
+            }
+            _ => &[],
+        }
+    }
+
+    /// Active frames, innermost first, in stack-trace form.
+    pub fn backtrace(&self) -> Vec<StackFrameInfo> {
+        self.ip()
+            .map(|ip| self.build_stack_trace(ip))
+            .unwrap_or_default()
+    }
+
+    /// Capture globals, stack, frames and output so execution can later be
+    /// rewound to this point with `restore`.
+    pub fn snapshot(&self) -> VmSnapshot {
+        VmSnapshot {
+            stack: self.stack.clone(),
+            globals: self.globals.clone(),
+            frames: self.frames.clone(),
+            last_popped: self.last_popped.clone(),
+            output: self.output.clone(),
+            instructions_executed: self.instructions_executed,
+            allocated_bytes: self.allocated_bytes,
+            allocations: self.allocations,
+            heap_estimate: self.heap_estimate,
+        }
+    }
+
+    /// Reset execution state to `snapshot`. A paused snapshot resumes with
+    /// `step`, `run_for` or `run`; a finished one stays finished.
+    pub fn restore(&mut self, snapshot: VmSnapshot) {
+        self.stack = snapshot.stack;
+        self.globals = snapshot.globals;
+        self.frames = snapshot.frames;
+        self.last_popped = snapshot.last_popped;
+        self.output = snapshot.output;
+        self.instructions_executed = snapshot.instructions_executed;
+        self.allocated_bytes = snapshot.allocated_bytes;
+        self.allocations = snapshot.allocations;
+        self.heap_estimate = snapshot.heap_estimate;
+    }
+
+    /// Run until the program finishes or, with `Some(limit)`, until `limit`
+    /// instructions have executed; `Ok(None)` means paused.
+    fn execute(&mut self, mut limit: Option<u64>) -> Result<Option<ObjectRef>, RuntimeError> {
+        // The active frame's code, ip and base pointer live in locals; they
+        // are written back before calls and reloaded after frame switches.
+        // When stepping, ip is also stored before every instruction so the
+        // accessors see the paused (or failing) instruction.
+        let Some((mut code, mut ip, mut base)) = self.load_frame() else {
+            return Ok(Some(Object::Null.rc()));
+        };
+        let instrumented = self.options.max_instructions.is_some()
+            || self.deadline.is_some()
+            || self.trace.is_some()
+            || self.profile.is_some()
+            || self.coverage.is_some();
+        #[cfg(feature = "jit")]
+        {
+            self.jit_active = self.options.jit && limit.is_none() && !instrumented;
+        }
+
+        loop {
+            if let Some(remaining) = limit.as_mut() {
+                self.store_ip(ip);
+                if *remaining == 0 {
+                    return Ok(None);
+                }
+                *remaining -= 1;
+            }
+
+            if ip >= code.len() {
+                self.store_ip(ip);
                 if self.frames.len() == 1 {
-                    return Ok(Object::Null.rc());
+                    return Ok(Some(Object::Null.rc()));
                 }
                 return Err(self.runtime_error(
                     ip,
@@ -91,7 +693,7 @@ impl Vm {
                 ));
             }
 
-            let opcode_byte = self.current_instructions()[ip];
+            let opcode_byte = code[ip];
             let Some(opcode) = Opcode::from_byte(opcode_byte) else {
                 return Err(self.runtime_error(
                     ip,
@@ -100,9 +702,14 @@ impl Vm {
                 ));
             };
 
+            if instrumented {
+                self.before_instruction(ip, opcode)?;
+            }
+            self.instructions_executed += 1;
+
             match opcode {
                 Opcode::Constant => {
-                    let idx = self.read_u16_operand(ip)?;
+                    let idx = self.operand_u16(&code, ip)?;
                     let Some(constant) = self.chunk.constants.get(idx).cloned() else {
                         return Err(self.runtime_error(
                             ip,
@@ -111,27 +718,43 @@ impl Vm {
                         ));
                     };
                     self.push(constant, ip)?;
-                    self.advance_ip(3)?;
+                    ip += 3;
+                }
+                Opcode::ConstInt => {
+                    let value = self.operand_u16(&code, ip)? as u16 as i16;
+                    let value = self.small_int(i64::from(value));
+                    self.push(value, ip)?;
+                    ip += 3;
+                }
+                Opcode::Const0 => {
+                    let value = self.small_int(0);
+                    self.push(value, ip)?;
+                    ip += 1;
+                }
+                Opcode::Const1 => {
+                    let value = self.small_int(1);
+                    self.push(value, ip)?;
+                    ip += 1;
                 }
                 Opcode::True => {
-                    self.push(Object::Boolean(true).rc(), ip)?;
-                    self.advance_ip(1)?;
+                    self.push(self.true_obj.clone(), ip)?;
+                    ip += 1;
                 }
                 Opcode::False => {
-                    self.push(Object::Boolean(false).rc(), ip)?;
-                    self.advance_ip(1)?;
+                    self.push(self.false_obj.clone(), ip)?;
+                    ip += 1;
                 }
                 Opcode::Null => {
-                    self.push(Object::Null.rc(), ip)?;
-                    self.advance_ip(1)?;
+                    self.push(self.null.clone(), ip)?;
+                    ip += 1;
                 }
                 Opcode::Pop => {
                     self.pop(ip)?;
-                    self.advance_ip(1)?;
+                    ip += 1;
                 }
                 Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div => {
                     self.exec_binary_arithmetic(opcode, ip)?;
-                    self.advance_ip(1)?;
+                    ip += 1;
                 }
                 Opcode::Neg => {
                     let operand = self.pop(ip)?;
@@ -147,43 +770,43 @@ impl Vm {
                         }
                     };
                     self.push(result, ip)?;
-                    self.advance_ip(1)?;
+                    ip += 1;
                 }
                 Opcode::Bang => {
                     let operand = self.pop(ip)?;
-                    self.push(Object::Boolean(!operand.as_ref().is_truthy()).rc(), ip)?;
-                    self.advance_ip(1)?;
+                    self.push(self.boolean(!operand.as_ref().is_truthy()), ip)?;
+                    ip += 1;
                 }
                 Opcode::Eq | Opcode::Ne | Opcode::Lt | Opcode::Gt | Opcode::Le | Opcode::Ge => {
                     self.exec_comparison(opcode, ip)?;
-                    self.advance_ip(1)?;
+                    ip += 1;
                 }
                 Opcode::Jump => {
-                    let target = self.read_u16_operand(ip)?;
-                    self.ensure_jump_target(ip, target)?;
-                    self.set_ip(target)?;
+                    let target = self.operand_u16(&code, ip)?;
+                    self.ensure_jump_target(&code, ip, target)?;
+                    ip = target;
                 }
                 Opcode::JumpIfFalse => {
-                    let target = self.read_u16_operand(ip)?;
-                    self.ensure_jump_target(ip, target)?;
+                    let target = self.operand_u16(&code, ip)?;
+                    self.ensure_jump_target(&code, ip, target)?;
                     let condition = self.peek(ip)?;
                     if !condition.as_ref().is_truthy() {
-                        self.set_ip(target)?;
+                        ip = target;
                     } else {
-                        self.advance_ip(3)?;
+                        ip += 3;
                     }
                 }
                 Opcode::SetGlobal => {
-                    let idx = self.read_u16_operand(ip)?;
+                    let idx = self.operand_u16(&code, ip)?;
                     let value = self.pop(ip)?;
                     while self.globals.len() <= idx {
                         self.globals.push(Object::Null.rc());
                     }
                     self.globals[idx] = value;
-                    self.advance_ip(3)?;
+                    ip += 3;
                 }
                 Opcode::GetGlobal => {
-                    let idx = self.read_u16_operand(ip)?;
+                    let idx = self.operand_u16(&code, ip)?;
                     let Some(value) = self.globals.get(idx).cloned() else {
                         return Err(self.runtime_error(
                             ip,
@@ -192,11 +815,10 @@ impl Vm {
                         ));
                     };
                     self.push(value, ip)?;
-                    self.advance_ip(3)?;
+                    ip += 3;
                 }
                 Opcode::GetLocal => {
-                    let idx = self.read_u8_operand(ip)?;
-                    let base = self.current_frame_required(ip)?.base_pointer;
+                    let idx = self.code_byte(&code, ip + 1, ip)?;
                     let slot = base + idx;
                     let Some(value) = self.stack.get(slot).cloned() else {
                         return Err(self.runtime_error(
@@ -206,12 +828,11 @@ impl Vm {
                         ));
                     };
                     self.push(value, ip)?;
-                    self.advance_ip(2)?;
+                    ip += 2;
                 }
                 Opcode::SetLocal => {
-                    let idx = self.read_u8_operand(ip)?;
+                    let idx = self.code_byte(&code, ip + 1, ip)?;
                     let value = self.pop(ip)?;
-                    let base = self.current_frame_required(ip)?.base_pointer;
                     let slot = base + idx;
                     if slot >= self.stack.len() {
                         return Err(self.runtime_error(
@@ -221,10 +842,10 @@ impl Vm {
                         ));
                     }
                     self.stack[slot] = value;
-                    self.advance_ip(2)?;
+                    ip += 2;
                 }
                 Opcode::GetBuiltin => {
-                    let idx = self.read_u8_operand(ip)?;
+                    let idx = self.code_byte(&code, ip + 1, ip)?;
                     let Some(name) = builtin_name_at(idx) else {
                         return Err(self.runtime_error(
                             ip,
@@ -239,10 +860,10 @@ impl Vm {
                         .rc(),
                         ip,
                     )?;
-                    self.advance_ip(2)?;
+                    ip += 2;
                 }
                 Opcode::GetFree => {
-                    let idx = self.read_u8_operand(ip)?;
+                    let idx = self.code_byte(&code, ip + 1, ip)?;
                     let Some(value) = self
                         .current_frame_required(ip)?
                         .closure
@@ -257,16 +878,16 @@ impl Vm {
                         ));
                     };
                     self.push(value, ip)?;
-                    self.advance_ip(2)?;
+                    ip += 2;
                 }
                 Opcode::CurrentClosure => {
-                    let closure = Rc::clone(&self.current_frame_required(ip)?.closure);
+                    let closure = Shared::clone(&self.current_frame_required(ip)?.closure);
                     self.push(Object::Closure(closure).rc(), ip)?;
-                    self.advance_ip(1)?;
+                    ip += 1;
                 }
                 Opcode::Closure => {
-                    let const_idx = self.read_u16_operand(ip)?;
-                    let free_count = self.read_u8_at(ip + 3, ip)?;
+                    let const_idx = self.operand_u16(&code, ip)?;
+                    let free_count = self.code_byte(&code, ip + 3, ip)?;
                     let Some(constant) = self.chunk.constants.get(const_idx).cloned() else {
                         return Err(self.runtime_error(
                             ip,
@@ -275,7 +896,7 @@ impl Vm {
                         ));
                     };
                     let function = match constant.as_ref() {
-                        Object::CompiledFunction(f) => Rc::clone(f),
+                        Object::CompiledFunction(f) => Shared::clone(f),
                         other => {
                             return Err(self.runtime_error(
                                 ip,
@@ -299,28 +920,31 @@ impl Vm {
                     let free = self.stack[start..].to_vec();
                     self.stack.truncate(start);
 
-                    let closure = Rc::new(ClosureObject { function, free });
+                    let closure = Shared::new(ClosureObject { function, free });
                     self.push(Object::Closure(closure).rc(), ip)?;
-                    self.advance_ip(4)?;
+                    ip += 4;
                 }
                 Opcode::Call => {
-                    let argc = self.read_u8_operand(ip)?;
-                    self.advance_ip(2)?;
+                    let argc = self.code_byte(&code, ip + 1, ip)?;
+                    self.store_ip(ip + 2);
                     self.exec_call(argc, ip)?;
+                    (code, ip, base) = self.load_frame().expect("caller frame remains");
                 }
                 Opcode::ReturnValue => {
                     let value = self.pop(ip)?;
                     if let Some(final_value) = self.return_from_frame(value)? {
-                        return Ok(final_value);
+                        return Ok(Some(final_value));
                     }
+                    (code, ip, base) = self.load_frame().expect("caller frame remains");
                 }
                 Opcode::Return => {
-                    if let Some(final_value) = self.return_from_frame(Object::Null.rc())? {
-                        return Ok(final_value);
+                    if let Some(final_value) = self.return_from_frame(self.null.clone())? {
+                        return Ok(Some(final_value));
                     }
+                    (code, ip, base) = self.load_frame().expect("caller frame remains");
                 }
                 Opcode::Array => {
-                    let count = self.read_u16_operand(ip)?;
+                    let count = self.operand_u16(&code, ip)?;
                     if self.stack.len() < count {
                         return Err(self.runtime_error(
                             ip,
@@ -331,11 +955,13 @@ impl Vm {
                     let start = self.stack.len() - count;
                     let items = self.stack[start..].to_vec();
                     self.stack.truncate(start);
-                    self.push(Object::Array(items).rc(), ip)?;
-                    self.advance_ip(3)?;
+                    let array = Object::Array(items).rc();
+                    self.charge_allocation(&array, ip)?;
+                    self.push(array, ip)?;
+                    ip += 3;
                 }
                 Opcode::Hash => {
-                    let pair_count = self.read_u16_operand(ip)?;
+                    let pair_count = self.operand_u16(&code, ip)?;
                     let value_count = pair_count * 2;
                     if self.stack.len() < value_count {
                         return Err(self.runtime_error(
@@ -348,28 +974,27 @@ impl Vm {
                     let values = self.stack[start..].to_vec();
                     self.stack.truncate(start);
 
-                    let mut pairs = Vec::with_capacity(pair_count);
-                    for i in 0..pair_count {
-                        let key = values[i * 2].clone();
-                        let value = values[i * 2 + 1].clone();
-                        if key.as_ref().hash_key().is_none() {
-                            return Err(self.runtime_error(
-                                ip,
-                                RuntimeErrorType::Unhashable,
-                                format!("unusable as hash key: {}", key.as_ref().type_name()),
-                            ));
-                        }
-                        pairs.push((key, value));
-                    }
-                    self.push(Object::Hash(pairs).rc(), ip)?;
-                    self.advance_ip(3)?;
+                    let pairs = values
+                        .chunks_exact(2)
+                        .map(|kv| (kv[0].clone(), kv[1].clone()));
+                    let hash = HashObject::from_pairs(pairs).map_err(|key| {
+                        self.runtime_error(
+                            ip,
+                            RuntimeErrorType::Unhashable,
+                            format!("unusable as hash key: {}", key.as_ref().type_name()),
+                        )
+                    })?;
+                    let hash = Object::Hash(hash).rc();
+                    self.charge_allocation(&hash, ip)?;
+                    self.push(hash, ip)?;
+                    ip += 3;
                 }
                 Opcode::Index => {
                     let index = self.pop(ip)?;
                     let left = self.pop(ip)?;
                     let out = self.exec_index(left, index, ip)?;
                     self.push(out, ip)?;
-                    self.advance_ip(1)?;
+                    ip += 1;
                 }
                 Opcode::InvalidBreak => {
                     return Err(self.runtime_error(
@@ -385,17 +1010,94 @@ impl Vm {
                         "continue used outside of loop",
                     ));
                 }
-                Opcode::Nop => {
+                Opcode::AddConstants => {
+                    let a = self.operand_u16(&code, ip)?;
+                    let b = self.operand_u16(&code, ip + 2)?;
+                    for idx in [a, b] {
+                        let Some(constant) = self.chunk.constants.get(idx).cloned() else {
+                            return Err(self.runtime_error(
+                                ip,
+                                RuntimeErrorType::UnsupportedOperation,
+                                format!("constant index out of bounds: {idx}"),
+                            ));
+                        };
+                        self.push(constant, ip)?;
+                    }
+                    // Errors are reported at the fused `Add` (now padding).
+                    self.exec_binary_arithmetic(Opcode::Add, ip + 6)?;
+                    ip += 7;
+                }
+                Opcode::AddLocals => {
+                    for idx in [
+                        self.code_byte(&code, ip + 1, ip)?,
+                        self.code_byte(&code, ip + 2, ip)?,
+                    ] {
+                        let Some(value) = self.stack.get(base + idx).cloned() else {
+                            return Err(self.runtime_error(
+                                ip,
+                                RuntimeErrorType::UnsupportedOperation,
+                                format!("local slot out of bounds: {idx}"),
+                            ));
+                        };
+                        self.push(value, ip)?;
+                    }
+                    self.exec_binary_arithmetic(Opcode::Add, ip + 4)?;
+                    ip += 5;
+                }
+                Opcode::JumpIfFalsePop => {
+                    let target = self.operand_u16(&code, ip)?;
+                    self.ensure_jump_target(&code, ip, target)?;
+                    if !self.peek(ip)?.as_ref().is_truthy() {
+                        ip = target;
+                    } else {
+                        self.pop(ip)?;
+                        ip += 4;
+                    }
+                }
+                // Padding left by superinstruction fusion; fused
+                // instructions step over it, but bytecode loaded from a
+                // `.mkc` file may still land on it.
+                Opcode::Nop => ip += 1,
+            }
+        }
+    }
+
+    /// Budget, deadline, trace, profile and coverage hooks; only called when at least
+    /// one of them is enabled so the plain dispatch path stays lean.
+    fn before_instruction(&mut self, ip: usize, opcode: Opcode) -> Result<(), RuntimeError> {
+        if let Some(limit) = self.options.max_instructions {
+            if self.instructions_executed >= limit {
+                return Err(self.runtime_error(
+                    ip,
+                    RuntimeErrorType::BudgetExceeded,
+                    format!("instruction budget of {limit} exhausted"),
+                ));
+            }
+        }
+        if let Some(sink) = &self.trace {
+            sink.0.emit(&self.trace_line(ip, opcode));
+        }
+        if let Some(profile) = &mut self.profile {
+            profile.record_opcode(opcode);
+        }
+        if let (Some(coverage), Some(frame)) = (&mut self.coverage, self.frames.last()) {
+            coverage.record(&frame.closure.function, ip);
+        }
+        if self
+            .instructions_executed
+            .is_multiple_of(DEADLINE_CHECK_INTERVAL)
+        {
+            if let Some((deadline, timeout)) = self.deadline {
+                if Instant::now() >= deadline {
                     return Err(self.runtime_error(
                         ip,
-                        RuntimeErrorType::UnsupportedOperation,
-                        "opcode not implemented in step 17: Nop",
+                        RuntimeErrorType::Timeout,
+                        format!("execution timed out after {} ms", timeout.as_millis()),
                     ));
                 }
             }
         }
-
-        Ok(Object::Null.rc())
+        Ok(())
     }
 
     pub fn last_popped(&self) -> Option<ObjectRef> {
@@ -406,6 +1108,15 @@ impl Vm {
         &self.globals
     }
 
+    /// Store `value` in global slot `index`, as `SetGlobal` would; slots
+    /// before it that were never set hold `null`.
+    pub fn set_global(&mut self, index: usize, value: ObjectRef) {
+        while self.globals.len() <= index {
+            self.globals.push(Object::Null.rc());
+        }
+        self.globals[index] = value;
+    }
+
     pub fn output(&self) -> &[String] {
         &self.output
     }
@@ -425,7 +1136,7 @@ impl Vm {
         let callee_index = self.stack.len() - 1 - argc;
         let callee = self.stack[callee_index].clone();
         match callee.as_ref() {
-            Object::Closure(closure) => self.call_closure(Rc::clone(closure), argc, ip),
+            Object::Closure(closure) => self.call_closure(Shared::clone(closure), argc, ip),
             Object::Builtin(builtin) => self.call_builtin(&builtin.name, argc, callee_index, ip),
             other => Err(self.runtime_error(
                 ip,
@@ -437,7 +1148,7 @@ impl Vm {
 
     fn call_closure(
         &mut self,
-        closure: Rc<ClosureObject>,
+        closure: Shared<ClosureObject>,
         argc: usize,
         ip: usize,
     ) -> Result<(), RuntimeError> {
@@ -455,11 +1166,33 @@ impl Vm {
             ));
         }
 
+        if self.call_depth() >= self.options.max_call_depth {
+            return Err(self.stack_overflow_error(ip));
+        }
+
         let callee_index = self.stack.len() - 1 - argc;
+        #[cfg(feature = "jit")]
+        if self.jit_active {
+            if let Some(value) = self.jit.call(
+                &closure.function,
+                &self.chunk.constants,
+                &self.stack[callee_index + 1..],
+                self.call_depth() + 1,
+            ) {
+                self.stack.truncate(callee_index);
+                let value = match value {
+                    Object::Boolean(value) => self.boolean(value),
+                    other => other.rc(),
+                };
+                return self.push(value, ip);
+            }
+        }
         let base_pointer = callee_index + 1;
         let required = base_pointer + closure.function.num_locals;
-        while self.stack.len() < required {
-            self.stack.push(Object::Null.rc());
+        if self.stack.len() < required {
+            let null = self.null.clone();
+            self.stack.resize(required, null);
+            self.peak_stack_depth = self.peak_stack_depth.max(required);
         }
         let call_pos = self.current_position(ip);
         self.push_frame(Frame::new(closure, base_pointer, call_pos, argc));
@@ -473,15 +1206,211 @@ impl Vm {
         callee_index: usize,
         ip: usize,
     ) -> Result<(), RuntimeError> {
+        if name == "eval" {
+            return self.call_eval(argc, callee_index, ip);
+        }
+
         let args_start = callee_index + 1;
         let args_end = args_start + argc;
         let args = self.stack[args_start..args_end].to_vec();
-        let result = execute_builtin(name, &args, &mut self.output)
+        let mut ctx = BuiltinContext {
+            output: &mut self.output,
+            script_args: &self.script_args,
+            strings: &mut self.strings,
+        };
+        let result = execute_builtin(name, &args, &mut ctx)
             .map_err(|err| self.runtime_error(ip, err.error_type, err.message))?;
+        if let Some(sink) = &self.output_sink {
+            for line in self.output.drain(..) {
+                sink.0.emit(&line);
+            }
+        }
+        // Shared results (`first(arr)`, interned strings) are charged too;
+        // measuring the live heap corrects any overcount.
+        self.charge_allocation(&result, ip)?;
         self.stack.truncate(callee_index);
         self.push(result, ip)
     }
 
+    /// Compile and run a source string in a fresh child VM.
+    ///
+    /// The child shares no bindings with the parent; its `puts` output is
+    /// appended to the parent's output and its errors are re-raised at the
+    /// `eval` call site.
+    fn call_eval(
+        &mut self,
+        argc: usize,
+        callee_index: usize,
+        ip: usize,
+    ) -> Result<(), RuntimeError> {
+        if argc != 1 {
+            let err = BuiltinError::wrong_arg_count("eval", 1, argc);
+            return Err(self.runtime_error(ip, err.error_type, err.message));
+        }
+        if self.call_depth() >= self.options.max_call_depth {
+            return Err(self.stack_overflow_error(ip));
+        }
+        if self.eval_depth >= MAX_EVAL_DEPTH {
+            return Err(self.runtime_error(
+                ip,
+                RuntimeErrorType::StackOverflow,
+                format!("maximum eval nesting depth of {MAX_EVAL_DEPTH} exceeded"),
+            ));
+        }
+        let source = match self.stack[callee_index + 1].as_ref() {
+            Object::String(source) => source.to_string(),
+            other => {
+                let err = BuiltinError::invalid_arg_type("eval", "STRING", other.type_name());
+                return Err(self.runtime_error(ip, err.error_type, err.message));
+            }
+        };
+        let mut parser = Parser::new(Lexer::new(source));
+        let program = parser.parse_program();
+        if !parser.errors().is_empty() {
+            let rendered = parser
+                .errors()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; ");
+            return Err(self.runtime_error(
+                ip,
+                RuntimeErrorType::EvalError,
+                format!("eval parse error: {rendered}"),
+            ));
+        }
+
+        let mut compiler = Compiler::new();
+        if let Err(errors) = compiler.compile_program(&program) {
+            let rendered = errors
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; ");
+            return Err(self.runtime_error(
+                ip,
+                RuntimeErrorType::EvalError,
+                format!("eval compile error: {rendered}"),
+            ));
+        }
+
+        let mut child = self.child_vm(compiler.into_bytecode());
+        child.profile = self.profile.take();
+        let result = child.run();
+        self.profile = child.profile.take();
+        self.output.append(&mut child.output);
+        self.instructions_executed = child.instructions_executed;
+        self.allocated_bytes = child.allocated_bytes;
+        self.allocations = child.allocations;
+        self.heap_estimate = child.heap_estimate;
+        self.peak_stack_depth = self
+            .peak_stack_depth
+            .max(self.stack.len() + child.peak_stack_depth);
+        self.peak_frame_depth = self
+            .peak_frame_depth
+            .max(self.frames.len() + child.peak_frame_depth);
+        let value = result.map_err(|err| {
+            self.runtime_error(
+                ip,
+                err.error_type,
+                format!("in eval at {}: {}", err.pos, err.message),
+            )
+        })?;
+
+        self.stack.truncate(callee_index);
+        self.push(value, ip)
+    }
+
+    /// Build a VM for nested execution that inherits this VM's configuration.
+    fn child_vm(&self, chunk: Chunk) -> Vm {
+        let options = VmOptions {
+            coverage: false,
+            ..self.options.clone()
+        };
+        let mut child = Vm::with_options(chunk, options);
+        child.script_args = self.script_args.clone();
+        child.instructions_executed = self.instructions_executed;
+        child.deadline = self.deadline;
+        child.allocated_bytes = self.allocated_bytes;
+        child.allocations = self.allocations;
+        child.heap_estimate = self.heap_estimate;
+        if self.options.max_memory_bytes.is_some() {
+            child.outer_heap_bytes = self.live_heap_bytes(None);
+        }
+        child.outer_frames = self.call_depth();
+        child.eval_depth = self.eval_depth + 1;
+        child.trace = self.trace.clone();
+        child.output_sink = self.output_sink.clone();
+        child
+    }
+
+    fn trace_line(&self, ip: usize, opcode: Opcode) -> String {
+        let def = lookup_definition(opcode);
+        let operands = read_operands(def, &self.current_instructions()[ip + 1..])
+            .map(|(operands, _)| {
+                operands
+                    .iter()
+                    .map(|operand| format!(" {operand}"))
+                    .collect::<String>()
+            })
+            .unwrap_or_else(|_| " <truncated>".to_string());
+        let function = self
+            .current_frame()
+            .and_then(|frame| frame.closure.function.name.clone())
+            .unwrap_or_else(|| "<anonymous>".to_string());
+        format!(
+            "{function} {ip:04} {}{operands} stack={} @{}",
+            def.name,
+            self.stack.len(),
+            self.current_position(ip)
+        )
+    }
+
+    /// Account for a newly created value, enforcing `max_memory_bytes`.
+    /// Once the estimate passes the limit, the reachable heap is measured,
+    /// and only a live heap over the limit is an error.
+    fn charge_allocation(&mut self, value: &ObjectRef, ip: usize) -> Result<(), RuntimeError> {
+        let size = value.heap_size();
+        if size == 0 {
+            return Ok(());
+        }
+        self.allocated_bytes = self.allocated_bytes.saturating_add(size);
+        self.allocations += 1;
+        self.heap_estimate = self.heap_estimate.saturating_add(size);
+        let Some(limit) = self.options.max_memory_bytes else {
+            return Ok(());
+        };
+        if self.heap_estimate > limit {
+            self.heap_estimate = self.live_heap_bytes(Some(value));
+            if self.heap_estimate > limit {
+                return Err(self.runtime_error(
+                    ip,
+                    RuntimeErrorType::OutOfMemory,
+                    format!("memory limit of {limit} bytes exceeded"),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Approximate bytes of the strings, arrays and hashes reachable from
+    /// the stack, globals and running closures, plus `extra` and the heap
+    /// of the VMs this one is nested in by `eval`.
+    fn live_heap_bytes(&self, extra: Option<&ObjectRef>) -> usize {
+        let mut heap = HeapMeasure::new();
+        let roots = self
+            .stack
+            .iter()
+            .chain(&self.globals)
+            .chain(&self.last_popped)
+            .chain(extra)
+            .chain(self.frames.iter().flat_map(|frame| &frame.closure.free));
+        for value in roots {
+            heap.add(value);
+        }
+        heap.total() + self.outer_heap_bytes
+    }
+
     fn return_from_frame(&mut self, value: ObjectRef) -> Result<Option<ObjectRef>, RuntimeError> {
         let Some(frame) = self.pop_frame() else {
             return Err(RuntimeError::new(
@@ -502,8 +1431,17 @@ impl Vm {
         Ok(None)
     }
 
+    fn small_int(&mut self, value: i64) -> ObjectRef {
+        match usize::try_from(value) {
+            Ok(slot) if slot < SMALL_INT_CACHE_SIZE => self.small_ints[slot]
+                .get_or_insert_with(|| Object::Integer(value).rc())
+                .clone(),
+            _ => Object::Integer(value).rc(),
+        }
+    }
+
     fn exec_index(
-        &self,
+        &mut self,
         left: ObjectRef,
         index: ObjectRef,
         ip: usize,
@@ -526,7 +1464,17 @@ impl Vm {
                     format!("array index must be INTEGER, got {}", other.type_name()),
                 )),
             },
-            Object::Hash(pairs) => {
+            Object::Hash(hash) => {
+                // The cached slot is only a hint: it is used when the pair
+                // stored there has this exact key, which holds for hashes
+                // built by the same literal and indexed with constant keys.
+                let cache = ip & (INDEX_CACHE_SIZE - 1);
+                if let Some((key, value)) = hash.pair_at(self.index_cache[cache]) {
+                    if same_key(key, &index) {
+                        return Ok(value.clone());
+                    }
+                }
+
                 let Some(target_key) = index.as_ref().hash_key() else {
                     return Err(self.runtime_error(
                         ip,
@@ -535,12 +1483,13 @@ impl Vm {
                     ));
                 };
 
-                for (key, value) in pairs.iter().rev() {
-                    if key.as_ref().hash_key() == Some(target_key.clone()) {
-                        return Ok(value.clone());
-                    }
+                match hash.slot_of(&target_key).and_then(|slot| {
+                    self.index_cache[cache] = slot;
+                    hash.pair_at(slot)
+                }) {
+                    Some((_, value)) => Ok(value.clone()),
+                    None => Ok(Object::Null.rc()),
                 }
-                Ok(Object::Null.rc())
             }
             other => Err(self.runtime_error(
                 ip,
@@ -559,6 +1508,7 @@ impl Vm {
             ));
         }
         self.stack.push(obj);
+        self.peak_stack_depth = self.peak_stack_depth.max(self.stack.len());
         Ok(())
     }
 
@@ -584,48 +1534,33 @@ impl Vm {
         })
     }
 
-    fn read_u8_at(&self, byte_index: usize, ip: usize) -> Result<usize, RuntimeError> {
-        let value = self.current_instructions().get(byte_index).ok_or_else(|| {
+    fn code_byte(&self, code: &[u8], byte_index: usize, ip: usize) -> Result<usize, RuntimeError> {
+        code.get(byte_index).map(|b| *b as usize).ok_or_else(|| {
             self.runtime_error(
                 ip,
                 RuntimeErrorType::UnsupportedOperation,
                 format!("truncated instruction at offset {ip}"),
             )
-        })?;
-        Ok(*value as usize)
-    }
-
-    fn read_u8_operand(&self, ip: usize) -> Result<usize, RuntimeError> {
-        self.read_u8_at(ip + 1, ip)
+        })
     }
 
-    fn read_u16_operand(&self, ip: usize) -> Result<usize, RuntimeError> {
-        let hi = self.current_instructions().get(ip + 1).ok_or_else(|| {
-            self.runtime_error(
-                ip,
-                RuntimeErrorType::UnsupportedOperation,
-                format!("truncated instruction at offset {ip}"),
-            )
-        })?;
-        let lo = self.current_instructions().get(ip + 2).ok_or_else(|| {
-            self.runtime_error(
-                ip,
-                RuntimeErrorType::UnsupportedOperation,
-                format!("truncated instruction at offset {ip}"),
-            )
-        })?;
-        Ok(u16::from_be_bytes([*hi, *lo]) as usize)
+    fn operand_u16(&self, code: &[u8], ip: usize) -> Result<usize, RuntimeError> {
+        let hi = self.code_byte(code, ip + 1, ip)?;
+        let lo = self.code_byte(code, ip + 2, ip)?;
+        Ok((hi << 8) | lo)
     }
 
-    fn ensure_jump_target(&self, ip: usize, target: usize) -> Result<(), RuntimeError> {
-        if target > self.current_instructions().len() {
+    fn ensure_jump_target(
+        &self,
+        code: &[u8],
+        ip: usize,
+        target: usize,
+    ) -> Result<(), RuntimeError> {
+        if target > code.len() {
             return Err(self.runtime_error(
                 ip,
                 RuntimeErrorType::UnsupportedOperation,
-                format!(
-                    "jump target out of bounds: {target} (len {})",
-                    self.current_instructions().len()
-                ),
+                format!("jump target out of bounds: {target} (len {})", code.len()),
             ));
         }
         Ok(())
@@ -648,7 +1583,9 @@ impl Vm {
             }
             (Object::Integer(a), Object::Integer(b), Opcode::Div) => Object::Integer(a / b).rc(),
             (Object::String(a), Object::String(b), Opcode::Add) => {
-                Object::String(format!("{a}{b}")).rc()
+                let joined = Object::String(self.strings.intern(&format!("{a}{b}"))).rc();
+                self.charge_allocation(&joined, ip)?;
+                joined
             }
             (Object::String(_), Object::String(_), _) => {
                 return Err(self.runtime_error(
@@ -748,7 +1685,7 @@ impl Vm {
             }
         };
 
-        self.push(Object::Boolean(value).rc(), ip)
+        self.push(self.boolean(value), ip)
     }
 
     fn current_frame(&self) -> Option<&Frame> {
@@ -778,51 +1715,70 @@ impl Vm {
             .instructions
     }
 
-    fn advance_ip(&mut self, delta: usize) -> Result<(), RuntimeError> {
-        let frame = self.current_frame_mut().ok_or_else(|| {
-            RuntimeError::new(
-                RuntimeErrorType::UnsupportedOperation,
-                "frame stack underflow",
-                Position::default(),
-            )
-        })?;
-        frame.ip += delta;
-        Ok(())
+    fn boolean(&self, value: bool) -> ObjectRef {
+        if value {
+            self.true_obj.clone()
+        } else {
+            self.false_obj.clone()
+        }
     }
 
-    fn set_ip(&mut self, ip: usize) -> Result<(), RuntimeError> {
-        let frame = self.current_frame_mut().ok_or_else(|| {
-            RuntimeError::new(
-                RuntimeErrorType::UnsupportedOperation,
-                "frame stack underflow",
-                Position::default(),
+    /// Code, ip and base pointer of the active frame.
+    fn load_frame(&self) -> Option<(Shared<[u8]>, usize, usize)> {
+        self.current_frame().map(|frame| {
+            (
+                Shared::clone(&frame.closure.function.instructions),
+                frame.ip,
+                frame.base_pointer,
             )
-        })?;
-        frame.ip = ip;
-        Ok(())
+        })
     }
 
-    fn push_frame(&mut self, frame: Frame) {
+    fn store_ip(&mut self, ip: usize) {
+        if let Some(frame) = self.current_frame_mut() {
+            frame.ip = ip;
+        }
+    }
+
+    fn push_frame(&mut self, mut frame: Frame) {
+        if let Some(profile) = &mut self.profile {
+            if profile.enter_function(frame_name(&frame)) {
+                frame.entered_at = Some(Instant::now());
+            }
+        }
         self.frames.push(frame);
+        self.peak_frame_depth = self.peak_frame_depth.max(self.frames.len());
     }
 
     fn pop_frame(&mut self) -> Option<Frame> {
-        self.frames.pop()
+        let frame = self.frames.pop()?;
+        // The root frame is never pushed through `push_frame`.
+        if !self.frames.is_empty() {
+            if let Some(profile) = &mut self.profile {
+                let elapsed = frame.entered_at.map(|at| at.elapsed());
+                profile.exit_function(frame_name(&frame), elapsed);
+            }
+        }
+        Some(frame)
     }
 
     fn current_position(&self, ip: usize) -> Position {
+        self.current_span(ip).pos
+    }
+
+    fn current_span(&self, ip: usize) -> Span {
         let Some(frame) = self.current_frame() else {
-            return Position::default();
+            return Span::default();
         };
-        frame
-            .closure
-            .function
-            .positions
-            .iter()
-            .take_while(|(offset, _)| *offset <= ip)
-            .last()
-            .map(|(_, pos)| *pos)
-            .unwrap_or_default()
+        let function = &frame.closure.function;
+        if let Some((cached_fn, cached_ip, span)) = self.position_cache.borrow().as_ref() {
+            if *cached_ip == ip && Shared::ptr_eq(cached_fn, function) {
+                return *span;
+            }
+        }
+        let span = span_at(&function.positions, ip).unwrap_or_default();
+        *self.position_cache.borrow_mut() = Some((Shared::clone(function), ip, span));
+        span
     }
 
     fn runtime_error(
@@ -831,9 +1787,39 @@ impl Vm {
         error_type: RuntimeErrorType,
         message: impl Into<String>,
     ) -> RuntimeError {
-        let pos = self.current_position(ip);
+        let span = self.current_span(ip);
         let stack = self.build_stack_trace(ip);
-        RuntimeError::new(error_type, message, pos).with_stack(stack)
+        RuntimeError::new(error_type, message, span.pos)
+            .with_span(span)
+            .with_stack(stack)
+    }
+
+    /// Active frames, counting those of the VMs this one is nested in by
+    /// `eval`.
+    fn call_depth(&self) -> usize {
+        self.outer_frames + self.frames.len()
+    }
+
+    /// Build a `StackOverflow` error whose trace keeps only the innermost
+    /// frames plus the root frame.
+    fn stack_overflow_error(&self, ip: usize) -> RuntimeError {
+        let mut stack = self.build_stack_trace(ip);
+        let omitted = stack.len().saturating_sub(OVERFLOW_TRACE_FRAMES + 1);
+        if omitted > 0 {
+            let root = stack.pop().expect("trace always has a root frame");
+            stack.truncate(OVERFLOW_TRACE_FRAMES);
+            stack.push(root);
+        }
+        let message = format!(
+            "maximum call depth of {} exceeded ({} frames omitted from trace)",
+            self.options.max_call_depth, omitted
+        );
+        RuntimeError::new(
+            RuntimeErrorType::StackOverflow,
+            message,
+            self.current_position(ip),
+        )
+        .with_stack(stack)
     }
 
     fn build_stack_trace(&self, current_ip: usize) -> Vec<StackFrameInfo> {
@@ -846,15 +1832,7 @@ impl Vm {
                 .clone()
                 .unwrap_or_else(|| "<anonymous>".to_string());
             let pos = if idx == self.frames.len() - 1 {
-                frame
-                    .closure
-                    .function
-                    .positions
-                    .iter()
-                    .take_while(|(offset, _)| *offset <= current_ip)
-                    .last()
-                    .map(|(_, pos)| *pos)
-                    .unwrap_or_default()
+                self.current_position(current_ip)
             } else {
                 frame.call_site_pos
             };
@@ -863,3 +1841,20 @@ impl Vm {
         out
     }
 }
+
+/// Cheap key identity check for the `Index` inline cache.
+fn same_key(cached: &ObjectRef, key: &ObjectRef) -> bool {
+    match (cached.as_ref(), key.as_ref()) {
+        (Object::Integer(a), Object::Integer(b)) => a == b,
+        _ => Shared::ptr_eq(cached, key),
+    }
+}
+
+fn frame_name(frame: &Frame) -> &str {
+    frame
+        .closure
+        .function
+        .name
+        .as_deref()
+        .unwrap_or("<anonymous>")
+}
@@ -0,0 +1,1246 @@
+//! WebAssembly backend (`monkey wasm`).
+//!
+//! Lowers a statically typed subset of Monkey straight from the AST to a
+//! standalone module. Values are integers (`i64`), booleans (`i32`),
+//! strings (`i32` pointer into linear memory) and null (`i32` zero); every
+//! variable and function parameter must keep one type for the whole
+//! program. Top-level `let name = fn(...) { ... }` functions are
+//! specialized per argument types, and may be recursive. Arrays, hashes,
+//! closures and first-class functions are rejected with a `CompileError`;
+//! a function is only lowered (and checked) once something calls it.
+//!
+//! The module exports `main` and `memory` and imports its output from the
+//! `monkey` namespace:
+//!
+//! ```text
+//! print_int(i64)   append an integer to the current line
+//! print_str(i32)   append the string at this address
+//! newline()        finish the current line
+//! ```
+//!
+//! A string at address `p` is laid out as a little-endian `u32` byte
+//! length, a `u32` character count, then its UTF-8 bytes from `p + 8`.
+//!
+//! `main` runs the program and then prints its result, so a host that
+//! prints each finished line reproduces `monkey run`. Runtime errors the VM
+//! would report (such as division by zero) trap instead.
+
+use std::collections::HashMap;
+
+use crate::ast::{BlockStatement, Expression, Identifier, Program, Statement};
+use crate::compiler::{CompileError, CompileErrorKind};
+use crate::position::Position;
+
+const MAGIC: &[u8] = b"\0asm";
+const VERSION: &[u8] = &[1, 0, 0, 0];
+
+const IMPORT_PRINT_INT: u32 = 0;
+const IMPORT_PRINT_STR: u32 = 1;
+const IMPORT_NEWLINE: u32 = 2;
+const FN_MAIN: u32 = 3;
+const FN_CONCAT: u32 = 4;
+const FN_STR_EQ: u32 = 5;
+/// Index of the first specialized Monkey function.
+const FIRST_USER_FN: u32 = 6;
+
+/// Global 0 is the bump allocator's next free address.
+const GLOBAL_HEAP: u32 = 0;
+
+const I32: u8 = 0x7f;
+const I64: u8 = 0x7e;
+const EMPTY_BLOCK: u8 = 0x40;
+
+const UNREACHABLE: u8 = 0x00;
+const BLOCK: u8 = 0x02;
+const LOOP: u8 = 0x03;
+const IF: u8 = 0x04;
+const ELSE: u8 = 0x05;
+const END: u8 = 0x0b;
+const BR: u8 = 0x0c;
+const BR_IF: u8 = 0x0d;
+const RETURN: u8 = 0x0f;
+const CALL: u8 = 0x10;
+const DROP: u8 = 0x1a;
+const LOCAL_GET: u8 = 0x20;
+const LOCAL_SET: u8 = 0x21;
+const LOCAL_TEE: u8 = 0x22;
+const GLOBAL_GET: u8 = 0x23;
+const GLOBAL_SET: u8 = 0x24;
+const I32_LOAD: u8 = 0x28;
+const I32_LOAD8_U: u8 = 0x2d;
+const I32_STORE: u8 = 0x36;
+const MEMORY_SIZE: u8 = 0x3f;
+const MEMORY_GROW: u8 = 0x40;
+const I32_CONST: u8 = 0x41;
+const I64_CONST: u8 = 0x42;
+const I32_EQZ: u8 = 0x45;
+const I32_EQ: u8 = 0x46;
+const I32_NE: u8 = 0x47;
+const I32_LE_U: u8 = 0x4d;
+const I32_GE_U: u8 = 0x4f;
+const I64_EQ: u8 = 0x51;
+const I64_NE: u8 = 0x52;
+const I64_LT_S: u8 = 0x53;
+const I64_GT_S: u8 = 0x55;
+const I64_LE_S: u8 = 0x57;
+const I64_GE_S: u8 = 0x59;
+const I32_ADD: u8 = 0x6a;
+const I32_SUB: u8 = 0x6b;
+const I32_AND: u8 = 0x71;
+const I32_SHL: u8 = 0x74;
+const I32_SHR_U: u8 = 0x76;
+const I64_ADD: u8 = 0x7c;
+const I64_SUB: u8 = 0x7d;
+const I64_MUL: u8 = 0x7e;
+const I64_DIV_S: u8 = 0x7f;
+const I64_EXTEND_I32_U: u8 = 0xad;
+const MISC_PREFIX: u8 = 0xfc;
+const MEMORY_COPY: u32 = 10;
+
+/// Compile a parsed program to the bytes of a wasm module.
+pub fn compile_program(program: &Program) -> Result<Vec<u8>, CompileError> {
+    let mut lowering = Lowering::default();
+    let main = lowering.lower_main(program)?;
+    Ok(lowering.finish(main))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Ty {
+    Int,
+    Bool,
+    Str,
+    Null,
+    /// The expression always branches away (`return`, `break`, ...), so
+    /// nothing is left on the stack.
+    Never,
+}
+
+impl Ty {
+    fn val_type(self) -> u8 {
+        match self {
+            Ty::Int => I64,
+            _ => I32,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Ty::Int => "INTEGER",
+            Ty::Bool => "BOOLEAN",
+            Ty::Str => "STRING",
+            Ty::Null => "NULL",
+            Ty::Never => "NEVER",
+        }
+    }
+}
+
+/// A wasm function body ready to be encoded.
+#[derive(Debug, Clone)]
+struct Body {
+    params: Vec<u8>,
+    result: Option<u8>,
+    /// Types of the locals after the parameters.
+    locals: Vec<u8>,
+    code: Vec<u8>,
+}
+
+/// One specialization of a Monkey function for concrete argument types.
+#[derive(Debug, Clone)]
+struct Instance {
+    slot: usize,
+    /// `None` while the body is still being lowered.
+    ret: Option<Ty>,
+    /// Set when a recursive call needed `ret` before it was known.
+    wanted: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FunctionDef<'a> {
+    params: &'a [Identifier],
+    body: &'a BlockStatement,
+}
+
+#[derive(Default)]
+struct Lowering<'a> {
+    data: Vec<u8>,
+    strings: HashMap<String, u32>,
+    /// Monkey globals; the wasm global index is one past the position.
+    globals: Vec<Ty>,
+    global_names: HashMap<&'a str, u32>,
+    functions: HashMap<&'a str, FunctionDef<'a>>,
+    instances: HashMap<(&'a str, Vec<Ty>), Instance>,
+    /// Specialized functions by slot; `None` while being lowered.
+    bodies: Vec<Option<Body>>,
+}
+
+/// Per-function lowering state.
+struct FnCx<'a> {
+    code: Vec<u8>,
+    /// `None` in `main`, where `let` defines globals.
+    locals: Option<HashMap<&'a str, u32>>,
+    local_tys: Vec<Ty>,
+    params: usize,
+    /// Number of enclosing wasm blocks.
+    depth: u32,
+    /// Depth outside each enclosing `while`'s outer block.
+    loops: Vec<u32>,
+    ret: Option<Ty>,
+}
+
+impl<'a> FnCx<'a> {
+    fn new(params: &'a [Identifier], tys: &[Ty], ret: Option<Ty>) -> Self {
+        let locals = params
+            .iter()
+            .enumerate()
+            .map(|(idx, param)| (param.value.as_str(), idx as u32))
+            .collect();
+        Self {
+            code: Vec::new(),
+            locals: Some(locals),
+            local_tys: tys.to_vec(),
+            params: tys.len(),
+            depth: 0,
+            loops: Vec::new(),
+            ret,
+        }
+    }
+
+    fn main() -> Self {
+        Self {
+            code: Vec::new(),
+            locals: None,
+            local_tys: Vec::new(),
+            params: 0,
+            depth: 0,
+            loops: Vec::new(),
+            ret: None,
+        }
+    }
+
+    fn new_local(&mut self, ty: Ty) -> u32 {
+        self.local_tys.push(ty);
+        (self.local_tys.len() - 1) as u32
+    }
+
+    fn op(&mut self, op: u8) {
+        self.code.push(op);
+    }
+
+    fn op_u32(&mut self, op: u8, operand: u32) {
+        self.code.push(op);
+        uleb(&mut self.code, operand.into());
+    }
+
+    fn i32_const(&mut self, value: i32) {
+        self.code.push(I32_CONST);
+        sleb(&mut self.code, value.into());
+    }
+
+    /// Emit `if` producing a `ty` (nothing for `None`) and enter its block.
+    fn begin_if(&mut self, ty: Option<Ty>) {
+        self.code.push(IF);
+        self.code.push(match ty {
+            None | Some(Ty::Never) => EMPTY_BLOCK,
+            Some(ty) => ty.val_type(),
+        });
+        self.depth += 1;
+    }
+
+    fn end_block(&mut self) {
+        self.code.push(END);
+        self.depth -= 1;
+    }
+
+    /// Lower one arm of an `if` into a fresh code buffer and return what
+    /// it emitted, so the block type can be chosen once both arms are typed.
+    fn branch<T>(
+        &mut self,
+        lower: impl FnOnce(&mut Self) -> Result<T, CompileError>,
+    ) -> Result<(T, Vec<u8>), CompileError> {
+        let outer = std::mem::take(&mut self.code);
+        self.depth += 1;
+        let result = lower(self);
+        self.depth -= 1;
+        let inner = std::mem::replace(&mut self.code, outer);
+        Ok((result?, inner))
+    }
+
+    fn into_body(self, result: Option<Ty>) -> Body {
+        let mut code = self.code;
+        code.push(END);
+        Body {
+            params: self.local_tys[..self.params]
+                .iter()
+                .map(|ty| ty.val_type())
+                .collect(),
+            result: result.map(Ty::val_type),
+            locals: self.local_tys[self.params..]
+                .iter()
+                .map(|ty| ty.val_type())
+                .collect(),
+            code,
+        }
+    }
+}
+
+fn unsupported(what: &str, pos: Position) -> CompileError {
+    CompileError::new(
+        format!("{what} is not supported by the wasm backend"),
+        Some(pos),
+    )
+    .with_kind(CompileErrorKind::UnsupportedByBackend)
+}
+
+fn type_error(message: String, pos: Position) -> CompileError {
+    CompileError::new(format!("wasm backend: {message}"), Some(pos))
+        .with_kind(CompileErrorKind::BackendTypeMismatch)
+}
+
+impl<'a> Lowering<'a> {
+    fn lower_main(&mut self, program: &'a Program) -> Result<Body, CompileError> {
+        let mut cx = FnCx::main();
+        let result = self.statements(&mut cx, &program.statements, true)?;
+        self.print_value(&mut cx, result);
+        Ok(cx.into_body(None))
+    }
+
+    /// Lower `statements`, leaving the last expression's value on the stack
+    /// when `want` is set (or null if there is none).
+    fn statements(
+        &mut self,
+        cx: &mut FnCx<'a>,
+        statements: &'a [Statement],
+        want: bool,
+    ) -> Result<Ty, CompileError> {
+        for (idx, stmt) in statements.iter().enumerate() {
+            let last = idx + 1 == statements.len();
+            let ty = match stmt {
+                Statement::Expression { expression, .. } if last && want => {
+                    return self.expr(cx, expression);
+                }
+                Statement::Expression { expression, .. } => self.discard(cx, expression)?,
+                _ => self.statement(cx, stmt)?,
+            };
+            if ty == Ty::Never {
+                // Anything after an unconditional branch is dead code.
+                return Ok(Ty::Never);
+            }
+        }
+        if want {
+            cx.i32_const(0);
+        }
+        Ok(Ty::Null)
+    }
+
+    /// Lower a non-expression statement; returns `Never` if it branches away.
+    fn statement(&mut self, cx: &mut FnCx<'a>, stmt: &'a Statement) -> Result<Ty, CompileError> {
+        match stmt {
+            Statement::Let { name, value, pos } => self.let_statement(cx, name, value, *pos),
+            Statement::Return { value, pos } => {
+                let ty = self.expr(cx, value)?;
+                if ty == Ty::Never {
+                    return Ok(Ty::Never);
+                }
+                if cx.locals.is_none() {
+                    // A top-level `return` ends the program with its value.
+                    self.print_value(cx, ty);
+                } else {
+                    match cx.ret {
+                        None => cx.ret = Some(ty),
+                        Some(ret) if ret == ty => {}
+                        Some(ret) => {
+                            return Err(type_error(
+                                format!("function returns both {} and {}", ret.name(), ty.name()),
+                                *pos,
+                            ))
+                        }
+                    }
+                }
+                cx.op(RETURN);
+                Ok(Ty::Never)
+            }
+            Statement::While {
+                condition, body, ..
+            } => {
+                cx.loops.push(cx.depth);
+                cx.op(BLOCK);
+                cx.op(EMPTY_BLOCK);
+                cx.op(LOOP);
+                cx.op(EMPTY_BLOCK);
+                cx.depth += 2;
+                let cond = self.expr(cx, condition)?;
+                self.truthy(cx, cond);
+                cx.op(I32_EQZ);
+                cx.op_u32(BR_IF, 1);
+                self.statements(cx, &body.statements, false)?;
+                cx.op_u32(BR, 0);
+                cx.end_block();
+                cx.end_block();
+                cx.loops.pop();
+                Ok(Ty::Null)
+            }
+            Statement::Break { pos } | Statement::Continue { pos } => {
+                let Some(&base) = cx.loops.last() else {
+                    return Err(unsupported("break or continue outside a loop", *pos));
+                };
+                let label = cx.depth - base - 1;
+                if matches!(stmt, Statement::Break { .. }) {
+                    cx.op_u32(BR, label);
+                } else {
+                    cx.op_u32(BR, label - 1);
+                }
+                Ok(Ty::Never)
+            }
+            Statement::Expression { expression, .. } => self.discard(cx, expression),
+        }
+    }
+
+    fn let_statement(
+        &mut self,
+        cx: &mut FnCx<'a>,
+        name: &'a Identifier,
+        value: &'a Expression,
+        pos: Position,
+    ) -> Result<Ty, CompileError> {
+        let name_str = name.value.as_str();
+        if let Expression::FunctionLiteral {
+            parameters, body, ..
+        } = value
+        {
+            if cx.locals.is_some() {
+                return Err(unsupported("a nested function", pos));
+            }
+            if self.functions.contains_key(name_str) || self.global_names.contains_key(name_str) {
+                return Err(unsupported(&format!("redefining '{name_str}'"), pos));
+            }
+            self.functions.insert(
+                name_str,
+                FunctionDef {
+                    params: parameters,
+                    body,
+                },
+            );
+            return Ok(Ty::Null);
+        }
+
+        let ty = self.expr(cx, value)?;
+        if ty == Ty::Never {
+            return Ok(Ty::Never);
+        }
+        let rebind = |old: Ty| {
+            type_error(
+                format!(
+                    "'{name_str}' changes type from {} to {}",
+                    old.name(),
+                    ty.name()
+                ),
+                pos,
+            )
+        };
+        match &mut cx.locals {
+            Some(locals) => match locals.get(name_str) {
+                Some(&idx) if cx.local_tys[idx as usize] != ty => {
+                    return Err(rebind(cx.local_tys[idx as usize]))
+                }
+                Some(&idx) => cx.op_u32(LOCAL_SET, idx),
+                None => {
+                    let idx = cx.local_tys.len() as u32;
+                    locals.insert(name_str, idx);
+                    cx.local_tys.push(ty);
+                    cx.op_u32(LOCAL_SET, idx);
+                }
+            },
+            None => {
+                if self.functions.contains_key(name_str) {
+                    return Err(unsupported(&format!("redefining '{name_str}'"), pos));
+                }
+                let idx = match self.global_names.get(name_str) {
+                    Some(&idx) if self.globals[idx as usize] != ty => {
+                        return Err(rebind(self.globals[idx as usize]))
+                    }
+                    Some(&idx) => idx,
+                    None => {
+                        self.globals.push(ty);
+                        let idx = (self.globals.len() - 1) as u32;
+                        self.global_names.insert(name_str, idx);
+                        idx
+                    }
+                };
+                cx.op_u32(GLOBAL_SET, GLOBAL_HEAP + 1 + idx);
+            }
+        }
+        Ok(Ty::Null)
+    }
+
+    /// Lower `expr` for its side effects only.
+    fn discard(&mut self, cx: &mut FnCx<'a>, expr: &'a Expression) -> Result<Ty, CompileError> {
+        if let Expression::If {
+            condition,
+            consequence,
+            alternative,
+            ..
+        } = expr
+        {
+            // Statement-level `if`s may have branches of different types.
+            let cond = self.expr(cx, condition)?;
+            if cond == Ty::Never {
+                return Ok(Ty::Never);
+            }
+            self.truthy(cx, cond);
+            let (then_ty, then_code) =
+                cx.branch(|cx| self.statements(cx, &consequence.statements, false))?;
+            let (else_ty, else_code) = match alternative {
+                Some(block) => cx.branch(|cx| self.statements(cx, &block.statements, false))?,
+                None => (Ty::Null, Vec::new()),
+            };
+            let diverges = then_ty == Ty::Never && else_ty == Ty::Never;
+            self.emit_if(cx, None, then_code, else_code);
+            if diverges {
+                cx.op(UNREACHABLE);
+                return Ok(Ty::Never);
+            }
+            return Ok(Ty::Null);
+        }
+        let ty = self.expr(cx, expr)?;
+        if ty != Ty::Never {
+            cx.op(DROP);
+        }
+        Ok(ty)
+    }
+
+    fn emit_if(
+        &mut self,
+        cx: &mut FnCx<'a>,
+        ty: Option<Ty>,
+        then_code: Vec<u8>,
+        else_code: Vec<u8>,
+    ) {
+        cx.begin_if(ty);
+        cx.code.extend(then_code);
+        cx.op(ELSE);
+        cx.code.extend(else_code);
+        cx.end_block();
+        if ty == Some(Ty::Never) {
+            cx.op(UNREACHABLE);
+        }
+    }
+
+    fn expr(&mut self, cx: &mut FnCx<'a>, expr: &'a Expression) -> Result<Ty, CompileError> {
+        match expr {
+            Expression::IntegerLiteral { value, .. } => {
+                cx.op(I64_CONST);
+                sleb(&mut cx.code, *value);
+                Ok(Ty::Int)
+            }
+            Expression::BooleanLiteral { value, .. } => {
+                cx.i32_const(i32::from(*value));
+                Ok(Ty::Bool)
+            }
+            Expression::StringLiteral { value, .. } => {
+                let addr = self.string(value);
+                cx.i32_const(addr as i32);
+                Ok(Ty::Str)
+            }
+            Expression::Identifier { value, pos } => self.identifier(cx, value, *pos),
+            Expression::Prefix {
+                operator,
+                right,
+                pos,
+            } => match operator.as_str() {
+                "!" => {
+                    let ty = self.expr(cx, right)?;
+                    if ty == Ty::Never {
+                        return Ok(Ty::Never);
+                    }
+                    self.truthy(cx, ty);
+                    cx.op(I32_EQZ);
+                    Ok(Ty::Bool)
+                }
+                "-" => {
+                    cx.op(I64_CONST);
+                    sleb(&mut cx.code, 0);
+                    match self.expr(cx, right)? {
+                        Ty::Int => {
+                            cx.op(I64_SUB);
+                            Ok(Ty::Int)
+                        }
+                        Ty::Never => Ok(Ty::Never),
+                        ty => Err(type_error(
+                            format!("unsupported operand type for -: {}", ty.name()),
+                            *pos,
+                        )),
+                    }
+                }
+                other => Err(unsupported(&format!("prefix operator {other}"), *pos)),
+            },
+            Expression::Infix {
+                left,
+                operator,
+                right,
+                pos,
+            } => self.infix(cx, left, operator, right, *pos),
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+                pos,
+            } => {
+                let cond = self.expr(cx, condition)?;
+                if cond == Ty::Never {
+                    return Ok(Ty::Never);
+                }
+                self.truthy(cx, cond);
+                let (then_ty, then_code) =
+                    cx.branch(|cx| self.statements(cx, &consequence.statements, true))?;
+                let (else_ty, else_code) = match alternative {
+                    Some(block) => cx.branch(|cx| self.statements(cx, &block.statements, true))?,
+                    None => (Ty::Null, vec![I32_CONST, 0]),
+                };
+                let ty = match (then_ty, else_ty) {
+                    (Ty::Never, other) | (other, Ty::Never) => other,
+                    (a, b) if a == b => a,
+                    (a, b) => {
+                        return Err(type_error(
+                            format!(
+                                "if branches produce different types: {} and {}",
+                                a.name(),
+                                b.name()
+                            ),
+                            *pos,
+                        ))
+                    }
+                };
+                self.emit_if(cx, Some(ty), then_code, else_code);
+                Ok(ty)
+            }
+            Expression::Call {
+                function,
+                arguments,
+                pos,
+            } => self.call(cx, function, arguments, *pos),
+            Expression::FunctionLiteral { pos, .. } => Err(unsupported(
+                "a function literal outside a top-level let",
+                *pos,
+            )),
+            Expression::ArrayLiteral { pos, .. } => Err(unsupported("an array", *pos)),
+            Expression::HashLiteral { pos, .. } => Err(unsupported("a hash", *pos)),
+            Expression::Index { pos, .. } => Err(unsupported("indexing", *pos)),
+        }
+    }
+
+    fn identifier(
+        &mut self,
+        cx: &mut FnCx<'a>,
+        name: &str,
+        pos: Position,
+    ) -> Result<Ty, CompileError> {
+        if let Some(&idx) = cx.locals.as_ref().and_then(|locals| locals.get(name)) {
+            cx.op_u32(LOCAL_GET, idx);
+            return Ok(cx.local_tys[idx as usize]);
+        }
+        if let Some(&idx) = self.global_names.get(name) {
+            cx.op_u32(GLOBAL_GET, GLOBAL_HEAP + 1 + idx);
+            return Ok(self.globals[idx as usize]);
+        }
+        if self.functions.contains_key(name) {
+            return Err(unsupported(
+                &format!("using function '{name}' as a value"),
+                pos,
+            ));
+        }
+        if crate::builtins::builtin_names().contains(&name) {
+            return Err(unsupported(
+                &format!("using builtin '{name}' as a value"),
+                pos,
+            ));
+        }
+        Err(
+            CompileError::new(format!("unresolved identifier: {name}"), Some(pos))
+                .with_kind(CompileErrorKind::UnresolvedIdentifier),
+        )
+    }
+
+    fn infix(
+        &mut self,
+        cx: &mut FnCx<'a>,
+        left: &'a Expression,
+        operator: &str,
+        right: &'a Expression,
+        pos: Position,
+    ) -> Result<Ty, CompileError> {
+        if operator == "&&" || operator == "||" {
+            let lhs = self.expr(cx, left)?;
+            if lhs == Ty::Never {
+                return Ok(Ty::Never);
+            }
+            self.truthy(cx, lhs);
+            let ((), rhs_code) = cx.branch(|cx| {
+                let rhs = self.expr(cx, right)?;
+                self.truthy(cx, rhs);
+                Ok(())
+            })?;
+            let short = vec![I32_CONST, u8::from(operator == "||")];
+            let (then_code, else_code) = if operator == "&&" {
+                (rhs_code, short)
+            } else {
+                (short, rhs_code)
+            };
+            self.emit_if(cx, Some(Ty::Bool), then_code, else_code);
+            return Ok(Ty::Bool);
+        }
+
+        let lhs = self.expr(cx, left)?;
+        if lhs == Ty::Never {
+            return Ok(Ty::Never);
+        }
+        let rhs = self.expr(cx, right)?;
+        if rhs == Ty::Never {
+            return Ok(Ty::Never);
+        }
+        let mismatch = || {
+            type_error(
+                format!(
+                    "unsupported operand types for {operator}: {} and {}",
+                    lhs.name(),
+                    rhs.name()
+                ),
+                pos,
+            )
+        };
+        let (op, ty) = match (lhs, rhs, operator) {
+            (Ty::Int, Ty::Int, "+") => (I64_ADD, Ty::Int),
+            (Ty::Int, Ty::Int, "-") => (I64_SUB, Ty::Int),
+            (Ty::Int, Ty::Int, "*") => (I64_MUL, Ty::Int),
+            (Ty::Int, Ty::Int, "/") => (I64_DIV_S, Ty::Int),
+            (Ty::Int, Ty::Int, "==") => (I64_EQ, Ty::Bool),
+            (Ty::Int, Ty::Int, "!=") => (I64_NE, Ty::Bool),
+            (Ty::Int, Ty::Int, "<") => (I64_LT_S, Ty::Bool),
+            (Ty::Int, Ty::Int, ">") => (I64_GT_S, Ty::Bool),
+            (Ty::Int, Ty::Int, "<=") => (I64_LE_S, Ty::Bool),
+            (Ty::Int, Ty::Int, ">=") => (I64_GE_S, Ty::Bool),
+            (Ty::Bool, Ty::Bool, "==") => (I32_EQ, Ty::Bool),
+            (Ty::Bool, Ty::Bool, "!=") => (I32_NE, Ty::Bool),
+            (Ty::Str, Ty::Str, "+") => {
+                cx.op_u32(CALL, FN_CONCAT);
+                return Ok(Ty::Str);
+            }
+            (Ty::Str, Ty::Str, "==" | "!=") => {
+                cx.op_u32(CALL, FN_STR_EQ);
+                if operator == "!=" {
+                    cx.op(I32_EQZ);
+                }
+                return Ok(Ty::Bool);
+            }
+            (Ty::Null, Ty::Null, "==" | "!=") => {
+                cx.op(DROP);
+                cx.op(DROP);
+                cx.i32_const(i32::from(operator == "=="));
+                return Ok(Ty::Bool);
+            }
+            _ => return Err(mismatch()),
+        };
+        cx.op(op);
+        Ok(ty)
+    }
+
+    fn call(
+        &mut self,
+        cx: &mut FnCx<'a>,
+        function: &'a Expression,
+        arguments: &'a [Expression],
+        pos: Position,
+    ) -> Result<Ty, CompileError> {
+        let Expression::Identifier { value: name, .. } = function else {
+            return Err(unsupported("calling a computed function", pos));
+        };
+        let shadowed = cx
+            .locals
+            .as_ref()
+            .is_some_and(|locals| locals.contains_key(name.as_str()))
+            || self.global_names.contains_key(name.as_str());
+        if shadowed {
+            return Err(unsupported(&format!("calling variable '{name}'"), pos));
+        }
+
+        let mut tys = Vec::with_capacity(arguments.len());
+        for arg in arguments {
+            match self.expr(cx, arg)? {
+                Ty::Never => return Ok(Ty::Never),
+                ty => tys.push(ty),
+            }
+        }
+
+        if self.functions.contains_key(name.as_str()) {
+            let (index, ret) = self.instantiate(name, tys, pos)?;
+            cx.op_u32(CALL, index);
+            return Ok(ret);
+        }
+        match name.as_str() {
+            "puts" => {
+                // Every argument is evaluated before anything is printed.
+                let temps: Vec<_> = tys.iter().map(|&ty| (cx.new_local(ty), ty)).collect();
+                for &(idx, _) in temps.iter().rev() {
+                    cx.op_u32(LOCAL_SET, idx);
+                }
+                for &(idx, ty) in &temps {
+                    cx.op_u32(LOCAL_GET, idx);
+                    self.print_inline(cx, ty);
+                }
+                cx.op_u32(CALL, IMPORT_NEWLINE);
+                cx.i32_const(0);
+                Ok(Ty::Null)
+            }
+            "len" => match tys.as_slice() {
+                [Ty::Str] => {
+                    cx.op(I32_LOAD);
+                    uleb(&mut cx.code, 2);
+                    uleb(&mut cx.code, 4);
+                    cx.op(I64_EXTEND_I32_U);
+                    Ok(Ty::Int)
+                }
+                _ => Err(unsupported("len on anything but a string", pos)),
+            },
+            _ if crate::builtins::builtin_names().contains(&name.as_str()) => {
+                Err(unsupported(&format!("builtin '{name}'"), pos))
+            }
+            _ => Err(
+                CompileError::new(format!("unresolved identifier: {name}"), Some(pos))
+                    .with_kind(CompileErrorKind::UnresolvedIdentifier),
+            ),
+        }
+    }
+
+    /// Specialize function `name` for argument types `tys`, returning its
+    /// wasm index and result type.
+    fn instantiate(
+        &mut self,
+        name: &'a str,
+        tys: Vec<Ty>,
+        pos: Position,
+    ) -> Result<(u32, Ty), CompileError> {
+        let key = (name, tys);
+        if let Some(instance) = self.instances.get_mut(&key) {
+            let index = FIRST_USER_FN + instance.slot as u32;
+            return match instance.ret {
+                Some(ret) => Ok((index, ret)),
+                None => {
+                    instance.wanted = true;
+                    Err(type_error(
+                        format!("cannot infer the return type of '{name}'"),
+                        pos,
+                    ))
+                }
+            };
+        }
+
+        let def = self.functions[name];
+        if def.params.len() != key.1.len() {
+            return Err(type_error(
+                format!(
+                    "{name} expected {} argument(s), got {}",
+                    def.params.len(),
+                    key.1.len()
+                ),
+                pos,
+            ));
+        }
+        let slot = self.bodies.len();
+        self.bodies.push(None);
+        let tys = key.1.clone();
+        self.instances.insert(
+            key.clone(),
+            Instance {
+                slot,
+                ret: None,
+                wanted: false,
+            },
+        );
+        let saved = self.instances.clone();
+
+        let mut result = self.lower_function(def, &tys, None);
+        if result.is_err() && self.instances[&key].wanted {
+            // The body calls itself before its return type is known: guess
+            // the type, and keep the first guess the body agrees with.
+            for guess in [Ty::Int, Ty::Bool, Ty::Str, Ty::Null] {
+                self.restore(slot, &saved);
+                if let Some(instance) = self.instances.get_mut(&key) {
+                    instance.ret = Some(guess);
+                }
+                if let Ok(lowered) = self.lower_function(def, &tys, Some(guess)) {
+                    result = Ok(lowered);
+                    break;
+                }
+            }
+        }
+        let (body, ret) = result?;
+        self.bodies[slot] = Some(body);
+        if let Some(instance) = self.instances.get_mut(&key) {
+            instance.ret = Some(ret);
+        }
+        Ok((FIRST_USER_FN + slot as u32, ret))
+    }
+
+    /// Forget the specializations made since `saved` was taken, keeping
+    /// note of which pending instances turned out to be recursive.
+    fn restore(&mut self, slot: usize, saved: &HashMap<(&'a str, Vec<Ty>), Instance>) {
+        self.bodies.truncate(slot + 1);
+        let mut instances = saved.clone();
+        for (key, instance) in &mut instances {
+            instance.wanted |= self.instances.get(key).is_some_and(|now| now.wanted);
+        }
+        self.instances = instances;
+    }
+
+    fn lower_function(
+        &mut self,
+        def: FunctionDef<'a>,
+        tys: &[Ty],
+        ret: Option<Ty>,
+    ) -> Result<(Body, Ty), CompileError> {
+        let mut cx = FnCx::new(def.params, tys, ret);
+        let tail = self.statements(&mut cx, &def.body.statements, true)?;
+        let ret = match (cx.ret, tail) {
+            (ret, Ty::Never) => ret.unwrap_or(Ty::Null),
+            (None, tail) => tail,
+            (Some(ret), tail) if ret == tail => ret,
+            (Some(ret), tail) => {
+                return Err(type_error(
+                    format!("function returns both {} and {}", ret.name(), tail.name()),
+                    def.body.pos,
+                ))
+            }
+        };
+        Ok((cx.into_body(Some(ret)), ret))
+    }
+
+    /// Convert the value on top of the stack to an `i32` condition.
+    fn truthy(&mut self, cx: &mut FnCx<'a>, ty: Ty) {
+        match ty {
+            Ty::Bool | Ty::Never => {}
+            Ty::Int | Ty::Str => {
+                cx.op(DROP);
+                cx.i32_const(1);
+            }
+            Ty::Null => {
+                cx.op(DROP);
+                cx.i32_const(0);
+            }
+        }
+    }
+
+    /// Append the value on top of the stack to the current output line.
+    fn print_inline(&mut self, cx: &mut FnCx<'a>, ty: Ty) {
+        match ty {
+            Ty::Int => cx.op_u32(CALL, IMPORT_PRINT_INT),
+            Ty::Str => cx.op_u32(CALL, IMPORT_PRINT_STR),
+            Ty::Bool => {
+                let yes = self.string("true");
+                let no = self.string("false");
+                cx.begin_if(Some(Ty::Str));
+                cx.i32_const(yes as i32);
+                cx.op(ELSE);
+                cx.i32_const(no as i32);
+                cx.end_block();
+                cx.op_u32(CALL, IMPORT_PRINT_STR);
+            }
+            Ty::Null => {
+                let null = self.string("null");
+                cx.op(DROP);
+                cx.i32_const(null as i32);
+                cx.op_u32(CALL, IMPORT_PRINT_STR);
+            }
+            Ty::Never => {}
+        }
+    }
+
+    /// Print the value on top of the stack as its own line.
+    fn print_value(&mut self, cx: &mut FnCx<'a>, ty: Ty) {
+        if ty != Ty::Never {
+            self.print_inline(cx, ty);
+            cx.op_u32(CALL, IMPORT_NEWLINE);
+        }
+    }
+
+    /// Address of `value` in the data segment, adding it on first use.
+    fn string(&mut self, value: &str) -> u32 {
+        if let Some(&addr) = self.strings.get(value) {
+            return addr;
+        }
+        let addr = self.data.len() as u32;
+        self.data
+            .extend_from_slice(&(value.len() as u32).to_le_bytes());
+        self.data
+            .extend_from_slice(&(value.chars().count() as u32).to_le_bytes());
+        self.data.extend_from_slice(value.as_bytes());
+        while !self.data.len().is_multiple_of(4) {
+            self.data.push(0);
+        }
+        self.strings.insert(value.to_string(), addr);
+        addr
+    }
+
+    fn finish(self, main: Body) -> Vec<u8> {
+        let mut bodies = vec![main, concat_body(), str_eq_body()];
+        bodies.extend(self.bodies.into_iter().flatten());
+
+        let mut types: Vec<(Vec<u8>, Option<u8>)> = Vec::new();
+        let mut type_of = |params: &[u8], result: Option<u8>| {
+            let sig = (params.to_vec(), result);
+            match types.iter().position(|t| *t == sig) {
+                Some(idx) => idx as u32,
+                None => {
+                    types.push(sig);
+                    (types.len() - 1) as u32
+                }
+            }
+        };
+        let imports = [
+            ("print_int", type_of(&[I64], None)),
+            ("print_str", type_of(&[I32], None)),
+            ("newline", type_of(&[], None)),
+        ];
+        let funcs: Vec<u32> = bodies
+            .iter()
+            .map(|body| type_of(&body.params, body.result))
+            .collect();
+
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(VERSION);
+
+        section(&mut out, 1, types.len(), |s| {
+            for (params, result) in &types {
+                s.push(0x60);
+                vec_bytes(s, params);
+                vec_bytes(s, result.as_slice());
+            }
+        });
+        section(&mut out, 2, imports.len(), |s| {
+            for (name, ty) in imports {
+                name_bytes(s, "monkey");
+                name_bytes(s, name);
+                s.push(0x00);
+                uleb(s, ty.into());
+            }
+        });
+        section(&mut out, 3, funcs.len(), |s| {
+            for ty in &funcs {
+                uleb(s, (*ty).into());
+            }
+        });
+        let heap = (self.data.len() as u32).max(8);
+        section(&mut out, 5, 1, |s| {
+            s.push(0x00);
+            uleb(s, heap.div_ceil(65536).max(1).into());
+        });
+        section(&mut out, 6, 1 + self.globals.len(), |s| {
+            s.extend_from_slice(&[I32, 1, I32_CONST]);
+            sleb(s, heap.into());
+            s.push(END);
+            for ty in &self.globals {
+                let val = ty.val_type();
+                s.extend_from_slice(&[val, 1]);
+                s.push(if val == I64 { I64_CONST } else { I32_CONST });
+                s.extend_from_slice(&[0, END]);
+            }
+        });
+        section(&mut out, 7, 2, |s| {
+            name_bytes(s, "main");
+            s.push(0x00);
+            uleb(s, FN_MAIN.into());
+            name_bytes(s, "memory");
+            s.push(0x02);
+            uleb(s, 0);
+        });
+        section(&mut out, 10, bodies.len(), |s| {
+            for body in &bodies {
+                let mut encoded = Vec::new();
+                let mut groups: Vec<(u32, u8)> = Vec::new();
+                for &ty in &body.locals {
+                    match groups.last_mut() {
+                        Some((count, last)) if *last == ty => *count += 1,
+                        _ => groups.push((1, ty)),
+                    }
+                }
+                uleb(&mut encoded, groups.len() as u64);
+                for (count, ty) in groups {
+                    uleb(&mut encoded, count.into());
+                    encoded.push(ty);
+                }
+                encoded.extend_from_slice(&body.code);
+                vec_bytes(s, &encoded);
+            }
+        });
+        section(&mut out, 11, 1, |s| {
+            s.extend_from_slice(&[0x00, I32_CONST, 0, END]);
+            vec_bytes(s, &self.data);
+        });
+        out
+    }
+}
+
+/// `concat(a, b) -> ptr`: bump-allocate `a + b`, growing memory as needed.
+fn concat_body() -> Body {
+    let (a, b, la, lb, p, end) = (0, 1, 2, 3, 4, 5);
+    let mut c = Code::default();
+    c.get(a).load(0).set(la);
+    c.get(b).load(0).set(lb);
+    c.op(GLOBAL_GET).u(GLOBAL_HEAP).set(p);
+    // end = align4(p + 8 + la + lb)
+    c.get(p)
+        .i32(11)
+        .op(I32_ADD)
+        .get(la)
+        .op(I32_ADD)
+        .get(lb)
+        .op(I32_ADD);
+    c.i32(-4).op(I32_AND).set(end);
+    c.op(BLOCK).op(EMPTY_BLOCK);
+    c.get(end)
+        .op(MEMORY_SIZE)
+        .u(0)
+        .i32(16)
+        .op(I32_SHL)
+        .op(I32_LE_U);
+    c.op(BR_IF).u(0);
+    c.get(end)
+        .op(MEMORY_SIZE)
+        .u(0)
+        .i32(16)
+        .op(I32_SHL)
+        .op(I32_SUB);
+    c.i32(65535).op(I32_ADD).i32(16).op(I32_SHR_U);
+    c.op(MEMORY_GROW).u(0).op(DROP);
+    c.op(END);
+    c.get(end).op(GLOBAL_SET).u(GLOBAL_HEAP);
+    c.get(p).get(la).get(lb).op(I32_ADD).store(0);
+    c.get(p).get(a).load(4).get(b).load(4).op(I32_ADD).store(4);
+    c.get(p).i32(8).op(I32_ADD);
+    c.get(a).i32(8).op(I32_ADD).get(la).memory_copy();
+    c.get(p).i32(8).op(I32_ADD).get(la).op(I32_ADD);
+    c.get(b).i32(8).op(I32_ADD).get(lb).memory_copy();
+    c.get(p).op(END);
+    Body {
+        params: vec![I32, I32],
+        result: Some(I32),
+        locals: vec![I32; 4],
+        code: c.0,
+    }
+}
+
+/// `str_eq(a, b) -> bool`: byte-wise string comparison.
+fn str_eq_body() -> Body {
+    let (a, b, len, i) = (0, 1, 2, 3);
+    let mut c = Code::default();
+    c.get(a)
+        .load(0)
+        .op(LOCAL_TEE)
+        .u(len)
+        .get(b)
+        .load(0)
+        .op(I32_NE);
+    c.op(IF).op(EMPTY_BLOCK).i32(0).op(RETURN).op(END);
+    c.op(BLOCK).op(EMPTY_BLOCK).op(LOOP).op(EMPTY_BLOCK);
+    c.get(i).get(len).op(I32_GE_U).op(BR_IF).u(1);
+    c.get(a).get(i).op(I32_ADD).op(I32_LOAD8_U).u(0).u(8);
+    c.get(b).get(i).op(I32_ADD).op(I32_LOAD8_U).u(0).u(8);
+    c.op(I32_NE)
+        .op(IF)
+        .op(EMPTY_BLOCK)
+        .i32(0)
+        .op(RETURN)
+        .op(END);
+    c.get(i).i32(1).op(I32_ADD).set(i).op(BR).u(0);
+    c.op(END).op(END);
+    c.i32(1).op(END);
+    Body {
+        params: vec![I32, I32],
+        result: Some(I32),
+        locals: vec![I32; 2],
+        code: c.0,
+    }
+}
+
+/// Small builder for the hand-written runtime helpers.
+#[derive(Default)]
+struct Code(Vec<u8>);
+
+impl Code {
+    fn op(&mut self, op: u8) -> &mut Self {
+        self.0.push(op);
+        self
+    }
+
+    fn u(&mut self, value: u32) -> &mut Self {
+        uleb(&mut self.0, value.into());
+        self
+    }
+
+    fn i32(&mut self, value: i32) -> &mut Self {
+        self.0.push(I32_CONST);
+        sleb(&mut self.0, value.into());
+        self
+    }
+
+    fn get(&mut self, local: u32) -> &mut Self {
+        self.op(LOCAL_GET).u(local)
+    }
+
+    fn set(&mut self, local: u32) -> &mut Self {
+        self.op(LOCAL_SET).u(local)
+    }
+
+    fn load(&mut self, offset: u32) -> &mut Self {
+        self.op(I32_LOAD).u(2).u(offset)
+    }
+
+    fn store(&mut self, offset: u32) -> &mut Self {
+        self.op(I32_STORE).u(2).u(offset)
+    }
+
+    fn memory_copy(&mut self) -> &mut Self {
+        self.op(MISC_PREFIX).u(MEMORY_COPY).u(0).u(0)
+    }
+}
+
+fn section(out: &mut Vec<u8>, id: u8, count: usize, fill: impl FnOnce(&mut Vec<u8>)) {
+    let mut payload = Vec::new();
+    uleb(&mut payload, count as u64);
+    fill(&mut payload);
+    out.push(id);
+    vec_bytes(out, &payload);
+}
+
+fn vec_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    uleb(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+fn name_bytes(out: &mut Vec<u8>, name: &str) {
+    vec_bytes(out, name.as_bytes());
+}
+
+fn uleb(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn sleb(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
@@ -0,0 +1,65 @@
+//! Change detection for `monkey run --watch`.
+//!
+//! The file is polled for its modification time and length instead of
+//! being watched through OS notifications, which needs no extra
+//! dependency and behaves the same on every platform. Monkey has no
+//! module imports, so the program file is the only one watched.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How often `FileWatch::wait` looks at the file.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Modification time and length, or `None` while the file is missing.
+type Stamp = Option<(SystemTime, u64)>;
+
+#[derive(Debug, Clone)]
+pub struct FileWatch {
+    path: PathBuf,
+    stamp: Stamp,
+}
+
+impl FileWatch {
+    /// Watch `path`, taking its current state as unchanged.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let stamp = stamp(&path);
+        Self { path, stamp }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the file changed since the last call (or `new`). Deleting
+    /// or recreating it counts as a change.
+    pub fn changed(&mut self) -> bool {
+        let stamp = stamp(&self.path);
+        let changed = stamp != self.stamp;
+        self.stamp = stamp;
+        changed
+    }
+
+    /// Block until the file changes, checking every `interval`. Returns
+    /// once the file has stopped changing for one interval, so an editor
+    /// that writes in several steps triggers a single run.
+    pub fn wait(&mut self, interval: Duration) {
+        while !self.changed() {
+            thread::sleep(interval);
+        }
+        loop {
+            thread::sleep(interval);
+            if !self.changed() {
+                return;
+            }
+        }
+    }
+}
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
@@ -0,0 +1,147 @@
+use monkey_rust_compiler::bytecode::{assemble, AssembleError, Chunk};
+use monkey_rust_compiler::object::Object;
+use monkey_rust_compiler::position::{Position, Span};
+use monkey_rust_compiler::runner::compile_source;
+use monkey_rust_compiler::vm::Vm;
+
+fn run(chunk: Chunk) -> String {
+    match Vm::new(chunk).run() {
+        Ok(value) => value.inspect(),
+        Err(err) => err.format_single_line(),
+    }
+}
+
+fn error(text: &str) -> String {
+    assemble(text)
+        .map(|_| ())
+        .expect_err("assembly should fail")
+        .to_string()
+}
+
+#[test]
+fn assembles_hand_written_programs() {
+    let chunk = assemble(
+        "; add(2, 40)
+        Closure 1 0
+        ConstInt 2
+        Constant 0
+        Call 2 @3:7
+        ReturnValue
+
+        Constants:
+        INTEGER 40
+        FUNCTION add arity=2 locals=2
+            GetLocal 0
+            GetLocal 1
+            Add @2:5 (2:3-2:8)
+            ReturnValue",
+    )
+    .expect("assemble");
+    assert_eq!(chunk.positions, [(10, Span::at(Position::new(3, 7)))]);
+    let Object::CompiledFunction(function) = chunk.constants[1].as_ref() else {
+        panic!("expected a compiled function");
+    };
+    assert_eq!(function.name.as_deref(), Some("add"));
+    assert_eq!(
+        function.positions.to_vec(),
+        [(
+            4,
+            Span::new(
+                Position::new(2, 5),
+                Position::new(2, 3),
+                Position::new(2, 8)
+            )
+        )]
+    );
+    assert_eq!(run(chunk), "42");
+
+    let negative = assemble("ConstInt -3\nReturnValue").expect("assemble");
+    assert_eq!(run(negative), "-3");
+
+    let failing = assemble("Const1\nConst0\nDiv @7:3\nReturnValue").expect("assemble");
+    assert_eq!(
+        run(failing),
+        "Error[DIVISION_BY_ZERO] at 7:3: division by zero"
+    );
+}
+
+#[test]
+fn disassembly_assembles_back_to_the_same_chunk() {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let mut sources = vec![
+        "let s = \"tab\there é\"; let n = if (false) { 1 }; [s, -7, 1000, true, {1: n}];"
+            .to_string(),
+        "let f = fn(a) { fn() { if (a > 1) { a } else { while (false) { break; } } } }; f(2)();"
+            .to_string(),
+    ];
+    for entry in std::fs::read_dir(format!("{root}/examples")).expect("read examples") {
+        let path = entry.expect("dir entry").path();
+        if path.extension().is_some_and(|ext| ext == "monkey") {
+            sources.push(std::fs::read_to_string(path).expect("read source"));
+        }
+    }
+
+    for source in sources {
+        let chunk = compile_source(&source).expect("compile");
+        let text = chunk.disassemble();
+        let assembled = assemble(&text).unwrap_or_else(|err| panic!("{err}\n{text}"));
+        assert_eq!(assembled.instructions, chunk.instructions, "{source}");
+        assert_eq!(assembled.positions, chunk.positions, "{source}");
+        assert_eq!(assembled.disassemble(), text, "{source}");
+        assert_eq!(run(assembled), run(chunk), "{source}");
+    }
+}
+
+#[test]
+fn reports_the_line_of_each_mistake() {
+    assert_eq!(
+        error("Null\nFrobnicate"),
+        "line 2: unknown opcode Frobnicate"
+    );
+    assert_eq!(
+        error("Constant"),
+        "line 1: wrong operand count for Constant: expected 1, got 0"
+    );
+    assert_eq!(error("GetLocal x"), "line 1: invalid operand x");
+    assert_eq!(
+        error("0000 Null\n0002 Pop"),
+        "line 2: 0002 does not match the actual offset or index 0001"
+    );
+    assert_eq!(error("Null @1"), "line 1: invalid position 1");
+    assert_eq!(
+        error("Constants:\nARRAY [1]"),
+        "line 2: unsupported constant type ARRAY"
+    );
+    assert_eq!(
+        error("Constants:\nSTRING \"open"),
+        "line 2: invalid STRING constant \"open"
+    );
+    assert_eq!(
+        error("Constant 1\nConstants:\nINTEGER 1"),
+        "line 1: constant 1 out of range (1 constants)"
+    );
+    assert_eq!(
+        error("Null\nJump 2\nReturnValue"),
+        "line 2: jump target 0002 is not an instruction"
+    );
+    assert_eq!(error("GetBuiltin 200"), "line 1: builtin 200 out of range");
+    assert_eq!(
+        error("Constants:\nFUNCTION f arity=1 locals=1\n    GetLocal 1"),
+        "line 3: local 1 out of range (locals=1)"
+    );
+    assert_eq!(error("ConstInt 40000"), "line 1: invalid operand 40000");
+    assert_eq!(
+        error("Constants:\nINTEGER 1\n    Null"),
+        "line 3: indented instruction outside a FUNCTION constant"
+    );
+    let err = assemble("Constants:\nFUNCTION f arity=1")
+        .map(|_| ())
+        .expect_err("missing locals");
+    assert_eq!(
+        err,
+        AssembleError {
+            line: 2,
+            message: "invalid FUNCTION constant f arity=1".to_string()
+        }
+    );
+}
@@ -0,0 +1,104 @@
+use monkey_rust_compiler::ast::{Expression, Identifier, Program, Statement};
+use monkey_rust_compiler::fold::{fold_expression, Folder};
+use monkey_rust_compiler::lexer::Lexer;
+use monkey_rust_compiler::parser::Parser;
+use monkey_rust_compiler::runner::run_source;
+
+fn parse(input: &str) -> Program {
+    let mut parser = Parser::new(Lexer::new(input));
+    let program = parser.parse_program();
+    assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+    program
+}
+
+/// Folds `+` and `*` of integer literals, innermost first.
+struct ConstantFolder;
+
+impl Folder for ConstantFolder {
+    fn fold_expression(&mut self, expression: Expression) -> Expression {
+        let expression = fold_expression(self, expression);
+        let Expression::Infix {
+            left,
+            operator,
+            right,
+            pos,
+        } = &expression
+        else {
+            return expression;
+        };
+        let (
+            Expression::IntegerLiteral { value: left, .. },
+            Expression::IntegerLiteral { value: right, .. },
+        ) = (left.as_ref(), right.as_ref())
+        else {
+            return expression;
+        };
+        let value = match operator.as_str() {
+            "+" => left.wrapping_add(*right),
+            "*" => left.wrapping_mul(*right),
+            _ => return expression,
+        };
+        Expression::IntegerLiteral {
+            value,
+            raw: value.to_string(),
+            pos: *pos,
+        }
+    }
+}
+
+#[test]
+fn rebuilds_the_program_with_replaced_nodes() {
+    let program = parse("let x = 1 + 2 * 3;\nfn(y) { y + 4 * 5 }(x) - 2;");
+    let spans = program.spans.clone();
+    let folded = ConstantFolder.fold_program(program);
+    assert_eq!(
+        folded.to_string(),
+        "let x = 7;\n(fn(y) { (y + 20); }(x) - 2);"
+    );
+    assert_eq!(folded.spans, spans);
+}
+
+/// Renames bindings and the reads of them, and drops `puts` statements.
+struct Rewriter;
+
+impl Folder for Rewriter {
+    fn fold_statements(&mut self, statements: Vec<Statement>) -> Vec<Statement> {
+        statements
+            .into_iter()
+            .filter(|statement| !matches!(statement, Statement::Expression { expression: Expression::Call { function, .. }, .. } if function.to_string() == "puts"))
+            .map(|statement| self.fold_statement(statement))
+            .collect()
+    }
+
+    fn fold_expression(&mut self, expression: Expression) -> Expression {
+        match expression {
+            Expression::Identifier { value, pos } if value == "n" => Expression::Identifier {
+                value: "count".to_string(),
+                pos,
+            },
+            expression => fold_expression(self, expression),
+        }
+    }
+
+    fn fold_binding(&mut self, name: Identifier) -> Identifier {
+        if name.value == "n" {
+            Identifier::new("count", name.pos)
+        } else {
+            name
+        }
+    }
+}
+
+#[test]
+fn overrides_can_rename_and_drop_statements() {
+    let program =
+        parse("let n = 2;\nputs(n);\nlet f = fn(n) { puts(n); if (n) { puts(0); n } };\nf(n);");
+    let rewritten = Rewriter.fold_program(program);
+    assert_eq!(
+        rewritten.to_string(),
+        "let count = 2;\nlet f = fn(count) { if (count) { count; }; };\nf(count);"
+    );
+    let outcome = run_source(&rewritten.to_string()).expect("rewritten program runs");
+    assert!(outcome.output.is_empty());
+    assert_eq!(outcome.result.to_string(), "2");
+}
@@ -0,0 +1,157 @@
+use monkey_rust_compiler::ast::{Expression, Program, Statement};
+use monkey_rust_compiler::ast_json::program_to_json;
+use monkey_rust_compiler::position::Position;
+use monkey_rust_compiler::runner::dump_ast_json;
+
+#[test]
+fn renders_nodes_with_positions_spans_and_fields() {
+    let json = dump_ast_json("let s = \"a\tb\";\nif (!ok) { s } else { x[0] }").expect("parses");
+    assert_eq!(
+        json,
+        r#"{
+  "node": "Program",
+  "statements": [
+    {
+      "node": "Let",
+      "pos": {"line": 1, "col": 1},
+      "span": {
+        "start": {"line": 1, "col": 1},
+        "end": {"line": 1, "col": 15}
+      },
+      "name": {
+        "node": "Identifier",
+        "pos": {"line": 1, "col": 5},
+        "value": "s"
+      },
+      "value": {
+        "node": "StringLiteral",
+        "pos": {"line": 1, "col": 9},
+        "span": {
+          "start": {"line": 1, "col": 9},
+          "end": {"line": 1, "col": 14}
+        },
+        "value": "a\tb"
+      }
+    },
+    {
+      "node": "ExpressionStatement",
+      "pos": {"line": 2, "col": 1},
+      "span": {
+        "start": {"line": 2, "col": 1},
+        "end": {"line": 2, "col": 29}
+      },
+      "expression": {
+        "node": "If",
+        "pos": {"line": 2, "col": 1},
+        "span": {
+          "start": {"line": 2, "col": 1},
+          "end": {"line": 2, "col": 29}
+        },
+        "condition": {
+          "node": "Prefix",
+          "pos": {"line": 2, "col": 5},
+          "span": {
+            "start": {"line": 2, "col": 5},
+            "end": {"line": 2, "col": 8}
+          },
+          "operator": "!",
+          "right": {
+            "node": "Identifier",
+            "pos": {"line": 2, "col": 6},
+            "span": {
+              "start": {"line": 2, "col": 6},
+              "end": {"line": 2, "col": 8}
+            },
+            "value": "ok"
+          }
+        },
+        "consequence": {
+          "node": "Block",
+          "pos": {"line": 2, "col": 10},
+          "statements": [
+            {
+              "node": "ExpressionStatement",
+              "pos": {"line": 2, "col": 12},
+              "span": {
+                "start": {"line": 2, "col": 12},
+                "end": {"line": 2, "col": 13}
+              },
+              "expression": {
+                "node": "Identifier",
+                "pos": {"line": 2, "col": 12},
+                "span": {
+                  "start": {"line": 2, "col": 12},
+                  "end": {"line": 2, "col": 13}
+                },
+                "value": "s"
+              }
+            }
+          ]
+        },
+        "alternative": {
+          "node": "Block",
+          "pos": {"line": 2, "col": 21},
+          "statements": [
+            {
+              "node": "ExpressionStatement",
+              "pos": {"line": 2, "col": 23},
+              "span": {
+                "start": {"line": 2, "col": 23},
+                "end": {"line": 2, "col": 27}
+              },
+              "expression": {
+                "node": "Index",
+                "pos": {"line": 2, "col": 24},
+                "span": {
+                  "start": {"line": 2, "col": 23},
+                  "end": {"line": 2, "col": 27}
+                },
+                "left": {
+                  "node": "Identifier",
+                  "pos": {"line": 2, "col": 23},
+                  "span": {
+                    "start": {"line": 2, "col": 23},
+                    "end": {"line": 2, "col": 24}
+                  },
+                  "value": "x"
+                },
+                "index": {
+                  "node": "IntegerLiteral",
+                  "pos": {"line": 2, "col": 25},
+                  "span": {
+                    "start": {"line": 2, "col": 25},
+                    "end": {"line": 2, "col": 26}
+                  },
+                  "value": 0,
+                  "raw": "0"
+                }
+              }
+            }
+          ]
+        }
+      }
+    }
+  ]
+}"#
+    );
+}
+
+#[test]
+fn hand_built_programs_have_no_spans() {
+    let program = Program::new(vec![Statement::Expression {
+        expression: Expression::BooleanLiteral {
+            value: true,
+            pos: Position::new(1, 1),
+        },
+        pos: Position::new(1, 1),
+    }]);
+    let json = program_to_json(&program);
+    assert!(!json.contains("span"));
+    assert!(json.contains(r#""node": "BooleanLiteral""#));
+}
+
+#[test]
+fn reports_parse_errors_instead_of_json() {
+    let errors = dump_ast_json("let = 1;").expect_err("does not parse");
+    assert!(!errors.is_empty());
+}
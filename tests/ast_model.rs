@@ -1,4 +1,7 @@
 use monkey_rust_compiler::ast::{BlockStatement, Expression, Identifier, Program, Statement};
+use monkey_rust_compiler::format::FormatOptions;
+use monkey_rust_compiler::lexer::Lexer;
+use monkey_rust_compiler::parser::Parser;
 use monkey_rust_compiler::position::Position;
 use monkey_rust_compiler::pretty;
 
@@ -319,3 +322,61 @@ fn pretty_wrapper_matches_program_display() {
     let program = Program::new(vec![Statement::Break { pos: p(1, 1) }]);
     assert_eq!(pretty::format_ast(&program), program.to_string());
 }
+
+#[test]
+fn sexpr_rendering_puts_heads_first() {
+    let source = "let x = a + b * -2;\nreturn f(x, [1, \"s\"], {true: y}[0]);\nwhile (x) { if (y) { break; } else { continue; } }\nfn(a, b) {};\nif (z) { 1 }";
+    let mut parser = Parser::new(Lexer::new(source));
+    let program = parser.parse_program();
+    assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+    assert_eq!(
+        pretty::format_ast_sexpr(&program),
+        "(let x (+ a (* b (- 2))))\n\
+         (return (call f x (array 1 \"s\") (index (hash (true y)) 0)))\n\
+         (while x (block (if y (block (break)) (block (continue)))))\n\
+         (fn (a b) (block))\n\
+         (if z (block 1))"
+    );
+    assert_eq!(
+        pretty::format_ast_as(&program, pretty::AstFormat::Sexpr),
+        pretty::format_ast_sexpr(&program)
+    );
+}
+
+#[test]
+fn pretty_rendering_indents_blocks_and_wraps_long_lists() {
+    let source = "let f = fn(x) { if (x > 0) { x * -x } else { f(x[0]) } };\nwhile (true) { break; }\nputs(1 + 2, {});";
+    let mut parser = Parser::new(Lexer::new(source));
+    let program = parser.parse_program();
+    assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+    assert_eq!(
+        pretty::format_ast_pretty(&program, FormatOptions::default()),
+        "let f = fn(x) {\n  \
+         if ((x > 0)) {\n    (x * (-x));\n  } else {\n    f((x[0]));\n  };\n\
+         };\n\
+         while (true) {\n  break;\n}\n\
+         puts((1 + 2), {});"
+    );
+    assert_eq!(
+        pretty::format_ast_pretty(
+            &program,
+            FormatOptions::default()
+                .with_indent_width(4)
+                .with_max_width(12)
+        )
+        .lines()
+        .last(),
+        Some(");")
+    );
+
+    // Without blocks or long lists it is the one-line rendering.
+    let mut parser = Parser::new(Lexer::new("let x = -a * (b + c)[1];\nx;"));
+    let program = parser.parse_program();
+    assert_eq!(
+        pretty::format_ast_as(
+            &program,
+            pretty::AstFormat::Pretty(FormatOptions::default())
+        ),
+        pretty::format_ast(&program)
+    );
+}
@@ -0,0 +1,81 @@
+use monkey_rust_compiler::ast::{Expression, Identifier, Program};
+use monkey_rust_compiler::lexer::Lexer;
+use monkey_rust_compiler::parser::Parser;
+use monkey_rust_compiler::visit::{walk_expression, Visitor};
+
+fn parse(input: &str) -> Program {
+    let mut parser = Parser::new(Lexer::new(input));
+    let program = parser.parse_program();
+    assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+    program
+}
+
+/// Names bound and read, in visiting order.
+#[derive(Default)]
+struct Names<'ast> {
+    events: Vec<(&'static str, &'ast str)>,
+}
+
+impl<'ast> Visitor<'ast> for Names<'ast> {
+    fn visit_expression(&mut self, expression: &'ast Expression) {
+        if let Expression::Identifier { value, .. } = expression {
+            self.events.push(("read", value));
+        }
+        walk_expression(self, expression);
+    }
+
+    fn visit_binding(&mut self, name: &'ast Identifier) {
+        self.events.push(("bind", &name.value));
+    }
+}
+
+#[test]
+fn visits_every_node_in_source_order() {
+    let program = parse(
+        "let f = fn(a, b) { if (a) { b[0] } else { {a: [b]} } };\nwhile (f(x, -y)) { let z = !w; break; }",
+    );
+    let mut names = Names::default();
+    names.visit_program(&program);
+    assert_eq!(
+        names.events,
+        vec![
+            ("bind", "f"),
+            ("bind", "a"),
+            ("bind", "b"),
+            ("read", "a"),
+            ("read", "b"),
+            ("read", "a"),
+            ("read", "b"),
+            ("read", "f"),
+            ("read", "x"),
+            ("read", "y"),
+            ("bind", "z"),
+            ("read", "w"),
+        ]
+    );
+}
+
+/// Counts calls outside function bodies by not walking into them.
+#[derive(Default)]
+struct TopLevelCalls(usize);
+
+impl<'ast> Visitor<'ast> for TopLevelCalls {
+    fn visit_expression(&mut self, expression: &'ast Expression) {
+        match expression {
+            Expression::FunctionLiteral { .. } => {}
+            Expression::Call { .. } => {
+                self.0 += 1;
+                walk_expression(self, expression);
+            }
+            _ => walk_expression(self, expression),
+        }
+    }
+}
+
+#[test]
+fn overrides_can_prune_the_traversal() {
+    let program = parse("let f = fn() { g(h()) };\nputs(f(), len([1]));");
+    let mut calls = TopLevelCalls::default();
+    calls.visit_program(&program);
+    assert_eq!(calls.0, 3);
+}
@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use monkey_rust_compiler::bench::BenchStats;
+
+fn ms(values: &[u64]) -> Vec<Duration> {
+    values.iter().map(|&v| Duration::from_millis(v)).collect()
+}
+
+#[test]
+fn stats_summarize_the_samples() {
+    let stats = BenchStats::new(ms(&[4, 1, 3, 2])).expect("samples");
+    assert_eq!(stats.iterations(), 4);
+    assert_eq!(stats.min(), Duration::from_millis(1));
+    assert_eq!(stats.max(), Duration::from_millis(4));
+    assert_eq!(stats.median(), Duration::from_micros(2500));
+    assert_eq!(stats.mean(), Duration::from_micros(2500));
+    // Sample standard deviation of 1..=4 is sqrt(5/3).
+    let stddev = stats.stddev().as_secs_f64() * 1000.0;
+    assert!((stddev - (5.0f64 / 3.0).sqrt()).abs() < 1e-6, "{stddev}");
+    assert_eq!(
+        BenchStats::new(ms(&[5, 1, 9])).map(|s| s.median()),
+        Some(Duration::from_millis(5))
+    );
+    assert_eq!(BenchStats::new(Vec::new()), None);
+}
+
+#[test]
+fn a_single_sample_has_no_spread() {
+    let stats = BenchStats::new(ms(&[7])).expect("samples");
+    assert_eq!(stats.stddev(), Duration::ZERO);
+    assert_eq!(stats.median(), stats.mean());
+}
+
+#[test]
+fn report_lists_timings_and_throughput() {
+    let stats = BenchStats::new(ms(&[2, 2])).expect("samples");
+    assert_eq!(
+        stats.render(3, Some(1000)),
+        "Iterations: 2 (3 warmup)\nMin: 2.000 ms\nMedian: 2.000 ms\nMean: 2.000 ms ± 0.000 ms\n\
+Max: 2.000 ms\nInstructions: 1000 per run, 500000 per second"
+    );
+    assert!(!stats.render(0, None).contains("Instructions"));
+}
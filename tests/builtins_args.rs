@@ -0,0 +1,41 @@
+use monkey_rust_compiler::runner::{
+    run_source, run_source_with, RunOptions, RunOutcome, RunnerError,
+};
+use monkey_rust_compiler::runtime_error::RuntimeErrorType;
+
+fn run_with_args(source: &str, args: &[&str]) -> Result<RunOutcome, RunnerError> {
+    let args = args.iter().map(|s| s.to_string()).collect();
+    run_source_with(source, &RunOptions::default().with_args(args))
+}
+
+#[test]
+fn args_returns_program_arguments_as_strings() {
+    let out = run_with_args("args();", &["a", "b c", "3"]).expect("run");
+    assert_eq!(out.result.inspect(), "[a, b c, 3]");
+
+    let out = run_with_args("args()[2] + \"!\";", &["a", "b", "3"]).expect("run");
+    assert_eq!(out.result.inspect(), "3!");
+}
+
+#[test]
+fn args_is_empty_without_arguments() {
+    let out = run_source("len(args());").expect("run");
+    assert_eq!(out.result.inspect(), "0");
+}
+
+#[test]
+fn args_are_visible_to_eval_child() {
+    let out = run_with_args("eval(\"first(args())\");", &["x"]).expect("run");
+    assert_eq!(out.result.inspect(), "x");
+}
+
+#[test]
+fn args_rejects_arguments() {
+    match run_source("args(1);") {
+        Err(RunnerError::Runtime(err)) => {
+            assert_eq!(err.error_type, RuntimeErrorType::WrongArgumentCount);
+            assert_eq!(err.message, "args expected 0 argument(s), got 1");
+        }
+        other => panic!("expected runtime error, got {other:?}"),
+    }
+}
@@ -0,0 +1,59 @@
+use monkey_rust_compiler::runner::{run_source, RunnerError};
+use monkey_rust_compiler::runtime_error::RuntimeErrorType;
+
+fn eval_err(src: &str) -> (RuntimeErrorType, String) {
+    match run_source(src) {
+        Err(RunnerError::Runtime(err)) => (err.error_type, err.message),
+        other => panic!("expected runtime error for {src:?}, got {other:?}"),
+    }
+}
+
+#[test]
+fn assert_returns_null_for_truthy_conditions() {
+    for src in [
+        "assert(true);",
+        "assert(0);",
+        "assert(\"\", \"unused\");",
+        "assert([]);",
+    ] {
+        let outcome = run_source(src).unwrap_or_else(|err| panic!("{src}: {err:?}"));
+        assert_eq!(outcome.result.inspect(), "null");
+    }
+}
+
+#[test]
+fn assert_fails_on_false_and_null_with_the_message() {
+    assert_eq!(
+        eval_err("assert(1 > 2);"),
+        (
+            RuntimeErrorType::AssertionFailed,
+            "assertion failed".to_string()
+        )
+    );
+    assert_eq!(
+        eval_err("assert([][0], \"empty\");"),
+        (
+            RuntimeErrorType::AssertionFailed,
+            "assertion failed: empty".to_string()
+        )
+    );
+    assert_eq!(
+        eval_err("assert(false, [1, 2]);"),
+        (
+            RuntimeErrorType::AssertionFailed,
+            "assertion failed: [1, 2]".to_string()
+        )
+    );
+}
+
+#[test]
+fn assert_takes_one_or_two_arguments() {
+    assert_eq!(
+        eval_err("assert();").0,
+        RuntimeErrorType::WrongArgumentCount
+    );
+    assert_eq!(
+        eval_err("assert(true, 1, 2);").0,
+        RuntimeErrorType::WrongArgumentCount
+    );
+}
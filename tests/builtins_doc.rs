@@ -0,0 +1,24 @@
+use monkey_rust_compiler::builtins::{builtin_doc, builtin_docs, builtin_names};
+
+#[test]
+fn every_builtin_is_documented_in_order() {
+    let documented: Vec<&str> = builtin_docs().iter().map(|doc| doc.name).collect();
+    assert_eq!(documented, builtin_names());
+    for doc in builtin_docs() {
+        assert!(
+            doc.signature.starts_with(&format!("{}(", doc.name)),
+            "{doc:?}"
+        );
+        assert!(doc.signature.contains(") -> "), "{doc:?}");
+        assert!(doc.summary.ends_with('.'), "{doc:?}");
+    }
+}
+
+#[test]
+fn lookup_is_by_name() {
+    assert_eq!(
+        builtin_doc("push").map(|doc| doc.signature),
+        Some("push(arr, value) -> ARRAY")
+    );
+    assert_eq!(builtin_doc("pop"), None);
+}
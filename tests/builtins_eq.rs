@@ -0,0 +1,72 @@
+use monkey_rust_compiler::runner::{run_source, RunnerError};
+use monkey_rust_compiler::runtime_error::RuntimeErrorType;
+
+fn eval(src: &str) -> String {
+    run_source(src)
+        .unwrap_or_else(|err| panic!("expected success for {src:?}, got {err:?}"))
+        .result
+        .inspect()
+}
+
+#[test]
+fn eq_compares_scalars_by_value() {
+    assert_eq!(eval("eq(1, 1);"), "true");
+    assert_eq!(eval("eq(1, 2);"), "false");
+    assert_eq!(eval(r#"eq("a", "a");"#), "true");
+    assert_eq!(eval("eq(true, true);"), "true");
+    assert_eq!(eval(r#"eq(1, "1");"#), "false");
+}
+
+#[test]
+fn eq_treats_null_as_equal_only_to_null() {
+    assert_eq!(eval("eq(if (false) { 1 }, if (false) { 2 });"), "true");
+    assert_eq!(eval("eq(if (false) { 1 }, false);"), "false");
+    assert_eq!(eval("eq([][0], 0);"), "false");
+}
+
+#[test]
+fn eq_compares_arrays_recursively() {
+    assert_eq!(eval("eq([1, [2, 3]], [1, [2, 3]]);"), "true");
+    assert_eq!(eval("eq([1, [2, 3]], [1, [2, 4]]);"), "false");
+    assert_eq!(eval("eq([1, 2], [1, 2, 3]);"), "false");
+    assert_eq!(eval("eq([], []);"), "true");
+}
+
+#[test]
+fn eq_compares_hashes_ignoring_insertion_order() {
+    assert_eq!(
+        eval(r#"eq({"a": 1, "b": [2]}, {"b": [2], "a": 1});"#),
+        "true"
+    );
+    assert_eq!(eval(r#"eq({"a": 1}, {"a": 2});"#), "false");
+    assert_eq!(eval(r#"eq({"a": 1}, {"a": 1, "b": 2});"#), "false");
+    assert_eq!(eval(r#"eq({"a": 1, "a": 2}, {"a": 2});"#), "true");
+    assert_eq!(
+        eval(r#"eq({1: {"x": [true]}}, {1: {"x": [true]}});"#),
+        "true"
+    );
+}
+
+#[test]
+fn eq_compares_functions_by_identity() {
+    assert_eq!(eval("let f = fn(x) { x }; eq(f, f);"), "true");
+    assert_eq!(eval("eq(fn(x) { x }, fn(x) { x });"), "false");
+    assert_eq!(
+        eval("let mk = fn() { fn() { 1 } }; eq(mk(), mk());"),
+        "false"
+    );
+    assert_eq!(eval("eq(len, len);"), "true");
+    assert_eq!(eval("eq(len, first);"), "false");
+    assert_eq!(eval("let f = fn() { 1 }; eq([f], [f]);"), "true");
+}
+
+#[test]
+fn eq_requires_two_arguments() {
+    match run_source("eq(1);") {
+        Err(RunnerError::Runtime(err)) => {
+            assert_eq!(err.error_type, RuntimeErrorType::WrongArgumentCount);
+            assert_eq!(err.message, "eq expected 2 argument(s), got 1");
+        }
+        other => panic!("expected runtime error, got {other:?}"),
+    }
+}
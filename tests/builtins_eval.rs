@@ -0,0 +1,75 @@
+use monkey_rust_compiler::runner::{run_source, RunnerError};
+use monkey_rust_compiler::runtime_error::RuntimeErrorType;
+
+fn run_ok(src: &str) -> String {
+    run_source(src)
+        .unwrap_or_else(|err| panic!("expected success for {src:?}, got {err:?}"))
+        .result
+        .inspect()
+}
+
+fn run_runtime_err(src: &str) -> monkey_rust_compiler::runtime_error::RuntimeError {
+    match run_source(src) {
+        Err(RunnerError::Runtime(err)) => err,
+        other => panic!("expected runtime error for {src:?}, got {other:?}"),
+    }
+}
+
+#[test]
+fn eval_returns_child_program_result() {
+    assert_eq!(run_ok(r#"eval("1 + 2 * 3");"#), "7");
+    assert_eq!(run_ok(r#"eval("let f = fn(x) { x * 2 }; f(21);");"#), "42");
+    assert_eq!(run_ok(r#"eval("let x = 1;");"#), "null");
+    assert_eq!(run_ok(r#"eval("");"#), "null");
+    assert_eq!(run_ok(r#"let src = "[1, " + "2]"; len(eval(src));"#), "2");
+}
+
+#[test]
+fn eval_runs_in_a_fresh_child_vm() {
+    let err = run_runtime_err(r#"let secret = 1; eval("secret");"#);
+    assert_eq!(err.error_type, RuntimeErrorType::EvalError);
+    assert!(
+        err.message.contains("unresolved identifier: secret"),
+        "{}",
+        err.message
+    );
+}
+
+#[test]
+fn eval_output_flows_into_parent_output() {
+    let out =
+        run_source(r#"puts("a"); eval("puts(2); 1"); puts("c");"#).expect("run should succeed");
+    assert_eq!(out.output, vec!["a", "2", "c"]);
+}
+
+#[test]
+fn eval_argument_checks_match_builtin_conventions() {
+    let err = run_runtime_err("eval(1);");
+    assert_eq!(err.error_type, RuntimeErrorType::InvalidArgumentType);
+    assert_eq!(err.message, "eval expected STRING, got INTEGER");
+
+    let err = run_runtime_err(r#"eval("1", "2");"#);
+    assert_eq!(err.error_type, RuntimeErrorType::WrongArgumentCount);
+    assert_eq!(err.message, "eval expected 1 argument(s), got 2");
+}
+
+#[test]
+fn eval_errors_are_reported_at_the_call_site() {
+    let err = run_runtime_err("let x = 1;\neval(\"let = ;\");");
+    assert_eq!(err.error_type, RuntimeErrorType::EvalError);
+    assert!(
+        err.message.starts_with("eval parse error: 1:5:"),
+        "{}",
+        err.message
+    );
+    assert_eq!(err.pos.line, 2);
+
+    let err = run_runtime_err("\n  eval(\"1 / 0\");");
+    assert_eq!(err.error_type, RuntimeErrorType::DivisionByZero);
+    assert_eq!(err.message, "in eval at 1:3: division by zero");
+    assert_eq!(err.pos.line, 2);
+    assert_eq!(
+        err.stack.last().map(|f| f.format_frame()),
+        Some("at <repl>(0 args) @ 2:7".to_string())
+    );
+}
@@ -0,0 +1,75 @@
+use monkey_rust_compiler::runner::{run_source, RunnerError};
+use monkey_rust_compiler::runtime_error::RuntimeErrorType;
+
+fn eval(src: &str) -> String {
+    run_source(src)
+        .unwrap_or_else(|err| panic!("expected success for {src:?}, got {err:?}"))
+        .result
+        .inspect()
+}
+
+fn eval_err(src: &str) -> (RuntimeErrorType, String) {
+    match run_source(src) {
+        Err(RunnerError::Runtime(err)) => (err.error_type, err.message),
+        other => panic!("expected runtime error for {src:?}, got {other:?}"),
+    }
+}
+
+#[test]
+fn substr_takes_character_ranges_and_clamps_to_the_end() {
+    assert_eq!(eval(r#"substr("monkey", 0, 3);"#), "mon");
+    assert_eq!(eval(r#"substr("monkey", 3, 10);"#), "key");
+    assert_eq!(eval(r#"len(substr("monkey", 6, 1));"#), "0");
+    assert_eq!(eval(r#"len(substr("monkey", 99, 1));"#), "0");
+    assert_eq!(eval(r#"substr("héllo", 1, 2);"#), "él");
+    assert_eq!(eval(r#"substr("monkey", -1, 2);"#), "null");
+    assert_eq!(eval(r#"substr("monkey", 1, -2);"#), "null");
+}
+
+#[test]
+fn char_at_returns_single_character_or_null() {
+    assert_eq!(eval(r#"char_at("abc", 0);"#), "a");
+    assert_eq!(eval(r#"char_at("abc", 2);"#), "c");
+    assert_eq!(eval(r#"char_at("abc", 3);"#), "null");
+    assert_eq!(eval(r#"char_at("abc", -1);"#), "null");
+}
+
+#[test]
+fn chars_splits_into_single_character_strings() {
+    assert_eq!(eval(r#"chars("abc");"#), "[a, b, c]");
+    assert_eq!(eval(r#"chars("");"#), "[]");
+    assert_eq!(eval(r#"len(chars("héllo"));"#), "5");
+    assert_eq!(eval(r#"first(chars("xy")) + last(chars("xy"));"#), "xy");
+}
+
+#[test]
+fn string_builtins_validate_arity_and_types() {
+    assert_eq!(
+        eval_err(r#"substr("abc", 1);"#),
+        (
+            RuntimeErrorType::WrongArgumentCount,
+            "substr expected 3 argument(s), got 2".to_string()
+        )
+    );
+    assert_eq!(
+        eval_err("substr(1, 0, 1);"),
+        (
+            RuntimeErrorType::InvalidArgumentType,
+            "substr expected STRING, got INTEGER".to_string()
+        )
+    );
+    assert_eq!(
+        eval_err(r#"char_at("abc", "1");"#),
+        (
+            RuntimeErrorType::InvalidArgumentType,
+            "char_at expected INTEGER, got STRING".to_string()
+        )
+    );
+    assert_eq!(
+        eval_err("chars([1]);"),
+        (
+            RuntimeErrorType::InvalidArgumentType,
+            "chars expected STRING, got ARRAY".to_string()
+        )
+    );
+}
@@ -1,8 +1,10 @@
 use monkey_rust_compiler::bytecode::{
-    lookup_definition, make, read_operands, Bytecode, BytecodeError, Opcode,
+    lookup_definition, make, position_at, read_operands, Bytecode, BytecodeError, ChunkStats,
+    Opcode, OperandKind,
 };
 use monkey_rust_compiler::object::Object;
-use monkey_rust_compiler::position::Position;
+use monkey_rust_compiler::position::{Position, Span};
+use monkey_rust_compiler::runner::compile_source;
 
 #[test]
 fn opcode_roundtrip_and_unknown_byte_behavior() {
@@ -48,6 +50,38 @@ fn opcode_definition_metadata_is_stable() {
     assert_eq!(ret.operand_widths, &[]);
 }
 
+#[test]
+fn every_operand_has_a_kind() {
+    for &op in Opcode::all() {
+        let def = lookup_definition(op);
+        assert_eq!(def.operand_kinds.len(), def.operand_widths.len(), "{op:?}");
+    }
+    assert_eq!(
+        lookup_definition(Opcode::Closure).operand_kinds,
+        &[OperandKind::ConstIndex, OperandKind::Count]
+    );
+    assert_eq!(
+        lookup_definition(Opcode::JumpIfFalsePop).operand_kinds,
+        &[OperandKind::JumpTarget]
+    );
+    assert_eq!(
+        lookup_definition(Opcode::AddLocals).operand_kinds,
+        &[OperandKind::LocalSlot, OperandKind::LocalSlot]
+    );
+    assert_eq!(
+        lookup_definition(Opcode::Call).operand_kinds,
+        &[OperandKind::Arity]
+    );
+
+    // Small integers are shown signed rather than as their raw `u16`.
+    let mut chunk = Bytecode::new();
+    chunk.push_bytes(&make(Opcode::ConstInt, &[(-5i16) as u16 as usize]).expect("encode"));
+    assert_eq!(chunk.disassemble(), "0000 ConstInt -5");
+    assert_eq!(OperandKind::SmallInt.parse("-5"), Some(65531));
+    assert_eq!(OperandKind::SmallInt.parse("40000"), None);
+    assert_eq!(OperandKind::GlobalSlot.render(65531), "65531");
+}
+
 #[test]
 fn make_encodes_instructions_deterministically() {
     let add = make(Opcode::Add, &[]).expect("encode add");
@@ -124,7 +158,7 @@ fn chunk_constant_pool_preserves_indices_and_order() {
     let mut chunk = Bytecode::new();
 
     let i0 = chunk.add_constant(Object::Integer(1).rc());
-    let i1 = chunk.add_constant(Object::String("hello".to_string()).rc());
+    let i1 = chunk.add_constant(Object::String("hello".into()).rc());
     let i2 = chunk.add_constant(Object::Boolean(true).rc());
 
     assert_eq!(i0, 0);
@@ -133,7 +167,7 @@ fn chunk_constant_pool_preserves_indices_and_order() {
 
     assert_eq!(chunk.constants.len(), 3);
     assert_eq!(*chunk.constants[0], Object::Integer(1));
-    assert_eq!(*chunk.constants[1], Object::String("hello".to_string()));
+    assert_eq!(*chunk.constants[1], Object::String("hello".into()));
     assert_eq!(*chunk.constants[2], Object::Boolean(true));
 }
 
@@ -155,6 +189,42 @@ fn chunk_position_mapping_uses_nearest_lower_offset() {
     assert_eq!(empty.position_for_offset(2), None);
 }
 
+#[test]
+fn spans_keep_the_reported_position_and_the_extent() {
+    let mut chunk = Bytecode::new();
+    let span = Span::new(
+        Position::new(1, 3),
+        Position::new(1, 1),
+        Position::new(1, 6),
+    );
+    chunk.record_pos(0, span);
+    chunk.record_pos(1, Position::new(2, 1));
+
+    assert_eq!(chunk.position_for_offset(0), Some(Position::new(1, 3)));
+    assert_eq!(chunk.span_for_offset(0), Some(span));
+    assert_eq!(
+        chunk.span_for_offset(1),
+        Some(Span::at(Position::new(2, 1)))
+    );
+    assert_eq!(span.to_string(), "1:3 (1:1-1:6)");
+    assert_eq!(Span::at(Position::new(2, 1)).to_string(), "2:1");
+}
+
+#[test]
+fn position_lookup_prefers_last_entry_for_duplicate_offsets() {
+    let positions = vec![
+        (0, Span::at(Position::new(1, 1))),
+        (4, Span::at(Position::new(1, 7))),
+        (4, Span::at(Position::new(2, 3))),
+        (9, Span::at(Position::new(3, 1))),
+    ];
+    assert_eq!(position_at(&positions, 3), Some(Position::new(1, 1)));
+    assert_eq!(position_at(&positions, 4), Some(Position::new(2, 3)));
+    assert_eq!(position_at(&positions, 8), Some(Position::new(2, 3)));
+    assert_eq!(position_at(&positions, 9), Some(Position::new(3, 1)));
+    assert_eq!(position_at(&[], 0), None);
+}
+
 #[test]
 fn disassembler_output_is_deterministic() {
     let mut chunk = Bytecode::new();
@@ -171,3 +241,66 @@ fn disassembler_output_is_deterministic() {
     let expected = "0000 Constant 1 @1:1\n0003 Add @1:5\n0004 JumpIfFalse 12 @2:3";
     assert_eq!(chunk.disassemble(), expected);
 }
+
+#[test]
+fn disassembly_lists_constants_and_nested_functions() {
+    let chunk = compile_source("let f = fn(a) { fn() { a + \"s\" } }; f(1)();").expect("compile");
+    assert_eq!(
+        chunk.disassemble(),
+        "\
+0000 Closure 2 0 @1:9 (1:9-1:35)
+0004 SetGlobal 0 @1:1
+0007 GetGlobal 0 @1:37 (1:37-1:38)
+0010 Const1 @1:39 (1:39-1:40)
+0011 Call 1 @1:38 (1:37-1:41)
+0013 Call 0 @1:41 (1:37-1:43)
+0015 ReturnValue @1:37 (1:37-1:43)
+
+Constants:
+0000 STRING \"s\"
+0001 FUNCTION <anonymous> arity=0 locals=0
+    0000 GetFree 0 @1:24 (1:24-1:25)
+    0002 Constant 0 @1:28 (1:28-1:31)
+    0005 Add @1:26 (1:24-1:31)
+    0006 ReturnValue @1:24 (1:24-1:31)
+0002 FUNCTION f arity=1 locals=1
+    0000 GetLocal 0 @1:17 (1:17-1:33)
+    0002 Closure 1 1 @1:17 (1:17-1:33)
+    0006 ReturnValue @1:17 (1:17-1:33)"
+    );
+}
+
+#[test]
+fn stats_count_code_constants_jumps_and_nesting() {
+    let chunk = compile_source(
+        "let make = fn(n) { fn(x) { if (x > n) { \"big\" } else { \"small\" } } };
+         let i = 0;
+         while (i < 3) { puts(make(1)(i)); let i = i + 1; }",
+    )
+    .expect("compile");
+    let stats = chunk.stats();
+    assert_eq!(
+        stats,
+        ChunkStats {
+            instructions: 37,
+            bytes: 78,
+            functions: 2,
+            constants: [("FUNCTION", 2), ("STRING", 2)].into_iter().collect(),
+            max_jump_distance: 35,
+            max_function_depth: 2,
+        }
+    );
+    assert_eq!(
+        stats.to_string(),
+        "instructions: 37 (78 bytes)\nfunctions: 2 (max nesting depth 2)\n\
+         constants: 4 (FUNCTION 2, STRING 2)\nmax jump distance: 35 bytes"
+    );
+
+    let empty = Bytecode::new().stats();
+    assert_eq!(empty, ChunkStats::default());
+    assert_eq!(
+        empty.to_string(),
+        "instructions: 0 (0 bytes)\nfunctions: 0 (max nesting depth 0)\nconstants: 0\n\
+         max jump distance: 0 bytes"
+    );
+}
@@ -0,0 +1,121 @@
+use monkey_rust_compiler::bytecode::{make, Opcode};
+use monkey_rust_compiler::cfg::{build, to_dot, BasicBlock, Edge, EdgeKind};
+use monkey_rust_compiler::runner::compile_source;
+
+fn blocks_of(input: &str) -> Vec<BasicBlock> {
+    let chunk = compile_source(input).expect("compile should succeed");
+    build(&chunk.instructions).expect("bytecode should decode")
+}
+
+fn edges(blocks: &[BasicBlock]) -> Vec<Vec<(usize, EdgeKind)>> {
+    blocks
+        .iter()
+        .map(|block| {
+            block
+                .edges
+                .iter()
+                .map(|edge| (edge.target, edge.kind))
+                .collect()
+        })
+        .collect()
+}
+
+fn encode(code: &[(Opcode, &[usize])]) -> Vec<u8> {
+    code.iter()
+        .flat_map(|(op, operands)| make(*op, operands).expect("encode"))
+        .collect()
+}
+
+#[test]
+fn straight_line_code_is_one_block() {
+    let blocks = blocks_of("let a = 1; a + 2;");
+    assert_eq!(blocks.len(), 1);
+    assert_eq!(blocks[0].start, 0);
+    assert!(blocks[0].edges.is_empty());
+}
+
+#[test]
+fn if_else_branches_and_joins() {
+    use EdgeKind::*;
+    let blocks = blocks_of("let a = true; if (a) { 1 } else { 2 };");
+    assert_eq!(
+        edges(&blocks),
+        [
+            vec![(1, True), (2, False)],
+            vec![(3, Jump)],
+            vec![(3, Fallthrough)],
+            vec![],
+        ]
+    );
+    assert_eq!(blocks[3].instructions[0].1, Opcode::ReturnValue);
+}
+
+#[test]
+fn while_loops_jump_back_to_the_condition() {
+    use EdgeKind::*;
+    let blocks = blocks_of("let i = 0; while (i < 3) { let i = i + 1; };");
+    assert_eq!(
+        edges(&blocks),
+        [
+            vec![(1, Fallthrough)],
+            vec![(2, True), (3, False)],
+            vec![(1, Jump)],
+            vec![],
+        ]
+    );
+    assert_eq!(
+        blocks[2].edges[0],
+        Edge {
+            target: 1,
+            kind: Jump
+        }
+    );
+}
+
+#[test]
+fn short_circuit_operators_get_their_own_blocks() {
+    use EdgeKind::*;
+    let blocks = blocks_of("let a = true; a && false;");
+    assert_eq!(
+        edges(&blocks),
+        [
+            vec![(1, True), (2, False)],
+            vec![(3, Jump)],
+            vec![(3, Fallthrough)],
+            vec![],
+        ]
+    );
+}
+
+#[test]
+fn blocks_cover_the_code_in_order() {
+    let blocks = blocks_of("let f = fn(n) { if (n > 1) { return n; } 0 }; f(2) || f(0);");
+    let mut next = 0;
+    for block in &blocks {
+        assert_eq!(block.start, next);
+        assert!(block.end > block.start);
+        next = block.end;
+    }
+}
+
+#[test]
+fn rejects_code_that_does_not_decode() {
+    assert_eq!(build(&[0xff]), None);
+    let into_operand = encode(&[(Opcode::Jump, &[1]), (Opcode::Null, &[])]);
+    assert_eq!(build(&into_operand), None);
+    assert_eq!(build(&[]), Some(Vec::new()));
+}
+
+#[test]
+fn dot_output_has_a_cluster_per_function() {
+    let chunk = compile_source("let f = fn(n) { if (n) { 1 } else { 2 } }; f(true);")
+        .expect("compile should succeed");
+    let dot = to_dot(&chunk);
+    assert!(dot.starts_with("digraph cfg {\n"));
+    assert!(dot.ends_with("}\n"));
+    assert!(dot.contains("  subgraph cluster_0 {\n    label=\"<main>\";\n"));
+    assert!(dot.contains("  subgraph cluster_1 {\n    label=\"f (constant 0)\";\n"));
+    assert!(dot.contains("    f1_b0 -> f1_b1 [label=\"true\"];\n"));
+    assert!(dot.contains("    f1_b0 -> f1_b2 [label=\"false\"];\n"));
+    assert!(dot.contains("0000 GetLocal 0\\l"));
+}
@@ -1,4 +1,7 @@
-use monkey_rust_compiler::cli::{parse_args, Command};
+use monkey_rust_compiler::cli::{parse_args, Command, RunFlags, UsageError, STDIN_PATH, USAGE};
+use monkey_rust_compiler::diagnostics::ErrorFormat;
+use monkey_rust_compiler::format::FormatOptions;
+use monkey_rust_compiler::pretty::{AstFormat, TokenFormat};
 
 fn args(parts: &[&str]) -> Vec<String> {
     parts.iter().map(|s| s.to_string()).collect()
@@ -6,40 +9,580 @@ fn args(parts: &[&str]) -> Vec<String> {
 
 #[test]
 fn parses_valid_commands() {
-    assert_eq!(parse_args(&args(&[])), Ok(Command::Repl));
-    assert_eq!(parse_args(&args(&["repl"])), Ok(Command::Repl));
+    assert_eq!(
+        parse_args(&args(&[])),
+        Ok(Command::Repl { no_color: false })
+    );
+    assert_eq!(
+        parse_args(&args(&["repl"])),
+        Ok(Command::Repl { no_color: false })
+    );
+    assert_eq!(
+        parse_args(&args(&["repl", "--no-color"])),
+        Ok(Command::Repl { no_color: true })
+    );
+    assert_eq!(
+        parse_args(&args(&["--no-color"])),
+        Ok(Command::Repl { no_color: true })
+    );
     assert_eq!(parse_args(&args(&["--help"])), Ok(Command::Help));
     assert_eq!(parse_args(&args(&["-h"])), Ok(Command::Help));
     assert_eq!(
         parse_args(&args(&["run", "a.monkey"])),
         Ok(Command::Run {
-            path: "a.monkey".to_string()
+            path: "a.monkey".to_string(),
+            args: Vec::new(),
+            flags: RunFlags::default(),
         })
     );
     assert_eq!(
         parse_args(&args(&["bench", "a.monkey"])),
         Ok(Command::Bench {
-            path: "a.monkey".to_string()
+            path: "a.monkey".to_string(),
+            args: Vec::new(),
+            flags: RunFlags::default(),
         })
     );
     assert_eq!(
         parse_args(&args(&["--tokens", "a.monkey"])),
         Ok(Command::Tokens {
-            path: "a.monkey".to_string()
+            path: "a.monkey".to_string(),
+            format: TokenFormat::Text,
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["--tokens", "a.monkey", "--format", "json"])),
+        Ok(Command::Tokens {
+            path: "a.monkey".to_string(),
+            format: TokenFormat::Json,
         })
     );
+    assert_eq!(
+        parse_args(&args(&["--tokens", "a.monkey", "--format", "sexpr"])),
+        Err(UsageError)
+    );
     assert_eq!(
         parse_args(&args(&["--ast", "a.monkey"])),
         Ok(Command::Ast {
+            path: "a.monkey".to_string(),
+            format: AstFormat::Text,
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["--ast", "--format", "sexpr", "a.monkey"])),
+        Ok(Command::Ast {
+            path: "a.monkey".to_string(),
+            format: AstFormat::Sexpr,
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["--ast", "a.monkey", "--format", "json"])),
+        Ok(Command::Ast {
+            path: "a.monkey".to_string(),
+            format: AstFormat::Json,
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["--ast", "a.monkey", "--format", "yaml"])),
+        Err(UsageError)
+    );
+    assert_eq!(
+        parse_args(&args(&["--ast", "a.monkey", "--format"])),
+        Err(UsageError)
+    );
+    assert_eq!(
+        parse_args(&args(&[
+            "--ast", "a.monkey", "--width", "60", "--format", "pretty", "--indent", "4"
+        ])),
+        Ok(Command::Ast {
+            path: "a.monkey".to_string(),
+            format: AstFormat::Pretty(
+                FormatOptions::default()
+                    .with_indent_width(4)
+                    .with_max_width(60)
+            ),
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["--ast", "a.monkey", "--indent", "4"])),
+        Err(UsageError)
+    );
+    assert_eq!(
+        parse_args(&args(&[
+            "--ast", "a.monkey", "--format", "pretty", "--width", "x"
+        ])),
+        Err(UsageError)
+    );
+    assert_eq!(
+        parse_args(&args(&["--ast-json", "a.monkey"])),
+        Ok(Command::AstJson {
             path: "a.monkey".to_string()
         })
     );
 }
 
+#[test]
+fn parses_trailing_script_arguments_after_separator() {
+    assert_eq!(
+        parse_args(&args(&["run", "a.monkey", "--", "x", "--", "-h"])),
+        Ok(Command::Run {
+            path: "a.monkey".to_string(),
+            args: args(&["x", "--", "-h"]),
+            flags: RunFlags::default(),
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["bench", "a.monkey", "--"])),
+        Ok(Command::Bench {
+            path: "a.monkey".to_string(),
+            args: Vec::new(),
+            flags: RunFlags::default(),
+        })
+    );
+}
+
 #[test]
 fn invalid_combinations_return_usage_error() {
     assert!(parse_args(&args(&["run"])).is_err());
     assert!(parse_args(&args(&["--tokens"])).is_err());
     assert!(parse_args(&args(&["unknown"])).is_err());
     assert!(parse_args(&args(&["run", "a", "extra"])).is_err());
+    assert!(parse_args(&args(&["--tokens", "a", "--", "x"])).is_err());
+    assert!(parse_args(&args(&["run", "a", "--timeout"])).is_err());
+    assert!(parse_args(&args(&["run", "a", "--timeout", "soon"])).is_err());
+    assert!(parse_args(&args(&["run", "--timeout", "5", "--", "x"])).is_err());
+    assert!(parse_args(&args(&["run", "a", "--bogus"])).is_err());
+    assert!(parse_args(&args(&["debug"])).is_err());
+    assert!(parse_args(&args(&["debug", "a", "--trace"])).is_err());
+
+    let err = parse_args(&args(&["unknown"])).expect_err("unknown command");
+    assert_eq!(err, UsageError);
+    assert_eq!(err.to_string(), USAGE);
+    assert!(USAGE.starts_with("Usage: monkey [repl"));
+}
+
+#[test]
+fn parses_timeout_flag_before_or_after_path() {
+    let expected = Command::Run {
+        path: "a.monkey".to_string(),
+        args: args(&["x"]),
+        flags: RunFlags {
+            timeout_ms: Some(250),
+            ..RunFlags::default()
+        },
+    };
+    assert_eq!(
+        parse_args(&args(&["run", "a.monkey", "--timeout", "250", "--", "x"])),
+        Ok(expected.clone())
+    );
+    assert_eq!(
+        parse_args(&args(&["run", "--timeout", "250", "a.monkey", "--", "x"])),
+        Ok(expected)
+    );
+}
+
+#[test]
+fn parses_instrumentation_flags() {
+    assert_eq!(
+        parse_args(&args(&[
+            "bench",
+            "a.monkey",
+            "--trace",
+            "--profile",
+            "--no-fuse"
+        ])),
+        Ok(Command::Bench {
+            path: "a.monkey".to_string(),
+            args: Vec::new(),
+            flags: RunFlags {
+                trace: true,
+                profile: true,
+                no_fuse: true,
+                ..RunFlags::default()
+            },
+        })
+    );
+}
+
+#[test]
+fn parses_warnings_flag() {
+    assert_eq!(
+        parse_args(&args(&["run", "a.monkey", "--warnings"])),
+        Ok(Command::Run {
+            path: "a.monkey".to_string(),
+            args: Vec::new(),
+            flags: RunFlags {
+                warnings: true,
+                ..RunFlags::default()
+            },
+        })
+    );
+}
+
+#[test]
+fn parses_stats_flag() {
+    assert_eq!(
+        parse_args(&args(&["bench", "--stats", "a.monkey"])),
+        Ok(Command::Bench {
+            path: "a.monkey".to_string(),
+            args: Vec::new(),
+            flags: RunFlags {
+                stats: true,
+                ..RunFlags::default()
+            },
+        })
+    );
+}
+
+#[test]
+fn parses_coverage_flag() {
+    assert_eq!(
+        parse_args(&args(&["run", "a.monkey", "--coverage", "--", "x"])),
+        Ok(Command::Run {
+            path: "a.monkey".to_string(),
+            args: args(&["x"]),
+            flags: RunFlags {
+                coverage: true,
+                ..RunFlags::default()
+            },
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["run", "a.monkey", "--register", "--coverage"])),
+        Err(UsageError)
+    );
+}
+
+#[test]
+fn parses_exit_with_result_flag() {
+    assert_eq!(
+        parse_args(&args(&["bench", "a.monkey", "--exit-with-result"])),
+        Ok(Command::Bench {
+            path: "a.monkey".to_string(),
+            args: vec![],
+            flags: RunFlags {
+                exit_with_result: true,
+                ..RunFlags::default()
+            },
+        })
+    );
+}
+
+#[test]
+fn parses_debug_command() {
+    assert_eq!(
+        parse_args(&args(&["debug", "a.monkey", "--", "x"])),
+        Ok(Command::Debug {
+            path: "a.monkey".to_string(),
+            args: args(&["x"]),
+        })
+    );
+}
+
+#[test]
+fn parses_compile_command() {
+    assert_eq!(
+        parse_args(&args(&["compile", "a.monkey"])),
+        Ok(Command::Compile {
+            path: "a.monkey".to_string(),
+            output: None,
+            stats: false,
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["compile", "a.monkey", "-o", "b.mkc"])),
+        Ok(Command::Compile {
+            path: "a.monkey".to_string(),
+            output: Some("b.mkc".to_string()),
+            stats: false,
+        })
+    );
+    assert!(parse_args(&args(&["compile", "a.monkey", "-o"])).is_err());
+    assert_eq!(
+        parse_args(&args(&["compile", "--stats", "a.monkey", "-o", "b.mkc"])),
+        Ok(Command::Compile {
+            path: "a.monkey".to_string(),
+            output: Some("b.mkc".to_string()),
+            stats: true,
+        })
+    );
+    assert!(parse_args(&args(&["compile"])).is_err());
+    assert!(parse_args(&args(&["compile", "a.monkey", "b.monkey"])).is_err());
+    assert!(parse_args(&args(&["compile", "a.monkey", "-o", "b", "-o", "c"])).is_err());
+}
+
+#[test]
+fn parses_wasm_command() {
+    assert_eq!(
+        parse_args(&args(&["wasm", "a.monkey"])),
+        Ok(Command::Wasm {
+            path: "a.monkey".to_string(),
+            output: None,
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["wasm", "a.monkey", "-o", "a.wasm"])),
+        Ok(Command::Wasm {
+            path: "a.monkey".to_string(),
+            output: Some("a.wasm".to_string()),
+        })
+    );
+}
+
+#[test]
+fn parses_emit_c_command() {
+    assert_eq!(
+        parse_args(&args(&["emit-c", "a.monkey", "-o", "a.c"])),
+        Ok(Command::EmitC {
+            path: "a.monkey".to_string(),
+            output: Some("a.c".to_string()),
+        })
+    );
+    assert!(parse_args(&args(&["emit-c"])).is_err());
+}
+
+#[test]
+fn parses_explain_command() {
+    assert_eq!(
+        parse_args(&args(&["explain", "C001"])),
+        Ok(Command::Explain {
+            code: "C001".to_string()
+        })
+    );
+    assert!(parse_args(&args(&["explain"])).is_err());
+}
+
+#[test]
+fn parses_disasm_command() {
+    assert_eq!(
+        parse_args(&args(&["disasm", "a.monkey"])),
+        Ok(Command::Disasm {
+            path: "a.monkey".to_string()
+        })
+    );
+    assert!(parse_args(&args(&["disasm", "a.monkey", "b.monkey"])).is_err());
+}
+
+#[test]
+fn parses_cfg_command() {
+    assert_eq!(
+        parse_args(&args(&["cfg", "a.monkey"])),
+        Ok(Command::Cfg {
+            path: "a.monkey".to_string()
+        })
+    );
+    assert!(parse_args(&args(&["cfg"])).is_err());
+}
+
+#[test]
+fn parses_register_flag_without_instrumentation() {
+    assert_eq!(
+        parse_args(&args(&["bench", "a.monkey", "--register"])),
+        Ok(Command::Bench {
+            path: "a.monkey".to_string(),
+            args: Vec::new(),
+            flags: RunFlags {
+                register: true,
+                ..RunFlags::default()
+            },
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["run", "a.monkey", "--register", "--trace"])),
+        Err(UsageError)
+    );
+}
+
+#[test]
+fn parses_fmt_paths_and_check_flag() {
+    assert_eq!(
+        parse_args(&args(&["fmt", "a.monkey", "b.monkey"])),
+        Ok(Command::Fmt {
+            paths: args(&["a.monkey", "b.monkey"]),
+            check: false,
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["fmt", "--check", "a.monkey"])),
+        Ok(Command::Fmt {
+            paths: args(&["a.monkey"]),
+            check: true,
+        })
+    );
+    assert_eq!(parse_args(&args(&["fmt"])), Err(UsageError));
+    assert_eq!(parse_args(&args(&["fmt", "--check"])), Err(UsageError));
+    assert_eq!(
+        parse_args(&args(&["fmt", "a.monkey", "--write"])),
+        Err(UsageError)
+    );
+}
+
+#[test]
+fn parses_check_paths_and_deny_warnings_flag() {
+    assert_eq!(
+        parse_args(&args(&["check", "a.monkey", "--deny-warnings", "b.monkey"])),
+        Ok(Command::Check {
+            paths: args(&["a.monkey", "b.monkey"]),
+            deny_warnings: true,
+            error_format: ErrorFormat::Text,
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["check", "a.monkey"])),
+        Ok(Command::Check {
+            paths: args(&["a.monkey"]),
+            deny_warnings: false,
+            error_format: ErrorFormat::Text,
+        })
+    );
+    assert_eq!(parse_args(&args(&["check"])), Err(UsageError));
+    assert_eq!(
+        parse_args(&args(&["check", "a.monkey", "--run"])),
+        Err(UsageError)
+    );
+}
+
+#[test]
+fn parses_error_format_flag() {
+    assert_eq!(
+        parse_args(&args(&["run", "a.monkey", "--error-format", "json"])),
+        Ok(Command::Run {
+            path: "a.monkey".to_string(),
+            args: vec![],
+            flags: RunFlags {
+                error_format: ErrorFormat::Json,
+                ..RunFlags::default()
+            },
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["check", "--error-format", "json", "a.monkey"])),
+        Ok(Command::Check {
+            paths: args(&["a.monkey"]),
+            deny_warnings: false,
+            error_format: ErrorFormat::Json,
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["run", "a.monkey", "--error-format", "xml"])),
+        Err(UsageError)
+    );
+    assert_eq!(
+        parse_args(&args(&["check", "a.monkey", "--error-format"])),
+        Err(UsageError)
+    );
+}
+
+#[test]
+fn dash_reads_the_program_from_stdin() {
+    assert_eq!(
+        parse_args(&args(&["run", "-", "--", "x"])),
+        Ok(Command::Run {
+            path: STDIN_PATH.to_string(),
+            args: args(&["x"]),
+            flags: RunFlags::default(),
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["--tokens", "-"])),
+        Ok(Command::Tokens {
+            path: STDIN_PATH.to_string(),
+            format: TokenFormat::Text,
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["--ast", "--format", "sexpr", "-"])),
+        Ok(Command::Ast {
+            path: STDIN_PATH.to_string(),
+            format: AstFormat::Sexpr,
+        })
+    );
+    assert_eq!(parse_args(&args(&["--ast", "-", "-"])), Err(UsageError));
+    assert_eq!(parse_args(&args(&["debug", "-"])), Err(UsageError));
+}
+
+#[test]
+fn parses_watch_flag_for_run_only() {
+    assert_eq!(
+        parse_args(&args(&["run", "a.monkey", "--watch"])),
+        Ok(Command::Run {
+            path: "a.monkey".to_string(),
+            args: vec![],
+            flags: RunFlags {
+                watch: true,
+                ..RunFlags::default()
+            },
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["bench", "a.monkey", "--watch"])),
+        Err(UsageError)
+    );
+    assert_eq!(parse_args(&args(&["run", "-", "--watch"])), Err(UsageError));
+}
+
+#[test]
+fn parses_test_command() {
+    assert_eq!(
+        parse_args(&args(&["test"])),
+        Ok(Command::Test { paths: vec![] })
+    );
+    assert_eq!(
+        parse_args(&args(&["test", "tests", "a_test.monkey"])),
+        Ok(Command::Test {
+            paths: args(&["tests", "a_test.monkey"]),
+        })
+    );
+    assert_eq!(parse_args(&args(&["test", "--verbose"])), Err(UsageError));
+}
+
+#[test]
+fn parses_bench_iterations_and_warmup() {
+    assert_eq!(
+        parse_args(&args(&[
+            "bench",
+            "a.monkey",
+            "--iterations",
+            "5",
+            "--warmup",
+            "0"
+        ])),
+        Ok(Command::Bench {
+            path: "a.monkey".to_string(),
+            args: vec![],
+            flags: RunFlags {
+                iterations: Some(5),
+                warmup: Some(0),
+                ..RunFlags::default()
+            },
+        })
+    );
+    assert_eq!(
+        parse_args(&args(&["bench", "a.monkey", "--iterations", "0"])),
+        Err(UsageError)
+    );
+    assert_eq!(
+        parse_args(&args(&["bench", "a.monkey", "--warmup", "x"])),
+        Err(UsageError)
+    );
+    assert_eq!(
+        parse_args(&args(&["run", "a.monkey", "--iterations", "5"])),
+        Err(UsageError)
+    );
+}
+
+#[test]
+fn parses_profile_command() {
+    assert_eq!(
+        parse_args(&args(&["profile", "a.monkey", "--", "x"])),
+        Ok(Command::Profile {
+            path: "a.monkey".to_string(),
+            args: args(&["x"]),
+        })
+    );
+    assert_eq!(parse_args(&args(&["profile"])), Err(UsageError));
+    assert_eq!(
+        parse_args(&args(&["profile", "a.monkey", "--trace"])),
+        Err(UsageError)
+    );
 }
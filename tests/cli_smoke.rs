@@ -32,6 +32,19 @@ fn tokens_and_ast_modes_smoke() {
     assert!(tokens.status.success());
     assert!(String::from_utf8_lossy(&tokens.stdout).contains("While('while')"));
 
+    let json_tokens = Command::new(bin())
+        .args([
+            "--tokens",
+            "--format",
+            "json",
+            tokens_path.to_str().expect("utf8 path"),
+        ])
+        .output()
+        .expect("failed to execute monkey --tokens --format json");
+    assert!(json_tokens.status.success());
+    assert!(String::from_utf8_lossy(&json_tokens.stdout)
+        .contains(r#"{"kind": "While", "literal": "while", "line": "#));
+
     let mut ast_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     ast_path.push("examples/closures.monkey");
 
@@ -41,4 +54,737 @@ fn tokens_and_ast_modes_smoke() {
         .expect("failed to execute monkey --ast");
     assert!(ast.status.success());
     assert!(String::from_utf8_lossy(&ast.stdout).contains("fn(a)"));
+
+    let sexpr = Command::new(bin())
+        .args([
+            "--ast",
+            ast_path.to_str().expect("utf8 path"),
+            "--format",
+            "sexpr",
+        ])
+        .output()
+        .expect("failed to execute monkey --ast --format sexpr");
+    assert!(sexpr.status.success());
+    assert!(String::from_utf8_lossy(&sexpr.stdout).contains("(fn (a)"));
+
+    let pretty = Command::new(bin())
+        .args([
+            "--ast",
+            ast_path.to_str().expect("utf8 path"),
+            "--format",
+            "pretty",
+            "--indent",
+            "4",
+        ])
+        .output()
+        .expect("failed to execute monkey --ast --format pretty");
+    assert!(pretty.status.success());
+    assert!(
+        String::from_utf8_lossy(&pretty.stdout).contains("fn(a) {\n    fn(b) {\n        (a + b);")
+    );
+
+    let ast_json = Command::new(bin())
+        .args(["--ast-json", ast_path.to_str().expect("utf8 path")])
+        .output()
+        .expect("failed to execute monkey --ast-json");
+    assert!(ast_json.status.success());
+    let stdout = String::from_utf8_lossy(&ast_json.stdout);
+    assert!(stdout.starts_with("{\n  \"node\": \"Program\","));
+    assert!(stdout.contains("\"node\": \"FunctionLiteral\""));
+}
+
+#[test]
+fn run_mode_passes_trailing_arguments_to_program() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("monkey_cli_args_{}.monkey", std::process::id()));
+    std::fs::write(&path, "let a = args(); puts(len(a)); a;").expect("write temp script");
+
+    let output = Command::new(bin())
+        .args(["run", path.to_str().expect("utf8 path"), "--", "one", "two"])
+        .output()
+        .expect("failed to execute monkey binary");
+    let _ = std::fs::remove_file(&path);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "2\n[one, two]\n");
+}
+
+#[test]
+fn run_mode_timeout_flag_stops_infinite_loop() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("monkey_cli_timeout_{}.monkey", std::process::id()));
+    std::fs::write(&path, "while (true) {}").expect("write temp script");
+
+    let output = Command::new(bin())
+        .args(["run", path.to_str().expect("utf8 path"), "--timeout", "50"])
+        .output()
+        .expect("failed to execute monkey binary");
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Error[TIMEOUT]"), "stderr: {stderr}");
+}
+
+#[test]
+fn run_mode_trace_flag_writes_trace_to_stderr() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("monkey_cli_trace_{}.monkey", std::process::id()));
+    std::fs::write(&path, "1 + 2;").expect("write temp script");
+
+    let output = Command::new(bin())
+        .args([
+            "run",
+            "--trace",
+            "--no-fuse",
+            path.to_str().expect("utf8 path"),
+        ])
+        .output()
+        .expect("failed to execute monkey binary");
+    let _ = std::fs::remove_file(&path);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("<repl> 0004 Add stack=2 @1:3"),
+        "stderr: {stderr}"
+    );
+}
+
+#[test]
+fn bench_mode_profile_flag_prints_report_to_stderr() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("examples/closures.monkey");
+
+    let output = Command::new(bin())
+        .args(["bench", path.to_str().expect("utf8 path"), "--profile"])
+        .output()
+        .expect("failed to execute monkey binary");
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Execution time:"), "stderr: {stderr}");
+    assert!(stderr.contains("Opcodes:"), "stderr: {stderr}");
+    assert!(stderr.contains("Functions:"), "stderr: {stderr}");
+}
+
+#[test]
+fn run_prints_output_as_the_program_runs() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("monkey_cli_stream_{}.monkey", std::process::id()));
+    std::fs::write(
+        &path,
+        "puts(\"before\");\nlet f = fn() { puts(\"inside\"); 1 / 0 };\nf();",
+    )
+    .expect("write temp script");
+
+    let output = Command::new(bin())
+        .arg("run")
+        .arg(&path)
+        .output()
+        .expect("failed to execute monkey binary");
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "before\ninside\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("DIVISION_BY_ZERO"), "stderr: {stderr}");
+}
+
+#[test]
+fn bench_prints_output_once_and_timing_statistics() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("monkey_cli_bench_{}.monkey", std::process::id()));
+    std::fs::write(&path, "puts(\"hi\");\n1 + 2").expect("write temp script");
+
+    let output = Command::new(bin())
+        .arg("bench")
+        .arg(&path)
+        .args(["--iterations", "4", "--warmup", "2"])
+        .output()
+        .expect("failed to execute monkey binary");
+    let _ = std::fs::remove_file(&path);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hi\n3\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let labels: Vec<&str> = stderr
+        .lines()
+        .map(|line| line.split(':').next().unwrap_or_default())
+        .collect();
+    assert_eq!(
+        labels,
+        [
+            "Execution time",
+            "Iterations",
+            "Min",
+            "Median",
+            "Mean",
+            "Max",
+            "Instructions"
+        ]
+    );
+    assert!(stderr.starts_with("Execution time: "), "{stderr}");
+    assert!(stderr.contains("\nIterations: 4 (2 warmup)\n"), "{stderr}");
+    assert!(stderr.contains("Instructions: 8 per run, "), "{stderr}");
+}
+
+#[test]
+fn profile_prints_folded_stacks_and_sends_output_to_stderr() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("monkey_cli_profile_{}.monkey", std::process::id()));
+    std::fs::write(
+        &path,
+        "let double = fn(x) { x * 2 };\nlet run = fn() { double(21) };\nputs(run());\n",
+    )
+    .expect("write temp script");
+
+    let output = Command::new(bin())
+        .arg("profile")
+        .arg(&path)
+        .output()
+        .expect("failed to execute monkey binary");
+    let _ = std::fs::remove_file(&path);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "42\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stacks: Vec<&str> = stdout
+        .lines()
+        .map(|line| line.rsplit_once(' ').expect("stack and count").0)
+        .collect();
+    assert_eq!(stacks, ["<main>", "<main>;run", "<main>;run;double"]);
+}
+
+#[test]
+fn compile_writes_mkc_that_run_executes() {
+    let dir = std::env::temp_dir();
+    let source = dir.join(format!("monkey_cli_compile_{}.monkey", std::process::id()));
+    let compiled = dir.join(format!("monkey_cli_compile_{}.mkc", std::process::id()));
+    std::fs::write(&source, "puts(\"compiled\"); len(args());").expect("write temp script");
+
+    let compile = Command::new(bin())
+        .args(["compile", source.to_str().expect("utf8 path")])
+        .output()
+        .expect("failed to execute monkey compile");
+    let _ = std::fs::remove_file(&source);
+    assert!(compile.status.success());
+
+    let output = Command::new(bin())
+        .args(["run", compiled.to_str().expect("utf8 path"), "--", "a"])
+        .output()
+        .expect("failed to execute monkey run");
+    let _ = std::fs::remove_file(&compiled);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "compiled\n1\n");
+}
+
+#[test]
+fn warnings_are_printed_by_compile_and_by_run_only_on_request() {
+    let dir = std::env::temp_dir();
+    let path = dir.join(format!("monkey_cli_warnings_{}.monkey", std::process::id()));
+    let mkc = path.with_extension("mkc");
+    std::fs::write(&path, "let f = fn(x) { 1 };\nf(2);").expect("write temp script");
+    let path = path.to_str().expect("utf8 path");
+    let expected = format!("Warnings in {path}:\n- 1:12: unused parameter: x [W002]\n");
+
+    let plain = Command::new(bin())
+        .args(["run", path])
+        .output()
+        .expect("failed to execute monkey binary");
+    let warned = Command::new(bin())
+        .args(["run", path, "--warnings"])
+        .output()
+        .expect("failed to execute monkey binary");
+    let compiled = Command::new(bin())
+        .args(["compile", path])
+        .output()
+        .expect("failed to execute monkey binary");
+    let _ = std::fs::remove_file(path);
+    let _ = std::fs::remove_file(&mkc);
+
+    assert!(plain.status.success());
+    assert_eq!(String::from_utf8_lossy(&plain.stderr), "");
+    assert!(warned.status.success());
+    assert_eq!(String::from_utf8_lossy(&warned.stdout), "1\n");
+    assert_eq!(String::from_utf8_lossy(&warned.stderr), expected);
+    assert!(compiled.status.success());
+    assert_eq!(String::from_utf8_lossy(&compiled.stderr), expected);
+}
+
+#[test]
+fn run_mode_reports_every_compile_error() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("monkey_cli_errors_{}.monkey", std::process::id()));
+    std::fs::write(&path, "puts(a);\nputs(b);").expect("write temp script");
+    let path = path.to_str().expect("utf8 path").to_string();
+
+    let output = Command::new(bin())
+        .args(["run", &path])
+        .output()
+        .expect("failed to execute monkey binary");
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!(
+            "Compile error in {path}:\n1:6: unresolved identifier: a\n2:6: unresolved identifier: b\n"
+        )
+    );
+}
+
+#[test]
+fn explain_prints_the_description_of_a_code() {
+    let output = Command::new(bin())
+        .args(["explain", "r009"])
+        .output()
+        .expect("failed to execute monkey binary");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "R009: DIVISION_BY_ZERO\n\nInteger division by zero.\n\nExample:\n\n    1 / 0;\n"
+    );
+
+    let unknown = Command::new(bin())
+        .args(["explain", "X999"])
+        .output()
+        .expect("failed to execute monkey binary");
+    assert_eq!(unknown.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&unknown.stderr),
+        "Unknown error code: X999\n"
+    );
+}
+
+#[test]
+fn cfg_prints_a_graphviz_digraph() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("examples/fact.monkey");
+
+    let output = Command::new(bin())
+        .args(["cfg", path.to_str().expect("utf8 path")])
+        .output()
+        .expect("failed to execute monkey binary");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("digraph cfg {\n"));
+    assert!(stdout.contains("label=\"<main>\";"));
+    assert!(stdout.contains("[label=\"false\"]"));
+}
+
+#[test]
+fn disasm_prints_code_and_constants() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("examples/fact.monkey");
+
+    let output = Command::new(bin())
+        .args(["disasm", path.to_str().expect("utf8 path")])
+        .output()
+        .expect("failed to execute monkey binary");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("0000 Closure 0 0 @1:12 (1:12-3:2)\n"));
+    assert!(stdout.contains(
+        "\n\nConstants:\n0000 FUNCTION fact arity=1 locals=1\n    0000 GetLocal 0 @2:7 (2:7-2:8)\n"
+    ));
+    assert!(stdout.ends_with("    0023 ReturnValue @2:3 (2:3-2:45)\n"));
+}
+
+#[test]
+fn stats_flag_prints_bytecode_size_and_execution_stats_to_stderr() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("examples/fact.monkey");
+    let path = path.to_str().expect("utf8 path");
+
+    let run = Command::new(bin())
+        .args(["run", path, "--stats"])
+        .output()
+        .expect("failed to execute monkey binary");
+    assert!(run.status.success());
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "720\n");
+    let stderr = String::from_utf8_lossy(&run.stderr);
+    assert!(stderr.starts_with(&format!("Bytecode stats for {path}:\n  instructions: ")));
+    assert!(stderr.contains("\n  functions: 1 (max nesting depth 1)\n"));
+    let (bytecode, execution) = stderr
+        .split_once(&format!("Execution stats for {path}:\n"))
+        .expect("execution stats follow the run");
+    assert!(execution.starts_with("  instructions: 91\n"), "{execution}");
+    assert!(
+        execution.contains("\n  peak frame depth: 8\n"),
+        "{execution}"
+    );
+    assert!(
+        execution.contains("\n  allocations: 0 (0 bytes)\n"),
+        "{execution}"
+    );
+    assert!(execution.contains("\n  elapsed: "), "{execution}");
+
+    let output = std::env::temp_dir().join(format!("monkey-stats-{}.mkc", std::process::id()));
+    let compile = Command::new(bin())
+        .args([
+            "compile",
+            path,
+            "--stats",
+            "-o",
+            output.to_str().expect("utf8 path"),
+        ])
+        .output()
+        .expect("failed to execute monkey binary");
+    let _ = std::fs::remove_file(&output);
+    assert!(compile.status.success());
+    assert_eq!(String::from_utf8_lossy(&compile.stderr), bytecode);
+}
+
+#[test]
+fn coverage_flag_lists_unexecuted_lines_on_stderr() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("monkey_cli_coverage_{}.monkey", std::process::id()));
+    std::fs::write(&path, "let x = 1;\nif (x > 1) {\n  puts(\"big\");\n}\nx;\n")
+        .expect("write temp script");
+    let path = path.to_str().expect("utf8 path");
+
+    let output = Command::new(bin())
+        .args(["run", path, "--coverage"])
+        .output()
+        .expect("failed to execute monkey binary");
+    let _ = std::fs::remove_file(path);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!(
+            "Coverage for {path}:\n  3 of 4 lines executed (75.0%)\n  Unexecuted lines:\n    3 |   puts(\"big\");\n"
+        )
+    );
+}
+
+#[test]
+fn exit_with_result_flag_turns_an_integer_result_into_the_exit_status() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("monkey_cli_exit_{}.monkey", std::process::id()));
+    let path = path.to_str().expect("utf8 path").to_string();
+    let run = |source: &str, flag: bool| {
+        std::fs::write(&path, source).expect("write temp script");
+        let mut args = vec!["run", path.as_str()];
+        if flag {
+            args.push("--exit-with-result");
+        }
+        Command::new(bin())
+            .args(args)
+            .output()
+            .expect("failed to execute monkey binary")
+    };
+
+    let output = run("puts(\"checking\");\n1 + 2", true);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "checking\n3\n");
+    assert_eq!(run("3", false).status.code(), Some(0));
+    assert_eq!(run("-1", true).status.code(), Some(255));
+    assert_eq!(run("256", true).status.code(), Some(0));
+    assert_eq!(run("\"7\"", true).status.code(), Some(0));
+    assert_eq!(run("1 / 0", true).status.code(), Some(1));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn error_format_json_prints_one_object_per_diagnostic() {
+    let mut path = std::env::temp_dir();
+    path.push(format!(
+        "monkey_cli_error_json_{}.monkey",
+        std::process::id()
+    ));
+    let path = path.to_str().expect("utf8 path").to_string();
+    let run = |source: &str, args: &[&str]| {
+        std::fs::write(&path, source).expect("write temp script");
+        let output = Command::new(bin())
+            .args(args)
+            .arg(&path)
+            .args(["--error-format", "json"])
+            .output()
+            .expect("failed to execute monkey binary");
+        assert_eq!(output.status.code(), Some(1));
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    };
+
+    assert_eq!(
+        run("let f = fn(x) { x / 0 };\nf(1);\n", &["run"]),
+        format!(
+            "{{\"file\": \"{path}\", \"kind\": \"runtime\", \"type\": \"DIVISION_BY_ZERO\", \"code\": \"R009\", \"message\": \"division by zero\", \"line\": 1, \"col\": 19, \"stack\": [{{\"function\": \"f\", \"line\": 1, \"col\": 19, \"args\": 1}}, {{\"function\": \"<repl>\", \"line\": 1, \"col\": 1, \"args\": 0}}]}}\n"
+        )
+    );
+    assert_eq!(
+        run("let = 1;\n", &["check"]),
+        format!(
+            "{{\"file\": \"{path}\", \"kind\": \"parse\", \"code\": \"P001\", \"message\": \"expected next token to be Ident, got Assign\", \"line\": 1, \"col\": 5}}\n"
+        )
+    );
+    assert_eq!(run("puts(a);\nputs(b);\n", &["check"]).lines().count(), 2);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn watch_flag_runs_again_after_the_file_changes() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+
+    let mut path = std::env::temp_dir();
+    path.push(format!("monkey_cli_watch_{}.monkey", std::process::id()));
+    std::fs::write(&path, "1 + 1").expect("write temp script");
+    let mut child = Command::new(bin())
+        .args(["run", path.to_str().expect("utf8 path"), "--watch"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to execute monkey binary");
+    let mut stdout = BufReader::new(child.stdout.take().expect("stdout is piped")).lines();
+    let mut stderr = BufReader::new(child.stderr.take().expect("stderr is piped")).lines();
+    let next = |lines: &mut dyn Iterator<Item = std::io::Result<String>>| {
+        lines
+            .next()
+            .expect("monkey is still running")
+            .expect("read output")
+    };
+
+    assert_eq!(next(&mut stdout), "2");
+    assert!(next(&mut stderr).starts_with("[watch] Finished in "));
+    // Longer than before, in case the modification time did not move.
+    std::fs::write(&path, "10 + 10").expect("rewrite temp script");
+    assert_eq!(next(&mut stdout), "20");
+    std::fs::write(&path, "1 / 0").expect("rewrite temp script");
+    assert!(next(&mut stderr).starts_with("[watch] Finished in "));
+    assert!(next(&mut stderr).starts_with("Runtime error in "));
+
+    let _ = child.kill();
+    let _ = child.wait();
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_runs_test_files_and_summarizes_failures() {
+    let mut dir = std::env::temp_dir();
+    dir.push(format!("monkey_cli_test_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("nested")).expect("create temp dir");
+    std::fs::write(dir.join("math_test.monkey"), "assert(1 + 1 == 2, \"sum\");")
+        .expect("write test file");
+    std::fs::write(
+        dir.join("nested").join("len_test.monkey"),
+        "puts(\"hidden\");\nassert(len(\"ab\") == 3, \"len\");\n",
+    )
+    .expect("write test file");
+    std::fs::write(dir.join("helper.monkey"), "assert(false);").expect("write helper");
+    let path = |name: &str| dir.join(name).display().to_string();
+
+    let output = Command::new(bin())
+        .arg("test")
+        .arg(&dir)
+        .output()
+        .expect("failed to execute monkey binary");
+
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines[0].starts_with(&format!("PASS {} (", path("math_test.monkey"))));
+    let failing = path("nested/len_test.monkey");
+    assert_eq!(
+        lines[1..],
+        [
+            format!("FAIL {failing}"),
+            String::new(),
+            "Failures:".to_string(),
+            format!("  {failing}:2:7: assertion failed: len"),
+            String::new(),
+            "1 passed, 1 failed".to_string(),
+        ]
+    );
+    assert!(
+        String::from_utf8_lossy(&output.stderr).starts_with(&format!(
+            "Runtime error in {failing}:\nError[ASSERTION_FAILED]"
+        ))
+    );
+
+    let passing = Command::new(bin())
+        .arg("test")
+        .arg(path("math_test.monkey"))
+        .output()
+        .expect("failed to execute monkey binary");
+    assert!(passing.status.success());
+    assert!(String::from_utf8_lossy(&passing.stdout).ends_with("\n1 passed, 0 failed\n"));
+
+    // A missing path is named and fails without stopping the other tests.
+    let missing = path("missing_test.monkey");
+    let output = Command::new(bin())
+        .arg("test")
+        .arg(&missing)
+        .arg(path("math_test.monkey"))
+        .output()
+        .expect("failed to execute monkey binary");
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with(&format!("FAIL {missing}\nPASS ")),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains(&format!("Failures:\n  {missing}: ")),
+        "{stdout}"
+    );
+    assert!(stdout.ends_with("\n1 passed, 1 failed\n"), "{stdout}");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .starts_with(&format!("Failed to find tests in {missing}: ")));
+
+    // So is a test file that cannot be read.
+    let unreadable = path("binary_test.monkey");
+    std::fs::write(&unreadable, [0xff, 0xfe, 0xfd]).expect("write test file");
+    let output = Command::new(bin())
+        .arg("test")
+        .arg(&unreadable)
+        .arg(path("math_test.monkey"))
+        .output()
+        .expect("failed to execute monkey binary");
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with(&format!("FAIL {unreadable}\nPASS ")),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains(&format!("Failures:\n  {unreadable}: ")),
+        "{stdout}"
+    );
+    assert!(stdout.ends_with("\n1 passed, 1 failed\n"), "{stdout}");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .starts_with(&format!("Failed to read {unreadable}: ")));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn fmt_rewrites_files_and_check_reports_unformatted_ones() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("monkey_cli_fmt_{}.monkey", std::process::id()));
+    std::fs::write(&path, "let x=1;puts( x )\n").expect("write temp script");
+    let path = path.to_str().expect("utf8 path");
+
+    let check = Command::new(bin())
+        .args(["fmt", "--check", path])
+        .output()
+        .expect("failed to execute monkey fmt --check");
+    assert_eq!(check.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&check.stderr),
+        format!("{path} is not formatted\n")
+    );
+    assert_eq!(
+        std::fs::read_to_string(path).expect("read back"),
+        "let x=1;puts( x )\n"
+    );
+
+    let fmt = Command::new(bin())
+        .args(["fmt", path])
+        .output()
+        .expect("failed to execute monkey fmt");
+    assert!(fmt.status.success());
+    assert_eq!(
+        std::fs::read_to_string(path).expect("read back"),
+        "let x = 1;\nputs(x)\n"
+    );
+
+    let recheck = Command::new(bin())
+        .args(["fmt", "--check", path])
+        .output()
+        .expect("failed to execute monkey fmt --check");
+    let _ = std::fs::remove_file(path);
+    assert!(recheck.status.success());
+    assert!(recheck.stderr.is_empty());
+}
+
+#[test]
+fn check_compiles_without_running_and_reports_problems() {
+    let dir = std::env::temp_dir();
+    let clean = dir.join(format!(
+        "monkey_cli_check_clean_{}.monkey",
+        std::process::id()
+    ));
+    let warned = dir.join(format!(
+        "monkey_cli_check_warn_{}.monkey",
+        std::process::id()
+    ));
+    let broken = dir.join(format!(
+        "monkey_cli_check_broken_{}.monkey",
+        std::process::id()
+    ));
+    std::fs::write(&clean, "puts(\"ran\");\n1 / 0;").expect("write temp script");
+    std::fs::write(&warned, "let f = fn(x) { 1 };\nf(2);").expect("write temp script");
+    std::fs::write(&broken, "puts(a);\nlet = 1;").expect("write temp script");
+    let [clean, warned, broken] =
+        [&clean, &warned, &broken].map(|p| p.to_str().unwrap().to_string());
+
+    let check = |args: &[&str]| {
+        Command::new(bin())
+            .arg("check")
+            .args(args)
+            .output()
+            .expect("failed to execute monkey check")
+    };
+    let ok = check(&[&clean, &warned]);
+    let denied = check(&["--deny-warnings", &warned]);
+    let failed = check(&[&broken, &clean]);
+    for path in [&clean, &warned, &broken] {
+        let _ = std::fs::remove_file(path);
+    }
+
+    let warning = format!("Warnings in {warned}:\n- 1:12: unused parameter: x [W002]\n");
+    assert!(ok.status.success());
+    assert_eq!(String::from_utf8_lossy(&ok.stdout), "");
+    assert_eq!(String::from_utf8_lossy(&ok.stderr), warning);
+    assert_eq!(denied.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&denied.stderr), warning);
+    assert_eq!(failed.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&failed.stderr);
+    assert!(
+        stderr.starts_with(&format!("Parse errors in {broken}:\n- 2:5:")),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn dash_reads_the_program_from_stdin() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let pipe = |args: &[&str], input: &str| {
+        let mut child = Command::new(bin())
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("failed to execute monkey binary");
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(input.as_bytes())
+            .expect("write program to stdin");
+        child.wait_with_output().expect("wait for monkey")
+    };
+
+    let run = pipe(&["run", "-", "--", "a"], "puts(len(args()));\n1 + 2");
+    assert!(run.status.success());
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "1\n3\n");
+
+    let ast = pipe(&["--ast", "-", "--format", "sexpr"], "let x = 1 + 2;");
+    assert!(ast.status.success());
+    assert_eq!(String::from_utf8_lossy(&ast.stdout), "(let x (+ 1 2))\n");
+
+    let tokens = pipe(&["--tokens", "-"], "x");
+    assert!(tokens.status.success());
+    assert!(String::from_utf8_lossy(&tokens.stdout).starts_with("Ident('x')"));
+
+    let broken = pipe(&["run", "-"], "let = 1;");
+    assert_eq!(broken.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&broken.stderr).starts_with("Parse errors in -:\n"));
 }
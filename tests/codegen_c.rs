@@ -0,0 +1,149 @@
+use std::process::Command;
+
+use monkey_rust_compiler::bytecode::{make, Chunk, Opcode};
+use monkey_rust_compiler::codegen_c::emit_c;
+use monkey_rust_compiler::runner::{compile_source, run_source, RunnerError};
+
+fn chunk_of(code: &[(Opcode, &[usize])]) -> Chunk {
+    let mut chunk = Chunk::new();
+    for (op, operands) in code {
+        chunk.push_bytes(&make(*op, operands).expect("encode"));
+    }
+    chunk
+}
+
+fn verify_error(chunk: &Chunk) -> String {
+    emit_c(chunk)
+        .expect_err("chunk should be rejected")
+        .to_string()
+}
+
+/// Compile the generated C for `source` with `cc` and run it. Returns
+/// `None` when no C compiler is installed.
+fn run_native(name: &str, source: &str) -> Option<std::process::Output> {
+    if Command::new("cc").arg("--version").output().is_err() {
+        return None;
+    }
+    let chunk = compile_source(source).expect("program should compile");
+    let c = emit_c(&chunk).expect("chunk should translate");
+    let dir = std::env::temp_dir();
+    let stem = format!("monkey-c-{}-{name}", std::process::id());
+    let c_path = dir.join(format!("{stem}.c"));
+    let exe = dir.join(stem);
+    std::fs::write(&c_path, c).expect("write C source");
+    let cc = Command::new("cc")
+        .args(["-O1", "-o"])
+        .arg(&exe)
+        .arg(&c_path)
+        .output()
+        .expect("run cc");
+    let _ = std::fs::remove_file(&c_path);
+    assert!(
+        cc.status.success(),
+        "{}",
+        String::from_utf8_lossy(&cc.stderr)
+    );
+    let output = Command::new(&exe).output().expect("run generated program");
+    let _ = std::fs::remove_file(&exe);
+    Some(output)
+}
+
+fn assert_same_natively(name: &str, source: &str) {
+    let Some(output) = run_native(name, source) else {
+        return;
+    };
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let expected = run_source(source).expect("VM run should succeed");
+    let mut lines = expected.output;
+    lines.push(expected.result.inspect());
+    let stdout = String::from_utf8(output.stdout).expect("utf8 output");
+    assert_eq!(stdout.lines().collect::<Vec<_>>(), lines, "{source}");
+}
+
+#[test]
+fn emits_a_c_function_per_compiled_function() {
+    let chunk = compile_source("let f = fn(a) { a + 1 }; f(2);").expect("compile");
+    let c = emit_c(&chunk).expect("translate");
+    assert!(c.contains("static Value monkey_main(void)"));
+    assert!(c.contains("int main(int argc, char **argv)"));
+    assert!(c.contains("static const Function F_0 = {\"f\", 1, 1, 2, fn_0};"));
+    assert_eq!(
+        c.matches("static void fn_0(Value *bp, Closure *self) {")
+            .count(),
+        1
+    );
+    assert!(c.contains("op_arith(0, 1, 19); /* 0003 Add */"));
+}
+
+#[test]
+fn rejects_bytecode_that_fails_verification() {
+    assert_eq!(
+        verify_error(&chunk_of(&[(Opcode::Add, &[]), (Opcode::ReturnValue, &[])])),
+        "<main> at 0000: stack underflow"
+    );
+    assert_eq!(
+        verify_error(&chunk_of(&[(Opcode::Jump, &[1]), (Opcode::Null, &[])])),
+        "<main> at 0000: jump target 1 is not an instruction"
+    );
+    assert_eq!(
+        verify_error(&chunk_of(&[(Opcode::Constant, &[0])])),
+        "<main> at 0000: constant 0 out of range"
+    );
+    assert_eq!(
+        verify_error(&chunk_of(&[(Opcode::Null, &[])])),
+        "<main> at 0000: execution runs past the end"
+    );
+
+    let mut unknown = Chunk::new();
+    unknown.push_bytes(&[0xff]);
+    assert_eq!(verify_error(&unknown), "<main> at 0000: unknown opcode 255");
+}
+
+#[test]
+fn generated_programs_match_the_vm() {
+    assert_same_natively(
+        "fib",
+        "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(20);",
+    );
+    assert_same_natively(
+        "closures",
+        "let adder = fn(a) { fn(b) { a + b } }; puts(adder(2)(3), \"x\"); adder(10)(1);",
+    );
+    assert_same_natively(
+        "collections",
+        "let h = {\"a\": [1, 2], true: \"t\"}; puts(h); let a = push(h[\"a\"], 3);
+         [len(a), first(a), last(a), rest(a), h[true], h[5], \"mon\" + \"key\"];",
+    );
+    assert_same_natively(
+        "loops",
+        "let i = 0; let s = 0;
+         while (i < 10) { let i = i + 1; if (i == 3) { continue; } if (i == 8) { break; } let s = s + i; };
+         s;",
+    );
+}
+
+#[test]
+fn runtime_errors_match_the_vm_message() {
+    for (name, source) in [
+        ("div", "let f = fn(x) { x / 0 }; f(1);"),
+        (
+            "assert",
+            "assert(true); assert(1 > 2, \"one is not greater\");",
+        ),
+    ] {
+        let Some(output) = run_native(name, source) else {
+            return;
+        };
+        assert_eq!(output.status.code(), Some(1));
+        let expected = match run_source(source) {
+            Err(RunnerError::Runtime(err)) => err.format_single_line(),
+            other => panic!("expected a runtime error, got {other:?}"),
+        };
+        let stderr = String::from_utf8(output.stderr).expect("utf8 stderr");
+        assert!(stderr.lines().any(|line| line == expected), "{stderr}");
+    }
+}
@@ -6,8 +6,9 @@ use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use monkey_rust_compiler::compiler::CompileError;
 use monkey_rust_compiler::parse_error::ParseError;
-use monkey_rust_compiler::repl::{format_parse_errors, ReplEvalResult, ReplSession};
+use monkey_rust_compiler::repl::{render_transcript, ReplSession};
 use monkey_rust_compiler::runner::{dump_ast, format_tokens, run_source, RunnerError};
 
 pub fn normalize_text(s: &str) -> String {
@@ -87,12 +88,20 @@ pub fn render_ast(source: &str) -> String {
 }
 
 fn render_parse_errors(errors: &[ParseError]) -> String {
-    let lines = errors
+    errors
         .iter()
         .map(|e| format!("- {e}"))
         .collect::<Vec<_>>()
-        .join("\n");
-    format!("{lines}")
+        .join("\n")
+}
+
+/// One error per line, matching what the CLI prints under its header.
+fn render_compile_errors(errors: &[CompileError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 pub fn render_run(source: &str) -> String {
@@ -115,9 +124,10 @@ pub fn render_run(source: &str) -> String {
             "STATUS: error\nKIND: parse\nPUTS: <none>\nERROR:\n{}",
             render_parse_errors(&errors)
         ),
-        Err(RunnerError::Compile(err)) => {
-            format!("STATUS: error\nKIND: compile\nPUTS: <none>\nERROR:\n{err}")
-        }
+        Err(RunnerError::Compile(errors)) => format!(
+            "STATUS: error\nKIND: compile\nPUTS: <none>\nERROR:\n{}",
+            render_compile_errors(&errors)
+        ),
         Err(RunnerError::Runtime(err)) => format!(
             "STATUS: error\nKIND: runtime\nPUTS: <none>\nERROR:\n{}",
             err.format_multiline()
@@ -127,36 +137,10 @@ pub fn render_run(source: &str) -> String {
 
 pub fn render_repl_transcript(transcript: &str) -> String {
     let mut repl = ReplSession::new();
-    let mut blocks = Vec::new();
-
-    for line in transcript.lines() {
-        let trimmed = line.trim();
-        if trimmed.is_empty() || trimmed.starts_with('#') {
-            continue;
+    for line in transcript.lines().map(str::trim) {
+        if !line.starts_with('#') {
+            repl.eval_line(line);
         }
-
-        let rendered = match repl.eval_line(trimmed) {
-            ReplEvalResult::Empty => "(empty)".to_string(),
-            ReplEvalResult::Value { result, output } => {
-                if output.is_empty() {
-                    format!("RESULT: {}", result.inspect())
-                } else {
-                    format!("PUTS:\n{}\nRESULT: {}", output.join("\n"), result.inspect())
-                }
-            }
-            ReplEvalResult::ParseErrors(errors) => {
-                format!("PARSE_ERROR:\n{}", format_parse_errors(&errors))
-            }
-            ReplEvalResult::CompileError(err) => format!("COMPILE_ERROR:\n{err}"),
-            ReplEvalResult::RuntimeError(err) => {
-                format!("RUNTIME_ERROR:\n{}", err.format_multiline())
-            }
-            ReplEvalResult::MetaOutput(text) => format!("META:\n{text}"),
-            ReplEvalResult::ExitRequested => "EXIT".to_string(),
-        };
-
-        blocks.push(format!("INPUT: {trimmed}\nOUTPUT:\n{rendered}"));
     }
-
-    blocks.join("\n\n")
+    render_transcript(repl.transcript())
 }
@@ -1,10 +1,8 @@
-use std::rc::Rc;
-
 use monkey_rust_compiler::ast::Program;
 use monkey_rust_compiler::bytecode::{lookup_definition, read_operands, Chunk, Opcode};
 use monkey_rust_compiler::compiler::{CompileError, Compiler};
 use monkey_rust_compiler::lexer::Lexer;
-use monkey_rust_compiler::object::{CompiledFunctionObject, Object};
+use monkey_rust_compiler::object::{CompiledFunctionObject, Object, Shared};
 use monkey_rust_compiler::parser::Parser;
 use monkey_rust_compiler::position::Position;
 
@@ -26,7 +24,9 @@ fn parse_program(input: &str) -> Program {
 
 fn compile_input(input: &str) -> Result<Chunk, CompileError> {
     let mut compiler = Compiler::new();
-    compiler.compile_program(&parse_program(input))?;
+    compiler
+        .compile_program(&parse_program(input))
+        .map_err(|mut errors| errors.remove(0))?;
     Ok(compiler.into_bytecode())
 }
 
@@ -51,9 +51,9 @@ fn decode_chunk(chunk: &Chunk) -> Vec<(usize, Opcode, Vec<usize>)> {
     decode_instructions(&chunk.instructions)
 }
 
-fn as_compiled_function(obj: &Rc<Object>) -> Rc<CompiledFunctionObject> {
+fn as_compiled_function(obj: &Shared<Object>) -> Shared<CompiledFunctionObject> {
     match obj.as_ref() {
-        Object::CompiledFunction(f) => Rc::clone(f),
+        Object::CompiledFunction(f) => Shared::clone(f),
         other => panic!("expected compiled function constant, got {other:?}"),
     }
 }
@@ -68,7 +68,7 @@ fn function_pos_for_offset(function: &CompiledFunctionObject, offset: usize) ->
         .iter()
         .filter(|(pos_offset, _)| *pos_offset <= offset)
         .max_by_key(|(pos_offset, _)| *pos_offset)
-        .map(|(_, pos)| *pos)
+        .map(|(_, span)| span.pos)
 }
 
 #[test]
@@ -92,17 +92,14 @@ fn array_literal_compilation_basic() {
     assert_eq!(
         decoded,
         vec![
-            (Opcode::Constant, vec![0]),
-            (Opcode::Constant, vec![1]),
-            (Opcode::Constant, vec![2]),
+            (Opcode::Const1, vec![]),
+            (Opcode::ConstInt, vec![2]),
+            (Opcode::ConstInt, vec![3]),
             (Opcode::Array, vec![3]),
             (Opcode::ReturnValue, vec![]),
         ]
     );
-    assert_eq!(
-        chunk_constants(&chunk),
-        vec![Object::Integer(1), Object::Integer(2), Object::Integer(3)]
-    );
+    assert!(chunk.constants.is_empty());
 
     let chunk = compile_input("[1 + 2, 3 * 4, 5];").expect("compile should succeed");
     let ops = decode_chunk(&chunk)
@@ -112,13 +109,13 @@ fn array_literal_compilation_basic() {
     assert_eq!(
         ops,
         vec![
-            Opcode::Constant,
-            Opcode::Constant,
+            Opcode::Const1,
+            Opcode::ConstInt,
             Opcode::Add,
-            Opcode::Constant,
-            Opcode::Constant,
+            Opcode::ConstInt,
+            Opcode::ConstInt,
             Opcode::Mul,
-            Opcode::Constant,
+            Opcode::ConstInt,
             Opcode::Array,
             Opcode::ReturnValue,
         ]
@@ -146,21 +143,16 @@ fn hash_literal_compilation_basic() {
         decoded,
         vec![
             (Opcode::Constant, vec![0]),
+            (Opcode::Const1, vec![]),
             (Opcode::Constant, vec![1]),
-            (Opcode::Constant, vec![2]),
-            (Opcode::Constant, vec![3]),
+            (Opcode::ConstInt, vec![2]),
             (Opcode::Hash, vec![2]),
             (Opcode::ReturnValue, vec![]),
         ]
     );
     assert_eq!(
         chunk_constants(&chunk),
-        vec![
-            Object::String("one".to_string()),
-            Object::Integer(1),
-            Object::String("two".to_string()),
-            Object::Integer(2),
-        ]
+        vec![Object::String("one".into()), Object::String("two".into())]
     );
 
     let chunk = compile_input("{1 + 1: 2 * 2, \"x\": 3};").expect("compile should succeed");
@@ -171,14 +163,14 @@ fn hash_literal_compilation_basic() {
     assert_eq!(
         ops,
         vec![
-            Opcode::Constant,
-            Opcode::Constant,
+            Opcode::Const1,
+            Opcode::Const1,
             Opcode::Add,
-            Opcode::Constant,
-            Opcode::Constant,
+            Opcode::ConstInt,
+            Opcode::ConstInt,
             Opcode::Mul,
             Opcode::Constant,
-            Opcode::Constant,
+            Opcode::ConstInt,
             Opcode::Hash,
             Opcode::ReturnValue,
         ]
@@ -191,12 +183,9 @@ fn hash_source_order_preserved() {
     assert_eq!(
         chunk_constants(&chunk),
         vec![
-            Object::String("b".to_string()),
-            Object::Integer(2),
-            Object::String("a".to_string()),
-            Object::Integer(1),
-            Object::String("c".to_string()),
-            Object::Integer(3),
+            Object::String("b".into()),
+            Object::String("a".into()),
+            Object::String("c".into()),
         ]
     );
 
@@ -218,11 +207,11 @@ fn index_expression_compilation() {
     assert_eq!(
         ops,
         vec![
-            Opcode::Constant,
-            Opcode::Constant,
-            Opcode::Constant,
+            Opcode::Const1,
+            Opcode::ConstInt,
+            Opcode::ConstInt,
             Opcode::Array,
-            Opcode::Constant,
+            Opcode::Const1,
             Opcode::Index,
             Opcode::ReturnValue,
         ]
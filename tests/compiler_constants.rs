@@ -0,0 +1,131 @@
+use monkey_rust_compiler::ast::Program;
+use monkey_rust_compiler::bytecode::{lookup_definition, read_operands, Chunk, Opcode};
+use monkey_rust_compiler::compiler::Compiler;
+use monkey_rust_compiler::lexer::Lexer;
+use monkey_rust_compiler::object::{Object, Shared};
+use monkey_rust_compiler::parser::Parser;
+use monkey_rust_compiler::runner::run_source;
+
+fn parse_program(input: &str) -> Program {
+    let mut parser = Parser::new(Lexer::new(input));
+    let program = parser.parse_program();
+    assert!(parser.errors().is_empty(), "parse errors for {input}");
+    program
+}
+
+fn compile_input(input: &str) -> Chunk {
+    let mut compiler = Compiler::new();
+    compiler
+        .compile_program(&parse_program(input))
+        .expect("compile should succeed");
+    compiler.into_bytecode()
+}
+
+fn constant_operands(bytes: &[u8]) -> Vec<usize> {
+    let mut out = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let op = Opcode::from_byte(bytes[offset]).expect("known opcode");
+        let (operands, consumed) =
+            read_operands(lookup_definition(op), &bytes[offset + 1..]).expect("operands");
+        if op == Opcode::Constant {
+            out.push(operands[0]);
+        }
+        offset += 1 + consumed;
+    }
+    out
+}
+
+fn chunk_constants(chunk: &Chunk) -> Vec<Object> {
+    chunk.constants.iter().map(|c| c.as_ref().clone()).collect()
+}
+
+#[test]
+fn repeated_integer_literals_share_one_constant() {
+    let chunk = compile_input("[40000, 40000, 40000, 40000];");
+    assert_eq!(chunk_constants(&chunk), vec![Object::Integer(40000)]);
+    assert_eq!(constant_operands(&chunk.instructions), vec![0, 0, 0, 0]);
+}
+
+#[test]
+fn strings_and_integers_are_interned_by_type_and_value() {
+    let chunk = compile_input(r#"[40000, "40000", 50000, "40000", 40000, 50000];"#);
+    assert_eq!(
+        chunk_constants(&chunk),
+        vec![
+            Object::Integer(40000),
+            Object::String("40000".into()),
+            Object::Integer(50000),
+        ]
+    );
+    assert_eq!(
+        constant_operands(&chunk.instructions),
+        vec![0, 1, 2, 1, 0, 2]
+    );
+}
+
+#[test]
+fn nested_function_scopes_reuse_program_constants() {
+    let chunk =
+        compile_input(r#"let k = "key"; let f = fn() { {"key": 40000}["key"] + 40000 }; f();"#);
+    let strings = chunk
+        .constants
+        .iter()
+        .filter(|c| matches!(c.as_ref(), Object::String(_)))
+        .count();
+    let integers = chunk
+        .constants
+        .iter()
+        .filter(|c| matches!(c.as_ref(), Object::Integer(40000)))
+        .count();
+    assert_eq!(strings, 1);
+    assert_eq!(integers, 1);
+}
+
+#[test]
+fn sibling_and_nested_functions_share_string_constants() {
+    let chunk = compile_input(
+        r#"let get = fn(h) { h["name"] };
+        let set = fn(h, v) { {"name": v, "id": h["id"]} };
+        let make = fn() { fn(id) { {"id": id, "name": "anon"} } };
+        set(make()(1), "x")["name"];"#,
+    );
+    let strings = chunk
+        .constants
+        .iter()
+        .filter_map(|c| match c.as_ref() {
+            Object::String(value) => Some(value.to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(strings, ["name", "id", "anon", "x"]);
+}
+
+#[test]
+fn small_integer_literals_bypass_the_constant_pool() {
+    let chunk = compile_input("[0, 1, 2, 32767, 32768];");
+    assert_eq!(chunk_constants(&chunk), vec![Object::Integer(32768)]);
+    assert_eq!(constant_operands(&chunk.instructions), vec![0]);
+
+    let out = run_source("[0, 1, 2, -7, 32767, 32768, 0 - 1 + 1];").expect("run should succeed");
+    assert_eq!(out.result.inspect(), "[0, 1, 2, -7, 32767, 32768, 0]");
+}
+
+#[test]
+fn function_literals_are_never_merged() {
+    let chunk = compile_input("let a = fn() { 1 }; let b = fn() { 1 };");
+    let functions = chunk
+        .constants
+        .iter()
+        .filter(|c| matches!(c.as_ref(), Object::CompiledFunction(_)))
+        .collect::<Vec<_>>();
+    assert_eq!(functions.len(), 2);
+    assert!(!Shared::ptr_eq(functions[0], functions[1]));
+}
+
+#[test]
+fn interning_keeps_runtime_results_unchanged() {
+    let out = run_source(r#"let h = {"a": 1, "b": 1}; [h["a"] + h["b"], "a" + "a"];"#)
+        .expect("run should succeed");
+    assert_eq!(out.result.inspect(), "[2, aa]");
+}
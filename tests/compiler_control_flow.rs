@@ -4,6 +4,7 @@ use monkey_rust_compiler::compiler::{CompileError, Compiler};
 use monkey_rust_compiler::lexer::Lexer;
 use monkey_rust_compiler::parser::Parser;
 use monkey_rust_compiler::position::Position;
+use monkey_rust_compiler::runner::run_source;
 
 fn parse_program(input: &str) -> Program {
     let mut parser = Parser::new(Lexer::new(input));
@@ -23,7 +24,9 @@ fn parse_program(input: &str) -> Program {
 
 fn compile_input(input: &str) -> Result<Chunk, CompileError> {
     let mut compiler = Compiler::new();
-    compiler.compile_program(&parse_program(input))?;
+    compiler
+        .compile_program(&parse_program(input))
+        .map_err(|mut errors| errors.remove(0))?;
     Ok(compiler.into_bytecode())
 }
 
@@ -46,11 +49,12 @@ fn decode_instructions(chunk: &Chunk) -> Vec<(usize, Opcode, Vec<usize>)> {
 
 #[test]
 fn if_without_else_compiles_and_pushes_null_on_false() {
-    let chunk = compile_input("if (true) { 10; };").expect("compile should succeed");
+    // A literal condition would be folded, so read it from a global.
+    let chunk = compile_input("let c = true; if (c) { 10; };").expect("compile should succeed");
     let decoded = decode_instructions(&chunk);
     let ops = decoded.iter().map(|(_, op, _)| *op).collect::<Vec<_>>();
 
-    assert!(ops.starts_with(&[Opcode::True, Opcode::JumpIfFalse, Opcode::Pop]));
+    assert!(ops[2..].starts_with(&[Opcode::GetGlobal, Opcode::JumpIfFalse, Opcode::Pop]));
     assert!(ops.contains(&Opcode::Null));
     assert_eq!(ops.last(), Some(&Opcode::ReturnValue));
 
@@ -69,7 +73,8 @@ fn if_without_else_compiles_and_pushes_null_on_false() {
 
 #[test]
 fn if_else_compiles_with_value_branches() {
-    let chunk = compile_input("if (true) { 10; } else { 20; };").expect("compile should succeed");
+    let chunk = compile_input("let c = true; if (c) { 10; } else { 20; };")
+        .expect("compile should succeed");
     let decoded = decode_instructions(&chunk);
     let ops = decoded.iter().map(|(_, op, _)| *op).collect::<Vec<_>>();
 
@@ -77,7 +82,7 @@ fn if_else_compiles_with_value_branches() {
     assert!(ops.iter().filter(|&&op| op == Opcode::Jump).count() >= 1);
     assert_eq!(ops.last(), Some(&Opcode::ReturnValue));
 
-    let const_count = ops.iter().filter(|&&op| op == Opcode::Constant).count();
+    let const_count = ops.iter().filter(|&&op| op == Opcode::ConstInt).count();
     assert_eq!(const_count, 2);
 }
 
@@ -99,6 +104,66 @@ fn if_branch_fallback_to_null_when_no_value() {
     assert!(null_count >= 1);
 }
 
+#[test]
+fn constant_if_conditions_keep_only_the_live_branch() {
+    let ops = |input: &str| {
+        decode_instructions(&compile_input(input).expect("compile should succeed"))
+            .iter()
+            .map(|(_, op, _)| *op)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        ops("if (true) { 10 } else { 20 };"),
+        [Opcode::ConstInt, Opcode::ReturnValue]
+    );
+    assert_eq!(
+        ops("if (false) { 10 } else { 20 };"),
+        [Opcode::ConstInt, Opcode::ReturnValue]
+    );
+    assert_eq!(
+        ops("if (false) { 10 };"),
+        [Opcode::Null, Opcode::ReturnValue]
+    );
+}
+
+#[test]
+fn code_after_return_break_or_continue_is_dropped_with_a_warning() {
+    let input = "let f = fn() {\n  return 1;\n  puts(2);\n  puts(3);\n};\nwhile (f()) {\n  break;\n  if (true) { 4 } else { return 5; };\n}\nif (false) { 6 };";
+    let mut compiler = Compiler::new();
+    compiler
+        .compile_program(&parse_program(input))
+        .expect("compile should succeed");
+    let warnings = compiler
+        .warnings()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        warnings,
+        [
+            "3:3: unreachable code [W003]",
+            "8:3: unreachable code [W003]",
+            "10:12: unreachable code [W003]",
+        ]
+    );
+
+    let chunk = compiler.into_bytecode();
+    assert!(!decode_instructions(&chunk)
+        .iter()
+        .any(|(_, op, _)| *op == Opcode::GetBuiltin));
+}
+
+#[test]
+fn bindings_in_dead_code_stay_resolvable() {
+    let chunk = compile_input(
+        "if (true) { 1 } else { let x = 2; x }; let f = fn() { return 0; let y = 1; y }; f;",
+    )
+    .expect("compile should succeed");
+    assert!(decode_instructions(&chunk)
+        .iter()
+        .all(|(_, op, _)| *op != Opcode::SetLocal));
+}
+
 #[test]
 fn nested_if_compiles_deterministically() {
     let chunk = compile_input(
@@ -154,6 +219,26 @@ fn break_inside_loop_patches_to_loop_end() {
     assert!(!decoded.iter().any(|(_, op, _)| *op == Opcode::InvalidBreak));
 }
 
+#[test]
+fn if_block_ending_in_a_loop_keeps_break_targets_valid() {
+    let chunk = compile_input("let c = true; if (c) { while (true) { break; } }")
+        .expect("compile should succeed");
+    let decoded = decode_instructions(&chunk);
+    let starts = decoded
+        .iter()
+        .map(|(offset, _, _)| *offset)
+        .chain([chunk.instructions.len()])
+        .collect::<Vec<_>>();
+
+    for (_, op, operands) in &decoded {
+        if matches!(op, Opcode::Jump | Opcode::JumpIfFalse) {
+            assert!(starts.contains(&operands[0]), "jump into an instruction");
+        }
+    }
+    let outcome = run_source("if (true) { while (true) { break; } }").expect("program should run");
+    assert_eq!(outcome.result.inspect(), "null");
+}
+
 #[test]
 fn continue_inside_loop_jumps_to_loop_start() {
     let chunk = compile_input("while (true) { continue; }").expect("compile should succeed");
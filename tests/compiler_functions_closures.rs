@@ -1,10 +1,8 @@
-use std::rc::Rc;
-
 use monkey_rust_compiler::ast::Program;
 use monkey_rust_compiler::bytecode::{lookup_definition, read_operands, Chunk, Opcode};
 use monkey_rust_compiler::compiler::{CompileError, Compiler};
 use monkey_rust_compiler::lexer::Lexer;
-use monkey_rust_compiler::object::{CompiledFunctionObject, Object};
+use monkey_rust_compiler::object::{CompiledFunctionObject, Object, Shared};
 use monkey_rust_compiler::parser::Parser;
 
 fn parse_program(input: &str) -> Program {
@@ -25,7 +23,9 @@ fn parse_program(input: &str) -> Program {
 
 fn compile_input(input: &str) -> Result<Chunk, CompileError> {
     let mut compiler = Compiler::new();
-    compiler.compile_program(&parse_program(input))?;
+    compiler
+        .compile_program(&parse_program(input))
+        .map_err(|mut errors| errors.remove(0))?;
     Ok(compiler.into_bytecode())
 }
 
@@ -50,9 +50,9 @@ fn decode_chunk(chunk: &Chunk) -> Vec<(usize, Opcode, Vec<usize>)> {
     decode_instructions(&chunk.instructions)
 }
 
-fn as_compiled_function(obj: &Rc<Object>) -> Rc<CompiledFunctionObject> {
+fn as_compiled_function(obj: &Shared<Object>) -> Shared<CompiledFunctionObject> {
     match obj.as_ref() {
-        Object::CompiledFunction(f) => Rc::clone(f),
+        Object::CompiledFunction(f) => Shared::clone(f),
         other => panic!("expected compiled function constant, got {other:?}"),
     }
 }
@@ -75,7 +75,7 @@ fn function_literal_compiles_to_closure_constant() {
         .into_iter()
         .map(|(_, op, _)| op)
         .collect::<Vec<_>>();
-    assert_eq!(body, vec![Opcode::Constant, Opcode::ReturnValue]);
+    assert_eq!(body, vec![Opcode::Const1, Opcode::ReturnValue]);
     assert_eq!(function.num_params, 0);
     assert_eq!(function.num_locals, 0);
 }
@@ -91,7 +91,7 @@ fn explicit_return_in_function_preserved() {
         .map(|(_, op, _)| op)
         .collect::<Vec<_>>();
 
-    assert_eq!(body, vec![Opcode::Constant, Opcode::ReturnValue]);
+    assert_eq!(body, vec![Opcode::Const1, Opcode::ReturnValue]);
 }
 
 #[test]
@@ -165,8 +165,8 @@ fn call_expression_compilation_order_and_argc() {
         .expect("expected call opcode");
     assert_eq!(ops[call_idx], (Opcode::Call, vec![2]));
     assert_eq!(ops[call_idx - 3].0, Opcode::GetGlobal);
-    assert_eq!(ops[call_idx - 2].0, Opcode::Constant);
-    assert_eq!(ops[call_idx - 1].0, Opcode::Constant);
+    assert_eq!(ops[call_idx - 2].0, Opcode::Const1);
+    assert_eq!(ops[call_idx - 1], (Opcode::ConstInt, vec![2]));
 
     let chunk = compile_input("fn(x) { x }(5);").expect("compile should succeed");
     let ops = decode_chunk(&chunk)
@@ -201,7 +201,7 @@ fn closure_free_variable_capture() {
         .constants
         .iter()
         .filter_map(|obj| match obj.as_ref() {
-            Object::CompiledFunction(f) => Some(Rc::clone(f)),
+            Object::CompiledFunction(f) => Some(Shared::clone(f)),
             _ => None,
         })
         .collect::<Vec<_>>();
@@ -251,7 +251,7 @@ fn nested_closure_chain_capture() {
         .constants
         .iter()
         .filter_map(|obj| match obj.as_ref() {
-            Object::CompiledFunction(f) => Some(Rc::clone(f)),
+            Object::CompiledFunction(f) => Some(Shared::clone(f)),
             _ => None,
         })
         .collect::<Vec<_>>();
@@ -278,7 +278,7 @@ fn recursive_function_self_reference_via_current_closure() {
         .constants
         .iter()
         .find_map(|obj| match obj.as_ref() {
-            Object::CompiledFunction(f) => Some(Rc::clone(f)),
+            Object::CompiledFunction(f) => Some(Shared::clone(f)),
             _ => None,
         })
         .expect("expected compiled function");
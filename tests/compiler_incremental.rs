@@ -0,0 +1,53 @@
+use monkey_rust_compiler::ast::Program;
+use monkey_rust_compiler::compiler::Compiler;
+use monkey_rust_compiler::lexer::Lexer;
+use monkey_rust_compiler::parser::Parser;
+use monkey_rust_compiler::vm::Vm;
+
+fn parse(input: &str) -> Program {
+    let mut parser = Parser::new(Lexer::new(input));
+    let program = parser.parse_program();
+    assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+    program
+}
+
+#[test]
+fn deltas_share_globals_and_the_constant_pool() {
+    let mut compiler = Compiler::new();
+    let first = compiler
+        .extend(&parse("let greeting = \"hi\"; let f = fn(x) { x + 1000 };"))
+        .expect("first delta");
+    assert_eq!(first.constants.len(), 2);
+
+    // Interned constants are reused, so only the new string is added.
+    let second = compiler
+        .extend(&parse("puts(greeting + \"hi\" + \"!\"); f(1000);"))
+        .expect("second delta");
+    assert_eq!(second.constants.len(), 1);
+    assert_eq!(compiler.bytecode().constants.len(), 3);
+
+    let mut vm = Vm::new(first);
+    vm.run().expect("first run");
+    vm.append(second);
+    assert_eq!(vm.run().expect("second run").inspect(), "2000");
+    assert_eq!(vm.take_output(), ["hihi!"]);
+}
+
+#[test]
+fn failed_extends_are_rolled_back() {
+    let mut compiler = Compiler::new();
+    compiler.extend(&parse("let a = 1;")).expect("first delta");
+
+    let errors = compiler
+        .extend(&parse("let b = \"new\"; missing;"))
+        .expect_err("missing is unresolved");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(compiler.bytecode().constants.len(), 0);
+    assert_eq!(compiler.global_index("a"), Some(0));
+    assert_eq!(compiler.global_index("b"), None);
+
+    compiler.extend(&parse("let c = a;")).expect("third delta");
+    assert_eq!(compiler.global_index("c"), Some(1));
+    compiler.forget_last_extend();
+    assert_eq!(compiler.global_index("c"), None);
+}
@@ -23,7 +23,9 @@ fn parse_program(input: &str) -> Program {
 
 fn compile_input(input: &str) -> Result<Chunk, CompileError> {
     let mut compiler = Compiler::new();
-    compiler.compile_program(&parse_program(input))?;
+    compiler
+        .compile_program(&parse_program(input))
+        .map_err(|mut errors| errors.remove(0))?;
     Ok(compiler.into_bytecode())
 }
 
@@ -0,0 +1,213 @@
+use monkey_rust_compiler::bytecode::{make, Chunk, Opcode};
+use monkey_rust_compiler::compiler::CompilerOptions;
+use monkey_rust_compiler::object::Object;
+use monkey_rust_compiler::peephole::optimize_scope;
+use monkey_rust_compiler::position::{Position, Span};
+use monkey_rust_compiler::runner::{compile_source_with_options, run_chunk_with, RunOptions};
+use monkey_rust_compiler::vm::VmOptions;
+
+fn compile(input: &str, optimize: bool) -> Chunk {
+    compile_source_with_options(input, CompilerOptions::default().with_optimize(optimize))
+        .expect("compile should succeed")
+}
+
+fn opcode_names(chunk: &Chunk) -> Vec<String> {
+    chunk
+        .disassemble()
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .map(|line| {
+            line.split_whitespace()
+                .nth(1)
+                .unwrap_or_default()
+                .to_string()
+        })
+        .collect()
+}
+
+fn run(chunk: Chunk) -> (String, Vec<String>) {
+    // Superinstructions off so both runs execute exactly what was compiled.
+    let options = RunOptions::default().with_vm(VmOptions::default().with_superinstructions(false));
+    match run_chunk_with(chunk, &options) {
+        Ok(outcome) => (outcome.result.inspect(), outcome.output),
+        Err(err) => (format!("{err:?}"), Vec::new()),
+    }
+}
+
+fn encode(code: &[(Opcode, &[usize])]) -> Vec<u8> {
+    code.iter()
+        .flat_map(|(op, operands)| make(*op, operands).expect("encode"))
+        .collect()
+}
+
+#[test]
+fn optimization_is_off_by_default() {
+    let input = "let x = 1; true && x > 0;";
+    assert_eq!(
+        compile_source_with_options(input, CompilerOptions::default())
+            .expect("compile")
+            .instructions,
+        compile(input, false).instructions
+    );
+    assert!(opcode_names(&compile(input, false)).contains(&"Bang".to_string()));
+}
+
+#[test]
+fn drops_bool_normalization_after_comparisons() {
+    let chunk = compile("let x = 1; true && x > 0;", true);
+    assert!(!opcode_names(&chunk).contains(&"Bang".to_string()));
+
+    // A truthy but non-boolean operand still needs normalizing.
+    let chunk = compile("let x = 1; true && x;", true);
+    assert_eq!(
+        opcode_names(&chunk)
+            .iter()
+            .filter(|name| *name == "Bang")
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn drops_discarded_literals_in_every_scope() {
+    let chunk = compile("1; \"a\"; 3;", false);
+    assert!(opcode_names(&chunk).contains(&"Const1".to_string()));
+
+    let chunk = compile("let f = fn() { 1; 2; true; 3 }; f(); 4;", true);
+    assert_eq!(
+        opcode_names(&chunk),
+        [
+            "Closure",
+            "SetGlobal",
+            "GetGlobal",
+            "Call",
+            "Pop",
+            "ConstInt",
+            "ReturnValue"
+        ]
+    );
+    let Object::CompiledFunction(function) = chunk.constants[0].as_ref() else {
+        panic!("expected a compiled function");
+    };
+    assert_eq!(
+        function.instructions.to_vec(),
+        encode(&[(Opcode::ConstInt, &[3]), (Opcode::ReturnValue, &[])])
+    );
+}
+
+#[test]
+fn removes_jumps_to_the_next_instruction_and_remaps_the_rest() {
+    let mut code = encode(&[
+        (Opcode::True, &[]),
+        (Opcode::JumpIfFalse, &[12]),
+        (Opcode::Jump, &[7]),
+        (Opcode::Pop, &[]),
+        (Opcode::Null, &[]),
+        (Opcode::Jump, &[12]),
+        (Opcode::ReturnValue, &[]),
+    ]);
+    let pos = |line| Span::at(Position::new(line, 1));
+    let mut positions = vec![
+        (0, pos(1)),
+        (1, pos(2)),
+        (4, pos(3)),
+        (7, pos(4)),
+        (8, pos(5)),
+        (9, pos(6)),
+        (12, pos(7)),
+    ];
+
+    assert!(optimize_scope(&mut code, &mut positions));
+    assert_eq!(
+        code,
+        encode(&[
+            (Opcode::True, &[]),
+            (Opcode::JumpIfFalse, &[6]),
+            (Opcode::Pop, &[]),
+            (Opcode::Null, &[]),
+            (Opcode::ReturnValue, &[]),
+        ])
+    );
+    assert_eq!(
+        positions,
+        vec![
+            (0, pos(1)),
+            (1, pos(2)),
+            (4, pos(4)),
+            (5, pos(5)),
+            (6, pos(7))
+        ]
+    );
+    assert!(!optimize_scope(&mut code, &mut positions));
+}
+
+#[test]
+fn threads_jumps_through_chains_of_unconditional_jumps() {
+    let input = "let a = true; let b = false;
+        if (a) { if (b) { 1 } else { if (a) { 2 } else { 3 } } } else { 4 };";
+    let jumps_to_jumps = |chunk: &Chunk| {
+        let disassembly = chunk.disassemble();
+        let lines = disassembly.split("\n\n").next().unwrap_or_default();
+        let starts = lines
+            .lines()
+            .filter(|line| line.split_whitespace().nth(1) == Some("Jump"))
+            .map(|line| line[..4].parse::<usize>().expect("offset"))
+            .collect::<Vec<_>>();
+        lines
+            .lines()
+            .filter(|line| line.contains(" Jump") || line.contains(" JumpIfFalse"))
+            .filter_map(|line| line.split_whitespace().nth(2)?.parse::<usize>().ok())
+            .filter(|target| starts.contains(target))
+            .count()
+    };
+    assert!(jumps_to_jumps(&compile(input, false)) > 0);
+    assert_eq!(jumps_to_jumps(&compile(input, true)), 0);
+
+    let program = |first: usize, second: usize, third: usize| {
+        encode(&[
+            (Opcode::JumpIfFalse, &[first]), // 0
+            (Opcode::Null, &[]),             // 3
+            (Opcode::ReturnValue, &[]),      // 4
+            (Opcode::Null, &[]),             // 5
+            (Opcode::Jump, &[second]),       // 6
+            (Opcode::True, &[]),             // 9
+            (Opcode::Jump, &[third]),        // 10
+            (Opcode::ReturnValue, &[]),      // 13
+            (Opcode::Null, &[]),             // 14
+            (Opcode::ReturnValue, &[]),      // 15
+        ])
+    };
+    let mut code = program(6, 10, 14);
+    assert!(optimize_scope(&mut code, &mut Vec::new()));
+    assert_eq!(code, program(14, 14, 14));
+
+    // A jump that loops onto itself is left alone.
+    let mut code = encode(&[(Opcode::Jump, &[0])]);
+    assert!(!optimize_scope(&mut code, &mut Vec::new()));
+}
+
+#[test]
+fn optimized_programs_behave_the_same() {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let mut sources = vec![
+        "let a = 5; if (a > 1 && a < 10 || !(a == 5)) { puts(\"in\") }; a;".to_string(),
+        "let f = fn(n) { if (n) { 1 } else { }; if (n > 2) { return n; } n * 2 }; [f(1), f(5)];"
+            .to_string(),
+        "let i = 0; while (i < 5) { let i = i + 1; if (i == 2) { continue; } puts(i); }; i;"
+            .to_string(),
+        "1 / 0;".to_string(),
+    ];
+    for entry in std::fs::read_dir(format!("{root}/examples")).expect("read examples") {
+        let path = entry.expect("dir entry").path();
+        if path.extension().is_some_and(|ext| ext == "monkey") {
+            sources.push(std::fs::read_to_string(path).expect("read source"));
+        }
+    }
+
+    for source in sources {
+        let plain = compile(&source, false);
+        let optimized = compile(&source, true);
+        assert!(optimized.instructions.len() <= plain.instructions.len());
+        assert_eq!(run(optimized), run(plain), "{source}");
+    }
+}
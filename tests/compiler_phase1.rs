@@ -25,7 +25,9 @@ fn parse_program(input: &str) -> Program {
 fn compile_input(input: &str) -> Result<Chunk, CompileError> {
     let program = parse_program(input);
     let mut compiler = Compiler::new();
-    compiler.compile_program(&program)?;
+    compiler
+        .compile_program(&program)
+        .map_err(|mut errors| errors.remove(0))?;
     Ok(compiler.into_bytecode())
 }
 
@@ -55,8 +57,26 @@ fn compiles_literals_and_expression_pop() {
     let cases = [
         (
             "1;",
+            vec![(Opcode::Const1, vec![]), (Opcode::ReturnValue, vec![])],
+            vec![],
+        ),
+        (
+            "0;",
+            vec![(Opcode::Const0, vec![]), (Opcode::ReturnValue, vec![])],
+            vec![],
+        ),
+        (
+            "32767;",
+            vec![
+                (Opcode::ConstInt, vec![32767]),
+                (Opcode::ReturnValue, vec![]),
+            ],
+            vec![],
+        ),
+        (
+            "32768;",
             vec![(Opcode::Constant, vec![0]), (Opcode::ReturnValue, vec![])],
-            vec![Object::Integer(1)],
+            vec![Object::Integer(32768)],
         ),
         (
             "true;",
@@ -71,7 +91,7 @@ fn compiles_literals_and_expression_pop() {
         (
             "\"abc\";",
             vec![(Opcode::Constant, vec![0]), (Opcode::ReturnValue, vec![])],
-            vec![Object::String("abc".to_string())],
+            vec![Object::String("abc".into())],
         ),
     ];
 
@@ -107,22 +127,22 @@ fn compiles_prefix_expressions() {
         (
             "-5;",
             vec![
-                (Opcode::Constant, vec![0]),
+                (Opcode::ConstInt, vec![5]),
                 (Opcode::Neg, vec![]),
                 (Opcode::ReturnValue, vec![]),
             ],
-            vec![Object::Integer(5)],
+            vec![],
         ),
         (
             "!(1 == 2);",
             vec![
-                (Opcode::Constant, vec![0]),
-                (Opcode::Constant, vec![1]),
+                (Opcode::Const1, vec![]),
+                (Opcode::ConstInt, vec![2]),
                 (Opcode::Eq, vec![]),
                 (Opcode::Bang, vec![]),
                 (Opcode::ReturnValue, vec![]),
             ],
-            vec![Object::Integer(1), Object::Integer(2)],
+            vec![],
         ),
     ];
 
@@ -149,7 +169,7 @@ fn compiles_infix_expressions() {
         ("1 + 2;", Opcode::Add),
         ("1 - 2;", Opcode::Sub),
         ("1 * 2;", Opcode::Mul),
-        ("4 / 2;", Opcode::Div),
+        ("1 / 2;", Opcode::Div),
         ("1 == 2;", Opcode::Eq),
         ("1 != 2;", Opcode::Ne),
         ("1 < 2;", Opcode::Lt),
@@ -168,8 +188,8 @@ fn compiles_infix_expressions() {
         assert_eq!(
             decoded,
             vec![
-                (Opcode::Constant, vec![0]),
-                (Opcode::Constant, vec![1]),
+                (Opcode::Const1, vec![]),
+                (Opcode::ConstInt, vec![2]),
                 (expected_op, vec![]),
                 (Opcode::ReturnValue, vec![]),
             ],
@@ -189,10 +209,10 @@ fn grouped_precedence_uses_ast_shape() {
     assert_eq!(
         decoded,
         vec![
-            (Opcode::Constant, vec![0]),
-            (Opcode::Constant, vec![1]),
+            (Opcode::Const1, vec![]),
+            (Opcode::ConstInt, vec![2]),
             (Opcode::Add, vec![]),
-            (Opcode::Constant, vec![2]),
+            (Opcode::ConstInt, vec![3]),
             (Opcode::Mul, vec![]),
             (Opcode::ReturnValue, vec![]),
         ]
@@ -210,7 +230,7 @@ fn compiles_let_and_identifier_globals() {
     assert_eq!(
         decoded,
         vec![
-            (Opcode::Constant, vec![0]),
+            (Opcode::Const1, vec![]),
             (Opcode::SetGlobal, vec![0]),
             (Opcode::GetGlobal, vec![0]),
             (Opcode::ReturnValue, vec![]),
@@ -226,9 +246,9 @@ fn compiles_let_and_identifier_globals() {
     assert_eq!(
         decoded,
         vec![
-            (Opcode::Constant, vec![0]),
+            (Opcode::Const1, vec![]),
             (Opcode::SetGlobal, vec![0]),
-            (Opcode::Constant, vec![1]),
+            (Opcode::ConstInt, vec![2]),
             (Opcode::SetGlobal, vec![1]),
             (Opcode::GetGlobal, vec![0]),
             (Opcode::GetGlobal, vec![1]),
@@ -270,6 +290,48 @@ fn unresolved_identifier_is_compile_error() {
     assert_eq!(err.pos, Some(Position::new(1, 1)));
 }
 
+#[test]
+fn unresolved_identifiers_suggest_similar_names() {
+    assert_eq!(
+        compile_error("let total = 1; let f = fn(count) { totl + cout }; f(1);").message,
+        "unresolved identifier: totl (did you mean total?)"
+    );
+    assert_eq!(
+        compile_error("let f = fn(count) { cout }; f(1);").message,
+        "unresolved identifier: cout (did you mean count?)"
+    );
+    assert_eq!(
+        compile_error("let first_name = 1; let last_name = 2; frist_name;").message,
+        "unresolved identifier: frist_name (did you mean first_name or last_name?)"
+    );
+    assert_eq!(
+        compile_error("lenn(\"abc\");").message,
+        "unresolved identifier: lenn (did you mean len?)"
+    );
+    assert_eq!(
+        compile_error("let alpha = 1; zzzzz;").message,
+        "unresolved identifier: zzzzz"
+    );
+    // Every one-letter name is one edit from every other.
+    assert_eq!(
+        compile_error("let a = 1; let c = b;").message,
+        "unresolved identifier: b"
+    );
+    assert_eq!(
+        compile_error("let ab = 1; ac;").message,
+        "unresolved identifier: ac (did you mean ab?)"
+    );
+    // Keywords only when no visible name is close.
+    assert_eq!(
+        compile_error("retrun 5;").message,
+        "unresolved identifier: retrun (did you mean return?)"
+    );
+    assert_eq!(
+        compile_error("let lets = 1; lett;").message,
+        "unresolved identifier: lett (did you mean lets?)"
+    );
+}
+
 #[test]
 fn records_instruction_positions() {
     let chunk = compile_input("let a = 1;\na + 2;").expect("compile should succeed");
@@ -302,3 +364,27 @@ fn records_instruction_positions() {
         Some(Position::new(2, 3))
     );
 }
+
+#[test]
+fn collects_errors_from_independent_statements() {
+    let input = "let a = missing1;
+let b = a + missing2;
+let f = fn(x) { let y = missing3; y + x };
+while (nope) { break; }
+puts(missing4, b, f);";
+    let mut compiler = Compiler::new();
+    let errors = compiler
+        .compile_program(&parse_program(input))
+        .expect_err("expected compile errors");
+    let rendered = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+    assert_eq!(
+        rendered,
+        [
+            "1:9: unresolved identifier: missing1",
+            "2:13: unresolved identifier: missing2",
+            "3:25: unresolved identifier: missing3",
+            "4:8: unresolved identifier: nope",
+            "5:6: unresolved identifier: missing4",
+        ]
+    );
+}
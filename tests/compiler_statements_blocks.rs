@@ -23,7 +23,9 @@ fn parse_program(input: &str) -> Program {
 
 fn compile_input(input: &str) -> Result<Chunk, CompileError> {
     let mut compiler = Compiler::new();
-    compiler.compile_program(&parse_program(input))?;
+    compiler
+        .compile_program(&parse_program(input))
+        .map_err(|mut errors| errors.remove(0))?;
     Ok(compiler.into_bytecode())
 }
 
@@ -53,7 +55,7 @@ fn return_statement_compiles_to_return_value() {
         .collect::<Vec<_>>();
     assert_eq!(
         decoded,
-        vec![(Opcode::Constant, vec![0]), (Opcode::ReturnValue, vec![])]
+        vec![(Opcode::Const1, vec![]), (Opcode::ReturnValue, vec![])]
     );
 
     let chunk = compile_input("return 1 + 2;").expect("compile should succeed");
@@ -64,8 +66,8 @@ fn return_statement_compiles_to_return_value() {
     assert_eq!(
         decoded,
         vec![
-            (Opcode::Constant, vec![0]),
-            (Opcode::Constant, vec![1]),
+            (Opcode::Const1, vec![]),
+            (Opcode::ConstInt, vec![2]),
             (Opcode::Add, vec![]),
             (Opcode::ReturnValue, vec![]),
         ]
@@ -81,7 +83,7 @@ fn top_level_final_expression_is_preserved() {
         .collect::<Vec<_>>();
     assert_eq!(
         decoded,
-        vec![(Opcode::Constant, vec![0]), (Opcode::ReturnValue, vec![])]
+        vec![(Opcode::Const1, vec![]), (Opcode::ReturnValue, vec![])]
     );
 
     let chunk = compile_input("1; 2;").expect("compile should succeed");
@@ -92,9 +94,9 @@ fn top_level_final_expression_is_preserved() {
     assert_eq!(
         decoded,
         vec![
-            (Opcode::Constant, vec![0]),
+            (Opcode::Const1, vec![]),
             (Opcode::Pop, vec![]),
-            (Opcode::Constant, vec![1]),
+            (Opcode::ConstInt, vec![2]),
             (Opcode::ReturnValue, vec![]),
         ]
     );
@@ -107,7 +109,7 @@ fn top_level_final_expression_is_preserved() {
     assert_eq!(
         decoded,
         vec![
-            (Opcode::Constant, vec![0]),
+            (Opcode::Const1, vec![]),
             (Opcode::SetGlobal, vec![0]),
             (Opcode::GetGlobal, vec![0]),
             (Opcode::ReturnValue, vec![]),
@@ -122,7 +124,7 @@ fn top_level_final_expression_is_preserved() {
     assert_eq!(
         decoded,
         vec![
-            (Opcode::Constant, vec![0]),
+            (Opcode::Const1, vec![]),
             (Opcode::SetGlobal, vec![0]),
             (Opcode::Return, vec![]),
         ]
@@ -223,3 +225,75 @@ fn empty_program_emits_synthetic_return() {
     assert_eq!(decoded, vec![(Opcode::Return, vec![])]);
     assert_eq!(chunk.position_for_offset(0), Some(Position::default()));
 }
+
+#[test]
+fn lets_inside_if_and_while_bodies_are_scoped_to_the_block() {
+    let err = compile_input("let c = true; if (c) { let inner = 1; inner }; inner;")
+        .expect_err("inner should be out of scope");
+    assert_eq!(err.error_code(), "C006");
+    assert_eq!(err.pos, Some(Position::new(1, 48)));
+    assert_eq!(
+        err.message,
+        "inner is out of scope: it is only defined inside an earlier block"
+    );
+
+    let err = compile_input("let i = 0; while (i < 2) { let step = 1; let i = i + step; }; step;")
+        .expect_err("step should be out of scope");
+    assert_eq!(err.error_code(), "C006");
+
+    let err = compile_input("if (true) { let t = 1; } else { t };")
+        .expect_err("the else branch cannot see the consequence's bindings");
+    assert_eq!(err.error_code(), "C006");
+
+    let err = compile_input("nope;").expect_err("nope is unresolved");
+    assert_eq!(err.error_code(), "C001");
+}
+
+#[test]
+fn block_lets_of_visible_names_rebind_them() {
+    let run = |input: &str| {
+        monkey_rust_compiler::runner::run_source(input)
+            .expect("run should succeed")
+            .result
+            .inspect()
+    };
+    assert_eq!(
+        run("let i = 0; let s = 0; while (i < 4) { let i = i + 1; let s = s + i; }; s;"),
+        "10"
+    );
+    assert_eq!(
+        run("let f = fn() { let x = 1; if (true) { let x = 2; }; x }; f();"),
+        "2"
+    );
+    // A block may shadow a builtin; the builtin is back once it ends.
+    assert_eq!(
+        run("let c = true; if (c) { let len = fn(x) { 0 }; len(\"ab\") }; len(\"ab\");"),
+        "2"
+    );
+    assert_eq!(
+        run("let c = true; let g = if (c) { let a = 5; fn() { a } }; g();"),
+        "5"
+    );
+}
+
+#[test]
+fn block_lets_inside_functions_define_function_locals() {
+    let run = |input: &str| {
+        monkey_rust_compiler::runner::run_source(input)
+            .expect("run should succeed")
+            .result
+            .inspect()
+    };
+    assert_eq!(
+        run("let x = 1; let f = fn() { if (true) { let x = 2; } x }; f();"),
+        "2"
+    );
+    assert_eq!(
+        run("let f = fn(c) { while (c) { let t = 3; let c = false; } t }; f(true);"),
+        "3"
+    );
+    assert_eq!(
+        run("let f = fn(c) { if (c) { let t = 1; } else { t } }; f(false);"),
+        "null"
+    );
+}
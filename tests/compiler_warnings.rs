@@ -0,0 +1,109 @@
+use monkey_rust_compiler::compiler::{CompileWarning, CompilerOptions, RedefinitionPolicy};
+use monkey_rust_compiler::runner::{compile_source_with_warnings, run_source, RunnerError};
+
+fn warnings(input: &str) -> Vec<String> {
+    let (_, warnings) = compile_source_with_warnings(input, CompilerOptions::default())
+        .expect("compile should succeed");
+    warnings.iter().map(CompileWarning::to_string).collect()
+}
+
+#[test]
+fn reports_unused_lets_and_parameters() {
+    assert_eq!(
+        warnings("let a = 1;\nlet f = fn(x, y) { let z = y; 2 };\nf(1, 2);"),
+        [
+            "1:5: unused variable: a [W001]",
+            "2:12: unused parameter: x [W002]",
+            "2:24: unused variable: z [W001]",
+        ]
+    );
+}
+
+#[test]
+fn any_read_counts_as_a_use() {
+    assert!(warnings("let x = 1; let y = x + 1; puts(y);").is_empty());
+    assert!(warnings("let n = 2; let f = fn() { n }; f();").is_empty());
+    assert!(warnings("let add = fn(a) { fn(b) { a + b } }; add(1)(2);").is_empty());
+    assert!(warnings("let i = 0; while (i < 3) { let i = i + 1; }").is_empty());
+    assert!(
+        warnings("let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) } }; fib(3);").is_empty()
+    );
+}
+
+#[test]
+fn underscore_names_and_eval_programs_are_exempt() {
+    assert!(warnings("let _unused = 1; let f = fn(_x) { 1 }; f(0);").is_empty());
+    assert!(warnings("let hidden = 41; eval(\"hidden + 1\");").is_empty());
+    // eval only reaches globals, so locals are still checked.
+    assert_eq!(
+        warnings("let f = fn(x) { eval(\"1\") }; f(1);"),
+        ["1:12: unused parameter: x [W002]"]
+    );
+}
+
+#[test]
+fn warnings_are_sorted_and_do_not_fail_compilation() {
+    assert_eq!(
+        warnings("let f = fn() {\n  return 1;\n  2;\n};\nlet g = 5;\nf();"),
+        [
+            "3:3: unreachable code [W003]",
+            "5:5: unused variable: g [W001]"
+        ]
+    );
+    let outcome = run_source("let unused = 1; 2;").expect("run should succeed");
+    assert_eq!(outcome.result.inspect(), "2");
+    assert_eq!(outcome.warnings.len(), 1);
+}
+
+#[test]
+fn reports_redefinitions_in_the_same_block() {
+    assert_eq!(
+        warnings("let x = 1;\nlet x = x + 1;\nputs(x);"),
+        ["2:5: redefinition of x (first defined at 1:5) [W004]"]
+    );
+    assert_eq!(
+        warnings("let f = fn(a, a) { a }; f(1, 2);"),
+        ["1:15: redefinition of a (first defined at 1:12) [W004]"]
+    );
+    // Rebinding an outer name from a block, or the same name in another
+    // function, is not a redefinition.
+    assert!(warnings(
+        "let i = 0; while (i < 3) { let i = i + 1; }; let f = fn() { let i = 5; i }; f();"
+    )
+    .is_empty());
+    // A block-local name may be defined again once its block has ended.
+    assert!(
+        warnings("let c = true; if (c) { let t = 1; puts(t); }; let t = 2; puts(t);").is_empty()
+    );
+}
+
+#[test]
+fn redefinitions_can_be_made_errors() {
+    let options = CompilerOptions::default().with_redefinition(RedefinitionPolicy::Error);
+    let errors = match compile_source_with_warnings(
+        "let x = 1;\nlet x = 2;\nlet y = 3;\nlet y = 4;",
+        options,
+    ) {
+        Err(RunnerError::Compile(errors)) => errors,
+        other => panic!("expected compile errors, got {other:?}"),
+    };
+    assert_eq!(
+        errors.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        [
+            "2:5: redefinition of x (first defined at 1:5)",
+            "4:5: redefinition of y (first defined at 3:5)"
+        ]
+    );
+    assert_eq!(errors[0].error_code(), "C007");
+}
+
+#[test]
+fn warns_when_a_binding_shadows_a_builtin() {
+    assert_eq!(
+        warnings("let f = fn(len) { len + 1 }; let puts = f(1); puts;"),
+        [
+            "1:12: shadowed builtin: len [W005]",
+            "1:34: shadowed builtin: puts [W005]"
+        ]
+    );
+}
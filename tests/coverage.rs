@@ -0,0 +1,80 @@
+use monkey_rust_compiler::coverage::Coverage;
+use monkey_rust_compiler::runner::{compile_source, run_chunk_with, RunOptions, RunnerError};
+use monkey_rust_compiler::vm::VmOptions;
+
+const PROGRAM: &str = "let f = fn(x) {
+  if (x > 1) {
+    puts(\"big\");
+  } else {
+    puts(\"small\");
+  }
+};
+f(5);
+let unused = fn() {
+  1 + 2
+};
+let i = 0;
+while (i < 3) { let i = i + 1; }";
+
+fn coverage(source: &str, options: VmOptions) -> Coverage {
+    let chunk = compile_source(source).expect("compile");
+    run_chunk_with(
+        chunk,
+        &RunOptions::default().with_vm(options.with_coverage(true)),
+    )
+    .expect("run")
+    .coverage
+    .expect("coverage was requested")
+}
+
+#[test]
+fn reports_lines_that_never_ran() {
+    let coverage = coverage(PROGRAM, VmOptions::default());
+    assert_eq!(
+        coverage.lines().iter().copied().collect::<Vec<_>>(),
+        [1, 2, 3, 5, 8, 9, 10, 12, 13]
+    );
+    assert_eq!(coverage.unexecuted_lines(), [5, 10]);
+    assert_eq!(
+        coverage.render(Some(PROGRAM)),
+        "7 of 9 lines executed (77.8%)
+Unexecuted lines:
+   5 |     puts(\"small\");
+  10 |   1 + 2"
+    );
+    assert_eq!(
+        coverage.render(None),
+        "7 of 9 lines executed (77.8%)\nUnexecuted lines:\n   5\n  10"
+    );
+}
+
+#[test]
+fn superinstructions_cover_the_lines_they_replaced() {
+    let source = "let f = fn(a, b) {
+  a +
+  b
+};
+f(1, 2);";
+    let fused = coverage(source, VmOptions::default());
+    let plain = coverage(source, VmOptions::default().with_superinstructions(false));
+    assert_eq!(fused, plain);
+    assert!(fused.unexecuted_lines().is_empty());
+    assert_eq!(fused.render(None), "4 of 4 lines executed (100.0%)");
+}
+
+#[test]
+fn coverage_is_off_by_default_and_skips_eval() {
+    let chunk = compile_source("1;").expect("compile");
+    let outcome = run_chunk_with(chunk, &RunOptions::default()).expect("run");
+    assert!(outcome.coverage.is_none());
+
+    // Lines inside the evaluated string are not lines of this program.
+    let coverage = coverage("let x = eval(\"1;\n2;\n3;\");\nx;", VmOptions::default());
+    assert_eq!(coverage.lines().iter().copied().collect::<Vec<_>>(), [1, 4]);
+    assert!(coverage.unexecuted_lines().is_empty());
+
+    let chunk = compile_source("1 / 0;").expect("compile");
+    let options = RunOptions::default().with_vm(VmOptions::default().with_coverage(true));
+    let err = run_chunk_with(chunk, &options);
+    assert!(matches!(err, Err(RunnerError::Runtime(_))));
+}
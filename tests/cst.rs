@@ -0,0 +1,151 @@
+use monkey_rust_compiler::cst::{lossless_tokens, Cst, CstNode, NodeKind, TriviaKind};
+use monkey_rust_compiler::position::Position;
+use monkey_rust_compiler::token::TokenKind;
+
+const SOURCE: &str = "# leading comment\r\nlet  add = fn(a,\tb) {\n  a +   # inline\n  b\n};\n\nputs(add(1, \"x\\ty\"), [\"é\"], {1: true})[0]   # trailing";
+
+fn round_trip(source: &str) -> String {
+    lossless_tokens(source)
+        .iter()
+        .flat_map(|token| {
+            token
+                .leading
+                .iter()
+                .map(|trivia| trivia.text.clone())
+                .chain(std::iter::once(token.text.clone()))
+        })
+        .collect()
+}
+
+#[test]
+fn reproduces_the_source_exactly() {
+    let cst = Cst::parse(SOURCE).expect("parses");
+    assert_eq!(cst.to_string(), SOURCE);
+    for source in [
+        "",
+        "   ",
+        "# only a comment",
+        "\n\n# a\n\t# b\n",
+        "x",
+        "x;\r\n",
+    ] {
+        assert_eq!(Cst::parse(source).expect("parses").to_string(), source);
+    }
+    // Tokens alone work for input that does not parse.
+    for source in ["let = ;;", "\"unterminated", "1.2.3 @ ~", "fn(x { # c"] {
+        assert_eq!(round_trip(source), source);
+    }
+}
+
+#[test]
+fn tokens_keep_their_text_and_trivia() {
+    let cst = Cst::parse(SOURCE).expect("parses");
+    let tokens = cst.tokens();
+
+    let first = &tokens[0];
+    assert_eq!(first.kind, TokenKind::Let);
+    assert_eq!(first.text, "let");
+    assert_eq!(
+        first
+            .leading
+            .iter()
+            .map(|trivia| (trivia.kind, trivia.text.as_str(), trivia.pos))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                TriviaKind::Comment,
+                "# leading comment\r",
+                Position::new(1, 1)
+            ),
+            (TriviaKind::Whitespace, "\n", Position::new(1, 19)),
+        ]
+    );
+
+    let b = tokens
+        .iter()
+        .find(|token| token.pos == Position::new(4, 3))
+        .expect("b");
+    assert_eq!(b.text, "b");
+    assert_eq!(
+        b.comments().map(|c| c.text.as_str()).collect::<Vec<_>>(),
+        vec!["# inline"]
+    );
+
+    let string = tokens
+        .iter()
+        .find(|token| token.kind == TokenKind::String)
+        .expect("string");
+    assert_eq!(string.text, "\"x\\ty\"");
+
+    let eof = tokens.last().expect("eof");
+    assert_eq!(eof.kind, TokenKind::Eof);
+    assert_eq!(
+        eof.comments().map(|c| c.text.as_str()).collect::<Vec<_>>(),
+        vec!["# trailing"]
+    );
+}
+
+fn outline(cst: &Cst, node: &CstNode, out: &mut Vec<(NodeKind, String)>) {
+    out.push((node.kind, cst.text(node)));
+    for child in &node.children {
+        outline(cst, child, out);
+    }
+}
+
+#[test]
+fn nodes_cover_the_source_of_their_ast_nodes() {
+    let cst = Cst::parse(SOURCE).expect("parses");
+    assert_eq!(cst.root().children.len(), cst.program().statements.len());
+    let mut nodes = Vec::new();
+    for statement in &cst.root().children {
+        outline(&cst, statement, &mut nodes);
+    }
+    let nodes = nodes
+        .iter()
+        .map(|(kind, text)| (*kind, text.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        nodes,
+        vec![
+            (
+                NodeKind::Let,
+                "let  add = fn(a,\tb) {\n  a +   # inline\n  b\n};"
+            ),
+            (NodeKind::Binding, "add"),
+            (
+                NodeKind::FunctionLiteral,
+                "fn(a,\tb) {\n  a +   # inline\n  b\n}"
+            ),
+            (NodeKind::Binding, "a"),
+            (NodeKind::Binding, "b"),
+            (NodeKind::Block, "{\n  a +   # inline\n  b\n}"),
+            (NodeKind::ExpressionStatement, "a +   # inline\n  b"),
+            (NodeKind::Infix, "a +   # inline\n  b"),
+            (NodeKind::Identifier, "a"),
+            (NodeKind::Identifier, "b"),
+            (
+                NodeKind::ExpressionStatement,
+                "puts(add(1, \"x\\ty\"), [\"é\"], {1: true})[0]"
+            ),
+            (
+                NodeKind::Index,
+                "puts(add(1, \"x\\ty\"), [\"é\"], {1: true})[0]"
+            ),
+            (
+                NodeKind::Call,
+                "puts(add(1, \"x\\ty\"), [\"é\"], {1: true})"
+            ),
+            (NodeKind::Identifier, "puts"),
+            (NodeKind::Call, "add(1, \"x\\ty\")"),
+            (NodeKind::Identifier, "add"),
+            (NodeKind::IntegerLiteral, "1"),
+            (NodeKind::StringLiteral, "\"x\\ty\""),
+            (NodeKind::ArrayLiteral, "[\"é\"]"),
+            (NodeKind::StringLiteral, "\"é\""),
+            (NodeKind::HashLiteral, "{1: true}"),
+            (NodeKind::IntegerLiteral, "1"),
+            (NodeKind::BooleanLiteral, "true"),
+            (NodeKind::IntegerLiteral, "0"),
+        ]
+    );
+}
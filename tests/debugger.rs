@@ -0,0 +1,105 @@
+use monkey_rust_compiler::debugger::{Debugger, StopReason};
+use monkey_rust_compiler::object::Object;
+
+const PROGRAM: &str = "let add = fn(a, b) {
+  let sum = a + b;
+  sum
+};
+let x = add(1, 2);
+puts(x);
+let y = add(x, 10);
+y";
+
+fn debugger(source: &str) -> Debugger {
+    Debugger::from_source(source, &[]).expect("program should compile")
+}
+
+#[test]
+fn breakpoints_snap_to_lines_with_code() {
+    let mut dbg = debugger(PROGRAM);
+    assert_eq!(dbg.set_breakpoint(2), Some(2));
+    assert_eq!(dbg.set_breakpoint(4), Some(5));
+    assert_eq!(dbg.set_breakpoint(99), None);
+    assert_eq!(dbg.breakpoints().collect::<Vec<_>>(), vec![2, 5]);
+    assert!(dbg.clear_breakpoint(5));
+    assert!(!dbg.clear_breakpoint(5));
+}
+
+#[test]
+fn continue_stops_at_each_breakpoint_hit() {
+    let mut dbg = debugger(PROGRAM);
+    dbg.set_breakpoint(2);
+    assert!(matches!(dbg.resume(), StopReason::Breakpoint(2)));
+    assert_eq!(dbg.vm().current_function_name(), Some("add"));
+    let locals = dbg.locals();
+    assert_eq!(locals[0].1.as_ref(), &Object::Integer(1));
+    assert_eq!(locals[1].1.as_ref(), &Object::Integer(2));
+
+    assert!(matches!(dbg.resume(), StopReason::Breakpoint(2)));
+    assert_eq!(dbg.locals()[0].1.as_ref(), &Object::Integer(3));
+    let globals = dbg.globals();
+    assert_eq!(globals[0].0, "add");
+    assert_eq!(globals[1].0, "x");
+    assert_eq!(globals[1].1.as_ref(), &Object::Integer(3));
+
+    match dbg.resume() {
+        StopReason::Finished(value) => assert_eq!(value.as_ref(), &Object::Integer(13)),
+        other => panic!("expected finish, got {other:?}"),
+    }
+    assert!(matches!(dbg.resume(), StopReason::Finished(_)));
+}
+
+#[test]
+fn step_into_over_and_out_follow_call_depth() {
+    let mut dbg = debugger(PROGRAM);
+    assert_eq!(dbg.line(), Some(1));
+    assert!(matches!(dbg.step_over(), StopReason::Step));
+    assert_eq!(dbg.line(), Some(5));
+
+    assert!(matches!(dbg.step_into(), StopReason::Step));
+    assert_eq!(dbg.line(), Some(2));
+    assert_eq!(dbg.vm().frame_depth(), 2);
+
+    assert!(matches!(dbg.step_out(), StopReason::Step));
+    assert_eq!(dbg.vm().frame_depth(), 1);
+    assert_eq!(dbg.line(), Some(5));
+
+    assert!(matches!(dbg.step_over(), StopReason::Step));
+    assert_eq!(dbg.line(), Some(6));
+    assert!(matches!(dbg.step_over(), StopReason::Step));
+    assert_eq!(dbg.line(), Some(7));
+    assert!(matches!(dbg.step_over(), StopReason::Step));
+    assert_eq!(dbg.line(), Some(8));
+    assert_eq!(dbg.vm().frame_depth(), 1);
+}
+
+#[test]
+fn runtime_errors_end_the_session() {
+    let mut dbg = debugger("let f = fn() { 1 + true };\nf();");
+    match dbg.resume() {
+        StopReason::Error(err) => assert_eq!(err.pos.line, 1),
+        other => panic!("expected error, got {other:?}"),
+    }
+    assert!(matches!(dbg.step_into(), StopReason::Error(_)));
+}
+
+#[test]
+fn command_session_reports_stops_and_program_output() {
+    let mut dbg = debugger(PROGRAM);
+    let input = "break 7\ncontinue\nglobals\nbt\nnext\nc\nbogus\nquit\nwhere\n";
+    let mut output = Vec::new();
+    assert_eq!(dbg.run_session(input.as_bytes(), &mut output), 0);
+    let output = String::from_utf8(output).expect("utf8 output");
+    assert_eq!(
+        output,
+        "Stopped in <repl> at line 1\n   1 | let add = fn(a, b) {\n\
+         (debug) Breakpoint set at line 7\n\
+         (debug) 3\nBreakpoint hit at line 7\nStopped in <repl> at line 7\n   7 | let y = add(x, 10);\n\
+         (debug)   add = <closure>\n  x = 3\n\
+         (debug)   at <repl>(0 args) @ 7:9\n\
+         (debug) Stopped in <repl> at line 8\n   8 | y\n\
+         (debug) Program finished: 13\n\
+         (debug) Unknown command: bogus (try 'help')\n\
+         (debug) "
+    );
+}
@@ -0,0 +1,59 @@
+use std::collections::HashSet;
+
+use monkey_rust_compiler::compiler::CompilerOptions;
+use monkey_rust_compiler::explain::{explain, EXPLANATIONS};
+use monkey_rust_compiler::runner::{
+    compile_source_with_warnings, compile_wasm, run_source, RunnerError,
+};
+
+/// The codes `source` reports, in the order a user would see them.
+fn reported_codes(code: &str, source: &str) -> Vec<&'static str> {
+    if code.starts_with('W') {
+        let (_, warnings) = compile_source_with_warnings(source, CompilerOptions::default())
+            .expect("example should compile");
+        return warnings.iter().map(|w| w.error_code()).collect();
+    }
+    let result = match code {
+        "C003" | "C004" => compile_wasm(source).map(|_| ()),
+        _ => run_source(source).map(|_| ()),
+    };
+    match result {
+        Ok(()) => Vec::new(),
+        Err(RunnerError::Parse(errors)) => errors.iter().map(|e| e.error_code()).collect(),
+        Err(RunnerError::Compile(errors)) => errors.iter().map(|e| e.error_code()).collect(),
+        Err(RunnerError::Runtime(err)) => vec![err.error_type.error_code()],
+    }
+}
+
+#[test]
+fn codes_are_unique_and_grouped_by_phase() {
+    let mut seen = HashSet::new();
+    for explanation in EXPLANATIONS {
+        assert!(seen.insert(explanation.code), "{}", explanation.code);
+        assert_eq!(explanation.code.len(), 4);
+        assert!(matches!(&explanation.code[..1], "P" | "C" | "R" | "W"));
+    }
+    assert_eq!(explain("w003").map(|e| e.code), Some("W003"));
+    assert_eq!(explain("R099"), None);
+}
+
+#[test]
+fn examples_report_the_code_they_explain() {
+    for explanation in EXPLANATIONS {
+        let Some(example) = explanation.example else {
+            continue;
+        };
+        assert_eq!(
+            reported_codes(explanation.code, example).first().copied(),
+            Some(explanation.code),
+            "{example}"
+        );
+    }
+}
+
+#[test]
+fn rendered_explanation_indents_the_example() {
+    let rendered = explain("W001").expect("known code").render();
+    assert!(rendered.starts_with("W001: unused variable\n\n"));
+    assert!(rendered.ends_with("\nExample:\n\n    let unused = 1;\n"));
+}
@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+
+use monkey_rust_compiler::format::{format_source, FormatOptions};
+use monkey_rust_compiler::runner::dump_ast;
+
+fn format(source: &str) -> String {
+    format_source(source, FormatOptions::default()).expect("parses")
+}
+
+#[test]
+fn normalizes_spacing_indentation_and_parentheses() {
+    assert_eq!(
+        format("let   add=fn(a,b){a+b};\nlet r=add( 1 , (2+3)*4 )-(5-6)-(7)-  -x;\nif(r>3){puts(\"big\")}else{\nputs(!f(x), (-a)[0], [1,2][0], {\"a\":1})\n}"),
+        "let add = fn(a, b) { a + b };\n\
+         let r = add(1, (2 + 3) * 4) - (5 - 6) - 7 - -x;\n\
+         if (r > 3) { puts(\"big\") } else {\n  \
+           puts(!f(x), (-a)[0], [1, 2][0], {\"a\": 1})\n\
+         }\n"
+    );
+    assert_eq!(
+        format("while(i<10){let i=i+1; if (i==5) {break;} }"),
+        "while (i < 10) {\n  let i = i + 1;\n  if (i == 5) { break; }\n}\n"
+    );
+    assert_eq!(
+        format("puts(a||b&&c, (a||b)&&c, a-(b-c), (a-b)-c)"),
+        "puts(a || b && c, (a || b) && c, a - (b - c), a - b - c)\n"
+    );
+    assert_eq!(format(""), "");
+    assert_eq!(format("   \n\n"), "");
+}
+
+#[test]
+fn keeps_comments_and_single_blank_lines() {
+    let source = "# header\n\n\n\nlet f = fn(x) { # opens\n  x # result\n  # closing\n};\n\nlet g = fn() {\n  # only comment\n};\n# a\n\n# b\nputs(1)  ;  # done\n";
+    assert_eq!(
+        format(source),
+        "# header\n\n\
+         let f = fn(x) {\n  \
+           # opens\n  \
+           x # result\n  \
+           # closing\n\
+         };\n\n\
+         let g = fn() {\n  \
+           # only comment\n\
+         };\n\
+         # a\n\n\
+         # b\n\
+         puts(1); # done\n"
+    );
+}
+
+#[test]
+fn keeps_statements_with_comments_inside_expressions_as_written() {
+    assert_eq!(
+        format("if (a) {\n        let h = add(1, # one\n           2);\n}"),
+        "if (a) {\n  let h = add(1, # one\n     2);\n}\n"
+    );
+}
+
+#[test]
+fn breaks_lists_that_do_not_fit() {
+    let options = FormatOptions::default().with_max_width(30);
+    assert_eq!(
+        format_source(
+            "let xs = [alpha, beta, gamma, delta];\nreduce(b, fn(acc, x) { push(acc, x) }, a);",
+            options
+        )
+        .expect("parses"),
+        "let xs = [\n  alpha,\n  beta,\n  gamma,\n  delta\n];\n\
+         reduce(b, fn(acc, x) {\n  push(acc, x)\n}, a);\n"
+    );
+    assert_eq!(
+        format_source(
+            "let f = fn() { [1, 2] };",
+            FormatOptions::default().with_indent_width(4)
+        )
+        .expect("parses"),
+        "let f = fn() { [1, 2] };\n"
+    );
+    assert_eq!(
+        format_source(
+            "let f = fn() { 1; 2 };",
+            FormatOptions::default().with_indent_width(4)
+        )
+        .expect("parses"),
+        "let f = fn() {\n    1;\n    2\n};\n"
+    );
+}
+
+#[test]
+fn reports_parse_errors() {
+    assert!(format_source("let = 1;", FormatOptions::default()).is_err());
+}
+
+#[test]
+fn formatting_the_bundled_programs_keeps_their_meaning_and_is_stable() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let mut checked = 0;
+    for dir in ["examples", "bench"] {
+        for entry in std::fs::read_dir(root.join(dir)).expect("read dir") {
+            let path = entry.expect("dir entry").path();
+            if path.extension().is_none_or(|ext| ext != "monkey") {
+                continue;
+            }
+            let source = std::fs::read_to_string(&path).expect("read program");
+            let ast = dump_ast(&source).expect("bundled programs parse");
+            for width in [100, 30] {
+                let options = FormatOptions::default().with_max_width(width);
+                let formatted = format_source(&source, options).expect("formats");
+                assert_eq!(
+                    dump_ast(&formatted).as_ref(),
+                    Ok(&ast),
+                    "{} at width {width}",
+                    path.display()
+                );
+                assert_eq!(
+                    format_source(&formatted, options).as_ref(),
+                    Ok(&formatted),
+                    "{} at width {width}",
+                    path.display()
+                );
+            }
+            checked += 1;
+        }
+    }
+    assert!(checked > 5);
+}
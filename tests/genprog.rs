@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use monkey_rust_compiler::compiler::CompilerOptions;
+use monkey_rust_compiler::format::{format_source, FormatOptions};
+use monkey_rust_compiler::genprog::{generate, GenOptions};
+use monkey_rust_compiler::runner::{compile_source_with_options, run_chunk_with, RunOptions};
+use monkey_rust_compiler::vm::VmOptions;
+
+const SEEDS: u64 = 100;
+
+fn mixes() -> Vec<GenOptions> {
+    let all = GenOptions::default();
+    vec![
+        all,
+        all.with_statements(30).with_max_depth(6),
+        all.with_functions(false).with_collections(false),
+        all.with_loops(false).with_strings(false),
+        all.with_max_depth(1),
+    ]
+}
+
+/// Result and output of `source`, failing the test on any error.
+fn run(source: &str, optimize: bool, superinstructions: bool) -> (String, Vec<String>) {
+    let chunk =
+        compile_source_with_options(source, CompilerOptions::default().with_optimize(optimize))
+            .unwrap_or_else(|err| panic!("{err:?} compiling:\n{source}"));
+    let options = RunOptions::default()
+        .with_vm(VmOptions::default().with_superinstructions(superinstructions))
+        .with_timeout(Duration::from_secs(10));
+    let outcome =
+        run_chunk_with(chunk, &options).unwrap_or_else(|err| panic!("{err:?} running:\n{source}"));
+    (outcome.result.inspect(), outcome.output)
+}
+
+#[test]
+fn generation_is_deterministic_in_the_seed() {
+    let options = GenOptions::default();
+    assert_eq!(generate(7, options), generate(7, options));
+    assert_ne!(generate(7, options), generate(8, options));
+}
+
+#[test]
+fn disabled_features_stay_out_of_programs() {
+    let options = GenOptions::default()
+        .with_functions(false)
+        .with_loops(false)
+        .with_collections(false)
+        .with_strings(false);
+    for seed in 0..SEEDS {
+        let program = generate(seed, options);
+        for absent in ["fn(", "while", "[", "{\"", "\""] {
+            assert!(!program.contains(absent), "{absent} in:\n{program}");
+        }
+    }
+}
+
+#[test]
+fn optimizer_and_superinstructions_agree_on_generated_programs() {
+    for options in mixes() {
+        for seed in 0..SEEDS {
+            let program = generate(seed, options);
+            let expected = run(&program, false, false);
+            assert_eq!(run(&program, true, false), expected, "{program}");
+            assert_eq!(run(&program, true, true), expected, "{program}");
+        }
+    }
+}
+
+#[test]
+fn formatting_generated_programs_keeps_their_behavior() {
+    for seed in 0..SEEDS {
+        let program = generate(seed, GenOptions::default());
+        let formatted = format_source(&program, FormatOptions::default())
+            .unwrap_or_else(|err| panic!("{err:?} formatting:\n{program}"));
+        assert_eq!(
+            run(&formatted, true, true),
+            run(&program, true, true),
+            "{program}"
+        );
+    }
+}
+
+#[cfg(feature = "register-vm")]
+#[test]
+fn register_vm_agrees_on_generated_programs() {
+    use monkey_rust_compiler::runner::{compile_source, run_chunk_register};
+
+    for seed in 0..SEEDS {
+        let program = generate(seed, GenOptions::default());
+        let chunk = compile_source(&program).expect("program should compile");
+        let outcome = run_chunk_register(chunk, &RunOptions::default())
+            .unwrap_or_else(|err| panic!("{err:?} running:\n{program}"));
+        assert_eq!(
+            (outcome.result.inspect(), outcome.output),
+            run(&program, false, true),
+            "{program}"
+        );
+    }
+}
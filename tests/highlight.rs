@@ -0,0 +1,68 @@
+use monkey_rust_compiler::highlight::{highlight, paint, paint_value, Style};
+use monkey_rust_compiler::object::Object;
+use monkey_rust_compiler::runner::run_source;
+
+fn strip_escapes(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\x1b' {
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+#[test]
+fn tokens_are_colored_by_kind() {
+    assert_eq!(
+        highlight("let s = \"hi\"; # note"),
+        format!(
+            "{} s = {}; {}",
+            paint("let", Style::Keyword),
+            paint("\"hi\"", Style::String),
+            paint("# note", Style::Comment)
+        )
+    );
+    assert_eq!(
+        highlight("if (true) { 10 } else { x }"),
+        format!(
+            "{} ({}) {{ {} }} {} {{ x }}",
+            paint("if", Style::Keyword),
+            paint("true", Style::Constant),
+            paint("10", Style::Number),
+            paint("else", Style::Keyword)
+        )
+    );
+}
+
+#[test]
+fn highlighting_keeps_the_source_text() {
+    for source in [
+        "let f = fn(a, b) {\n\treturn a + b; # sum\n};\nputs(f(1, 2));\n",
+        "\"multi\nline\" @ 1.5e3 while",
+        "",
+    ] {
+        assert_eq!(strip_escapes(&highlight(source)), source);
+    }
+}
+
+#[test]
+fn values_are_colored_inside_collections() {
+    let outcome = run_source("[1, \"a\", {true: if (false) { 1 }}];").expect("program should run");
+    let painted = paint_value(&outcome.result);
+    assert_eq!(
+        painted,
+        format!(
+            "[{}, {}, {{{}: {}}}]",
+            paint("1", Style::Number),
+            paint("a", Style::String),
+            paint("true", Style::Constant),
+            paint("null", Style::Constant)
+        )
+    );
+    assert_eq!(strip_escapes(&painted), outcome.result.inspect());
+    assert_eq!(paint_value(&Object::Null), paint("null", Style::Constant));
+}
@@ -0,0 +1,96 @@
+#![cfg(feature = "jit")]
+
+use monkey_rust_compiler::runner::compile_source;
+use monkey_rust_compiler::runtime_error::RuntimeError;
+use monkey_rust_compiler::vm::{Vm, VmOptions};
+
+/// Run with and without the JIT; returns the JIT run's outcome and the
+/// number of functions it compiled after checking both runs agree.
+fn run_both(source: &str) -> (Result<String, RuntimeError>, usize) {
+    let chunk = compile_source(source).expect("program should compile");
+    let mut interpreted = Vm::with_options(chunk.clone(), VmOptions::default().with_jit(false));
+    let expected = interpreted.run().map(|value| value.inspect());
+
+    let mut vm = Vm::new(chunk);
+    let actual = vm.run().map(|value| value.inspect());
+    match (&actual, &expected) {
+        (Ok(actual), Ok(expected)) => assert_eq!(actual, expected),
+        (Err(actual), Err(expected)) => {
+            assert_eq!(actual.format_multiline(), expected.format_multiline())
+        }
+        _ => panic!("JIT {actual:?} vs interpreter {expected:?}"),
+    }
+    assert_eq!(vm.take_output(), interpreted.take_output());
+    (actual, vm.jit_compiled_count())
+}
+
+#[test]
+fn hot_recursive_integer_function_is_compiled() {
+    let (result, compiled) =
+        run_both("let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(20);");
+    assert_eq!(result.expect("should run"), "6765");
+    assert_eq!(compiled, 1);
+}
+
+#[test]
+fn boolean_results_loops_and_locals_are_compiled() {
+    let (result, compiled) = run_both(
+        "let isEven = fn(n) { if (n == 0) { true } else { !isEven(n - 1) } };
+         let sum = fn(n) { let i = 0; let s = 0; while (i < n) { let s = s + i; let i = i + 1; }; s };
+         let i = 0; let acc = 0; let evens = 0;
+         while (i < 200) {
+           let acc = acc + sum(i);
+           if (isEven(i)) { let evens = evens + 1; }
+           let i = i + 1;
+         };
+         [acc, evens];",
+    );
+    assert_eq!(result.expect("should run"), "[1313400, 100]");
+    assert_eq!(compiled, 2);
+}
+
+#[test]
+fn functions_using_other_values_stay_interpreted() {
+    let (result, compiled) = run_both(
+        "let greet = fn(n) { \"hi\" };
+         let first2 = fn(a) { a[0] };
+         let i = 0;
+         while (i < 300) { greet(i); first2([i]); let i = i + 1; };
+         first2([7]);",
+    );
+    assert_eq!(result.expect("should run"), "7");
+    assert_eq!(compiled, 0);
+}
+
+#[test]
+fn non_integer_arguments_fall_back_to_the_interpreter() {
+    let (result, compiled) = run_both(
+        "let same = fn(x) { x == x };
+         let i = 0;
+         while (i < 300) { same(i); let i = i + 1; };
+         [same(1), same(true), same(\"s\")];",
+    );
+    assert_eq!(result.expect("should run"), "[true, true, true]");
+    assert_eq!(compiled, 1);
+}
+
+#[test]
+fn runtime_errors_in_compiled_code_match_the_interpreter() {
+    let (division, _) = run_both(
+        "let div = fn(a, b) { a / b };
+         let i = 1;
+         while (i < 300) { div(10, i); let i = i + 1; };
+         div(1, 0);",
+    );
+    assert!(division.is_err());
+
+    let (overflow, compiled) = run_both(
+        "let down = fn(n) { if (n == 0) { 0 } else { down(n - 1) } };
+         let i = 0;
+         while (i < 300) { down(3); let i = i + 1; };
+         down(20000);",
+    );
+    let err = overflow.expect_err("should overflow");
+    assert!(err.format_multiline().contains("STACK_OVERFLOW"));
+    assert_eq!(compiled, 1);
+}
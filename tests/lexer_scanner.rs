@@ -1,10 +1,16 @@
-use monkey_rust_compiler::lexer::Lexer;
+use std::io::{BufReader, Cursor};
+use std::path::PathBuf;
+
+use monkey_rust_compiler::lexer::{Lexer, LexerOptions};
+use monkey_rust_compiler::parser::Parser;
 use monkey_rust_compiler::position::Position;
-use monkey_rust_compiler::token::TokenKind;
+use monkey_rust_compiler::pretty::TokenFormat;
+use monkey_rust_compiler::runner::format_tokens_as;
+use monkey_rust_compiler::token::{lookup_ident, similar_keyword, TokenKind, KEYWORDS};
 
 fn collect(input: &str) -> Vec<(TokenKind, String, Position)> {
     Lexer::new(input)
-        .tokenize_all()
+        .tokenize_all(LexerOptions::default())
         .into_iter()
         .map(|t| (t.kind, t.literal, t.pos))
         .collect()
@@ -227,3 +233,197 @@ fn unterminated_string_emits_illegal_then_eof() {
         ]
     );
 }
+
+#[test]
+fn floats_are_single_tokens_with_their_start_position() {
+    let got = collect("1.5 2.0e3 4E-2\n  10e+1 7");
+    assert_eq!(
+        got,
+        vec![
+            (TokenKind::Float, "1.5".to_string(), Position::new(1, 1)),
+            (TokenKind::Float, "2.0e3".to_string(), Position::new(1, 5)),
+            (TokenKind::Float, "4E-2".to_string(), Position::new(1, 11)),
+            (TokenKind::Float, "10e+1".to_string(), Position::new(2, 3)),
+            (TokenKind::Int, "7".to_string(), Position::new(2, 9)),
+            (TokenKind::Eof, "".to_string(), Position::new(2, 10)),
+        ]
+    );
+}
+
+#[test]
+fn dots_and_letters_after_integers_stay_separate_tokens() {
+    let kinds = |input: &str| {
+        collect(input)
+            .into_iter()
+            .map(|(k, l, _)| (k, l))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        kinds("1.x 2e"),
+        vec![
+            (TokenKind::Int, "1".to_string()),
+            (TokenKind::Illegal, ".".to_string()),
+            (TokenKind::Ident, "x".to_string()),
+            (TokenKind::Int, "2".to_string()),
+            (TokenKind::Ident, "e".to_string()),
+            (TokenKind::Eof, "".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn numbers_with_two_decimal_points_are_one_illegal_token() {
+    let got = collect("v = 1.2.3;");
+    assert_eq!(
+        got[2],
+        (TokenKind::Illegal, "1.2.3".to_string(), Position::new(1, 5))
+    );
+    assert_eq!(
+        got[3],
+        (TokenKind::Semicolon, ";".to_string(), Position::new(1, 10))
+    );
+}
+
+#[test]
+fn comment_mode_keeps_comments_with_text_and_position() {
+    let input = "# header\nlet x = 1; # trailing\r\n  #\nx";
+    let got = Lexer::new(input)
+        .tokenize_all(LexerOptions::default().with_comments(true))
+        .into_iter()
+        .filter(|t| t.kind == TokenKind::Comment)
+        .map(|t| (t.literal, t.pos))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        got,
+        vec![
+            ("# header".to_string(), Position::new(1, 1)),
+            ("# trailing".to_string(), Position::new(2, 12)),
+            ("#".to_string(), Position::new(3, 3)),
+        ]
+    );
+
+    // Everything else is unchanged by the mode.
+    let without_comments = Lexer::new(input)
+        .tokenize_all(LexerOptions::default().with_comments(true))
+        .into_iter()
+        .filter(|t| t.kind != TokenKind::Comment)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        without_comments,
+        Lexer::new(input).tokenize_all(LexerOptions::default())
+    );
+}
+
+#[test]
+fn tokens_record_the_position_just_past_their_text() {
+    let input = "let ab = \"x\\ny\";\n  a >= 10";
+    let got = Lexer::new(input)
+        .tokenize_all(LexerOptions::default())
+        .into_iter()
+        .map(|t| (t.literal, t.pos, t.end))
+        .collect::<Vec<_>>();
+    let p = Position::new;
+    assert_eq!(
+        got,
+        vec![
+            ("let".to_string(), p(1, 1), p(1, 4)),
+            ("ab".to_string(), p(1, 5), p(1, 7)),
+            ("=".to_string(), p(1, 8), p(1, 9)),
+            // The end of a string is past its closing quote.
+            ("x\\ny".to_string(), p(1, 10), p(1, 16)),
+            (";".to_string(), p(1, 16), p(1, 17)),
+            ("a".to_string(), p(2, 3), p(2, 4)),
+            (">=".to_string(), p(2, 5), p(2, 7)),
+            ("10".to_string(), p(2, 8), p(2, 10)),
+            ("".to_string(), p(2, 10), p(2, 10)),
+        ]
+    );
+}
+
+#[test]
+fn json_token_format_has_one_object_per_line() {
+    assert_eq!(
+        format_tokens_as("x = \"a\tb\";", TokenFormat::Json),
+        r#"[
+  {"kind": "Ident", "literal": "x", "line": 1, "col": 1, "end_line": 1, "end_col": 2},
+  {"kind": "Assign", "literal": "=", "line": 1, "col": 3, "end_line": 1, "end_col": 4},
+  {"kind": "String", "literal": "a\tb", "line": 1, "col": 5, "end_line": 1, "end_col": 10},
+  {"kind": "Semicolon", "literal": ";", "line": 1, "col": 10, "end_line": 1, "end_col": 11},
+  {"kind": "Eof", "literal": "", "line": 1, "col": 11, "end_line": 1, "end_col": 11}
+]"#
+    );
+}
+
+#[test]
+fn reader_lexer_matches_string_lexer() {
+    let mut sources = vec![
+        "let s = \"héllo wörld\"; # ünïcode\nlet x = 1.5e3 + 2e-1;\n".to_string(),
+        // Long enough for consumed input to be discarded several times.
+        "let total = total + 12345; # keep going\n".repeat(1000),
+    ];
+    for dir in ["examples", "bench"] {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push(dir);
+        for entry in std::fs::read_dir(path).expect("read dir") {
+            let path = entry.expect("dir entry").path();
+            if path.extension().is_some_and(|ext| ext == "monkey") {
+                sources.push(std::fs::read_to_string(path).expect("read source"));
+            }
+        }
+    }
+    for source in sources {
+        for options in [
+            LexerOptions::default(),
+            LexerOptions::default().with_comments(true),
+        ] {
+            // One-byte reads split multi-byte characters between reads.
+            let reader = BufReader::with_capacity(1, Cursor::new(source.clone().into_bytes()));
+            let lexer = Lexer::from_reader(reader);
+            assert_eq!(
+                lexer.tokenize_all(options),
+                Lexer::new(source.as_str()).tokenize_all(options)
+            );
+        }
+    }
+}
+
+#[test]
+fn reader_lexer_stops_at_invalid_utf8() {
+    let mut bytes = b"let a = 1;\nlet b = ".to_vec();
+    bytes.extend([0xff, b'2', b';']);
+    let mut lexer = Lexer::from_reader(Cursor::new(bytes));
+    let mut kinds = Vec::new();
+    loop {
+        let token = lexer.next_token();
+        let is_eof = token.kind == TokenKind::Eof;
+        kinds.push(token.kind);
+        if is_eof {
+            break;
+        }
+    }
+    // `let b =` is the last token before the bad byte.
+    assert_eq!(kinds.len(), 9);
+    assert_eq!(kinds[7], TokenKind::Assign);
+    assert_eq!(
+        lexer.read_error().map(|err| err.kind()),
+        Some(std::io::ErrorKind::InvalidData)
+    );
+
+    let mut parser = Parser::new(Lexer::from_reader(Cursor::new(b"1 + 2;".to_vec())));
+    assert_eq!(parser.parse_program().to_string(), "(1 + 2);");
+    assert!(parser.lexer().read_error().is_none());
+}
+
+#[test]
+fn near_misses_of_keywords_are_recognized() {
+    assert_eq!(similar_keyword("retrun"), Some("return"));
+    assert_eq!(similar_keyword("fnn"), Some("fn"));
+    assert_eq!(similar_keyword("elze"), Some("else"));
+    assert_eq!(similar_keyword("contnue"), Some("continue"));
+    assert_eq!(similar_keyword("return"), None);
+    assert_eq!(similar_keyword("f"), None);
+    assert_eq!(similar_keyword("counter"), None);
+    for (keyword, kind) in KEYWORDS {
+        assert_eq!(lookup_ident(keyword), kind);
+    }
+}
@@ -0,0 +1,216 @@
+use monkey_rust_compiler::bytecode::opcode_set_hash;
+use monkey_rust_compiler::bytecode::Chunk;
+use monkey_rust_compiler::mkc::{header, is_mkc, MkcError, FORMAT_VERSION, HEADER_LEN, MAGIC};
+use monkey_rust_compiler::object::Object;
+use monkey_rust_compiler::runner::{compile_source, run_chunk_with, run_source, RunOptions};
+
+const PROGRAM: &str = r#"let greet = fn(name) { "hello " + name };
+let counter = fn() { let n = 0; fn(step) { n + step } };
+let add = counter();
+puts(greet("mkc"));
+[add(41), {"k": true}["k"], if (false) { 1 }];"#;
+
+fn compile(source: &str) -> Chunk {
+    compile_source(source).expect("program should compile")
+}
+
+#[test]
+fn serialization_roundtrips_code_constants_and_positions() {
+    let chunk = compile(PROGRAM);
+    let bytes = chunk.serialize().expect("serialize");
+    assert!(is_mkc(&bytes));
+
+    let decoded = Chunk::deserialize(&bytes).expect("deserialize");
+    assert_eq!(decoded.instructions, chunk.instructions);
+    assert_eq!(decoded.positions, chunk.positions);
+    assert_eq!(decoded.constants, chunk.constants);
+    assert!(decoded.constants.iter().any(
+        |c| matches!(c.as_ref(), Object::CompiledFunction(f) if f.name.as_deref() == Some("greet"))
+    ));
+    assert_eq!(decoded.serialize().expect("serialize again"), bytes);
+}
+
+#[test]
+fn deserialized_chunk_runs_like_the_source() {
+    let expected = run_source(PROGRAM).expect("source run");
+    let bytes = compile(PROGRAM).serialize().expect("serialize");
+    let chunk = Chunk::deserialize(&bytes).expect("deserialize");
+    let outcome = run_chunk_with(chunk, &RunOptions::default()).expect("chunk run");
+    assert_eq!(outcome.output, expected.output);
+    assert_eq!(outcome.result.inspect(), expected.result.inspect());
+    assert_eq!(outcome.result.inspect(), "[41, true, null]");
+}
+
+#[test]
+fn runtime_errors_keep_source_positions() {
+    let bytes = compile("let f = fn() {\n  1 + true\n};\nf();")
+        .serialize()
+        .expect("serialize");
+    let chunk = Chunk::deserialize(&bytes).expect("deserialize");
+    let err = match run_chunk_with(chunk, &RunOptions::default()) {
+        Err(monkey_rust_compiler::runner::RunnerError::Runtime(err)) => err,
+        other => panic!("expected runtime error, got {other:?}"),
+    };
+    assert_eq!(err.pos.line, 2);
+    assert_eq!(err.stack[0].function_name, "f");
+}
+
+#[test]
+fn malformed_files_are_rejected() {
+    assert_eq!(
+        Chunk::deserialize(b"let x = 1;").expect_err("bad magic"),
+        MkcError::BadMagic
+    );
+
+    let bytes = compile("\"text\";").serialize().expect("serialize");
+    let err = Chunk::deserialize(&bytes[..bytes.len() - 2]).expect_err("truncated");
+    assert!(matches!(err, MkcError::UnexpectedEof { .. }));
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(
+        Chunk::deserialize(&trailing).expect_err("trailing"),
+        MkcError::TrailingBytes {
+            offset: bytes.len()
+        }
+    );
+
+    let mut bad_tag = header();
+    bad_tag.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 9]);
+    assert_eq!(
+        Chunk::deserialize(&bad_tag).expect_err("bad tag"),
+        MkcError::UnknownConstantTag {
+            tag: 9,
+            offset: HEADER_LEN + 12
+        }
+    );
+}
+
+#[test]
+fn header_records_format_version_and_opcode_set() {
+    let bytes = compile("1;").serialize().expect("serialize");
+    assert_eq!(&bytes[..MAGIC.len()], MAGIC);
+    assert_eq!(&bytes[..HEADER_LEN], header());
+    assert_eq!(
+        bytes[MAGIC.len()..MAGIC.len() + 4],
+        FORMAT_VERSION.to_le_bytes()
+    );
+    assert_eq!(
+        bytes[MAGIC.len() + 4..HEADER_LEN],
+        opcode_set_hash().to_le_bytes()
+    );
+    assert_eq!(opcode_set_hash(), opcode_set_hash());
+}
+
+#[test]
+fn files_from_other_versions_or_opcode_sets_are_rejected() {
+    let bytes = compile("1;").serialize().expect("serialize");
+
+    let mut newer = bytes.clone();
+    newer[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+    let err = Chunk::deserialize(&newer).expect_err("newer version");
+    assert_eq!(
+        err,
+        MkcError::UnsupportedVersion {
+            found: FORMAT_VERSION + 1
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "unsupported .mkc format version {} (this build reads version {FORMAT_VERSION}); recompile the source",
+            FORMAT_VERSION + 1
+        )
+    );
+
+    let mut other_vm = bytes.clone();
+    other_vm[MAGIC.len() + 4..HEADER_LEN].copy_from_slice(&7u64.to_le_bytes());
+    let err = Chunk::deserialize(&other_vm).expect_err("other opcode set");
+    assert_eq!(
+        err,
+        MkcError::OpcodeSetMismatch {
+            found: 7,
+            expected: opcode_set_hash()
+        }
+    );
+    assert!(err
+        .to_string()
+        .starts_with("compiled for a different instruction set (opcode hash 0000000000000007,"));
+
+    // A header cut short is reported as such, not as a version mismatch.
+    assert!(matches!(
+        Chunk::deserialize(&bytes[..MAGIC.len() + 2]).expect_err("short header"),
+        MkcError::UnexpectedEof { .. }
+    ));
+}
+
+#[test]
+fn runtime_only_constants_cannot_be_serialized() {
+    let mut chunk = Chunk::new();
+    chunk.add_constant(Object::Array(Vec::new()).rc());
+    assert_eq!(
+        chunk.serialize().expect_err("array constant"),
+        MkcError::UnsupportedConstant("ARRAY".to_string())
+    );
+}
+
+#[test]
+fn implausible_local_counts_are_rejected() {
+    let bytes = compile("let id = fn(x) { x }; id(1);")
+        .serialize()
+        .expect("serialize");
+    // Tag, the name "id", one parameter, one local.
+    let function = [4, 1, 2, 0, 0, 0, b'i', b'd', 1, 0, 0, 0, 1, 0, 0, 0];
+    let offset = bytes
+        .windows(function.len())
+        .position(|window| window == function)
+        .expect("function constant");
+
+    let mut huge = bytes.clone();
+    huge[offset + 15] = 0x80;
+    assert_eq!(
+        Chunk::deserialize(&huge).expect_err("huge local count"),
+        MkcError::InvalidLocalCount {
+            num_params: 1,
+            num_locals: 0x8000_0001,
+            offset
+        }
+    );
+
+    let mut params = bytes.clone();
+    params[offset + 8] = 2;
+    assert_eq!(
+        Chunk::deserialize(&params).expect_err("more parameters than locals"),
+        MkcError::InvalidLocalCount {
+            num_params: 2,
+            num_locals: 1,
+            offset
+        }
+    );
+}
+
+#[test]
+fn truncated_and_mutated_files_never_decode_huge_functions() {
+    let bytes = compile(PROGRAM).serialize().expect("serialize");
+    for len in 0..bytes.len() {
+        assert!(
+            Chunk::deserialize(&bytes[..len]).is_err(),
+            "truncated to {len}"
+        );
+    }
+    for index in HEADER_LEN..bytes.len() {
+        for flip in [0x01, 0x80, 0xff] {
+            let mut mutated = bytes.clone();
+            mutated[index] ^= flip;
+            let Ok(chunk) = Chunk::deserialize(&mutated) else {
+                continue;
+            };
+            for constant in &chunk.constants {
+                if let Object::CompiledFunction(function) = constant.as_ref() {
+                    assert!(function.num_params <= function.num_locals);
+                    assert!(function.num_locals <= 256, "byte {index} ^ {flip:#x}");
+                }
+            }
+        }
+    }
+}
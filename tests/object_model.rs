@@ -1,39 +1,43 @@
-use std::rc::Rc;
-
 use monkey_rust_compiler::object::{
-    BuiltinObject, ClosureObject, CompiledFunctionObject, HashKey, Object,
+    BuiltinObject, ClosureObject, CompiledFunctionObject, HashKey, HashObject, Object, Shared,
+    StringInterner, MAX_INTERNED_LEN,
 };
-use monkey_rust_compiler::position::Position;
+use monkey_rust_compiler::position::{Position, Span};
 
-fn int(v: i64) -> Rc<Object> {
+fn int(v: i64) -> Shared<Object> {
     Object::Integer(v).rc()
 }
 
-fn str_obj(v: &str) -> Rc<Object> {
-    Object::String(v.to_string()).rc()
+fn str_obj(v: &str) -> Shared<Object> {
+    Object::String(v.into()).rc()
 }
 
 #[test]
 fn type_name_is_stable_for_all_supported_variants() {
-    let compiled = Rc::new(CompiledFunctionObject {
+    let compiled = Shared::new(CompiledFunctionObject {
         name: Some("adder".to_string()),
         num_params: 2,
         num_locals: 1,
-        instructions: vec![1, 2, 3],
-        positions: vec![(0, Position::new(1, 1))],
+        instructions: vec![1, 2, 3].into(),
+        positions: vec![(0, Span::at(Position::new(1, 1)))].into(),
     });
-    let closure = Rc::new(ClosureObject {
-        function: Rc::clone(&compiled),
+    let closure = Shared::new(ClosureObject {
+        function: Shared::clone(&compiled),
         free: vec![int(1)],
     });
 
     let cases = vec![
         (Object::Integer(1), "INTEGER"),
         (Object::Boolean(true), "BOOLEAN"),
-        (Object::String("x".to_string()), "STRING"),
+        (Object::String("x".into()), "STRING"),
         (Object::Null, "NULL"),
         (Object::Array(vec![int(1)]), "ARRAY"),
-        (Object::Hash(vec![(str_obj("a"), int(1))]), "HASH"),
+        (
+            Object::Hash(
+                HashObject::from_pairs(vec![(str_obj("a"), int(1))]).expect("hashable keys"),
+            ),
+            "HASH",
+        ),
         (Object::CompiledFunction(compiled), "FUNCTION"),
         (Object::Closure(closure), "CLOSURE"),
         (
@@ -56,9 +60,9 @@ fn truthiness_matches_monkey_rules() {
 
     assert!(Object::Boolean(true).is_truthy());
     assert!(Object::Integer(0).is_truthy());
-    assert!(Object::String("".to_string()).is_truthy());
+    assert!(Object::String("".into()).is_truthy());
     assert!(Object::Array(vec![]).is_truthy());
-    assert!(Object::Hash(vec![]).is_truthy());
+    assert!(Object::Hash(HashObject::new()).is_truthy());
 }
 
 #[test]
@@ -69,25 +73,25 @@ fn hash_key_is_only_defined_for_hashable_types() {
         Some(HashKey::Boolean(true))
     );
     assert_eq!(
-        Object::String("abc".to_string()).hash_key(),
-        Some(HashKey::String("abc".to_string()))
+        Object::String("abc".into()).hash_key(),
+        Some(HashKey::String("abc".into()))
     );
 
-    let compiled = Rc::new(CompiledFunctionObject {
+    let compiled = Shared::new(CompiledFunctionObject {
         name: None,
         num_params: 0,
         num_locals: 0,
-        instructions: vec![],
-        positions: vec![],
+        instructions: vec![].into(),
+        positions: vec![].into(),
     });
-    let closure = Rc::new(ClosureObject {
-        function: Rc::clone(&compiled),
+    let closure = Shared::new(ClosureObject {
+        function: Shared::clone(&compiled),
         free: vec![],
     });
 
     assert_eq!(Object::Null.hash_key(), None);
     assert_eq!(Object::Array(vec![int(1)]).hash_key(), None);
-    assert_eq!(Object::Hash(vec![]).hash_key(), None);
+    assert_eq!(Object::Hash(HashObject::new()).hash_key(), None);
     assert_eq!(Object::CompiledFunction(compiled).hash_key(), None);
     assert_eq!(Object::Closure(closure).hash_key(), None);
     assert_eq!(
@@ -101,27 +105,27 @@ fn hash_key_is_only_defined_for_hashable_types() {
 
 #[test]
 fn inspect_formatting_is_deterministic() {
-    let compiled_named = Object::CompiledFunction(Rc::new(CompiledFunctionObject {
+    let compiled_named = Object::CompiledFunction(Shared::new(CompiledFunctionObject {
         name: Some("sum".to_string()),
         num_params: 2,
         num_locals: 2,
-        instructions: vec![1, 2, 3],
-        positions: vec![(0, Position::new(1, 1))],
+        instructions: vec![1, 2, 3].into(),
+        positions: vec![(0, Span::at(Position::new(1, 1)))].into(),
     }));
-    let compiled_anon = Object::CompiledFunction(Rc::new(CompiledFunctionObject {
+    let compiled_anon = Object::CompiledFunction(Shared::new(CompiledFunctionObject {
         name: None,
         num_params: 0,
         num_locals: 0,
-        instructions: vec![],
-        positions: vec![],
+        instructions: vec![].into(),
+        positions: vec![].into(),
     }));
-    let closure = Object::Closure(Rc::new(ClosureObject {
-        function: Rc::new(CompiledFunctionObject {
+    let closure = Object::Closure(Shared::new(ClosureObject {
+        function: Shared::new(CompiledFunctionObject {
             name: Some("sum".to_string()),
             num_params: 2,
             num_locals: 2,
-            instructions: vec![1],
-            positions: vec![(0, Position::new(1, 1))],
+            instructions: vec![1].into(),
+            positions: vec![(0, Span::at(Position::new(1, 1)))].into(),
         }),
         free: vec![int(99)],
     }));
@@ -132,7 +136,7 @@ fn inspect_formatting_is_deterministic() {
     assert_eq!(Object::Integer(123).inspect(), "123");
     assert_eq!(Object::Boolean(true).inspect(), "true");
     assert_eq!(Object::Boolean(false).inspect(), "false");
-    assert_eq!(Object::String("abc".to_string()).inspect(), "abc");
+    assert_eq!(Object::String("abc".into()).inspect(), "abc");
     assert_eq!(Object::Null.inspect(), "null");
 
     assert_eq!(
@@ -140,7 +144,11 @@ fn inspect_formatting_is_deterministic() {
         "[1, true]"
     );
     assert_eq!(
-        Object::Hash(vec![(str_obj("a"), int(1)), (str_obj("b"), int(2))]).inspect(),
+        Object::Hash(
+            HashObject::from_pairs(vec![(str_obj("a"), int(1)), (str_obj("b"), int(2))])
+                .expect("hashable keys")
+        )
+        .inspect(),
         "{a: 1, b: 2}"
     );
 
@@ -152,11 +160,14 @@ fn inspect_formatting_is_deterministic() {
 
 #[test]
 fn hash_inspect_preserves_pair_order() {
-    let hash = Object::Hash(vec![
-        (str_obj("first"), int(1)),
-        (str_obj("second"), int(2)),
-        (str_obj("third"), int(3)),
-    ]);
+    let hash = Object::Hash(
+        HashObject::from_pairs(vec![
+            (str_obj("first"), int(1)),
+            (str_obj("second"), int(2)),
+            (str_obj("third"), int(3)),
+        ])
+        .expect("hashable keys"),
+    );
     assert_eq!(hash.inspect(), "{first: 1, second: 2, third: 3}");
 }
 
@@ -164,14 +175,93 @@ fn hash_inspect_preserves_pair_order() {
 fn object_ref_helpers_support_shared_ownership() {
     let shared = Object::Integer(42).rc();
 
-    let array = Object::Array(vec![Rc::clone(&shared), Rc::clone(&shared)]);
+    let array = Object::Array(vec![Shared::clone(&shared), Shared::clone(&shared)]);
     assert_eq!(array.inspect(), "[42, 42]");
 
-    let hash = Object::Hash(vec![(
-        Object::String("k".to_string()).rc(),
-        Rc::clone(&shared),
-    )]);
+    let hash = Object::Hash(
+        HashObject::from_pairs(vec![(
+            Object::String("k".into()).rc(),
+            Shared::clone(&shared),
+        )])
+        .expect("hashable keys"),
+    );
     assert_eq!(hash.inspect(), "{k: 42}");
 
     assert_eq!(*shared, Object::Integer(42));
 }
+
+#[test]
+fn hash_object_keeps_first_insertion_order_with_last_write_wins() {
+    let hash = HashObject::from_pairs(vec![
+        (str_obj("a"), int(1)),
+        (int(2), int(2)),
+        (str_obj("a"), int(3)),
+    ])
+    .expect("hashable keys");
+
+    assert_eq!(hash.len(), 2);
+    assert_eq!(
+        hash.get(&HashKey::String("a".into())).map(|v| v.inspect()),
+        Some("3".to_string())
+    );
+    assert_eq!(
+        hash.get(&HashKey::Integer(2)).map(|v| v.inspect()),
+        Some("2".to_string())
+    );
+    assert_eq!(hash.get(&HashKey::Boolean(true)), None);
+    assert_eq!(Object::Hash(hash).inspect(), "{a: 3, 2: 2}");
+}
+
+#[test]
+fn hash_object_rejects_unhashable_keys() {
+    let key = Object::Array(vec![]).rc();
+    let err = HashObject::from_pairs(vec![(Shared::clone(&key), int(1))])
+        .expect_err("array keys are unhashable");
+    assert!(Shared::ptr_eq(&err, &key));
+}
+
+#[test]
+fn string_interner_shares_one_allocation_per_text() {
+    let mut strings = StringInterner::new();
+    let a = strings.intern("monkey");
+    let b = strings.intern(&format!("mon{}", "key"));
+    let c = strings.intern("ape");
+    assert!(Shared::ptr_eq(&a, &b));
+    assert!(!Shared::ptr_eq(&a, &c));
+    assert_eq!(strings.len(), 2);
+}
+
+#[test]
+fn string_interner_drops_strings_nothing_else_holds() {
+    let mut strings = StringInterner::new();
+    let kept = strings.intern("kept");
+    for i in 0..100_000 {
+        strings.intern(&format!("temporary {i}"));
+    }
+    assert!(strings.len() <= 2048, "{} entries", strings.len());
+    assert!(Shared::ptr_eq(&kept, &strings.intern("kept")));
+}
+
+#[test]
+fn string_interner_leaves_long_texts_alone() {
+    let mut strings = StringInterner::new();
+    let long = "x".repeat(MAX_INTERNED_LEN + 1);
+    let a = strings.intern(&long);
+    let b = strings.intern(&long);
+    assert!(!Shared::ptr_eq(&a, &b));
+    assert!(strings.is_empty());
+}
+
+#[test]
+fn cloned_compiled_functions_share_code_buffers() {
+    let function = CompiledFunctionObject {
+        name: None,
+        num_params: 0,
+        num_locals: 0,
+        instructions: vec![1, 2, 3].into(),
+        positions: vec![(0, Span::at(Position::new(1, 1)))].into(),
+    };
+    let copy = function.clone();
+    assert!(Shared::ptr_eq(&function.instructions, &copy.instructions));
+    assert!(Shared::ptr_eq(&function.positions, &copy.positions));
+}
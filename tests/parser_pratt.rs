@@ -1,7 +1,8 @@
 use monkey_rust_compiler::ast::{Expression, Program, Statement};
 use monkey_rust_compiler::lexer::Lexer;
 use monkey_rust_compiler::parser::Parser;
-use monkey_rust_compiler::position::Position;
+use monkey_rust_compiler::position::{Position, Span};
+use monkey_rust_compiler::token::TokenKind;
 
 fn parse(input: &str) -> (Program, Vec<String>) {
     let lexer = Lexer::new(input);
@@ -314,6 +315,8 @@ fn parses_function_parameter_variants() {
         ("fn() {};", Vec::<&str>::new()),
         ("fn(x) {};", vec!["x"]),
         ("fn(x, y, z) {};", vec!["x", "y", "z"]),
+        ("fn(x,) {};", vec!["x"]),
+        ("fn(\n  x,\n  y,\n) {};", vec!["x", "y"]),
     ];
 
     for (input, expected_params) in cases {
@@ -337,6 +340,7 @@ fn parses_call_parameter_variants() {
         ("func()", Vec::<&str>::new()),
         ("func(3 - 1)", vec!["(3 - 1)"]),
         ("func(1, 2 * 3 + 1, 98)", vec!["1", "((2 * 3) + 1)", "98"]),
+        ("func(1, 2,)", vec!["1", "2"]),
     ];
 
     for (input, expected_args) in cases {
@@ -382,6 +386,10 @@ fn parses_arrays_hashes_and_index_expressions() {
         other => panic!("expected hash literal, got {other:?}"),
     }
 
+    let (trailing, trailing_errors) = parse("[1, 2, 3,]");
+    assert_no_errors("[1, 2, 3,]", &trailing_errors);
+    assert_eq!(trailing.to_string(), "[1, 2, 3];");
+
     let (idx_program, idx_errors) = parse("myArray[1 + 1]");
     assert_no_errors("myArray[1 + 1]", &idx_errors);
     assert_eq!(idx_program.to_string(), "(myArray[(1 + 1)]);");
@@ -394,6 +402,8 @@ fn parses_hash_literals_with_various_key_types_and_expression_pairs() {
         ("{}", 0usize),
         ("{1: 100}", 1usize),
         ("{true: \"assa\", false: \"false\"}", 2usize),
+        ("{1: 100,}", 1usize),
+        ("{\n  \"a\": 1,\n  \"b\": 2,\n}", 2usize),
     ];
 
     for (input, expected_len) in cases {
@@ -419,7 +429,18 @@ fn parses_hash_literals_with_various_key_types_and_expression_pairs() {
 
 #[test]
 fn accumulates_parse_errors_without_panicking() {
-    let cases = ["let = 5;", "if (x < ) { x }", "fn(,x) {}", "x = x + 1;"];
+    let cases = [
+        "let = 5;",
+        "if (x < ) { x }",
+        "fn(,x) {}",
+        "x = x + 1;",
+        "[,]",
+        "[1,,]",
+        "f(,)",
+        "{,}",
+        "{1: 2,,}",
+        "fn(x,,) {}",
+    ];
 
     for input in cases {
         let (program, errors) = parse(input);
@@ -438,6 +459,166 @@ fn accumulates_parse_errors_without_panicking() {
     }
 }
 
+#[test]
+fn resyncs_at_the_next_statement_and_notes_where() {
+    let source =
+        "let f = fn(x) {\n  let a 1\n  let b = { \"k\": x };\n  b\n};\nputs(f(1));\nlet y 2";
+    let mut parser = Parser::new(Lexer::new(source));
+    let program = parser.parse_program();
+    // The typos cost only their own lines.
+    assert_eq!(
+        program.to_string(),
+        "let f = fn(x) { let b = {\"k\": x}; b; };\nputs(f(1));"
+    );
+    let errors = parser.errors();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(
+        errors[0].to_string(),
+        "2:9: expected next token to be Assign, got Int"
+    );
+    let notes = errors
+        .iter()
+        .flat_map(|error| &error.notes)
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        notes,
+        [
+            "3:3: note: skipped to `let`, which starts the next statement",
+            "7:8: note: skipped to the end of the input"
+        ]
+    );
+
+    // Braces inside the skipped text do not end the enclosing block.
+    let (program, errors) = parse("fn() { let = { 1 } + 2; 3 }");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(program.to_string(), "fn() { 3; };");
+}
+
+#[test]
+fn errors_list_every_token_that_was_acceptable() {
+    let first_error = |source: &str| {
+        let mut parser = Parser::new(Lexer::new(source));
+        parser.parse_program();
+        parser.errors()[0].clone()
+    };
+
+    let error = first_error("f(1 2)");
+    // The message stays the single expected kind.
+    assert_eq!(
+        error.to_string(),
+        "1:5: expected next token to be RParen, got Int"
+    );
+    assert_eq!(error.expected[..2], [TokenKind::RParen, TokenKind::Comma]);
+    assert!(error.expected.contains(&TokenKind::Plus));
+    assert!(error.expected.contains(&TokenKind::LBracket));
+    assert_eq!(
+        error.render_with_source("f(1 2)"),
+        "1:5: expected next token to be RParen, got Int [P001]\n  1 | f(1 2)\n    |     ^\n\
+         help: expected one of `)`, `,`, an operator, `(` or `[`\n\
+         1:7: note: skipped to the end of the input\n  1 | f(1 2)\n    |       ^"
+    );
+
+    let error = first_error("let x = );");
+    assert_eq!(error.expected.len(), 12);
+    assert!(error.render_with_source("let x = );").contains(
+        "help: expected one of an identifier, an integer, a string, `true`, `false`, `!`, `-`, \
+         `(`, `[`, `{`, `if` or `fn`"
+    ));
+
+    assert_eq!(
+        first_error("if (x) {} else x").expected,
+        [TokenKind::LBrace, TokenKind::If]
+    );
+    assert_eq!(
+        first_error("fn(a b) {}").expected,
+        [TokenKind::RParen, TokenKind::Comma]
+    );
+    assert_eq!(
+        first_error("fn(1) {}").expected,
+        [TokenKind::Ident, TokenKind::RParen]
+    );
+    // A single expected kind adds nothing to the message.
+    let error = first_error("let 1;");
+    assert_eq!(error.expected, [TokenKind::Ident]);
+    assert!(!error.render_with_source("let 1;").contains("help:"));
+}
+
+#[test]
+fn reports_expressions_nested_too_deeply() {
+    for source in [
+        format!("let x = {}1{};", "(".repeat(10_000), ")".repeat(10_000)),
+        format!("{}1{}", "[".repeat(10_000), "]".repeat(10_000)),
+        format!("{}{}", "{1: ".repeat(10_000), "}".repeat(10_000)),
+        format!("{}1{}", "fn() { ".repeat(10_000), " }".repeat(10_000)),
+        format!("{}1", "-".repeat(10_000)),
+    ] {
+        let mut parser = Parser::new(Lexer::new(source.as_str()));
+        parser.parse_program();
+        let errors = parser.errors();
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert_eq!(errors[0].error_code(), "P007");
+        assert!(errors[0]
+            .to_string()
+            .ends_with("expression too deeply nested: more than 128 levels"));
+    }
+
+    let parse = |source: &str| {
+        let mut parser = Parser::new(Lexer::new(source)).with_max_depth(3);
+        let program = parser.parse_program();
+        (program.to_string(), parser.errors().to_vec())
+    };
+    let (program, errors) = parse("((1)); [[x]];");
+    assert!(errors.is_empty(), "{errors:?}");
+    assert_eq!(program, "1;\n[[x]];");
+    let (_, errors) = parse("(((1)));");
+    assert_eq!(
+        errors[0].to_string(),
+        "1:4: expression too deeply nested: more than 3 levels"
+    );
+}
+
+#[test]
+fn errors_after_a_misspelt_keyword_suggest_it() {
+    let notes = |source: &str| {
+        let mut parser = Parser::new(Lexer::new(source));
+        parser.parse_program();
+        parser
+            .errors()
+            .iter()
+            .flat_map(|error| &error.notes)
+            .map(ToString::to_string)
+            .filter(|note| note.contains("did you mean"))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(notes("lett x = 5;"), ["1:1: note: did you mean `let`?"]);
+    assert_eq!(
+        notes("let a = 1;\n  fnn(x) { x }"),
+        ["2:3: note: did you mean `fn`?"]
+    );
+    assert_eq!(
+        notes("whille (x) { y = 1; }"),
+        ["1:1: note: did you mean `while`?"]
+    );
+    // The next token has to fit the keyword, and the error has to start
+    // on the same line.
+    assert!(notes("lst = [1];").is_empty());
+    assert!(notes("lett\nx = 5;").is_empty());
+    assert!(notes("it = 1;").is_empty());
+}
+
+#[test]
+fn reports_float_and_malformed_number_literals() {
+    let (_program, errors) = parse("let a = 2.5e3;\nlet b = 1.2.3;\nlet c = 1;");
+    assert_eq!(
+        errors,
+        [
+            "1:9: float literals are not supported: 2.5e3",
+            "2:9: malformed number literal 1.2.3"
+        ]
+    );
+}
+
 #[test]
 fn reports_no_prefix_parse_error_for_unexpected_rparen() {
     let (_program, errors) = parse(")");
@@ -448,3 +629,59 @@ fn reports_no_prefix_parse_error_for_unexpected_rparen() {
         errors[0]
     );
 }
+
+#[test]
+fn records_the_extent_of_every_expression() {
+    let input = "puts(a + 2 * (b - \"cd\"));\nif (x) {\n  1\n}";
+    let (program, errors) = parse(input);
+    assert_no_errors(input, &errors);
+    let span = |line, col| program.spans[&Position::new(line, col)];
+    let p = Position::new;
+
+    // Keyed by each expression's `pos`: the operator of an infix
+    // expression, the `(` of a call.
+    assert_eq!(span(1, 8), Span::new(p(1, 8), p(1, 6), p(1, 24)));
+    assert_eq!(span(1, 12), Span::new(p(1, 12), p(1, 10), p(1, 24)));
+    // Parentheses belong to the grouped expression.
+    assert_eq!(span(1, 17), Span::new(p(1, 17), p(1, 14), p(1, 24)));
+    assert_eq!(span(1, 19), Span::new(p(1, 19), p(1, 19), p(1, 23)));
+    assert_eq!(span(1, 5), Span::new(p(1, 5), p(1, 1), p(1, 25)));
+    assert_eq!(span(2, 1), Span::new(p(2, 1), p(2, 1), p(4, 2)));
+}
+
+#[test]
+fn records_the_extent_of_every_statement() {
+    let input = "let x = 1 + 2;\nreturn x\nwhile (x) {\n  break;\n}\nx;";
+    let (program, errors) = parse(input);
+    assert_no_errors(input, &errors);
+    let p = Position::new;
+    let spans = program
+        .statements
+        .iter()
+        .map(|statement| program.statement_span(statement))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        spans,
+        vec![
+            // Including the `;` when there is one.
+            Span::new(p(1, 1), p(1, 1), p(1, 15)),
+            Span::new(p(2, 1), p(2, 1), p(2, 9)),
+            Span::new(p(3, 1), p(3, 1), p(5, 2)),
+            Span::new(p(6, 1), p(6, 1), p(6, 3)),
+        ]
+    );
+    let Statement::Expression { expression, .. } = &program.statements[3] else {
+        panic!("expected an expression statement");
+    };
+    assert_eq!(
+        program.expression_span(expression),
+        Span::new(p(6, 1), p(6, 1), p(6, 2))
+    );
+
+    // Hand-built programs fall back to an empty span at the position.
+    let bare = Program::new(program.statements.clone());
+    assert_eq!(
+        bare.statement_span(&program.statements[0]),
+        Span::at(p(1, 1))
+    );
+}
@@ -0,0 +1,150 @@
+use monkey_rust_compiler::bytecode::{make, Chunk, Opcode};
+use monkey_rust_compiler::compiler::Compiler;
+use monkey_rust_compiler::lexer::Lexer;
+use monkey_rust_compiler::object::Object;
+use monkey_rust_compiler::parser::Parser;
+use monkey_rust_compiler::peephole::fuse_superinstructions;
+use monkey_rust_compiler::vm::{Vm, VmOptions};
+
+fn compile_input(input: &str) -> Chunk {
+    let mut parser = Parser::new(Lexer::new(input));
+    let program = parser.parse_program();
+    assert!(parser.errors().is_empty(), "parse errors for {input}");
+    let mut compiler = Compiler::new();
+    compiler
+        .compile_program(&program)
+        .expect("compile should succeed");
+    compiler.into_bytecode()
+}
+
+fn opcode_names(chunk: &Chunk) -> Vec<String> {
+    chunk
+        .disassemble()
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .map(|line| {
+            line.split_whitespace()
+                .nth(1)
+                .unwrap_or_default()
+                .to_string()
+        })
+        .collect()
+}
+
+fn run(input: &str, superinstructions: bool) -> String {
+    let options = VmOptions::default().with_superinstructions(superinstructions);
+    let mut vm = Vm::with_options(compile_input(input), options);
+    match vm.run() {
+        Ok(value) => value.inspect(),
+        Err(err) => err.format_multiline(),
+    }
+}
+
+#[test]
+fn fuses_constant_constant_add_with_nop_padding() {
+    let mut chunk = compile_input("\"a\" + \"b\";");
+    let len = chunk.instructions.len();
+    fuse_superinstructions(&mut chunk);
+    assert_eq!(chunk.instructions.len(), len);
+    assert_eq!(
+        opcode_names(&chunk),
+        ["AddConstants", "Nop", "Nop", "ReturnValue"]
+    );
+    assert!(chunk.disassemble().starts_with("0000 AddConstants 0 1"));
+}
+
+#[test]
+fn fuses_inside_function_constants() {
+    let mut chunk = compile_input("let f = fn(a, b) { a + b }; f(1, 2);");
+    fuse_superinstructions(&mut chunk);
+    let function = chunk
+        .constants
+        .iter()
+        .find_map(|c| match c.as_ref() {
+            Object::CompiledFunction(f) => Some(f.clone()),
+            _ => None,
+        })
+        .expect("function constant");
+    assert_eq!(function.instructions[0], Opcode::AddLocals.to_byte());
+    assert_eq!(&function.instructions[1..3], &[0, 1]);
+    assert_eq!(function.instructions[3], Opcode::Nop.to_byte());
+    assert_eq!(function.instructions[4], Opcode::Nop.to_byte());
+}
+
+#[test]
+fn fuses_conditional_jump_and_pop() {
+    let mut chunk = compile_input("let c = true; if (c) { 1 } else { 2 };");
+    fuse_superinstructions(&mut chunk);
+    let names = opcode_names(&chunk);
+    assert!(names.contains(&"JumpIfFalsePop".to_string()), "{names:?}");
+    assert!(!names.contains(&"JumpIfFalse".to_string()), "{names:?}");
+}
+
+fn constant_add_then_jump_to(target: usize) -> Chunk {
+    let mut chunk = Chunk::new();
+    chunk.push_bytes(&make(Opcode::Constant, &[0]).expect("constant"));
+    chunk.push_bytes(&make(Opcode::Constant, &[1]).expect("constant"));
+    chunk.push_bytes(&make(Opcode::Add, &[]).expect("add"));
+    chunk.push_bytes(&make(Opcode::Jump, &[target]).expect("jump"));
+    chunk
+}
+
+#[test]
+fn does_not_fuse_across_jump_targets() {
+    let mut into_middle = constant_add_then_jump_to(3);
+    let before = into_middle.instructions.clone();
+    fuse_superinstructions(&mut into_middle);
+    assert_eq!(into_middle.instructions, before);
+
+    let mut to_start = constant_add_then_jump_to(0);
+    fuse_superinstructions(&mut to_start);
+    assert_eq!(to_start.instructions[0], Opcode::AddConstants.to_byte());
+}
+
+#[test]
+fn conditional_pop_jumps_are_jump_targets_too() {
+    // JumpIfFalsePop to the second constant of `Constant; Constant; Add`.
+    let mut chunk = Chunk::new();
+    chunk.push_bytes(&make(Opcode::JumpIfFalsePop, &[6]).expect("jump"));
+    chunk.push_bytes(&make(Opcode::Constant, &[0]).expect("constant"));
+    chunk.push_bytes(&make(Opcode::Constant, &[1]).expect("constant"));
+    chunk.push_bytes(&make(Opcode::Add, &[]).expect("add"));
+    let before = chunk.instructions.clone();
+    fuse_superinstructions(&mut chunk);
+    assert_eq!(chunk.instructions, before);
+}
+
+#[test]
+fn nop_does_nothing() {
+    let mut chunk = Chunk::new();
+    let five = chunk.add_constant(Object::Integer(5).rc());
+    chunk.push_bytes(&make(Opcode::Nop, &[]).expect("nop"));
+    chunk.push_bytes(&make(Opcode::Constant, &[five]).expect("constant"));
+    chunk.push_bytes(&make(Opcode::Nop, &[]).expect("nop"));
+    chunk.push_bytes(&make(Opcode::ReturnValue, &[]).expect("return"));
+    let mut vm = Vm::new(chunk);
+    let result = vm.run().expect("should run");
+    assert_eq!(result.as_ref(), &Object::Integer(5));
+}
+
+#[test]
+fn fused_and_unfused_runs_agree() {
+    let programs = [
+        "1 + 2;",
+        r#""a" + "b";"#,
+        "let f = fn(a, b) { a + b }; f(3, 4);",
+        "let f = fn(a, b) { a + b }; f(1, true);",
+        "1 + true;",
+        "let i = 0; let s = 0; while (i < 10) { let s = s + i; let i = i + 1; } s;",
+        "if (1 < 2) { 10 } else { 20 };",
+        "if (false) { 10 };",
+        "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15);",
+    ];
+    for program in programs {
+        assert_eq!(
+            run(program, true),
+            run(program, false),
+            "program: {program}"
+        );
+    }
+}
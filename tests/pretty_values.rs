@@ -0,0 +1,49 @@
+use monkey_rust_compiler::pretty::{format_value_pretty, ValueLayout};
+use monkey_rust_compiler::runner::run_source;
+
+fn pretty(source: &str, layout: ValueLayout) -> String {
+    let outcome = run_source(source).expect("program should run");
+    format_value_pretty(&outcome.result, layout)
+}
+
+#[test]
+fn values_that_fit_render_like_inspect() {
+    let source = r#"[1, "two", {"k": [true, 3]}, fn() { 1 }]"#;
+    let outcome = run_source(source).expect("program should run");
+    assert_eq!(
+        format_value_pretty(&outcome.result, ValueLayout::default()),
+        outcome.result.inspect()
+    );
+    assert_eq!(pretty("[]", ValueLayout::default()), "[]");
+    assert_eq!(pretty("{}", ValueLayout::default()), "{}");
+}
+
+#[test]
+fn wide_collections_break_one_item_per_line() {
+    let layout = ValueLayout::default().with_max_width(20);
+    assert_eq!(
+        pretty(
+            r#"{"name": "monkey", "tags": [1, 2, 3], "deep": [[10, 20, 30, 40], 5]}"#,
+            layout
+        ),
+        "{\n  name: monkey,\n  tags: [1, 2, 3],\n  deep: [\n    [10, 20, 30, 40],\n    5\n  ]\n}"
+    );
+    assert_eq!(
+        pretty("[1, 2, 3]", layout.with_max_width(5).with_indent_width(4)),
+        "[\n    1,\n    2,\n    3\n]"
+    );
+}
+
+#[test]
+fn long_collections_are_truncated() {
+    let layout = ValueLayout::default().with_max_items(Some(3));
+    assert_eq!(
+        pretty("[1, 2, 3, 4, 5, [6, 7, 8, 9]]", layout),
+        "[1, 2, 3, … (3 more)]"
+    );
+    assert_eq!(pretty("[[6, 7, 8, 9]]", layout), "[[6, 7, 8, … (1 more)]]");
+    assert_eq!(
+        pretty("[1, 2, 3, 4]", layout.with_max_items(None)),
+        "[1, 2, 3, 4]"
+    );
+}
@@ -0,0 +1,149 @@
+#![cfg(feature = "register-vm")]
+
+use std::sync::{Arc, Mutex};
+
+use monkey_rust_compiler::regvm::RegisterVm;
+use monkey_rust_compiler::runner::{
+    compile_source, run_chunk_register, run_source, RunOptions, RunnerError,
+};
+use monkey_rust_compiler::vm::{OutputSink, VmOptions};
+
+/// Run `source` on both backends and check the register VM handled it and
+/// agreed with the stack VM.
+fn assert_same(source: &str) {
+    let chunk = compile_source(source).expect("program should compile");
+    let mut vm = RegisterVm::new(&chunk).expect("program should translate");
+    let result = vm.run().expect("register VM should finish");
+    let expected = run_source(source).expect("stack VM should finish");
+    assert_eq!(result.inspect(), expected.result.inspect(), "{source}");
+    assert_eq!(vm.take_output(), expected.output, "{source}");
+}
+
+#[test]
+fn arithmetic_comparison_and_globals() {
+    assert_same("let a = 40000; let b = 2; (a + b) * 3 - a / b;");
+    assert_same("let x = 5; x < 10 == true;");
+    assert_same("!(1 > 2) == !false;");
+    assert_same("-5 + 3;");
+    assert_same("\"mon\" + \"key\" == \"monkey\";");
+    assert_same("if (false) { 1 } == if (false) { 2 };");
+}
+
+#[test]
+fn conditionals_and_short_circuit() {
+    assert_same("if (1 < 2) { 10 } else { 20 };");
+    assert_same("if (false) { 10 };");
+    assert_same("let t = true; t && 1 > 2 || t;");
+    assert_same("let x = 1; x + if (x > 0) { let x = 5; x } else { 0 } + x;");
+}
+
+#[test]
+fn loops_with_break_and_continue() {
+    assert_same(
+        "let i = 0; let s = 0;
+         while (i < 10) {
+           let i = i + 1;
+           if (i == 3) { continue; }
+           if (i == 8) { break; }
+           let s = s + i;
+         };
+         s;",
+    );
+}
+
+#[test]
+fn functions_closures_and_recursion() {
+    assert_same("let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15);");
+    assert_same(
+        "let adder = fn(a) { fn(b) { a + b } }; let add2 = adder(2); add2(3) + adder(10)(1);",
+    );
+    assert_same(
+        "let count = fn(n) { let i = 0; let acc = 0;
+           while (i < n) { let acc = acc + i; let i = i + 1; }; acc };
+         count(100);",
+    );
+    assert_same("let f = fn(a, b) { let a = b; let b = a + 1; a * b }; f(1, 2);");
+}
+
+#[test]
+fn collections_and_builtins() {
+    assert_same("let a = [1, 2, 3]; len(push(a, 4)) + a[1] + first(rest(a));");
+    assert_same("let h = {\"a\": 1, 2: \"b\", true: 3}; [h[\"a\"], h[2], h[true], h[\"x\"]];");
+    assert_same("puts(\"hello\", 1); puts([1, 2]); 5;");
+    assert_same("[1, 2][5];");
+}
+
+#[test]
+fn errors_and_eval_fall_back_to_the_stack_vm() {
+    for source in [
+        "1 / 0;",
+        "1 + true;",
+        "eval(\"1 + 2\");",
+        "break;",
+        "puts(\"x\"); 5();",
+    ] {
+        let chunk = compile_source(source).expect("program should compile");
+        let mut vm = RegisterVm::new(&chunk).expect("program should translate");
+        assert!(vm.run().is_none(), "{source}");
+
+        let register = run_chunk_register(chunk, &RunOptions::default());
+        match (register, run_source(source)) {
+            (Ok(register), Ok(stack)) => {
+                assert_eq!(register.result.inspect(), stack.result.inspect());
+                assert_eq!(register.output, stack.output);
+            }
+            (Err(RunnerError::Runtime(register)), Err(RunnerError::Runtime(stack))) => {
+                assert_eq!(register.format_multiline(), stack.format_multiline());
+            }
+            other => panic!("backends disagree for {source}: {other:?}"),
+        }
+    }
+}
+
+#[test]
+fn deep_recursion_falls_back_to_report_stack_overflow() {
+    let source = "let f = fn(n) { f(n + 1) }; f(0);";
+    let chunk = compile_source(source).expect("program should compile");
+    match run_chunk_register(chunk, &RunOptions::default()) {
+        Err(RunnerError::Runtime(err)) => {
+            assert!(err.format_multiline().contains("STACK_OVERFLOW"))
+        }
+        other => panic!("expected a stack overflow, got {other:?}"),
+    }
+}
+
+#[test]
+fn run_options_apply_to_register_runs() {
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let sink = {
+        let lines = Arc::clone(&lines);
+        OutputSink::new(move |line| lines.lock().unwrap().push(line.to_string()))
+    };
+    let options = RunOptions::default()
+        .with_args(vec!["x".to_string()])
+        .with_output(sink);
+    let chunk = compile_source("puts(first(args())); 2;").expect("program should compile");
+    let outcome = run_chunk_register(chunk, &options).expect("should run");
+    assert_eq!(outcome.result.inspect(), "2");
+    assert!(outcome.output.is_empty());
+    assert_eq!(*lines.lock().unwrap(), vec!["x"]);
+
+    // Limits the register VM does not enforce go to the stack VM.
+    let chunk = compile_source("while (true) {}").expect("program should compile");
+    match run_chunk_register(chunk, &RunOptions::default().with_fuel(1000)) {
+        Err(RunnerError::Runtime(err)) => {
+            assert!(err.format_multiline().contains("BUDGET_EXCEEDED"))
+        }
+        other => panic!("expected the budget to run out, got {other:?}"),
+    }
+
+    let source = "let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; f(50);";
+    let chunk = compile_source(source).expect("program should compile");
+    let options = RunOptions::default().with_vm(VmOptions::default().with_max_call_depth(20));
+    match run_chunk_register(chunk, &options) {
+        Err(RunnerError::Runtime(err)) => {
+            assert!(err.format_multiline().contains("STACK_OVERFLOW"))
+        }
+        other => panic!("expected a stack overflow, got {other:?}"),
+    }
+}
@@ -1,4 +1,7 @@
-use monkey_rust_compiler::repl::{ReplEvalResult, ReplSession};
+use monkey_rust_compiler::format::FormatOptions;
+use monkey_rust_compiler::object::Object;
+use monkey_rust_compiler::repl::{LineReader, ReplEvalResult, ReplSession};
+use monkey_rust_compiler::runner::run_source;
 
 #[test]
 fn state_persists_across_lines() {
@@ -88,17 +91,12 @@ fn repl_multiline_buffering_and_meta_gating() {
         other => panic!("expected buffered empty, got {other:?}"),
     }
 
-    match repl.eval_line(":help") {
-        ReplEvalResult::Empty => {}
-        other => panic!("expected buffered empty for meta text, got {other:?}"),
-    }
-
     match repl.eval_line("a + b") {
         ReplEvalResult::Empty => {}
         other => panic!("expected buffered empty, got {other:?}"),
     }
 
-    match repl.eval_line("};") {
+    match repl.eval_line(":help") {
         ReplEvalResult::ParseErrors(errors) => assert!(!errors.is_empty()),
         other => panic!("expected parse error from buffered :help line, got {other:?}"),
     }
@@ -138,3 +136,587 @@ fn meta_commands_work() {
         other => panic!("expected exit request, got {other:?}"),
     }
 }
+
+#[test]
+fn ast_layout_indents_the_ast_command_output() {
+    let mut repl = ReplSession::new().with_ast_layout(FormatOptions::default());
+    match repl.eval_line(":ast if (x) { 1 + 2 }") {
+        ReplEvalResult::MetaOutput(text) => {
+            assert_eq!(text, "AST:\n  if (x) {\n    (1 + 2);\n  };")
+        }
+        other => panic!("expected meta output, got {other:?}"),
+    }
+
+    let mut repl = ReplSession::new();
+    match repl.eval_line(":ast if (x) { 1 + 2 }") {
+        ReplEvalResult::MetaOutput(text) => assert_eq!(text, "AST:\n  if (x) { (1 + 2); };"),
+        other => panic!("expected meta output, got {other:?}"),
+    }
+}
+
+#[test]
+fn compile_errors_suggest_names_from_earlier_lines() {
+    let mut session = ReplSession::new();
+    session.eval_line("let counter = 1;");
+    match session.eval_line("countr + 1;") {
+        ReplEvalResult::CompileErrors(errors) => assert_eq!(
+            errors[0].message,
+            "unresolved identifier: countr (did you mean counter?)"
+        ),
+        other => panic!("expected compile errors, got {other:?}"),
+    }
+}
+
+fn value(repl: &mut ReplSession, line: &str) -> String {
+    match repl.eval_line(line) {
+        ReplEvalResult::Value { result, .. } => result.inspect(),
+        other => panic!("expected a value for {line}, got {other:?}"),
+    }
+}
+
+#[test]
+fn failed_inputs_leave_no_definitions_behind() {
+    let mut repl = ReplSession::new();
+    value(&mut repl, "let x = 5;");
+
+    assert!(matches!(
+        repl.eval_line("let y = 1; nope;"),
+        ReplEvalResult::CompileErrors(_)
+    ));
+    assert!(matches!(
+        repl.eval_line("y;"),
+        ReplEvalResult::CompileErrors(_)
+    ));
+
+    match repl.eval_line("let x = 7; let z = 2;\nputs(z); z / 0;") {
+        ReplEvalResult::RuntimeError(err) => assert_eq!(err.pos.line, 2),
+        other => panic!("expected a runtime error, got {other:?}"),
+    }
+    assert_eq!(value(&mut repl, "x;"), "5");
+    assert!(matches!(
+        repl.eval_line("z;"),
+        ReplEvalResult::CompileErrors(_)
+    ));
+    assert_eq!(value(&mut repl, "let z = \"again\";"), "again");
+    match repl.eval_line(":env") {
+        ReplEvalResult::MetaOutput(text) => assert_eq!(text, "ENV:\n  x = 5\n  z = again"),
+        other => panic!("expected meta output, got {other:?}"),
+    }
+}
+
+#[test]
+fn each_input_is_compiled_on_its_own() {
+    let mut repl = ReplSession::new();
+    value(&mut repl, "let n = 0;");
+    for _ in 0..50 {
+        value(&mut repl, "let n = n + 1;");
+    }
+    assert_eq!(value(&mut repl, "n;"), "50");
+    // Positions are relative to the input, not to the whole session.
+    match repl.eval_line("n / 0;") {
+        ReplEvalResult::RuntimeError(err) => assert_eq!(
+            err.format_single_line(),
+            "Error[DIVISION_BY_ZERO] at 1:3: division by zero"
+        ),
+        other => panic!("expected a runtime error, got {other:?}"),
+    }
+}
+
+#[test]
+fn cost_of_an_input_does_not_grow_with_the_session() {
+    let mut repl = ReplSession::new();
+    let executed = |repl: &mut ReplSession| match repl.eval_line(":time puts(1); 2") {
+        ReplEvalResult::MetaOutput(text) => text
+            .lines()
+            .last()
+            .unwrap()
+            .split(", ")
+            .nth(1)
+            .unwrap()
+            .to_string(),
+        other => panic!("expected meta output, got {other:?}"),
+    };
+    let first = executed(&mut repl);
+    for i in 0..20 {
+        value(&mut repl, &format!("let v{i} = puts({i});"));
+    }
+    assert_eq!(executed(&mut repl), first);
+}
+
+#[test]
+fn history_file_persists_successful_inputs_across_sessions() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("monkey_repl_history_{}", std::process::id()));
+    std::fs::write(&path, "let old = 1;\n").expect("write history file");
+
+    let mut repl = ReplSession::new().with_history_file(&path);
+    assert_eq!(repl.history(), ["let old = 1;"]);
+    value(&mut repl, "let a = 2;");
+    value(&mut repl, "let a = 2;");
+    assert!(matches!(
+        repl.eval_line("missing;"),
+        ReplEvalResult::CompileErrors(_)
+    ));
+    repl.eval_line("if (a) {");
+    value(&mut repl, "a }");
+
+    let repl = ReplSession::new().with_history_file(&path);
+    let saved = std::fs::read_to_string(&path).expect("read history file");
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(saved, "let old = 1;\nlet a = 2;\nif (a) {\na }\n");
+    assert_eq!(
+        repl.history(),
+        ["let old = 1;", "let a = 2;", "if (a) {", "a }"]
+    );
+}
+
+#[test]
+fn load_runs_a_file_in_the_session() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("monkey_repl_load_{}.monkey", std::process::id()));
+    std::fs::write(
+        &path,
+        "let square = fn(x) { x * x };\nlet base = 7;\nputs(\"loaded\");\n",
+    )
+    .expect("write script");
+
+    let mut repl = ReplSession::new();
+    value(&mut repl, "let offset = 1;");
+    let loaded = repl.eval_line(&format!(":load {}", path.display()));
+    let _ = std::fs::remove_file(&path);
+    match loaded {
+        ReplEvalResult::Value { output, .. } => assert_eq!(output, ["loaded"]),
+        other => panic!("expected a value, got {other:?}"),
+    }
+    assert_eq!(value(&mut repl, "square(base) + offset;"), "50");
+    match repl.eval_line(":env") {
+        ReplEvalResult::MetaOutput(text) => {
+            assert_eq!(text, "ENV:\n  base = 7\n  offset = 1\n  square = <closure>")
+        }
+        other => panic!("expected meta output, got {other:?}"),
+    }
+
+    match repl.eval_line(&format!(":load {}", path.display())) {
+        ReplEvalResult::MetaOutput(text) => {
+            assert!(text.starts_with(&format!("Failed to read {}: ", path.display())))
+        }
+        other => panic!("expected meta output, got {other:?}"),
+    }
+}
+
+#[test]
+fn save_writes_successful_inputs_as_a_script() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("monkey_repl_save_{}.monkey", std::process::id()));
+
+    let mut repl = ReplSession::new();
+    value(&mut repl, "let n = 1;");
+    value(&mut repl, "let n = n + 1;");
+    value(&mut repl, "let n = n + 1;");
+    repl.eval_line("nope;");
+    repl.eval_line("let double = fn(x) {");
+    value(&mut repl, "  x * 2 }");
+    value(&mut repl, "puts(double(n)) # show");
+    value(&mut repl, "(n)");
+    match repl.eval_line(&format!(":save {}", path.display())) {
+        ReplEvalResult::MetaOutput(text) => {
+            assert_eq!(text, format!("Saved 6 inputs to {}", path.display()))
+        }
+        other => panic!("expected meta output, got {other:?}"),
+    }
+
+    let script = std::fs::read_to_string(&path).expect("read saved script");
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(
+        script,
+        "let n = 1;\nlet n = n + 1;\nlet n = n + 1;\nlet double = fn(x) {\n  x * 2 };\n\
+         puts(double(n)) # show\n;\n(n);\n"
+    );
+    let outcome = run_source(&script).expect("saved script should run");
+    assert_eq!(outcome.output, ["6"]);
+    assert_eq!(outcome.result.inspect(), "3");
+}
+
+#[test]
+fn reset_forgets_history_and_definitions() {
+    let mut repl = ReplSession::new();
+    value(&mut repl, "let a = 1;");
+    repl.eval_line("let f = fn() {");
+    match repl.eval_line(":reset") {
+        ReplEvalResult::MetaOutput(text) => assert_eq!(text, "Session reset"),
+        other => panic!("expected meta output, got {other:?}"),
+    }
+    assert!(repl.history().is_empty());
+    assert!(matches!(
+        repl.eval_line("a;"),
+        ReplEvalResult::CompileErrors(_)
+    ));
+    value(&mut repl, "let a = 2;");
+    assert_eq!(repl.history(), ["let a = 2;"]);
+}
+
+#[test]
+fn time_runs_the_input_and_reports_its_cost() {
+    let mut repl = ReplSession::new();
+    value(&mut repl, "let double = fn(x) { x * 2 };");
+    match repl.eval_line(":time let n = double(21); puts(n); n") {
+        ReplEvalResult::MetaOutput(text) => {
+            let lines: Vec<&str> = text.lines().collect();
+            assert_eq!(lines[..2], ["42", "42"]);
+            assert!(lines[2].starts_with("Time: "), "{text}");
+            assert!(lines[2].ends_with(" instructions"), "{text}");
+            assert!(!lines[2].contains(" 0 instructions"), "{text}");
+        }
+        other => panic!("expected meta output, got {other:?}"),
+    }
+    assert_eq!(value(&mut repl, "n + 1;"), "43");
+    assert!(matches!(
+        repl.eval_line(":time missing;"),
+        ReplEvalResult::CompileErrors(_)
+    ));
+}
+
+#[test]
+fn disasm_compiles_in_the_session_without_running() {
+    let mut repl = ReplSession::new();
+    value(&mut repl, "let double = fn(x) { x * 2 };");
+    match repl.eval_line(":disasm let y = double(\"ab\");") {
+        ReplEvalResult::MetaOutput(text) => {
+            assert!(text.starts_with("DISASM:\n  0000 GetGlobal"), "{text}");
+            assert!(text.contains("Call 1"), "{text}");
+            assert!(text.contains("STRING \"ab\""), "{text}");
+            assert!(text.contains("double arity=1"), "{text}");
+        }
+        other => panic!("expected meta output, got {other:?}"),
+    }
+    assert!(matches!(
+        repl.eval_line("y;"),
+        ReplEvalResult::CompileErrors(_)
+    ));
+    assert_eq!(value(&mut repl, "[\"cd\", double(3)];"), "[cd, 6]");
+    match repl.eval_line(":disasm") {
+        ReplEvalResult::MetaOutput(text) => assert!(text.contains("Array 2"), "{text}"),
+        other => panic!("expected meta output, got {other:?}"),
+    }
+}
+
+#[test]
+fn type_reports_the_object_type_of_an_expression() {
+    let mut repl = ReplSession::new();
+    value(&mut repl, "let xs = [1, 2];");
+    value(&mut repl, "let inc = fn(x) { x + 1 };");
+    for (input, expected) in [
+        ("1", "TYPE: INTEGER"),
+        ("\"a\"", "TYPE: STRING"),
+        ("len", "TYPE: BUILTIN"),
+        ("fn() { 1 }", "TYPE: CLOSURE"),
+        ("xs", "TYPE: ARRAY"),
+        ("first(xs) == 1", "TYPE: BOOLEAN"),
+        ("{1: xs}", "TYPE: HASH"),
+        ("inc", "TYPE: CLOSURE"),
+        ("xs[5]", "TYPE: NULL"),
+        ("puts(inc(1))", "2\nTYPE: NULL"),
+        ("let y = 1;", "Usage: :type <expression>"),
+    ] {
+        match repl.eval_line(&format!(":type {input}")) {
+            ReplEvalResult::MetaOutput(text) => assert_eq!(text, expected, "{input}"),
+            other => panic!("expected meta output for {input}, got {other:?}"),
+        }
+    }
+    assert!(matches!(
+        repl.eval_line(":type xs + 1"),
+        ReplEvalResult::RuntimeError(_)
+    ));
+}
+
+#[test]
+fn paste_mode_runs_lines_together_at_end() {
+    let mut repl = ReplSession::new();
+    assert!(matches!(
+        repl.eval_line(":paste"),
+        ReplEvalResult::MetaOutput(_)
+    ));
+    for line in [
+        "let open = \"{\";",
+        "",
+        "let wrap = fn(s) {",
+        "  open + s + \"}\"",
+        "};",
+        "puts(wrap(\"x\"));",
+    ] {
+        assert!(
+            matches!(repl.eval_line(line), ReplEvalResult::Empty),
+            "{line}"
+        );
+    }
+    match repl.eval_line(":end") {
+        ReplEvalResult::Value { result, output } => {
+            assert_eq!(output, ["{x}"]);
+            assert_eq!(result.inspect(), "null");
+        }
+        other => panic!("expected a value, got {other:?}"),
+    }
+    assert_eq!(value(&mut repl, "wrap(open)"), "{{}");
+    assert!(!repl.history().iter().any(|line| line.is_empty()));
+
+    repl.eval_line(":paste");
+    repl.eval_line("let lost = 1;");
+    repl.eval_line(":reset");
+    assert!(matches!(
+        repl.eval_line("lost;"),
+        ReplEvalResult::CompileErrors(_)
+    ));
+}
+
+#[test]
+fn doc_prints_builtin_documentation() {
+    let mut repl = ReplSession::new();
+    for (input, expected) in [
+        (
+            ":doc push",
+            "push(arr, value) -> ARRAY\n  New ARRAY with value appended; arr itself is unchanged.",
+        ),
+        (":doc nope", "No documentation for nope"),
+        (
+            ":doc",
+            "Builtins: len, first, last, rest, push, puts, eval, args, substr, char_at, chars, eq, assert",
+        ),
+    ] {
+        match repl.eval_line(input) {
+            ReplEvalResult::MetaOutput(text) => assert_eq!(text, expected),
+            other => panic!("expected meta output for {input}, got {other:?}"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ScriptedReader {
+    lines: Vec<&'static str>,
+    prompts: usize,
+    history: Vec<String>,
+}
+
+impl LineReader for ScriptedReader {
+    fn read_line(&mut self, _prompt: &str) -> std::io::Result<Option<String>> {
+        self.prompts += 1;
+        if self.lines.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(self.lines.remove(0).to_string()))
+    }
+
+    fn add_history(&mut self, line: &str) {
+        self.history.push(line.to_string());
+    }
+}
+
+#[test]
+fn run_reads_lines_from_a_line_reader() {
+    let mut repl = ReplSession::new();
+    let mut reader = ScriptedReader {
+        lines: vec!["let x = 4;", "", "nope;", "let y = x * 2;"],
+        ..ScriptedReader::default()
+    };
+    assert_eq!(repl.run(&mut reader), 0);
+    assert_eq!(reader.prompts, 5);
+    assert_eq!(reader.history, ["let x = 4;", "nope;", "let y = x * 2;"]);
+    assert_eq!(value(&mut repl, "y;"), "8");
+
+    let mut reader = ScriptedReader {
+        lines: vec![":quit", "let z = 1;"],
+        ..ScriptedReader::default()
+    };
+    assert_eq!(repl.run(&mut reader), 0);
+    assert_eq!(reader.lines, ["let z = 1;"]);
+}
+
+#[test]
+fn pretty_configures_the_result_layout() {
+    let mut repl = ReplSession::new();
+    for (input, expected) in [
+        (":pretty", "Pretty results: off"),
+        (":pretty on", "Pretty results: width 80, items 100"),
+        (":pretty width 40", "Pretty results: width 40, items 100"),
+        (":pretty items all", "Pretty results: width 40, items all"),
+        (":pretty items 5", "Pretty results: width 40, items 5"),
+        (
+            ":pretty items",
+            "Usage: :pretty [on|off|width <n>|items <n|all>]",
+        ),
+        (":pretty off", "Pretty results: off"),
+        (":pretty on", "Pretty results: width 80, items 100"),
+    ] {
+        match repl.eval_line(input) {
+            ReplEvalResult::MetaOutput(text) => assert_eq!(text, expected, "{input}"),
+            other => panic!("expected meta output for {input}, got {other:?}"),
+        }
+    }
+}
+
+#[test]
+fn inputs_continue_only_while_they_could_still_parse() {
+    for input in [
+        "let f = fn(x) {",
+        "1 +",
+        "push([1],",
+        "{\"a\": [1, 2",
+        "if (true) { 1 } else",
+        "let s = \"two",
+        "puts(1 # comment",
+    ] {
+        let mut repl = ReplSession::new();
+        assert!(
+            matches!(repl.eval_line(input), ReplEvalResult::Empty),
+            "{input}"
+        );
+    }
+    for input in ["let s = \"{\";", "fn(x) { x + }", "1 )", "let a = 1 # {"] {
+        let mut repl = ReplSession::new();
+        assert!(
+            !matches!(repl.eval_line(input), ReplEvalResult::Empty),
+            "{input}"
+        );
+    }
+
+    let mut repl = ReplSession::new();
+    assert!(matches!(
+        repl.eval_line("let f = fn(x) {"),
+        ReplEvalResult::Empty
+    ));
+    assert!(matches!(
+        repl.eval_line("  let = 1;"),
+        ReplEvalResult::ParseErrors(_)
+    ));
+    assert_eq!(value(&mut repl, "1 + 2"), "3");
+}
+
+#[test]
+fn history_lists_entries_and_recalls_them() {
+    let mut repl = ReplSession::new();
+    match repl.eval_line(":history") {
+        ReplEvalResult::MetaOutput(text) => assert_eq!(text, "HISTORY:\n  (empty)"),
+        other => panic!("expected meta output, got {other:?}"),
+    }
+    value(&mut repl, "let n = 1;");
+    value(&mut repl, "let n = n * 10;");
+    value(&mut repl, "n;");
+    match repl.eval_line(":history") {
+        ReplEvalResult::MetaOutput(text) => assert_eq!(
+            text,
+            "HISTORY:\n  1  let n = 1;\n  2  let n = n * 10;\n  3  n;"
+        ),
+        other => panic!("expected meta output, got {other:?}"),
+    }
+    assert_eq!(value(&mut repl, "!2"), "100");
+    assert_eq!(value(&mut repl, "!!"), "1000");
+    assert_eq!(value(&mut repl, "!1"), "1");
+    assert_eq!(repl.history().len(), 5);
+    match repl.eval_line(":history 2") {
+        ReplEvalResult::MetaOutput(text) => {
+            assert_eq!(text, "HISTORY:\n  4  let n = n * 10;\n  5  let n = 1;")
+        }
+        other => panic!("expected meta output, got {other:?}"),
+    }
+    match repl.eval_line("!9") {
+        ReplEvalResult::MetaOutput(text) => assert_eq!(text, "No history entry !9"),
+        other => panic!("expected meta output, got {other:?}"),
+    }
+    assert_eq!(value(&mut repl, "!n"), "false");
+}
+
+#[test]
+fn rc_file_runs_at_startup_without_touching_history() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("monkey_repl_rc_{}.monkey", std::process::id()));
+    std::fs::write(
+        &path,
+        "# helpers\nlet double = fn(x) {\n  x * 2\n};\n:pretty on\n\nlet broken = ;\nlet half = fn(x) { x / 0 }; half(1);\nlet answer = double(21);\nlet open = fn() {\n",
+    )
+    .expect("write rc file");
+
+    let mut repl = ReplSession::new();
+    let failures = repl.run_rc_file(&path).expect("rc file should be read");
+    let _ = std::fs::remove_file(&path);
+    let shown = path.display().to_string();
+    assert_eq!(failures.len(), 3, "{failures:?}");
+    assert!(failures[0].starts_with(&format!("Error in {shown} at line 7:\n")));
+    assert!(failures[1].starts_with(&format!("Error in {shown} at line 8:\n")));
+    assert!(failures[1].contains("DIVISION_BY_ZERO"), "{}", failures[1]);
+    assert_eq!(
+        failures[2],
+        format!("Error in {shown} at line 10: unfinished input at end of file")
+    );
+
+    assert!(repl.history().is_empty());
+    assert_eq!(value(&mut repl, "answer + double(1)"), "44");
+    match repl.eval_line(":pretty") {
+        ReplEvalResult::MetaOutput(text) => assert_eq!(text, "Pretty results: width 80, items 100"),
+        other => panic!("expected meta output, got {other:?}"),
+    }
+    assert!(repl.run_rc_file(&path).is_err());
+}
+
+#[test]
+fn define_seeds_bindings_from_the_host() {
+    let mut repl = ReplSession::new().with_binding("limit", Object::Integer(3).rc());
+    repl.define_all([
+        (
+            "names",
+            Object::Array(vec![
+                Object::String("ann".into()).rc(),
+                Object::String("bo".into()).rc(),
+            ])
+            .rc(),
+        ),
+        ("greeting", Object::String("hi ".into()).rc()),
+    ]);
+    assert_eq!(value(&mut repl, "greeting + names[1]"), "hi bo");
+    assert_eq!(value(&mut repl, "let twice = limit * 2;"), "6");
+
+    repl.define("limit", Object::Integer(10).rc());
+    assert_eq!(value(&mut repl, "limit + twice"), "16");
+    assert!(matches!(
+        repl.eval_line("let limit = 0; limit / 0;"),
+        ReplEvalResult::RuntimeError(_)
+    ));
+    assert_eq!(value(&mut repl, "limit;"), "10");
+    match repl.eval_line(":env") {
+        ReplEvalResult::MetaOutput(text) => assert_eq!(
+            text,
+            "ENV:\n  greeting = hi \n  limit = 10\n  names = [ann, bo]\n  twice = 6"
+        ),
+        other => panic!("expected meta output, got {other:?}"),
+    }
+}
+
+#[test]
+fn transcript_records_lines_and_exports_them() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("monkey_repl_export_{}.golden", std::process::id()));
+
+    let mut repl = ReplSession::new();
+    repl.eval_line("let a = 2;");
+    repl.eval_line("");
+    repl.eval_line("puts(a);");
+    repl.eval_line(":reset");
+    repl.eval_line("a;");
+    let inputs: Vec<&str> = repl.transcript().iter().map(|e| e.input.as_str()).collect();
+    assert_eq!(inputs, ["let a = 2;", "puts(a);", ":reset", "a;"]);
+    assert_eq!(repl.transcript()[1].output, "PUTS:\n2\nRESULT: null");
+
+    match repl.eval_line(&format!(":export {}", path.display())) {
+        ReplEvalResult::MetaOutput(text) => {
+            assert_eq!(text, format!("Exported 4 entries to {}", path.display()))
+        }
+        other => panic!("expected meta output, got {other:?}"),
+    }
+    let exported = std::fs::read_to_string(&path).expect("read exported transcript");
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(
+        exported,
+        "INPUT: let a = 2;\nOUTPUT:\nRESULT: 2\n\n\
+         INPUT: puts(a);\nOUTPUT:\nPUTS:\n2\nRESULT: null\n\n\
+         INPUT: :reset\nOUTPUT:\nMETA:\nSession reset\n\n\
+         INPUT: a;\nOUTPUT:\nCOMPILE_ERROR:\n1:1: unresolved identifier: a\n"
+    );
+}
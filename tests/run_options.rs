@@ -0,0 +1,106 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use monkey_rust_compiler::object::{Object, Shared};
+use monkey_rust_compiler::runner::{run_source_with, RunOptions, Runner, RunnerError};
+use monkey_rust_compiler::runtime_error::RuntimeErrorType;
+use monkey_rust_compiler::vm::OutputSink;
+
+fn runtime_error(source: &str, options: &RunOptions) -> RuntimeErrorType {
+    match run_source_with(source, options) {
+        Err(RunnerError::Runtime(err)) => err.error_type,
+        other => panic!("expected runtime error, got {other:?}"),
+    }
+}
+
+#[test]
+fn default_options_match_run_source() {
+    let outcome = run_source_with("puts(args()); 1 + 2;", &RunOptions::default())
+        .expect("run should succeed");
+    assert_eq!(outcome.result.inspect(), "3");
+    assert_eq!(outcome.output, vec!["[]".to_string()]);
+}
+
+#[test]
+fn args_are_passed_to_the_program() {
+    let options = RunOptions::default().with_args(vec!["a".into(), "b".into()]);
+    let outcome = run_source_with("len(args());", &options).expect("run should succeed");
+    assert_eq!(outcome.result.inspect(), "2");
+}
+
+#[test]
+fn fuel_bounds_the_instructions_executed() {
+    let options = RunOptions::default().with_fuel(1_000);
+    assert_eq!(
+        runtime_error("while (true) { 1; }", &options),
+        RuntimeErrorType::BudgetExceeded
+    );
+    assert!(run_source_with("1 + 2;", &options).is_ok());
+}
+
+#[test]
+fn memory_limit_stops_growing_programs() {
+    let options = RunOptions::default().with_max_memory_bytes(64 * 1024);
+    let source = "let grow = fn(xs, n) { if (n == 0) { xs } else { grow(push(xs, \"item\"), n - 1) } }; grow([], 5000);";
+    assert_eq!(
+        runtime_error(source, &options),
+        RuntimeErrorType::OutOfMemory
+    );
+}
+
+#[test]
+fn timeout_stops_long_runs() {
+    let options = RunOptions::default().with_timeout(Duration::from_millis(50));
+    assert_eq!(
+        runtime_error("while (true) { 1; }", &options),
+        RuntimeErrorType::Timeout
+    );
+}
+
+#[test]
+fn preset_globals_are_visible_to_the_program() {
+    let options = RunOptions::default()
+        .with_global("base", Shared::new(Object::Integer(40)))
+        .with_global("name", Shared::new(Object::String("monkey".into())));
+    let outcome = run_source_with("let answer = base + 2; [name, answer];", &options)
+        .expect("run should succeed");
+    assert_eq!(outcome.result.inspect(), "[monkey, 42]");
+}
+
+#[test]
+fn a_repeated_preset_global_takes_the_last_value() {
+    let options = RunOptions::default()
+        .with_global("a", Shared::new(Object::Integer(1)))
+        .with_global("a", Shared::new(Object::Integer(2)))
+        .with_global("b", Shared::new(Object::Integer(3)));
+    let source = "let c = 4; [a, b, c];";
+    let outcome = run_source_with(source, &options).expect("run should succeed");
+    assert_eq!(outcome.result.inspect(), "[2, 3, 4]");
+
+    let mut runner = Runner::with_options(options);
+    let outcome = runner.eval(source).expect("eval should succeed");
+    assert_eq!(outcome.result.inspect(), "[2, 3, 4]");
+}
+
+#[test]
+fn output_sink_receives_printed_lines() {
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let sink = {
+        let lines = Arc::clone(&lines);
+        OutputSink::new(move |line| lines.lock().unwrap().push(line.to_string()))
+    };
+    let options = RunOptions::default().with_output(sink);
+    let outcome =
+        run_source_with("puts(1); puts(\"two\"); 3;", &options).expect("run should succeed");
+    assert!(outcome.output.is_empty());
+    assert_eq!(*lines.lock().unwrap(), vec!["1", "two"]);
+}
+
+#[test]
+fn optimize_keeps_results() {
+    let source = "let x = 2 * 3 + 4; if (true) { x } else { 0 };";
+    let plain = run_source_with(source, &RunOptions::default()).expect("run should succeed");
+    let optimized = run_source_with(source, &RunOptions::default().with_optimize(true))
+        .expect("run should succeed");
+    assert_eq!(optimized.result, plain.result);
+}
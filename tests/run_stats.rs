@@ -0,0 +1,83 @@
+use monkey_rust_compiler::runner::{run_source, RunStats, Runner};
+
+fn stats(source: &str) -> RunStats {
+    run_source(source)
+        .unwrap_or_else(|err| panic!("{err:?} running {source}"))
+        .stats
+        .expect("stack VM runs have stats")
+}
+
+const COUNTDOWN: &str = "let down = fn(n) { if (n == 0) { 0 } else { down(n - 1) } };";
+
+#[test]
+fn frame_depth_follows_recursion() {
+    assert_eq!(stats("1 + 2;").peak_frame_depth, 1);
+    assert_eq!(stats(&format!("{COUNTDOWN} down(0);")).peak_frame_depth, 2);
+    assert_eq!(stats(&format!("{COUNTDOWN} down(9);")).peak_frame_depth, 11);
+}
+
+#[test]
+fn stack_depth_counts_operands_and_locals() {
+    assert_eq!(stats("1;").peak_stack_depth, 1);
+    assert_eq!(stats("[1, 2, 3];").peak_stack_depth, 3);
+    let shallow = stats(&format!("{COUNTDOWN} down(1);")).peak_stack_depth;
+    let deep = stats(&format!("{COUNTDOWN} down(11);")).peak_stack_depth;
+    assert!(deep > shallow, "{deep} <= {shallow}");
+}
+
+#[test]
+fn allocations_count_created_collections_and_strings() {
+    assert_eq!(stats("1 + 2;").allocations, 0);
+    let run = stats("let a = [1, 2]; let b = push(a, 3); \"x\" + \"y\";");
+    assert_eq!(run.allocations, 3);
+    assert!(run.allocated_bytes > 0);
+}
+
+#[test]
+fn eval_counts_toward_the_caller() {
+    let direct = stats(&format!("{COUNTDOWN} down(5);"));
+    let nested = stats(&format!(r#"eval("{COUNTDOWN} down(5);");"#));
+    assert!(nested.peak_frame_depth > direct.peak_frame_depth);
+    assert!(nested.instructions > direct.instructions);
+    assert_eq!(stats(r#"eval("push([1], 2);");"#).allocations, 2);
+}
+
+#[test]
+fn runner_stats_cover_each_eval_alone() {
+    let mut runner = Runner::new();
+    let first = runner
+        .eval(&format!("{COUNTDOWN} down(20); [1];"))
+        .expect("eval should succeed")
+        .stats
+        .expect("stats");
+    let second = runner
+        .eval("down(2);")
+        .expect("eval should succeed")
+        .stats
+        .expect("stats");
+    assert_eq!(first.peak_frame_depth, 22);
+    assert_eq!(first.allocations, 1);
+    assert_eq!(second.peak_frame_depth, 4);
+    assert_eq!(second.allocations, 0);
+    assert!(second.instructions < first.instructions);
+}
+
+#[test]
+fn stats_render_one_line_each() {
+    let rendered = stats("[1];").to_string();
+    let labels: Vec<&str> = rendered
+        .lines()
+        .map(|line| line.split(':').next().unwrap_or_default())
+        .collect();
+    assert_eq!(
+        labels,
+        [
+            "instructions",
+            "peak stack depth",
+            "peak frame depth",
+            "allocations",
+            "elapsed"
+        ]
+    );
+    assert!(rendered.contains("allocations: 1 ("), "{rendered}");
+}
@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use monkey_rust_compiler::object::{Object, Shared};
+use monkey_rust_compiler::runner::{RunOptions, Runner, RunnerError};
+use monkey_rust_compiler::runtime_error::RuntimeErrorType;
+
+fn eval(runner: &mut Runner, source: &str) -> String {
+    runner
+        .eval(source)
+        .unwrap_or_else(|err| panic!("{err:?} evaluating {source}"))
+        .result
+        .inspect()
+}
+
+#[test]
+fn bindings_persist_between_evals() {
+    let mut runner = Runner::new();
+    eval(&mut runner, "let add = fn(a, b) { a + b };");
+    eval(&mut runner, "let x = add(1, 2);");
+    assert_eq!(eval(&mut runner, "add(x, 10);"), "13");
+    assert_eq!(runner.global("x").map(|x| x.inspect()), Some("3".into()));
+    assert!(runner.global("missing").is_none());
+}
+
+#[test]
+fn failed_evals_leave_no_definitions_behind() {
+    let mut runner = Runner::new();
+    eval(&mut runner, "let x = 1;");
+    match runner.eval("let y = 2; 1 / 0;") {
+        Err(RunnerError::Runtime(err)) => {
+            assert_eq!(err.error_type, RuntimeErrorType::DivisionByZero)
+        }
+        other => panic!("expected runtime error, got {other:?}"),
+    }
+    assert!(matches!(runner.eval("let = ;"), Err(RunnerError::Parse(_))));
+    assert!(matches!(runner.eval("z;"), Err(RunnerError::Compile(_))));
+    assert!(runner.global("y").is_none());
+    assert!(matches!(runner.eval("y;"), Err(RunnerError::Compile(_))));
+    assert_eq!(eval(&mut runner, "x;"), "1");
+}
+
+#[test]
+fn outcomes_report_each_evals_own_output_and_instructions() {
+    let mut runner = Runner::new();
+    let first = runner
+        .eval("puts(1); let x = 2;")
+        .expect("eval should succeed");
+    assert_eq!(first.output, vec!["1".to_string()]);
+    let second = runner.eval("x;").expect("eval should succeed");
+    assert!(second.output.is_empty());
+    let instructions = |outcome: &monkey_rust_compiler::runner::RunOutcome| {
+        outcome.stats.expect("stack VM counts").instructions
+    };
+    assert!(instructions(&second) < instructions(&first));
+}
+
+#[test]
+fn preview_compiles_without_defining() {
+    let mut runner = Runner::new();
+    let chunk = runner
+        .preview("let x = 1;")
+        .expect("preview should compile");
+    assert!(!chunk.instructions.is_empty());
+    assert!(runner.compiler().global_index("x").is_none());
+    assert_eq!(eval(&mut runner, "let x = 5; x;"), "5");
+}
+
+#[test]
+fn options_apply_to_every_eval_and_survive_reset() {
+    let options = RunOptions::default()
+        .with_args(vec!["a".into()])
+        .with_global("base", Shared::new(Object::Integer(40)))
+        .with_timeout(Duration::from_millis(50));
+    let mut runner = Runner::with_options(options);
+    runner.define("extra", Shared::new(Object::Integer(1)));
+    assert_eq!(eval(&mut runner, "base + len(args()) + extra;"), "42");
+    match runner.eval("while (true) { 1; }") {
+        Err(RunnerError::Runtime(err)) => assert_eq!(err.error_type, RuntimeErrorType::Timeout),
+        other => panic!("expected timeout, got {other:?}"),
+    }
+
+    runner.reset();
+    assert!(runner.global("extra").is_none());
+    assert_eq!(eval(&mut runner, "base;"), "40");
+}
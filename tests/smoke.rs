@@ -22,5 +22,12 @@ fn lexer_reads_first_token() {
 #[test]
 fn builtin_names_match_contract_set() {
     let names = builtin_names();
-    assert_eq!(names, ["len", "first", "last", "rest", "push", "puts"]);
+    assert_eq!(
+        &names[..6],
+        ["len", "first", "last", "rest", "push", "puts"]
+    );
+    assert_eq!(
+        &names[6..],
+        ["eval", "args", "substr", "char_at", "chars", "eq", "assert"]
+    );
 }
@@ -0,0 +1,33 @@
+use monkey_rust_compiler::position::{Position, Span};
+use monkey_rust_compiler::source::{Location, SourceMap};
+
+#[test]
+fn locations_name_the_file_they_point_into() {
+    let mut files = SourceMap::new();
+    let main = files.add("main.mk", "let x = lib(1);\nputs(x);");
+    let lib = files.add("lib.mk", "let lib = fn(n) { n / 0 };");
+    let bytecode = files.add_without_source("prelude.mkc");
+    assert_eq!(files.files().collect::<Vec<_>>(), vec![main, lib, bytecode]);
+
+    let p = Position::new;
+    let in_lib = Location::new(lib, Span::new(p(1, 21), p(1, 19), p(1, 24)));
+    assert_eq!(files.describe(in_lib), "lib.mk:1:21");
+    assert_eq!(
+        files.snippet(in_lib).as_deref(),
+        Some("  1 | let lib = fn(n) { n / 0 };\n    |                   ^^^^^")
+    );
+
+    let in_main = Location::new(main, p(2, 6));
+    assert_eq!(files.describe(in_main), "main.mk:2:6");
+    assert_eq!(
+        files.snippet(in_main).as_deref(),
+        Some("  2 | puts(x);\n    |      ^")
+    );
+
+    // Files without text are still named.
+    let in_bytecode = Location::new(bytecode, p(3, 1));
+    assert_eq!(files.name(bytecode), "prelude.mkc");
+    assert_eq!(files.source(bytecode), None);
+    assert_eq!(files.describe(in_bytecode), "prelude.mkc:3:1");
+    assert_eq!(files.snippet(in_bytecode), None);
+}
@@ -0,0 +1,111 @@
+use monkey_rust_compiler::position::{Position, Span};
+use monkey_rust_compiler::runner::{run_source, RunnerError};
+use monkey_rust_compiler::source::{snippet, snippet_for_span, snippet_span};
+
+#[test]
+fn underlines_the_token_at_the_position() {
+    let source = "let total = 1;\nputs(totl + \"abc\");";
+    assert_eq!(
+        snippet(source, Position::new(2, 6)).as_deref(),
+        Some("  2 | puts(totl + \"abc\");\n    |      ^^^^")
+    );
+    assert_eq!(
+        snippet(source, Position::new(2, 13)).as_deref(),
+        Some("  2 | puts(totl + \"abc\");\n    |             ^^^^^")
+    );
+    assert_eq!(
+        snippet(source, Position::new(2, 11)).as_deref(),
+        Some("  2 | puts(totl + \"abc\");\n    |           ^")
+    );
+    assert_eq!(
+        snippet_span(source, Position::new(1, 5), 9).as_deref(),
+        Some("  1 | let total = 1;\n    |     ^^^^^^^^^")
+    );
+}
+
+#[test]
+fn underlines_whole_spans_on_one_line() {
+    let source = "puts(a + 2 / (a - 4));\nlet b = [\n1];";
+    let span = Span::new(
+        Position::new(1, 12),
+        Position::new(1, 10),
+        Position::new(1, 21),
+    );
+    assert_eq!(
+        snippet_for_span(source, span).as_deref(),
+        Some("  1 | puts(a + 2 / (a - 4));\n    |          ^^^^^^^^^^^")
+    );
+    // Spans over several lines, or without an extent, fall back to the
+    // token at the reported position.
+    let multiline = Span::new(
+        Position::new(2, 9),
+        Position::new(2, 9),
+        Position::new(3, 3),
+    );
+    assert_eq!(
+        snippet_for_span(source, multiline).as_deref(),
+        Some("  2 | let b = [\n    |         ^")
+    );
+    assert_eq!(
+        snippet_for_span(source, Span::at(Position::new(1, 6))),
+        snippet(source, Position::new(1, 6))
+    );
+}
+
+#[test]
+fn keeps_tabs_and_widens_the_gutter() {
+    let source = format!("{}\tx;", "\n".repeat(9));
+    assert_eq!(
+        snippet(&source, Position::new(10, 2)).as_deref(),
+        Some("  10 | \tx;\n     | \t^")
+    );
+}
+
+#[test]
+fn positions_outside_the_source_have_no_snippet() {
+    assert_eq!(snippet("1;", Position::new(2, 1)), None);
+    assert_eq!(snippet("1;", Position::new(1, 9)), None);
+    // One past the end of a line still points at something.
+    assert!(snippet("1 +", Position::new(1, 4)).is_some());
+}
+
+#[test]
+fn errors_render_with_their_source_line() {
+    let parse = "let = 5;";
+    match run_source(parse) {
+        Err(RunnerError::Parse(errors)) => assert_eq!(
+            errors[0].render_with_source(parse),
+            "1:5: expected next token to be Ident, got Assign [P001]\n  1 | let = 5;\n    |     ^\n\
+             1:8: note: skipped to the end of the statement\n  1 | let = 5;\n    |        ^"
+        ),
+        other => panic!("expected parse errors, got {other:?}"),
+    }
+
+    let compile = "puts(nope);";
+    match run_source(compile) {
+        Err(RunnerError::Compile(errors)) => assert_eq!(
+            errors[0].render_with_source(compile),
+            "1:6: unresolved identifier: nope [C001]\n  1 | puts(nope);\n    |      ^^^^"
+        ),
+        other => panic!("expected compile errors, got {other:?}"),
+    }
+
+    let runtime = "let f = fn(x) { x / 0 };\nf(1);";
+    match run_source(runtime) {
+        Err(RunnerError::Runtime(err)) => {
+            let rendered = err.format_multiline_with_source(runtime);
+            let mut lines = rendered.lines();
+            assert_eq!(
+                lines.next(),
+                Some(format!("{} [R009]", err.format_single_line()).as_str())
+            );
+            assert_eq!(lines.next(), Some("  1 | let f = fn(x) { x / 0 };"));
+            assert_eq!(lines.next(), Some("    |                 ^^^^^"));
+            assert_eq!(
+                lines.collect::<Vec<_>>().join("\n"),
+                err.format_multiline().split_once('\n').expect("stack").1
+            );
+        }
+        other => panic!("expected a runtime error, got {other:?}"),
+    }
+}
@@ -1,4 +1,5 @@
 use monkey_rust_compiler::builtins::builtin_names;
+use monkey_rust_compiler::position::Position;
 use monkey_rust_compiler::symbol_table::{
     define_builtins, Symbol, SymbolScope, SymbolTable, BUILTIN_NAMES,
 };
@@ -212,3 +213,60 @@ fn function_scope_symbol_can_be_captured_as_free() {
 fn builtin_constant_order_matches_builtin_registry() {
     assert_eq!(BUILTIN_NAMES, builtin_names());
 }
+
+#[test]
+fn block_bindings_end_with_their_block() {
+    let mut table = SymbolTable::new();
+    define_builtins(&mut table);
+    let counter = table.define("i");
+
+    table.enter_block();
+    assert_eq!(table.define("i"), counter);
+    let len = table.define("len");
+    let tmp = table.define("tmp");
+    table.enter_block();
+    table.define("inner");
+    table.leave_block();
+    assert!(table.resolve("inner").is_none());
+    assert_eq!(table.resolve("tmp"), Some(tmp));
+    table.leave_block();
+
+    assert_eq!(table.resolve("i"), Some(counter));
+    assert!(table.resolve("tmp").is_none());
+    assert!(table.ended_in_block("tmp"));
+    assert_ne!(table.resolve("len"), Some(len));
+    assert_eq!(
+        table.resolve("len").map(|s| s.scope),
+        Some(SymbolScope::Builtin)
+    );
+
+    // Slots stay allocated, so a later definition gets a fresh one.
+    assert_eq!(
+        table.define("tmp"),
+        Symbol::new("tmp", SymbolScope::Global, 4)
+    );
+}
+
+#[test]
+fn define_at_records_positions_and_reports_redefinitions() {
+    let mut table = SymbolTable::new();
+    let at = |col| Position::new(1, col);
+
+    assert_eq!(table.define_at("x", at(5)).1, None);
+    assert_eq!(table.definition("x"), Some(at(5)));
+    assert_eq!(
+        table.define_at("x", at(16)),
+        (Symbol::new("x", SymbolScope::Global, 0), Some(at(5)))
+    );
+
+    table.enter_block();
+    // Rebinding from an inner block keeps the outer definition.
+    assert_eq!(table.define_at("x", at(30)).1, None);
+    assert_eq!(table.definition("x"), Some(at(5)));
+    assert_eq!(table.define_at("y", at(40)).1, None);
+    assert_eq!(table.define_at("y", at(50)).1, Some(at(40)));
+    table.leave_block();
+
+    assert_eq!(table.definition("y"), None);
+    assert_eq!(table.define_at("y", at(60)).1, None);
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "sync")]
+
+use std::thread;
+
+use monkey_rust_compiler::debugger::Debugger;
+use monkey_rust_compiler::runner::{compile_source, run_source};
+use monkey_rust_compiler::vm::{StepOutcome, Vm, VmOptions};
+
+fn assert_send<T: Send>() {}
+
+#[test]
+fn vm_and_results_are_send() {
+    assert_send::<Vm>();
+    assert_send::<Debugger>();
+    assert_send::<monkey_rust_compiler::object::ObjectRef>();
+}
+
+#[test]
+fn scripts_run_on_worker_threads() {
+    let handles = (1..=4)
+        .map(|n| {
+            thread::spawn(move || {
+                let outcome = run_source(&format!(
+                    "let fib = fn(x) {{ if (x < 2) {{ x }} else {{ fib(x - 1) + fib(x - 2) }} }}; fib({})",
+                    n * 5
+                ))
+                .expect("script should run");
+                outcome.result.inspect()
+            })
+        })
+        .collect::<Vec<_>>();
+    let results = handles
+        .into_iter()
+        .map(|handle| handle.join().expect("worker should not panic"))
+        .collect::<Vec<_>>();
+    assert_eq!(results, ["5", "55", "610", "6765"]);
+}
+
+#[test]
+fn paused_vm_can_move_between_threads() {
+    let chunk = compile_source("let xs = [1, 2, 3]; puts(len(xs)); xs[2] * 10")
+        .expect("program should compile");
+    let mut vm = Vm::with_options(chunk, VmOptions::default());
+    assert!(matches!(vm.run_for(3), StepOutcome::Continue));
+
+    let (result, output) = thread::spawn(move || {
+        let result = vm.run().expect("should finish");
+        (result, vm.take_output())
+    })
+    .join()
+    .expect("worker should not panic");
+    assert_eq!(result.inspect(), "30");
+    assert_eq!(output, ["3"]);
+}
@@ -0,0 +1,73 @@
+use std::path::Path;
+
+use monkey_rust_compiler::runner::run_source;
+use monkey_rust_compiler::source::SourceMap;
+use monkey_rust_compiler::testing::{discover_tests, failure_summary, is_test_file};
+
+#[test]
+fn test_files_are_found_recursively_in_order() {
+    let mut dir = std::env::temp_dir();
+    dir.push(format!("monkey_discover_{}", std::process::id()));
+    for sub in ["b", ".git", "target"] {
+        std::fs::create_dir_all(dir.join(sub)).expect("create temp dir");
+    }
+    for file in [
+        "z_test.monkey",
+        "b/a_test.monkey",
+        "lib.monkey",
+        ".git/x_test.monkey",
+        "target/y_test.monkey",
+    ] {
+        std::fs::write(dir.join(file), "1").expect("write temp file");
+    }
+
+    let found = discover_tests(&[
+        dir.join("missing_test.monkey"),
+        dir.clone(),
+        dir.join("lib.monkey"),
+    ]);
+    assert_eq!(
+        found.files,
+        vec![
+            dir.join("b/a_test.monkey"),
+            dir.join("lib.monkey"),
+            dir.join("z_test.monkey"),
+        ]
+    );
+    let missing = found
+        .errors
+        .iter()
+        .map(|(path, _)| path)
+        .collect::<Vec<_>>();
+    assert_eq!(missing, [&dir.join("missing_test.monkey")]);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn only_names_ending_in_the_suffix_are_tests() {
+    assert!(is_test_file(Path::new("dir/math_test.monkey")));
+    assert!(!is_test_file(Path::new("dir/math.monkey")));
+    assert!(!is_test_file(Path::new("math_test.monkey.bak")));
+}
+
+#[test]
+fn failures_are_summarized_by_their_first_error() {
+    let summary = |source: &str| {
+        let mut files = SourceMap::new();
+        files.add("other_test.monkey", "1;");
+        let file = files.add("math_test.monkey", source);
+        failure_summary(&files, file, &run_source(source).expect_err("should fail"))
+    };
+    assert_eq!(
+        summary("\nassert(false, \"no\");"),
+        "math_test.monkey:2:7: assertion failed: no"
+    );
+    assert_eq!(
+        summary("let = 1;"),
+        "math_test.monkey:1:5: expected next token to be Ident, got Assign"
+    );
+    assert_eq!(
+        summary("missing;"),
+        "math_test.monkey:1:1: unresolved identifier: missing"
+    );
+}
@@ -0,0 +1,306 @@
+use std::time::Duration;
+
+use monkey_rust_compiler::compiler::Compiler;
+use monkey_rust_compiler::lexer::Lexer;
+use monkey_rust_compiler::object::{Object, Shared};
+use monkey_rust_compiler::parser::Parser;
+use monkey_rust_compiler::runner::{run_source, RunnerError};
+use monkey_rust_compiler::runtime_error::{RuntimeError, RuntimeErrorType};
+use monkey_rust_compiler::vm::{Vm, VmOptions};
+
+fn compile_vm(input: &str, options: VmOptions) -> Vm {
+    let mut parser = Parser::new(Lexer::new(input));
+    let program = parser.parse_program();
+    assert!(parser.errors().is_empty(), "parse errors for {input}");
+    let mut compiler = Compiler::new();
+    compiler
+        .compile_program(&program)
+        .expect("compile should succeed");
+    Vm::with_options(compiler.into_bytecode(), options)
+}
+
+fn run_with(input: &str, options: VmOptions) -> Result<Object, RuntimeError> {
+    compile_vm(input, options)
+        .run()
+        .map(|obj| obj.as_ref().clone())
+}
+
+const COUNTDOWN: &str = "let down = fn(n) { if (n == 0) { 0 } else { down(n - 1) } };";
+
+#[test]
+fn recursion_within_call_depth_limit_succeeds() {
+    let options = VmOptions::default().with_max_call_depth(50);
+    let result = run_with(&format!("{COUNTDOWN} down(48);"), options).expect("should run");
+    assert_eq!(result, Object::Integer(0));
+}
+
+#[test]
+fn exceeding_call_depth_raises_stack_overflow() {
+    let options = VmOptions::default().with_max_call_depth(50);
+    let err = run_with(&format!("{COUNTDOWN} down(49);"), options).expect_err("should overflow");
+    assert_eq!(err.error_type, RuntimeErrorType::StackOverflow);
+    assert_eq!(err.error_type.code(), "STACK_OVERFLOW");
+    assert_eq!(
+        err.message,
+        "maximum call depth of 50 exceeded (33 frames omitted from trace)"
+    );
+}
+
+#[test]
+fn stack_overflow_trace_is_truncated_but_keeps_root_frame() {
+    let options = VmOptions::default().with_max_call_depth(100);
+    let err = run_with(&format!("{COUNTDOWN} down(1000);"), options).expect_err("should overflow");
+    assert_eq!(err.stack.len(), 17);
+    assert!(err.stack[..16]
+        .iter()
+        .all(|frame| frame.function_name == "down"));
+    assert_eq!(
+        err.stack.last().map(|frame| frame.format_frame()),
+        Some("at <repl>(0 args) @ 1:1".to_string())
+    );
+}
+
+#[test]
+fn default_options_stop_runaway_recursion() {
+    let source = "let fact = fn(n) { if (n == 0) { 1 } else { n * fact(n - 1) } }; fact(100000);";
+    match run_source(source) {
+        Err(RunnerError::Runtime(err)) => {
+            assert_eq!(err.error_type, RuntimeErrorType::StackOverflow)
+        }
+        other => panic!("expected stack overflow, got {other:?}"),
+    }
+}
+
+#[test]
+fn eval_children_inherit_call_depth_limit() {
+    let options = VmOptions::default().with_max_call_depth(20);
+    let source = format!(r#"eval("{COUNTDOWN} down(100);");"#);
+    let err = run_with(&source, options).expect_err("should overflow");
+    assert_eq!(err.error_type, RuntimeErrorType::StackOverflow);
+    assert!(err.message.contains("maximum call depth of 20 exceeded"));
+}
+
+#[test]
+fn infinite_loop_exhausts_instruction_budget() {
+    let options = VmOptions::default().with_max_instructions(1_000);
+    let err = run_with(
+        "let spin = fn() { spin() }; spin();",
+        options.with_max_call_depth(1_000_000),
+    )
+    .expect_err("should exhaust budget");
+    assert_eq!(err.error_type, RuntimeErrorType::BudgetExceeded);
+    assert_eq!(err.error_type.code(), "BUDGET_EXCEEDED");
+    assert_eq!(err.message, "instruction budget of 1000 exhausted");
+}
+
+#[test]
+fn budget_is_exact_and_counts_executed_instructions() {
+    let mut unlimited = compile_vm("1 + 2;", VmOptions::default());
+    unlimited.run().expect("should run");
+    let needed = unlimited.instructions_executed();
+    assert!(needed > 0);
+
+    let mut exact = compile_vm("1 + 2;", VmOptions::default().with_max_instructions(needed));
+    assert_eq!(
+        exact.run().expect("exact budget should suffice").as_ref(),
+        &Object::Integer(3)
+    );
+
+    let short = run_with(
+        "1 + 2;",
+        VmOptions::default().with_max_instructions(needed - 1),
+    )
+    .expect_err("one instruction short");
+    assert_eq!(short.error_type, RuntimeErrorType::BudgetExceeded);
+}
+
+#[test]
+fn eval_children_draw_from_the_parent_budget() {
+    let source = r#"let spin = fn() { eval("let f = fn() { f() }; f();") }; spin();"#;
+    let options = VmOptions::default()
+        .with_max_instructions(500)
+        .with_max_call_depth(1_000_000);
+    let mut vm = compile_vm(source, options);
+    let err = vm.run().expect_err("should exhaust budget");
+    assert_eq!(err.error_type, RuntimeErrorType::BudgetExceeded);
+    assert_eq!(vm.instructions_executed(), 500);
+}
+
+#[test]
+fn run_with_deadline_times_out_infinite_loop() {
+    let mut vm = compile_vm("while (true) {}", VmOptions::default());
+    let err = vm
+        .run_with_deadline(Duration::from_millis(20))
+        .expect_err("should time out");
+    assert_eq!(err.error_type, RuntimeErrorType::Timeout);
+    assert_eq!(err.error_type.code(), "TIMEOUT");
+    assert_eq!(err.message, "execution timed out after 20 ms");
+}
+
+#[test]
+fn run_with_deadline_returns_result_when_fast_enough() {
+    let mut vm = compile_vm(
+        "let x = 0; while (x < 10) { let x = x + 1; } 7;",
+        VmOptions::default(),
+    );
+    let result = vm
+        .run_with_deadline(Duration::from_secs(10))
+        .expect("should finish");
+    assert_eq!(result.as_ref(), &Object::Integer(7));
+}
+
+#[test]
+fn eval_children_inherit_deadline() {
+    let mut vm = compile_vm(r#"eval("while (true) {}");"#, VmOptions::default());
+    let err = vm
+        .run_with_deadline(Duration::from_millis(20))
+        .expect_err("should time out");
+    assert_eq!(err.error_type, RuntimeErrorType::Timeout);
+}
+
+#[test]
+fn growing_string_hits_memory_limit() {
+    let source = r#"let s = "x"; let grow = fn(s) { grow(s + s) }; grow(s);"#;
+    let options = VmOptions::default().with_max_memory_bytes(4096);
+    let err = run_with(source, options).expect_err("should run out of memory");
+    assert_eq!(err.error_type, RuntimeErrorType::OutOfMemory);
+    assert_eq!(err.error_type.code(), "OUT_OF_MEMORY");
+    assert_eq!(err.message, "memory limit of 4096 bytes exceeded");
+}
+
+#[test]
+fn allocated_bytes_tracks_arrays_hashes_and_strings() {
+    let slot = std::mem::size_of::<Shared<Object>>();
+
+    let mut vm = compile_vm(r#""ab" + "cd";"#, VmOptions::default());
+    vm.run().expect("should run");
+    assert_eq!(vm.allocated_bytes(), 4);
+
+    let mut vm = compile_vm("[1, 2, 3];", VmOptions::default());
+    vm.run().expect("should run");
+    assert_eq!(vm.allocated_bytes(), 3 * slot);
+
+    let mut vm = compile_vm("push([1], 2);", VmOptions::default());
+    vm.run().expect("should run");
+    assert_eq!(vm.allocated_bytes(), slot + 2 * slot);
+
+    // Builtin results are charged even when shared, like the inner array
+    // `first` returns here.
+    let mut vm = compile_vm("first([[1, 2]]);", VmOptions::default());
+    vm.run().expect("should run");
+    assert_eq!(vm.allocated_bytes(), slot + 2 * slot + 2 * slot);
+
+    let mut vm = compile_vm("{1: 2};", VmOptions::default());
+    vm.run().expect("should run");
+    assert!(vm.allocated_bytes() >= 2 * slot);
+}
+
+#[test]
+fn memory_limit_applies_to_the_live_heap() {
+    // Each round builds an array and drops the previous one, so the total
+    // allocated passes the limit many times over.
+    let source = r#"
+        let i = 0;
+        while (i < 2000) { let a = [1, 2, 3, 4, 5, 6, 7, 8]; let i = i + 1; }
+        i;
+    "#;
+    let options = VmOptions::default().with_max_memory_bytes(16 * 1024);
+    let mut vm = compile_vm(source, options);
+    let result = vm.run().expect("should run within the limit");
+    assert_eq!(result.as_ref(), &Object::Integer(2000));
+    assert!(
+        vm.allocated_bytes() > 4 * 16 * 1024,
+        "{}",
+        vm.allocated_bytes()
+    );
+}
+
+#[test]
+fn shared_builtin_results_count_toward_the_memory_limit() {
+    let source = r#"
+        let big = "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx";
+        let keep = fn(n, acc) { if (n == 0) { acc } else { keep(n - 1, push(acc, chars(big))) } };
+        keep(100, []);
+    "#;
+    let options = VmOptions::default().with_max_memory_bytes(16 * 1024);
+    let err = run_with(source, options).expect_err("should run out of memory");
+    assert_eq!(err.error_type, RuntimeErrorType::OutOfMemory);
+}
+
+#[test]
+fn eval_counts_the_parent_heap_toward_the_memory_limit() {
+    let slot = std::mem::size_of::<Shared<Object>>();
+    let options = VmOptions::default().with_max_memory_bytes(250 * slot);
+    let fill = |n: usize| format!("let a = []; while (len(a) < {n}) {{ let a = push(a, 1); }}");
+    let child = format!(r#"eval("{} 1;");"#, fill(100));
+
+    run_with(&child, options.clone()).expect("the child alone fits");
+
+    let nested = format!("{} {child}", fill(100));
+    let err = run_with(&nested, options).expect_err("should run out of memory");
+    assert_eq!(err.error_type, RuntimeErrorType::OutOfMemory);
+}
+
+#[test]
+fn memory_within_limit_runs_normally() {
+    let options = VmOptions::default().with_max_memory_bytes(1 << 20);
+    let result = run_with(r#"len(chars("monkey"));"#, options).expect("should run");
+    assert_eq!(result, Object::Integer(6));
+}
+
+#[test]
+fn capacity_knobs_do_not_change_results() {
+    let source =
+        format!("{COUNTDOWN} let f = fn(a, b) {{ let c = a + b; c }}; [down(200), f(1, 2)];");
+    for options in [
+        VmOptions::default(),
+        VmOptions::default()
+            .with_stack_capacity(0)
+            .with_frame_capacity(0),
+        VmOptions::default()
+            .with_stack_capacity(1 << 16)
+            .with_frame_capacity(1 << 12)
+            .with_max_call_depth(1),
+    ] {
+        let depth_limited = options.max_call_depth == 1;
+        match run_with(&source, options) {
+            Ok(result) => {
+                assert!(!depth_limited);
+                assert_eq!(result.inspect(), "[0, 3]");
+            }
+            Err(err) => {
+                assert!(depth_limited, "unexpected error: {err}");
+                assert_eq!(err.error_type, RuntimeErrorType::StackOverflow);
+            }
+        }
+    }
+}
+
+#[test]
+fn self_evaluating_eval_raises_stack_overflow() {
+    let mut vm = compile_vm("eval(first(args()));", VmOptions::default());
+    vm.set_script_args(vec!["eval(first(args()))".to_string()]);
+    let err = vm.run().expect_err("should overflow");
+    assert_eq!(err.error_type, RuntimeErrorType::StackOverflow);
+    assert!(
+        err.message
+            .ends_with("maximum eval nesting depth of 32 exceeded"),
+        "{}",
+        err.message
+    );
+}
+
+#[test]
+fn eval_frames_count_toward_the_parent_call_depth() {
+    let options = VmOptions::default().with_max_call_depth(20);
+    let inner = "let g = fn(m) { if (m == 0) { 0 } else { g(m - 1) } }; g(10);";
+    let source = format!(
+        r#"let f = fn(n) {{ if (n == 0) {{ eval("{inner}") }} else {{ f(n - 1) }} }}; f(10);"#
+    );
+    let err = run_with(&source, options.clone()).expect_err("should overflow");
+    assert_eq!(err.error_type, RuntimeErrorType::StackOverflow);
+    assert!(err.message.contains("maximum call depth of 20 exceeded"));
+
+    let shallow = source.replace("f(10);", "f(3);");
+    assert_eq!(run_with(&shallow, options), Ok(Object::Integer(0)));
+}
@@ -1,7 +1,7 @@
 use monkey_rust_compiler::ast::Program;
 use monkey_rust_compiler::compiler::{CompileError, Compiler};
 use monkey_rust_compiler::lexer::Lexer;
-use monkey_rust_compiler::object::Object;
+use monkey_rust_compiler::object::{HashObject, Object, Shared};
 use monkey_rust_compiler::parser::Parser;
 use monkey_rust_compiler::position::Position;
 use monkey_rust_compiler::runtime_error::{RuntimeError, RuntimeErrorType};
@@ -27,10 +27,10 @@ fn run_input(input: &str) -> Result<Object, RuntimeError> {
     let mut compiler = Compiler::new();
     compiler
         .compile_program(&parse_program(input))
-        .map_err(|err: CompileError| {
+        .map_err(|errors: Vec<CompileError>| {
             panic!(
                 "compile failed for input:\n{input}\nmessage: {}\npos: {:?}",
-                err.message, err.pos
+                errors[0].message, errors[0].pos
             )
         })?;
     let chunk = compiler.into_bytecode();
@@ -47,7 +47,7 @@ fn assert_bool(obj: Object, expected: bool) {
 }
 
 fn assert_string(obj: Object, expected: &str) {
-    assert_eq!(obj, Object::String(expected.to_string()));
+    assert_eq!(obj, Object::String(expected.into()));
 }
 
 fn assert_null(obj: Object) {
@@ -238,10 +238,13 @@ fn phase2_opcodes_execute_without_boundary_errors() {
     );
     assert_eq!(
         run_input("{\"a\": 1};").expect("vm run should succeed"),
-        Object::Hash(vec![(
-            Object::String("a".to_string()).rc(),
-            Object::Integer(1).rc()
-        )])
+        Object::Hash(
+            HashObject::from_pairs(vec![(
+                Object::String("a".into()).rc(),
+                Object::Integer(1).rc()
+            )])
+            .expect("hashable keys")
+        )
     );
     assert_int(
         run_input("let a = [1, 2]; let i = 0; a[i];").expect("vm run should succeed"),
@@ -255,3 +258,20 @@ fn runtime_error_position_propagates_from_chunk_metadata() {
     assert_eq!(err.error_type, RuntimeErrorType::DivisionByZero);
     assert_eq!(err.pos, Position::new(3, 3));
 }
+
+#[test]
+fn string_concatenation_results_are_interned() {
+    let result = run_input(r#"["ab" + "c", "a" + "bc", "abc"];"#).expect("vm run should succeed");
+    let Object::Array(items) = result else {
+        panic!("expected array, got {result:?}");
+    };
+    let texts = items
+        .iter()
+        .map(|item| match item.as_ref() {
+            Object::String(text) => text.clone(),
+            other => panic!("expected string, got {other:?}"),
+        })
+        .collect::<Vec<_>>();
+    assert!(Shared::ptr_eq(&texts[0], &texts[1]));
+    assert!(Shared::ptr_eq(&texts[0], &texts[2]));
+}
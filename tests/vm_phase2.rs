@@ -1,7 +1,7 @@
 use monkey_rust_compiler::ast::Program;
 use monkey_rust_compiler::compiler::{CompileError, Compiler};
 use monkey_rust_compiler::lexer::Lexer;
-use monkey_rust_compiler::object::Object;
+use monkey_rust_compiler::object::{HashObject, Object, Shared};
 use monkey_rust_compiler::parser::Parser;
 use monkey_rust_compiler::runtime_error::{RuntimeError, RuntimeErrorType};
 use monkey_rust_compiler::vm::Vm;
@@ -26,10 +26,10 @@ fn compile_to_vm(input: &str) -> Vm {
     let mut compiler = Compiler::new();
     compiler
         .compile_program(&parse_program(input))
-        .map_err(|err: CompileError| {
+        .map_err(|errors: Vec<CompileError>| {
             panic!(
                 "compile failed for input:\n{input}\nmessage: {}\npos: {:?}",
-                err.message, err.pos
+                errors[0].message, errors[0].pos
             )
         })
         .expect("compilation should succeed");
@@ -199,20 +199,17 @@ fn executes_arrays_hashes_and_indexing() {
 
     assert_eq!(
         run_input("{};").expect("vm run should succeed"),
-        Object::Hash(vec![])
+        Object::Hash(HashObject::new())
     );
     assert_eq!(
         run_input("{\"a\": 1, \"b\": 2};").expect("vm run should succeed"),
-        Object::Hash(vec![
-            (
-                Object::String("a".to_string()).rc(),
-                Object::Integer(1).rc()
-            ),
-            (
-                Object::String("b".to_string()).rc(),
-                Object::Integer(2).rc()
-            )
-        ])
+        Object::Hash(
+            HashObject::from_pairs(vec![
+                (Object::String("a".into()).rc(), Object::Integer(1).rc()),
+                (Object::String("b".into()).rc(), Object::Integer(2).rc())
+            ])
+            .expect("hashable keys")
+        )
     );
     assert_eq!(
         run_input("{\"a\": 1, \"a\": 2}[\"a\"];").expect("vm run should succeed"),
@@ -302,3 +299,55 @@ f();
     assert!(!err.stack.is_empty());
     assert_eq!(err.stack[0].function_name, "f");
 }
+
+#[test]
+fn hash_literals_dedupe_keys_in_first_insertion_order() {
+    let value = run_input("{\"a\": 1, \"b\": 2, \"a\": 3};").expect("vm run should succeed");
+    assert_eq!(value.inspect(), "{a: 3, b: 2}");
+
+    let src = "let h = {1: \"x\", true: \"y\", \"1\": \"z\"}; [h[1], h[true], h[\"1\"], h[2]];";
+    assert_eq!(
+        run_input(src).expect("vm run should succeed").inspect(),
+        "[x, y, z, null]"
+    );
+}
+
+#[test]
+fn repeated_hash_index_sites_stay_correct_across_hash_shapes() {
+    let src = "let get = fn(h, k) { h[k] };
+        let a = {\"x\": 1, \"y\": 2};
+        let b = {\"y\": 20, \"x\": 10};
+        let c = {\"z\": 30};
+        let k = \"x\";
+        [get(a, \"x\"), get(b, \"x\"), get(c, \"x\"), get(a, k + \"\"), get(b, \"y\"), get({1: 5, 2: 6}, 2), get({2: 7}, 2), get(a, 1)];";
+    assert_eq!(
+        run_input(src).expect("vm run should succeed").inspect(),
+        "[1, 10, null, 1, 20, 6, 7, null]"
+    );
+
+    let src = "let total = 0; let i = 0;
+        while (i < 10) { let h = {\"a\": i, \"b\": i * 2}; let total = total + h[\"b\"] - h[\"a\"]; let i = i + 1; };
+        total;";
+    assert_eq!(
+        run_input(src).expect("vm run should succeed"),
+        Object::Integer(45)
+    );
+}
+
+#[test]
+fn recursive_closures_are_freed_with_their_last_reference() {
+    let mut vm = compile_to_vm(
+        "let outer = fn(x) { let inner = fn(n) { if (n == 0) { inner } else { inner(n - 1) } }; inner(x) }; outer(3);",
+    );
+    let result = vm.run().expect("vm run should succeed");
+    let Object::Closure(closure) = result.as_ref() else {
+        panic!("expected closure result, got {result:?}");
+    };
+    let closure = Shared::downgrade(closure);
+    drop(vm);
+    drop(result);
+    assert!(
+        closure.upgrade().is_none(),
+        "closure should not keep itself alive"
+    );
+}
@@ -0,0 +1,101 @@
+use monkey_rust_compiler::compiler::Compiler;
+use monkey_rust_compiler::lexer::Lexer;
+use monkey_rust_compiler::parser::Parser;
+use monkey_rust_compiler::runner::{run_source, run_source_with, RunOptions};
+use monkey_rust_compiler::vm::{Vm, VmOptions};
+
+fn compile_vm(input: &str) -> Vm {
+    let mut parser = Parser::new(Lexer::new(input));
+    let program = parser.parse_program();
+    assert!(parser.errors().is_empty(), "parse errors for {input}");
+    let mut compiler = Compiler::new();
+    compiler
+        .compile_program(&program)
+        .expect("compile should succeed");
+    Vm::with_options(
+        compiler.into_bytecode(),
+        VmOptions::default().with_superinstructions(false),
+    )
+}
+
+const FIB: &str = "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(10);";
+
+#[test]
+fn profile_is_absent_unless_enabled() {
+    let mut vm = compile_vm("1;");
+    vm.run().expect("should run");
+    assert!(vm.profile().is_none());
+    assert!(run_source("1;").expect("should run").profile.is_none());
+}
+
+#[test]
+fn profile_counts_opcodes() {
+    let mut vm = compile_vm("40000 + 50000; 60000 + 70000;");
+    vm.set_profiling(true);
+    vm.run().expect("should run");
+    let counts = vm.profile().expect("profiling enabled").opcode_counts();
+    assert_eq!(counts[0], ("Constant", 4));
+    assert!(counts.contains(&("Add", 2)));
+    let total: u64 = counts.iter().map(|(_, count)| count).sum();
+    assert_eq!(total, vm.instructions_executed());
+}
+
+#[test]
+fn profile_counts_calls_per_function() {
+    let mut vm = compile_vm(FIB);
+    vm.set_profiling(true);
+    vm.run().expect("should run");
+    let profile = vm.profile().expect("profiling enabled");
+    let fib = profile.functions().get("fib").expect("fib was called");
+    assert_eq!(fib.calls, 177);
+    assert!(!profile.functions().contains_key("<repl>"));
+}
+
+#[test]
+fn profile_includes_calls_made_inside_eval() {
+    let mut vm = compile_vm(r#"let f = fn() { 1 }; f(); eval("let g = fn() { 2 }; g(); g();");"#);
+    vm.set_profiling(true);
+    vm.run().expect("should run");
+    let functions = vm.profile().expect("profiling enabled").functions();
+    assert_eq!(functions.get("f").map(|f| f.calls), Some(1));
+    assert_eq!(functions.get("g").map(|f| f.calls), Some(2));
+}
+
+#[test]
+fn runner_returns_rendered_profile() {
+    let outcome =
+        run_source_with(FIB, &RunOptions::default().with_profile(true)).expect("should run");
+    let report = outcome.profile.expect("profile requested").render();
+    assert!(report.starts_with("Opcodes:\n"), "report: {report}");
+    assert!(report.contains("Functions:\n  fib"), "report: {report}");
+    assert!(report.contains("calls=177"), "report: {report}");
+}
+
+#[test]
+fn profile_attributes_instructions_to_call_stacks() {
+    let mut vm = compile_vm(
+        "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } };
+         let run = fn() { fib(2) };
+         run(); eval(\"let g = fn() { 1 }; g();\");",
+    );
+    vm.set_profiling(true);
+    vm.run().expect("should run");
+    let profile = vm.profile().expect("profiling enabled");
+    let stacks = profile.folded_stacks();
+    let names: Vec<&str> = stacks.iter().map(|(stack, _)| stack.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "<main>",
+            "<main>;g",
+            "<main>;run",
+            "<main>;run;fib",
+            "<main>;run;fib;fib"
+        ]
+    );
+    let total: u64 = stacks.iter().map(|(_, count)| count).sum();
+    assert_eq!(total, vm.instructions_executed());
+    assert!(profile.render_folded().lines().all(|line| line
+        .rsplit_once(' ')
+        .is_some_and(|(_, n)| n.parse::<u64>().is_ok())));
+}
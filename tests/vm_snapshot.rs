@@ -0,0 +1,76 @@
+use monkey_rust_compiler::object::Object;
+use monkey_rust_compiler::runner::compile_source;
+use monkey_rust_compiler::vm::{StepOutcome, Vm};
+
+const PROGRAM: &str = "let total = 0;
+let add = fn(a, b) { puts(a); a + b };
+let total = add(total, 10);
+let total = add(total, 20);
+total";
+
+fn vm() -> Vm {
+    Vm::new(compile_source(PROGRAM).expect("program should compile"))
+}
+
+fn pause_inside_function(vm: &mut Vm) {
+    while vm.frame_depth() < 2 {
+        assert!(matches!(vm.step(), StepOutcome::Continue));
+    }
+}
+
+#[test]
+fn restore_rewinds_a_finished_vm_to_the_checkpoint() {
+    let mut vm = vm();
+    pause_inside_function(&mut vm);
+    let snapshot = vm.snapshot();
+    assert_eq!(snapshot.frame_depth(), 2);
+    assert_eq!(snapshot.globals()[0].as_ref(), &Object::Integer(0));
+
+    let first = vm.run().expect("should run");
+    assert_eq!(first.as_ref(), &Object::Integer(30));
+    assert_eq!(vm.output(), ["0", "10"]);
+    let executed = vm.instructions_executed();
+
+    vm.restore(snapshot);
+    assert_eq!(vm.frame_depth(), 2);
+    assert!(vm.output().is_empty());
+    assert_eq!(vm.globals()[0].as_ref(), &Object::Integer(0));
+
+    let second = vm.run().expect("should run again");
+    assert_eq!(second, first);
+    assert_eq!(vm.output(), ["0", "10"]);
+    assert_eq!(vm.instructions_executed(), executed);
+}
+
+#[test]
+fn snapshot_can_be_restored_into_another_vm_for_the_same_chunk() {
+    let mut original = vm();
+    pause_inside_function(&mut original);
+    assert!(matches!(original.run_for(12), StepOutcome::Continue));
+    let snapshot = original.snapshot();
+    let stack = snapshot.stack().to_vec();
+
+    let mut copy = vm();
+    copy.restore(snapshot);
+    assert_eq!(copy.stack(), stack.as_slice());
+    assert_eq!(copy.ip(), original.ip());
+    assert_eq!(
+        copy.run().expect("copy run"),
+        original.run().expect("original run")
+    );
+    assert_eq!(copy.output(), original.output());
+}
+
+#[test]
+fn snapshot_of_a_finished_vm_stays_finished() {
+    let mut vm = vm();
+    vm.run().expect("should run");
+    let snapshot = vm.snapshot();
+    assert_eq!(snapshot.frame_depth(), 0);
+    assert_eq!(snapshot.output(), ["0", "10"]);
+
+    let mut fresh = self::vm();
+    fresh.restore(snapshot);
+    assert_eq!(fresh.ip(), None);
+    assert_eq!(fresh.globals().len(), 2);
+}
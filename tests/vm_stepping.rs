@@ -0,0 +1,110 @@
+use monkey_rust_compiler::compiler::Compiler;
+use monkey_rust_compiler::lexer::Lexer;
+use monkey_rust_compiler::object::Object;
+use monkey_rust_compiler::parser::Parser;
+use monkey_rust_compiler::runtime_error::RuntimeErrorType;
+use monkey_rust_compiler::vm::{StepOutcome, Vm, VmOptions};
+
+fn compile_vm(input: &str) -> Vm {
+    let mut parser = Parser::new(Lexer::new(input));
+    let program = parser.parse_program();
+    assert!(parser.errors().is_empty(), "parse errors for {input}");
+    let mut compiler = Compiler::new();
+    compiler
+        .compile_program(&program)
+        .expect("compile should succeed");
+    // Native calls are not counted as instructions, so keep everything
+    // interpreted for the instruction-count comparisons.
+    Vm::with_options(
+        compiler.into_bytecode(),
+        VmOptions::default()
+            .with_superinstructions(false)
+            .with_jit(false),
+    )
+}
+
+#[test]
+fn stepping_walks_one_instruction_at_a_time() {
+    let mut vm = compile_vm("1 + 2;");
+    assert_eq!(vm.ip(), Some(0));
+    assert!(matches!(vm.step(), StepOutcome::Continue));
+    assert_eq!(vm.ip(), Some(1));
+    assert_eq!(vm.stack().len(), 1);
+    assert!(matches!(vm.step(), StepOutcome::Continue));
+    assert!(matches!(vm.step(), StepOutcome::Continue));
+    assert_eq!(vm.stack().len(), 1);
+    assert_eq!(vm.stack()[0].as_ref(), &Object::Integer(3));
+    match vm.step() {
+        StepOutcome::Done(value) => assert_eq!(value.as_ref(), &Object::Integer(3)),
+        other => panic!("expected done, got {other:?}"),
+    }
+    assert_eq!(vm.frame_depth(), 0);
+    assert_eq!(vm.ip(), None);
+}
+
+#[test]
+fn run_for_matches_uninterrupted_run() {
+    let source = "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(12);";
+    let expected = compile_vm(source).run().expect("should run");
+
+    let mut vm = compile_vm(source);
+    let mut slices = 0;
+    let value = loop {
+        slices += 1;
+        match vm.run_for(7) {
+            StepOutcome::Continue => continue,
+            StepOutcome::Done(value) => break value,
+            StepOutcome::Error(err) => panic!("unexpected error: {err}"),
+        }
+    };
+    assert_eq!(value, expected);
+    assert!(slices > 1);
+    assert_eq!(vm.instructions_executed(), {
+        let mut full = compile_vm(source);
+        full.run().expect("should run");
+        full.instructions_executed()
+    });
+}
+
+#[test]
+fn paused_vm_exposes_frame_locals_and_position() {
+    let mut vm = compile_vm("let f = fn(a, b) {\n  let c = a + b;\n  c * 2\n};\nf(3, 4);");
+    while vm.current_function_name() != Some("f") {
+        assert!(matches!(vm.step(), StepOutcome::Continue));
+    }
+    assert_eq!(vm.frame_depth(), 2);
+    assert_eq!(vm.locals().len(), 3);
+    assert_eq!(vm.locals()[0].as_ref(), &Object::Integer(3));
+    assert_eq!(vm.locals()[1].as_ref(), &Object::Integer(4));
+    assert_eq!(vm.position().map(|pos| pos.line), Some(2));
+    let trace = vm.backtrace();
+    assert_eq!(trace.len(), 2);
+    assert_eq!(trace[0].function_name, "f");
+    assert_eq!(trace[1].function_name, "<repl>");
+
+    match vm.run_for(1_000) {
+        StepOutcome::Done(value) => assert_eq!(value.as_ref(), &Object::Integer(14)),
+        other => panic!("expected done, got {other:?}"),
+    }
+}
+
+#[test]
+fn stepping_reports_errors_at_the_failing_instruction() {
+    let mut vm = compile_vm("let x = 1;\nx + true;");
+    let err = loop {
+        match vm.step() {
+            StepOutcome::Continue => {}
+            StepOutcome::Error(err) => break err,
+            StepOutcome::Done(value) => panic!("unexpected result {value:?}"),
+        }
+    };
+    assert_eq!(err.error_type, RuntimeErrorType::TypeMismatch);
+    assert_eq!(vm.position(), Some(err.pos));
+}
+
+#[test]
+fn run_finishes_a_partially_stepped_program() {
+    let mut vm = compile_vm("let a = [1, 2, 3]; len(a) + 1;");
+    assert!(matches!(vm.run_for(2), StepOutcome::Continue));
+    assert_eq!(vm.run().expect("should run").as_ref(), &Object::Integer(4));
+}
@@ -0,0 +1,115 @@
+use std::sync::{Arc, Mutex};
+
+use monkey_rust_compiler::compiler::Compiler;
+use monkey_rust_compiler::lexer::Lexer;
+use monkey_rust_compiler::parser::Parser;
+use monkey_rust_compiler::vm::{OutputSink, TraceSink, Vm, VmOptions};
+
+fn traced_run(input: &str) -> Vec<String> {
+    traced_run_with(input, VmOptions::default().with_superinstructions(false))
+}
+
+fn traced_run_with(input: &str, options: VmOptions) -> Vec<String> {
+    let mut parser = Parser::new(Lexer::new(input));
+    let program = parser.parse_program();
+    assert!(parser.errors().is_empty(), "parse errors for {input}");
+    let mut compiler = Compiler::new();
+    compiler
+        .compile_program(&program)
+        .expect("compile should succeed");
+
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let captured = Arc::clone(&lines);
+    let mut vm = Vm::with_options(compiler.into_bytecode(), options);
+    vm.set_trace(Some(TraceSink::new(move |line| {
+        captured.lock().expect("trace lines").push(line.to_string())
+    })));
+    let _ = vm.run();
+    let out = lines.lock().expect("trace lines").clone();
+    out
+}
+
+#[test]
+fn traces_every_instruction_with_operands_stack_and_position() {
+    assert_eq!(
+        traced_run("1 + 2;"),
+        vec![
+            "<repl> 0000 Const1 stack=0 @1:1",
+            "<repl> 0001 ConstInt 2 stack=1 @1:5",
+            "<repl> 0004 Add stack=2 @1:3",
+            "<repl> 0005 ReturnValue stack=1 @1:1",
+        ]
+    );
+}
+
+#[test]
+fn trace_shows_fused_superinstructions_by_default() {
+    assert_eq!(
+        traced_run_with("\"a\" + \"b\";", VmOptions::default()),
+        vec![
+            "<repl> 0000 AddConstants 0 1 stack=0 @1:1",
+            "<repl> 0007 ReturnValue stack=1 @1:1",
+        ]
+    );
+}
+
+#[test]
+fn trace_names_the_executing_function() {
+    let lines = traced_run("let add = fn(a, b) { a + b }; add(1, 2);");
+    assert!(lines
+        .iter()
+        .any(|line| line.starts_with("add ") && line.contains("Add")));
+    assert!(lines.iter().any(|line| line.contains("Call 2")));
+}
+
+#[test]
+fn trace_stops_at_runtime_error_and_covers_eval_children() {
+    let lines = traced_run(r#"eval("1 - true");"#);
+    let last = lines.last().expect("trace should not be empty");
+    assert!(last.contains("Sub"), "last line: {last}");
+}
+
+#[test]
+fn no_trace_is_emitted_without_a_sink() {
+    let mut parser = Parser::new(Lexer::new("1;"));
+    let program = parser.parse_program();
+    let mut compiler = Compiler::new();
+    compiler.compile_program(&program).expect("compile");
+    let mut vm = Vm::new(compiler.into_bytecode());
+    vm.set_trace(None);
+    assert!(vm.run().is_ok());
+}
+
+/// A `Write` handle onto a buffer the test keeps.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().expect("buffer").extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn output_sink_streams_printed_lines_to_a_writer() {
+    let mut parser = Parser::new(Lexer::new("puts(1); puts(\"two\"); eval(\"puts(3)\"); 4;"));
+    let program = parser.parse_program();
+    let mut compiler = Compiler::new();
+    compiler
+        .compile_program(&program)
+        .expect("compile should succeed");
+
+    let buffer = SharedBuffer::default();
+    let mut vm = Vm::new(compiler.into_bytecode());
+    vm.set_output_sink(Some(OutputSink::from_writer(buffer.clone())));
+    vm.run().expect("run should succeed");
+
+    let written = buffer.0.lock().expect("buffer").clone();
+    assert_eq!(String::from_utf8(written).expect("utf8"), "1\ntwo\n3\n");
+    assert!(vm.output().is_empty());
+}
@@ -0,0 +1,116 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+use monkey_rust_compiler::runner::{compile_wasm, run_source, RunnerError};
+
+fn compile_error(source: &str) -> String {
+    match compile_wasm(source) {
+        Err(RunnerError::Compile(errors)) => errors[0].to_string(),
+        other => panic!("expected a compile error for {source}, got {other:?}"),
+    }
+}
+
+/// Run `source` through `examples/wasm_host.js` and check it prints what
+/// the VM does. Skipped when `node` is not installed.
+fn assert_same_under_node(name: &str, source: &str) {
+    if Command::new("node").arg("--version").output().is_err() {
+        return;
+    }
+    let bytes = compile_wasm(source).expect("program should compile to wasm");
+    let module =
+        std::env::temp_dir().join(format!("monkey-wasm-{}-{name}.wasm", std::process::id()));
+    std::fs::write(&module, bytes).expect("write module");
+    let host = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("examples/wasm_host.js");
+    let output = Command::new("node")
+        .arg(host)
+        .arg(&module)
+        .output()
+        .expect("run node");
+    let _ = std::fs::remove_file(&module);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let expected = run_source(source).expect("VM run should succeed");
+    let mut lines = expected.output;
+    lines.push(expected.result.inspect());
+    let stdout = String::from_utf8(output.stdout).expect("utf8 output");
+    assert_eq!(stdout.lines().collect::<Vec<_>>(), lines, "{source}");
+}
+
+#[test]
+fn emits_a_module_exporting_main_and_memory() {
+    let bytes = compile_wasm("puts(1);").expect("compile");
+    assert_eq!(&bytes[..8], b"\0asm\x01\0\0\0");
+    let contains = |needle: &[u8]| bytes.windows(needle.len()).any(|w| w == needle);
+    for name in [
+        "monkey",
+        "print_int",
+        "print_str",
+        "newline",
+        "main",
+        "memory",
+    ] {
+        assert!(contains(name.as_bytes()), "missing {name}");
+    }
+}
+
+#[test]
+fn rejects_constructs_outside_the_typed_subset() {
+    assert!(compile_error("[1, 2];").contains("an array is not supported"));
+    assert!(compile_error("let f = fn(x) { fn(y) { x + y } }; f(1);").contains("function literal"));
+    assert!(compile_error("let x = 1; let x = \"s\";").contains("'x' changes type"));
+    assert!(compile_error("1 + true;").contains("unsupported operand types for +"));
+    assert!(compile_error("if (true) { 1 };").contains("if branches produce different types"));
+    assert!(compile_error("missing;").contains("unresolved identifier: missing"));
+    assert!(matches!(
+        compile_wasm("let = 1;"),
+        Err(RunnerError::Parse(_))
+    ));
+}
+
+#[test]
+fn statement_ifs_may_mix_branch_types() {
+    assert!(compile_wasm("if (true) { 1 } else { \"one\" }; 2;").is_ok());
+}
+
+#[test]
+fn runs_integer_boolean_and_string_programs() {
+    assert_same_under_node(
+        "basics",
+        "let x = 7; puts(x * 6, \" \", -x / 2, !x, x > 3 && x < 5 || x == 7);
+         puts(\"mon\" + \"key\" == \"monkey\", \"a\" != \"a\", len(\"héllo\"));
+         if (false) { 1 } else { 2 };",
+    );
+}
+
+#[test]
+fn runs_loops_and_recursive_functions() {
+    assert_same_under_node(
+        "functions",
+        "let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) };
+         let rep = fn(s, n) { if (n == 0) { \"\" } else { s + rep(s, n - 1) } };
+         let sum = fn(n) {
+           let i = 0; let acc = 0;
+           while (true) {
+             let i = i + 1;
+             if (i > n) { break; }
+             if (i == 2) { continue; }
+             let acc = acc + i;
+           }
+           acc
+         };
+         let say = fn(x) { puts(\"say \", x); };
+         say(1); say(\"two\");
+         puts(fib(15), len(rep(\"abcdefghij\", 2000)), sum(10));
+         puts(puts(1, puts(2)));
+         fib(20);",
+    );
+}
+
+#[test]
+fn top_level_return_ends_the_program() {
+    assert_same_under_node("return", "puts(1); if (true) { return 42; } puts(2);");
+}
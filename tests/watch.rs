@@ -0,0 +1,31 @@
+use std::fs::{self, File};
+use std::time::{Duration, SystemTime};
+
+use monkey_rust_compiler::watch::FileWatch;
+
+#[test]
+fn changes_to_the_file_are_seen_once() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("monkey_watch_{}.monkey", std::process::id()));
+    fs::write(&path, "1").expect("write temp script");
+    let mut watch = FileWatch::new(&path);
+    assert_eq!(watch.path(), path.as_path());
+    assert!(!watch.changed());
+
+    // Same length, so only the modification time tells them apart.
+    fs::write(&path, "2").expect("rewrite temp script");
+    File::options()
+        .write(true)
+        .open(&path)
+        .and_then(|file| file.set_modified(SystemTime::now() + Duration::from_secs(5)))
+        .expect("set modification time");
+    assert!(watch.changed());
+    assert!(!watch.changed());
+
+    fs::write(&path, "1 + 2").expect("rewrite temp script");
+    assert!(watch.changed());
+
+    fs::remove_file(&path).expect("remove temp script");
+    assert!(watch.changed());
+    assert!(!watch.changed());
+}